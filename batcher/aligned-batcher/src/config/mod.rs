@@ -1,3 +1,4 @@
+use aligned_sdk::encryption::EncryptionKey;
 use ethers::types::Address;
 use serde::Deserialize;
 
@@ -18,10 +19,38 @@ pub struct BatcherConfigFromYaml {
     pub block_interval: u64,
     pub batch_size_interval: usize,
     pub max_proof_size: usize,
+    /// Largest client message reassembled from chunks, in bytes. Defaults to 5 times
+    /// `max_proof_size`.
+    #[serde(default)]
+    pub max_message_size: Option<usize>,
     pub max_batch_size: usize,
     pub eth_ws_reconnects: usize,
     pub pre_verification_is_enabled: bool,
     pub non_paying: Option<NonPayingConfig>,
+    /// Whether to reject messages without a nonce and an expiry. Clients of the SDK sign every
+    /// message with them, see `aligned_sdk::sdk::REPLAY_PROTECTION_FEATURE`.
+    #[serde(default)]
+    pub replay_protection_is_required: bool,
+    /// File holding the X25519 private key of the batcher, as 32 bytes in hex. When set, the
+    /// batcher publishes its public key in the handshake and accepts messages encrypted to it.
+    #[serde(default)]
+    pub encryption_key_path: Option<String>,
+    /// File holding the access tokens allowed to connect, one per line. When set, the batcher
+    /// refuses the connections of clients that don't send one of them.
+    #[serde(default)]
+    pub auth_tokens_path: Option<String>,
+    /// Seconds the session of a lost connection is kept for the client to resume it. Defaults to
+    /// 300.
+    #[serde(default)]
+    pub session_ttl_secs: Option<u64>,
+    /// Number of proofs of the latest batches whose inclusion data is kept to answer the
+    /// inclusion queries of clients that lost their responses. Defaults to 100000.
+    #[serde(default)]
+    pub inclusions_kept: Option<usize>,
+    /// Directory where the verification data of the pending batches is written until they are
+    /// uploaded. Defaults to the temporary directory of the system.
+    #[serde(default)]
+    pub batch_data_dir: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,6 +69,33 @@ impl ConfigFromYaml {
     }
 }
 
+/// Reads the encryption key of the batcher from a file holding it as 32 bytes in hex.
+pub fn read_encryption_key(path: &str) -> EncryptionKey {
+    let key = std::fs::read_to_string(path).expect("Failed to read encryption key file");
+    let key: [u8; 32] = hex::decode(key.trim().trim_start_matches("0x"))
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .expect("Encryption key file must hold 32 bytes in hex");
+    EncryptionKey::from_bytes(key)
+}
+
+/// Reads the access tokens allowed to connect from a file holding one per line. Blank lines are
+/// skipped.
+pub fn read_auth_tokens(path: &str) -> Vec<String> {
+    let tokens = std::fs::read_to_string(path).expect("Failed to read access tokens file");
+    let tokens: Vec<String> = tokens
+        .lines()
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect();
+    assert!(
+        !tokens.is_empty(),
+        "Access tokens file must hold at least one token"
+    );
+    tokens
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Addresses {
     #[serde(rename = "alignedLayerServiceManager")]
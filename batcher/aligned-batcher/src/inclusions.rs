@@ -0,0 +1,140 @@
+//! Inclusion data of the proofs of the latest batches, kept to answer the inclusion queries of
+//! clients that didn't receive the responses to their proofs, as described in
+//! `aligned_sdk::pending`, and their submission status queries. It's kept in memory, so it's
+//! lost when the batcher restarts.
+
+use std::collections::{HashMap, VecDeque};
+
+use aligned_sdk::types::{BatchInclusionData, SubmissionStatus};
+use ethers::types::H256;
+
+struct Inclusion {
+    batch_inclusion_data: BatchInclusionData,
+    /// Transaction in which the verification task of the batch was created, once it was.
+    tx_hash: Option<H256>,
+}
+
+/// Inclusion data of the latest `capacity` proofs, by the leaf of their commitment.
+pub(crate) struct InclusionStore {
+    capacity: usize,
+    /// Leaves of the kept proofs, from the oldest to the newest.
+    leaves: VecDeque<[u8; 32]>,
+    inclusions: HashMap<[u8; 32], Inclusion>,
+}
+
+impl InclusionStore {
+    pub(crate) fn new(capacity: usize) -> Self {
+        InclusionStore {
+            capacity,
+            leaves: VecDeque::new(),
+            inclusions: HashMap::new(),
+        }
+    }
+
+    /// Records the inclusion data of a proof, forgetting the oldest one if the store is full. A
+    /// proof included again keeps its latest inclusion data, and is forgotten as the oldest one.
+    pub(crate) fn record(&mut self, leaf: [u8; 32], batch_inclusion_data: BatchInclusionData) {
+        if self.capacity == 0 {
+            return;
+        }
+        let inclusion = Inclusion {
+            batch_inclusion_data,
+            tx_hash: None,
+        };
+        if self.inclusions.insert(leaf, inclusion).is_some() {
+            return;
+        }
+        self.leaves.push_back(leaf);
+        if self.leaves.len() > self.capacity {
+            if let Some(oldest) = self.leaves.pop_front() {
+                self.inclusions.remove(&oldest);
+            }
+        }
+    }
+
+    /// Records the transaction in which the verification task of the batch of a proof was
+    /// created. Proofs that are not kept are ignored.
+    pub(crate) fn record_submission(&mut self, leaf: &[u8; 32], tx_hash: H256) {
+        if let Some(inclusion) = self.inclusions.get_mut(leaf) {
+            inclusion.tx_hash = Some(tx_hash);
+        }
+    }
+
+    pub(crate) fn get(&self, leaf: &[u8; 32]) -> Option<&BatchInclusionData> {
+        self.inclusions
+            .get(leaf)
+            .map(|inclusion| &inclusion.batch_inclusion_data)
+    }
+
+    /// Returns how far a kept proof has gone: batched until the verification task of its batch
+    /// is created, and submitted after. Proofs that are not kept are unknown.
+    pub(crate) fn submission_status(&self, leaf: &[u8; 32]) -> SubmissionStatus {
+        let Some(inclusion) = self.inclusions.get(leaf) else {
+            return SubmissionStatus::Unknown;
+        };
+        let batch_merkle_root = inclusion.batch_inclusion_data.batch_merkle_root;
+        let index_in_batch = inclusion.batch_inclusion_data.index_in_batch;
+        match inclusion.tx_hash {
+            None => SubmissionStatus::Batched {
+                batch_merkle_root,
+                index_in_batch,
+            },
+            Some(tx_hash) => SubmissionStatus::Submitted {
+                batch_merkle_root,
+                index_in_batch,
+                tx_hash,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::merkle::BatchMerkleTree;
+    use aligned_sdk::test_utils::{random_verification_data, seeded_rng};
+    use aligned_sdk::types::{BatchVersion, VerificationDataCommitment};
+
+    #[test]
+    fn test_only_the_latest_proofs_are_kept() {
+        let mut rng = seeded_rng(0);
+        let commitments: Vec<VerificationDataCommitment> = (0..3)
+            .map(|_| random_verification_data(&mut rng).into())
+            .collect();
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+        let inclusion = |index| tree.batch_inclusion_data(index).unwrap();
+
+        let mut store = InclusionStore::new(2);
+        store.record([0; 32], inclusion(0));
+        store.record([1; 32], inclusion(1));
+        store.record([0; 32], inclusion(2));
+        assert_eq!(store.get(&[0; 32]).unwrap().index_in_batch, 2);
+
+        store.record([2; 32], inclusion(2));
+        assert!(store.get(&[0; 32]).is_none());
+        assert_eq!(store.get(&[1; 32]).unwrap().index_in_batch, 1);
+        assert!(store.get(&[2; 32]).is_some());
+
+        assert!(matches!(
+            store.submission_status(&[1; 32]),
+            SubmissionStatus::Batched {
+                index_in_batch: 1,
+                ..
+            }
+        ));
+        store.record_submission(&[1; 32], H256::repeat_byte(7));
+        assert_eq!(
+            store.submission_status(&[1; 32]),
+            SubmissionStatus::Submitted {
+                batch_merkle_root: tree.root,
+                index_in_batch: 1,
+                tx_hash: H256::repeat_byte(7),
+            }
+        );
+        assert_eq!(store.submission_status(&[0; 32]), SubmissionStatus::Unknown);
+
+        let mut disabled = InclusionStore::new(0);
+        disabled.record([0; 32], inclusion(0));
+        assert!(disabled.get(&[0; 32]).is_none());
+    }
+}
@@ -1,49 +1,91 @@
 extern crate core;
 
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::eth::BatchVerifiedEventStream;
+use aligned_sdk::auth;
+use aligned_sdk::chunks::{ChunkAssembler, ChunkError, MessageChunk};
+use aligned_sdk::encryption::{self, EncryptionKey};
+use aligned_sdk::fees::{self, AGGREGATOR_COST};
+use aligned_sdk::sdk::{
+    CLIENT_VERSION_HEADER, ERROR_RESPONSES_FEATURE, PROTOCOL_FEATURES_HEADER,
+    PROTOCOL_VERSION_HEADER, SESSION_RESUMPTION_FEATURE,
+};
+use aligned_sdk::session;
 use aligned_sdk::types::{
-    BatchInclusionData, ClientMessage, VerificationCommitmentBatch, VerificationData,
-    VerificationDataCommitment,
+    BatchInclusionData, BatchVersion, ErrorResponse, GetSubmissionStatus, InclusionQuery,
+    InclusionQueryResponse, InclusionStatus, Rejection, RejectionCode, ReplayProtection,
+    SessionHandshake, SubmissionStatus, SubmissionStatusResponse, VerificationCommitmentBatch,
+    VerificationData,
 };
+use aligned_sdk::wire::{self, parse_features, WireFormat, WireMessage};
 use aws_sdk_s3::client::Client as S3Client;
 use eth::{BatchVerifiedFilter, BatcherPaymentService};
 use ethers::prelude::{Middleware, Provider};
 use ethers::providers::Ws;
-use ethers::types::{Address, U256};
+use ethers::types::{Address, H256, U256};
 use futures_util::stream::{self, SplitSink};
 use futures_util::{future, SinkExt, StreamExt, TryStreamExt};
-use lambdaworks_crypto::merkle_tree::merkle::MerkleTree;
-use log::{debug, error, info, warn};
+use inclusions::InclusionStore;
+use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+use log::{error, info, warn};
+use merkle::BatchMerkleTree;
+use replay::{NonceTracker, ReplayError};
+use sessions::{Responder, Session, SessionStore};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::timeout;
 use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::http::StatusCode;
 use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
 use tokio_tungstenite::tungstenite::{Error, Message};
 use tokio_tungstenite::WebSocketStream;
-use types::batch_queue::BatchQueue;
+use types::batch_queue::{BatchQueue, BatchQueueEntry, PendingBatch};
 use types::errors::BatcherError;
 
-use crate::config::{ConfigFromYaml, ContractDeploymentOutput, NonPayingConfig};
+use crate::config::{
+    read_auth_tokens, read_encryption_key, ConfigFromYaml, ContractDeploymentOutput,
+    NonPayingConfig,
+};
 use crate::eth::AlignedLayerServiceManager;
 
 mod config;
 mod eth;
 pub mod gnark;
 pub mod halo2;
+mod inclusions;
+pub mod merkle;
+pub mod replay;
 pub mod risc_zero;
 pub mod s3;
+mod sessions;
 pub mod sp1;
 pub mod types;
 mod zk_utils;
 
+#[cfg(test)]
+mod wire_protocol_tests;
+
 const S3_BUCKET_NAME: &str = "storage.alignedlayer.com";
 
+/// Seconds the session of a lost connection is kept, unless configured otherwise.
+const DEFAULT_SESSION_TTL_SECS: u64 = 300;
+
+/// Number of proofs of the latest batches whose inclusion data is kept to answer inclusion
+/// queries, unless configured otherwise.
+const DEFAULT_INCLUSIONS_KEPT: usize = 100_000;
+
+/// Version of the batches built by the batcher. The service manager contract only verifies the
+/// inclusion proofs of V1 batches, the binary merkle trees.
+const BATCH_VERSION: BatchVersion = BatchVersion::V1;
+
 pub struct Batcher {
     s3_client: S3Client,
     eth_ws_provider: Provider<Ws>,
@@ -53,10 +95,17 @@ pub struct Batcher {
     max_block_interval: u64,
     min_batch_len: usize,
     max_proof_size: usize,
-    max_batch_size: usize,
+    max_message_size: usize,
     last_uploaded_batch_block: Mutex<u64>,
     pre_verification_is_enabled: bool,
     non_paying_config: Option<NonPayingConfig>,
+    nonce_tracker: Mutex<NonceTracker>,
+    replay_protection_is_required: bool,
+    encryption_key: Option<EncryptionKey>,
+    /// Access tokens allowed to connect, or `None` if anyone can connect.
+    auth_tokens: Option<Vec<String>>,
+    sessions: Mutex<SessionStore>,
+    inclusions: Mutex<InclusionStore>,
 }
 
 impl Batcher {
@@ -105,19 +154,76 @@ impl Batcher {
                 non_paying_config.address, non_paying_config.replacement);
         }
 
+        let encryption_key = config
+            .batcher
+            .encryption_key_path
+            .as_deref()
+            .map(read_encryption_key);
+        if let Some(encryption_key) = &encryption_key {
+            info!(
+                "Accepting messages encrypted to the public key {}",
+                hex::encode(encryption_key.public_key())
+            );
+        }
+
+        let auth_tokens = config
+            .batcher
+            .auth_tokens_path
+            .as_deref()
+            .map(read_auth_tokens);
+        if let Some(auth_tokens) = &auth_tokens {
+            info!(
+                "Only accepting connections with one of {} access tokens",
+                auth_tokens.len()
+            );
+        }
+
+        // The verification data of the pending batches is kept in this directory until they are
+        // uploaded, see `BatchQueue`
+        let batch_data_dir = config
+            .batcher
+            .batch_data_dir
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        std::fs::create_dir_all(&batch_data_dir).expect("Failed to create batch data directory");
+
         Self {
             s3_client,
             eth_ws_provider,
             service_manager,
             payment_service,
-            batch_queue: Mutex::new(BatchQueue::new()),
+            batch_queue: Mutex::new(BatchQueue::new(
+                BATCH_VERSION,
+                config.batcher.max_batch_size,
+                batch_data_dir,
+            )),
             max_block_interval: config.batcher.block_interval,
             min_batch_len: config.batcher.batch_size_interval,
             max_proof_size: config.batcher.max_proof_size,
-            max_batch_size: config.batcher.max_batch_size,
+            // Each byte of a proof takes up to 4 characters of the JSON message
+            max_message_size: config
+                .batcher
+                .max_message_size
+                .unwrap_or(5 * config.batcher.max_proof_size),
             last_uploaded_batch_block: Mutex::new(last_uploaded_batch_block),
             pre_verification_is_enabled: config.batcher.pre_verification_is_enabled,
             non_paying_config: config.batcher.non_paying,
+            nonce_tracker: Mutex::new(NonceTracker::new()),
+            replay_protection_is_required: config.batcher.replay_protection_is_required,
+            encryption_key,
+            auth_tokens,
+            sessions: Mutex::new(SessionStore::new(Duration::from_secs(
+                config
+                    .batcher
+                    .session_ttl_secs
+                    .unwrap_or(DEFAULT_SESSION_TTL_SECS),
+            ))),
+            inclusions: Mutex::new(InclusionStore::new(
+                config
+                    .batcher
+                    .inclusions_kept
+                    .unwrap_or(DEFAULT_INCLUSIONS_KEPT),
+            )),
         }
     }
 
@@ -158,54 +264,352 @@ impl Batcher {
 
     async fn handle_connection(self: Arc<Self>, raw_stream: TcpStream, addr: SocketAddr) {
         info!("Incoming TCP connection from: {}", addr);
-        let ws_stream = tokio_tungstenite::accept_async(raw_stream)
-            .await
-            .expect("Error during the websocket handshake occurred");
+        let mut client_version = None;
+        let mut client_protocol_version = None;
+        let mut client_features = Vec::new();
+        let mut resumption = None;
+        let ws_stream =
+            tokio_tungstenite::accept_hdr_async(raw_stream, |request: &Request, response| {
+                resumption = session::resumption_request(request.headers());
+                client_version = request
+                    .headers()
+                    .get(CLIENT_VERSION_HEADER)
+                    .and_then(|version| version.to_str().ok())
+                    .map(str::to_string);
+                client_protocol_version = request
+                    .headers()
+                    .get(PROTOCOL_VERSION_HEADER)
+                    .and_then(|version| version.to_str().ok())
+                    .map(str::to_string);
+                client_features = request
+                    .headers()
+                    .get(PROTOCOL_FEATURES_HEADER)
+                    .and_then(|features| features.to_str().ok())
+                    .map(parse_features)
+                    .unwrap_or_default();
+                if let Some(auth_tokens) = &self.auth_tokens {
+                    if !auth::is_authorized(request.headers().get(AUTHORIZATION), auth_tokens) {
+                        return Err(auth::unauthorized_response());
+                    }
+                }
+                Ok(response)
+            })
+            .await;
+        let ws_stream = match ws_stream {
+            Ok(ws_stream) => ws_stream,
+            Err(Error::Http(response)) if response.status() == StatusCode::UNAUTHORIZED => {
+                warn!(
+                    "Refused connection from {}: missing or invalid access token",
+                    addr
+                );
+                return;
+            }
+            Err(e) => {
+                error!("Error during the websocket handshake with {}: {}", addr, e);
+                return;
+            }
+        };
 
-        debug!("WebSocket connection established: {}", addr);
+        // Clients older than the version header are reported as unknown
+        let client_version = client_version.unwrap_or_else(|| "unknown".to_string());
+        info!(
+            "WebSocket connection established: {}, client version: {}",
+            addr, client_version
+        );
         let (outgoing, incoming) = ws_stream.split();
         let outgoing = Arc::new(RwLock::new(outgoing));
 
-        // Send the protocol version to the client
-        let protocol_version_msg = Message::binary(
-            aligned_sdk::sdk::CURRENT_PROTOCOL_VERSION
-                .to_be_bytes()
-                .to_vec(),
-        );
-
-        outgoing
-            .write()
-            .await
-            .send(protocol_version_msg)
-            .await
-            .expect("Failed to send protocol version");
+        let wire_format = match &client_protocol_version {
+            Some(client_protocol_version) => {
+                let wire_format = WireFormat::negotiate(&client_features);
+                info!(
+                    "Client {} speaks protocol version {}, sending {:?} messages",
+                    addr, client_protocol_version, wire_format
+                );
+                wire_format
+            }
+            None => WireFormat::Json,
+        };
+        // Clients that support error responses are told the details of their rejections before
+        // the connection is closed
+        let error_responses = client_features
+            .iter()
+            .any(|feature| feature == ERROR_RESPONSES_FEATURE);
+        let supports_sessions = client_protocol_version.is_some()
+            && client_features
+                .iter()
+                .any(|feature| feature == SESSION_RESUMPTION_FEATURE);
+
+        // Clients that announce their protocol version receive the handshake with the features
+        // of the batcher, while older clients only understand the protocol version. Clients
+        // supporting sessions are also sent the token of their session, which is attached to
+        // the connection
+        let (responder, session_token) = if supports_sessions {
+            let (session_token, session, received) = self.open_session(resumption, &addr).await;
+            let mut session_lock = session.lock().await;
+            let session_handshake = SessionHandshake {
+                token: session_token.clone(),
+                accepted_proofs: session_lock.accepted_proofs(),
+            };
+            let handshake =
+                protocol_handshake_message(self.encryption_key.as_ref(), Some(&session_handshake));
+            if let Err(e) = session_lock
+                .attach(outgoing.clone(), handshake, received)
+                .await
+            {
+                error!("Failed to send the handshake to {}: {}", addr, e);
+                return;
+            }
+            drop(session_lock);
+            (Responder::Session(session), Some(session_token))
+        } else {
+            let handshake = match client_protocol_version {
+                Some(_) => protocol_handshake_message(self.encryption_key.as_ref(), None),
+                None => protocol_version_message(),
+            };
+            outgoing
+                .write()
+                .await
+                .send(handshake)
+                .await
+                .expect("Failed to send protocol version");
+            (Responder::Connection(outgoing.clone()), None)
+        };
 
-        match incoming
-            .try_filter(|msg| future::ready(msg.is_text()))
-            .try_for_each(|msg| self.clone().handle_message(msg, outgoing.clone()))
-            .await
-        {
+        // Chunks of messages too large for a frame are reassembled per connection
+        let chunk_assembler = Arc::new(Mutex::new(ChunkAssembler::new(self.max_message_size)));
+
+        let result = incoming
+            .try_filter(|msg| future::ready(msg.is_text() || msg.is_binary()))
+            .try_for_each(|msg| {
+                self.clone().handle_frame(
+                    msg,
+                    wire_format,
+                    error_responses,
+                    chunk_assembler.clone(),
+                    outgoing.clone(),
+                    responder.clone(),
+                )
+            })
+            .await;
+        let closed_by_client = result.is_ok();
+        match result {
             Err(Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => {
                 info!("Client {} reset connection", &addr)
             }
-            Err(e) => error!("Unexpected error: {}", e),
+            Err(e) => error!(
+                "Unexpected error with client {} ({}): {}",
+                &addr, client_version, e
+            ),
             Ok(_) => info!("{} disconnected", &addr),
         }
+
+        if let (Responder::Session(session), Some(session_token)) = (&responder, &session_token) {
+            self.detach_session(session_token, session, &outgoing, closed_by_client)
+                .await;
+        }
     }
 
-    /// Handle an individual message from the client.
+    /// Resumes the session a client presents in its handshake, or opens a new one if it doesn't
+    /// present one or the session is unknown. Returns the token of the session and the number of
+    /// its responses the client received.
+    async fn open_session(
+        &self,
+        resumption: Option<(String, usize)>,
+        addr: &SocketAddr,
+    ) -> (String, Arc<Mutex<Session>>, usize) {
+        let mut sessions = self.sessions.lock().await;
+        let now = Instant::now();
+        if let Some((session_token, received)) = resumption {
+            if let Some(session) = sessions.resume(&session_token, now) {
+                info!(
+                    "Client {} resumed its session, having received {} responses",
+                    addr, received
+                );
+                return (session_token, session, received);
+            }
+            info!(
+                "Session of client {} is unknown or expired, opening a new one",
+                addr
+            );
+        }
+        let (session_token, session) = sessions.open(now);
+        (session_token, session, 0)
+    }
+
+    /// Detaches the session from a connection once it ends. The session is kept to be resumed,
+    /// unless the client closed the connection once every proof of the session was responded.
+    async fn detach_session(
+        &self,
+        session_token: &str,
+        session: &Mutex<Session>,
+        ws_conn_sink: &Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+        closed_by_client: bool,
+    ) {
+        let mut sessions = self.sessions.lock().await;
+        let mut session = session.lock().await;
+        // The session may have been resumed by a new connection before this one ended
+        if session.detach(ws_conn_sink) {
+            let kept = !(closed_by_client && session.is_responded());
+            sessions.disconnect(session_token, Instant::now(), kept);
+        }
+    }
+
+    /// Handle a frame from the client. Text frames hold a whole JSON client message, while
+    /// binary frames hold the chunks of a message, which is handled once all of them are
+    /// received. Chunked messages are encoded in the format negotiated with the client, as
+    /// described in `aligned_sdk::wire`, and may be encrypted to the key of the batcher, as
+    /// described in `aligned_sdk::encryption`.
+    async fn handle_frame(
+        self: Arc<Self>,
+        frame: Message,
+        wire_format: WireFormat,
+        error_responses: bool,
+        chunk_assembler: Arc<Mutex<ChunkAssembler>>,
+        ws_conn_sink: Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+        responder: Responder,
+    ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+        let message = match frame {
+            Message::Binary(data) => {
+                let assembled = match MessageChunk::from_bytes(&data) {
+                    Some(chunk) => chunk_assembler.lock().await.push(chunk),
+                    None => Err(ChunkError::Malformed),
+                };
+                match assembled {
+                    Ok(None) => return Ok(()),
+                    Ok(Some(message)) => encryption::decrypt(message, self.encryption_key.as_ref())
+                        .and_then(|message| wire::decompress(message, self.max_message_size))
+                        .and_then(|message| WireMessage::decode(wire_format, &message)),
+                    Err(e) => {
+                        error!("Invalid message chunk: {:?}", e);
+                        let reason = format!("{:?}", e);
+                        send_rejection(
+                            &ws_conn_sink,
+                            error_responses,
+                            Rejection::InvalidMessage {
+                                reason: reason.clone(),
+                            },
+                            None,
+                            &reason,
+                        )
+                        .await;
+                        return Err(tokio_tungstenite::tungstenite::Error::Protocol(
+                            ProtocolError::HandshakeIncomplete,
+                        ));
+                    }
+                }
+            }
+            frame => WireMessage::decode(WireFormat::Json, &frame.into_data()),
+        };
+
+        match message {
+            Ok(message) => {
+                self.handle_message(message, error_responses, ws_conn_sink, responder)
+                    .await
+            }
+            Err(e) => {
+                error!("Failed to deserialize client message: {:?}", e);
+                send_rejection(
+                    &ws_conn_sink,
+                    error_responses,
+                    Rejection::InvalidMessage {
+                        reason: format!("{:?}", e),
+                    },
+                    None,
+                    "Failed to deserialize client message",
+                )
+                .await;
+                Err(tokio_tungstenite::tungstenite::Error::Protocol(
+                    ProtocolError::HandshakeIncomplete,
+                ))
+            }
+        }
+    }
+
+    /// Handle an individual message from the client. Rejections are sent to the connection of
+    /// the client, while the responses to its proofs are sent through `responder`.
     async fn handle_message(
         self: Arc<Self>,
-        message: Message,
+        message: WireMessage,
+        error_responses: bool,
         ws_conn_sink: Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+        responder: Responder,
     ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
-        // Deserialize verification data from message
-        let client_msg: ClientMessage =
-            serde_json::from_str(message.to_text().expect("Message is not text"))
-                .expect("Failed to deserialize task");
+        // The message can either be a single verification data or several verification data
+        // entries under a single signature, possibly sponsored.
+        // The replay protection of a message is checked against the nonces of its signer
+        let submission_id = message.submission_id();
+        let (verification_data, recovered_addr, replay_protection) = match message {
+            WireMessage::Client(client_msg) => {
+                let recovered_addr = client_msg.verify_signature();
+                let replay_protection = client_msg
+                    .replay_protection
+                    .zip(recovered_addr.as_ref().ok().copied());
+                (
+                    vec![client_msg.verification_data],
+                    recovered_addr,
+                    replay_protection,
+                )
+            }
+            WireMessage::Batched(batched_client_msg) => {
+                if !batched_client_msg.verify_entries() {
+                    error!("Batched message entries don't match the signed merkle root and entry count");
+                    let reason = "Entries don't match the signed merkle root and entry count";
+                    send_rejection(
+                        &ws_conn_sink,
+                        error_responses,
+                        Rejection::InvalidMessage {
+                            reason: reason.to_string(),
+                        },
+                        submission_id,
+                        reason,
+                    )
+                    .await;
+                    return Err(tokio_tungstenite::tungstenite::Error::Protocol(
+                        ProtocolError::HandshakeIncomplete,
+                    ));
+                }
+                let recovered_addr = batched_client_msg.verify_signature();
+                let replay_protection = batched_client_msg
+                    .replay_protection
+                    .zip(recovered_addr.as_ref().ok().copied());
+                let verification_data = batched_client_msg
+                    .entries
+                    .into_iter()
+                    .map(|entry| entry.verification_data)
+                    .collect();
+                (verification_data, recovered_addr, replay_protection)
+            }
+            WireMessage::Sponsored(sponsored_client_msg) => {
+                // The sponsor pays for the proof, so its balance is the one checked and charged,
+                // while the nonce is the one of the prover, who signed it
+                let signatures = sponsored_client_msg.verify_signatures();
+                let replay_protection = sponsored_client_msg.client_message.replay_protection.zip(
+                    signatures
+                        .as_ref()
+                        .ok()
+                        .map(|(prover_addr, _)| *prover_addr),
+                );
+                let recovered_addr = signatures.map(|(prover_addr, sponsor_addr)| {
+                    info!("Proof of {:?} sponsored by {:?}", prover_addr, sponsor_addr);
+                    sponsor_addr
+                });
+                (
+                    vec![sponsored_client_msg.client_message.verification_data],
+                    recovered_addr,
+                    replay_protection,
+                )
+            }
+            WireMessage::InclusionQuery(query) => {
+                return self.answer_inclusion_query(query, &ws_conn_sink).await;
+            }
+            WireMessage::GetSubmissionStatus(query) => {
+                return self.answer_submission_status(query, &ws_conn_sink).await;
+            }
+        };
 
         info!("Verifying message signature...");
-        let submitter_addr = if let Ok(addr) = client_msg.verify_signature() {
+        let submitter_addr = if let Ok(addr) = recovered_addr {
             info!("Message signature verified");
 
             let mut addr = addr;
@@ -225,6 +629,14 @@ impl Batcher {
 
             if user_balance == U256::from(0) {
                 error!("Insufficient funds for address {:?}", addr);
+                send_rejection(
+                    &ws_conn_sink,
+                    error_responses,
+                    Rejection::InsufficientBalance { address: addr },
+                    submission_id,
+                    "Insufficient funds in the batcher payment service",
+                )
+                .await;
                 return Err(tokio_tungstenite::tungstenite::Error::Protocol(
                     ProtocolError::HandshakeIncomplete,
                 ));
@@ -233,50 +645,214 @@ impl Batcher {
             addr
         } else {
             error!("Signature verification error");
+            send_rejection(
+                &ws_conn_sink,
+                error_responses,
+                Rejection::InvalidSignature,
+                submission_id,
+                "Signature verification error",
+            )
+            .await;
             return Err(tokio_tungstenite::tungstenite::Error::Protocol(
                 ProtocolError::HandshakeIncomplete,
             ));
         };
 
-        let verification_data = client_msg.verification_data;
-        if verification_data.proof.len() <= self.max_proof_size {
+        // Replayed messages are rejected before verifying their proofs, but their nonce is only
+        // recorded once they are accepted, below
+        if let Err(e) = self.check_replay_protection(&replay_protection).await {
+            return reject_replayed_message(&ws_conn_sink, error_responses, submission_id, e).await;
+        }
+
+        // All entries are checked before adding any of them, so that a message is either
+        // entirely added to the batch or rejected.
+        for verification_data in verification_data.iter() {
+            if verification_data.proof.len() > self.max_proof_size {
+                // FIXME(marian): Handle this error correctly
+                send_rejection(
+                    &ws_conn_sink,
+                    error_responses,
+                    Rejection::ProofTooLarge {
+                        proof_size: verification_data.proof.len(),
+                        max_proof_size: self.max_proof_size,
+                    },
+                    submission_id,
+                    &format!(
+                        "Proof size exceeds the maximum of {} bytes",
+                        self.max_proof_size
+                    ),
+                )
+                .await;
+                return Err(tokio_tungstenite::tungstenite::Error::Protocol(
+                    ProtocolError::HandshakeIncomplete,
+                ));
+            }
+
             // When pre-verification is enabled, batcher will verify proofs for faster feedback with clients
-            if self.pre_verification_is_enabled && !zk_utils::verify(&verification_data) {
+            if self.pre_verification_is_enabled && !zk_utils::verify(verification_data) {
+                send_rejection(
+                    &ws_conn_sink,
+                    error_responses,
+                    Rejection::InvalidProof {
+                        proving_system: verification_data.proving_system.clone(),
+                    },
+                    submission_id,
+                    "Invalid proof",
+                )
+                .await;
                 return Err(tokio_tungstenite::tungstenite::Error::Protocol(
                     ProtocolError::HandshakeIncomplete,
                 ));
             }
-            self.add_to_batch(verification_data, ws_conn_sink.clone(), submitter_addr)
+        }
+
+        // Checked again while recording it, in case the same message was accepted meanwhile
+        if let Err(e) = self.record_replay_protection(&replay_protection).await {
+            return reject_replayed_message(&ws_conn_sink, error_responses, submission_id, e).await;
+        }
+
+        for verification_data in verification_data {
+            self.clone()
+                .add_to_batch(
+                    verification_data,
+                    responder.clone(),
+                    submitter_addr,
+                    submission_id,
+                )
                 .await;
-        } else {
-            // FIXME(marian): Handle this error correctly
-            return Err(tokio_tungstenite::tungstenite::Error::Protocol(
-                ProtocolError::HandshakeIncomplete,
-            ));
-        };
+        }
 
         info!("Verification data message handled");
 
         Ok(())
     }
 
-    /// Adds verification data to the current batch queue.
+    /// Answers an inclusion query with the status of each proof: pending while it's in the batch
+    /// queue, and included once it's in one of the latest batches, as kept in `inclusions`.
+    async fn answer_inclusion_query(
+        &self,
+        query: InclusionQuery,
+        ws_conn_sink: &Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+    ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+        let pending_leaves: HashSet<[u8; 32]> =
+            self.batch_queue.lock().await.leaves().copied().collect();
+        let inclusions = self.inclusions.lock().await;
+        let statuses = query
+            .commitments
+            .iter()
+            .map(|commitment| {
+                let leaf = VerificationCommitmentBatch::hash_data(commitment);
+                if pending_leaves.contains(&leaf) {
+                    return InclusionStatus::Pending;
+                }
+                match inclusions.get(&leaf) {
+                    Some(batch_inclusion_data) => {
+                        InclusionStatus::Included(batch_inclusion_data.clone())
+                    }
+                    None => InclusionStatus::Unknown,
+                }
+            })
+            .collect();
+        drop(inclusions);
+        info!(
+            "Answering inclusion query of {} proofs",
+            query.commitments.len()
+        );
+
+        ws_conn_sink
+            .write()
+            .await
+            .send(inclusion_query_response_message(&InclusionQueryResponse {
+                statuses,
+            }))
+            .await
+    }
+
+    /// Answers a submission status query with how far the proof has gone: pending while it's in
+    /// the batch queue, and then as kept in `inclusions`.
+    async fn answer_submission_status(
+        &self,
+        query: GetSubmissionStatus,
+        ws_conn_sink: &Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+    ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+        let leaf = VerificationCommitmentBatch::hash_data(&query.verification_data_commitment);
+        let pending = self
+            .batch_queue
+            .lock()
+            .await
+            .leaves()
+            .any(|pending_leaf| *pending_leaf == leaf);
+        let status = if pending {
+            SubmissionStatus::Pending
+        } else {
+            self.inclusions.lock().await.submission_status(&leaf)
+        };
+        info!("Answering submission status query: {:?}", status);
+
+        ws_conn_sink
+            .write()
+            .await
+            .send(submission_status_response_message(
+                &SubmissionStatusResponse { status },
+            ))
+            .await
+    }
+
+    /// Checks the nonce and expiry of a message, along with the address that signed them, without
+    /// recording the nonce. Messages without them are only accepted if the batcher doesn't require
+    /// them.
+    async fn check_replay_protection(
+        &self,
+        replay_protection: &Option<(ReplayProtection, Address)>,
+    ) -> Result<(), ReplayError> {
+        match replay_protection {
+            Some((replay_protection, signer_addr)) => self.nonce_tracker.lock().await.check(
+                signer_addr,
+                replay_protection,
+                SystemTime::now(),
+            ),
+            None if self.replay_protection_is_required => Err(ReplayError::Missing),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the replay protection of a message as `check_replay_protection` does and records its
+    /// nonce, once the message is accepted into the batch.
+    async fn record_replay_protection(
+        &self,
+        replay_protection: &Option<(ReplayProtection, Address)>,
+    ) -> Result<(), ReplayError> {
+        match replay_protection {
+            Some((replay_protection, signer_addr)) => self
+                .nonce_tracker
+                .lock()
+                .await
+                .check_and_record(*signer_addr, replay_protection, SystemTime::now()),
+            None if self.replay_protection_is_required => Err(ReplayError::Missing),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds verification data to the current batch queue, with the submission id of its message
+    /// to be echoed in its response.
     async fn add_to_batch(
         self: Arc<Self>,
         verification_data: VerificationData,
-        ws_conn_sink: Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+        responder: Responder,
         proof_submitter_addr: Address,
+        submission_id: Option<u64>,
     ) {
         let mut batch_queue_lock = self.batch_queue.lock().await;
-        info!("Calculating verification data commitments...");
-        let verification_data_comm = verification_data.clone().into();
         info!("Adding verification data to batch...");
-        batch_queue_lock.push((
-            verification_data,
-            verification_data_comm,
-            ws_conn_sink,
-            proof_submitter_addr,
-        ));
+        responder.accept_proof().await;
+        batch_queue_lock
+            .push(
+                verification_data,
+                responder,
+                proof_submitter_addr,
+                submission_id,
+            )
+            .expect("Failed to write batch data");
         info!("Current batch queue length: {}", batch_queue_lock.len());
     }
 
@@ -286,20 +862,17 @@ impl Batcher {
     ///     * Has the received block number surpassed the maximum interval with respect to the last posted batch block?
     /// An extra sanity check is made to check if the batch size is 0, since it does not make sense to post
     /// an empty batch, even if the block interval has been reached.
-    /// Once the batch meets the conditions for submission, it check if it needs to be splitted into smaller batches,
-    /// depending on the configured maximum batch size. The batch is splitted at the index where the max size is surpassed,
-    /// and all the elements up to that index are copied and cleared from the batch queue. The copy is then passed to the
-    /// `finalize_batch` function.
-    async fn is_batch_ready(&self, block_number: u64) -> Option<BatchQueue> {
+    /// Once the batch meets the conditions for submission, its oldest batch is removed from the queue and passed to the
+    /// `finalize_batch` function. The queue is splitted into batches of at most the configured maximum batch size as
+    /// proofs are added to it, so this is the whole queue unless it exceeds that size.
+    async fn is_batch_ready(&self, block_number: u64) -> Option<PendingBatch> {
         let mut batch_queue_lock = self.batch_queue.lock().await;
         let current_batch_len = batch_queue_lock.len();
 
         let last_uploaded_batch_block_lock = self.last_uploaded_batch_block.lock().await;
 
-        // FIXME(marian): This condition should be changed to current_batch_size == 0
-        // once the bug in Lambdaworks merkle tree is fixed.
-        if current_batch_len < 2 {
-            info!("Current batch is empty or length 1. Waiting for more proofs...");
+        if current_batch_len == 0 {
+            info!("Current batch is empty. Waiting for more proofs...");
             return None;
         }
 
@@ -313,67 +886,35 @@ impl Batcher {
             return None;
         }
 
-        let batch_verification_data: Vec<VerificationData> = batch_queue_lock
-            .iter()
-            .map(|(vd, _, _, _)| vd.clone())
-            .collect();
-
-        let current_batch_size = serde_json::to_vec(&batch_verification_data).unwrap().len();
-
-        // check if the current batch needs to be splitted into smaller batches
-        if current_batch_size > self.max_batch_size {
-            info!("Batch max size exceded. Splitting current batch...");
-            let mut acc_batch_size = 0;
-            let mut finalized_batch_idx = 0;
-            for (idx, (verification_data, _, _, _)) in batch_queue_lock.iter().enumerate() {
-                acc_batch_size += serde_json::to_vec(verification_data).unwrap().len();
-                if acc_batch_size > self.max_batch_size {
-                    finalized_batch_idx = idx;
-                    break;
-                }
-            }
-            let finalized_batch = batch_queue_lock.drain(..finalized_batch_idx).collect();
-            return Some(finalized_batch);
-        }
-
-        // A copy of the batch is made to be returned and the current batch is cleared
-        let finalized_batch = batch_queue_lock.clone();
-        batch_queue_lock.clear();
-
-        Some(finalized_batch)
+        batch_queue_lock.pop_front()
     }
 
-    /// Takes the finalized batch as input and builds the merkle tree, posts verification data batch
+    /// Takes the finalized batch as input and finishes its merkle tree, posts its verification data file
     /// to s3, creates new task in Aligned contract and sends responses to all clients that added proofs
     /// to the batch. The last uploaded batch block is updated once the task is created in Aligned.
     async fn finalize_batch(
         &self,
         block_number: u64,
-        finalized_batch: BatchQueue,
+        finalized_batch: PendingBatch,
         wait_for_verification: bool,
     ) -> Result<(), BatcherError> {
-        let batch_verification_data: Vec<VerificationData> = finalized_batch
-            .clone()
-            .into_iter()
-            .map(|(data, _, _, _)| data)
-            .collect();
-
-        let batch_bytes = serde_json::to_vec(batch_verification_data.as_slice())
-            .expect("Failed to serialize batch");
+        let PendingBatch {
+            entries: finalized_batch,
+            merkle_tree: batch_merkle_tree_builder,
+            data: mut batch_data,
+        } = finalized_batch;
 
         info!("Finalizing batch. Length: {}", finalized_batch.len());
-        let batch_data_comm: Vec<VerificationDataCommitment> = finalized_batch
-            .clone()
-            .into_iter()
-            .map(|(_, data_comm, _, _)| data_comm)
-            .collect();
+        let leaves: Vec<[u8; 32]> = batch_merkle_tree_builder.leaves().to_vec();
+        let batch_merkle_tree = batch_merkle_tree_builder
+            .finalize()
+            .ok_or(BatcherError::EmptyBatch)?;
 
-        let batch_merkle_tree: MerkleTree<VerificationCommitmentBatch> =
-            MerkleTree::build(&batch_data_comm);
+        let batch_data_path = batch_data.finish().expect("Failed to write batch data");
 
         let submitter_addresses = finalized_batch
             .iter()
-            .map(|(_, _, _, addr)| *addr)
+            .map(|(_, addr, _, _)| *addr)
             .collect();
 
         let events = self.service_manager.event::<BatchVerifiedFilter>();
@@ -391,10 +932,32 @@ impl Batcher {
                 block_number
             );
         }
+        {
+            let mut inclusions = self.inclusions.lock().await;
+            for (index, leaf) in leaves.iter().enumerate() {
+                if let Some(batch_inclusion_data) = batch_merkle_tree.batch_inclusion_data(index) {
+                    inclusions.record(*leaf, batch_inclusion_data);
+                }
+            }
+        }
+
         // Moving this outside the previous scope is a hotfix until we merge https://github.com/yetanotherco/aligned_layer/pull/365
-        self.submit_batch(&batch_bytes, &batch_merkle_tree.root, submitter_addresses)
+        let tx_hash = self
+            .submit_batch(
+                batch_data_path,
+                &batch_merkle_tree.root,
+                batch_merkle_tree.batch_version,
+                submitter_addresses,
+            )
             .await;
 
+        if let Some(tx_hash) = tx_hash {
+            let mut inclusions = self.inclusions.lock().await;
+            for leaf in &leaves {
+                inclusions.record_submission(leaf, tx_hash);
+            }
+        }
+
         if !wait_for_verification {
             send_batch_inclusion_data_responses(finalized_batch, &batch_merkle_tree).await;
             return Ok(());
@@ -427,22 +990,32 @@ impl Batcher {
         Ok(())
     }
 
-    /// Post batch to s3 and submit new task to Ethereum
+    /// Post batch to s3 and submit new task to Ethereum. Returns the hash of the transaction
+    /// that created the task, if it was created.
     async fn submit_batch(
         &self,
-        batch_bytes: &[u8],
+        batch_data_path: &Path,
         batch_merkle_root: &[u8; 32],
+        batch_version: BatchVersion,
         submitter_addresses: Vec<Address>,
-    ) {
+    ) -> Option<H256> {
         let s3_client = self.s3_client.clone();
         let batch_merkle_root_hex = hex::encode(batch_merkle_root);
         info!("Batch merkle root: {}", batch_merkle_root_hex);
         let file_name = batch_merkle_root_hex.clone() + ".json";
 
         info!("Uploading batch to S3...");
-        s3::upload_object(&s3_client, S3_BUCKET_NAME, batch_bytes.to_vec(), &file_name)
-            .await
-            .expect("Failed to upload object to S3");
+        // The version is stored in the object metadata, so the contents are the same for all versions
+        let metadata = [("batch-version", format!("{:?}", batch_version))];
+        s3::upload_file(
+            &s3_client,
+            S3_BUCKET_NAME,
+            batch_data_path,
+            &file_name,
+            &metadata,
+        )
+        .await
+        .expect("Failed to upload object to S3");
 
         info!("Batch sent to S3 with name: {}", file_name);
 
@@ -452,15 +1025,7 @@ impl Batcher {
 
         let num_proofs_in_batch = submitter_addresses.len();
 
-        // FIXME: This constants should be aggregated into one constants file
-        const AGGREGATOR_COST: u128 = 400000;
-        const BATCHER_SUBMISSION_BASE_COST: u128 = 100000;
-        const ADDITIONAL_SUBMISSION_COST_PER_PROOF: u128 = 1325;
-        const CONSTANT_COST: u128 = AGGREGATOR_COST + BATCHER_SUBMISSION_BASE_COST;
-
-        let gas_per_proof = (CONSTANT_COST
-            + ADDITIONAL_SUBMISSION_COST_PER_PROOF * num_proofs_in_batch as u128)
-            / num_proofs_in_batch as u128;
+        let gas_per_proof = fees::gas_per_proof(num_proofs_in_batch);
 
         match eth::create_new_task(
             payment_service,
@@ -472,8 +1037,14 @@ impl Batcher {
         )
         .await
         {
-            Ok(_) => info!("Batch verification task created on Aligned contract"),
-            Err(e) => error!("Failed to create batch verification task: {}", e),
+            Ok(receipt) => {
+                info!("Batch verification task created on Aligned contract");
+                Some(receipt.transaction_hash)
+            }
+            Err(e) => {
+                error!("Failed to create batch verification task: {}", e);
+                None
+            }
         }
     }
 }
@@ -499,43 +1070,166 @@ async fn await_batch_verified_event<'s>(
 }
 
 async fn send_batch_inclusion_data_responses(
-    finalized_batch: BatchQueue,
-    batch_merkle_tree: &MerkleTree<VerificationCommitmentBatch>,
+    finalized_batch: Vec<BatchQueueEntry>,
+    batch_merkle_tree: &BatchMerkleTree,
 ) {
     stream::iter(finalized_batch.iter())
         .enumerate()
-        .for_each(|(vd_batch_idx, (_, _, ws_sink, _))| async move {
-            let response = BatchInclusionData::new(vd_batch_idx, batch_merkle_tree);
-            let serialized_response =
-                serde_json::to_vec(&response).expect("Could not serialize response");
+        .for_each(
+            |(vd_batch_idx, (responder, _, submission_id, metadata))| async move {
+                let response = BatchInclusionData {
+                    submission_id: *submission_id,
+                    metadata: metadata.clone(),
+                    ..batch_merkle_tree
+                        .batch_inclusion_data(vd_batch_idx)
+                        .expect("Every entry of the batch is in its merkle tree")
+                };
 
-            let sending_result = ws_sink
-                .write()
-                .await
-                .send(Message::binary(serialized_response))
-                .await;
+                let sending_result = responder
+                    .send_response(batch_inclusion_data_message(&response))
+                    .await;
 
-            match sending_result {
-                Err(Error::AlreadyClosed) => (),
-                Err(e) => error!("Error while sending batch inclusion data response: {}", e),
-                Ok(_) => (),
-            }
+                match sending_result {
+                    Err(Error::AlreadyClosed) => (),
+                    Err(e) => error!("Error while sending batch inclusion data response: {}", e),
+                    Ok(_) => (),
+                }
 
-            info!("Response sent");
-        })
+                info!("Response sent");
+            },
+        )
         .await;
 }
 
+/// Send a close response to the client indicating why its message was rejected. The close code
+/// and reason are handed to the SDK user as they were sent. Clients supporting error responses
+/// are sent the details of the rejection before, with the submission id of the message.
+async fn send_rejection(
+    ws_conn_sink: &Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+    error_responses: bool,
+    rejection: Rejection,
+    submission_id: Option<u64>,
+    reason: &str,
+) {
+    let rejection_code = RejectionCode::of(&rejection);
+    let mut ws_conn_sink = ws_conn_sink.write().await;
+
+    if error_responses {
+        let error_response = ErrorResponse {
+            rejection,
+            submission_id,
+        };
+        if let Err(e) = ws_conn_sink
+            .send(error_response_message(&error_response))
+            .await
+        {
+            error!("Error while sending error response: {}", e);
+        }
+    }
+
+    let rejection_msg = rejection_message(rejection_code, reason);
+    if let Err(e) = ws_conn_sink.send(rejection_msg).await {
+        error!("Error while sending rejection response: {}", e);
+    }
+}
+
+/// Rejects a message whose replay protection check failed, closing the connection.
+async fn reject_replayed_message(
+    ws_conn_sink: &Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+    error_responses: bool,
+    submission_id: Option<u64>,
+    error: ReplayError,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    error!("Replay protection check failed: {:?}", error);
+    let reason = format!("{:?}", error);
+    send_rejection(
+        ws_conn_sink,
+        error_responses,
+        Rejection::ReplayedMessage {
+            reason: reason.clone(),
+        },
+        submission_id,
+        &reason,
+    )
+    .await;
+    Err(tokio_tungstenite::tungstenite::Error::Protocol(
+        ProtocolError::HandshakeIncomplete,
+    ))
+}
+
+/// Message sent to every client when it connects, holding the protocol version of the batcher
+fn protocol_version_message() -> Message {
+    Message::binary(
+        aligned_sdk::sdk::CURRENT_PROTOCOL_VERSION
+            .to_be_bytes()
+            .to_vec(),
+    )
+}
+
+/// Message sent instead of the protocol version to clients that announce their own, holding the
+/// protocol version and the features of the batcher, its public key if it has an encryption
+/// key, and the session of the connection if the client supports sessions
+fn protocol_handshake_message(
+    encryption_key: Option<&EncryptionKey>,
+    session: Option<&SessionHandshake>,
+) -> Message {
+    let mut handshake = aligned_sdk::sdk::protocol_handshake();
+    if let Some(encryption_key) = encryption_key {
+        encryption_key.announce(&mut handshake);
+    }
+    handshake.session = session.cloned();
+    let serialized_handshake =
+        serde_json::to_string(&handshake).expect("Could not serialize handshake");
+    Message::text(serialized_handshake)
+}
+
+/// Message sent to a client once the batch with its verification data has been verified
+fn batch_inclusion_data_message(batch_inclusion_data: &BatchInclusionData) -> Message {
+    let serialized_response =
+        serde_json::to_vec(batch_inclusion_data).expect("Could not serialize response");
+
+    Message::binary(serialized_response)
+}
+
+/// Message sent to a client in answer to its inclusion query
+fn inclusion_query_response_message(response: &InclusionQueryResponse) -> Message {
+    let serialized_response =
+        serde_json::to_string(response).expect("Could not serialize inclusion query response");
+    Message::text(serialized_response)
+}
+
+/// Message sent to a client in answer to its submission status query
+fn submission_status_response_message(response: &SubmissionStatusResponse) -> Message {
+    let serialized_response =
+        serde_json::to_string(response).expect("Could not serialize submission status response");
+    Message::text(serialized_response)
+}
+
+/// Message sent before the rejection message to clients supporting error responses, with the
+/// details of the rejection
+fn error_response_message(error_response: &ErrorResponse) -> Message {
+    let serialized_error_response =
+        serde_json::to_string(error_response).expect("Could not serialize error response");
+    Message::text(serialized_error_response)
+}
+
+fn rejection_message(rejection_code: RejectionCode, reason: &str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::from(rejection_code as u16),
+        reason: Cow::from(reason.to_string()),
+    }))
+}
+
 /// Send a close response to all clients that included data in the batch indicated that a
 /// timeout was exceeded awaiting for the batch verification events
-async fn send_timeout_close(finalized_batch: BatchQueue) -> Result<(), BatcherError> {
+async fn send_timeout_close(finalized_batch: Vec<BatchQueueEntry>) -> Result<(), BatcherError> {
     let timeout_msg = Message::Close(Some(CloseFrame {
         code: CloseCode::Protocol,
         reason: Cow::from("Timeout: BatchVerified event not received"),
     }));
 
-    for (_, _, ws_sink, _) in finalized_batch.iter() {
-        let send_result = ws_sink.write().await.send(timeout_msg.clone()).await;
+    for (responder, _, _, _) in finalized_batch.iter() {
+        let send_result = responder.send(timeout_msg.clone()).await;
         match send_result {
             // When two or more proofs from the same client are included into a batch,
             // there will be more than one `ws_sink` corresponding to that client. When one is
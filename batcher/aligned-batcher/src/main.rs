@@ -4,8 +4,10 @@ use std::sync::Arc;
 
 use clap::Parser;
 use env_logger::Env;
+use log::info;
 
 use aligned_batcher::{types::errors::BatcherError, Batcher};
+use aligned_sdk::build_info::long_version;
 
 /// Batcher main flow:
 /// There are two main tasks spawned: `listen_connections` and `listen_new_blocks`
@@ -16,6 +18,7 @@ use aligned_batcher::{types::errors::BatcherError, Batcher};
 ///    is to be posted.
 #[derive(Parser)]
 #[command(name = "Aligned Batcher")]
+#[command(version = long_version(env!("CARGO_PKG_VERSION")))]
 #[command(about = "An application with server and client subcommands", long_about = None)]
 struct Cli {
     #[arg(short, long)]
@@ -37,6 +40,10 @@ async fn main() -> Result<(), BatcherError> {
     };
 
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    info!(
+        "Starting batcher version {}",
+        long_version(env!("CARGO_PKG_VERSION"))
+    );
 
     let batcher = Batcher::new(cli.config).await;
     let batcher = Arc::new(batcher);
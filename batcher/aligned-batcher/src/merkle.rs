@@ -0,0 +1,294 @@
+use std::io::{self, Read};
+use std::marker::PhantomData;
+
+pub use aligned_sdk::merkle::{pad_batch, padded_batch_len, BatchMerkleTree};
+use aligned_sdk::types::{
+    BatchMerkleTreeBackend, BatchVersion, VerificationCommitmentBatch, VerificationData,
+    VerificationDataCommitment,
+};
+use sha3::{Digest, Keccak256};
+
+/// Size of the chunks in which `IncrementalBatchMerkleTree::push_proof` reads a proof.
+const PROOF_CHUNK_LEN: usize = 64 * 1024;
+
+/// Builder of the merkle tree of a batch that takes its entries one at a time, so the batcher
+/// only keeps the nodes of the tree, 32 bytes each, instead of every proof of the batch until it
+/// is finalized. Parents are hashed as soon as all of their children are added, and the tree is
+/// padded like `BatchMerkleTree::build` pads a batch when it is finalized, so both give the same
+/// tree and inclusion proofs.
+#[derive(Clone, Debug)]
+pub struct IncrementalBatchMerkleTree<B = VerificationCommitmentBatch> {
+    batch_version: BatchVersion,
+    // Nodes of every level hashed so far, from the leaves up
+    levels: Vec<Vec<[u8; 32]>>,
+    backend: PhantomData<B>,
+}
+
+impl IncrementalBatchMerkleTree {
+    /// Creates the builder of the keccak merkle tree of a batch of the given version.
+    pub fn new(batch_version: BatchVersion) -> Self {
+        Self::with_backend(batch_version)
+    }
+}
+
+impl<B: BatchMerkleTreeBackend> IncrementalBatchMerkleTree<B> {
+    /// Creates the builder of the merkle tree of a batch with the backend of the tree, as `new`
+    /// does with keccak.
+    pub fn with_backend(batch_version: BatchVersion) -> Self {
+        IncrementalBatchMerkleTree {
+            batch_version,
+            levels: vec![Vec::new()],
+            backend: PhantomData,
+        }
+    }
+
+    /// Returns the number of entries added to the batch.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the leaves of the entries added to the batch, in their order.
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.levels[0]
+    }
+
+    /// Adds the entry with the given commitment to the batch.
+    /// # Returns
+    /// * The position of the entry in the batch.
+    pub fn push(&mut self, commitment: &VerificationDataCommitment) -> usize {
+        let index_in_batch = self.len();
+        self.push_leaf(B::hash_data(commitment));
+        index_in_batch
+    }
+
+    /// Adds an entry to the batch reading its proof in chunks, so proofs spilled to disk can be
+    /// hashed without loading them whole. The proof of the verification data is not used, so it
+    /// can be left empty.
+    /// # Arguments
+    /// * `proof` - The reader of the proof of the entry.
+    /// * `verification_data` - The rest of the verification data of the entry.
+    /// # Returns
+    /// * The commitment of the entry, the same as the one of its verification data with the proof.
+    /// # Errors
+    /// * The error of the reader if the proof can't be read.
+    pub fn push_proof<R: Read>(
+        &mut self,
+        proof: R,
+        verification_data: &VerificationData,
+    ) -> io::Result<VerificationDataCommitment> {
+        let commitment = VerificationDataCommitment {
+            proof_commitment: proof_commitment(proof)?,
+            ..VerificationDataCommitment::from(verification_data)
+        };
+        self.push(&commitment);
+        Ok(commitment)
+    }
+
+    /// Pads the batch as `pad_batch` does and hashes the rest of the tree.
+    /// # Returns
+    /// * The merkle tree of the batch, or `None` if the batch is empty.
+    pub fn finalize(mut self) -> Option<BatchMerkleTree<B>> {
+        let last_leaf = *self.levels[0].last()?;
+        for _ in self.len()..padded_batch_len(self.batch_version, self.len()) {
+            self.push_leaf(last_leaf);
+        }
+        BatchMerkleTree::from_levels(self.batch_version, self.levels)
+    }
+
+    fn push_leaf(&mut self, leaf: [u8; 32]) {
+        let arity = self.batch_version.arity();
+        let mut node = leaf;
+        let mut level = 0;
+        loop {
+            if level == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            let nodes = &mut self.levels[level];
+            nodes.push(node);
+            if nodes.len() % arity != 0 {
+                return;
+            }
+            node = B::hash_children(&nodes[nodes.len() - arity..]);
+            level += 1;
+        }
+    }
+}
+
+/// Returns the keccak256 hash of a proof read in chunks, which is its commitment.
+fn proof_commitment<R: Read>(mut proof: R) -> io::Result<[u8; 32]> {
+    let mut hasher = Keccak256::new();
+    let mut chunk = vec![0; PROOF_CHUNK_LEN];
+    loop {
+        match proof.read(&mut chunk) {
+            Ok(0) => return Ok(hasher.finalize().into()),
+            Ok(read) => hasher.update(&chunk[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Checks that a leaf is included in the V1 batch merkle tree with the given root, following the
+/// same rules as the `verifyBatchInclusion` function of the Aligned service manager contract.
+/// The parent of two nodes is the keccak256 hash of their concatenation, with the left child
+/// first. Whether the current node is the left or right child is given by the bit of the index
+/// of the leaf for that level, starting from the least significant one.
+/// As in the contract, bits of the index beyond the length of the path are not checked.
+/// Trees of other versions are checked with `BatchVersion::verify_merkle_inclusion`.
+/// # Arguments
+/// * `root` - The root of the batch merkle tree.
+/// * `leaf` - The leaf to check, which is the hash of a `VerificationDataCommitment`.
+/// * `path` - The sibling of the node at each level of the tree, from the leaves to the root.
+/// * `index` - The position of the leaf in the batch.
+/// # Returns
+/// * Whether the leaf is included in the tree.
+pub fn verify_merkle_inclusion(
+    root: &[u8; 32],
+    leaf: &[u8; 32],
+    path: &[[u8; 32]],
+    index: usize,
+) -> bool {
+    BatchVersion::V1.verify_merkle_inclusion(root, leaf, path, index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aligned_sdk::types::{PoseidonCommitmentBatch, ProvingSystemId};
+    use ethers::types::Address;
+    use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+
+    fn commitments(batch_size: u8) -> Vec<VerificationDataCommitment> {
+        (0..batch_size)
+            .map(|i| VerificationDataCommitment {
+                proof_commitment: [i; 32],
+                pub_input_commitment: [i.wrapping_add(1); 32],
+                proving_system_aux_data_commitment: [i.wrapping_add(2); 32],
+                proof_generator_addr: [i; 20],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_merkle_inclusion_rejects_wrong_inputs() {
+        let commitments = commitments(4);
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+        let proof = tree.get_proof_by_pos(1).unwrap();
+        let leaf = VerificationCommitmentBatch::hash_data(&commitments[1]);
+
+        assert!(!verify_merkle_inclusion(
+            &tree.root,
+            &leaf,
+            &proof.merkle_path,
+            0
+        ));
+        assert!(!verify_merkle_inclusion(
+            &tree.root,
+            &[0; 32],
+            &proof.merkle_path,
+            1
+        ));
+        assert!(!verify_merkle_inclusion(
+            &tree.root,
+            &leaf,
+            &proof.merkle_path[..1],
+            1
+        ));
+
+        let mut wrong_path = proof.merkle_path.clone();
+        wrong_path.reverse();
+        assert!(!verify_merkle_inclusion(&tree.root, &leaf, &wrong_path, 1));
+    }
+
+    #[test]
+    fn test_incremental_tree_matches_built_tree() {
+        for batch_version in [BatchVersion::V1, BatchVersion::V2] {
+            for batch_size in [1, 2, 3, 4, 5, 8, 13, 16, 17] {
+                let commitments = commitments(batch_size);
+                let tree = BatchMerkleTree::build(batch_version, &commitments).unwrap();
+
+                let mut builder = IncrementalBatchMerkleTree::new(batch_version);
+                for (index, commitment) in commitments.iter().enumerate() {
+                    assert_eq!(builder.push(commitment), index);
+                }
+                let incremental_tree = builder.finalize().unwrap();
+
+                assert_eq!(incremental_tree.root, tree.root);
+                for index in 0..padded_batch_len(batch_version, commitments.len()) {
+                    assert_eq!(
+                        incremental_tree
+                            .get_proof_by_pos(index)
+                            .unwrap()
+                            .merkle_path,
+                        tree.get_proof_by_pos(index).unwrap().merkle_path
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_poseidon_tree_matches_built_tree() {
+        let commitments = commitments(5);
+        let tree: BatchMerkleTree<PoseidonCommitmentBatch> =
+            BatchMerkleTree::build_with_backend(BatchVersion::V1, &commitments).unwrap();
+
+        let mut builder =
+            IncrementalBatchMerkleTree::<PoseidonCommitmentBatch>::with_backend(BatchVersion::V1);
+        for commitment in &commitments {
+            builder.push(commitment);
+        }
+
+        assert_eq!(builder.finalize().unwrap().root, tree.root);
+    }
+
+    #[test]
+    fn test_empty_incremental_tree_has_no_merkle_tree() {
+        let builder = IncrementalBatchMerkleTree::new(BatchVersion::V1);
+
+        assert!(builder.is_empty());
+        assert!(builder.finalize().is_none());
+    }
+
+    #[test]
+    fn test_streamed_proof_has_the_commitment_of_the_whole_proof() {
+        // Larger than a chunk so the proof is read in several parts
+        let proof: Vec<u8> = (0..3 * PROOF_CHUNK_LEN + 7).map(|i| i as u8).collect();
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: proof.clone(),
+            pub_input: Some(vec![1, 2, 3]),
+            verification_key: None,
+            vm_program_code: Some(vec![4, 5, 6]),
+            proof_generator_addr: Address::repeat_byte(7),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        let without_proof = VerificationData {
+            proof: Vec::new(),
+            ..verification_data.clone()
+        };
+
+        let mut builder = IncrementalBatchMerkleTree::new(BatchVersion::V1);
+        let commitment = builder
+            .push_proof(proof.as_slice(), &without_proof)
+            .unwrap();
+
+        let expected = VerificationDataCommitment::from(&verification_data);
+        assert_eq!(
+            serde_json::to_value(commitment).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+        assert_eq!(
+            builder.finalize().unwrap().root,
+            BatchMerkleTree::build(BatchVersion::V1, &[expected])
+                .unwrap()
+                .root
+        );
+    }
+}
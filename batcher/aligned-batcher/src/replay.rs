@@ -0,0 +1,257 @@
+//! Validation of the nonce and expiry of client messages, so that a captured message can't be
+//! sent again. Nonces are tracked in memory and forgotten when the batcher restarts, so after a
+//! restart only the expiry of a message protects it from being replayed.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aligned_sdk::types::ReplayProtection;
+use ethers::types::Address;
+
+pub enum ReplayError {
+    /// The message has no nonce and expiry, and the batcher requires them.
+    Missing,
+    Expired {
+        expires_at: u64,
+        now: u64,
+    },
+    /// The nonce was already accepted from the same address, and hasn't expired yet.
+    NonceReused {
+        nonce: u64,
+    },
+}
+
+impl fmt::Debug for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Missing => write!(f, "Message has no nonce and expiry"),
+            ReplayError::Expired { expires_at, now } => write!(
+                f,
+                "Message expired at {}, current time is {}",
+                expires_at, now
+            ),
+            ReplayError::NonceReused { nonce } => {
+                write!(f, "Nonce {} was already accepted", nonce)
+            }
+        }
+    }
+}
+
+/// Checks that a message with the given replay protection has not expired at `now`.
+pub fn check_expiry(
+    replay_protection: &ReplayProtection,
+    now: SystemTime,
+) -> Result<(), ReplayError> {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    if replay_protection.expires_at < now {
+        return Err(ReplayError::Expired {
+            expires_at: replay_protection.expires_at,
+            now,
+        });
+    }
+    Ok(())
+}
+
+/// Nonces accepted from each address that haven't expired yet. Nonces can be accepted in any
+/// order, so that a wallet can submit on several connections at once. Once a message expires, it's
+/// rejected by its expiry, so its nonce is forgotten.
+#[derive(Default)]
+pub struct NonceTracker {
+    accepted_nonces: HashMap<Address, HashSet<u64>>,
+    /// Expiry of each accepted nonce, the earliest first.
+    expiries: BinaryHeap<Reverse<(u64, Address, u64)>>,
+}
+
+impl NonceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_accepted(&self, addr: &Address, nonce: u64) -> bool {
+        self.accepted_nonces
+            .get(addr)
+            .is_some_and(|nonces| nonces.contains(&nonce))
+    }
+
+    /// Returns the number of nonces tracked, across all addresses.
+    pub fn len(&self) -> usize {
+        self.expiries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expiries.is_empty()
+    }
+
+    /// Checks that a message signed by `addr` has not expired at `now` and that its nonce was not
+    /// accepted from `addr` yet, without recording it.
+    pub fn check(
+        &self,
+        addr: &Address,
+        replay_protection: &ReplayProtection,
+        now: SystemTime,
+    ) -> Result<(), ReplayError> {
+        check_expiry(replay_protection, now)?;
+
+        if self.is_accepted(addr, replay_protection.nonce) {
+            return Err(ReplayError::NonceReused {
+                nonce: replay_protection.nonce,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the message as `check` does and, if it passes, records its nonce as accepted until
+    /// its expiry. The nonces that expired at `now` are forgotten.
+    pub fn check_and_record(
+        &mut self,
+        addr: Address,
+        replay_protection: &ReplayProtection,
+        now: SystemTime,
+    ) -> Result<(), ReplayError> {
+        self.prune(now);
+        self.check(&addr, replay_protection, now)?;
+
+        self.accepted_nonces
+            .entry(addr)
+            .or_default()
+            .insert(replay_protection.nonce);
+        self.expiries.push(Reverse((
+            replay_protection.expires_at,
+            addr,
+            replay_protection.nonce,
+        )));
+        Ok(())
+    }
+
+    /// Forgets the nonces of the messages that expired at `now`.
+    fn prune(&mut self, now: SystemTime) {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        while let Some(Reverse((expires_at, addr, nonce))) = self.expiries.peek().copied() {
+            if expires_at >= now {
+                break;
+            }
+            self.expiries.pop();
+            if let Some(nonces) = self.accepted_nonces.get_mut(&addr) {
+                nonces.remove(&nonce);
+                if nonces.is_empty() {
+                    self.accepted_nonces.remove(&addr);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_replayed_and_expired_messages_are_rejected() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let addr = Address::repeat_byte(1);
+        let replay_protection = ReplayProtection {
+            nonce: 5,
+            expires_at: 1000,
+        };
+
+        let mut nonce_tracker = NonceTracker::new();
+        assert!(nonce_tracker
+            .check_and_record(addr, &replay_protection, now)
+            .is_ok());
+        assert!(nonce_tracker.is_accepted(&addr, 5));
+
+        // The same message sent again
+        assert!(matches!(
+            nonce_tracker.check_and_record(addr, &replay_protection, now),
+            Err(ReplayError::NonceReused { nonce: 5 })
+        ));
+
+        // Nonces are tracked per address
+        assert!(nonce_tracker
+            .check_and_record(Address::repeat_byte(2), &replay_protection, now)
+            .is_ok());
+
+        let expired = ReplayProtection {
+            nonce: 6,
+            expires_at: 999,
+        };
+        assert!(matches!(
+            nonce_tracker.check_and_record(addr, &expired, now),
+            Err(ReplayError::Expired {
+                expires_at: 999,
+                now: 1000
+            })
+        ));
+        assert!(!nonce_tracker.is_accepted(&addr, 6));
+    }
+
+    #[test]
+    fn test_nonces_are_accepted_in_any_order() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let addr = Address::repeat_byte(1);
+        let mut nonce_tracker = NonceTracker::new();
+
+        // As sent by two connections of the same wallet
+        for nonce in [7, 3, 5] {
+            let replay_protection = ReplayProtection {
+                nonce,
+                expires_at: 1060,
+            };
+            assert!(nonce_tracker
+                .check_and_record(addr, &replay_protection, now)
+                .is_ok());
+        }
+        assert_eq!(nonce_tracker.len(), 3);
+    }
+
+    #[test]
+    fn test_checking_a_message_does_not_record_its_nonce() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let addr = Address::repeat_byte(1);
+        let replay_protection = ReplayProtection {
+            nonce: 5,
+            expires_at: 1060,
+        };
+
+        let mut nonce_tracker = NonceTracker::new();
+        assert!(nonce_tracker.check(&addr, &replay_protection, now).is_ok());
+        assert!(nonce_tracker.is_empty());
+        assert!(nonce_tracker
+            .check_and_record(addr, &replay_protection, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_expired_nonces_are_forgotten() {
+        let addr = Address::repeat_byte(1);
+        let mut nonce_tracker = NonceTracker::new();
+        for (nonce, expires_at) in [(1, 1010), (2, 1020)] {
+            let replay_protection = ReplayProtection { nonce, expires_at };
+            assert!(nonce_tracker
+                .check_and_record(
+                    addr,
+                    &replay_protection,
+                    UNIX_EPOCH + Duration::from_secs(1000)
+                )
+                .is_ok());
+        }
+
+        let replay_protection = ReplayProtection {
+            nonce: 3,
+            expires_at: 1080,
+        };
+        assert!(nonce_tracker
+            .check_and_record(
+                addr,
+                &replay_protection,
+                UNIX_EPOCH + Duration::from_secs(1015)
+            )
+            .is_ok());
+        assert!(!nonce_tracker.is_accepted(&addr, 1));
+        assert!(nonce_tracker.is_accepted(&addr, 2));
+        assert_eq!(nonce_tracker.len(), 2);
+    }
+}
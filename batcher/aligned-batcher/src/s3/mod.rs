@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use aws_config::meta::region::RegionProviderChain;
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::error::SdkError;
@@ -14,16 +16,23 @@ pub async fn create_client() -> Client {
     Client::new(&config)
 }
 
-pub async fn upload_object(
+/// Uploads the contents of a file, streaming it from disk instead of loading it whole.
+pub async fn upload_file(
     client: &Client,
     bucket_name: &str,
-    bytes: Vec<u8>,
+    path: &Path,
     key: &str,
+    metadata: &[(&str, String)],
 ) -> Result<PutObjectOutput, SdkError<PutObjectError>> {
-    let body = ByteStream::from(bytes);
+    let body = ByteStream::from_path(path)
+        .await
+        .map_err(SdkError::construction_failure)?;
 
-    client
-        .put_object()
+    metadata
+        .iter()
+        .fold(client.put_object(), |request, (key, value)| {
+            request.metadata(*key, value)
+        })
         .bucket(bucket_name)
         .key(key)
         .body(body)
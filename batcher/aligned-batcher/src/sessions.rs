@@ -0,0 +1,240 @@
+//! Sessions of the connections of clients supporting `session_resumption`, with which a client
+//! that lost its connection resumes the submission on a new one, as described in
+//! `aligned_sdk::session`. Sessions are kept in memory, so they are lost when the batcher
+//! restarts, and the clients send their proofs without a response again.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use aligned_sdk::session::new_session_token;
+use futures_util::stream::SplitSink;
+use futures_util::SinkExt;
+use log::debug;
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, RwLock};
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::WebSocketStream;
+
+pub(crate) type WsSink = Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>;
+
+/// Where the responses to the proofs of a message are sent.
+#[derive(Clone)]
+pub(crate) enum Responder {
+    /// The connection that sent the message.
+    Connection(WsSink),
+    /// The session of the connection that sent the message, which sends them to the connection
+    /// it's attached to when they are ready.
+    Session(Arc<Mutex<Session>>),
+}
+
+impl Responder {
+    /// Records that a proof of the message was added to the batch queue.
+    pub(crate) async fn accept_proof(&self) {
+        if let Responder::Session(session) = self {
+            session.lock().await.accepted_proofs += 1;
+        }
+    }
+
+    /// Sends the response to a proof. The responses of a session are kept, so that they are
+    /// replayed to the client when it resumes the session if its connection was lost.
+    pub(crate) async fn send_response(&self, response: Message) -> Result<(), Error> {
+        match self {
+            Responder::Connection(sink) => sink.write().await.send(response).await,
+            Responder::Session(session) => {
+                let mut session = session.lock().await;
+                session.responses.push(response.clone());
+                session.send(response).await
+            }
+        }
+    }
+
+    /// Sends a message that is not a response, such as a close frame.
+    pub(crate) async fn send(&self, message: Message) -> Result<(), Error> {
+        match self {
+            Responder::Connection(sink) => sink.write().await.send(message).await,
+            Responder::Session(session) => session.lock().await.send(message).await,
+        }
+    }
+}
+
+/// Session of the connections of a client, attached to one connection at a time.
+#[derive(Default)]
+pub(crate) struct Session {
+    /// The connection the session is attached to, or `None` while it's disconnected.
+    sink: Option<WsSink>,
+    /// Responses sent in the session, in the order they were sent.
+    responses: Vec<Message>,
+    accepted_proofs: usize,
+}
+
+impl Session {
+    pub(crate) fn accepted_proofs(&self) -> usize {
+        self.accepted_proofs
+    }
+
+    /// Attaches the session to the connection of `sink`, sending it the handshake and then the
+    /// responses from the `received`-th on, which the client didn't receive.
+    pub(crate) async fn attach(
+        &mut self,
+        sink: WsSink,
+        handshake: Message,
+        received: usize,
+    ) -> Result<(), Error> {
+        {
+            let mut ws_sink = sink.write().await;
+            ws_sink.send(handshake).await?;
+            for response in self.responses.iter().skip(received) {
+                ws_sink.send(response.clone()).await?;
+            }
+        }
+        self.sink = Some(sink);
+        Ok(())
+    }
+
+    /// Detaches the session from the connection of `sink` once it ends. Returns whether the
+    /// session was attached to it, which it isn't if it was resumed by another connection.
+    pub(crate) fn detach(&mut self, sink: &WsSink) -> bool {
+        let attached = matches!(&self.sink, Some(attached) if Arc::ptr_eq(attached, sink));
+        if attached {
+            self.sink = None;
+        }
+        attached
+    }
+
+    /// Returns whether every proof accepted in the session was responded.
+    pub(crate) fn is_responded(&self) -> bool {
+        self.responses.len() >= self.accepted_proofs
+    }
+
+    /// Sends a message to the connection the session is attached to. A disconnected session
+    /// keeps the responses until it's resumed, so failing to send is not an error.
+    async fn send(&mut self, message: Message) -> Result<(), Error> {
+        let Some(sink) = &self.sink else {
+            return Ok(());
+        };
+        let result = sink.write().await.send(message).await;
+        if let Err(e) = result {
+            debug!("Session disconnected: {}", e);
+            self.sink = None;
+        }
+        Ok(())
+    }
+}
+
+struct StoredSession {
+    session: Arc<Mutex<Session>>,
+    /// When the last connection of the session ended, or `None` while it's attached to one.
+    disconnected_at: Option<Instant>,
+}
+
+/// Sessions that can be resumed, by token. Sessions are forgotten `ttl` after their last
+/// connection ends.
+pub(crate) struct SessionStore {
+    sessions: HashMap<String, StoredSession>,
+    ttl: Duration,
+}
+
+impl SessionStore {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        SessionStore {
+            sessions: HashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Opens a new session, returning its token.
+    pub(crate) fn open(&mut self, now: Instant) -> (String, Arc<Mutex<Session>>) {
+        self.remove_expired(now);
+        let token = new_session_token();
+        let session = Arc::new(Mutex::new(Session::default()));
+        self.sessions.insert(
+            token.clone(),
+            StoredSession {
+                session: session.clone(),
+                disconnected_at: None,
+            },
+        );
+        (token, session)
+    }
+
+    /// Returns the session with this token to be attached to a new connection, or `None` if
+    /// there is no such session or it expired.
+    pub(crate) fn resume(&mut self, token: &str, now: Instant) -> Option<Arc<Mutex<Session>>> {
+        self.remove_expired(now);
+        let stored_session = self.sessions.get_mut(token)?;
+        stored_session.disconnected_at = None;
+        Some(stored_session.session.clone())
+    }
+
+    /// Records that the connection of the session ended at `now`. The session is removed at
+    /// once unless it's `kept` to be resumed.
+    pub(crate) fn disconnect(&mut self, token: &str, now: Instant, kept: bool) {
+        if !kept {
+            self.sessions.remove(token);
+        } else if let Some(stored_session) = self.sessions.get_mut(token) {
+            stored_session.disconnected_at = Some(now);
+        }
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.sessions.retain(|_, stored_session| {
+            stored_session
+                .disconnected_at
+                .map_or(true, |disconnected_at| {
+                    now.duration_since(disconnected_at) < ttl
+                })
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disconnected_sessions_expire() {
+        let ttl = Duration::from_secs(60);
+        let mut session_store = SessionStore::new(ttl);
+        let start = Instant::now();
+
+        let (token, session) = session_store.open(start);
+        let (other_token, _) = session_store.open(start);
+        assert_ne!(token, other_token);
+
+        // Sessions are kept while connected, and for the ttl after their connection ends
+        session_store.disconnect(&token, start + ttl, true);
+        let resumed = session_store.resume(&token, start + ttl * 2 - Duration::from_secs(1));
+        assert!(Arc::ptr_eq(&resumed.unwrap(), &session));
+
+        session_store.disconnect(&token, start + ttl * 2, true);
+        assert!(session_store.resume(&token, start + ttl * 3).is_none());
+        assert!(session_store
+            .resume(&other_token, start + ttl * 3)
+            .is_some());
+    }
+
+    #[test]
+    fn test_sessions_not_kept_are_removed() {
+        let mut session_store = SessionStore::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        let (token, _) = session_store.open(now);
+        session_store.disconnect(&token, now, false);
+        assert!(session_store.resume(&token, now).is_none());
+        assert!(session_store.resume("unknown", now).is_none());
+    }
+
+    #[test]
+    fn test_sessions_are_responded_once_every_accepted_proof_is() {
+        let mut session = Session::default();
+        assert!(session.is_responded());
+
+        session.accepted_proofs = 2;
+        session.responses.push(Message::binary(vec![1]));
+        assert!(!session.is_responded());
+        session.responses.push(Message::binary(vec![2]));
+        assert!(session.is_responded());
+    }
+}
@@ -1,15 +1,24 @@
 use lazy_static::lazy_static;
 use log::{debug, warn};
-use sp1_sdk::ProverClient;
+use sp1_sdk::{ProverClient, SP1CompressedProof};
 
 lazy_static! {
     static ref SP1_PROVER_CLIENT: ProverClient = ProverClient::new();
 }
 
-pub fn verify_sp1_proof(proof: &[u8], elf: &[u8]) -> bool {
+/// Verifies an SP1 proof. When public values are given, they must match the ones committed in
+/// the proof.
+pub fn verify_sp1_proof(proof: &[u8], elf: &[u8], public_values: Option<&[u8]>) -> bool {
     debug!("Verifying SP1 proof");
     let (_pk, vk) = SP1_PROVER_CLIENT.setup(elf);
-    if let Ok(proof) = bincode::deserialize(proof) {
+    if let Ok(proof) = bincode::deserialize::<SP1CompressedProof>(proof) {
+        if public_values
+            .is_some_and(|public_values| public_values != proof.public_values.as_slice())
+        {
+            warn!("SP1 public values do not match the public input");
+            return false;
+        }
+
         let res = SP1_PROVER_CLIENT.verify_compressed(&proof, &vk).is_ok();
         debug!("SP1 proof is valid: {}", res);
         if res {
@@ -1,17 +1,300 @@
-use std::sync::Arc;
+//! Queue of the proofs waiting to be sent in a batch. Proofs are hashed into the merkle tree of
+//! their batch as they are added, and their verification data is written to the data file of the
+//! batch, so the batcher only keeps in memory the leaves of the tree and what's needed to respond
+//! to each proof, instead of every proof until the batch is finalized.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use ethers::types::Address;
-use futures_util::stream::SplitSink;
-use tokio::{net::TcpStream, sync::RwLock};
-use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 
-use aligned_sdk::types::{VerificationData, VerificationDataCommitment};
+use aligned_sdk::types::{BatchVersion, VerificationData};
+
+use crate::merkle::IncrementalBatchMerkleTree;
+use crate::sessions::Responder;
 
 pub(crate) type BatchQueueEntry = (
-    VerificationData,
-    VerificationDataCommitment,
-    Arc<RwLock<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+    Responder,
     Address,
+    // Submission id of the message, echoed in the response
+    Option<u64>,
+    // Metadata of the verification data, only echoed in the response
+    Option<Vec<u8>>,
 );
 
-pub(crate) type BatchQueue = Vec<BatchQueueEntry>;
+/// Verification data of the proofs of a batch, as the JSON array uploaded to S3, written to a
+/// file as the proofs are added. The file is removed when it's dropped.
+pub(crate) struct BatchDataFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    // Bytes of the JSON array, including its closing bracket
+    len: usize,
+}
+
+impl BatchDataFile {
+    fn create(path: PathBuf) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(b"[")?;
+        Ok(BatchDataFile {
+            path,
+            writer,
+            len: 2,
+        })
+    }
+
+    /// Returns the size the batch data would have with an entry of `entry_len` bytes more.
+    fn len_with(&self, entry_len: usize) -> usize {
+        match self.len {
+            2 => self.len + entry_len,
+            len => len + 1 + entry_len,
+        }
+    }
+
+    fn push(&mut self, entry: &[u8]) -> io::Result<()> {
+        if self.len > 2 {
+            self.writer.write_all(b",")?;
+        }
+        self.writer.write_all(entry)?;
+        self.len = self.len_with(entry.len());
+        Ok(())
+    }
+
+    /// Closes the JSON array, and returns the path of the file to upload it.
+    pub(crate) fn finish(&mut self) -> io::Result<&Path> {
+        self.writer.write_all(b"]")?;
+        self.writer.flush()?;
+        Ok(&self.path)
+    }
+}
+
+impl Drop for BatchDataFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A batch of the queue: the merkle tree of its proofs, their data file, and the entries to
+/// respond to them, in the same order.
+pub(crate) struct PendingBatch {
+    pub(crate) entries: Vec<BatchQueueEntry>,
+    pub(crate) merkle_tree: IncrementalBatchMerkleTree,
+    pub(crate) data: BatchDataFile,
+}
+
+/// Batches of the queue, the oldest first. Proofs are added to the last one, and a new batch is
+/// started when the data of a proof would make the last one larger than `max_batch_size`, so
+/// batches are split as they are filled instead of when they are sent.
+pub(crate) struct BatchQueue {
+    batches: VecDeque<PendingBatch>,
+    batch_version: BatchVersion,
+    max_batch_size: usize,
+    data_dir: PathBuf,
+    next_file_id: u64,
+}
+
+impl BatchQueue {
+    /// Creates an empty queue whose batches are at most `max_batch_size` bytes, unless a single
+    /// proof is larger, and whose data files are written in `data_dir`.
+    pub(crate) fn new(
+        batch_version: BatchVersion,
+        max_batch_size: usize,
+        data_dir: PathBuf,
+    ) -> Self {
+        BatchQueue {
+            batches: VecDeque::new(),
+            batch_version,
+            max_batch_size,
+            data_dir,
+            next_file_id: 0,
+        }
+    }
+
+    /// Returns the number of proofs of the queue, across all of its batches.
+    pub(crate) fn len(&self) -> usize {
+        self.batches.iter().map(|batch| batch.entries.len()).sum()
+    }
+
+    /// Returns the leaves of the proofs of the queue.
+    pub(crate) fn leaves(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.batches
+            .iter()
+            .flat_map(|batch| batch.merkle_tree.leaves())
+    }
+
+    /// Adds a proof to the last batch of the queue. Its metadata is kept to respond to it, and the
+    /// rest of its verification data is written to the data file of the batch, after which its
+    /// proof is dropped.
+    /// # Errors
+    /// * The error of the data file if it can't be written.
+    pub(crate) fn push(
+        &mut self,
+        mut verification_data: VerificationData,
+        responder: Responder,
+        proof_submitter_addr: Address,
+        submission_id: Option<u64>,
+    ) -> io::Result<()> {
+        // The metadata of a proof is only echoed to the client that sent it, and is not part of
+        // the batch data
+        let metadata = verification_data.metadata.take();
+        let entry = serde_json::to_vec(&verification_data)?;
+
+        let starts_batch = self.batches.back().map_or(true, |batch| {
+            !batch.entries.is_empty() && batch.data.len_with(entry.len()) > self.max_batch_size
+        });
+        if starts_batch {
+            let batch = self.new_batch()?;
+            self.batches.push_back(batch);
+        }
+        let batch = self.batches.back_mut().expect("The queue has a batch");
+
+        batch.data.push(&entry)?;
+        let proof = std::mem::take(&mut verification_data.proof);
+        batch
+            .merkle_tree
+            .push_proof(proof.as_slice(), &verification_data)?;
+        batch
+            .entries
+            .push((responder, proof_submitter_addr, submission_id, metadata));
+        Ok(())
+    }
+
+    /// Removes the oldest batch of the queue.
+    pub(crate) fn pop_front(&mut self) -> Option<PendingBatch> {
+        self.batches.pop_front()
+    }
+
+    fn new_batch(&mut self) -> io::Result<PendingBatch> {
+        let file_name = format!("batch_{}_{}.json", std::process::id(), self.next_file_id);
+        self.next_file_id += 1;
+        Ok(PendingBatch {
+            entries: Vec::new(),
+            merkle_tree: IncrementalBatchMerkleTree::new(self.batch_version),
+            data: BatchDataFile::create(self.data_dir.join(file_name))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    use aligned_sdk::types::{ProvingSystemId, VerificationDataCommitment};
+    use tokio::sync::Mutex;
+
+    use crate::merkle::BatchMerkleTree;
+    use crate::sessions::Session;
+
+    fn verification_data(i: u8) -> VerificationData {
+        VerificationData {
+            proving_system: ProvingSystemId::GnarkPlonkBn254,
+            proof: vec![i; 100],
+            pub_input: Some(vec![i; 4]),
+            verification_key: None,
+            vm_program_code: None,
+            proof_generator_addr: Address::repeat_byte(i),
+            tag: None,
+            metadata: Some(vec![i]),
+            schema_version: VerificationData::SCHEMA_VERSION,
+        }
+    }
+
+    fn batch_queue(name: &str, max_batch_size: usize) -> BatchQueue {
+        let data_dir = std::env::temp_dir().join(format!(
+            "aligned_batch_queue_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&data_dir);
+        fs::create_dir_all(&data_dir).unwrap();
+        BatchQueue::new(BatchVersion::V1, max_batch_size, data_dir)
+    }
+
+    fn push(batch_queue: &mut BatchQueue, verification_data: VerificationData) {
+        let responder = Responder::Session(Arc::new(Mutex::new(Session::default())));
+        batch_queue
+            .push(verification_data, responder, Address::zero(), None)
+            .unwrap();
+    }
+
+    /// Returns the batch data as it was uploaded before the queue kept it in files.
+    fn batch_data(batch: &[VerificationData]) -> Vec<u8> {
+        let batch: Vec<VerificationData> = batch
+            .iter()
+            .cloned()
+            .map(|verification_data| VerificationData {
+                metadata: None,
+                ..verification_data
+            })
+            .collect();
+        serde_json::to_vec(&batch).unwrap()
+    }
+
+    #[test]
+    fn test_batch_data_and_tree_match_the_proofs() {
+        let mut batch_queue = batch_queue("match", usize::MAX);
+        let batch: Vec<VerificationData> = (0..5).map(verification_data).collect();
+        for verification_data in batch.iter().cloned() {
+            push(&mut batch_queue, verification_data);
+        }
+        assert_eq!(batch_queue.len(), 5);
+
+        let commitments: Vec<VerificationDataCommitment> =
+            batch.iter().map(VerificationDataCommitment::from).collect();
+        let mut pending_batch = batch_queue.pop_front().unwrap();
+        assert!(batch_queue.pop_front().is_none());
+        assert_eq!(pending_batch.entries[3].3, Some(vec![3]));
+
+        let path = pending_batch.data.finish().unwrap().to_path_buf();
+        assert_eq!(fs::read(&path).unwrap(), batch_data(&batch));
+
+        let tree = pending_batch.merkle_tree.finalize().unwrap();
+        assert_eq!(
+            tree.root,
+            BatchMerkleTree::build(BatchVersion::V1, &commitments)
+                .unwrap()
+                .root
+        );
+
+        // The data file is removed with its batch
+        drop(pending_batch.data);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_batches_are_split_at_the_max_batch_size() {
+        let entry_len = serde_json::to_vec(&VerificationData {
+            metadata: None,
+            ..verification_data(0)
+        })
+        .unwrap()
+        .len();
+        // Room for two entries, their separator and the brackets of the array
+        let mut batch_queue = batch_queue("split", 2 * entry_len + 3);
+        for i in 0..5 {
+            push(&mut batch_queue, verification_data(i));
+        }
+        assert_eq!(batch_queue.len(), 5);
+
+        let lens: Vec<usize> = std::iter::from_fn(|| batch_queue.pop_front())
+            .map(|mut batch| {
+                let path = batch.data.finish().unwrap();
+                assert!(fs::metadata(path).unwrap().len() as usize <= 2 * entry_len + 3);
+                batch.entries.len()
+            })
+            .collect();
+        assert_eq!(lens, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn test_a_proof_larger_than_the_max_batch_size_is_batched_alone() {
+        let mut batch_queue = batch_queue("large", 10);
+        push(&mut batch_queue, verification_data(0));
+        push(&mut batch_queue, verification_data(1));
+
+        assert_eq!(batch_queue.pop_front().unwrap().entries.len(), 1);
+        assert_eq!(batch_queue.pop_front().unwrap().entries.len(), 1);
+    }
+}
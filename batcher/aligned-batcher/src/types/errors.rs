@@ -8,6 +8,7 @@ pub enum BatcherError {
     BatchVerifiedEventStreamError(String),
     EthereumSubscriptionError(String),
     SignatureError(SignatureError),
+    EmptyBatch,
 }
 
 impl From<tungstenite::Error> for BatcherError {
@@ -37,6 +38,9 @@ impl fmt::Debug for BatcherError {
             BatcherError::SignatureError(e) => {
                 write!(f, "Message signature verification error: {}", e)
             }
+            BatcherError::EmptyBatch => {
+                write!(f, "Tried to finalize an empty batch")
+            }
         }
     }
 }
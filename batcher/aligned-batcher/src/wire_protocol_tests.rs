@@ -0,0 +1,444 @@
+//! Conformance suite of the wire protocol between the SDK and the batcher. Every message type is
+//! serialized with fixed inputs and compared against the golden bytes committed in
+//! `test_files/wire_protocol`, so changes that break clients already deployed are caught at
+//! review time.
+//! When a change to the protocol is intended, bump the protocol version and regenerate the
+//! golden files with `UPDATE_GOLDEN_FILES=1 cargo test wire_protocol`.
+
+use std::path::PathBuf;
+
+use aligned_sdk::chunks::MessageChunk;
+use aligned_sdk::encryption::EncryptionKey;
+use aligned_sdk::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+use aligned_sdk::types::{
+    BatchInclusionData, BatchVersion, BatchedClientMessage, ClientMessage, ErrorResponse,
+    GetSubmissionStatus, InclusionQuery, InclusionQueryResponse, InclusionStatus, Rejection,
+    RejectionCode, SessionHandshake, SponsoredClientMessage, SubmissionStatus,
+    SubmissionStatusResponse, VerificationCommitmentBatch, VerificationDataCommitment,
+};
+use aligned_sdk::wire::{WireFormat, WireMessage};
+use ethers::types::H256;
+use lambdaworks_crypto::merkle_tree::merkle::MerkleTree;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::merkle::BatchMerkleTree;
+use crate::{
+    batch_inclusion_data_message, error_response_message, inclusion_query_response_message,
+    protocol_handshake_message, protocol_version_message, rejection_message,
+    submission_status_response_message,
+};
+
+fn assert_matches_golden_file(file_name: &str, bytes: &[u8]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test_files/wire_protocol")
+        .join(file_name);
+
+    if std::env::var("UPDATE_GOLDEN_FILES").is_ok() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read(&path)
+        .unwrap_or_else(|e| panic!("Could not read golden file {:?}: {}", path, e));
+    assert!(
+        golden == bytes,
+        "Wire format of {} changed, which breaks deployed clients. If this is intended, bump the \
+         protocol version and regenerate the golden files with UPDATE_GOLDEN_FILES=1",
+        file_name
+    );
+}
+
+fn message_bytes(message: Message) -> Vec<u8> {
+    match message {
+        // Close frames are sent as the close code followed by the reason
+        Message::Close(Some(frame)) => {
+            let mut bytes = u16::from(frame.code).to_be_bytes().to_vec();
+            bytes.extend_from_slice(frame.reason.as_bytes());
+            bytes
+        }
+        message => message.into_data(),
+    }
+}
+
+async fn client_message() -> ClientMessage {
+    let verification_data = random_verification_data(&mut seeded_rng(0));
+    ClientMessage::new(verification_data, &deterministic_wallet(0))
+        .await
+        .unwrap()
+}
+
+async fn identified_client_message() -> ClientMessage {
+    ClientMessage {
+        submission_id: Some(7),
+        ..client_message().await
+    }
+}
+
+async fn batched_client_message() -> BatchedClientMessage {
+    let mut rng = seeded_rng(1);
+    let verification_data = (0..3).map(|_| random_verification_data(&mut rng)).collect();
+    BatchedClientMessage::new(verification_data, &deterministic_wallet(0))
+        .await
+        .unwrap()
+}
+
+async fn sponsored_client_message() -> SponsoredClientMessage {
+    SponsoredClientMessage::new(client_message().await, &deterministic_wallet(1))
+        .await
+        .unwrap()
+}
+
+fn batch_commitments() -> Vec<VerificationDataCommitment> {
+    let mut rng = seeded_rng(2);
+    (0..4)
+        .map(|_| random_verification_data(&mut rng).into())
+        .collect()
+}
+
+fn inclusion_query() -> InclusionQuery {
+    InclusionQuery {
+        commitments: batch_commitments()[..2].to_vec(),
+    }
+}
+
+fn get_submission_status() -> GetSubmissionStatus {
+    GetSubmissionStatus {
+        verification_data_commitment: batch_commitments().remove(1),
+    }
+}
+
+fn batch_inclusion_data() -> BatchInclusionData {
+    let batch_merkle_tree: MerkleTree<VerificationCommitmentBatch> =
+        MerkleTree::build(&batch_commitments());
+
+    BatchInclusionData::new(2, &batch_merkle_tree)
+}
+
+#[test]
+fn test_protocol_version_message() {
+    assert_matches_golden_file(
+        "protocol_version.bin",
+        &message_bytes(protocol_version_message()),
+    );
+}
+
+#[test]
+fn test_protocol_handshake_message() {
+    assert_matches_golden_file(
+        "protocol_handshake.json",
+        &message_bytes(protocol_handshake_message(None, None)),
+    );
+}
+
+#[test]
+fn test_encrypted_protocol_handshake_message() {
+    let encryption_key = EncryptionKey::from_bytes([7; 32]);
+    assert_matches_golden_file(
+        "encrypted_protocol_handshake.json",
+        &message_bytes(protocol_handshake_message(Some(&encryption_key), None)),
+    );
+}
+
+#[test]
+fn test_session_protocol_handshake_message() {
+    let session = SessionHandshake {
+        token: "5e55105e".to_string(),
+        accepted_proofs: 3,
+    };
+    assert_matches_golden_file(
+        "session_protocol_handshake.json",
+        &message_bytes(protocol_handshake_message(None, Some(&session))),
+    );
+}
+
+#[tokio::test]
+async fn test_client_message() {
+    let msg = client_message().await;
+    let serialized = serde_json::to_vec(&msg).unwrap();
+    assert_matches_golden_file("client_message.json", &serialized);
+
+    // The batcher must also keep accepting messages as deployed clients send them
+    let deserialized: ClientMessage = serde_json::from_slice(&serialized).unwrap();
+    assert_eq!(
+        deserialized.verify_signature().unwrap(),
+        msg.verify_signature().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_identified_client_message() {
+    let serialized = serde_json::to_vec(&identified_client_message().await).unwrap();
+    assert_matches_golden_file("identified_client_message.json", &serialized);
+
+    let deserialized: ClientMessage = serde_json::from_slice(&serialized).unwrap();
+    assert_eq!(deserialized.submission_id, Some(7));
+    // The submission id is not signed
+    assert_eq!(
+        deserialized.verify_signature().unwrap(),
+        client_message().await.verify_signature().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_batched_client_message() {
+    let msg = batched_client_message().await;
+    let serialized = serde_json::to_vec(&msg).unwrap();
+    assert_matches_golden_file("batched_client_message.json", &serialized);
+
+    let deserialized: BatchedClientMessage = serde_json::from_slice(&serialized).unwrap();
+    assert!(deserialized.verify_entries());
+    assert_eq!(
+        deserialized.verify_signature().unwrap(),
+        msg.verify_signature().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_sponsored_client_message() {
+    let msg = sponsored_client_message().await;
+    let serialized = serde_json::to_vec(&msg).unwrap();
+    assert_matches_golden_file("sponsored_client_message.json", &serialized);
+
+    let deserialized: SponsoredClientMessage = serde_json::from_slice(&serialized).unwrap();
+    assert_eq!(
+        deserialized.verify_signatures().unwrap(),
+        msg.verify_signatures().unwrap()
+    );
+    // Sponsored messages can't be mistaken for the messages of other types
+    assert!(serde_json::from_slice::<ClientMessage>(&serialized).is_err());
+    assert!(serde_json::from_slice::<BatchedClientMessage>(&serialized).is_err());
+}
+
+#[tokio::test]
+async fn test_binary_client_messages() {
+    let messages = [
+        (
+            "client_message.bin",
+            WireMessage::Client(client_message().await),
+        ),
+        (
+            "batched_client_message.bin",
+            WireMessage::Batched(batched_client_message().await),
+        ),
+        (
+            "sponsored_client_message.bin",
+            WireMessage::Sponsored(sponsored_client_message().await),
+        ),
+        (
+            "identified_client_message.bin",
+            WireMessage::Client(identified_client_message().await),
+        ),
+        (
+            "inclusion_query.bin",
+            WireMessage::InclusionQuery(inclusion_query()),
+        ),
+        (
+            "get_submission_status.bin",
+            WireMessage::GetSubmissionStatus(get_submission_status()),
+        ),
+    ];
+
+    for (file_name, msg) in messages {
+        let serialized = msg.encode(WireFormat::Binary).unwrap();
+        assert_matches_golden_file(file_name, &serialized);
+
+        // A binary message holds the same message as its JSON
+        let deserialized = WireMessage::decode(WireFormat::Binary, &serialized).unwrap();
+        assert_eq!(deserialized.to_json().unwrap(), msg.to_json().unwrap());
+    }
+}
+
+#[test]
+fn test_inclusion_query() {
+    let serialized = WireMessage::InclusionQuery(inclusion_query())
+        .encode(WireFormat::Json)
+        .unwrap();
+    assert_matches_golden_file("inclusion_query.json", &serialized);
+
+    // Inclusion queries can't be mistaken for the messages of other types
+    assert!(serde_json::from_slice::<ClientMessage>(&serialized).is_err());
+    assert!(serde_json::from_slice::<BatchedClientMessage>(&serialized).is_err());
+    assert!(serde_json::from_slice::<SponsoredClientMessage>(&serialized).is_err());
+    assert!(matches!(
+        WireMessage::decode(WireFormat::Json, &serialized),
+        Ok(WireMessage::InclusionQuery(query)) if query.commitments.len() == 2
+    ));
+}
+
+#[test]
+fn test_inclusion_query_response_message() {
+    let response = InclusionQueryResponse {
+        statuses: vec![
+            InclusionStatus::Included(batch_inclusion_data()),
+            InclusionStatus::Pending,
+            InclusionStatus::Unknown,
+        ],
+    };
+    let bytes = message_bytes(inclusion_query_response_message(&response));
+    assert_matches_golden_file("inclusion_query_response.json", &bytes);
+
+    let deserialized: InclusionQueryResponse = serde_json::from_slice(&bytes).unwrap();
+    assert!(matches!(
+        deserialized.statuses.as_slice(),
+        [
+            InclusionStatus::Included(batch_inclusion_data),
+            InclusionStatus::Pending,
+            InclusionStatus::Unknown
+        ] if batch_inclusion_data.index_in_batch == 2
+    ));
+}
+
+#[test]
+fn test_get_submission_status() {
+    let serialized = WireMessage::GetSubmissionStatus(get_submission_status())
+        .encode(WireFormat::Json)
+        .unwrap();
+    assert_matches_golden_file("get_submission_status.json", &serialized);
+
+    // Submission status queries can't be mistaken for the messages of other types
+    assert!(serde_json::from_slice::<ClientMessage>(&serialized).is_err());
+    assert!(serde_json::from_slice::<BatchedClientMessage>(&serialized).is_err());
+    assert!(serde_json::from_slice::<SponsoredClientMessage>(&serialized).is_err());
+    assert!(serde_json::from_slice::<InclusionQuery>(&serialized).is_err());
+    assert!(matches!(
+        WireMessage::decode(WireFormat::Json, &serialized),
+        Ok(WireMessage::GetSubmissionStatus(_))
+    ));
+}
+
+#[test]
+fn test_submission_status_response_message() {
+    let batch_inclusion_data = batch_inclusion_data();
+    let statuses = [
+        ("pending", SubmissionStatus::Pending),
+        (
+            "batched",
+            SubmissionStatus::Batched {
+                batch_merkle_root: batch_inclusion_data.batch_merkle_root,
+                index_in_batch: batch_inclusion_data.index_in_batch,
+            },
+        ),
+        (
+            "submitted",
+            SubmissionStatus::Submitted {
+                batch_merkle_root: batch_inclusion_data.batch_merkle_root,
+                index_in_batch: batch_inclusion_data.index_in_batch,
+                tx_hash: H256::repeat_byte(0xab),
+            },
+        ),
+        ("unknown", SubmissionStatus::Unknown),
+    ];
+
+    for (name, status) in statuses {
+        let response = SubmissionStatusResponse { status };
+        let bytes = message_bytes(submission_status_response_message(&response));
+        assert_matches_golden_file(&format!("submission_status_response_{}.json", name), &bytes);
+
+        let deserialized: SubmissionStatusResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(deserialized.status, response.status);
+    }
+}
+
+#[test]
+fn test_message_chunk() {
+    let chunk = MessageChunk {
+        submission_id: 1,
+        chunk_index: 2,
+        total_size: 3,
+        data: b"chunk".to_vec(),
+    };
+    let bytes = chunk.to_bytes();
+    assert_matches_golden_file("message_chunk.bin", &bytes);
+    assert_eq!(MessageChunk::from_bytes(&bytes), Some(chunk));
+}
+
+#[test]
+fn test_batch_inclusion_data_message() {
+    assert_matches_golden_file(
+        "batch_inclusion_data.json",
+        &message_bytes(batch_inclusion_data_message(&batch_inclusion_data())),
+    );
+}
+
+#[test]
+fn test_identified_batch_inclusion_data_message() {
+    let batch_inclusion_data = BatchInclusionData {
+        submission_id: Some(7),
+        ..batch_inclusion_data()
+    };
+    assert_matches_golden_file(
+        "identified_batch_inclusion_data.json",
+        &message_bytes(batch_inclusion_data_message(&batch_inclusion_data)),
+    );
+}
+
+#[test]
+fn test_batched_merkle_tree_inclusion_data_messages() {
+    // V1 batches must be sent as before batches were versioned
+    let v1_tree = BatchMerkleTree::build(BatchVersion::V1, &batch_commitments()).unwrap();
+    assert_matches_golden_file(
+        "batch_inclusion_data.json",
+        &message_bytes(batch_inclusion_data_message(
+            &v1_tree.batch_inclusion_data(2).unwrap(),
+        )),
+    );
+
+    let v2_tree = BatchMerkleTree::build(BatchVersion::V2, &batch_commitments()).unwrap();
+    assert_matches_golden_file(
+        "batch_inclusion_data_v2.json",
+        &message_bytes(batch_inclusion_data_message(
+            &v2_tree.batch_inclusion_data(2).unwrap(),
+        )),
+    );
+}
+
+#[test]
+fn test_rejection_messages() {
+    let rejection_codes = [
+        ("invalid_signature", RejectionCode::InvalidSignature),
+        ("insufficient_balance", RejectionCode::InsufficientBalance),
+        ("proof_too_large", RejectionCode::ProofTooLarge),
+        ("invalid_proof", RejectionCode::InvalidProof),
+        ("invalid_message", RejectionCode::InvalidMessage),
+        ("replayed_message", RejectionCode::ReplayedMessage),
+    ];
+
+    for (name, rejection_code) in rejection_codes {
+        assert_matches_golden_file(
+            &format!("rejection_{}.bin", name),
+            &message_bytes(rejection_message(rejection_code, "Rejection reason")),
+        );
+    }
+}
+
+#[test]
+fn test_error_response_messages() {
+    let error_responses = [
+        (
+            "error_response_proof_too_large.json",
+            ErrorResponse {
+                rejection: Rejection::ProofTooLarge {
+                    proof_size: 2048,
+                    max_proof_size: 1024,
+                },
+                submission_id: Some(3),
+            },
+        ),
+        (
+            "error_response_invalid_signature.json",
+            ErrorResponse {
+                rejection: Rejection::InvalidSignature,
+                submission_id: None,
+            },
+        ),
+    ];
+
+    for (file_name, error_response) in error_responses {
+        let bytes = message_bytes(error_response_message(&error_response));
+        assert_matches_golden_file(file_name, &bytes);
+
+        let deserialized: ErrorResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(deserialized, error_response);
+    }
+}
@@ -12,7 +12,11 @@ pub(crate) fn verify(verification_data: &VerificationData) -> bool {
     match verification_data.proving_system {
         ProvingSystemId::SP1 => {
             if let Some(elf) = &verification_data.vm_program_code {
-                return verify_sp1_proof(verification_data.proof.as_slice(), elf.as_slice());
+                return verify_sp1_proof(
+                    verification_data.proof.as_slice(),
+                    elf.as_slice(),
+                    verification_data.pub_input.as_deref(),
+                );
             }
             warn!("Trying to verify SP1 proof but ELF was not provided. Returning false");
             false
@@ -50,8 +54,13 @@ pub(crate) fn verify(verification_data: &VerificationData) -> bool {
                 &verification_data.vm_program_code,
                 &verification_data.pub_input,
             ) {
-                let mut image_id = [0u8; 32];
-                image_id.copy_from_slice(image_id_slice.as_slice());
+                let Ok(image_id) = <[u8; 32]>::try_from(image_id_slice.as_slice()) else {
+                    warn!(
+                        "Risc0 image id must be 32 bytes, got {}. Returning false",
+                        image_id_slice.len()
+                    );
+                    return false;
+                };
                 return verify_risc_zero_proof(
                     verification_data.proof.as_slice(),
                     &image_id,
@@ -85,3 +94,26 @@ pub(crate) fn verify(verification_data: &VerificationData) -> bool {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::Address;
+
+    #[test]
+    fn test_risc0_proof_with_invalid_image_id_is_rejected() {
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Risc0,
+            proof: vec![1, 2, 3],
+            pub_input: Some(vec![4, 5, 6]),
+            verification_key: None,
+            vm_program_code: Some(vec![0; 31]),
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+
+        assert!(!verify(&verification_data));
+    }
+}
@@ -0,0 +1,74 @@
+//! Time to compute the commitments of a batch and build its merkle tree, the work that delays
+//! the responses to every user of a batch when it's sent. Compare the sequential and parallel
+//! paths with:
+//!
+//! ```bash
+//! cargo bench -p aligned-sdk --bench batch_merkle_tree
+//! cargo bench -p aligned-sdk --bench batch_merkle_tree --features parallel
+//! ```
+
+use std::time::{Duration, Instant};
+
+use aligned_sdk::merkle::{batch_commitments, BatchMerkleTree};
+use aligned_sdk::types::{
+    BatchVersion, ProvingSystemId, VerificationData, VerificationDataCommitment,
+};
+use ethers::types::Address;
+
+const RUNS: usize = 5;
+
+/// Returns the median time of the runs of the function.
+fn median_time<T>(mut f: impl FnMut() -> T) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    let parallel = if cfg!(feature = "parallel") {
+        "parallel"
+    } else {
+        "sequential"
+    };
+
+    // 1024 proofs of 256 KiB, 256 MiB of proofs in total
+    let batch_data: Vec<VerificationData> = (0..1024u32)
+        .map(|i| VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: i.to_be_bytes().repeat(64 * 1024),
+            pub_input: Some(vec![1; 64]),
+            verification_key: None,
+            vm_program_code: Some(vec![2; 1024]),
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        })
+        .collect();
+    println!(
+        "{} commitments of 1024 proofs of 256 KiB: {:?}",
+        parallel,
+        median_time(|| batch_commitments(&batch_data))
+    );
+
+    let commitments: Vec<VerificationDataCommitment> = (0..65536u32)
+        .map(|i| VerificationDataCommitment {
+            proof_commitment: [i.to_be_bytes(); 8].concat().try_into().unwrap(),
+            ..Default::default()
+        })
+        .collect();
+    for batch_version in [BatchVersion::V1, BatchVersion::V2] {
+        println!(
+            "{} {:?} tree of 65536 commitments: {:?}",
+            parallel,
+            batch_version,
+            median_time(|| BatchMerkleTree::build(batch_version, &commitments))
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    env,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const UNKNOWN: &str = "unknown";
+
+fn main() {
+    println!("cargo:rustc-env=ALIGNED_GIT_HASH={}", git_hash());
+    println!("cargo:rustc-env=ALIGNED_BUILD_TIMESTAMP={}", build_timestamp());
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // Rebuilds when a commit is checked out or created, as the files of the SDK may not change
+    for git_path in ["HEAD", "refs/heads"] {
+        if let Some(path) = git(&["rev-parse", "--git-path", git_path]) {
+            if Path::new(&path).exists() {
+                println!("cargo:rerun-if-changed={}", path);
+            }
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().to_string())
+}
+
+fn git_hash() -> String {
+    git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| UNKNOWN.to_string())
+}
+
+/// Build time in RFC 3339 format, taken from `SOURCE_DATE_EPOCH` if set so that builds are
+/// reproducible.
+fn build_timestamp() -> String {
+    let unix_secs = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(source_date_epoch) => match source_date_epoch.parse::<u64>() {
+            Ok(unix_secs) => unix_secs,
+            Err(_) => return UNKNOWN.to_string(),
+        },
+        Err(_) => match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs(),
+            Err(_) => return UNKNOWN.to_string(),
+        },
+    };
+
+    let (year, month, day) = civil_from_days(unix_secs / 86400);
+    let secs_of_day = unix_secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Converts days since the unix epoch to a (year, month, day) date, as in
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
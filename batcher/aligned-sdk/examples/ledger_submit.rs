@@ -0,0 +1,50 @@
+//! Submits an SP1 proof to the batcher signed with a Ledger, which asks for a confirmation on
+//! the device. The Ethereum app has to be open on the Ledger.
+//!
+//! ```sh
+//! cargo run -p aligned-sdk --features ledger --example ledger_submit -- \
+//!     <proof_file> <elf_file> [batcher_addr]
+//! ```
+
+use aligned_sdk::sdk::submit_multiple;
+use aligned_sdk::types::{ProvingSystemId, VerificationData};
+use ethers::signers::{HDPath, Ledger, Signer};
+
+/// Chain ID of a local devnet.
+const CHAIN_ID: u64 = 31337;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let proof = std::fs::read(args.next().ok_or("Missing the proof file")?)?;
+    let vm_program_code = std::fs::read(args.next().ok_or("Missing the ELF file")?)?;
+    let batcher_addr = args
+        .next()
+        .unwrap_or_else(|| "ws://localhost:8080".to_string());
+
+    // The first account of Ledger Live
+    let ledger = Ledger::new(HDPath::LedgerLive(0), CHAIN_ID).await?;
+    let verification_data = VerificationData {
+        proving_system: ProvingSystemId::SP1,
+        proof,
+        pub_input: None,
+        verification_key: None,
+        vm_program_code: Some(vm_program_code),
+        proof_generator_addr: ledger.address(),
+        tag: None,
+        metadata: None,
+        schema_version: VerificationData::SCHEMA_VERSION,
+    };
+
+    match submit_multiple(&batcher_addr, &[verification_data], ledger)
+        .await
+        .map_err(|e| format!("{:?}", e))?
+    {
+        Some(aligned_verification_data) => println!(
+            "Proof submitted to batch 0x{}",
+            hex::encode(aligned_verification_data[0].batch_merkle_root)
+        ),
+        None => eprintln!("The batcher didn't respond to the proof"),
+    }
+    Ok(())
+}
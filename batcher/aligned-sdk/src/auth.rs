@@ -0,0 +1,87 @@
+//! Access tokens with which private batcher deployments restrict who can connect.
+//!
+//! Batchers configured with a list of allowed tokens refuse the websocket handshake of clients
+//! that don't send one of them as a bearer token in the `Authorization` header, answering it
+//! with `401 Unauthorized`. Clients set their token in `TlsConfig::auth_token`, with the rest of
+//! the options of the connection. The token is sent in the clear to `ws://` addresses, so it
+//! should only be sent to `wss://` ones outside of local networks.
+
+use tokio_tungstenite::tungstenite::handshake::server::ErrorResponse;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+
+use crate::errors::SubmitError;
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// Returns the value of the `Authorization` header sending the token.
+/// # Errors
+/// * `Unauthorized` if the token has characters that can't be sent in a header.
+pub(crate) fn bearer_header(token: &str) -> Result<HeaderValue, SubmitError> {
+    let mut header =
+        HeaderValue::from_str(&format!("{}{}", BEARER_PREFIX, token)).map_err(|_| {
+            SubmitError::Unauthorized("Access token can't be sent in a header".to_string())
+        })?;
+    header.set_sensitive(true);
+    Ok(header)
+}
+
+/// Returns whether the `Authorization` header of a handshake holds one of the allowed tokens.
+/// Tokens are compared in constant time, so that the time taken doesn't tell how much of a
+/// token was guessed.
+pub fn is_authorized(authorization: Option<&HeaderValue>, allowed_tokens: &[String]) -> bool {
+    let Some(token) = authorization
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix(BEARER_PREFIX))
+    else {
+        return false;
+    };
+    allowed_tokens
+        .iter()
+        .fold(false, |authorized, allowed_token| {
+            authorized | constant_time_eq(token.as_bytes(), allowed_token.as_bytes())
+        })
+}
+
+/// Returns the response refusing the handshake of a client without an allowed token.
+pub fn unauthorized_response() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("Missing or invalid access token".to_string()));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_only_allowed_tokens_are_authorized() {
+        let allowed_tokens = vec!["first token".to_string(), "second token".to_string()];
+
+        for token in &allowed_tokens {
+            let header = bearer_header(token).unwrap();
+            assert!(header.is_sensitive());
+            assert!(is_authorized(Some(&header), &allowed_tokens));
+        }
+
+        let other_token = bearer_header("other token").unwrap();
+        assert!(!is_authorized(Some(&other_token), &allowed_tokens));
+        let prefix_of_token = bearer_header("first").unwrap();
+        assert!(!is_authorized(Some(&prefix_of_token), &allowed_tokens));
+        assert!(!is_authorized(None, &allowed_tokens));
+        // Tokens must be sent as bearer tokens
+        let basic = HeaderValue::from_static("Basic first token");
+        assert!(!is_authorized(Some(&basic), &allowed_tokens));
+    }
+
+    #[test]
+    fn test_tokens_that_are_not_header_values_are_refused() {
+        assert!(matches!(
+            bearer_header("token\nwith a new line"),
+            Err(SubmitError::Unauthorized(_))
+        ));
+    }
+}
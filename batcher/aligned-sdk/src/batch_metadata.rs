@@ -0,0 +1,177 @@
+//! Batch metadata of the aligned verification data of a proof: the blocks and transactions in
+//! which its batch was created and verified, taken from the `NewBatch` and `BatchVerified` events
+//! of the service manager. Files saved by previous releases don't have it, so it's looked up when
+//! they are loaded, giving downstream tooling the same data for old and new files.
+
+use std::path::Path;
+
+use ethers::prelude::LogMeta;
+
+use crate::errors::{AlignedError, SubmitError, VerificationError};
+use crate::inclusion_file::{self, FileFormat};
+use crate::rpc::AlignedRpcClient;
+use crate::types::{AlignedVerificationData, BatchMetadata, Chain, EventLocation};
+
+/// Returns where a batch was created and, if it was, verified on-chain.
+/// # Arguments
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// * `chain` - The chain on which the batch was created.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Errors
+/// * `EthError` if there is an error calling the service manager or querying its events.
+/// * `InvalidAddress` if the address of the service manager is not valid.
+/// * `BatchNotFound` if no task was created for the batch, or its `NewBatch` event is not found.
+pub async fn get_batch_metadata(
+    batch_merkle_root: [u8; 32],
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<BatchMetadata, VerificationError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+    let (task_created_block, responded) = rpc_client.batch_state(batch_merkle_root, &chain).await?;
+    if task_created_block == 0 {
+        return Err(VerificationError::BatchNotFound(batch_merkle_root));
+    }
+
+    let created = rpc_client
+        .batch_created_events(batch_merkle_root, task_created_block, &chain)
+        .await?
+        .into_iter()
+        .next()
+        .map(|(_, log_meta)| event_location(&log_meta))
+        .ok_or(VerificationError::BatchNotFound(batch_merkle_root))?;
+
+    let verified = if responded {
+        rpc_client
+            .batch_verified_events(batch_merkle_root, task_created_block, &chain)
+            .await?
+            .into_iter()
+            .next()
+            .map(|(_, log_meta)| event_location(&log_meta))
+    } else {
+        None
+    };
+
+    Ok(BatchMetadata { created, verified })
+}
+
+/// Sets the batch metadata of an aligned verification data that doesn't have it, or whose batch
+/// was not verified when it was saved. Data of verified batches is left as is without querying
+/// the node.
+/// # Returns
+/// * Whether the batch metadata changed.
+/// # Errors
+/// * The errors of `get_batch_metadata`, leaving the aligned verification data unchanged.
+pub async fn enrich(
+    aligned_verification_data: &mut AlignedVerificationData,
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<bool, VerificationError> {
+    if matches!(
+        aligned_verification_data.batch_metadata,
+        Some(BatchMetadata {
+            verified: Some(_),
+            ..
+        })
+    ) {
+        return Ok(false);
+    }
+
+    let batch_metadata = get_batch_metadata(
+        aligned_verification_data.batch_merkle_root,
+        chain,
+        eth_rpc_url,
+    )
+    .await?;
+    let changed = aligned_verification_data.batch_metadata != Some(batch_metadata);
+    aligned_verification_data.batch_metadata = Some(batch_metadata);
+    Ok(changed)
+}
+
+/// Reads an aligned verification data from a file of any format and enriches it with its batch
+/// metadata.
+/// # Arguments
+/// * `path` - The path of the file.
+/// * `chain` - The chain on which the batch was created.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// * `rewrite` - Whether to save the batch metadata into the file, in its format, if it changed.
+/// # Errors
+/// * The errors of `inclusion_file::read`, and of `inclusion_file::write` when rewriting.
+/// * The errors of `get_batch_metadata`.
+pub async fn read_enriched(
+    path: &Path,
+    chain: Chain,
+    eth_rpc_url: &str,
+    rewrite: bool,
+) -> Result<AlignedVerificationData, AlignedError> {
+    let bytes = std::fs::read(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))?;
+    let mut aligned_verification_data = inclusion_file::from_bytes(&bytes)?;
+
+    let changed = enrich(&mut aligned_verification_data, chain, eth_rpc_url).await?;
+    if rewrite && changed {
+        inclusion_file::write(path, &aligned_verification_data, FileFormat::detect(&bytes))?;
+    }
+    Ok(aligned_verification_data)
+}
+
+fn event_location(log_meta: &LogMeta) -> EventLocation {
+    EventLocation {
+        block_number: log_meta.block_number.as_u64(),
+        tx_hash: log_meta.transaction_hash,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn aligned_verification_data() -> AlignedVerificationData {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_files/aligned_verification_data/unversioned_batch.json");
+        inclusion_file::read(&path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_verified_batches_are_not_looked_up() {
+        let mut aligned_verification_data = aligned_verification_data();
+        let batch_metadata = BatchMetadata {
+            created: EventLocation {
+                block_number: 1,
+                tx_hash: [1; 32].into(),
+            },
+            verified: Some(EventLocation {
+                block_number: 2,
+                tx_hash: [2; 32].into(),
+            }),
+        };
+        aligned_verification_data.batch_metadata = Some(batch_metadata);
+
+        let changed = enrich(
+            &mut aligned_verification_data,
+            Chain::Devnet,
+            "http://127.0.0.1:1",
+        )
+        .await
+        .unwrap();
+
+        assert!(!changed);
+        assert_eq!(
+            aligned_verification_data.batch_metadata,
+            Some(batch_metadata)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_data_is_unchanged_when_the_node_cant_be_reached() {
+        let mut aligned_verification_data = aligned_verification_data();
+
+        let result = enrich(
+            &mut aligned_verification_data,
+            Chain::Devnet,
+            "http://127.0.0.1:1",
+        )
+        .await;
+
+        assert!(matches!(result, Err(VerificationError::EthError(_))));
+        assert_eq!(aligned_verification_data.batch_metadata, None);
+    }
+}
@@ -0,0 +1,92 @@
+//! Status of a batch in the Aligned service manager, from the creation of its verification task
+//! to its attestation by the operators.
+
+use log::warn;
+
+use crate::errors::VerificationError;
+use crate::quorum::{get_batch_attestation, BatchAttestation};
+use crate::rpc::AlignedRpcClient;
+use crate::types::Chain;
+
+/// What the service manager knows about a batch. A batch whose task wasn't created is either
+/// still in the batcher or unknown to Aligned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchStatus {
+    pub batch_merkle_root: [u8; 32],
+    /// Block in which the verification task of the batch was created, if it was.
+    pub task_created_block: Option<u32>,
+    pub responded: bool,
+    /// Number of proofs in the batch, or `None` if its data couldn't be downloaded.
+    pub num_proofs: Option<usize>,
+    pub batch_data_pointer: Option<String>,
+    /// Attestation with which the operators verified the batch, holding the block in which it
+    /// was verified and the stake of each quorum that signed it.
+    pub attestation: Option<BatchAttestation>,
+}
+
+/// Returns the status of a batch: whether its task was created and responded, how many proofs
+/// it holds and the attestation of the quorum that verified it.
+/// # Arguments
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// * `chain` - The chain on which Aligned is deployed.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The status of the batch. The number of proofs is left out with a warning if the data of the
+///   batch can't be downloaded.
+/// # Errors
+/// * `EthError` if there is an error creating the rpc provider, calling the contracts or
+///   querying the events of the batch.
+/// * `InvalidAddress` if the address of the contracts is not valid.
+pub async fn get_batch_status(
+    batch_merkle_root: [u8; 32],
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<BatchStatus, VerificationError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+    let (task_created_block, responded) = rpc_client.batch_state(batch_merkle_root, &chain).await?;
+
+    // Batches whose task wasn't created have no state in the service manager
+    if task_created_block == 0 {
+        return Ok(BatchStatus {
+            batch_merkle_root,
+            task_created_block: None,
+            responded: false,
+            num_proofs: None,
+            batch_data_pointer: None,
+            attestation: None,
+        });
+    }
+
+    let batch_data_pointer = rpc_client
+        .new_batch_events(task_created_block.into(), &chain)
+        .await?
+        .into_iter()
+        .find(|new_batch| new_batch.batch_merkle_root == batch_merkle_root)
+        .map(|new_batch| new_batch.batch_data_pointer);
+
+    let num_proofs = match &batch_data_pointer {
+        Some(batch_data_pointer) => match rpc_client.batch_data(batch_data_pointer).await {
+            Ok(batch_data) => Some(batch_data.len()),
+            Err(e) => {
+                warn!("Could not download the data of the batch: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let attestation = if responded {
+        get_batch_attestation(batch_merkle_root, chain, eth_rpc_url).await?
+    } else {
+        None
+    };
+
+    Ok(BatchStatus {
+        batch_merkle_root,
+        task_created_block: Some(task_created_block),
+        responded,
+        num_proofs,
+        batch_data_pointer,
+        attestation,
+    })
+}
@@ -0,0 +1,52 @@
+//! Information about the build of the SDK. The client version is sent to the batcher when
+//! connecting, so that its logs can attribute problems to specific client versions.
+
+use std::sync::OnceLock;
+
+use crate::sdk::CURRENT_PROTOCOL_VERSION;
+
+pub const SDK_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short hash of the commit the SDK was built from, or `unknown` if it wasn't built from a git
+/// checkout.
+pub const GIT_HASH: &str = env!("ALIGNED_GIT_HASH");
+/// Time at which the SDK was built, in RFC 3339 format. Taken from `SOURCE_DATE_EPOCH` if set.
+pub const BUILD_TIMESTAMP: &str = env!("ALIGNED_BUILD_TIMESTAMP");
+
+/// Returns the version sent to the batcher in the handshake, such as
+/// `aligned-sdk/0.1.0 (git 3f2a1bc, built 2024-07-01T12:00:00Z, protocol version 0)`.
+pub fn client_version() -> &'static str {
+    static CLIENT_VERSION: OnceLock<String> = OnceLock::new();
+    CLIENT_VERSION.get_or_init(|| format!("aligned-sdk/{} ({})", SDK_VERSION, build_details()))
+}
+
+/// Returns the version printed by the `--version` flag of binaries built with the SDK.
+/// # Arguments
+/// * `binary_version` - The version of the binary, usually `env!("CARGO_PKG_VERSION")`.
+pub fn long_version(binary_version: &str) -> String {
+    format!("{} ({})", binary_version, build_details())
+}
+
+fn build_details() -> String {
+    format!(
+        "git {}, built {}, protocol version {}",
+        GIT_HASH, BUILD_TIMESTAMP, CURRENT_PROTOCOL_VERSION
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+    #[test]
+    fn test_client_version_is_a_valid_header_value() {
+        let client_version = client_version();
+
+        assert!(client_version.starts_with(&format!("aligned-sdk/{} (git ", SDK_VERSION)));
+        assert!(client_version.ends_with(&format!(
+            "protocol version {})",
+            CURRENT_PROTOCOL_VERSION
+        )));
+        assert!(HeaderValue::from_str(client_version).is_ok());
+    }
+}
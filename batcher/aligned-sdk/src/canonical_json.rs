@@ -0,0 +1,207 @@
+use serde::ser::Error;
+use serde::Serialize;
+use serde_json::{Number, Value};
+use sha3::{Digest, Keccak256};
+
+/// Largest integer that can be represented without loss by an IEEE 754 double, which is what
+/// JavaScript clients use for every JSON number.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Serializes a value to its canonical JSON representation, following the JSON
+/// Canonicalization Scheme (RFC 8785):
+/// * No whitespace between tokens.
+/// * Object keys sorted by their UTF-16 code units.
+/// * Strings with only the mandatory escapes, using lowercase hex digits.
+/// * Numbers written as integers.
+///
+/// Only integers in the range that doubles can represent exactly are accepted, so that clients
+/// in other languages can't read a different number than the one that was committed to.
+/// # Arguments
+/// * `value` - The value to serialize.
+/// # Returns
+/// * The canonical JSON bytes.
+/// # Errors
+/// * If the value can't be serialized to JSON.
+/// * If the value contains a fractional or unsafe number.
+pub fn to_canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let mut canonical_json = Vec::new();
+    write_canonical_value(&value, &mut canonical_json)?;
+    Ok(canonical_json)
+}
+
+/// Rewrites arbitrary JSON bytes, such as the ones produced by a client in another language,
+/// into their canonical representation. See `to_canonical_json`.
+pub fn canonicalize_json(json: &[u8]) -> Result<Vec<u8>, serde_json::Error> {
+    let value: Value = serde_json::from_slice(json)?;
+    to_canonical_json(&value)
+}
+
+/// Returns the keccak256 commitment of the canonical JSON representation of a value.
+pub fn get_canonical_json_commitment<T: Serialize>(
+    value: &T,
+) -> Result<[u8; 32], serde_json::Error> {
+    let canonical_json = to_canonical_json(value)?;
+    let mut hasher = Keccak256::new();
+    hasher.update(canonical_json);
+    Ok(hasher.finalize().into())
+}
+
+fn write_canonical_value(value: &Value, out: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Null => out.extend_from_slice(b"null"),
+        Value::Bool(true) => out.extend_from_slice(b"true"),
+        Value::Bool(false) => out.extend_from_slice(b"false"),
+        Value::Number(number) => write_canonical_number(number, out)?,
+        // serde_json already escapes strings as required by the canonicalization scheme
+        Value::String(string) => serde_json::to_writer(&mut *out, string)?,
+        Value::Array(values) => {
+            out.push(b'[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical_value(value, out)?;
+            }
+            out.push(b']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_canonical_value(value, out)?;
+            }
+            out.push(b'}');
+        }
+    }
+    Ok(())
+}
+
+fn write_canonical_number(number: &Number, out: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    let integer = if let Some(n) = number.as_u64() {
+        (n <= MAX_SAFE_INTEGER).then_some(n as i128)
+    } else if let Some(n) = number.as_i64() {
+        (n.unsigned_abs() <= MAX_SAFE_INTEGER).then_some(n as i128)
+    } else {
+        number
+            .as_f64()
+            .filter(|n| n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER as f64)
+            .map(|n| n as i128)
+    };
+
+    match integer {
+        Some(integer) => {
+            out.extend_from_slice(integer.to_string().as_bytes());
+            Ok(())
+        }
+        None => Err(serde_json::Error::custom(format!(
+            "Number {} has no canonical representation",
+            number
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{ProvingSystemId, VerificationData};
+    use ethers::types::Address;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_canonical_json_ignores_key_order_and_whitespace() {
+        let a = canonicalize_json(br#"{"b": [1, 2, {"d": true, "c": null}], "a": "x"}"#).unwrap();
+        let b = canonicalize_json(br#"{"a":"x","b":[1,2,{"c":null,"d":true}]}"#).unwrap();
+
+        assert_eq!(a, b);
+        assert_eq!(a, br#"{"a":"x","b":[1,2,{"c":null,"d":true}]}"#);
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_keys_by_utf16_code_units() {
+        // Sorting example from RFC 8785, section 3.2.3
+        let json = r#"{"\u20ac":"Euro Sign","\r":"Carriage Return","\ufb33":"Hebrew Letter Dalet With Dagesh","1":"One","\ud83d\ude00":"Emoji: Grinning Face","\u0080":"Control","\u00f6":"Latin Small Letter O With Diaeresis"}"#;
+        let canonical = String::from_utf8(canonicalize_json(json.as_bytes()).unwrap()).unwrap();
+
+        let keys = [
+            "\\r",
+            "1",
+            "\u{80}",
+            "\u{f6}",
+            "\u{20ac}",
+            "\u{1f600}",
+            "\u{fb33}",
+        ];
+        let positions: Vec<usize> = keys
+            .iter()
+            .map(|key| canonical.find(&format!("\"{}\":", key)).unwrap())
+            .collect();
+
+        let mut sorted_positions = positions.clone();
+        sorted_positions.sort();
+        assert_eq!(positions, sorted_positions);
+    }
+
+    #[test]
+    fn test_canonical_json_number_formatting() {
+        assert_eq!(
+            canonicalize_json(b"[1.0, -0.0, 1e3, -7]").unwrap(),
+            b"[1,0,1000,-7]"
+        );
+        assert!(canonicalize_json(b"[1.5]").is_err());
+        assert!(canonicalize_json(b"[9007199254740992]").is_err());
+        assert_eq!(
+            canonicalize_json(b"[9007199254740991]").unwrap(),
+            b"[9007199254740991]"
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_string_escaping() {
+        assert_eq!(
+            canonicalize_json(br#"["A\u00e9\u001f\n\"\\\/"]"#).unwrap(),
+            "[\"Aé\\u001f\\n\\\"\\\\/\"]".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_commitment_matches_reordered_json() {
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            proof: vec![1, 2, 3],
+            pub_input: Some(vec![4, 5]),
+            verification_key: None,
+            vm_program_code: None,
+            proof_generator_addr: Address::from_str("0x66f9664f97F2b50F62D13eA064982f936dE76657")
+                .unwrap(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+
+        let reordered_json = br#"{
+            "vm_program_code": null,
+            "verification_key": null,
+            "pub_input": [4, 5],
+            "proving_system": "Groth16Bn254",
+            "proof_generator_addr": "0x66f9664f97f2b50f62d13ea064982f936de76657",
+            "proof": [1, 2, 3]
+        }"#;
+
+        let mut hasher = Keccak256::new();
+        hasher.update(canonicalize_json(reordered_json).unwrap());
+        let expected: [u8; 32] = hasher.finalize().into();
+
+        assert_eq!(
+            get_canonical_json_commitment(&verification_data).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,500 @@
+//! Chunked upload of client messages too large for a single websocket frame, such as the ones of
+//! large SP1 programs or STARK proofs. The serialized message is split in chunks sent in order as
+//! binary frames, and the batcher reassembles them before parsing the message. Messages that fit
+//! in a frame are still sent as a single text frame, so batchers without chunked upload keep
+//! accepting them.
+//!
+//! Each chunk frame holds the submission id as 8 bytes, the chunk index as 4 bytes and the total
+//! size of the message as 8 bytes, all big-endian, followed by the data of the chunk. The data is
+//! not encoded as JSON, which would take several times its size.
+//!
+//! The chunks of a message don't need to have the same size. The SDK adapts the size of the
+//! chunks of a connection to its throughput with `ChunkSizer`, growing them while the throughput
+//! holds and halving them when it drops, as TCP does with its congestion window.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Frames larger than this are rejected by the batcher, which uses the default limit of
+/// tungstenite.
+pub const MAX_FRAME_SIZE: usize = 16 << 20;
+
+/// Size of the data of each chunk, leaving room for the header below `MAX_FRAME_SIZE`. It's the
+/// largest chunk size reached by `ChunkSizer`.
+pub const CHUNK_SIZE: usize = 8 << 20;
+
+/// Smallest chunk size to which `ChunkSizer` shrinks the chunks.
+pub const MIN_CHUNK_SIZE: usize = 256 << 10;
+
+/// Chunk size with which connections start, and by which it grows after each chunk sent at the
+/// best throughput of the connection.
+pub const CHUNK_SIZE_STEP: usize = 1 << 20;
+
+/// A chunk sent at a throughput lower than this percentage of the best one of the connection
+/// shrinks the chunks.
+const THROUGHPUT_DROP_PERCENTAGE: f64 = 80.0;
+
+/// A chunk that takes longer than this to be sent shrinks the chunks, whatever its throughput, so
+/// that frames stay short on slow links.
+pub const TARGET_CHUNK_SEND_TIME: Duration = Duration::from_secs(2);
+
+const HEADER_SIZE: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageChunk {
+    /// Identifies the chunks of the same message.
+    pub submission_id: u64,
+    pub chunk_index: u32,
+    /// Size of the whole serialized message, in bytes.
+    pub total_size: u64,
+    pub data: Vec<u8>,
+}
+
+impl MessageChunk {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        bytes.extend_from_slice(&self.submission_id.to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_index.to_be_bytes());
+        bytes.extend_from_slice(&self.total_size.to_be_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Parses a chunk frame. Returns `None` if it's shorter than the header.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE {
+            return None;
+        }
+        let (submission_id, rest) = bytes.split_at(8);
+        let (chunk_index, rest) = rest.split_at(4);
+        let (total_size, data) = rest.split_at(8);
+
+        Some(MessageChunk {
+            submission_id: u64::from_be_bytes(submission_id.try_into().ok()?),
+            chunk_index: u32::from_be_bytes(chunk_index.try_into().ok()?),
+            total_size: u64::from_be_bytes(total_size.try_into().ok()?),
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Splits a serialized message in chunks with at most `chunk_size` bytes of data.
+pub fn split_message(submission_id: u64, message: &[u8], chunk_size: usize) -> Vec<MessageChunk> {
+    message
+        .chunks(chunk_size.max(1))
+        .enumerate()
+        .map(|(chunk_index, data)| MessageChunk {
+            submission_id,
+            chunk_index: chunk_index as u32,
+            total_size: message.len() as u64,
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// How a message is sent to the batcher.
+pub(crate) enum Payload {
+    /// In a single text frame.
+    Text(String),
+    /// In the binary frames of its chunks.
+    Chunked(Vec<u8>),
+}
+
+impl Payload {
+    /// Returns how a JSON message is sent: in a single text frame, or in chunks if it's larger
+    /// than `MAX_FRAME_SIZE`.
+    pub(crate) fn json(message: String) -> Self {
+        if message.len() <= MAX_FRAME_SIZE {
+            Payload::Text(message)
+        } else {
+            Payload::Chunked(message.into_bytes())
+        }
+    }
+}
+
+/// Splits a message in chunks as they are sent, so that the size of each chunk can be chosen
+/// after the previous one is sent.
+pub(crate) struct ChunkSplitter<'a> {
+    submission_id: u64,
+    message: &'a [u8],
+    offset: usize,
+    chunk_index: u32,
+}
+
+impl<'a> ChunkSplitter<'a> {
+    pub(crate) fn new(submission_id: u64, message: &'a [u8]) -> Self {
+        ChunkSplitter {
+            submission_id,
+            message,
+            offset: 0,
+            chunk_index: 0,
+        }
+    }
+
+    /// Returns the next chunk, with at most `chunk_size` bytes of data, or `None` once the whole
+    /// message was returned.
+    pub(crate) fn next_chunk(&mut self, chunk_size: usize) -> Option<MessageChunk> {
+        if self.offset >= self.message.len() {
+            return None;
+        }
+        let end = self
+            .offset
+            .saturating_add(chunk_size.max(1))
+            .min(self.message.len());
+        let chunk = MessageChunk {
+            submission_id: self.submission_id,
+            chunk_index: self.chunk_index,
+            total_size: self.message.len() as u64,
+            data: self.message[self.offset..end].to_vec(),
+        };
+        self.offset = end;
+        self.chunk_index += 1;
+        Some(chunk)
+    }
+}
+
+/// Chooses the size of the chunks of a connection from the time the previous ones took to be
+/// sent, since the batcher doesn't acknowledge chunks. The size grows by `CHUNK_SIZE_STEP` after
+/// each chunk sent at least at `THROUGHPUT_DROP_PERCENTAGE` of the best throughput measured, and
+/// is halved after a chunk sent at a lower throughput or in more than `TARGET_CHUNK_SEND_TIME`,
+/// staying between `MIN_CHUNK_SIZE` and `CHUNK_SIZE`.
+pub struct ChunkSizer {
+    chunk_size: usize,
+    /// Best throughput measured since the size was last halved, in bytes per second.
+    best_throughput: f64,
+}
+
+impl Default for ChunkSizer {
+    fn default() -> Self {
+        ChunkSizer {
+            chunk_size: CHUNK_SIZE_STEP,
+            best_throughput: 0.0,
+        }
+    }
+}
+
+impl ChunkSizer {
+    /// Size of the data of the next chunk.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Records that a chunk with `size` bytes of data took `elapsed` to be sent. Chunks smaller
+    /// than the current size, such as the last one of a message, are not measured, since the
+    /// overhead of each frame lowers their throughput.
+    pub fn record(&mut self, size: usize, elapsed: Duration) {
+        if size < self.chunk_size {
+            return;
+        }
+
+        let throughput = size as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        let dropped = throughput < self.best_throughput * THROUGHPUT_DROP_PERCENTAGE / 100.0;
+        if dropped || elapsed > TARGET_CHUNK_SEND_TIME {
+            self.chunk_size = (self.chunk_size / 2).max(MIN_CHUNK_SIZE);
+            self.best_throughput = throughput;
+        } else {
+            self.chunk_size = (self.chunk_size + CHUNK_SIZE_STEP).min(CHUNK_SIZE);
+            self.best_throughput = self.best_throughput.max(throughput);
+        }
+    }
+}
+
+pub enum ChunkError {
+    /// The frame is shorter than the header of a chunk, or has no data.
+    Malformed,
+    /// A chunk of another message arrived before the current one was complete.
+    UnexpectedSubmission {
+        expected: u64,
+        received: u64,
+    },
+    UnexpectedIndex {
+        expected: u32,
+        received: u32,
+    },
+    TooLarge {
+        total_size: u64,
+        max_size: usize,
+    },
+    /// The chunks hold more data than the total size of the message.
+    SizeMismatch {
+        total_size: u64,
+        received: usize,
+    },
+}
+
+impl fmt::Debug for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChunkError::Malformed => write!(f, "Malformed message chunk"),
+            ChunkError::UnexpectedSubmission { expected, received } => write!(
+                f,
+                "Chunk of submission {} received before submission {} was complete",
+                received, expected
+            ),
+            ChunkError::UnexpectedIndex { expected, received } => write!(
+                f,
+                "Expected chunk {}, received chunk {}",
+                expected, received
+            ),
+            ChunkError::TooLarge {
+                total_size,
+                max_size,
+            } => write!(
+                f,
+                "Message of {} bytes exceeds the maximum of {} bytes",
+                total_size, max_size
+            ),
+            ChunkError::SizeMismatch {
+                total_size,
+                received,
+            } => write!(
+                f,
+                "Received {} bytes of a message of {} bytes",
+                received, total_size
+            ),
+        }
+    }
+}
+
+struct PartialMessage {
+    submission_id: u64,
+    total_size: u64,
+    next_index: u32,
+    data: Vec<u8>,
+}
+
+/// Reassembles the chunked messages of a connection. The chunks of a message have to arrive in
+/// order, and before the chunks of the next message, as the SDK sends them.
+pub struct ChunkAssembler {
+    max_message_size: usize,
+    partial_message: Option<PartialMessage>,
+}
+
+impl ChunkAssembler {
+    pub fn new(max_message_size: usize) -> Self {
+        ChunkAssembler {
+            max_message_size,
+            partial_message: None,
+        }
+    }
+
+    /// Adds a chunk, returning the whole message once its last chunk is added. After an error,
+    /// the chunks received of the message are discarded.
+    pub fn push(&mut self, chunk: MessageChunk) -> Result<Option<Vec<u8>>, ChunkError> {
+        let result = self.add(chunk);
+        if result.is_err() {
+            self.partial_message = None;
+        }
+        result
+    }
+
+    fn add(&mut self, chunk: MessageChunk) -> Result<Option<Vec<u8>>, ChunkError> {
+        if chunk.data.is_empty() {
+            return Err(ChunkError::Malformed);
+        }
+
+        let partial_message = match &mut self.partial_message {
+            Some(partial_message) => {
+                if chunk.submission_id != partial_message.submission_id {
+                    return Err(ChunkError::UnexpectedSubmission {
+                        expected: partial_message.submission_id,
+                        received: chunk.submission_id,
+                    });
+                }
+                if chunk.chunk_index != partial_message.next_index {
+                    return Err(ChunkError::UnexpectedIndex {
+                        expected: partial_message.next_index,
+                        received: chunk.chunk_index,
+                    });
+                }
+                partial_message
+            }
+            None => {
+                if chunk.chunk_index != 0 {
+                    return Err(ChunkError::UnexpectedIndex {
+                        expected: 0,
+                        received: chunk.chunk_index,
+                    });
+                }
+                if chunk.total_size > self.max_message_size as u64 {
+                    return Err(ChunkError::TooLarge {
+                        total_size: chunk.total_size,
+                        max_size: self.max_message_size,
+                    });
+                }
+                // The total size is not trusted to allocate the message
+                self.partial_message.insert(PartialMessage {
+                    submission_id: chunk.submission_id,
+                    total_size: chunk.total_size,
+                    next_index: 0,
+                    data: Vec::new(),
+                })
+            }
+        };
+
+        partial_message.data.extend_from_slice(&chunk.data);
+        partial_message.next_index += 1;
+
+        let received = partial_message.data.len();
+        if received as u64 > partial_message.total_size {
+            return Err(ChunkError::SizeMismatch {
+                total_size: partial_message.total_size,
+                received,
+            });
+        }
+        if (received as u64) < partial_message.total_size {
+            return Ok(None);
+        }
+
+        Ok(self
+            .partial_message
+            .take()
+            .map(|partial_message| partial_message.data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[test]
+    fn test_split_message_is_reassembled() {
+        let message: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let chunks = split_message(7, &message, 300);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[3].data.len(), 100);
+
+        let mut chunk_assembler = ChunkAssembler::new(1000);
+        for chunk in &chunks[..3] {
+            let bytes = chunk.to_bytes();
+            assert_eq!(MessageChunk::from_bytes(&bytes).as_ref(), Some(chunk));
+            assert!(chunk_assembler.push(chunk.clone()).unwrap().is_none());
+        }
+        assert_eq!(
+            chunk_assembler.push(chunks[3].clone()).unwrap(),
+            Some(message.clone())
+        );
+
+        // The next message starts from its first chunk
+        let chunks = split_message(8, &message, 600);
+        assert!(chunk_assembler.push(chunks[0].clone()).unwrap().is_none());
+        assert_eq!(
+            chunk_assembler.push(chunks[1].clone()).unwrap(),
+            Some(message)
+        );
+    }
+
+    #[test]
+    fn test_invalid_chunks_are_rejected() {
+        let chunks = split_message(7, &[1; 100], 40);
+
+        let mut chunk_assembler = ChunkAssembler::new(100);
+        assert!(matches!(
+            chunk_assembler.push(chunks[1].clone()),
+            Err(ChunkError::UnexpectedIndex {
+                expected: 0,
+                received: 1
+            })
+        ));
+
+        chunk_assembler.push(chunks[0].clone()).unwrap();
+        assert!(matches!(
+            chunk_assembler.push(split_message(8, &[1; 100], 40)[1].clone()),
+            Err(ChunkError::UnexpectedSubmission {
+                expected: 7,
+                received: 8
+            })
+        ));
+
+        // The total size can't be exceeded, neither announced nor received
+        assert!(matches!(
+            ChunkAssembler::new(99).push(chunks[0].clone()),
+            Err(ChunkError::TooLarge {
+                total_size: 100,
+                max_size: 99
+            })
+        ));
+        let oversized = MessageChunk {
+            total_size: 30,
+            ..chunks[0].clone()
+        };
+        assert!(matches!(
+            chunk_assembler.push(oversized),
+            Err(ChunkError::SizeMismatch {
+                total_size: 30,
+                received: 40
+            })
+        ));
+
+        assert!(MessageChunk::from_bytes(&[0; HEADER_SIZE - 1]).is_none());
+        assert!(matches!(
+            chunk_assembler.push(MessageChunk::from_bytes(&[0; HEADER_SIZE]).unwrap()),
+            Err(ChunkError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_small_messages_are_sent_in_a_text_frame() {
+        assert!(matches!(
+            Payload::json("{}".to_string()),
+            Payload::Text(text) if text == "{}"
+        ));
+
+        let message = "a".repeat(MAX_FRAME_SIZE + 1);
+        let Payload::Chunked(bytes) = Payload::json(message.clone()) else {
+            panic!("Message larger than a frame is not chunked");
+        };
+        assert_eq!(bytes, message.into_bytes());
+    }
+
+    #[test]
+    fn test_chunks_of_different_sizes_are_reassembled() {
+        let message: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut chunk_splitter = ChunkSplitter::new(7, &message);
+        let mut chunk_assembler = ChunkAssembler::new(message.len());
+
+        let mut reassembled = None;
+        for chunk_size in [100, 400, 50, 1000] {
+            let chunk = chunk_splitter.next_chunk(chunk_size).unwrap();
+            let frame = Message::Binary(chunk.to_bytes());
+            assert!(reassembled.is_none());
+            reassembled = chunk_assembler
+                .push(MessageChunk::from_bytes(&frame.into_data()).unwrap())
+                .unwrap();
+        }
+        assert!(chunk_splitter.next_chunk(1000).is_none());
+        assert_eq!(reassembled, Some(message));
+    }
+
+    #[test]
+    fn test_chunk_size_grows_additively_and_shrinks_multiplicatively() {
+        let mut chunk_sizer = ChunkSizer::default();
+        let second = Duration::from_secs(1);
+        assert_eq!(chunk_sizer.chunk_size(), CHUNK_SIZE_STEP);
+
+        // The size grows while the throughput holds, up to the largest chunk size
+        chunk_sizer.record(CHUNK_SIZE_STEP, second);
+        assert_eq!(chunk_sizer.chunk_size(), 2 * CHUNK_SIZE_STEP);
+        chunk_sizer.record(2 * CHUNK_SIZE_STEP, second);
+        assert_eq!(chunk_sizer.chunk_size(), 3 * CHUNK_SIZE_STEP);
+        for _ in 0..10 {
+            chunk_sizer.record(chunk_sizer.chunk_size(), second / 2);
+        }
+        assert_eq!(chunk_sizer.chunk_size(), CHUNK_SIZE);
+
+        // Smaller chunks are not measured
+        chunk_sizer.record(1, second);
+        assert_eq!(chunk_sizer.chunk_size(), CHUNK_SIZE);
+
+        // A drop of the throughput halves the size
+        chunk_sizer.record(CHUNK_SIZE, second);
+        assert_eq!(chunk_sizer.chunk_size(), CHUNK_SIZE / 2);
+
+        // Chunks slower than the target halve the size, down to the smallest chunk size
+        for _ in 0..10 {
+            chunk_sizer.record(chunk_sizer.chunk_size(), 2 * TARGET_CHUNK_SEND_TIME);
+        }
+        assert_eq!(chunk_sizer.chunk_size(), MIN_CHUNK_SIZE);
+    }
+}
@@ -0,0 +1,692 @@
+//! A client holding the configuration needed to submit proofs to the batcher and check their
+//! verification on-chain, so it doesn't have to be passed to every call.
+//!
+//! Each submission opens its own connection to the batcher, checks its protocol version and
+//! closes the connection once all the proofs are responded, since the batcher pairs the responses
+//! of a connection with its messages by their order.
+//!
+//! The client remembers the proofs it submitted within a short window, and warns about or
+//! rejects the ones submitted again within it, see the `duplicates` module. It can also be
+//! limited to a number of proofs and an amount of Ether spent per day, see the `usage_limits`
+//! module.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::providers::{Http, Provider};
+use ethers::signers::{Signer, Wallet};
+use log::warn;
+
+use crate::bug_report::{report_failed_submission, Transcript};
+use crate::duplicates::{RecentSubmissions, DEFAULT_DUPLICATE_WINDOW};
+use crate::encryption::EncryptionConfig;
+use crate::errors::{AlignedError, SubmitError, VerificationError};
+use crate::fees;
+use crate::retry::{submit_with_retry, RetryPolicy};
+use crate::rpc::{AlignedRpcClient, RpcMetrics};
+use crate::sdk::{self, TimedSubmission, VerificationWaitConfig, VerifiedProof};
+use crate::tls::TlsConfig;
+use crate::types::{AlignedVerificationData, Chain, ProtocolHandshake, VerificationData};
+use crate::usage_limits::{UsageLimits, UsageStore};
+
+/// How the proofs of the submissions of an `AlignedClient` are signed and checked, and where the
+/// bug reports of the ones that fail are written.
+#[derive(Clone, Debug, Default)]
+pub struct SubmitOptions {
+    /// Whether to sign all the proofs of a submission at once, as in
+    /// `sdk::submit_multiple_with_single_signature`.
+    pub single_signature: bool,
+    /// Whether to fail on any protocol anomaly of the batcher instead of skipping it: frames that
+    /// are not responses, responses that can't be read, responses that don't match the proof sent
+    /// in their position, and connections ending before every proof is responded. Submissions
+    /// fail with a `ProtocolViolation` describing the anomaly and where it happened.
+    pub strict: bool,
+    /// File to which a bug report is written if a submission fails or the batcher doesn't respond
+    /// to every proof, see `bug_report::BugReport`. The report of a retried submission covers all
+    /// its attempts.
+    pub bug_report_path: Option<PathBuf>,
+}
+
+/// Client signing the proofs with `S`, any `ethers` signer, such as a `LocalWallet` or a hardware
+/// wallet.
+pub struct AlignedClient<S = Wallet<SigningKey>> {
+    batcher_addr: String,
+    wallet: S,
+    options: SubmitOptions,
+    chain: Chain,
+    rpc_client: AlignedRpcClient,
+    retry_policy: Option<RetryPolicy>,
+    tls_config: TlsConfig,
+    recent_submissions: RecentSubmissions,
+    reject_duplicates: bool,
+    encryption: Option<EncryptionConfig>,
+    usage_limits: Option<(UsageLimits, UsageStore)>,
+}
+
+/// Builds an `AlignedClient`. Only the wallet is required, the rest of the configuration
+/// defaults to a local devnet.
+pub struct AlignedClientBuilder<S = Wallet<SigningKey>> {
+    batcher_addr: String,
+    wallet: Option<S>,
+    options: SubmitOptions,
+    chain: Chain,
+    eth_rpc_url: String,
+    rpc_retry_policy: Option<RetryPolicy>,
+    retry_policy: Option<RetryPolicy>,
+    tls_config: TlsConfig,
+    auth_token: Option<String>,
+    duplicate_window: Duration,
+    reject_duplicates: bool,
+    encryption: Option<EncryptionConfig>,
+    usage_limits: Option<(UsageLimits, UsageStore)>,
+}
+
+impl<S> AlignedClientBuilder<S> {
+    /// The address of the batcher to which proofs are submitted. Defaults to
+    /// `ws://localhost:8080`.
+    pub fn batcher_addr(mut self, batcher_addr: impl Into<String>) -> Self {
+        self.batcher_addr = batcher_addr.into();
+        self
+    }
+
+    /// The wallet used to sign the proofs and pay for their verification. Any `ethers` signer can
+    /// be used, such as a `LocalWallet` or a `Ledger`.
+    pub fn wallet<W: Signer>(self, wallet: W) -> AlignedClientBuilder<W> {
+        AlignedClientBuilder {
+            batcher_addr: self.batcher_addr,
+            wallet: Some(wallet),
+            options: self.options,
+            chain: self.chain,
+            eth_rpc_url: self.eth_rpc_url,
+            rpc_retry_policy: self.rpc_retry_policy,
+            retry_policy: self.retry_policy,
+            tls_config: self.tls_config,
+            auth_token: self.auth_token,
+            duplicate_window: self.duplicate_window,
+            reject_duplicates: self.reject_duplicates,
+            encryption: self.encryption,
+            usage_limits: self.usage_limits,
+        }
+    }
+
+    /// How the proofs of each submission are signed and checked, and where the bug reports of
+    /// the ones that fail are written. Defaults to `SubmitOptions::default()`: a signature per
+    /// proof, anomalies of the batcher skipped, and no bug reports.
+    pub fn submit_options(mut self, options: SubmitOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The chain on which the proofs are verified. Defaults to `Chain::Devnet`.
+    pub fn chain(mut self, chain: Chain) -> Self {
+        self.chain = chain;
+        self
+    }
+
+    /// Sets the chain, and the batcher and the Ethereum RPC node to its endpoints, such as the
+    /// ones of a `Chain::Custom` for a fork or a private deployment.
+    pub fn network(mut self, chain: Chain) -> Self {
+        self.batcher_addr = chain.batcher_url().to_string();
+        self.eth_rpc_url = chain.eth_rpc_url().to_string();
+        self.chain = chain;
+        self
+    }
+
+    /// The URL of the Ethereum RPC node used to check the verification of the proofs. Defaults
+    /// to `http://localhost:8545`.
+    pub fn eth_rpc_url(mut self, eth_rpc_url: impl Into<String>) -> Self {
+        self.eth_rpc_url = eth_rpc_url.into();
+        self
+    }
+
+    /// The policy with which failed requests to the Ethereum RPC node are retried. Requests are
+    /// not retried by default.
+    pub fn rpc_retry_policy(mut self, rpc_retry_policy: RetryPolicy) -> Self {
+        self.rpc_retry_policy = Some(rpc_retry_policy);
+        self
+    }
+
+    /// The policy with which submissions are retried when the connection to the batcher is lost,
+    /// resubmitting the proofs without a response. Submissions are not retried by default.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// The policy with which every network operation of the client is retried: connections and
+    /// submissions to the batcher, requests to the Ethereum RPC node and downloads of batch data,
+    /// as `rpc_retry_policy` and `retry_policy` together. Whichever of the three is set last
+    /// takes precedence.
+    pub fn network_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.rpc_retry_policy = Some(retry_policy.clone());
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// The root certificates to trust and the server name of the batcher, for `wss://`
+    /// addresses. Defaults to the root certificates of the system and the host of the address.
+    pub fn tls_config(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = tls_config;
+        self
+    }
+
+    /// The access token sent to batchers of private deployments, as `TlsConfig::auth_token`. It
+    /// takes precedence over the token of `tls_config`. No token is sent by default.
+    pub fn auth_token(mut self, auth_token: impl Into<String>) -> Self {
+        self.auth_token = Some(auth_token.into());
+        self
+    }
+
+    /// The window within which a proof submitted again by the client is a duplicate. A window
+    /// of zero disables the detection. Defaults to `DEFAULT_DUPLICATE_WINDOW`, one minute.
+    pub fn duplicate_window(mut self, duplicate_window: Duration) -> Self {
+        self.duplicate_window = duplicate_window;
+        self
+    }
+
+    /// Whether to fail submissions with duplicates with `DuplicateSubmission`, instead of
+    /// logging a warning and submitting them. Defaults to `false`.
+    pub fn reject_duplicates(mut self, reject_duplicates: bool) -> Self {
+        self.reject_duplicates = reject_duplicates;
+        self
+    }
+
+    /// Encrypts the messages of every submission to the key published by the batcher, as
+    /// described in `encryption`. Submissions fail if the batcher doesn't support it, or
+    /// presents a key other than the one of `encryption`. Messages are not encrypted by default.
+    pub fn encryption(mut self, encryption: EncryptionConfig) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    /// Limits the proofs submitted and the Ether spent per day by the client, recording its usage
+    /// in `store`, which can be shared with other clients and the CLI. Submissions exceeding
+    /// them fail with `UsageLimitExceeded` before anything is sent. With a spend limit, the gas
+    /// price is queried to estimate the fee of each submission. There are no limits by default.
+    pub fn usage_limits(mut self, limits: UsageLimits, store: UsageStore) -> Self {
+        self.usage_limits = Some((limits, store));
+        self
+    }
+
+    /// Builds the client.
+    /// # Errors
+    /// * `MissingParameter` if the wallet was not set.
+    /// * `EthError` if the Ethereum RPC URL is not valid.
+    pub fn build(self) -> Result<AlignedClient<S>, SubmitError> {
+        let wallet = self
+            .wallet
+            .ok_or(SubmitError::MissingParameter("wallet".to_string()))?;
+        let eth_rpc_provider = Provider::<Http>::try_from(self.eth_rpc_url.as_str())
+            .map_err(|e| SubmitError::EthError(e.to_string()))?;
+        let rpc_client = match self.rpc_retry_policy {
+            Some(rpc_retry_policy) => {
+                AlignedRpcClient::with_retry_policy(eth_rpc_provider, rpc_retry_policy)
+            }
+            None => AlignedRpcClient::new(eth_rpc_provider),
+        };
+        let mut tls_config = self.tls_config;
+        if let Some(auth_token) = self.auth_token {
+            tls_config.auth_token = Some(auth_token);
+        }
+
+        Ok(AlignedClient {
+            batcher_addr: self.batcher_addr,
+            wallet,
+            options: self.options,
+            chain: self.chain,
+            rpc_client,
+            retry_policy: self.retry_policy,
+            tls_config,
+            recent_submissions: RecentSubmissions::new(self.duplicate_window),
+            reject_duplicates: self.reject_duplicates,
+            encryption: self.encryption,
+            usage_limits: self.usage_limits,
+        })
+    }
+}
+
+impl AlignedClient {
+    /// Returns a builder of a client, which signs with a `LocalWallet` unless another signer is
+    /// set with `AlignedClientBuilder::wallet`.
+    pub fn builder() -> AlignedClientBuilder {
+        AlignedClientBuilder {
+            batcher_addr: Chain::Devnet.batcher_url().to_string(),
+            wallet: None,
+            options: SubmitOptions::default(),
+            chain: Chain::Devnet,
+            eth_rpc_url: Chain::Devnet.eth_rpc_url().to_string(),
+            rpc_retry_policy: None,
+            retry_policy: None,
+            tls_config: TlsConfig::default(),
+            auth_token: None,
+            duplicate_window: DEFAULT_DUPLICATE_WINDOW,
+            reject_duplicates: false,
+            encryption: None,
+            usage_limits: None,
+        }
+    }
+}
+
+impl<S: Signer> AlignedClient<S> {
+    /// Submits a proof to the batcher, as `sdk::submit`.
+    pub async fn submit(
+        &self,
+        verification_data: &VerificationData,
+    ) -> Result<Option<AlignedVerificationData>, SubmitError> {
+        let aligned_verification_data = self
+            .submit_multiple(std::slice::from_ref(verification_data))
+            .await?;
+        Ok(aligned_verification_data
+            .and_then(|mut aligned_verification_data| aligned_verification_data.pop()))
+    }
+
+    /// Submits multiple proofs to the batcher, as `sdk::submit_multiple`, with the
+    /// `SubmitOptions` of the client, connecting with its TLS configuration. If the client was
+    /// built with a retry policy, the submission is retried as in
+    /// `retry::submit_multiple_with_retry`. The messages are encrypted if the client was built
+    /// with encryption.
+    ///
+    /// Proofs already submitted by the client within its duplicate window, or repeated in
+    /// `verification_data`, are logged as duplicates, or fail the submission with
+    /// `DuplicateSubmission` before anything is sent if the client rejects duplicates. The proofs
+    /// of a submission that fails can be submitted again right away.
+    ///
+    /// If the client was built with usage limits, submissions exceeding them fail with
+    /// `UsageLimitExceeded` before anything is sent. The proofs of a submission count towards
+    /// the limits once it is sent, even if it fails afterwards.
+    pub async fn submit_multiple(
+        &self,
+        verification_data: &[VerificationData],
+    ) -> Result<Option<Vec<AlignedVerificationData>>, SubmitError> {
+        self.submit_multiple_with_timing(verification_data)
+            .await
+            .map(|submission| submission.aligned_verification_data)
+    }
+
+    /// Submits multiple proofs to the batcher as `submit_multiple`, also returning when each
+    /// proof was queued, sent, acknowledged and included, to analyze the throughput of bulk
+    /// submissions. With a retry policy, the timing covers all the attempts.
+    pub async fn submit_multiple_with_timing(
+        &self,
+        verification_data: &[VerificationData],
+    ) -> Result<TimedSubmission, SubmitError> {
+        let leaves = RecentSubmissions::leaves(verification_data);
+        let duplicates = self
+            .recent_submissions
+            .record(&leaves, self.reject_duplicates);
+        if !duplicates.is_empty() {
+            if self.reject_duplicates {
+                return Err(SubmitError::DuplicateSubmission(duplicates));
+            }
+            warn!(
+                "Submitting {} proofs already submitted recently by this client",
+                duplicates.len()
+            );
+        }
+
+        let result = match self.reserve_usage(verification_data.len()).await {
+            Ok(()) => self.send(verification_data).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let recorded: Vec<[u8; 32]> = leaves
+                .into_iter()
+                .filter(|leaf| !duplicates.contains(leaf))
+                .collect();
+            self.recent_submissions.forget(&recorded);
+        }
+        result
+    }
+
+    async fn reserve_usage(&self, num_proofs: usize) -> Result<(), SubmitError> {
+        let Some((limits, store)) = &self.usage_limits else {
+            return Ok(());
+        };
+        let spend = if limits.max_spend_per_day.is_some() {
+            let gas_price = self.rpc_client.gas_price().await.map_err(|e| {
+                SubmitError::EthError(format!(
+                    "Error while estimating the fee for the spend limit: {:?}",
+                    e
+                ))
+            })?;
+            fees::max_submission_fee(num_proofs, gas_price)
+        } else {
+            ethers::types::U256::zero()
+        };
+        store.reserve(limits, num_proofs, spend).map(|_| ())
+    }
+
+    async fn send(
+        &self,
+        verification_data: &[VerificationData],
+    ) -> Result<TimedSubmission, SubmitError> {
+        let mut transcript = Transcript::new();
+        let result = if let Some(retry_policy) = &self.retry_policy {
+            submit_with_retry(
+                &self.batcher_addr,
+                verification_data,
+                &self.wallet,
+                &self.options,
+                &self.tls_config,
+                self.encryption.as_ref(),
+                &mut transcript,
+                retry_policy,
+            )
+            .await
+        } else {
+            sdk::submit_with_tls(
+                &self.batcher_addr,
+                verification_data,
+                &self.wallet,
+                &self.options,
+                &self.tls_config,
+                self.encryption.as_ref(),
+                &mut transcript,
+            )
+            .await
+        };
+
+        if let Some(bug_report_path) = &self.options.bug_report_path {
+            report_failed_submission(
+                bug_report_path,
+                &self.batcher_addr,
+                verification_data,
+                transcript,
+                result
+                    .as_ref()
+                    .map(|submission| submission.aligned_verification_data.as_deref()),
+            );
+        }
+        result
+    }
+
+    /// Returns the protocol version and features negotiated with the batcher, as
+    /// `sdk::negotiate_protocol`, retrying the connection with the retry policy of the client.
+    pub async fn negotiate_protocol(&self) -> Result<ProtocolHandshake, SubmitError> {
+        match &self.retry_policy {
+            Some(retry_policy) => {
+                retry_policy
+                    .retry("Protocol negotiation", || {
+                        sdk::negotiate_protocol(&self.batcher_addr, &self.tls_config)
+                    })
+                    .await
+            }
+            None => sdk::negotiate_protocol(&self.batcher_addr, &self.tls_config).await,
+        }
+    }
+
+    /// Submits multiple proofs to the batcher as `submit_multiple`, and waits until their batches
+    /// are verified on-chain, as `sdk::submit_and_wait_verification`.
+    pub async fn submit_and_wait_verification(
+        &self,
+        verification_data: &[VerificationData],
+        wait_config: &VerificationWaitConfig,
+    ) -> Result<Option<Vec<VerifiedProof>>, AlignedError> {
+        let Some(aligned_verification_data) = self.submit_multiple(verification_data).await? else {
+            return Ok(None);
+        };
+
+        let verified_proofs = sdk::wait_verification(
+            aligned_verification_data,
+            self.chain.clone(),
+            &self.rpc_client,
+            wait_config,
+            None,
+        )
+        .await?;
+        Ok(Some(verified_proofs))
+    }
+
+    /// Checks if the proof has been verified with Aligned and is included in the batch, as
+    /// `sdk::verify_proof_onchain`.
+    pub async fn verify_proof_onchain(
+        &self,
+        aligned_verification_data: AlignedVerificationData,
+    ) -> Result<bool, VerificationError> {
+        sdk::_verify_proof_onchain(
+            aligned_verification_data,
+            self.chain.clone(),
+            &self.rpc_client,
+        )
+        .await
+    }
+
+    /// Returns the metrics of the requests made to the Ethereum RPC node by the client, by
+    /// method name. Identical concurrent checks of `verify_proof_onchain` share one request.
+    pub fn rpc_metrics(&self) -> BTreeMap<&'static str, RpcMetrics> {
+        self.rpc_client.metrics()
+    }
+
+    /// Returns the commitment of a verification key, as `sdk::get_commitment`.
+    pub fn get_vk_commitment(&self, verification_key: &[u8]) -> [u8; 32] {
+        sdk::get_commitment(verification_key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder_requires_wallet() {
+        assert!(matches!(
+            AlignedClient::builder().build(),
+            Err(SubmitError::MissingParameter(param)) if param == "wallet"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_network_retry_policy_retries_the_connection_to_the_batcher() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        use crate::retry::{is_transient, NetworkFailure};
+
+        static CLASSIFIED: AtomicU32 = AtomicU32::new(0);
+        fn counting_is_transient(failure: NetworkFailure) -> bool {
+            CLASSIFIED.fetch_add(1, Ordering::SeqCst);
+            is_transient(failure)
+        }
+
+        let client = AlignedClient::builder()
+            .batcher_addr("ws://127.0.0.1:1")
+            .wallet(ethers::signers::LocalWallet::new(&mut rand::thread_rng()))
+            .network_retry_policy(RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(1),
+                is_retryable: counting_is_transient,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            client.negotiate_protocol().await,
+            Err(SubmitError::ConnectionError(_))
+        ));
+        // The last attempt is not retried whatever the failure
+        assert_eq!(CLASSIFIED.load(Ordering::SeqCst), 2);
+        assert_eq!(client.rpc_client.retry_policy().max_attempts, 3);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_client_submits_to_batcher() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let mut rng = seeded_rng(0);
+        let verification_data: Vec<VerificationData> =
+            (0..3).map(|_| random_verification_data(&mut rng)).collect();
+
+        for single_signature in [false, true] {
+            let client = AlignedClient::builder()
+                .batcher_addr(mock_batcher.url())
+                .wallet(deterministic_wallet(0))
+                .submit_options(SubmitOptions {
+                    single_signature,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap();
+
+            let aligned_verification_data = client
+                .submit_multiple(&verification_data)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(aligned_verification_data.len(), 3);
+
+            // Each submission uses its own connection
+            assert!(client
+                .submit(&verification_data[0])
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_client_uses_the_endpoints_of_its_network() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+        use crate::types::NetworkEndpoints;
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let chain = Chain::Custom(NetworkEndpoints {
+            batcher_url: mock_batcher.url(),
+            ..Chain::Devnet.endpoints()
+        });
+        let client = AlignedClient::builder()
+            .network(chain.clone())
+            .wallet(deterministic_wallet(0))
+            .build()
+            .unwrap();
+        assert_eq!(client.chain, chain);
+
+        let verification_data = random_verification_data(&mut seeded_rng(0));
+        assert!(client.submit(&verification_data).await.unwrap().is_some());
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_client_rejects_duplicates() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let mut rng = seeded_rng(0);
+        let verification_data: Vec<VerificationData> =
+            (0..2).map(|_| random_verification_data(&mut rng)).collect();
+        let client = AlignedClient::builder()
+            .batcher_addr(mock_batcher.url())
+            .wallet(deterministic_wallet(0))
+            .reject_duplicates(true)
+            .build()
+            .unwrap();
+
+        assert!(client
+            .submit(&verification_data[0])
+            .await
+            .unwrap()
+            .is_some());
+        assert!(matches!(
+            client.submit_multiple(&verification_data).await,
+            Err(SubmitError::DuplicateSubmission(leaves)) if leaves == RecentSubmissions::leaves(&verification_data[..1])
+        ));
+        // Nothing of the rejected submission was recorded
+        assert!(client
+            .submit(&verification_data[1])
+            .await
+            .unwrap()
+            .is_some());
+
+        // The proofs of failed submissions are not recorded
+        let unreachable_client = AlignedClient::builder()
+            .batcher_addr("ws://localhost:1")
+            .wallet(deterministic_wallet(0))
+            .reject_duplicates(true)
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            assert!(matches!(
+                unreachable_client.submit(&verification_data[0]).await,
+                Err(SubmitError::ConnectionError(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_client_refuses_submissions_above_its_usage_limits() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+        use crate::usage_limits::{UsageLimits, UsageStore};
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let store_path =
+            std::env::temp_dir().join(format!("aligned_client_usage_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&store_path);
+        let limits = UsageLimits {
+            max_proofs_per_day: Some(2),
+            max_spend_per_day: None,
+        };
+        let client = AlignedClient::builder()
+            .batcher_addr(mock_batcher.url())
+            .wallet(deterministic_wallet(0))
+            .usage_limits(limits, UsageStore::new(&store_path))
+            .build()
+            .unwrap();
+
+        let mut rng = seeded_rng(0);
+        let verification_data: Vec<VerificationData> =
+            (0..3).map(|_| random_verification_data(&mut rng)).collect();
+        assert!(client
+            .submit_multiple(&verification_data[..2])
+            .await
+            .unwrap()
+            .is_some());
+        assert!(matches!(
+            client.submit(&verification_data[2]).await,
+            Err(SubmitError::UsageLimitExceeded(_))
+        ));
+        assert_eq!(UsageStore::new(&store_path).today().unwrap().proofs, 2);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_wait_verification_reports_rpc_errors() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+        use std::time::Duration;
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let client = AlignedClient::builder()
+            .batcher_addr(mock_batcher.url())
+            .wallet(deterministic_wallet(0))
+            .eth_rpc_url("http://localhost:1")
+            .build()
+            .unwrap();
+        let wait_config = VerificationWaitConfig {
+            poll_interval: Duration::from_millis(10),
+            timeout: Duration::from_secs(10),
+        };
+
+        // The proofs are submitted, but their batches can't be checked
+        let result = client
+            .submit_and_wait_verification(
+                &[random_verification_data(&mut seeded_rng(0))],
+                &wait_config,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(AlignedError::VerificationError(
+                VerificationError::EthError(_)
+            ))
+        ));
+    }
+}
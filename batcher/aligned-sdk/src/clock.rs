@@ -0,0 +1,35 @@
+use std::time::SystemTime;
+
+#[cfg(feature = "test-utils")]
+use std::cell::Cell;
+
+#[cfg(feature = "test-utils")]
+thread_local! {
+    static FROZEN_AT: Cell<Option<SystemTime>> = const { Cell::new(None) };
+}
+
+/// Returns the current time. Every timestamp taken by the SDK goes through this function, so that
+/// it can be frozen in tests with the `test-utils` feature. Durations are measured with `Instant`
+/// instead, since a frozen clock would make them zero.
+pub(crate) fn now() -> SystemTime {
+    #[cfg(feature = "test-utils")]
+    if let Some(frozen_at) = FROZEN_AT.with(Cell::get) {
+        return frozen_at;
+    }
+
+    SystemTime::now()
+}
+
+/// Freezes the time read by the SDK in the current thread at the given instant, until
+/// `unfreeze_clock` is called.
+/// Async tests have to run in a single threaded runtime, which is the default of `tokio::test`.
+#[cfg(feature = "test-utils")]
+pub fn freeze_clock(at: SystemTime) {
+    FROZEN_AT.with(|frozen_at| frozen_at.set(Some(at)));
+}
+
+/// Makes the SDK read the system time again in the current thread.
+#[cfg(feature = "test-utils")]
+pub fn unfreeze_clock() {
+    FROZEN_AT.with(|frozen_at| frozen_at.set(None));
+}
@@ -0,0 +1,227 @@
+//! Discovery of the batcher endpoints of an environment, such as `holesky`, from a metadata
+//! document published at a well-known HTTPS URL, so that the endpoints can be rotated without
+//! users updating the addresses they connect to.
+//!
+//! The document maps the name of each environment to its batcher endpoints, in order of
+//! preference:
+//!
+//! ```json
+//! { "environments": { "holesky": { "batchers": ["wss://batcher.alignedlayer.com"] } } }
+//! ```
+//!
+//! The document is signed as an Ethereum message (EIP-191) by the key of whoever publishes it,
+//! with `make sign_discovery_document`, and its signature is served next to it, at the URL of the
+//! document followed by `.sig`, in hex. The signature is checked against the address of the key,
+//! which callers must get from the publisher, before any endpoint is used, so that the endpoints
+//! can't be replaced by tampering with DNS or the HTTP responses. There is no default key, since
+//! a key that isn't published alongside the document proves nothing about it.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ethers::types::{Address, Signature};
+use serde::Deserialize;
+
+use crate::errors::SubmitError;
+
+/// URL of the metadata document of the environments of Aligned.
+pub const DEFAULT_DISCOVERY_URL: &str =
+    "https://batcher.alignedlayer.com/.well-known/aligned-environments.json";
+
+/// Suffix added to the URL of the metadata document to get the URL of its signature.
+pub const SIGNATURE_URL_SUFFIX: &str = ".sig";
+
+/// Environment of a local devnet, whose batcher is always on the same address and isn't looked
+/// up.
+pub const DEVNET_ENVIRONMENT: &str = "devnet";
+const DEVNET_BATCHER_URL: &str = "ws://localhost:8080";
+
+#[derive(Deserialize)]
+struct DiscoveryDocument {
+    environments: HashMap<String, EnvironmentEndpoints>,
+}
+
+#[derive(Deserialize)]
+struct EnvironmentEndpoints {
+    batchers: Vec<String>,
+}
+
+/// Returns the batcher endpoints of an environment, in order of preference, by downloading the
+/// metadata document at `discovery_url` and checking its signature. The endpoints of `devnet`
+/// are returned without a download.
+/// # Arguments
+/// * `env` - The name of the environment.
+/// * `discovery_url` - The URL of the metadata document, such as `DEFAULT_DISCOVERY_URL`.
+/// * `signer` - The address of the key that signed the document, as given by its publisher.
+/// # Returns
+/// * The batcher endpoints of the environment.
+/// # Errors
+/// * `DiscoveryError` if the document or its signature can't be downloaded, the document wasn't
+///   signed by `signer`, is not valid, or has no endpoints for the environment.
+pub async fn resolve_batcher_endpoints(
+    env: &str,
+    discovery_url: &str,
+    signer: Address,
+) -> Result<Vec<String>, SubmitError> {
+    if env == DEVNET_ENVIRONMENT {
+        return Ok(vec![DEVNET_BATCHER_URL.to_string()]);
+    }
+
+    let document = download(discovery_url).await?;
+    let signature = download(&format!("{}{}", discovery_url, SIGNATURE_URL_SUFFIX)).await?;
+    verify_document_signature(&document, &signature, signer)?;
+
+    parse_batcher_endpoints(&document, env)
+}
+
+/// Checks that the metadata document was signed by `signer`. The signature is the hex of the 65
+/// bytes of an EIP-191 signature over the document, with or without a `0x` prefix.
+/// # Errors
+/// * `DiscoveryError` if the signature is not valid or was made by another key.
+pub fn verify_document_signature(
+    document: &str,
+    signature: &str,
+    signer: Address,
+) -> Result<(), SubmitError> {
+    let signature = Signature::from_str(signature.trim())
+        .map_err(|e| SubmitError::DiscoveryError(format!("Invalid document signature: {}", e)))?;
+    signature.verify(document, signer).map_err(|_| {
+        SubmitError::DiscoveryError(format!(
+            "The document was not signed by {:?}, it may have been tampered with",
+            signer
+        ))
+    })
+}
+
+async fn download(url: &str) -> Result<String, SubmitError> {
+    reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            SubmitError::DiscoveryError(format!("Error while downloading {}: {}", url, e))
+        })?
+        .text()
+        .await
+        .map_err(|e| SubmitError::DiscoveryError(format!("Error while downloading {}: {}", url, e)))
+}
+
+/// Returns the batcher endpoints of an environment listed in a metadata document.
+/// # Errors
+/// * `DiscoveryError` if the document is not valid, has no endpoints for the environment or
+///   any of them is not a websocket URL.
+pub fn parse_batcher_endpoints(document: &str, env: &str) -> Result<Vec<String>, SubmitError> {
+    let mut document: DiscoveryDocument = serde_json::from_str(document)
+        .map_err(|e| SubmitError::DiscoveryError(format!("Invalid document: {}", e)))?;
+
+    let endpoints = document
+        .environments
+        .remove(env)
+        .map(|environment| environment.batchers)
+        .unwrap_or_default();
+    if endpoints.is_empty() {
+        return Err(SubmitError::DiscoveryError(format!(
+            "No batcher endpoints for environment {}",
+            env
+        )));
+    }
+    if let Some(endpoint) = endpoints
+        .iter()
+        .find(|endpoint| !endpoint.starts_with("ws://") && !endpoint.starts_with("wss://"))
+    {
+        return Err(SubmitError::DiscoveryError(format!(
+            "Invalid batcher endpoint {}, expected a websocket URL",
+            endpoint
+        )));
+    }
+
+    Ok(endpoints)
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::signers::{LocalWallet, Signer};
+
+    use super::*;
+
+    const DOCUMENT: &str = r#"{
+        "environments": {
+            "holesky": { "batchers": ["wss://batcher-2.example.com", "wss://batcher-1.example.com"] },
+            "staging": { "batchers": [] }
+        }
+    }"#;
+
+    #[test]
+    fn test_endpoints_are_parsed_in_order() {
+        assert_eq!(
+            parse_batcher_endpoints(DOCUMENT, "holesky").unwrap(),
+            vec!["wss://batcher-2.example.com", "wss://batcher-1.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_or_invalid_environments_are_rejected() {
+        for env in ["staging", "mainnet"] {
+            assert!(matches!(
+                parse_batcher_endpoints(DOCUMENT, env),
+                Err(SubmitError::DiscoveryError(_))
+            ));
+        }
+        assert!(matches!(
+            parse_batcher_endpoints(
+                r#"{"environments":{"holesky":{"batchers":["https://batcher.example.com"]}}}"#,
+                "holesky"
+            ),
+            Err(SubmitError::DiscoveryError(_))
+        ));
+        assert!(matches!(
+            parse_batcher_endpoints("not json", "holesky"),
+            Err(SubmitError::DiscoveryError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_documents_signed_by_other_keys_are_rejected() {
+        let wallet = LocalWallet::from_str(
+            "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        )
+        .unwrap();
+        let signature = wallet.sign_message(DOCUMENT).await.unwrap().to_string();
+
+        assert!(verify_document_signature(DOCUMENT, &signature, wallet.address()).is_ok());
+        // Signatures are usually served with a prefix and a trailing newline
+        assert!(verify_document_signature(
+            DOCUMENT,
+            &format!("0x{}\n", signature),
+            wallet.address()
+        )
+        .is_ok());
+
+        let tampered = DOCUMENT.replace("batcher-1", "attacker");
+        assert!(matches!(
+            verify_document_signature(&tampered, &signature, wallet.address()),
+            Err(SubmitError::DiscoveryError(_))
+        ));
+        assert!(matches!(
+            verify_document_signature(DOCUMENT, &signature, Address::repeat_byte(0x42)),
+            Err(SubmitError::DiscoveryError(_))
+        ));
+        assert!(matches!(
+            verify_document_signature(DOCUMENT, "0x1234", wallet.address()),
+            Err(SubmitError::DiscoveryError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_devnet_is_not_looked_up() {
+        assert_eq!(
+            resolve_batcher_endpoints(
+                DEVNET_ENVIRONMENT,
+                "http://localhost:1",
+                Address::repeat_byte(0x42)
+            )
+            .await
+            .unwrap(),
+            vec![DEVNET_BATCHER_URL]
+        );
+    }
+}
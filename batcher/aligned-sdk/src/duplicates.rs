@@ -0,0 +1,149 @@
+//! Detection of proofs submitted twice by the same client within a short window, which is
+//! usually a bug in the calling code, such as a retry loop that doesn't check the result of the
+//! first submission. Each duplicate is paid for again, so catching them before they are sent saves
+//! the fee.
+//!
+//! Proofs are compared by the leaf of their verification data commitment in the batch merkle
+//! tree, so proofs with the same artifacts and proof generator are duplicates.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+
+use crate::clock;
+use crate::types::{VerificationCommitmentBatch, VerificationData, VerificationDataCommitment};
+
+/// Default window in which a proof submitted again by the same client is a duplicate.
+pub const DEFAULT_DUPLICATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Leaves of the proofs submitted by a client within the window, with the time at which they
+/// were submitted.
+pub(crate) struct RecentSubmissions {
+    window: Duration,
+    submitted_at: Mutex<HashMap<[u8; 32], SystemTime>>,
+}
+
+impl RecentSubmissions {
+    /// A window of zero disables the detection.
+    pub(crate) fn new(window: Duration) -> Self {
+        RecentSubmissions {
+            window,
+            submitted_at: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the leaves of the proofs of a submission, in their order.
+    pub(crate) fn leaves(verification_data: &[VerificationData]) -> Vec<[u8; 32]> {
+        verification_data
+            .iter()
+            .map(|verification_data| {
+                VerificationCommitmentBatch::hash_data(&VerificationDataCommitment::from(
+                    verification_data.clone(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns the leaves that were submitted within the window, or that are repeated in the
+    /// submission, and records all the leaves as submitted now, which restarts the window of the
+    /// duplicates. If `reject` is set, nothing is recorded when there are duplicates, since the
+    /// submission won't be sent.
+    pub(crate) fn record(&self, leaves: &[[u8; 32]], reject: bool) -> Vec<[u8; 32]> {
+        if self.window.is_zero() {
+            return Vec::new();
+        }
+
+        let now = clock::now();
+        let mut submitted_at = self
+            .submitted_at
+            .lock()
+            .expect("Recent submissions lock is not poisoned");
+        submitted_at.retain(|_, at| {
+            now.duration_since(*at)
+                .map_or(true, |elapsed| elapsed < self.window)
+        });
+
+        let mut seen = HashSet::new();
+        let duplicates: Vec<[u8; 32]> = leaves
+            .iter()
+            .filter(|leaf| !seen.insert(**leaf) || submitted_at.contains_key(*leaf))
+            .copied()
+            .collect();
+
+        if !reject || duplicates.is_empty() {
+            for leaf in leaves {
+                submitted_at.insert(*leaf, now);
+            }
+        }
+        duplicates
+    }
+
+    /// Forgets the leaves recorded by a submission that failed, so that the proofs can be
+    /// submitted again.
+    pub(crate) fn forget(&self, leaves: &[[u8; 32]]) {
+        let mut submitted_at = self
+            .submitted_at
+            .lock()
+            .expect("Recent submissions lock is not poisoned");
+        for leaf in leaves {
+            submitted_at.remove(leaf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_leaves_submitted_within_the_window_are_duplicates() {
+        let recent_submissions = RecentSubmissions::new(Duration::from_secs(60));
+
+        assert!(recent_submissions
+            .record(&[[1; 32], [2; 32]], false)
+            .is_empty());
+        assert_eq!(
+            recent_submissions.record(&[[2; 32], [3; 32]], false),
+            vec![[2; 32]]
+        );
+        // Leaves repeated in the same submission are duplicates too
+        assert_eq!(
+            recent_submissions.record(&[[4; 32], [4; 32]], false),
+            vec![[4; 32]]
+        );
+
+        // Rejected submissions are not recorded
+        assert_eq!(
+            recent_submissions.record(&[[1; 32], [5; 32]], true),
+            vec![[1; 32]]
+        );
+        assert!(recent_submissions.record(&[[5; 32]], true).is_empty());
+
+        recent_submissions.forget(&[[1; 32]]);
+        assert!(recent_submissions.record(&[[1; 32]], false).is_empty());
+    }
+
+    #[test]
+    fn test_zero_window_disables_the_detection() {
+        let recent_submissions = RecentSubmissions::new(Duration::ZERO);
+        assert!(recent_submissions.record(&[[1; 32]], false).is_empty());
+        assert!(recent_submissions.record(&[[1; 32]], false).is_empty());
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn test_leaves_expire_after_the_window() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let recent_submissions = RecentSubmissions::new(Duration::from_secs(60));
+
+        clock::freeze_clock(start);
+        assert!(recent_submissions.record(&[[1; 32]], false).is_empty());
+        clock::freeze_clock(start + Duration::from_secs(59));
+        assert_eq!(recent_submissions.record(&[[1; 32]], true), vec![[1; 32]]);
+        clock::freeze_clock(start + Duration::from_secs(60));
+        assert!(recent_submissions.record(&[[1; 32]], false).is_empty());
+        clock::unfreeze_clock();
+    }
+}
@@ -0,0 +1,320 @@
+//! End-to-end encryption of the client messages sent to the batcher, for proofs whose public
+//! inputs hold sensitive data that shouldn't be readable by anyone relaying the connection.
+//!
+//! Batchers configured with an X25519 key announce the `encrypted_payloads` feature and publish
+//! their public key in the handshake. Clients that opt in with an `EncryptionConfig` encrypt each
+//! encoded message, after compressing it, to that key: a fresh ephemeral key is agreed with the
+//! key of the batcher, the shared secret is expanded with HKDF-SHA256 into a ChaCha20-Poly1305
+//! key, and the message is sealed with it. Since each key seals a single message, the nonce is
+//! always zero.
+//!
+//! An encrypted message is the magic number `ENCRYPTED_MAGIC`, the ephemeral public key and the
+//! sealed message, and is sent in chunks as binary messages are. The commitments of the proofs
+//! are still computed over the plaintext, so responses and batches are the same as for
+//! unencrypted messages.
+//!
+//! Encryption only protects the messages on their way to the batcher, as TLS already does for
+//! `wss://` batchers, for instance from proxies that terminate TLS. It doesn't hide the proofs
+//! from the batcher: it decrypts each message to verify it, and uploads the batches it builds,
+//! public inputs included, to S3 in plaintext, from where operators download them. Public inputs
+//! that must stay private shouldn't be submitted to Aligned, encrypted or not.
+
+use std::fmt;
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::errors::SubmitError;
+use crate::sdk::{CURRENT_PROTOCOL_VERSION, ENCRYPTED_PAYLOADS_FEATURE};
+use crate::types::ProtocolHandshake;
+use crate::wire::WireError;
+
+/// First bytes of an encrypted message, which neither JSON, binary nor compressed messages start
+/// with.
+const ENCRYPTED_MAGIC: [u8; 4] = *b"aenc";
+
+/// Context of the keys derived with HKDF, so they can't be confused with keys of other uses of
+/// the same shared secret.
+const KEY_INFO: &[u8] = b"aligned payload encryption v1";
+
+/// Encryption of the messages of a client, set to opt in.
+#[derive(Debug, Clone, Default)]
+pub struct EncryptionConfig {
+    /// The public key published by the batcher. When set, submissions fail unless the batcher
+    /// presents this key in its handshake, instead of trusting the key it presents.
+    pub batcher_public_key: Option<[u8; 32]>,
+}
+
+impl EncryptionConfig {
+    pub fn with_batcher_public_key(mut self, batcher_public_key: [u8; 32]) -> Self {
+        self.batcher_public_key = Some(batcher_public_key);
+        self
+    }
+}
+
+/// The X25519 key with which a batcher decrypts the messages encrypted to its public key.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    secret: StaticSecret,
+}
+
+impl EncryptionKey {
+    /// Generates a random key.
+    pub fn generate() -> Self {
+        EncryptionKey {
+            secret: StaticSecret::random_from_rng(rand::rngs::OsRng),
+        }
+    }
+
+    /// Returns the key with these bytes. Any 32 bytes are a valid key.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        EncryptionKey {
+            secret: StaticSecret::from(bytes),
+        }
+    }
+
+    /// Returns the public key published for the clients to encrypt their messages to.
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.secret).to_bytes()
+    }
+
+    /// Announces the `encrypted_payloads` feature and publishes the public key in the handshake
+    /// of a batcher.
+    pub fn announce(&self, handshake: &mut ProtocolHandshake) {
+        if !handshake.supports(ENCRYPTED_PAYLOADS_FEATURE) {
+            handshake
+                .features
+                .push(ENCRYPTED_PAYLOADS_FEATURE.to_string());
+        }
+        handshake.encryption_public_key = Some(self.public_key());
+    }
+
+    /// Decrypts a message encrypted to the public key with `encrypt`.
+    /// # Errors
+    /// * `Decryption` if the message is not encrypted, or not to this key, or was modified.
+    pub fn decrypt(&self, message: &[u8]) -> Result<Vec<u8>, WireError> {
+        let sealed = message
+            .strip_prefix(&ENCRYPTED_MAGIC)
+            .ok_or(WireError::Decryption)?;
+        if sealed.len() < 32 {
+            return Err(WireError::Decryption);
+        }
+        let (ephemeral_public_key, ciphertext) = sealed.split_at(32);
+        let ephemeral_public_key: [u8; 32] = ephemeral_public_key
+            .try_into()
+            .expect("Ephemeral public key has 32 bytes");
+
+        let shared_secret = self
+            .secret
+            .diffie_hellman(&PublicKey::from(ephemeral_public_key));
+        if !shared_secret.was_contributory() {
+            return Err(WireError::Decryption);
+        }
+        cipher(
+            shared_secret.as_bytes(),
+            &ephemeral_public_key,
+            &self.public_key(),
+        )
+        .decrypt(&Nonce::default(), ciphertext)
+        .map_err(|_| WireError::Decryption)
+    }
+}
+
+// The secret is never printed
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "EncryptionKey {{ public_key: {} }}",
+            hex::encode(self.public_key())
+        )
+    }
+}
+
+/// Returns the cipher sealing the message sent with an ephemeral key to a batcher key, from the
+/// secret they share.
+fn cipher(
+    shared_secret: &[u8; 32],
+    ephemeral_public_key: &[u8; 32],
+    batcher_public_key: &[u8; 32],
+) -> ChaCha20Poly1305 {
+    let salt = [ephemeral_public_key.as_slice(), batcher_public_key].concat();
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(&salt), shared_secret)
+        .expand(KEY_INFO, &mut key)
+        .expect("Key is shorter than the limit of HKDF");
+    ChaCha20Poly1305::new(&key)
+}
+
+/// Encrypts an encoded message to the public key of a batcher.
+/// # Errors
+/// * `InvalidEncryptionKey` if the key is a point of low order, to which messages would be
+///   encrypted with a key anyone can compute.
+pub fn encrypt(message: &[u8], batcher_public_key: &[u8; 32]) -> Result<Vec<u8>, WireError> {
+    let ephemeral_secret = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+    let ephemeral_public_key = PublicKey::from(&ephemeral_secret).to_bytes();
+    let shared_secret = ephemeral_secret.diffie_hellman(&PublicKey::from(*batcher_public_key));
+    if !shared_secret.was_contributory() {
+        return Err(WireError::InvalidEncryptionKey);
+    }
+
+    let ciphertext = cipher(
+        shared_secret.as_bytes(),
+        &ephemeral_public_key,
+        batcher_public_key,
+    )
+    .encrypt(&Nonce::default(), message)
+    .expect("Messages are shorter than the limit of ChaCha20-Poly1305");
+    Ok([&ENCRYPTED_MAGIC[..], &ephemeral_public_key, &ciphertext].concat())
+}
+
+/// Decrypts a message encrypted with `encrypt` to the key of the batcher, returning any other
+/// message as it is.
+/// # Errors
+/// * `Decryption` if the message is encrypted but the batcher has no key, or can't decrypt it
+///   with its key.
+pub fn decrypt(message: Vec<u8>, key: Option<&EncryptionKey>) -> Result<Vec<u8>, WireError> {
+    if !message.starts_with(&ENCRYPTED_MAGIC) {
+        return Ok(message);
+    }
+    key.ok_or(WireError::Decryption)?.decrypt(&message)
+}
+
+/// Returns the key to which the messages sent to a batcher with this handshake are encrypted,
+/// or `None` if the client didn't opt in.
+/// # Errors
+/// * `ProtocolMismatch` if the client opted in but the batcher doesn't support the
+///   `encrypted_payloads` feature or didn't publish its key.
+/// * `ProtocolViolation` if the batcher doesn't present the key set in the configuration.
+pub(crate) fn negotiate(
+    config: Option<&EncryptionConfig>,
+    handshake: &ProtocolHandshake,
+) -> Result<Option<[u8; 32]>, SubmitError> {
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let batcher_public_key = match handshake.encryption_public_key {
+        Some(key) if handshake.supports(ENCRYPTED_PAYLOADS_FEATURE) => key,
+        _ => {
+            return Err(SubmitError::ProtocolMismatch {
+                sdk_version: CURRENT_PROTOCOL_VERSION,
+                batcher_version: handshake.protocol_version,
+                missing_features: vec![ENCRYPTED_PAYLOADS_FEATURE.to_string()],
+            })
+        }
+    };
+    if let Some(expected_key) = config.batcher_public_key {
+        if expected_key != batcher_public_key {
+            return Err(SubmitError::ProtocolViolation(format!(
+                "Batcher presented the encryption key {} instead of {}",
+                hex::encode(batcher_public_key),
+                hex::encode(expected_key)
+            )));
+        }
+    }
+    Ok(Some(batcher_public_key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdk::protocol_handshake;
+
+    #[test]
+    fn test_encrypted_message_is_decrypted() {
+        let key = EncryptionKey::generate();
+        let message = b"{\"verification_data\":{\"pub_input\":[1,2,3]}}".to_vec();
+
+        let encrypted = encrypt(&message, &key.public_key()).unwrap();
+        assert!(!encrypted
+            .windows(message.len())
+            .any(|window| window == message.as_slice()));
+        assert_eq!(decrypt(encrypted, Some(&key)).unwrap(), message);
+
+        // Each message is encrypted with its own ephemeral key
+        assert_ne!(
+            encrypt(&message, &key.public_key()).unwrap(),
+            encrypt(&message, &key.public_key()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_public_key_of_known_key() {
+        // As computed by other X25519 implementations
+        assert_eq!(
+            hex::encode(EncryptionKey::from_bytes([7; 32]).public_key()),
+            "13be4feaeaf204c7fd3358fc9c00721881d174278128227ec674f37f7fe97b6d"
+        );
+    }
+
+    #[test]
+    fn test_unencrypted_messages_are_returned_as_they_are() {
+        let message = b"{\"verification_data\":{}}".to_vec();
+        assert_eq!(decrypt(message.clone(), None).unwrap(), message);
+    }
+
+    #[test]
+    fn test_messages_not_encrypted_to_the_key_are_rejected() {
+        let key = EncryptionKey::generate();
+        let encrypted = encrypt(b"message", &key.public_key()).unwrap();
+
+        let other_key = EncryptionKey::generate();
+        assert!(matches!(
+            decrypt(encrypted.clone(), Some(&other_key)),
+            Err(WireError::Decryption)
+        ));
+        assert!(matches!(
+            decrypt(encrypted.clone(), None),
+            Err(WireError::Decryption)
+        ));
+
+        let mut modified = encrypted.clone();
+        *modified.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            decrypt(modified, Some(&key)),
+            Err(WireError::Decryption)
+        ));
+        assert!(matches!(
+            decrypt(encrypted[..20].to_vec(), Some(&key)),
+            Err(WireError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn test_low_order_keys_are_refused() {
+        assert!(matches!(
+            encrypt(b"message", &[0; 32]),
+            Err(WireError::InvalidEncryptionKey)
+        ));
+    }
+
+    #[test]
+    fn test_encryption_is_negotiated() {
+        let key = EncryptionKey::generate();
+        let mut handshake = protocol_handshake();
+        assert!(negotiate(None, &handshake).unwrap().is_none());
+        assert!(matches!(
+            negotiate(Some(&EncryptionConfig::default()), &handshake),
+            Err(SubmitError::ProtocolMismatch { missing_features, .. })
+                if missing_features == vec![ENCRYPTED_PAYLOADS_FEATURE.to_string()]
+        ));
+
+        key.announce(&mut handshake);
+        assert_eq!(
+            negotiate(Some(&EncryptionConfig::default()), &handshake).unwrap(),
+            Some(key.public_key())
+        );
+        let pinned = EncryptionConfig::default().with_batcher_public_key(key.public_key());
+        assert!(negotiate(Some(&pinned), &handshake).unwrap().is_some());
+
+        let other_key = EncryptionKey::generate().public_key();
+        let pinned = EncryptionConfig::default().with_batcher_public_key(other_key);
+        assert!(matches!(
+            negotiate(Some(&pinned), &handshake),
+            Err(SubmitError::ProtocolViolation(_))
+        ));
+    }
+}
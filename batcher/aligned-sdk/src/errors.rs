@@ -1,10 +1,14 @@
 use core::fmt;
 use ethers::providers::ProviderError;
 use ethers::signers::WalletError;
+use ethers::types::{SignatureError, U256};
 use ethers::utils::hex::FromHexError;
 use std::io;
 use std::path::PathBuf;
 
+use crate::types::{BatchVersion, ProvingSystemId, Rejection, RejectionCode};
+use crate::wire::WireError;
+
 pub enum AlignedError {
     SubmitError(SubmitError),
     VerificationError(VerificationError),
@@ -33,14 +37,64 @@ impl fmt::Debug for AlignedError {
 
 pub enum SubmitError {
     ConnectionError(tokio_tungstenite::tungstenite::Error),
+    /// The TLS handshake with the batcher failed, such as when its certificate is not trusted or
+    /// is not valid for its name.
+    TlsError(String),
+    /// A root certificate of the TLS configuration is not valid.
+    InvalidCertificate(String),
+    /// The batcher refused the connection for lack of a valid access token, or the access token
+    /// can't be sent.
+    Unauthorized(String),
     IoError(PathBuf, io::Error),
     SerdeError(serde_json::Error),
+    InvalidBinaryData(String),
     EthError(String),
+    /// The wallet failed to sign a message, such as a hardware wallet that was disconnected, or
+    /// on which the signature was rejected.
     SignerError(String),
+    InvalidSignature(String),
     MissingParameter(String),
     InvalidProvingSystem(String),
+    /// The verification data lacks an artifact its proving system needs, holds one it doesn't
+    /// take, or an artifact has an invalid size.
+    InvalidVerificationData(String),
+    /// The proof was verified before being submitted, and it, or one of its artifacts, is
+    /// invalid.
+    InvalidProof {
+        proving_system: ProvingSystemId,
+        reason: String,
+    },
     InvalidAddress(String, String),
-    ProtocolVersionMismatch(u16, u16),
+    /// The batcher speaks a protocol the SDK can't submit to: a newer protocol version, or a
+    /// protocol without a feature needed by the messages sent.
+    ProtocolMismatch {
+        sdk_version: u16,
+        batcher_version: u16,
+        missing_features: Vec<String>,
+    },
+    /// The batcher sent an unexpected frame or response, in strict mode.
+    ProtocolViolation(String),
+    /// The batcher closed the connection. Holds the raw close code and reason sent by it,
+    /// see `RejectionCode` for the codes specific to the batcher.
+    BatcherRejection(u16, Vec<u8>),
+    /// The batcher rejected a message, telling why in an `ErrorResponse`. Batchers without the
+    /// `error_responses` feature only close the connection, which is a `BatcherRejection`.
+    MessageRejected(Rejection),
+    /// The balance of the payer in the batcher payment service is below the estimated fee of
+    /// the submission, so its proofs would not be paid for.
+    InsufficientBalance {
+        balance: U256,
+        estimated_fee: U256,
+    },
+    ProvingError(String),
+    /// The batcher endpoints of an environment couldn't be discovered.
+    DiscoveryError(String),
+    /// The client already submitted the proofs within its duplicate window. Holds the leaves of
+    /// their verification data commitments.
+    DuplicateSubmission(Vec<[u8; 32]>),
+    /// The submission would exceed a daily usage limit of the client, see `usage_limits`.
+    UsageLimitExceeded(String),
+    Timeout(String),
     GenericError(String),
 }
 
@@ -56,6 +110,16 @@ impl From<serde_json::Error> for SubmitError {
     }
 }
 
+impl From<WireError> for SubmitError {
+    fn from(e: WireError) -> Self {
+        match e {
+            WireError::Json(e) => SubmitError::SerdeError(e),
+            WireError::InvalidEncryptionKey => SubmitError::ProtocolViolation(format!("{:?}", e)),
+            e => SubmitError::InvalidBinaryData(format!("{:?}", e)),
+        }
+    }
+}
+
 impl From<ProviderError> for SubmitError {
     fn from(e: ProviderError) -> Self {
         SubmitError::EthError(e.to_string())
@@ -68,6 +132,12 @@ impl From<WalletError> for SubmitError {
     }
 }
 
+impl From<SignatureError> for SubmitError {
+    fn from(e: SignatureError) -> Self {
+        SubmitError::InvalidSignature(e.to_string())
+    }
+}
+
 impl From<FromHexError> for SubmitError {
     fn from(e: FromHexError) -> Self {
         SubmitError::EthError(e.to_string())
@@ -85,29 +155,136 @@ impl fmt::Debug for SubmitError {
             SubmitError::ConnectionError(e) => {
                 write!(f, "Web Socket Connection error: {}", e)
             }
-            SubmitError::IoError(path, e) => {
-                write!(f, "IO error for file: \"{}\", {}", path.display(), e)
-            }
+            SubmitError::TlsError(e) => write!(f, "TLS error: {}", e),
+            SubmitError::InvalidCertificate(e) => write!(f, "Invalid root certificate: {}", e),
+            SubmitError::Unauthorized(e) => write!(f, "Unauthorized: {}", e),
+            // Paths that are not valid unicode are shown with their bytes escaped, so that
+            // the failing path can still be told apart.
+            SubmitError::IoError(path, e) => match path.to_str() {
+                Some(path) => write!(f, "IO error for file: \"{}\", {}", path, e),
+                None => write!(f, "IO error for file: {:?}, {}", path, e),
+            },
             SubmitError::SerdeError(e) => write!(f, "Serialization error: {}", e),
+            SubmitError::InvalidBinaryData(e) => write!(f, "Invalid binary data: {}", e),
             SubmitError::EthError(e) => write!(f, "Ethereum error: {}", e),
             SubmitError::SignerError(e) => write!(f, "Signer error: {}", e),
+            SubmitError::InvalidSignature(e) => write!(f, "Invalid signature: {}", e),
+            SubmitError::InvalidVerificationData(e) => {
+                write!(f, "Invalid verification data: {}", e)
+            }
+            SubmitError::InvalidProof {
+                proving_system,
+                reason,
+            } => write!(f, "Invalid {:?} proof: {}", proving_system, reason),
             SubmitError::InvalidProvingSystem(proving_system) => {
                 write!(f, "Invalid proving system: {}", proving_system)
             }
             SubmitError::InvalidAddress(addr, msg) => {
                 write!(f, "Invalid address: {}, {}", addr, msg)
             }
-            SubmitError::ProtocolVersionMismatch(current, expected) => {
-                write!(f, "Protocol version mismatch, SDK should be updated: current version: {} != expected version: {}", current, expected)
+            SubmitError::ProtocolMismatch {
+                sdk_version,
+                batcher_version,
+                missing_features,
+            } if missing_features.is_empty() => {
+                write!(f, "Protocol version mismatch, SDK should be updated: current version: {} != expected version: {}", sdk_version, batcher_version)
+            }
+            SubmitError::ProtocolMismatch {
+                batcher_version,
+                missing_features,
+                ..
+            } => write!(
+                f,
+                "Protocol mismatch, batcher with protocol version {} doesn't support {}",
+                batcher_version,
+                missing_features.join(", ")
+            ),
+            SubmitError::ProtocolViolation(e) => write!(f, "Protocol violation: {}", e),
+            SubmitError::BatcherRejection(code, reason) => {
+                let code_name = RejectionCode::try_from(*code)
+                    .map(|code| format!("{:?}", code))
+                    .unwrap_or_else(|code| code.to_string());
+                write!(
+                    f,
+                    "Batcher rejected the message with code {}: {}",
+                    code_name,
+                    String::from_utf8_lossy(reason)
+                )
+            }
+            SubmitError::MessageRejected(rejection) => {
+                write!(f, "Batcher rejected the message: ")?;
+                match rejection {
+                    Rejection::InvalidSignature => write!(f, "invalid signature"),
+                    Rejection::InsufficientBalance { address } => write!(
+                        f,
+                        "{:?} has no balance in the batcher payment service",
+                        address
+                    ),
+                    Rejection::ProofTooLarge {
+                        proof_size,
+                        max_proof_size,
+                    } => write!(
+                        f,
+                        "proof of {} bytes is larger than the maximum of {} bytes",
+                        proof_size, max_proof_size
+                    ),
+                    Rejection::InvalidProof { proving_system } => {
+                        write!(f, "invalid {:?} proof", proving_system)
+                    }
+                    Rejection::InvalidMessage { reason } => {
+                        write!(f, "invalid message: {}", reason)
+                    }
+                    Rejection::ReplayedMessage { reason } => {
+                        write!(f, "replayed message: {}", reason)
+                    }
+                }
+            }
+            SubmitError::InsufficientBalance {
+                balance,
+                estimated_fee,
+            } => write!(
+                f,
+                "Insufficient balance in the batcher payment service: {} wei, while the submission is estimated to cost up to {} wei",
+                balance, estimated_fee
+            ),
+            SubmitError::ProvingError(e) => write!(f, "Proving error: {}", e),
+            SubmitError::DiscoveryError(e) => {
+                write!(f, "Error while discovering the batcher endpoints: {}", e)
             }
+            SubmitError::DuplicateSubmission(leaves) => write!(
+                f,
+                "{} proofs were already submitted recently by this client: {}",
+                leaves.len(),
+                leaves
+                    .iter()
+                    .map(|leaf| format!("0x{}", hex::encode(leaf)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            SubmitError::UsageLimitExceeded(e) => write!(f, "Usage limit exceeded: {}", e),
+            SubmitError::Timeout(e) => write!(f, "Timeout: {}", e),
             SubmitError::GenericError(e) => write!(f, "Generic error: {}", e),
         }
     }
 }
 
+#[derive(Clone)]
 pub enum VerificationError {
     ParsingError(String),
     EthError(String),
+    UnsupportedBatchVersion(BatchVersion),
+    BatchDataError(String),
+    /// The on-chain call reverted. Holds the decoded revert reason.
+    CallReverted(String),
+    /// There is no contract at the address called, usually because the Ethereum RPC node is of
+    /// another network. Holds the address.
+    ContractNotDeployed(String),
+    /// The address of a contract is not valid. Holds the address and the error.
+    InvalidAddress(String, String),
+    /// No verification task was created for the batch. Holds its merkle root.
+    BatchNotFound([u8; 32]),
+    /// None of the proofs of the batch has the commitment searched. Holds the merkle root.
+    ProofNotInBatch([u8; 32]),
 }
 
 impl fmt::Debug for VerificationError {
@@ -115,6 +292,33 @@ impl fmt::Debug for VerificationError {
         match self {
             VerificationError::ParsingError(e) => write!(f, "Parsing error: {}", e),
             VerificationError::EthError(e) => write!(f, "Ethereum error: {}", e),
+            VerificationError::UnsupportedBatchVersion(version) => write!(
+                f,
+                "Batches of version {:?} can't be verified on-chain",
+                version
+            ),
+            VerificationError::BatchDataError(e) => write!(f, "Batch data error: {}", e),
+            VerificationError::CallReverted(reason) => {
+                write!(f, "On-chain call reverted: {}", reason)
+            }
+            VerificationError::ContractNotDeployed(address) => write!(
+                f,
+                "No contract deployed at {}, the Ethereum RPC node may be of another network",
+                address
+            ),
+            VerificationError::InvalidAddress(address, e) => {
+                write!(f, "Invalid contract address {}: {}", address, e)
+            }
+            VerificationError::BatchNotFound(batch_merkle_root) => write!(
+                f,
+                "No task was created for batch 0x{}",
+                hex::encode(batch_merkle_root)
+            ),
+            VerificationError::ProofNotInBatch(batch_merkle_root) => write!(
+                f,
+                "The proof is not in batch 0x{}",
+                hex::encode(batch_merkle_root)
+            ),
         }
     }
 }
@@ -1,24 +1,199 @@
 use std::str::FromStr;
 use std::sync::Arc;
 
+use ethers::abi::AbiDecode;
 use ethers::prelude::*;
+use ethers::utils::hex;
 
 use crate::errors::VerificationError;
 
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
 abigen!(
     AlignedLayerServiceManagerContract,
     "abi/AlignedLayerServiceManager.json"
 );
 
-type AlignedLayerServiceManager = AlignedLayerServiceManagerContract<Provider<Http>>;
+abigen!(
+    AlignedPubInputOpenerContract,
+    "abi/AlignedPubInputOpener.json"
+);
+
+// Only the functions of the EigenLayer registries used by the SDK
+abigen!(
+    StakeRegistryContract,
+    r#"[
+        function getCurrentTotalStake(uint8 quorumNumber) external view returns (uint96)
+        function getCurrentStake(bytes32 operatorId, uint8 quorumNumber) external view returns (uint96)
+        function getTotalStakeIndicesAtBlockNumber(uint32 blockNumber, bytes quorumNumbers) external view returns (uint32[])
+        function getTotalStakeAtBlockNumberFromIndex(uint8 quorumNumber, uint32 blockNumber, uint256 index) external view returns (uint96)
+    ]"#
+);
+
+abigen!(
+    RegistryCoordinatorContract,
+    r#"[
+        function indexRegistry() external view returns (address)
+        function getOperatorFromId(bytes32 operatorId) external view returns (address)
+    ]"#
+);
+
+abigen!(
+    IndexRegistryContract,
+    r#"[
+        function totalOperatorsForQuorum(uint8 quorumNumber) external view returns (uint32)
+        function getOperatorListAtBlockNumber(uint8 quorumNumber, uint32 blockNumber) external view returns (bytes32[])
+    ]"#
+);
+
+pub type AlignedLayerServiceManager = AlignedLayerServiceManagerContract<Provider<Http>>;
+
+pub type AlignedPubInputOpener = AlignedPubInputOpenerContract<Provider<Http>>;
+
+pub type StakeRegistry = StakeRegistryContract<Provider<Http>>;
+
+pub type RegistryCoordinator = RegistryCoordinatorContract<Provider<Http>>;
+
+pub type IndexRegistry = IndexRegistryContract<Provider<Http>>;
 
 pub async fn aligned_service_manager(
     provider: Provider<Http>,
     contract_address: &str,
 ) -> Result<AlignedLayerServiceManager, VerificationError> {
     let client = Arc::new(provider);
-    let contract_addr = H160::from_str(contract_address)
-        .map_err(|e| VerificationError::ParsingError(e.to_string()))?;
+    let contract_addr = parse_contract_address(contract_address)?;
 
     Ok(AlignedLayerServiceManager::new(contract_addr, client))
 }
+
+pub async fn aligned_pub_input_opener(
+    provider: Provider<Http>,
+    contract_address: &str,
+) -> Result<AlignedPubInputOpener, VerificationError> {
+    let client = Arc::new(provider);
+    let contract_addr = parse_contract_address(contract_address)?;
+
+    Ok(AlignedPubInputOpener::new(contract_addr, client))
+}
+
+/// Parses the address of a contract, such as the one of a `Chain::Custom`.
+pub fn parse_contract_address(contract_address: &str) -> Result<H160, VerificationError> {
+    H160::from_str(contract_address)
+        .map_err(|e| VerificationError::InvalidAddress(contract_address.to_string(), e.to_string()))
+}
+
+/// Turns the error of a call to a contract into a `VerificationError`. If the call reverted, its
+/// revert reason is decoded. Otherwise, the code of the contract is checked, since calling an
+/// address without code returns no data, which fails to be decoded. That usually means the RPC
+/// node is of another network than the contract.
+pub async fn call_error(
+    error: ContractError<Provider<Http>>,
+    provider: &Provider<Http>,
+    contract_address: H160,
+) -> VerificationError {
+    if let Some(revert_data) = error.as_revert() {
+        return VerificationError::CallReverted(decode_revert_reason(revert_data));
+    }
+
+    match provider.get_code(contract_address, None).await {
+        Ok(code) if code.is_empty() => {
+            VerificationError::ContractNotDeployed(format!("{:?}", contract_address))
+        }
+        _ => VerificationError::EthError(error.to_string()),
+    }
+}
+
+/// Decodes the reason of a reverted call from its revert data: the message of a `require` or
+/// `revert` with a reason, the code of a panic, or the selector of a custom error.
+pub fn decode_revert_reason(revert_data: &[u8]) -> String {
+    if revert_data.is_empty() {
+        return "reverted without a reason".to_string();
+    }
+
+    if let Some(reason) = String::decode_with_selector(revert_data) {
+        return reason;
+    }
+
+    if let Some(code) = revert_data
+        .strip_prefix(&PANIC_SELECTOR)
+        .and_then(|code| U256::decode(code).ok())
+    {
+        return format!("panic 0x{:02x}: {}", code, panic_description(code));
+    }
+
+    match revert_data.split_first_chunk::<4>() {
+        Some((selector, args)) => format!(
+            "custom error with selector 0x{} and arguments 0x{}",
+            hex::encode(selector),
+            hex::encode(args)
+        ),
+        None => format!("invalid revert data 0x{}", hex::encode(revert_data)),
+    }
+}
+
+fn panic_description(code: U256) -> &'static str {
+    if code > U256::from(u8::MAX) {
+        return "unknown panic";
+    }
+
+    match code.low_u64() {
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array",
+        0x31 => "pop on an empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to an uninitialized function",
+        _ => "unknown panic",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::abi::AbiEncode;
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let reason =
+            "Merkle.processInclusionProofKeccak: proof length should be a non-zero multiple of 32";
+        let error_string = [String::selector().to_vec(), reason.to_string().encode()].concat();
+        assert_eq!(decode_revert_reason(&error_string), reason);
+
+        let panic = [PANIC_SELECTOR.to_vec(), U256::from(0x32).encode()].concat();
+        assert_eq!(
+            decode_revert_reason(&panic),
+            "panic 0x32: array index out of bounds"
+        );
+
+        assert_eq!(
+            decode_revert_reason(&[0xde, 0xad, 0xbe, 0xef, 0x01]),
+            "custom error with selector 0xdeadbeef and arguments 0x01"
+        );
+        assert_eq!(decode_revert_reason(&[]), "reverted without a reason");
+        assert_eq!(
+            decode_revert_reason(&[0x01, 0x02]),
+            "invalid revert data 0x0102"
+        );
+    }
+
+    #[test]
+    fn test_custom_chain_contract_address() {
+        let chain = crate::types::Chain::Custom(crate::types::NetworkEndpoints {
+            aligned_service_manager: "0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8".to_string(),
+            ..crate::types::Chain::Holesky.endpoints()
+        });
+        let address = crate::sdk::get_aligned_service_manager_address(&chain);
+        assert_eq!(
+            parse_contract_address(address).unwrap(),
+            H160::from_str("0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8").unwrap()
+        );
+
+        assert!(matches!(
+            parse_contract_address("0x1613"),
+            Err(VerificationError::InvalidAddress(address, _)) if address == "0x1613"
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! Fees paid for the proofs of a batch from the balances of their submitters in the batcher
+//! payment service. When a batch is sent, the payment service charges each of its proofs
+//! `gas_per_proof` times the gas price of the transaction, so the fee of a proof falls as more
+//! proofs share the constant cost of the batch.
+
+use ethers::types::{Address, U256};
+
+use crate::errors::{SubmitError, VerificationError};
+use crate::rpc::AlignedRpcClient;
+
+/// Gas paid to the aggregator for verifying the batch.
+pub const AGGREGATOR_COST: u128 = 400000;
+/// Gas of the transaction creating the verification task of the batch, before its proofs.
+pub const BATCHER_SUBMISSION_BASE_COST: u128 = 100000;
+/// Gas added to the transaction creating the verification task by each proof of the batch.
+pub const ADDITIONAL_SUBMISSION_COST_PER_PROOF: u128 = 1325;
+/// Gas of a batch shared by all of its proofs.
+pub const CONSTANT_COST: u128 = AGGREGATOR_COST + BATCHER_SUBMISSION_BASE_COST;
+
+/// Returns the gas charged to each proof of a batch with the given number of proofs.
+pub fn gas_per_proof(num_proofs_in_batch: usize) -> u128 {
+    let num_proofs_in_batch = num_proofs_in_batch.max(1) as u128;
+    (CONSTANT_COST + ADDITIONAL_SUBMISSION_COST_PER_PROOF * num_proofs_in_batch)
+        / num_proofs_in_batch
+}
+
+/// Estimates the fee charged for submitting the given number of proofs at the given gas price.
+/// The estimate assumes the proofs are the only ones of their batch, which is the most they can
+/// be charged: proofs of other users sharing the batch lower the fee.
+pub fn max_submission_fee(num_proofs: usize, gas_price: U256) -> U256 {
+    U256::from(gas_per_proof(num_proofs)) * U256::from(num_proofs) * gas_price
+}
+
+/// Balance of a payer in the batcher payment service and the estimated fee of a submission.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceCheck {
+    pub balance: U256,
+    pub gas_price: U256,
+    pub estimated_fee: U256,
+}
+
+impl BalanceCheck {
+    pub fn is_sufficient(&self) -> bool {
+        self.balance >= self.estimated_fee
+    }
+
+    /// # Errors
+    /// * `InsufficientBalance` if the balance is below the estimated fee.
+    pub fn ensure_sufficient(&self) -> Result<(), SubmitError> {
+        if self.is_sufficient() {
+            return Ok(());
+        }
+        Err(SubmitError::InsufficientBalance {
+            balance: self.balance,
+            estimated_fee: self.estimated_fee,
+        })
+    }
+}
+
+/// Queries the balance of the payer in the batcher payment service and estimates the fee of
+/// submitting the given number of proofs at the current gas price, so that an underfunded
+/// submission can be stopped before it is sent.
+/// # Arguments
+/// * `rpc_client` - The client of the Ethereum RPC node.
+/// * `batcher_payment_service` - The address of the batcher payment service contract.
+/// * `payer` - The address paying for the proofs.
+/// * `num_proofs` - The number of proofs to submit.
+/// # Returns
+/// * The balance of the payer, the gas price and the estimated fee.
+/// # Errors
+/// * `EthError` if there is an error querying the balance or the gas price.
+pub async fn check_balance(
+    rpc_client: &AlignedRpcClient,
+    batcher_payment_service: Address,
+    payer: Address,
+    num_proofs: usize,
+) -> Result<BalanceCheck, VerificationError> {
+    let balance = rpc_client
+        .user_balance(batcher_payment_service, payer)
+        .await?;
+    let gas_price = rpc_client.gas_price().await?;
+
+    Ok(BalanceCheck {
+        balance,
+        gas_price,
+        estimated_fee: max_submission_fee(num_proofs, gas_price),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gas_per_proof_shares_the_constant_cost() {
+        assert_eq!(gas_per_proof(1), 501325);
+        assert_eq!(gas_per_proof(100), 6325);
+        assert_eq!(gas_per_proof(0), gas_per_proof(1));
+    }
+
+    #[test]
+    fn test_max_submission_fee() {
+        let gas_price = U256::from(2_000_000_000u64);
+        assert_eq!(
+            max_submission_fee(100, gas_price),
+            U256::from(6325u64 * 100 * 2_000_000_000)
+        );
+        assert!(max_submission_fee(1, gas_price) > max_submission_fee(1, U256::one()));
+        assert_eq!(max_submission_fee(0, gas_price), U256::zero());
+    }
+
+    #[test]
+    fn test_balance_check() {
+        let check = BalanceCheck {
+            balance: U256::from(10),
+            gas_price: U256::one(),
+            estimated_fee: U256::from(10),
+        };
+        assert!(check.is_sufficient());
+        assert!(check.ensure_sufficient().is_ok());
+
+        let check = BalanceCheck {
+            balance: U256::from(9),
+            ..check
+        };
+        assert!(!check.is_sufficient());
+        assert!(matches!(
+            check.ensure_sufficient(),
+            Err(SubmitError::InsufficientBalance { balance, estimated_fee })
+                if balance == U256::from(9) && estimated_fee == U256::from(10)
+        ));
+    }
+}
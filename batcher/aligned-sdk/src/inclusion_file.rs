@@ -0,0 +1,523 @@
+//! Reading and writing of the files holding the `AlignedVerificationData` of a submitted proof.
+//! Files are JSON, as saved by the CLI, or a compact binary encoding. The format of a file is
+//! detected from its contents, so files can be loaded without knowing which one was used.
+//!
+//! Binary files start with `BINARY_MAGIC` followed by the format version, which can't be the
+//! start of a JSON document. The rest of the file holds, in order:
+//! * Since format version 3, the schema version, as 1 or 2.
+//! * The batch version, as 1 or 2.
+//! * The proof, public input and proving system auxiliary data commitments, 32 bytes each.
+//! * The proof generator address, 20 bytes.
+//! * The batch merkle root, 32 bytes.
+//! * The index in the batch, as a big endian u64.
+//! * In format version 2, the batch metadata: the block number of the creation of the batch,
+//!   as a big endian u64, and its transaction hash, 32 bytes, then 1 if the batch is verified,
+//!   followed by the block number and transaction hash of its verification, or 0 otherwise.
+//!   Since format version 3, 1 followed by the batch metadata if there is one, or 0 otherwise.
+//! * Since format version 4, 1 followed by the metadata of the verification data, as a big endian
+//!   u32 length and its bytes, if there is one, or 0 otherwise.
+//! * The number of nodes of the inclusion proof, as a big endian u32, followed by the nodes,
+//!   32 bytes each.
+//!
+//! Files of schema V1 without batch metadata nor metadata are written in format version 1, so
+//! previous releases can read them. Files of format versions 1 and 2 are loaded as schema V1.
+
+use std::path::Path;
+
+use lambdaworks_crypto::merkle_tree::proof::Proof;
+
+use crate::errors::SubmitError;
+use crate::types::{
+    AlignedVerificationData, BatchMetadata, BatchVersion, EventLocation, SchemaVersion,
+    VerificationDataCommitment,
+};
+
+pub const BINARY_MAGIC: &[u8; 4] = b"ALVD";
+/// Latest format version, the one of files with the metadata of their verification data.
+pub const BINARY_FORMAT_VERSION: u8 = 4;
+const BINARY_FORMAT_VERSION_WITH_SCHEMA_VERSION: u8 = 3;
+const BINARY_FORMAT_VERSION_WITH_METADATA: u8 = 2;
+const BINARY_FORMAT_VERSION_WITHOUT_METADATA: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Json,
+    Binary,
+}
+
+impl FileFormat {
+    /// Detects the format of the contents of a file from its first bytes.
+    pub fn detect(bytes: &[u8]) -> FileFormat {
+        if bytes.starts_with(BINARY_MAGIC) {
+            FileFormat::Binary
+        } else {
+            FileFormat::Json
+        }
+    }
+}
+
+/// Reads an aligned verification data from a file of any format.
+/// # Arguments
+/// * `path` - The path of the file.
+/// # Returns
+/// * The aligned verification data in the file.
+/// # Errors
+/// * `IoError` if the file can't be read.
+/// * `SerdeError` if the file is not a valid JSON aligned verification data.
+/// * `InvalidBinaryData` if the file is not a valid binary aligned verification data.
+pub fn read(path: &Path) -> Result<AlignedVerificationData, SubmitError> {
+    let bytes = std::fs::read(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))?;
+    from_bytes(&bytes)
+}
+
+/// Writes an aligned verification data to a file in the given format.
+/// # Errors
+/// * `IoError` if the file can't be written.
+/// * `SerdeError` if the aligned verification data can't be serialized to JSON.
+pub fn write(
+    path: &Path,
+    aligned_verification_data: &AlignedVerificationData,
+    format: FileFormat,
+) -> Result<(), SubmitError> {
+    let bytes = match format {
+        FileFormat::Json => serde_json::to_vec(aligned_verification_data)?,
+        FileFormat::Binary => to_binary(aligned_verification_data),
+    };
+    std::fs::write(path, bytes).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))
+}
+
+/// Migrates the aligned verification data of a file to the latest schema, see
+/// `AlignedVerificationData::migrate`, rewriting the file in its format if it changed.
+/// # Returns
+/// * Whether the file was of an older schema.
+/// # Errors
+/// * The errors of `read`, and of `write` when rewriting.
+pub fn migrate_file(path: &Path) -> Result<bool, SubmitError> {
+    let bytes = std::fs::read(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))?;
+    let mut aligned_verification_data = from_bytes(&bytes)?;
+
+    let migrated = aligned_verification_data.migrate();
+    if migrated {
+        write(path, &aligned_verification_data, FileFormat::detect(&bytes))?;
+    }
+    Ok(migrated)
+}
+
+/// Parses an aligned verification data from the contents of a file of any format.
+/// # Errors
+/// * `SerdeError` if the contents are not a valid JSON aligned verification data.
+/// * `InvalidBinaryData` if the contents are not a valid binary aligned verification data.
+pub fn from_bytes(bytes: &[u8]) -> Result<AlignedVerificationData, SubmitError> {
+    match FileFormat::detect(bytes) {
+        FileFormat::Json => Ok(serde_json::from_slice(bytes)?),
+        FileFormat::Binary => from_binary(bytes),
+    }
+}
+
+/// Encodes an aligned verification data in the binary format.
+pub fn to_binary(aligned_verification_data: &AlignedVerificationData) -> Vec<u8> {
+    let commitment = &aligned_verification_data.verification_data_commitment;
+    let merkle_path = &aligned_verification_data.batch_inclusion_proof.merkle_path;
+
+    let format_version = match aligned_verification_data {
+        AlignedVerificationData {
+            schema_version: SchemaVersion::V1,
+            batch_metadata: None,
+            metadata: None,
+            ..
+        } => BINARY_FORMAT_VERSION_WITHOUT_METADATA,
+        _ => BINARY_FORMAT_VERSION,
+    };
+
+    let mut bytes = Vec::with_capacity(
+        4 + 1
+            + 1
+            + 1
+            + 3 * 32
+            + 20
+            + 32
+            + 8
+            + (1 + 8 + 32 + 1 + 8 + 32)
+            + aligned_verification_data
+                .metadata
+                .as_ref()
+                .map_or(1, |metadata| 1 + 4 + metadata.len())
+            + 4
+            + 32 * merkle_path.len(),
+    );
+    bytes.extend_from_slice(BINARY_MAGIC);
+    bytes.push(format_version);
+    if format_version == BINARY_FORMAT_VERSION {
+        bytes.push(match aligned_verification_data.schema_version {
+            SchemaVersion::V1 => 1,
+            SchemaVersion::V2 => 2,
+        });
+    }
+    bytes.push(match aligned_verification_data.batch_version {
+        BatchVersion::V1 => 1,
+        BatchVersion::V2 => 2,
+    });
+    bytes.extend_from_slice(&commitment.proof_commitment);
+    bytes.extend_from_slice(&commitment.pub_input_commitment);
+    bytes.extend_from_slice(&commitment.proving_system_aux_data_commitment);
+    bytes.extend_from_slice(&commitment.proof_generator_addr);
+    bytes.extend_from_slice(&aligned_verification_data.batch_merkle_root);
+    bytes.extend_from_slice(&(aligned_verification_data.index_in_batch as u64).to_be_bytes());
+    if format_version == BINARY_FORMAT_VERSION {
+        match &aligned_verification_data.batch_metadata {
+            Some(batch_metadata) => {
+                bytes.push(1);
+                push_batch_metadata(&mut bytes, batch_metadata);
+            }
+            None => bytes.push(0),
+        }
+        match &aligned_verification_data.metadata {
+            Some(metadata) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(metadata.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(metadata);
+            }
+            None => bytes.push(0),
+        }
+    }
+    bytes.extend_from_slice(&(merkle_path.len() as u32).to_be_bytes());
+    for node in merkle_path {
+        bytes.extend_from_slice(node);
+    }
+    bytes
+}
+
+fn push_batch_metadata(bytes: &mut Vec<u8>, batch_metadata: &BatchMetadata) {
+    push_event_location(bytes, &batch_metadata.created);
+    match &batch_metadata.verified {
+        Some(verified) => {
+            bytes.push(1);
+            push_event_location(bytes, verified);
+        }
+        None => bytes.push(0),
+    }
+}
+
+fn push_event_location(bytes: &mut Vec<u8>, event_location: &EventLocation) {
+    bytes.extend_from_slice(&event_location.block_number.to_be_bytes());
+    bytes.extend_from_slice(event_location.tx_hash.as_bytes());
+}
+
+fn from_binary(bytes: &[u8]) -> Result<AlignedVerificationData, SubmitError> {
+    let mut reader = BinaryReader { bytes };
+
+    reader.take::<4>()?;
+    let format_version = reader.take::<1>()?[0];
+    if format_version > BINARY_FORMAT_VERSION || format_version == 0 {
+        return Err(SubmitError::InvalidBinaryData(format!(
+            "Unsupported format version {}, the SDK should be updated",
+            format_version
+        )));
+    }
+    let schema_version = if format_version >= BINARY_FORMAT_VERSION_WITH_SCHEMA_VERSION {
+        match reader.take::<1>()?[0] {
+            1 => SchemaVersion::V1,
+            2 => SchemaVersion::V2,
+            schema_version => {
+                return Err(SubmitError::InvalidBinaryData(format!(
+                    "Unknown schema version {}, the SDK should be updated",
+                    schema_version
+                )))
+            }
+        }
+    } else {
+        SchemaVersion::V1
+    };
+
+    let batch_version = match reader.take::<1>()?[0] {
+        1 => BatchVersion::V1,
+        2 => BatchVersion::V2,
+        batch_version => {
+            return Err(SubmitError::InvalidBinaryData(format!(
+                "Unknown batch version {}",
+                batch_version
+            )))
+        }
+    };
+    let verification_data_commitment = VerificationDataCommitment {
+        proof_commitment: reader.take()?,
+        pub_input_commitment: reader.take()?,
+        proving_system_aux_data_commitment: reader.take()?,
+        proof_generator_addr: reader.take()?,
+    };
+    let batch_merkle_root = reader.take()?;
+    let index_in_batch = usize::try_from(u64::from_be_bytes(reader.take()?))
+        .map_err(|_| SubmitError::InvalidBinaryData("Index in batch is too large".to_string()))?;
+    let batch_metadata = match format_version {
+        BINARY_FORMAT_VERSION_WITHOUT_METADATA => None,
+        BINARY_FORMAT_VERSION_WITH_METADATA => Some(reader.take_batch_metadata()?),
+        _ => match reader.take::<1>()?[0] {
+            0 => None,
+            1 => Some(reader.take_batch_metadata()?),
+            flag => {
+                return Err(SubmitError::InvalidBinaryData(format!(
+                    "Invalid batch metadata flag {}",
+                    flag
+                )))
+            }
+        },
+    };
+    let metadata = if format_version == BINARY_FORMAT_VERSION {
+        match reader.take::<1>()?[0] {
+            0 => None,
+            1 => {
+                let metadata_len = u32::from_be_bytes(reader.take()?) as usize;
+                Some(reader.take_slice(metadata_len)?.to_vec())
+            }
+            flag => {
+                return Err(SubmitError::InvalidBinaryData(format!(
+                    "Invalid metadata flag {}",
+                    flag
+                )))
+            }
+        }
+    } else {
+        None
+    };
+
+    let merkle_path_len = u32::from_be_bytes(reader.take()?) as usize;
+    if reader.bytes.len() != merkle_path_len * 32 {
+        return Err(SubmitError::InvalidBinaryData(format!(
+            "Expected {} bytes of inclusion proof, found {}",
+            merkle_path_len * 32,
+            reader.bytes.len()
+        )));
+    }
+    let merkle_path = (0..merkle_path_len)
+        .map(|_| reader.take())
+        .collect::<Result<_, _>>()?;
+
+    Ok(AlignedVerificationData {
+        verification_data_commitment,
+        batch_merkle_root,
+        batch_inclusion_proof: Proof { merkle_path },
+        index_in_batch,
+        batch_version,
+        batch_metadata,
+        metadata,
+        schema_version,
+    })
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SubmitError> {
+        Ok(self.take_slice(N)?.try_into().expect("Slice has N bytes"))
+    }
+
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], SubmitError> {
+        if self.bytes.len() < len {
+            return Err(SubmitError::InvalidBinaryData(
+                "Unexpected end of data".to_string(),
+            ));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn take_batch_metadata(&mut self) -> Result<BatchMetadata, SubmitError> {
+        let created = self.take_event_location()?;
+        let verified = match self.take::<1>()?[0] {
+            0 => None,
+            1 => Some(self.take_event_location()?),
+            flag => {
+                return Err(SubmitError::InvalidBinaryData(format!(
+                    "Invalid verified flag {}",
+                    flag
+                )))
+            }
+        };
+        Ok(BatchMetadata { created, verified })
+    }
+
+    fn take_event_location(&mut self) -> Result<EventLocation, SubmitError> {
+        Ok(EventLocation {
+            block_number: u64::from_be_bytes(self.take()?),
+            tx_hash: self.take::<32>()?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn files_of_previous_releases() -> Vec<Vec<u8>> {
+        let dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_files/aligned_verification_data");
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| std::fs::read(entry.unwrap().path()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_both_formats_load_the_same_data() {
+        for json in files_of_previous_releases() {
+            assert_eq!(FileFormat::detect(&json), FileFormat::Json);
+            let aligned_verification_data = from_bytes(&json).unwrap();
+
+            let binary = to_binary(&aligned_verification_data);
+            assert_eq!(FileFormat::detect(&binary), FileFormat::Binary);
+
+            assert_eq!(
+                serde_json::to_value(from_bytes(&binary).unwrap()).unwrap(),
+                serde_json::to_value(&aligned_verification_data).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_metadata_is_kept_in_both_formats() {
+        let json = &files_of_previous_releases()[0];
+        let mut aligned_verification_data = from_bytes(json).unwrap();
+        // Files without metadata can still be read by previous releases
+        assert_eq!(
+            to_binary(&aligned_verification_data)[4],
+            BINARY_FORMAT_VERSION_WITHOUT_METADATA
+        );
+
+        let created = EventLocation {
+            block_number: 1_572_531,
+            tx_hash: [1; 32].into(),
+        };
+        for verified in [
+            None,
+            Some(EventLocation {
+                block_number: 1_572_533,
+                tx_hash: [2; 32].into(),
+            }),
+        ] {
+            aligned_verification_data.batch_metadata = Some(BatchMetadata { created, verified });
+
+            let binary = to_binary(&aligned_verification_data);
+            assert_eq!(binary[4], BINARY_FORMAT_VERSION);
+            let json = serde_json::to_vec(&aligned_verification_data).unwrap();
+
+            // Files of format version 2 don't have the schema version nor the metadata flags
+            let merkle_path_len = aligned_verification_data
+                .batch_inclusion_proof
+                .merkle_path
+                .len();
+            let mut format_2_binary = binary.clone();
+            format_2_binary[4] = BINARY_FORMAT_VERSION_WITH_METADATA;
+            format_2_binary.remove(binary.len() - 32 * merkle_path_len - 4 - 1);
+            format_2_binary.remove(4 + 1 + 1 + 1 + 3 * 32 + 20 + 32 + 8);
+            format_2_binary.remove(5);
+
+            for bytes in [binary, format_2_binary, json] {
+                let loaded = from_bytes(&bytes).unwrap();
+                assert_eq!(
+                    loaded.batch_metadata,
+                    aligned_verification_data.batch_metadata
+                );
+                assert_eq!(loaded.schema_version, SchemaVersion::V1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_metadata_is_kept_in_both_formats() {
+        let json = &files_of_previous_releases()[0];
+        let mut aligned_verification_data = from_bytes(json).unwrap();
+        aligned_verification_data.metadata = Some(b"job-42".to_vec());
+
+        let binary = to_binary(&aligned_verification_data);
+        assert_eq!(binary[4], BINARY_FORMAT_VERSION);
+        let json = serde_json::to_vec(&aligned_verification_data).unwrap();
+        for bytes in [binary, json] {
+            let loaded = from_bytes(&bytes).unwrap();
+            assert_eq!(loaded.metadata, aligned_verification_data.metadata);
+            assert_eq!(loaded.batch_metadata, None);
+        }
+    }
+
+    #[test]
+    fn test_files_of_previous_releases_are_migrated_in_their_format() {
+        let dir = std::env::temp_dir().join(format!("aligned-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = &files_of_previous_releases()[0];
+        assert_eq!(from_bytes(json).unwrap().schema_version, SchemaVersion::V1);
+
+        for (name, contents) in [
+            ("aligned_verification_data.json", json.clone()),
+            (
+                "aligned_verification_data.bin",
+                to_binary(&from_bytes(json).unwrap()),
+            ),
+        ] {
+            let path = dir.join(name);
+            std::fs::write(&path, &contents).unwrap();
+
+            assert!(migrate_file(&path).unwrap());
+            let migrated = std::fs::read(&path).unwrap();
+            assert_eq!(FileFormat::detect(&migrated), FileFormat::detect(&contents));
+            assert_eq!(
+                from_bytes(&migrated).unwrap().schema_version,
+                AlignedVerificationData::SCHEMA_VERSION
+            );
+            assert!(!migrate_file(&path).unwrap());
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_newer_schema_versions_are_rejected() {
+        let mut json: serde_json::Value =
+            serde_json::from_slice(&files_of_previous_releases()[0]).unwrap();
+        json["schema_version"] = "V2".into();
+        assert_eq!(
+            from_bytes(&serde_json::to_vec(&json).unwrap())
+                .unwrap()
+                .schema_version,
+            SchemaVersion::V2
+        );
+
+        json["schema_version"] = "V3".into();
+        assert!(matches!(
+            from_bytes(&serde_json::to_vec(&json).unwrap()),
+            Err(SubmitError::SerdeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_binary_data_is_rejected() {
+        let json = &files_of_previous_releases()[0];
+        let binary = to_binary(&from_bytes(json).unwrap());
+
+        let mut unsupported_version = binary.clone();
+        unsupported_version[4] = BINARY_FORMAT_VERSION + 1;
+        let mut unknown_batch_version = binary.clone();
+        unknown_batch_version[5] = 3;
+        let mut trailing_bytes = binary.clone();
+        trailing_bytes.push(0);
+        let mut migrated = from_bytes(json).unwrap();
+        migrated.migrate();
+        let mut unknown_schema_version = to_binary(&migrated);
+        unknown_schema_version[5] = 3;
+
+        for invalid in [
+            &binary[..binary.len() - 1],
+            &binary[..10],
+            &unsupported_version,
+            &unknown_batch_version,
+            &unknown_schema_version,
+            &trailing_bytes,
+        ] {
+            assert!(matches!(
+                from_bytes(invalid),
+                Err(SubmitError::InvalidBinaryData(_))
+            ));
+        }
+    }
+}
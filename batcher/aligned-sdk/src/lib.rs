@@ -1,5 +1,51 @@
+pub mod auth;
+pub mod batch_metadata;
+pub mod batch_status;
+pub mod bug_report;
+pub mod build_info;
+pub mod canonical_json;
+pub mod chunks;
+pub mod client;
+mod clock;
+pub mod discovery;
+pub mod duplicates;
+pub mod encryption;
 pub mod errors;
-mod eth;
+pub mod eth;
+pub mod fees;
+pub mod inclusion_file;
+pub mod lifecycle;
+#[cfg(feature = "local-verification")]
+pub mod local_verification;
+pub mod merkle;
+#[cfg(feature = "test-utils")]
+pub mod mock_batcher;
+pub mod multiplex;
+pub mod pending;
+pub mod pipeline;
+pub mod quorum;
+pub mod recovery;
+pub mod retry;
+#[cfg(feature = "risc0")]
+pub mod risc0;
+pub mod rpc;
 pub mod sdk;
+mod serde_compat;
+pub mod session;
+pub mod shared_pub_input;
+pub mod signers;
+#[cfg(feature = "sp1")]
+pub mod sp1;
+pub mod submission_status;
+pub mod tags;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod tls;
 pub mod types;
+pub mod usage_limits;
 pub mod utils;
+pub mod wire;
+
+#[cfg(feature = "local-verification")]
+pub use local_verification::verify_local;
+pub use submission_status::get_submission_status;
@@ -0,0 +1,125 @@
+//! The lifecycle of submitted proofs, for applications showing their users how far each proof
+//! has gone: sent to the batcher, accepted in a batch, batch posted, and verified on-chain.
+//!
+//! `submit_and_track` reports each transition as a `SubmissionEvent` with the time it was
+//! observed, read from the clock of the client. Proofs are `Sent` when the last frame of their
+//! message is sent, and `Accepted` when a response with valid batch inclusion data for them is
+//! received. The batches are then checked on-chain at the polling interval of the
+//! `VerificationWaitConfig`, so `BatchPosted` and `BatchVerified` are reported when the batch is
+//! first seen in that state, up to an interval after it happened. Batches are waited for one at a
+//! time, in the order of their first proof, so a batch verified before an earlier one is reported
+//! once the earlier one is verified.
+
+use std::time::SystemTime;
+
+use ethers::signers::Signer;
+use ethers::types::H256;
+use log::debug;
+
+use crate::bug_report::Transcript;
+use crate::errors::AlignedError;
+use crate::rpc::AlignedRpcClient;
+use crate::sdk::{
+    connect, sign_with_signer, submit_with_progress, wait_verification, SubmissionProgress,
+    VerificationWaitConfig, VerifiedProof,
+};
+use crate::tls::TlsConfig;
+use crate::types::{Chain, VerificationData};
+
+/// A transition in the lifecycle of the submitted proofs. Proofs are identified by their
+/// position in the submitted verification data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmissionEvent {
+    /// The message of the proof was sent to the batcher.
+    Sent { position: usize, at: SystemTime },
+    /// The batcher included the proof in the batch with this merkle root.
+    Accepted {
+        position: usize,
+        batch_merkle_root: [u8; 32],
+        at: SystemTime,
+    },
+    /// The task of the batch was created on-chain in this block.
+    BatchPosted {
+        batch_merkle_root: [u8; 32],
+        task_created_block: u32,
+        at: SystemTime,
+    },
+    /// The batch was verified on-chain in this transaction.
+    BatchVerified {
+        batch_merkle_root: [u8; 32],
+        verification_tx_hash: H256,
+        at: SystemTime,
+    },
+    /// The proof was checked to be included in its verified batch.
+    Verified {
+        position: usize,
+        batch_merkle_root: [u8; 32],
+        verification_tx_hash: H256,
+        at: SystemTime,
+    },
+}
+
+/// Submits multiple proofs to the batcher and waits until they are verified on-chain like
+/// `submit_and_wait_verification`, reporting each transition of their lifecycle to `on_event`
+/// as it is observed.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proofs will be submitted.
+/// * `verification_data` - An array of verification data of each proof.
+/// * `wallet` - The wallet used to sign the proofs.
+/// * `chain` - The chain on which the proofs are verified.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// * `wait_config` - The interval at which the batches are checked and the maximum time waited.
+/// * `on_event` - Called with each `SubmissionEvent`, in the order they are observed.
+/// # Returns
+/// * The aligned verification data of each proof, with the hash of the transaction in which its
+///   batch was verified. `None` if the batcher didn't respond to every proof, in which case the
+///   batches of the accepted proofs are not waited for.
+/// # Errors
+/// * The same as `submit_and_wait_verification`.
+pub async fn submit_and_track<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: S,
+    chain: Chain,
+    eth_rpc_url: &str,
+    wait_config: &VerificationWaitConfig,
+    on_event: impl Fn(SubmissionEvent) + Sync,
+) -> Result<Option<Vec<VerifiedProof>>, AlignedError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+    let mut progress = SubmissionProgress {
+        on_event: Some(&on_event),
+        ..Default::default()
+    };
+    let all_responded = submit_with_progress(
+        ws_stream,
+        verification_data,
+        |message| sign_with_signer(&wallet, message),
+        false,
+        false,
+        &mut Transcript::new(),
+        &mut progress,
+        None,
+        None,
+    )
+    .await?;
+    if !all_responded {
+        debug!(
+            "Only {} of {} proofs were responded",
+            progress.responded,
+            verification_data.len()
+        );
+        return Ok(None);
+    }
+
+    let verified_proofs = wait_verification(
+        progress.aligned_verification_data,
+        chain,
+        &rpc_client,
+        wait_config,
+        Some(&on_event),
+    )
+    .await?;
+    Ok(Some(verified_proofs))
+}
@@ -0,0 +1,188 @@
+//! Verification of Groth16 proofs over BN254 made with gnark, whose proofs, verification keys
+//! and public witnesses are read from gnark's binary encoding into arkworks types.
+
+use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+use ark_ff::PrimeField;
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+
+const G1_SIZE: usize = 32;
+const G2_SIZE: usize = 64;
+const FR_SIZE: usize = 32;
+
+/// Flags of gnark in the two most significant bits of a compressed point.
+const GNARK_FLAGS_MASK: u8 = 0b11 << 6;
+const GNARK_COMPRESSED_SMALLEST: u8 = 0b10 << 6;
+const GNARK_COMPRESSED_LARGEST: u8 = 0b11 << 6;
+const GNARK_COMPRESSED_INFINITY: u8 = 0b01 << 6;
+
+/// Flags of arkworks in the two most significant bits of the last byte of a compressed point.
+const ARK_Y_IS_NEGATIVE: u8 = 1 << 7;
+const ARK_INFINITY: u8 = 1 << 6;
+
+/// Verifies a gnark Groth16 proof over BN254. Proofs of circuits with commitments are not
+/// supported, as gnark checks them with its own Pedersen proof of knowledge.
+pub(super) fn verify(
+    proof: &[u8],
+    public_input: &[u8],
+    verification_key: &[u8],
+) -> Result<(), String> {
+    let vk = read_verifying_key(verification_key)?;
+    let proof = read_proof(proof)?;
+    let public_inputs = read_public_witness(public_input)?;
+    if public_inputs.len() + 1 != vk.gamma_abc_g1.len() {
+        return Err(format!(
+            "the verification key takes {} public inputs, but {} were given",
+            vk.gamma_abc_g1.len().saturating_sub(1),
+            public_inputs.len()
+        ));
+    }
+
+    let pvk = prepare_verifying_key(&vk);
+    match Groth16::<Bn254>::verify_proof(&pvk, &proof, &public_inputs) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("the proof doesn't verify".to_string()),
+        Err(e) => Err(format!("the proof can't be verified: {}", e)),
+    }
+}
+
+/// Reads the verification key as written by gnark: alpha and beta in G1, beta and gamma in G2,
+/// delta in G1 and in G2, and the points of the public inputs. What follows, the commitment
+/// keys, is ignored.
+fn read_verifying_key(bytes: &[u8]) -> Result<VerifyingKey<Bn254>, String> {
+    let mut reader = GnarkReader::new(bytes, "verification key");
+    let alpha_g1 = reader.g1()?;
+    let _beta_g1 = reader.g1()?;
+    let beta_g2 = reader.g2()?;
+    let gamma_g2 = reader.g2()?;
+    let _delta_g1 = reader.g1()?;
+    let delta_g2 = reader.g2()?;
+    let gamma_abc_g1 = (0..reader.u32()?)
+        .map(|_| reader.g1())
+        .collect::<Result<_, _>>()?;
+    Ok(VerifyingKey {
+        alpha_g1,
+        beta_g2,
+        gamma_g2,
+        delta_g2,
+        gamma_abc_g1,
+    })
+}
+
+/// Reads the proof as written by gnark: A in G1, B in G2 and C in G1, followed by the
+/// commitments of the circuit when it's written by a gnark version with them.
+fn read_proof(bytes: &[u8]) -> Result<Proof<Bn254>, String> {
+    let mut reader = GnarkReader::new(bytes, "proof");
+    let proof = Proof {
+        a: reader.g1()?,
+        b: reader.g2()?,
+        c: reader.g1()?,
+    };
+    if !reader.is_empty() && reader.u32()? != 0 {
+        return Err("proofs of circuits with commitments can't be verified locally".to_string());
+    }
+    Ok(proof)
+}
+
+/// Reads the public witness as written by gnark: the number of public and secret variables and
+/// of elements, followed by the elements in big endian.
+fn read_public_witness(bytes: &[u8]) -> Result<Vec<Fr>, String> {
+    let mut reader = GnarkReader::new(bytes, "public input");
+    let _public = reader.u32()?;
+    let _secret = reader.u32()?;
+    let len = reader.u32()?;
+    let public_inputs = (0..len)
+        .map(|_| reader.take(FR_SIZE).map(Fr::from_be_bytes_mod_order))
+        .collect::<Result<_, _>>()?;
+    if !reader.is_empty() {
+        return Err("the public input has trailing bytes".to_string());
+    }
+    Ok(public_inputs)
+}
+
+/// Reader of gnark's binary encoding, naming the artifact read in its errors.
+struct GnarkReader<'a> {
+    bytes: &'a [u8],
+    artifact: &'static str,
+}
+
+impl<'a> GnarkReader<'a> {
+    fn new(bytes: &'a [u8], artifact: &'static str) -> Self {
+        GnarkReader { bytes, artifact }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err(format!("the {} is truncated", self.artifact));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn g1(&mut self) -> Result<G1Affine, String> {
+        let bytes = to_ark_compressed(self.take(G1_SIZE)?)?;
+        G1Affine::deserialize_compressed(bytes.as_slice())
+            .map_err(|e| format!("the {} holds an invalid G1 point: {}", self.artifact, e))
+    }
+
+    fn g2(&mut self) -> Result<G2Affine, String> {
+        let bytes = to_ark_compressed(self.take(G2_SIZE)?)?;
+        G2Affine::deserialize_compressed(bytes.as_slice())
+            .map_err(|e| format!("the {} holds an invalid G2 point: {}", self.artifact, e))
+    }
+}
+
+/// Converts a compressed point of gnark, in big endian with the flags in its first byte, to one
+/// of arkworks, in little endian with the flags in its last byte. The x of G2 points is written
+/// by gnark as its A1 and then its A0 coordinate, which arkworks writes in the opposite order,
+/// so reversing the bytes converts both G1 and G2 points.
+fn to_ark_compressed(gnark_bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let flags = gnark_bytes[0] & GNARK_FLAGS_MASK;
+    let mut bytes = gnark_bytes.to_vec();
+    bytes[0] &= !GNARK_FLAGS_MASK;
+    bytes.reverse();
+
+    let last = bytes.len() - 1;
+    match flags {
+        GNARK_COMPRESSED_SMALLEST => {}
+        GNARK_COMPRESSED_LARGEST => bytes[last] |= ARK_Y_IS_NEGATIVE,
+        GNARK_COMPRESSED_INFINITY => bytes[last] |= ARK_INFINITY,
+        _ => return Err("points must be compressed".to_string()),
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_ec::AffineRepr;
+
+    #[test]
+    fn test_gnark_compressed_points_are_read() {
+        // The generator of G1 is (1, 2), with the smallest y
+        let mut generator = [0u8; G1_SIZE];
+        generator[0] = GNARK_COMPRESSED_SMALLEST;
+        generator[G1_SIZE - 1] = 1;
+        let mut reader = GnarkReader::new(&generator, "proof");
+        assert_eq!(reader.g1().unwrap(), G1Affine::generator());
+        assert!(reader.is_empty());
+
+        let mut infinity = [0u8; G1_SIZE];
+        infinity[0] = GNARK_COMPRESSED_INFINITY;
+        assert!(GnarkReader::new(&infinity, "proof").g1().unwrap().is_zero());
+
+        // Uncompressed points
+        assert!(GnarkReader::new(&[0u8; G1_SIZE], "proof").g1().is_err());
+        assert!(GnarkReader::new(&generator[1..], "proof").g1().is_err());
+    }
+}
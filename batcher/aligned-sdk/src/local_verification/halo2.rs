@@ -0,0 +1,154 @@
+use std::io::BufReader;
+
+use halo2_proofs::{
+    plonk::{verify_proof, ConstraintSystem, VerifyingKey},
+    poly::{
+        commitment::Params,
+        ipa::{
+            commitment::IPACommitmentScheme, multiopen::VerifierIPA,
+            strategy::SingleStrategy as IpaSingleStrategy,
+        },
+        kzg::{
+            commitment::KZGCommitmentScheme, multiopen::VerifierSHPLONK,
+            strategy::SingleStrategy as KzgSingleStrategy,
+        },
+        VerificationStrategy,
+    },
+    transcript::{Blake2bRead, Challenge255, TranscriptReadBuffer},
+    SerdeFormat,
+};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+
+/// Size of the header of Halo2 verification keys, with the lengths of its three parts.
+const HEADER_SIZE: usize = 12;
+
+/// Parts of a Halo2 verification key as sent to Aligned: the constraint system, the verifying
+/// key and the commitment scheme params, preceded by their lengths as little endian u32.
+struct VerificationKeyParts<'a> {
+    constraint_system: &'a [u8],
+    verifying_key: &'a [u8],
+    params: &'a [u8],
+}
+
+impl<'a> VerificationKeyParts<'a> {
+    fn split(verification_key: &'a [u8]) -> Result<Self, String> {
+        if verification_key.len() < HEADER_SIZE {
+            return Err("the verification key is shorter than its header".to_string());
+        }
+        let len = |offset: usize| {
+            let bytes: [u8; 4] = verification_key[offset..offset + 4].try_into().unwrap();
+            u32::from_le_bytes(bytes) as usize
+        };
+        let (cs_len, vk_len, params_len) = (len(0), len(4), len(8));
+        if HEADER_SIZE + cs_len + vk_len + params_len != verification_key.len() {
+            return Err(
+                "the lengths in the header of the verification key don't match its size"
+                    .to_string(),
+            );
+        }
+
+        let (constraint_system, rest) = verification_key[HEADER_SIZE..].split_at(cs_len);
+        let (verifying_key, params) = rest.split_at(vk_len);
+        Ok(VerificationKeyParts {
+            constraint_system,
+            verifying_key,
+            params,
+        })
+    }
+
+    fn read_verifying_key(&self) -> Result<VerifyingKey<G1Affine>, String> {
+        let cs: ConstraintSystem<Fr> = bincode::deserialize(self.constraint_system)
+            .map_err(|e| format!("the constraint system can't be decoded: {}", e))?;
+        VerifyingKey::<G1Affine>::read(
+            &mut BufReader::new(self.verifying_key),
+            SerdeFormat::RawBytes,
+            cs,
+        )
+        .map_err(|e| format!("the verifying key can't be decoded: {}", e))
+    }
+}
+
+/// Verifies a Halo2 proof with KZG commitments over BN254, as the operators do.
+pub(super) fn verify_kzg(
+    proof: &[u8],
+    public_input: &[u8],
+    verification_key: &[u8],
+) -> Result<(), String> {
+    let parts = VerificationKeyParts::split(verification_key)?;
+    let vk = parts.read_verifying_key()?;
+    let params = Params::read::<_>(&mut BufReader::new(parts.params))
+        .map_err(|e| format!("the KZG params can't be decoded: {}", e))?;
+    let instances = read_fr(public_input)?;
+
+    let strategy = KzgSingleStrategy::new(&params);
+    let mut transcript = Blake2bRead::<&[u8], G1Affine, Challenge255<_>>::init(proof);
+    verify_proof::<
+        KZGCommitmentScheme<Bn256>,
+        VerifierSHPLONK<Bn256>,
+        Challenge255<G1Affine>,
+        Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
+        KzgSingleStrategy<Bn256>,
+    >(&params, &vk, strategy, &[vec![instances]], &mut transcript)
+    .map_err(|e| format!("the proof doesn't verify: {:?}", e))
+}
+
+/// Verifies a Halo2 proof with IPA commitments over BN254, as the operators do.
+pub(super) fn verify_ipa(
+    proof: &[u8],
+    public_input: &[u8],
+    verification_key: &[u8],
+) -> Result<(), String> {
+    let parts = VerificationKeyParts::split(verification_key)?;
+    let vk = parts.read_verifying_key()?;
+    let params = Params::read::<_>(&mut BufReader::new(parts.params))
+        .map_err(|e| format!("the IPA params can't be decoded: {}", e))?;
+    let instances = read_fr(public_input)?;
+
+    let strategy = IpaSingleStrategy::new(&params);
+    let mut transcript = Blake2bRead::<&[u8], G1Affine, Challenge255<_>>::init(proof);
+    verify_proof::<
+        IPACommitmentScheme<G1Affine>,
+        VerifierIPA<G1Affine>,
+        Challenge255<G1Affine>,
+        Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
+        IpaSingleStrategy<G1Affine>,
+    >(&params, &vk, strategy, &[vec![instances]], &mut transcript)
+    .map_err(|e| format!("the proof doesn't verify: {:?}", e))
+}
+
+/// Reads the public input as field elements of 32 bytes in little endian.
+fn read_fr(public_input: &[u8]) -> Result<Vec<Fr>, String> {
+    if public_input.len() % 32 != 0 {
+        return Err("the public input is not made of 32 byte field elements".to_string());
+    }
+    public_input
+        .chunks_exact(32)
+        .map(|bytes| {
+            Option::from(Fr::from_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(|| "the public input holds an invalid field element".to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verification_keys_are_split_by_their_header() {
+        let mut verification_key = Vec::new();
+        for len in [2u32, 1, 3] {
+            verification_key.extend_from_slice(&len.to_le_bytes());
+        }
+        verification_key.extend_from_slice(&[1, 1, 2, 3, 3, 3]);
+
+        let parts = VerificationKeyParts::split(&verification_key).unwrap();
+        assert_eq!(parts.constraint_system, &[1, 1]);
+        assert_eq!(parts.verifying_key, &[2]);
+        assert_eq!(parts.params, &[3, 3, 3]);
+
+        verification_key.push(4);
+        assert!(VerificationKeyParts::split(&verification_key).is_err());
+        assert!(VerificationKeyParts::split(&verification_key[..8]).is_err());
+    }
+}
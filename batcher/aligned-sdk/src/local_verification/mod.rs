@@ -0,0 +1,126 @@
+//! Verification of proofs before they are submitted, with the verifiers the operators run, so
+//! that an invalid proof is caught without paying for it and without the batcher closing the
+//! connection of the whole submission. Only built with the `local-verification` feature, since
+//! the verifiers pull in the dependencies of each proving system.
+//!
+//! SP1, Groth16 over BN254 and Halo2 proofs are verified locally. Gnark Plonk and Risc0 proofs
+//! are verified by the operators through FFI to Go and to the Risc0 zkvm, which the SDK doesn't
+//! link, so they can't be verified before submitting.
+
+mod groth16;
+mod halo2;
+mod sp1;
+
+use crate::errors::SubmitError;
+use crate::types::{ProvingSystemId, VerificationData};
+
+/// Verifies the proof of the verification data as the operators would.
+/// # Arguments
+/// * `verification_data` - The verification data of the proof, as it would be submitted.
+/// # Returns
+/// * Ok if the proof is valid.
+/// # Errors
+/// * `InvalidVerificationData` if the verification data lacks an artifact of the proving system.
+/// * `InvalidProvingSystem` if proofs of the proving system can't be verified locally.
+/// * `InvalidProof` if the proof, or one of its artifacts, is invalid.
+pub fn verify_local(verification_data: &VerificationData) -> Result<(), SubmitError> {
+    verification_data.validate()?;
+
+    let proving_system = verification_data.proving_system.clone();
+    let proof = &verification_data.proof;
+    let pub_input = verification_data.pub_input.as_deref();
+    let verification_key = verification_data
+        .verification_key
+        .as_deref()
+        .unwrap_or_default();
+    let result = match &proving_system {
+        ProvingSystemId::SP1 => sp1::verify(
+            proof,
+            verification_data
+                .vm_program_code
+                .as_deref()
+                .unwrap_or_default(),
+            pub_input,
+        ),
+        ProvingSystemId::Groth16Bn254 => {
+            groth16::verify(proof, pub_input.unwrap_or_default(), verification_key)
+        }
+        ProvingSystemId::Halo2KZG => {
+            halo2::verify_kzg(proof, pub_input.unwrap_or_default(), verification_key)
+        }
+        ProvingSystemId::Halo2IPA => {
+            halo2::verify_ipa(proof, pub_input.unwrap_or_default(), verification_key)
+        }
+        ProvingSystemId::GnarkPlonkBls12_381
+        | ProvingSystemId::GnarkPlonkBn254
+        | ProvingSystemId::Risc0 => {
+            return Err(SubmitError::InvalidProvingSystem(format!(
+                "{:?} proofs can't be verified locally",
+                proving_system
+            )))
+        }
+    };
+
+    result.map_err(|reason| SubmitError::InvalidProof {
+        proving_system,
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::Address;
+
+    #[test]
+    fn test_proofs_verified_by_ffi_are_not_verified_locally() {
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Risc0,
+            proof: vec![1],
+            pub_input: Some(vec![2]),
+            verification_key: None,
+            vm_program_code: Some(vec![3; 32]),
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        assert!(matches!(
+            verify_local(&verification_data),
+            Err(SubmitError::InvalidProvingSystem(_))
+        ));
+
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            verification_key: None,
+            vm_program_code: None,
+            ..verification_data
+        };
+        assert!(matches!(
+            verify_local(&verification_data),
+            Err(SubmitError::InvalidVerificationData(_))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_proofs_are_invalid() {
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            proof: vec![1; 10],
+            pub_input: Some(vec![2; 10]),
+            verification_key: Some(vec![3; 10]),
+            vm_program_code: None,
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        assert!(matches!(
+            verify_local(&verification_data),
+            Err(SubmitError::InvalidProof {
+                proving_system: ProvingSystemId::Groth16Bn254,
+                ..
+            })
+        ));
+    }
+}
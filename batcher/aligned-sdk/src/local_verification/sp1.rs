@@ -0,0 +1,22 @@
+use std::sync::OnceLock;
+
+use sp1_sdk::{ProverClient, SP1CompressedProof};
+
+/// Client of the SP1 prover, which is only set up once since it's expensive to build.
+static SP1_PROVER_CLIENT: OnceLock<ProverClient> = OnceLock::new();
+
+/// Verifies an SP1 compressed proof of the program `elf`. When public values are given, they must
+/// match the ones committed in the proof, as checked by the operators.
+pub(super) fn verify(proof: &[u8], elf: &[u8], public_values: Option<&[u8]>) -> Result<(), String> {
+    let proof = bincode::deserialize::<SP1CompressedProof>(proof)
+        .map_err(|e| format!("the proof can't be decoded: {}", e))?;
+    if public_values.is_some_and(|public_values| public_values != proof.public_values.as_slice()) {
+        return Err("the public input doesn't match the public values of the proof".to_string());
+    }
+
+    let client = SP1_PROVER_CLIENT.get_or_init(ProverClient::new);
+    let (_pk, vk) = client.setup(elf);
+    client
+        .verify_compressed(&proof, &vk)
+        .map_err(|e| format!("the proof doesn't verify: {}", e))
+}
@@ -0,0 +1,595 @@
+//! Merkle trees of the verification data commitments of batches, built by the batcher when a
+//! batch is sent, and by clients to rebuild the inclusion proofs of the proofs of a batch from
+//! its data.
+//!
+//! With the `parallel` feature, the commitments of a batch and the levels of large trees are
+//! hashed on every core with rayon, since batches of thousands of proofs of several megabytes
+//! delay every user of the batch while they are hashed. `benches/batch_merkle_tree.rs` measures
+//! both with and without the feature.
+
+use std::marker::PhantomData;
+
+use lambdaworks_crypto::merkle_tree::proof::Proof;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::types::{
+    BatchInclusionData, BatchMerkleTreeBackend, BatchVersion, VerificationCommitmentBatch,
+    VerificationData, VerificationDataCommitment,
+};
+
+/// Number of nodes from which a level of a tree is hashed in parallel with the `parallel`
+/// feature. Nodes are hashes of at most 116 bytes, so splitting smaller levels among threads
+/// costs more than it saves.
+#[cfg(feature = "parallel")]
+const PARALLEL_LEVEL_LEN: usize = 1024;
+
+/// Returns the commitments of the entries of a batch, in the same order. Each commitment hashes
+/// the whole proof of its entry, so with the `parallel` feature they are computed in parallel.
+pub fn batch_commitments(batch_data: &[VerificationData]) -> Vec<VerificationDataCommitment> {
+    #[cfg(feature = "parallel")]
+    return batch_data
+        .par_iter()
+        .map(VerificationDataCommitment::from)
+        .collect();
+
+    #[cfg(not(feature = "parallel"))]
+    batch_data
+        .iter()
+        .map(VerificationDataCommitment::from)
+        .collect()
+}
+
+fn hash_leaves<B: BatchMerkleTreeBackend>(entries: &[VerificationDataCommitment]) -> Vec<[u8; 32]> {
+    #[cfg(feature = "parallel")]
+    if entries.len() >= PARALLEL_LEVEL_LEN {
+        return entries.par_iter().map(B::hash_data).collect();
+    }
+
+    entries.iter().map(B::hash_data).collect()
+}
+
+fn hash_parents<B: BatchMerkleTreeBackend>(level: &[[u8; 32]], arity: usize) -> Vec<[u8; 32]> {
+    #[cfg(feature = "parallel")]
+    if level.len() >= PARALLEL_LEVEL_LEN {
+        return level
+            .par_chunks_exact(arity)
+            .map(B::hash_children)
+            .collect();
+    }
+
+    level.chunks_exact(arity).map(B::hash_children).collect()
+}
+
+/// Returns the number of leaves of the merkle tree of a batch with the given number of entries.
+/// Trees are complete trees of the arity of the batch version with at least one full level of
+/// leaves, so the number of leaves is the smallest power of the arity that is greater or equal
+/// to the number of entries, and a single entry V1 batch has two leaves.
+pub fn padded_batch_len(batch_version: BatchVersion, batch_len: usize) -> usize {
+    let arity = batch_version.arity();
+    let mut padded_len = arity;
+    while padded_len < batch_len {
+        padded_len *= arity;
+    }
+    padded_len
+}
+
+/// Pads the entries of a batch to `padded_batch_len` entries by repeating the last one. The
+/// duplicated entries are part of the tree, so the last entry of an odd batch can also be proven
+/// to be at the positions of its copies.
+/// # Arguments
+/// * `batch_version` - The version of the batch, which defines the arity of its tree.
+/// * `entries` - The entries of the batch, in the order they were added to it.
+/// # Returns
+/// * The padded entries, or no entries if the batch is empty.
+pub fn pad_batch<T: Clone>(batch_version: BatchVersion, entries: &[T]) -> Vec<T> {
+    let Some(last_entry) = entries.last() else {
+        return Vec::new();
+    };
+
+    let mut padded_entries = entries.to_vec();
+    padded_entries.resize(
+        padded_batch_len(batch_version, entries.len()),
+        last_entry.clone(),
+    );
+    padded_entries
+}
+
+/// Merkle tree of the verification data commitments of a batch, whose arity is given by the
+/// version of the batch. V1 trees are the binary trees built by lambdaworks with the same
+/// backend. Trees of the default keccak backend are the ones whose roots and paths are verified
+/// by the Aligned service manager contract, while trees of `PoseidonCommitmentBatch` are cheap
+/// to verify inside circuits.
+#[derive(Clone, Debug)]
+pub struct BatchMerkleTree<B = VerificationCommitmentBatch> {
+    pub root: [u8; 32],
+    pub batch_version: BatchVersion,
+    // Nodes of every level, from the padded leaves to the root
+    levels: Vec<Vec<[u8; 32]>>,
+    backend: PhantomData<B>,
+}
+
+impl BatchMerkleTree {
+    /// Builds the keccak merkle tree of a batch, padding it as described in `pad_batch`.
+    /// # Arguments
+    /// * `batch_version` - The version of the batch, which defines the arity of its tree.
+    /// * `batch_data_comm` - The commitments of the entries of the batch.
+    /// # Returns
+    /// * The merkle tree of the batch, or `None` if the batch is empty.
+    pub fn build(
+        batch_version: BatchVersion,
+        batch_data_comm: &[VerificationDataCommitment],
+    ) -> Option<Self> {
+        Self::build_with_backend(batch_version, batch_data_comm)
+    }
+}
+
+impl<B: BatchMerkleTreeBackend> BatchMerkleTree<B> {
+    /// Builds the merkle tree of a batch with the backend of the tree, as `build` does with
+    /// keccak. Its inclusion proofs are checked by `BatchVersion::verify_merkle_inclusion_with_backend`.
+    pub fn build_with_backend(
+        batch_version: BatchVersion,
+        batch_data_comm: &[VerificationDataCommitment],
+    ) -> Option<Self> {
+        let leaves = hash_leaves::<B>(&pad_batch(batch_version, batch_data_comm));
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let mut levels = vec![leaves];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let parents = hash_parents::<B>(level, batch_version.arity());
+            levels.push(parents);
+        }
+
+        Some(BatchMerkleTree {
+            root: levels[levels.len() - 1][0],
+            batch_version,
+            levels,
+            backend: PhantomData,
+        })
+    }
+
+    /// Builds a tree from the nodes of its levels, hashed elsewhere, such as by a builder adding
+    /// its leaves one at a time. The nodes are not checked to be the hashes of their children.
+    /// # Arguments
+    /// * `batch_version` - The version of the batch, which defines the arity of its tree.
+    /// * `levels` - The nodes of every level, from the padded leaves to the root.
+    /// # Returns
+    /// * The tree, or `None` if the levels don't have the sizes of the levels of a padded tree of
+    ///   the arity of the batch version.
+    pub fn from_levels(batch_version: BatchVersion, levels: Vec<Vec<[u8; 32]>>) -> Option<Self> {
+        let arity = batch_version.arity();
+        let leaves = levels.first()?;
+        if leaves.len() != padded_batch_len(batch_version, leaves.len())
+            || levels
+                .windows(2)
+                .any(|pair| pair[1].len() * arity != pair[0].len())
+            || levels[levels.len() - 1].len() != 1
+        {
+            return None;
+        }
+
+        Some(BatchMerkleTree {
+            root: levels[levels.len() - 1][0],
+            batch_version,
+            levels,
+            backend: PhantomData,
+        })
+    }
+
+    /// Returns the inclusion proof of the leaf at the given position, as checked by
+    /// `BatchVersion::verify_merkle_inclusion_with_backend`.
+    pub fn get_proof_by_pos(&self, pos: usize) -> Option<Proof<[u8; 32]>> {
+        if pos >= self.levels[0].len() {
+            return None;
+        }
+
+        let arity = self.batch_version.arity();
+        let mut merkle_path = Vec::new();
+        let mut pos = pos;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let first_sibling = pos - pos % arity;
+            merkle_path.extend(
+                (first_sibling..first_sibling + arity)
+                    .filter(|sibling| *sibling != pos)
+                    .map(|sibling| level[sibling]),
+            );
+            pos /= arity;
+        }
+
+        Some(Proof { merkle_path })
+    }
+
+    /// Returns the data sent to the client that added the entry at the given position.
+    pub fn batch_inclusion_data(&self, index_in_batch: usize) -> Option<BatchInclusionData> {
+        Some(BatchInclusionData {
+            batch_merkle_root: self.root,
+            batch_inclusion_proof: self.get_proof_by_pos(index_in_batch)?,
+            index_in_batch,
+            batch_version: self.batch_version,
+            submission_id: None,
+            metadata: None,
+            schema_version: BatchInclusionData::SCHEMA_VERSION,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::PoseidonCommitmentBatch;
+    use lambdaworks_crypto::merkle_tree::merkle::MerkleTree;
+    use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+
+    fn commitments(batch_size: u8) -> Vec<VerificationDataCommitment> {
+        (0..batch_size)
+            .map(|i| VerificationDataCommitment {
+                proof_commitment: [i; 32],
+                pub_input_commitment: [i.wrapping_add(1); 32],
+                proving_system_aux_data_commitment: [i.wrapping_add(2); 32],
+                proof_generator_addr: [i; 20],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_merkle_inclusion_of_every_leaf() {
+        for batch_version in [BatchVersion::V1, BatchVersion::V2] {
+            for batch_size in [1, 2, 3, 4, 5, 8, 13, 17] {
+                let commitments = commitments(batch_size);
+                let tree = BatchMerkleTree::build(batch_version, &commitments).unwrap();
+
+                for (index, commitment) in commitments.iter().enumerate() {
+                    let proof = tree.get_proof_by_pos(index).unwrap();
+                    let leaf = VerificationCommitmentBatch::hash_data(commitment);
+
+                    assert!(batch_version.verify_merkle_inclusion(
+                        &tree.root,
+                        &leaf,
+                        &proof.merkle_path,
+                        index
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_v1_tree_matches_lambdaworks_tree() {
+        for batch_size in [2, 3, 4, 5, 8, 13] {
+            let commitments = commitments(batch_size);
+            let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+            let lambdaworks_tree = MerkleTree::<VerificationCommitmentBatch>::build(&commitments);
+
+            assert_eq!(tree.root, lambdaworks_tree.root);
+            for index in 0..commitments.len() {
+                assert_eq!(
+                    tree.get_proof_by_pos(index).unwrap().merkle_path,
+                    lambdaworks_tree
+                        .get_proof_by_pos(index)
+                        .unwrap()
+                        .merkle_path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_poseidon_merkle_inclusion_of_every_leaf() {
+        for batch_version in [BatchVersion::V1, BatchVersion::V2] {
+            for batch_size in [1, 2, 3, 5, 17] {
+                let commitments = commitments(batch_size);
+                let tree = BatchMerkleTree::<PoseidonCommitmentBatch>::build_with_backend(
+                    batch_version,
+                    &commitments,
+                )
+                .unwrap();
+                let keccak_tree = BatchMerkleTree::build(batch_version, &commitments).unwrap();
+                assert_ne!(tree.root, keccak_tree.root);
+
+                for (index, commitment) in commitments.iter().enumerate() {
+                    let proof = tree.get_proof_by_pos(index).unwrap();
+                    let leaf = PoseidonCommitmentBatch::hash_data(commitment);
+
+                    assert!(batch_version
+                        .verify_merkle_inclusion_with_backend::<PoseidonCommitmentBatch>(
+                            &tree.root,
+                            &leaf,
+                            &proof.merkle_path,
+                            index
+                        ));
+                    // Poseidon paths are not keccak paths
+                    assert!(!batch_version.verify_merkle_inclusion(
+                        &tree.root,
+                        &leaf,
+                        &proof.merkle_path,
+                        index
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_v1_poseidon_tree_matches_lambdaworks_tree() {
+        for batch_size in [2, 3, 8] {
+            let commitments = commitments(batch_size);
+            let tree = BatchMerkleTree::<PoseidonCommitmentBatch>::build_with_backend(
+                BatchVersion::V1,
+                &commitments,
+            )
+            .unwrap();
+            let lambdaworks_tree = MerkleTree::<PoseidonCommitmentBatch>::build(&commitments);
+
+            assert_eq!(tree.root, lambdaworks_tree.root);
+            for index in 0..commitments.len() {
+                assert_eq!(
+                    tree.get_proof_by_pos(index).unwrap().merkle_path,
+                    lambdaworks_tree
+                        .get_proof_by_pos(index)
+                        .unwrap()
+                        .merkle_path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_trees_match_sequential_hashing() {
+        // Larger than a parallel level, so both levels and leaves are hashed in parallel with
+        // the `parallel` feature, and with the same padding as lambdaworks
+        let commitments: Vec<VerificationDataCommitment> = (0..2500u32)
+            .map(|i| VerificationDataCommitment {
+                proof_commitment: [(i % 256) as u8; 32],
+                pub_input_commitment: [(i / 256) as u8; 32],
+                ..Default::default()
+            })
+            .collect();
+
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+        let lambdaworks_tree = MerkleTree::<VerificationCommitmentBatch>::build(&commitments);
+        assert_eq!(tree.root, lambdaworks_tree.root);
+        assert_eq!(
+            tree.get_proof_by_pos(1777).unwrap().merkle_path,
+            lambdaworks_tree.get_proof_by_pos(1777).unwrap().merkle_path
+        );
+
+        let tree = BatchMerkleTree::build(BatchVersion::V2, &commitments).unwrap();
+        let mut level: Vec<[u8; 32]> = pad_batch(BatchVersion::V2, &commitments)
+            .iter()
+            .map(VerificationCommitmentBatch::hash_data)
+            .collect();
+        while level.len() > 1 {
+            level = level
+                .chunks_exact(4)
+                .map(VerificationCommitmentBatch::hash_children)
+                .collect();
+        }
+        assert_eq!(tree.root, level[0]);
+    }
+
+    #[test]
+    fn test_batch_commitments_keep_the_order_of_the_batch() {
+        let batch_data: Vec<VerificationData> = (0..4u8)
+            .map(|i| VerificationData {
+                proving_system: crate::types::ProvingSystemId::SP1,
+                proof: vec![i; 1024],
+                pub_input: None,
+                verification_key: None,
+                vm_program_code: Some(vec![i]),
+                proof_generator_addr: Default::default(),
+                tag: None,
+                metadata: None,
+                schema_version: VerificationData::SCHEMA_VERSION,
+            })
+            .collect();
+
+        let commitments = batch_commitments(&batch_data);
+        assert_eq!(commitments.len(), 4);
+        for (commitment, verification_data) in commitments.iter().zip(&batch_data) {
+            assert_eq!(
+                VerificationCommitmentBatch::hash_data(commitment),
+                VerificationCommitmentBatch::hash_data(&verification_data.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_v2_tree_hashes_four_children() {
+        let commitments = commitments(5);
+        let leaves: Vec<[u8; 32]> = pad_batch(BatchVersion::V2, &commitments)
+            .iter()
+            .map(VerificationCommitmentBatch::hash_data)
+            .collect();
+        let tree = BatchMerkleTree::build(BatchVersion::V2, &commitments).unwrap();
+
+        let parents: Vec<[u8; 32]> = leaves
+            .chunks_exact(4)
+            .map(VerificationCommitmentBatch::hash_children)
+            .collect();
+        assert_eq!(
+            tree.root,
+            VerificationCommitmentBatch::hash_children(&parents)
+        );
+
+        // Two levels of three siblings each
+        let path = tree.get_proof_by_pos(4).unwrap().merkle_path;
+        assert_eq!(
+            path,
+            vec![leaves[5], leaves[6], leaves[7], parents[0], parents[2], parents[3]]
+        );
+    }
+
+    #[test]
+    fn test_batch_inclusion_data_records_batch_version() {
+        let commitments = commitments(3);
+        let tree = BatchMerkleTree::build(BatchVersion::V2, &commitments).unwrap();
+        let batch_inclusion_data = tree.batch_inclusion_data(2).unwrap();
+
+        assert_eq!(batch_inclusion_data.batch_version, BatchVersion::V2);
+        assert_eq!(batch_inclusion_data.batch_merkle_root, tree.root);
+        assert_eq!(batch_inclusion_data.index_in_batch, 2);
+        assert!(tree.batch_inclusion_data(4).is_none());
+    }
+
+    #[test]
+    fn test_batch_inclusion_data_verifies_as_the_contract() {
+        let commitments = commitments(3);
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+
+        for (index, commitment) in commitments.iter().enumerate() {
+            let batch_inclusion_data = tree.batch_inclusion_data(index).unwrap();
+            assert!(batch_inclusion_data.verify(commitment));
+
+            // Nodes hashed in the opposite order, except for the last leaf, whose sibling is
+            // the padding leaf repeating it
+            let mut swapped = batch_inclusion_data.clone();
+            swapped.index_in_batch ^= 1;
+            assert_eq!(swapped.verify(commitment), index == 2);
+
+            // Bits of the index beyond the path are not checked by the contract
+            let mut beyond_path = batch_inclusion_data.clone();
+            beyond_path.index_in_batch += 4;
+            assert!(beyond_path.verify(commitment));
+        }
+
+        // The commitment of the padding leaf repeats the last one
+        let padding = tree.batch_inclusion_data(3).unwrap();
+        assert!(padding.verify(&commitments[2]));
+        assert!(!padding.verify(&commitments[1]));
+
+        let mut empty_path = tree.batch_inclusion_data(0).unwrap();
+        empty_path.batch_inclusion_proof.merkle_path.clear();
+        empty_path.batch_merkle_root = VerificationCommitmentBatch::hash_data(&commitments[0]);
+        assert!(!empty_path.verify(&commitments[0]));
+
+        let tree = BatchMerkleTree::build(BatchVersion::V2, &commitments).unwrap();
+        assert!(!tree
+            .batch_inclusion_data(0)
+            .unwrap()
+            .verify(&commitments[0]));
+    }
+
+    #[test]
+    fn test_padded_batch_len() {
+        assert_eq!(padded_batch_len(BatchVersion::V1, 1), 2);
+        assert_eq!(padded_batch_len(BatchVersion::V1, 2), 2);
+        assert_eq!(padded_batch_len(BatchVersion::V1, 3), 4);
+        assert_eq!(padded_batch_len(BatchVersion::V1, 5), 8);
+        assert_eq!(padded_batch_len(BatchVersion::V1, 8), 8);
+
+        assert_eq!(padded_batch_len(BatchVersion::V2, 1), 4);
+        assert_eq!(padded_batch_len(BatchVersion::V2, 4), 4);
+        assert_eq!(padded_batch_len(BatchVersion::V2, 5), 16);
+        assert_eq!(padded_batch_len(BatchVersion::V2, 17), 64);
+    }
+
+    #[test]
+    fn test_pad_batch_repeats_last_entry() {
+        assert_eq!(pad_batch::<u8>(BatchVersion::V1, &[]), Vec::<u8>::new());
+        assert_eq!(pad_batch(BatchVersion::V1, &[1]), vec![1, 1]);
+        assert_eq!(pad_batch(BatchVersion::V1, &[1, 2]), vec![1, 2]);
+        assert_eq!(pad_batch(BatchVersion::V1, &[1, 2, 3]), vec![1, 2, 3, 3]);
+        assert_eq!(
+            pad_batch(BatchVersion::V1, &[1, 2, 3, 4, 5]),
+            vec![1, 2, 3, 4, 5, 5, 5, 5]
+        );
+        assert_eq!(pad_batch(BatchVersion::V2, &[1, 2]), vec![1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_empty_batch_has_no_merkle_tree() {
+        assert!(BatchMerkleTree::build(BatchVersion::V1, &[]).is_none());
+        assert!(BatchMerkleTree::build(BatchVersion::V2, &[]).is_none());
+    }
+
+    #[test]
+    fn test_from_levels_rejects_levels_of_other_trees() {
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments(3)).unwrap();
+        let rebuilt = BatchMerkleTree::<VerificationCommitmentBatch>::from_levels(
+            BatchVersion::V1,
+            tree.levels.clone(),
+        )
+        .unwrap();
+        assert_eq!(rebuilt.root, tree.root);
+        assert_eq!(rebuilt.levels, tree.levels);
+
+        let mut unpadded_leaves = tree.levels.clone();
+        unpadded_leaves[0].pop();
+        let mut without_root = tree.levels.clone();
+        without_root.pop();
+        let mut above_root = tree.levels.clone();
+        above_root.push(vec![tree.root]);
+        for levels in [Vec::new(), unpadded_leaves, without_root, above_root] {
+            assert!(BatchMerkleTree::<VerificationCommitmentBatch>::from_levels(
+                BatchVersion::V1,
+                levels
+            )
+            .is_none());
+        }
+        // A binary tree of four leaves is not the tree of a V2 batch
+        assert!(BatchMerkleTree::<VerificationCommitmentBatch>::from_levels(
+            BatchVersion::V2,
+            tree.levels
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_single_entry_batch_duplicates_leaf() {
+        let commitments = commitments(1);
+        let leaf = VerificationCommitmentBatch::hash_data(&commitments[0]);
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+
+        assert_eq!(
+            tree.root,
+            VerificationCommitmentBatch::hash_new_parent(&leaf, &leaf)
+        );
+        assert_eq!(tree.get_proof_by_pos(0).unwrap().merkle_path, vec![leaf]);
+        assert!(BatchVersion::V1.verify_merkle_inclusion(&tree.root, &leaf, &[leaf], 0));
+        assert!(BatchVersion::V1.verify_merkle_inclusion(&tree.root, &leaf, &[leaf], 1));
+    }
+
+    #[test]
+    fn test_odd_batch_duplicates_last_leaf() {
+        let commitments = commitments(3);
+        let leaves: Vec<[u8; 32]> = commitments
+            .iter()
+            .map(VerificationCommitmentBatch::hash_data)
+            .collect();
+        let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments).unwrap();
+
+        let left = VerificationCommitmentBatch::hash_new_parent(&leaves[0], &leaves[1]);
+        let right = VerificationCommitmentBatch::hash_new_parent(&leaves[2], &leaves[2]);
+        assert_eq!(
+            tree.root,
+            VerificationCommitmentBatch::hash_new_parent(&left, &right)
+        );
+
+        let path = tree.get_proof_by_pos(2).unwrap().merkle_path;
+        assert_eq!(path, vec![leaves[2], left]);
+        assert!(BatchVersion::V1.verify_merkle_inclusion(&tree.root, &leaves[2], &path, 2));
+        assert!(BatchVersion::V1.verify_merkle_inclusion(&tree.root, &leaves[2], &path, 3));
+    }
+
+    // The same vectors are checked against the contract in contracts/test/BatchMerkleInclusion.t.sol
+    #[test]
+    fn test_batch_merkle_roots_match_contract_vectors() {
+        let vectors = [
+            (
+                1,
+                "2fa4dd7b3ae6ef007f6bf7ef5bb1ac9f943eb967824859af4e32f97c4ceb8cab",
+            ),
+            (
+                3,
+                "d911d7c0654f5d2804084ba6484642f515093830b9e80212723082de62da4a54",
+            ),
+        ];
+
+        for (batch_size, expected_root) in vectors {
+            let tree = BatchMerkleTree::build(BatchVersion::V1, &commitments(batch_size)).unwrap();
+            assert_eq!(hex::encode(tree.root), expected_root);
+        }
+    }
+}
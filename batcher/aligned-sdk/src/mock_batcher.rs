@@ -0,0 +1,1648 @@
+//! A batcher that answers every submission without verifying it, available with the `test-utils`
+//! feature. Each connection is answered as its own batch once the client stops sending messages.
+//! The inclusion data of every batch is kept to answer inclusion queries, including the one of
+//! the responses that were dropped.
+//! Faults can be injected in the responses to test how applications handle a misbehaving
+//! batcher, choosing the affected responses with a seeded random number generator so that
+//! tests are deterministic.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+use log::{debug, error};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::auth;
+use crate::chunks::{ChunkAssembler, MessageChunk};
+use crate::encryption::{self, EncryptionKey};
+use crate::merkle::BatchMerkleTree;
+use crate::sdk::{
+    protocol_handshake, CURRENT_PROTOCOL_VERSION, ERROR_RESPONSES_FEATURE,
+    PROTOCOL_FEATURES_HEADER, PROTOCOL_VERSION_HEADER, SESSION_RESUMPTION_FEATURE,
+};
+use crate::session;
+use crate::types::{
+    BatchInclusionData, BatchVersion, ErrorResponse, GetSubmissionStatus, InclusionQuery,
+    InclusionQueryResponse, InclusionStatus, ProtocolHandshake, Rejection, RejectionCode,
+    SessionHandshake, SubmissionStatus, SubmissionStatusResponse, VerificationCommitmentBatch,
+    VerificationData, VerificationDataCommitment,
+};
+use crate::wire::{self, parse_features, WireError, WireFormat, WireMessage};
+
+/// Time without new messages after which the messages of a connection are answered as a batch.
+const BATCH_IDLE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Largest message reassembled from chunks, as the batcher limits it.
+const MAX_MESSAGE_SIZE: usize = 256 << 20;
+
+/// Frame sent instead of a response when it's malformed.
+const MALFORMED_FRAME: &[u8] = b"not a batch inclusion data";
+
+/// Faults injected by the mock batcher in its responses.
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    /// Percentage of the responses that are never sent, from 0 to 100.
+    pub drop_responses_percentage: u8,
+    /// Time waited before sending each response.
+    pub response_delay: Duration,
+    /// Percentage of the responses replaced by a frame that is not a batch inclusion data, from
+    /// 0 to 100.
+    pub malformed_responses_percentage: u8,
+    /// Closes the connection after sending this many responses of a batch, without a reason.
+    pub close_after_responses: Option<usize>,
+    /// Rejects the submission with this rejection as soon as the first message is received,
+    /// without waiting for the rest of the batch. Clients supporting the `error_responses`
+    /// feature receive its error response before the connection is closed with its code.
+    pub reject_with: Option<Rejection>,
+    /// Handshake sent to the clients that announce their protocol version, instead of the one of
+    /// the SDK, to emulate batchers of other versions.
+    pub handshake: Option<ProtocolHandshake>,
+    /// Sends only the protocol version to every client, as batchers older than the handshake.
+    pub legacy_handshake: bool,
+    /// Sends the responses of a batch in the reverse order of their messages, which clients can
+    /// only match by the submission ids echoed in them.
+    pub reverse_responses: bool,
+    /// Publishes the public key of this key in the handshake, and decrypts the messages
+    /// encrypted to it.
+    pub encryption_key: Option<EncryptionKey>,
+    /// Refuses the handshake of clients that don't send one of these access tokens, unless it's
+    /// empty.
+    pub auth_tokens: Vec<String>,
+    /// Rejects the submission when a message has no nonce and expiry, as batchers configured
+    /// with `replay_protection_is_required`.
+    pub replay_protection_is_required: bool,
+    /// Seed choosing the responses that are dropped or malformed. Each connection starts from
+    /// the same seed, so the same responses of a batch are affected regardless of the order in
+    /// which connections are handled.
+    pub seed: u64,
+}
+
+pub struct MockBatcher {
+    address: SocketAddr,
+    handle: JoinHandle<()>,
+    state: Arc<MockState>,
+}
+
+/// State shared by the connections of the mock batcher.
+#[derive(Default)]
+struct MockState {
+    sessions: Mutex<HashMap<String, MockSession>>,
+    /// Number of proofs received over all the connections.
+    received_proofs: AtomicUsize,
+    /// Inclusion data of the proofs of every batch, by the leaf of their commitment.
+    inclusions: Mutex<HashMap<[u8; 32], BatchInclusionData>>,
+}
+
+/// Session of the connections of a client supporting `session_resumption`. Sessions are kept
+/// until the mock batcher stops.
+#[derive(Default)]
+struct MockSession {
+    accepted_proofs: usize,
+    /// Responses of the session that were not dropped, in the order they were sent.
+    responses: Vec<Message>,
+}
+
+impl MockBatcher {
+    /// Starts the mock batcher on a free local port.
+    /// # Arguments
+    /// * `faults` - The faults injected in the responses.
+    /// # Returns
+    /// * The running mock batcher, which stops when dropped.
+    /// # Errors
+    /// * If the port can't be bound.
+    pub async fn start(faults: FaultConfig) -> std::io::Result<MockBatcher> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(MockState::default());
+
+        let connection_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(
+                    stream,
+                    faults.clone(),
+                    connection_state.clone(),
+                ));
+            }
+        });
+
+        Ok(MockBatcher {
+            address,
+            handle,
+            state,
+        })
+    }
+
+    /// Returns the number of proofs received over all the connections, counting the ones sent
+    /// again.
+    pub fn received_proofs(&self) -> usize {
+        self.state.received_proofs.load(Ordering::SeqCst)
+    }
+
+    /// Returns the address to which the SDK submits proofs.
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.address)
+    }
+}
+
+impl Drop for MockBatcher {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_connection(stream: TcpStream, faults: FaultConfig, state: Arc<MockState>) {
+    let mut announces_protocol_version = false;
+    let mut client_features = Vec::new();
+    let mut resumption = None;
+    let accepted = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response| {
+        announces_protocol_version = request.headers().contains_key(PROTOCOL_VERSION_HEADER);
+        resumption = session::resumption_request(request.headers());
+        client_features = request
+            .headers()
+            .get(PROTOCOL_FEATURES_HEADER)
+            .and_then(|features| features.to_str().ok())
+            .map(parse_features)
+            .unwrap_or_default();
+        let authorization = request.headers().get(AUTHORIZATION);
+        if !faults.auth_tokens.is_empty()
+            && !auth::is_authorized(authorization, &faults.auth_tokens)
+        {
+            return Err(auth::unauthorized_response());
+        }
+        Ok(response)
+    })
+    .await;
+    let ws_stream = match accepted {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            error!("Mock batcher handshake failed: {}", e);
+            return;
+        }
+    };
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+
+    // Responses of the resumed session the client didn't receive
+    let mut replayed = Vec::new();
+    let mut session_token = None;
+    let (handshake, wire_format, error_responses) = if announces_protocol_version
+        && !faults.legacy_handshake
+    {
+        let mut handshake = faults.handshake.clone().unwrap_or_else(protocol_handshake);
+        if let Some(encryption_key) = &faults.encryption_key {
+            encryption_key.announce(&mut handshake);
+        }
+        if handshake.supports(SESSION_RESUMPTION_FEATURE)
+            && client_features
+                .iter()
+                .any(|feature| feature == SESSION_RESUMPTION_FEATURE)
+        {
+            let mut sessions = state
+                .sessions
+                .lock()
+                .expect("Sessions lock is not poisoned");
+            let resumed = resumption.and_then(|(token, received)| {
+                let session = sessions.get(&token)?;
+                replayed = session
+                    .responses
+                    .get(received..)
+                    .unwrap_or_default()
+                    .to_vec();
+                Some((token, session.accepted_proofs))
+            });
+            let (token, accepted_proofs) = resumed.unwrap_or_else(|| {
+                let token = session::new_session_token();
+                sessions.insert(token.clone(), MockSession::default());
+                (token, 0)
+            });
+            handshake.session = Some(SessionHandshake {
+                token: token.clone(),
+                accepted_proofs,
+            });
+            session_token = Some(token);
+        }
+        // Binary messages are only expected if both sides support them
+        let wire_format = match WireFormat::negotiate(&handshake.features) {
+            WireFormat::Binary => WireFormat::negotiate(&client_features),
+            WireFormat::Json => WireFormat::Json,
+        };
+        let error_responses = handshake.supports(ERROR_RESPONSES_FEATURE)
+            && client_features
+                .iter()
+                .any(|feature| feature == ERROR_RESPONSES_FEATURE);
+        let handshake = serde_json::to_string(&handshake).expect("Could not serialize handshake");
+        (Message::text(handshake), wire_format, error_responses)
+    } else {
+        let version = CURRENT_PROTOCOL_VERSION.to_be_bytes().to_vec();
+        (Message::binary(version), WireFormat::Json, false)
+    };
+    if ws_write.send(handshake).await.is_err() {
+        return;
+    }
+    let mut sent_responses = 0;
+    if !send_responses(&mut ws_write, replayed, &mut sent_responses, &faults).await {
+        return;
+    }
+
+    // The verification data of the messages, with their submission ids
+    let mut verification_data: Vec<(VerificationData, Option<u64>)> = Vec::new();
+    // Whether a message without a nonce and expiry was received
+    let mut unprotected = false;
+    let mut chunk_assembler = ChunkAssembler::new(MAX_MESSAGE_SIZE);
+    loop {
+        let next_message = if verification_data.is_empty() {
+            ws_read.next().await
+        } else {
+            match timeout(BATCH_IDLE_TIMEOUT, ws_read.next()).await {
+                Ok(next_message) => next_message,
+                Err(_) => break,
+            }
+        };
+
+        let received_before = verification_data.len();
+        match next_message {
+            Some(Ok(Message::Text(text))) => {
+                match WireMessage::decode(WireFormat::Json, text.as_bytes()) {
+                    Ok(WireMessage::InclusionQuery(query)) => {
+                        let response = inclusion_query_response(&state, &query);
+                        if ws_write.send(response).await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(WireMessage::GetSubmissionStatus(query)) => {
+                        let response = submission_status_response(&state, &query);
+                        if ws_write.send(response).await.is_err() {
+                            return;
+                        }
+                    }
+                    message => {
+                        unprotected |= is_unprotected(&message);
+                        verification_data.extend(parse_message(message))
+                    }
+                }
+            }
+            Some(Ok(Message::Binary(data))) => {
+                let chunk = MessageChunk::from_bytes(&data);
+                match chunk.map(|chunk| chunk_assembler.push(chunk)) {
+                    Some(Ok(Some(message))) => {
+                        let message = encryption::decrypt(message, faults.encryption_key.as_ref())
+                            .and_then(|message| wire::decompress(message, MAX_MESSAGE_SIZE));
+                        match message {
+                            Ok(message) => {
+                                let message = WireMessage::decode(wire_format, &message);
+                                unprotected |= is_unprotected(&message);
+                                verification_data.extend(parse_message(message))
+                            }
+                            Err(e) => error!("Mock batcher received an invalid message: {:?}", e),
+                        }
+                    }
+                    Some(Ok(None)) => {}
+                    Some(Err(e)) => error!("Mock batcher received an invalid chunk: {:?}", e),
+                    None => error!("Mock batcher received a malformed chunk"),
+                }
+            }
+            Some(Ok(_)) => {}
+            Some(Err(_)) | None => return,
+        }
+        state
+            .received_proofs
+            .fetch_add(verification_data.len() - received_before, Ordering::SeqCst);
+
+        let rejection = if faults.replay_protection_is_required && unprotected {
+            Some(Rejection::ReplayedMessage {
+                reason: "Message has no nonce and expiry".to_string(),
+            })
+        } else {
+            faults.reject_with.clone()
+        };
+        if let Some(rejection) = rejection.filter(|_| !verification_data.is_empty()) {
+            debug!("Mock batcher rejecting the submission with {:?}", rejection);
+            let code = RejectionCode::of(&rejection);
+            if error_responses {
+                let error_response = ErrorResponse {
+                    rejection,
+                    submission_id: verification_data[0].1,
+                };
+                let error_response = serde_json::to_string(&error_response)
+                    .expect("Could not serialize error response");
+                let _ = ws_write.send(Message::text(error_response)).await;
+            }
+            let close_frame = CloseFrame {
+                code: CloseCode::from(code as u16),
+                reason: "Rejected by the mock batcher".into(),
+            };
+            let _ = ws_write.send(Message::Close(Some(close_frame))).await;
+            return;
+        }
+    }
+
+    let (verification_data, submission_ids): (Vec<VerificationData>, Vec<Option<u64>>) =
+        verification_data.into_iter().unzip();
+    let metadata: Vec<Option<Vec<u8>>> = verification_data
+        .iter()
+        .map(|verification_data| verification_data.metadata.clone())
+        .collect();
+    let commitments: Vec<VerificationDataCommitment> = verification_data
+        .into_iter()
+        .map(VerificationDataCommitment::from)
+        .collect();
+    let batch_size = commitments.len();
+    // As in the batcher, the tree is padded as described in `merkle::pad_batch`
+    let batch_merkle_tree = BatchMerkleTree::build(BatchVersion::V1, &commitments)
+        .expect("Batches are only answered once they have an entry");
+    let batch_inclusion_data = |index| {
+        batch_merkle_tree
+            .batch_inclusion_data(index)
+            .expect("Every entry of the batch is in its tree")
+    };
+    {
+        let mut inclusions = state
+            .inclusions
+            .lock()
+            .expect("Inclusions lock is not poisoned");
+        for (index, commitment) in commitments.iter().enumerate() {
+            inclusions.insert(
+                VerificationCommitmentBatch::hash_data(commitment),
+                batch_inclusion_data(index),
+            );
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(faults.seed);
+    let mut indices: Vec<usize> = (0..batch_size).collect();
+    if faults.reverse_responses {
+        indices.reverse();
+    }
+    let mut responses = Vec::new();
+    for index in indices {
+        let dropped = rng.gen_range(0..100) < faults.drop_responses_percentage;
+        let malformed = rng.gen_range(0..100) < faults.malformed_responses_percentage;
+        if dropped {
+            debug!("Mock batcher dropping response {}", index);
+            continue;
+        }
+
+        responses.push(if malformed {
+            Message::binary(MALFORMED_FRAME.to_vec())
+        } else {
+            let batch_inclusion_data = BatchInclusionData {
+                submission_id: submission_ids[index],
+                metadata: metadata[index].clone(),
+                ..batch_inclusion_data(index)
+            };
+            Message::binary(
+                serde_json::to_vec(&batch_inclusion_data).expect("Could not serialize response"),
+            )
+        });
+    }
+
+    // The responses are kept in the session before they are sent, so the ones lost with the
+    // connection are replayed when the session is resumed
+    if let Some(token) = &session_token {
+        let mut sessions = state
+            .sessions
+            .lock()
+            .expect("Sessions lock is not poisoned");
+        let session = sessions.entry(token.clone()).or_default();
+        session.accepted_proofs += batch_size;
+        session.responses.extend(responses.iter().cloned());
+    }
+    if !send_responses(&mut ws_write, responses, &mut sent_responses, &faults).await {
+        return;
+    }
+
+    // Waits for the client to close the connection, as the batcher does
+    while let Some(Ok(_)) = ws_read.next().await {}
+}
+
+/// Sends the responses of a connection, counting them in `sent_responses`. Returns whether the
+/// connection is still open, which it isn't if it's closed after `close_after_responses`.
+async fn send_responses<S>(
+    ws_write: &mut S,
+    responses: Vec<Message>,
+    sent_responses: &mut usize,
+    faults: &FaultConfig,
+) -> bool
+where
+    S: SinkExt<Message> + Unpin,
+{
+    for response in responses {
+        if faults.close_after_responses == Some(*sent_responses) {
+            debug!(
+                "Mock batcher closing the connection after {} responses",
+                sent_responses
+            );
+            let _ = ws_write.send(Message::Close(None)).await;
+            return false;
+        }
+
+        sleep(faults.response_delay).await;
+        if ws_write.send(response).await.is_err() {
+            return false;
+        }
+        *sent_responses += 1;
+    }
+    true
+}
+
+/// Answers an inclusion query with the inclusion data of the proofs of the batches built so far.
+/// The batches of the mock batcher are built per connection, so no proof is ever pending.
+fn inclusion_query_response(state: &MockState, query: &InclusionQuery) -> Message {
+    let inclusions = state
+        .inclusions
+        .lock()
+        .expect("Inclusions lock is not poisoned");
+    let statuses = query
+        .commitments
+        .iter()
+        .map(|commitment| {
+            match inclusions.get(&VerificationCommitmentBatch::hash_data(commitment)) {
+                Some(batch_inclusion_data) => {
+                    InclusionStatus::Included(batch_inclusion_data.clone())
+                }
+                None => InclusionStatus::Unknown,
+            }
+        })
+        .collect();
+    let response = serde_json::to_string(&InclusionQueryResponse { statuses })
+        .expect("Could not serialize inclusion query response");
+    Message::text(response)
+}
+
+/// Answers a submission status query with the batch of the proof, if it was built. The mock
+/// batcher doesn't create verification tasks, so proofs are never submitted.
+fn submission_status_response(state: &MockState, query: &GetSubmissionStatus) -> Message {
+    let inclusions = state
+        .inclusions
+        .lock()
+        .expect("Inclusions lock is not poisoned");
+    let leaf = VerificationCommitmentBatch::hash_data(&query.verification_data_commitment);
+    let status = match inclusions.get(&leaf) {
+        Some(batch_inclusion_data) => SubmissionStatus::Batched {
+            batch_merkle_root: batch_inclusion_data.batch_merkle_root,
+            index_in_batch: batch_inclusion_data.index_in_batch,
+        },
+        None => SubmissionStatus::Unknown,
+    };
+    let response = serde_json::to_string(&SubmissionStatusResponse { status })
+        .expect("Could not serialize submission status response");
+    Message::text(response)
+}
+
+/// Returns whether the message is a submission without a nonce and expiry.
+fn is_unprotected(message: &Result<WireMessage, WireError>) -> bool {
+    matches!(message, Ok(message) if !message.verification_data().is_empty()
+        && message.replay_protection().is_none())
+}
+
+fn parse_message(message: Result<WireMessage, WireError>) -> Vec<(VerificationData, Option<u64>)> {
+    match message {
+        Ok(WireMessage::Client(client_msg)) => {
+            vec![(client_msg.verification_data, client_msg.submission_id)]
+        }
+        Ok(WireMessage::Batched(batched_client_msg)) => batched_client_msg
+            .entries
+            .into_iter()
+            .map(|entry| (entry.verification_data, None))
+            .collect(),
+        Ok(WireMessage::Sponsored(sponsored_client_msg)) => {
+            let client_msg = sponsored_client_msg.client_message;
+            vec![(client_msg.verification_data, client_msg.submission_id)]
+        }
+        // Queries are only answered in text frames, as the SDK sends them
+        Ok(WireMessage::InclusionQuery(_) | WireMessage::GetSubmissionStatus(_)) => Vec::new(),
+        Err(e) => {
+            error!("Mock batcher failed to deserialize client message: {:?}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bug_report::{BugReport, FrameDirection, Transcript};
+    use crate::chunks::MAX_FRAME_SIZE;
+    use crate::client::{AlignedClient, AlignedClientBuilder, SubmitOptions};
+    use crate::encryption::EncryptionConfig;
+    use crate::errors::{AlignedError, SubmitError, VerificationError};
+    use crate::lifecycle::{submit_and_track, SubmissionEvent};
+    use crate::multiplex::MultiplexedConnection;
+    use crate::pending::{query_inclusion, resume_submission};
+    use crate::sdk::{
+        connect, negotiate_protocol, sign_with_signer, submit_multiple,
+        submit_multiple_best_effort, submit_multiple_with_single_signature,
+        submit_multiple_with_timeout, submit_sponsored, submit_with_progress, SubmissionProgress,
+        VerificationWaitConfig, VerifiedProof, CHUNKED_MESSAGES_FEATURE,
+        ENCRYPTED_PAYLOADS_FEATURE, INCLUSION_QUERIES_FEATURE, SUBMISSION_IDS_FEATURE,
+        USER_METADATA_FEATURE, ZSTD_MESSAGES_FEATURE,
+    };
+    use crate::submission_status::get_submission_status;
+    use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+    use crate::tls::TlsConfig;
+    use crate::types::{
+        AlignedVerificationData, Chain, ClientMessage, ReplayProtection, SponsoredClientMessage,
+    };
+    use ethers::signers::Signer;
+    use futures_util::future::join_all;
+    use std::time::Instant;
+
+    fn verification_data(proofs: usize) -> Vec<VerificationData> {
+        let mut rng = seeded_rng(0);
+        (0..proofs)
+            .map(|_| random_verification_data(&mut rng))
+            .collect()
+    }
+
+    async fn submit_to(
+        mock_batcher: &MockBatcher,
+        proofs: usize,
+    ) -> Option<Vec<AlignedVerificationData>> {
+        submit_multiple(
+            &mock_batcher.url(),
+            &verification_data(proofs),
+            deterministic_wallet(0),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn client_to(batcher_addr: &str) -> AlignedClientBuilder {
+        AlignedClient::builder()
+            .batcher_addr(batcher_addr)
+            .wallet(deterministic_wallet(0))
+    }
+
+    #[tokio::test]
+    async fn test_responses_without_faults() {
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+
+        let aligned_verification_data = submit_to(&mock_batcher, 3).await.unwrap();
+        assert_eq!(aligned_verification_data.len(), 3);
+
+        assert_eq!(submit_to(&mock_batcher, 1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_message_larger_than_a_frame_is_sent_in_chunks() {
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let mut verification_data = verification_data(2);
+        // Each byte of the proof takes 4 characters of the JSON message
+        verification_data[0].proof = vec![0xff; MAX_FRAME_SIZE / 3];
+        let serialized = serde_json::to_string(
+            &ClientMessage::new(verification_data[0].clone(), &deterministic_wallet(0))
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(serialized.len() > MAX_FRAME_SIZE);
+
+        let aligned_verification_data = submit_multiple(
+            &mock_batcher.url(),
+            &verification_data,
+            deterministic_wallet(0),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(aligned_verification_data.len(), 2);
+        assert_eq!(
+            aligned_verification_data[0]
+                .verification_data_commitment
+                .proof_commitment,
+            VerificationDataCommitment::from(verification_data[0].clone()).proof_commitment
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_messages_are_compressed() {
+        let mut verification_data = verification_data(1);
+        verification_data[0].proof = vec![0xab; 4 << 20];
+
+        // Compressed binary messages, and compressed JSON messages
+        let json_handshake = ProtocolHandshake {
+            protocol_version: CURRENT_PROTOCOL_VERSION,
+            features: vec![
+                CHUNKED_MESSAGES_FEATURE.to_string(),
+                ZSTD_MESSAGES_FEATURE.to_string(),
+            ],
+            encryption_public_key: None,
+            session: None,
+        };
+        for handshake in [None, Some(json_handshake)] {
+            let mock_batcher = MockBatcher::start(FaultConfig {
+                handshake,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let ws_stream = connect(&mock_batcher.url(), &TlsConfig::default())
+                .await
+                .unwrap();
+
+            let wallet = deterministic_wallet(0);
+            let mut transcript = Transcript::new();
+            let mut progress = SubmissionProgress::default();
+            let all_responded = submit_with_progress(
+                ws_stream,
+                &verification_data,
+                |message| sign_with_signer(&wallet, message),
+                false,
+                false,
+                &mut transcript,
+                &mut progress,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+            assert!(all_responded);
+            assert_eq!(
+                progress.aligned_verification_data[0]
+                    .verification_data_commitment
+                    .proof_commitment,
+                VerificationDataCommitment::from(verification_data[0].clone()).proof_commitment
+            );
+
+            let report = BugReport::new(
+                &mock_batcher.url(),
+                &verification_data,
+                transcript,
+                String::new(),
+            );
+            let sent_bytes: usize = report
+                .frames
+                .iter()
+                .filter(|frame| frame.direction == FrameDirection::Sent)
+                .map(|frame| frame.size_bytes)
+                .sum();
+            assert!(sent_bytes < verification_data[0].proof.len() / 10);
+        }
+    }
+
+    fn insufficient_balance() -> Rejection {
+        Rejection::InsufficientBalance {
+            address: deterministic_wallet(0).address(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejection_while_sending_ends_the_submission() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            reject_with: Some(insufficient_balance()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let ws_stream = connect(&mock_batcher.url(), &TlsConfig::default())
+            .await
+            .unwrap();
+
+        // Only the first message is ever signed, so the sender never ends on its own
+        let signed = AtomicUsize::new(0);
+        let sign_message = |message| {
+            let first = signed.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if !first {
+                    std::future::pending::<()>().await;
+                }
+                sign_with_signer(&deterministic_wallet(0), message).await
+            }
+        };
+
+        let result = timeout(
+            Duration::from_secs(10),
+            submit_with_progress(
+                ws_stream,
+                &verification_data(3),
+                sign_message,
+                false,
+                false,
+                &mut Transcript::new(),
+                &mut SubmissionProgress::default(),
+                None,
+                None,
+            ),
+        )
+        .await
+        .expect("The submission should end when it's rejected");
+        assert!(matches!(
+            result,
+            Err(SubmitError::MessageRejected(rejection)) if rejection == insufficient_balance()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejection_without_error_responses_is_the_close_code() {
+        // A batcher older than error responses
+        let mut handshake = protocol_handshake();
+        handshake
+            .features
+            .retain(|feature| feature != ERROR_RESPONSES_FEATURE);
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            reject_with: Some(insufficient_balance()),
+            handshake: Some(handshake),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let result = submit_multiple(
+            &mock_batcher.url(),
+            &verification_data(1),
+            deterministic_wallet(0),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SubmitError::BatcherRejection(code, _))
+                if code == RejectionCode::InsufficientBalance as u16
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_submission_timeout_closes_the_connection() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            response_delay: Duration::from_secs(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let start = Instant::now();
+        let result = submit_multiple_with_timeout(
+            &mock_batcher.url(),
+            &verification_data(2),
+            deterministic_wallet(0),
+            Duration::from_millis(300),
+        )
+        .await;
+        assert!(matches!(result, Err(SubmitError::Timeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_protocol_is_negotiated() {
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let handshake = negotiate_protocol(&mock_batcher.url(), &TlsConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(handshake.protocol_version, CURRENT_PROTOCOL_VERSION);
+        assert!(handshake.supports(CHUNKED_MESSAGES_FEATURE));
+
+        // Batchers older than the handshake only send their protocol version
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            legacy_handshake: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let handshake = negotiate_protocol(&mock_batcher.url(), &TlsConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            handshake,
+            ProtocolHandshake::without_features(CURRENT_PROTOCOL_VERSION)
+        );
+        assert_eq!(submit_to(&mock_batcher, 2).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_mismatch_fails_fast() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            handshake: Some(ProtocolHandshake {
+                protocol_version: CURRENT_PROTOCOL_VERSION + 1,
+                features: vec!["unknown_feature".to_string()],
+                encryption_public_key: None,
+                session: None,
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let result = submit_multiple(
+            &mock_batcher.url(),
+            &verification_data(1),
+            deterministic_wallet(0),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SubmitError::ProtocolMismatch { batcher_version, missing_features, .. })
+                if batcher_version == CURRENT_PROTOCOL_VERSION + 1 && missing_features.is_empty()
+        ));
+
+        // A message that needs chunks can't be sent to a batcher without them
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            legacy_handshake: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let mut verification_data = verification_data(1);
+        verification_data[0].proof = vec![0xff; MAX_FRAME_SIZE / 3];
+        let result = submit_multiple(
+            &mock_batcher.url(),
+            &verification_data,
+            deterministic_wallet(0),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SubmitError::ProtocolMismatch { missing_features, .. })
+                if missing_features == vec![CHUNKED_MESSAGES_FEATURE.to_string()]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_is_echoed_in_the_aligned_verification_data() {
+        let mut verification_data = verification_data(2);
+        verification_data[1].metadata = Some(b"job-42".to_vec());
+
+        // Binary messages, and JSON messages
+        let json_handshake = ProtocolHandshake {
+            protocol_version: CURRENT_PROTOCOL_VERSION,
+            features: vec![
+                CHUNKED_MESSAGES_FEATURE.to_string(),
+                USER_METADATA_FEATURE.to_string(),
+            ],
+            encryption_public_key: None,
+            session: None,
+        };
+        for handshake in [None, Some(json_handshake)] {
+            let mock_batcher = MockBatcher::start(FaultConfig {
+                handshake,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let aligned_verification_data = submit_multiple(
+                &mock_batcher.url(),
+                &verification_data,
+                deterministic_wallet(0),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+            assert_eq!(aligned_verification_data[0].metadata, None);
+            assert_eq!(
+                aligned_verification_data[1].metadata,
+                Some(b"job-42".to_vec())
+            );
+        }
+
+        // Batchers without the feature would drop the metadata
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            legacy_handshake: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let result = submit_multiple(
+            &mock_batcher.url(),
+            &verification_data[1..],
+            deterministic_wallet(0),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SubmitError::ProtocolMismatch { missing_features, .. })
+                if missing_features == vec![USER_METADATA_FEATURE.to_string()]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sponsored_submission() {
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+
+        let mut sponsored_messages = Vec::new();
+        for verification_data in verification_data(2) {
+            let client_message = ClientMessage::new(verification_data, &deterministic_wallet(0))
+                .await
+                .unwrap();
+            sponsored_messages.push(
+                SponsoredClientMessage::new(client_message, &deterministic_wallet(1))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let aligned_verification_data = submit_sponsored(&mock_batcher.url(), &sponsored_messages)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(aligned_verification_data.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_replay_protection_is_required() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            replay_protection_is_required: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let wallet = deterministic_wallet(0);
+
+        // Every message the SDK signs is replay protected, including batched messages
+        let aligned_verification_data =
+            submit_multiple(&mock_batcher.url(), &verification_data(2), wallet.clone())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(aligned_verification_data.len(), 2);
+        let aligned_verification_data = submit_multiple_with_single_signature(
+            &mock_batcher.url(),
+            &verification_data(3),
+            wallet.clone(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(aligned_verification_data.len(), 3);
+        let connection = MultiplexedConnection::connect(&mock_batcher.url(), &TlsConfig::default())
+            .await
+            .unwrap();
+        assert!(connection
+            .submit(&verification_data(1)[0], &wallet)
+            .await
+            .is_ok());
+        connection.close().await;
+
+        // Sponsored messages are sent as the prover signed them
+        for replay_protected in [true, false] {
+            let verification_data = verification_data(1).remove(0);
+            let client_message = if replay_protected {
+                ClientMessage::new_with_replay_protection(
+                    verification_data,
+                    ReplayProtection::new(Duration::from_secs(60)),
+                    |message| sign_with_signer(&wallet, message),
+                )
+                .await
+                .unwrap()
+            } else {
+                ClientMessage::new(verification_data, &wallet)
+                    .await
+                    .unwrap()
+            };
+            let sponsored_message =
+                SponsoredClientMessage::new(client_message, &deterministic_wallet(1))
+                    .await
+                    .unwrap();
+
+            let result = submit_sponsored(&mock_batcher.url(), &[sponsored_message]).await;
+            if replay_protected {
+                assert_eq!(result.unwrap().unwrap().len(), 1);
+            } else {
+                assert!(matches!(
+                    result,
+                    Err(SubmitError::MessageRejected(
+                        Rejection::ReplayedMessage { .. }
+                    ))
+                ));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_responses_are_never_received() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            drop_responses_percentage: 100,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let submission = timeout(Duration::from_millis(500), submit_to(&mock_batcher, 2)).await;
+        assert!(submission.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_submission_is_resumed() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            drop_responses_percentage: 100,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let submission = timeout(Duration::from_millis(500), submit_to(&mock_batcher, 2)).await;
+        assert!(submission.is_err());
+
+        // The responses were lost, but the batcher still knows the batch of the proofs
+        let mut commitments: Vec<VerificationDataCommitment> = verification_data(2)
+            .into_iter()
+            .map(VerificationDataCommitment::from)
+            .collect();
+        commitments.push(random_verification_data(&mut seeded_rng(1)).into());
+        let resumed = resume_submission(&mock_batcher.url(), &TlsConfig::default(), &commitments)
+            .await
+            .unwrap();
+        assert_eq!(resumed.aligned_verification_data.len(), 2);
+        for (aligned_verification_data, index) in
+            resumed.aligned_verification_data.iter().zip([0, 1])
+        {
+            assert_eq!(aligned_verification_data.index_in_batch, index);
+        }
+        assert!(resumed.pending.is_empty());
+        assert_eq!(resumed.unknown.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_status_of_submitted_proofs() {
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let aligned_verification_data = submit_to(&mock_batcher, 2).await.unwrap();
+
+        let commitment = VerificationDataCommitment::from(verification_data(2).remove(1));
+        let status = get_submission_status(&mock_batcher.url(), &TlsConfig::default(), &commitment)
+            .await
+            .unwrap();
+        assert_eq!(
+            status,
+            SubmissionStatus::Batched {
+                batch_merkle_root: aligned_verification_data[1].batch_merkle_root,
+                index_in_batch: 1,
+            }
+        );
+
+        let unknown = random_verification_data(&mut seeded_rng(1)).into();
+        let status = get_submission_status(&mock_batcher.url(), &TlsConfig::default(), &unknown)
+            .await
+            .unwrap();
+        assert_eq!(status, SubmissionStatus::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_inclusion_queries_need_the_feature() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            legacy_handshake: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let result = query_inclusion(&mock_batcher.url(), &TlsConfig::default(), &[]).await;
+        assert!(matches!(
+            result,
+            Err(SubmitError::ProtocolMismatch { missing_features, .. })
+                if missing_features == vec![INCLUSION_QUERIES_FEATURE.to_string()]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delayed_responses() {
+        let response_delay = Duration::from_millis(100);
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            response_delay,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let start = Instant::now();
+        assert_eq!(submit_to(&mock_batcher, 2).await.unwrap().len(), 2);
+        assert!(start.elapsed() >= 2 * response_delay);
+    }
+
+    #[tokio::test]
+    async fn test_timing_of_each_proof() {
+        let response_delay = Duration::from_millis(50);
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            response_delay,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        for single_signature in [false, true] {
+            let submission = client_to(&mock_batcher.url())
+                .submit_options(SubmitOptions {
+                    single_signature,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap()
+                .submit_multiple_with_timing(&verification_data(3))
+                .await
+                .unwrap();
+
+            assert_eq!(submission.aligned_verification_data.unwrap().len(), 3);
+            assert_eq!(submission.timings.len(), 3);
+            for (position, timing) in submission.timings.iter().enumerate() {
+                assert_eq!(timing.position, position);
+                let sent_at = timing.sent_at.unwrap();
+                let acknowledged_at = timing.acknowledged_at.unwrap();
+                assert!(sent_at >= timing.queued_at);
+                assert!(acknowledged_at.duration_since(sent_at).unwrap() >= response_delay);
+                assert_eq!(timing.included_at, Some(acknowledged_at));
+            }
+        }
+
+        // Responses that can't be read acknowledge the proof without including it
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            malformed_responses_percentage: 100,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let submission = client_to(&mock_batcher.url())
+            .build()
+            .unwrap()
+            .submit_multiple_with_timing(&verification_data(2))
+            .await
+            .unwrap();
+        assert!(submission
+            .timings
+            .iter()
+            .all(|timing| timing.acknowledged_at.is_some() && timing.included_at.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_responses_are_skipped() {
+        let faults = FaultConfig {
+            malformed_responses_percentage: 50,
+            seed: 3,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(faults.seed);
+        let well_formed_responses = (0..8)
+            .filter(|_| {
+                let dropped = rng.gen_range(0..100) < faults.drop_responses_percentage;
+                let malformed = rng.gen_range(0..100) < faults.malformed_responses_percentage;
+                !dropped && !malformed
+            })
+            .count();
+        assert!(well_formed_responses > 0 && well_formed_responses < 8);
+
+        let mock_batcher = MockBatcher::start(faults).await.unwrap();
+
+        let aligned_verification_data = submit_to(&mock_batcher, 8).await.unwrap();
+        assert_eq!(aligned_verification_data.len(), well_formed_responses);
+    }
+
+    #[tokio::test]
+    async fn test_connection_closed_mid_batch() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert!(submit_to(&mock_batcher, 3).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_strict_submission_fails_on_anomalies() {
+        let submit_strict = |mock_batcher: MockBatcher| async move {
+            client_to(&mock_batcher.url())
+                .submit_options(SubmitOptions {
+                    strict: true,
+                    ..Default::default()
+                })
+                .build()
+                .unwrap()
+                .submit_multiple(&verification_data(3))
+                .await
+        };
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        assert_eq!(submit_strict(mock_batcher).await.unwrap().unwrap().len(), 3);
+
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            malformed_responses_percentage: 100,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            submit_strict(mock_batcher).await,
+            Err(SubmitError::ProtocolViolation(e)) if e.starts_with("Response 1 of 3 is not a batch inclusion data")
+        ));
+
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            submit_strict(mock_batcher).await,
+            Err(SubmitError::ProtocolViolation(e)) if e == "Connection closed without a reason after 1 of 3 responses"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bug_report_of_strict_submission_holds_the_violation() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            malformed_responses_percentage: 100,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let bug_report_path = std::env::temp_dir().join(format!(
+            "aligned_strict_bug_report_{}.json",
+            std::process::id()
+        ));
+
+        let result = client_to(&mock_batcher.url())
+            .submit_options(SubmitOptions {
+                strict: true,
+                bug_report_path: Some(bug_report_path.clone()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .submit_multiple(&verification_data(2))
+            .await;
+        assert!(matches!(result, Err(SubmitError::ProtocolViolation(_))));
+
+        let bug_report: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&bug_report_path).unwrap()).unwrap();
+        std::fs::remove_file(&bug_report_path).unwrap();
+        assert!(bug_report["error"]
+            .as_str()
+            .unwrap()
+            .contains("is not a batch inclusion data"));
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_submission_keeps_received_responses() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(2),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let verification_data = verification_data(4);
+
+        let partial_submission = submit_multiple_best_effort(
+            &mock_batcher.url(),
+            &verification_data,
+            deterministic_wallet(0),
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert!(!partial_submission.is_complete());
+        assert!(partial_submission.interrupted_by.is_none());
+        assert_eq!(partial_submission.aligned_verification_data.len(), 2);
+        let unconfirmed_positions: Vec<usize> = partial_submission
+            .unconfirmed
+            .iter()
+            .map(|unconfirmed| unconfirmed.position)
+            .collect();
+        assert_eq!(unconfirmed_positions, vec![2, 3]);
+        assert_eq!(
+            partial_submission.unconfirmed[0]
+                .verification_data_commitment
+                .proof_commitment,
+            VerificationDataCommitment::from(verification_data[2].clone()).proof_commitment
+        );
+        assert_eq!(partial_submission.timings.len(), 4);
+        assert!(partial_submission.timings[1].included_at.is_some());
+        assert!(partial_submission.timings[2].acknowledged_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bug_report_written_on_failed_submission() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(2),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let bug_report_path =
+            std::env::temp_dir().join(format!("aligned_bug_report_{}.json", std::process::id()));
+
+        let result = client_to(&mock_batcher.url())
+            .submit_options(SubmitOptions {
+                bug_report_path: Some(bug_report_path.clone()),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+            .submit_multiple(&verification_data(3))
+            .await;
+        assert!(result.unwrap().is_none());
+
+        let bug_report: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&bug_report_path).unwrap()).unwrap();
+        std::fs::remove_file(&bug_report_path).unwrap();
+
+        assert_eq!(bug_report["entries"].as_array().unwrap().len(), 3);
+        assert_eq!(bug_report["responses"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            bug_report["batcher_protocol_version"],
+            CURRENT_PROTOCOL_VERSION
+        );
+        // Protocol version, 3 sent messages, 2 responses and the close frame
+        assert_eq!(bug_report["frames"].as_array().unwrap().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_submission() {
+        let encryption_key = EncryptionKey::generate();
+        let encryption =
+            EncryptionConfig::default().with_batcher_public_key(encryption_key.public_key());
+        let verification_data = verification_data(2);
+
+        // Binary messages, and JSON messages, which are sent in chunks once encrypted
+        let json_handshake = ProtocolHandshake {
+            protocol_version: CURRENT_PROTOCOL_VERSION,
+            features: vec![CHUNKED_MESSAGES_FEATURE.to_string()],
+            encryption_public_key: None,
+            session: None,
+        };
+        for handshake in [None, Some(json_handshake)] {
+            let mock_batcher = MockBatcher::start(FaultConfig {
+                handshake,
+                encryption_key: Some(encryption_key.clone()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let aligned_verification_data = client_to(&mock_batcher.url())
+                .submit_options(SubmitOptions {
+                    strict: true,
+                    ..Default::default()
+                })
+                .encryption(encryption.clone())
+                .build()
+                .unwrap()
+                .submit_multiple(&verification_data)
+                .await
+                .unwrap()
+                .unwrap();
+
+            // Commitments are computed over the plaintext
+            for (aligned_verification_data, verification_data) in
+                aligned_verification_data.iter().zip(&verification_data)
+            {
+                let commitment = VerificationDataCommitment::from(verification_data.clone());
+                assert_eq!(
+                    aligned_verification_data
+                        .verification_data_commitment
+                        .pub_input_commitment,
+                    commitment.pub_input_commitment
+                );
+                assert_eq!(
+                    aligned_verification_data
+                        .verification_data_commitment
+                        .proof_commitment,
+                    commitment.proof_commitment
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encryption_needs_the_key_of_the_batcher() {
+        let verification_data = verification_data(1);
+        let submit = |url: String, encryption: EncryptionConfig| {
+            let verification_data = verification_data.clone();
+            async move {
+                client_to(&url)
+                    .encryption(encryption)
+                    .build()
+                    .unwrap()
+                    .submit_multiple(&verification_data)
+                    .await
+            }
+        };
+
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        assert!(matches!(
+            submit(mock_batcher.url(), EncryptionConfig::default()).await,
+            Err(SubmitError::ProtocolMismatch { missing_features, .. })
+                if missing_features == vec![ENCRYPTED_PAYLOADS_FEATURE.to_string()]
+        ));
+
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            encryption_key: Some(EncryptionKey::generate()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let other_key = EncryptionKey::generate().public_key();
+        assert!(matches!(
+            submit(
+                mock_batcher.url(),
+                EncryptionConfig::default().with_batcher_public_key(other_key)
+            )
+            .await,
+            Err(SubmitError::ProtocolViolation(_))
+        ));
+
+        // Clients that don't opt in keep sending their messages unencrypted
+        assert_eq!(submit_to(&mock_batcher, 1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_required() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            auth_tokens: vec!["secret".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let submit = |tls_config: TlsConfig| {
+            let url = mock_batcher.url();
+            async move {
+                client_to(&url)
+                    .tls_config(tls_config)
+                    .build()
+                    .unwrap()
+                    .submit_multiple(&verification_data(1))
+                    .await
+            }
+        };
+
+        let aligned_verification_data = submit(TlsConfig::default().with_auth_token("secret"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(aligned_verification_data.len(), 1);
+
+        for tls_config in [
+            TlsConfig::default(),
+            TlsConfig::default().with_auth_token("not the secret"),
+        ] {
+            assert!(matches!(
+                submit(tls_config).await,
+                Err(SubmitError::Unauthorized(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_submissions_share_a_connection() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            reverse_responses: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let connection = MultiplexedConnection::connect(&mock_batcher.url(), &TlsConfig::default())
+            .await
+            .unwrap();
+
+        // Responses come in reverse order, so each submission only gets its own one by its id
+        let wallet = deterministic_wallet(0);
+        let verification_data = verification_data(3);
+        let results = join_all(
+            verification_data
+                .iter()
+                .map(|verification_data| connection.submit(verification_data, &wallet)),
+        )
+        .await;
+
+        let mut indices_in_batch: Vec<usize> = results
+            .into_iter()
+            .map(|result| result.unwrap().index_in_batch)
+            .collect();
+        indices_in_batch.sort();
+        assert_eq!(indices_in_batch, vec![0, 1, 2]);
+        connection.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_multiplexing_needs_submission_ids() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            legacy_handshake: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let result =
+            MultiplexedConnection::connect(&mock_batcher.url(), &TlsConfig::default()).await;
+        assert!(matches!(
+            result,
+            Err(SubmitError::ProtocolMismatch { missing_features, .. })
+                if missing_features == vec![SUBMISSION_IDS_FEATURE.to_string()]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejection_fails_the_multiplexed_submissions() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            reject_with: Some(insufficient_balance()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let connection = MultiplexedConnection::connect(&mock_batcher.url(), &TlsConfig::default())
+            .await
+            .unwrap();
+
+        let wallet = deterministic_wallet(0);
+        let verification_data = verification_data(2);
+        // The rejected submission gets the error response naming it
+        assert!(matches!(
+            connection.submit(&verification_data[0], &wallet).await,
+            Err(SubmitError::MessageRejected(rejection)) if rejection == insufficient_balance()
+        ));
+        // Submissions after the rejection fail without being sent
+        assert!(matches!(
+            connection.submit(&verification_data[1], &wallet).await,
+            Err(SubmitError::BatcherRejection(code, _))
+                if code == RejectionCode::InsufficientBalance as u16
+        ));
+    }
+
+    /// Tracks the submission of the proofs to the mock batcher, with an RPC node that can't be
+    /// reached, so that the submission ends before its batches are waited for.
+    async fn track(
+        mock_batcher: &MockBatcher,
+        proofs: usize,
+    ) -> (
+        Result<Option<Vec<VerifiedProof>>, AlignedError>,
+        Vec<SubmissionEvent>,
+    ) {
+        let events = std::sync::Mutex::new(Vec::new());
+        let result = submit_and_track(
+            &mock_batcher.url(),
+            &verification_data(proofs),
+            deterministic_wallet(0),
+            Chain::Devnet,
+            "http://127.0.0.1:1",
+            &VerificationWaitConfig::default(),
+            |event| events.lock().unwrap().push(event),
+        )
+        .await;
+        (result, events.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_proofs_are_reported_sent_and_then_accepted() {
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+
+        let (result, events) = track(&mock_batcher, 2).await;
+        assert!(matches!(
+            result,
+            Err(AlignedError::VerificationError(
+                VerificationError::EthError(_)
+            ))
+        ));
+
+        // The mock batcher responds once it has received every message
+        assert_eq!(events.len(), 4);
+        let (
+            SubmissionEvent::Sent {
+                position: 0,
+                at: sent_at,
+            },
+            SubmissionEvent::Sent { position: 1, .. },
+        ) = (&events[0], &events[1])
+        else {
+            panic!("Unexpected events {:?}", events);
+        };
+        for (position, event) in events[2..].iter().enumerate() {
+            let SubmissionEvent::Accepted {
+                position: accepted_position,
+                at: accepted_at,
+                ..
+            } = event
+            else {
+                panic!("Unexpected event {:?}", event);
+            };
+            assert_eq!(*accepted_position, position);
+            assert!(accepted_at >= sent_at);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unresponded_proofs_are_not_reported_accepted() {
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let (result, events) = track(&mock_batcher, 2).await;
+        assert!(result.unwrap().is_none());
+        let accepted: Vec<usize> = events
+            .iter()
+            .filter_map(|event| match event {
+                SubmissionEvent::Accepted { position, .. } => Some(*position),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(accepted, vec![0]);
+    }
+}
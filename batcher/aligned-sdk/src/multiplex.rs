@@ -0,0 +1,313 @@
+//! Submissions sharing a single connection to the batcher. The batcher responds to the messages
+//! of a connection in the order they were sent, so concurrent submissions on the same connection
+//! couldn't tell which response is theirs. Batchers supporting the `submission_ids` feature echo
+//! the submission id of each client message in its response, which `MultiplexedConnection` uses
+//! to route each response to the submission waiting for it, so that many `submit` calls can run
+//! concurrently over one connection.
+//!
+//! The batcher closes the connection when it rejects a message, which fails every submission
+//! pending on it with the rejection. Batchers supporting the `error_responses` feature tell which
+//! message was rejected in the `ErrorResponse` they send before, whose submission fails with the
+//! details of the rejection.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ethers::signers::Signer;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use log::{debug, error};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::bug_report::Transcript;
+use crate::chunks::ChunkSizer;
+use crate::errors::SubmitError;
+use crate::sdk::{
+    connect, read_handshake, replay_protection_validity, send_message, sign_with_signer,
+    signed_client_message, verify_response, CURRENT_PROTOCOL_VERSION, SUBMISSION_IDS_FEATURE,
+};
+use crate::tls::TlsConfig;
+use crate::types::{
+    AlignedVerificationData, BatchInclusionData, ErrorResponse, ProtocolHandshake, Rejection,
+    ReplayProtection, VerificationData, VerificationDataCommitment,
+};
+use crate::wire::WireMessage;
+
+type WsWrite = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+type WsRead = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+type ResponseSender = oneshot::Sender<Result<BatchInclusionData, SubmitError>>;
+
+/// How the connection was closed, failing the submissions pending on it.
+#[derive(Clone)]
+enum ClosedBy {
+    Rejection(u16, Vec<u8>),
+    /// A rejection told in an error response without the submission id of a pending submission.
+    ErrorResponse(Rejection),
+    Disconnection,
+}
+
+impl ClosedBy {
+    fn error(&self) -> SubmitError {
+        match self {
+            ClosedBy::Rejection(code, reason) => {
+                SubmitError::BatcherRejection(*code, reason.clone())
+            }
+            ClosedBy::ErrorResponse(rejection) => SubmitError::MessageRejected(rejection.clone()),
+            ClosedBy::Disconnection => {
+                SubmitError::ConnectionError(tungstenite::Error::ConnectionClosed)
+            }
+        }
+    }
+}
+
+/// Submissions waiting for their response, by submission id.
+#[derive(Default)]
+struct Routes {
+    pending: HashMap<u64, ResponseSender>,
+    closed_by: Option<ClosedBy>,
+}
+
+/// A connection to the batcher over which many submissions can run at once.
+pub struct MultiplexedConnection {
+    ws_write: tokio::sync::Mutex<(WsWrite, ChunkSizer)>,
+    handshake: ProtocolHandshake,
+    routes: Arc<Mutex<Routes>>,
+    next_submission_id: AtomicU64,
+    router: JoinHandle<()>,
+}
+
+impl MultiplexedConnection {
+    /// Connects to the batcher, checking that it echoes submission ids.
+    /// # Arguments
+    /// * `batcher_addr` - The address of the batcher.
+    /// * `tls_config` - The root certificates and server name with which `wss://` addresses are
+    ///   connected.
+    /// # Errors
+    /// * `ConnectionError` if there is an error connecting to the batcher.
+    /// * `TlsError` if the TLS handshake with the batcher fails.
+    /// * `Unauthorized` if the batcher refuses the access token of `tls_config`, or the lack of
+    ///   one.
+    /// * `ProtocolMismatch` if the batcher doesn't support the `submission_ids` feature, or its
+    ///   protocol version is newer than the one of the SDK.
+    /// * `ProtocolViolation` if the batcher doesn't send its handshake.
+    pub async fn connect(batcher_addr: &str, tls_config: &TlsConfig) -> Result<Self, SubmitError> {
+        let ws_stream = connect(batcher_addr, tls_config).await?;
+        let (ws_write, mut ws_read) = ws_stream.split();
+
+        let handshake = read_handshake(&mut ws_read, true, &mut Transcript::new())
+            .await?
+            .ok_or_else(|| {
+                SubmitError::ProtocolViolation("Batcher did not send its handshake".to_string())
+            })?;
+        if !handshake.supports(SUBMISSION_IDS_FEATURE) {
+            return Err(SubmitError::ProtocolMismatch {
+                sdk_version: CURRENT_PROTOCOL_VERSION,
+                batcher_version: handshake.protocol_version,
+                missing_features: vec![SUBMISSION_IDS_FEATURE.to_string()],
+            });
+        }
+
+        let routes = Arc::new(Mutex::new(Routes::default()));
+        let router = tokio::spawn(route_responses(ws_read, routes.clone()));
+        Ok(MultiplexedConnection {
+            ws_write: tokio::sync::Mutex::new((ws_write, ChunkSizer::default())),
+            handshake,
+            routes,
+            next_submission_id: AtomicU64::new(0),
+            router,
+        })
+    }
+
+    /// Submits a proof over the connection, as `sdk::submit`, waiting for its response while
+    /// other submissions run over the same connection.
+    /// # Arguments
+    /// * `verification_data` - The verification data of the proof.
+    /// * `wallet` - The wallet used to sign the proof.
+    /// # Returns
+    /// * The aligned verification data obtained when submitting the proof.
+    /// # Errors
+    /// * `MessageRejected` if the batcher rejects this message, or any other message of the
+    ///   connection without telling which one, since it closes the connection.
+    /// * `BatcherRejection` if the batcher rejects a message of the connection without an error
+    ///   response.
+    /// * `ConnectionError` if the connection is lost before the proof is responded.
+    /// * `ProtocolViolation` if the response doesn't match the proof.
+    /// * The errors of `VerificationData::validate` and of signing the message.
+    pub async fn submit<S: Signer>(
+        &self,
+        verification_data: &VerificationData,
+        wallet: &S,
+    ) -> Result<AlignedVerificationData, SubmitError> {
+        verification_data.validate()?;
+        let submission_id = self.next_submission_id.fetch_add(1, Ordering::Relaxed);
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut routes = self.routes.lock().expect("Routes lock is not poisoned");
+            if let Some(closed_by) = &routes.closed_by {
+                return Err(closed_by.error());
+            }
+            routes.pending.insert(submission_id, sender);
+        }
+
+        // The message is signed before taking the connection, so that the submissions sharing it
+        // are signed concurrently. The batcher accepts their nonces in any order.
+        let replay_protection =
+            replay_protection_validity(&self.handshake).map(ReplayProtection::new);
+        let sent = match signed_client_message(verification_data.clone(), replay_protection, |m| {
+            sign_with_signer(wallet, m)
+        })
+        .await
+        {
+            Ok(mut client_message) => {
+                client_message.submission_id = Some(submission_id);
+                let mut ws_write = self.ws_write.lock().await;
+                let (ws_write, chunk_sizer) = &mut *ws_write;
+                send_message(
+                    ws_write,
+                    &WireMessage::Client(client_message),
+                    &self.handshake,
+                    None,
+                    &mut Transcript::new(),
+                    chunk_sizer,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            self.routes
+                .lock()
+                .expect("Routes lock is not poisoned")
+                .pending
+                .remove(&submission_id);
+            return Err(e);
+        }
+        debug!("Submission {} sent", submission_id);
+
+        let batch_inclusion_data = receiver
+            .await
+            .map_err(|_| SubmitError::ConnectionError(tungstenite::Error::ConnectionClosed))??;
+        let verification_data_commitment =
+            VerificationDataCommitment::from(verification_data.clone());
+        if !verify_response(&verification_data_commitment, &batch_inclusion_data) {
+            return Err(SubmitError::ProtocolViolation(format!(
+                "Response to submission {} doesn't match its proof",
+                submission_id
+            )));
+        }
+        Ok(AlignedVerificationData::new(
+            &verification_data_commitment,
+            &batch_inclusion_data,
+        ))
+    }
+
+    /// Closes the connection. The submissions still pending on it fail.
+    pub async fn close(self) {
+        let (ws_write, _) = &mut *self.ws_write.lock().await;
+        if let Err(e) = ws_write.close().await {
+            debug!("Connection already closed: {}", e);
+        }
+    }
+}
+
+impl Drop for MultiplexedConnection {
+    fn drop(&mut self) {
+        self.router.abort();
+    }
+}
+
+/// Routes the responses of the batcher to the submissions waiting for them until the connection
+/// is closed, and then fails the ones still pending. Responses that can't be read or have an
+/// unknown submission id are logged and dropped, so their submission waits until the connection
+/// is closed. Error responses fail the submission they name, or else every pending submission
+/// once the connection is closed.
+async fn route_responses(mut ws_read: WsRead, routes: Arc<Mutex<Routes>>) {
+    let mut unrouted_rejection = None;
+    let closed_by = loop {
+        match ws_read.next().await {
+            Some(Ok(Message::Binary(data))) => {
+                let batch_inclusion_data = match serde_json::from_slice::<BatchInclusionData>(&data)
+                {
+                    Ok(batch_inclusion_data) => batch_inclusion_data,
+                    Err(e) => {
+                        error!("Error while deserializing batcher response: {}", e);
+                        continue;
+                    }
+                };
+                let sender = batch_inclusion_data
+                    .submission_id
+                    .and_then(|submission_id| {
+                        routes
+                            .lock()
+                            .expect("Routes lock is not poisoned")
+                            .pending
+                            .remove(&submission_id)
+                    });
+                match sender {
+                    // The submission may have been dropped while waiting
+                    Some(sender) => {
+                        let _ = sender.send(Ok(batch_inclusion_data));
+                    }
+                    None => error!(
+                        "Received a response to an unknown submission: {:?}",
+                        batch_inclusion_data.submission_id
+                    ),
+                }
+            }
+            Some(Ok(Message::Text(text))) => {
+                let error_response = match serde_json::from_str::<ErrorResponse>(&text) {
+                    Ok(error_response) => error_response,
+                    Err(e) => {
+                        error!("Error while deserializing batcher error response: {}", e);
+                        continue;
+                    }
+                };
+                error!(
+                    "Message rejected by the batcher: {:?}",
+                    error_response.rejection
+                );
+                let sender = error_response.submission_id.and_then(|submission_id| {
+                    routes
+                        .lock()
+                        .expect("Routes lock is not poisoned")
+                        .pending
+                        .remove(&submission_id)
+                });
+                match sender {
+                    Some(sender) => {
+                        let _ = sender
+                            .send(Err(SubmitError::MessageRejected(error_response.rejection)));
+                    }
+                    None => unrouted_rejection = Some(error_response.rejection),
+                }
+            }
+            Some(Ok(Message::Close(Some(close_frame)))) => {
+                error!("Connection was closed by the batcher: {}", close_frame);
+                break match unrouted_rejection.take() {
+                    Some(rejection) => ClosedBy::ErrorResponse(rejection),
+                    None => ClosedBy::Rejection(
+                        close_frame.code.into(),
+                        close_frame.reason.as_bytes().to_vec(),
+                    ),
+                };
+            }
+            Some(Ok(Message::Close(None))) | Some(Err(_)) | None => {
+                break ClosedBy::Disconnection;
+            }
+            // Control frames are answered by the websocket library
+            Some(Ok(_)) => {}
+        }
+    };
+
+    let mut routes = routes.lock().expect("Routes lock is not poisoned");
+    for (_, sender) in routes.pending.drain() {
+        let _ = sender.send(Err(closed_by.error()));
+    }
+    routes.closed_by = Some(closed_by);
+}
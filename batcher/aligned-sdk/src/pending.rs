@@ -0,0 +1,294 @@
+//! Submissions interrupted before their responses were received, such as when the process
+//! submitting them is killed after sending the proofs. The proofs are in the batch queue, and
+//! once their batch is built its inclusion data is lost with the connection.
+//!
+//! The commitments of the proofs of a submission are recorded in a pending file before they are
+//! sent, and removed once their responses are received. The proofs left in the file are resumed
+//! later by asking the batcher for their status with an `InclusionQuery`, which batchers
+//! supporting the `inclusion_queries` feature answer with the inclusion data of the proofs of
+//! the batches they built recently. Batchers keep it in memory, so the proofs of batches built
+//! before the batcher restarted are unknown to it, and can be recovered from the data of their
+//! batch, as in `recovery`.
+
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::errors::SubmitError;
+use crate::sdk::{query_batcher, verify_response, INCLUSION_QUERIES_FEATURE};
+use crate::tls::TlsConfig;
+use crate::types::{
+    AlignedVerificationData, InclusionQuery, InclusionQueryResponse, InclusionStatus,
+    VerificationCommitmentBatch, VerificationDataCommitment,
+};
+use crate::wire::WireMessage;
+
+/// Name of the pending file in the directory where the CLI saves the aligned verification data.
+pub const PENDING_FILE_NAME: &str = ".aligned_pending.json";
+
+/// Proofs sent to a batcher whose responses were not received yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSubmission {
+    pub batcher_addr: String,
+    /// Seconds since the Unix epoch at which the proofs were sent.
+    pub submitted_at: u64,
+    pub commitments: Vec<VerificationDataCommitment>,
+}
+
+impl PendingSubmission {
+    /// Returns the pending submission of proofs sent to `batcher_addr` now.
+    pub fn new(batcher_addr: &str, commitments: Vec<VerificationDataCommitment>) -> Self {
+        PendingSubmission {
+            batcher_addr: batcher_addr.to_string(),
+            submitted_at: clock::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            commitments,
+        }
+    }
+}
+
+/// Pending submissions, kept in a JSON file.
+#[derive(Debug)]
+pub struct PendingStore {
+    path: PathBuf,
+}
+
+impl PendingStore {
+    /// Returns the store kept in the file at `path`, which is created on the first recorded
+    /// submission and removed once none is pending.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        PendingStore { path: path.into() }
+    }
+
+    /// Returns the store kept in the `PENDING_FILE_NAME` file of `dir`.
+    pub fn in_dir(dir: &Path) -> Self {
+        PendingStore::new(dir.join(PENDING_FILE_NAME))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the pending submissions, in the order they were recorded.
+    /// # Errors
+    /// * `IoError` if the file of the store exists but can't be read.
+    /// * `SerdeError` if the file of the store is not valid.
+    pub fn load(&self) -> Result<Vec<PendingSubmission>, SubmitError> {
+        match std::fs::read(&self.path) {
+            Ok(submissions) => Ok(serde_json::from_slice(&submissions)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(SubmitError::IoError(self.path.clone(), e)),
+        }
+    }
+
+    /// Records a submission before its proofs are sent.
+    /// # Errors
+    /// * `IoError` if the file of the store can't be read or written.
+    /// * `SerdeError` if the file of the store is not valid.
+    pub fn record(&self, submission: PendingSubmission) -> Result<(), SubmitError> {
+        let mut submissions = self.load()?;
+        submissions.push(submission);
+        self.save(&submissions)
+    }
+
+    /// Removes the proofs with these commitments from every pending submission, along with the
+    /// submissions left without proofs.
+    /// # Errors
+    /// * `IoError` if the file of the store can't be read or written.
+    /// * `SerdeError` if the file of the store is not valid.
+    pub fn remove(&self, commitments: &[VerificationDataCommitment]) -> Result<(), SubmitError> {
+        let removed: Vec<[u8; 32]> = commitments.iter().map(commitment_leaf).collect();
+        let mut submissions = self.load()?;
+        for submission in submissions.iter_mut() {
+            submission
+                .commitments
+                .retain(|commitment| !removed.contains(&commitment_leaf(commitment)));
+        }
+        submissions.retain(|submission| !submission.commitments.is_empty());
+        self.save(&submissions)
+    }
+
+    fn save(&self, submissions: &[PendingSubmission]) -> Result<(), SubmitError> {
+        if submissions.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(SubmitError::IoError(self.path.clone(), e))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| SubmitError::IoError(dir.into(), e))?;
+        }
+        // Written next to the store and renamed, so that an interrupted write doesn't lose it
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(submissions)?)
+            .map_err(|e| SubmitError::IoError(tmp_path.clone(), e))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .map_err(|e| SubmitError::IoError(self.path.clone(), e))
+    }
+}
+
+fn commitment_leaf(commitment: &VerificationDataCommitment) -> [u8; 32] {
+    VerificationCommitmentBatch::hash_data(commitment)
+}
+
+/// Proofs of a resumed submission, by what the batcher knows about them.
+#[derive(Default)]
+pub struct ResumedSubmission {
+    /// The aligned verification data of the proofs included in a batch.
+    pub aligned_verification_data: Vec<AlignedVerificationData>,
+    /// The proofs still waiting for their batch.
+    pub pending: Vec<VerificationDataCommitment>,
+    /// The proofs the batcher didn't receive or no longer knows, which have to be submitted
+    /// again or recovered from the data of their batch.
+    pub unknown: Vec<VerificationDataCommitment>,
+}
+
+/// Asks the batcher for the status of the proofs with these commitments.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher the proofs were sent to.
+/// * `tls_config` - The root certificates and server name with which `wss://` addresses are
+///   connected.
+/// * `commitments` - The verification data commitments of the proofs.
+/// # Returns
+/// * The status of each proof, in the order of `commitments`.
+/// # Errors
+/// * `ConnectionError` if there is an error connecting to the batcher.
+/// * `TlsError` if the TLS handshake with the batcher fails.
+/// * `Unauthorized` if the batcher refuses the access token of `tls_config`, or the lack of one.
+/// * `ProtocolMismatch` if the batcher doesn't support the `inclusion_queries` feature, or its
+///   protocol version is newer than the one of the SDK.
+/// * `MessageRejected` if the batcher rejects the query.
+/// * `ProtocolViolation` if the batcher doesn't send its handshake, or doesn't answer with the
+///   status of every proof.
+pub async fn query_inclusion(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+    commitments: &[VerificationDataCommitment],
+) -> Result<Vec<InclusionStatus>, SubmitError> {
+    let query = WireMessage::InclusionQuery(InclusionQuery {
+        commitments: commitments.to_vec(),
+    });
+    let response: InclusionQueryResponse = query_batcher(
+        batcher_addr,
+        tls_config,
+        INCLUSION_QUERIES_FEATURE,
+        &query,
+        "inclusion query",
+    )
+    .await?;
+
+    if response.statuses.len() != commitments.len() {
+        return Err(SubmitError::ProtocolViolation(format!(
+            "Batcher answered with the status of {} of {} proofs",
+            response.statuses.len(),
+            commitments.len()
+        )));
+    }
+    Ok(response.statuses)
+}
+
+/// Resumes a submission whose responses were not received, asking the batcher for the status of
+/// its proofs as `query_inclusion`. The inclusion data of each included proof is checked against
+/// its commitment, and the proofs whose inclusion data doesn't match are taken as unknown.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher the proofs were sent to.
+/// * `tls_config` - The root certificates and server name with which `wss://` addresses are
+///   connected.
+/// * `commitments` - The verification data commitments of the proofs.
+/// # Returns
+/// * The aligned verification data of the included proofs, and the proofs that are not.
+/// # Errors
+/// * The errors of `query_inclusion`.
+pub async fn resume_submission(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+    commitments: &[VerificationDataCommitment],
+) -> Result<ResumedSubmission, SubmitError> {
+    let statuses = query_inclusion(batcher_addr, tls_config, commitments).await?;
+
+    let mut resumed = ResumedSubmission::default();
+    for (commitment, status) in commitments.iter().zip(statuses) {
+        match status {
+            InclusionStatus::Included(batch_inclusion_data) => {
+                if verify_response(commitment, &batch_inclusion_data) {
+                    resumed
+                        .aligned_verification_data
+                        .push(AlignedVerificationData::new(
+                            commitment,
+                            &batch_inclusion_data,
+                        ));
+                } else {
+                    error!("Batcher answered with inclusion data that doesn't match the proof");
+                    resumed.unknown.push(commitment.clone());
+                }
+            }
+            InclusionStatus::Pending => resumed.pending.push(commitment.clone()),
+            InclusionStatus::Unknown => resumed.unknown.push(commitment.clone()),
+        }
+    }
+    Ok(resumed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{random_verification_data, seeded_rng};
+
+    fn commitments(seed: u64, proofs: usize) -> Vec<VerificationDataCommitment> {
+        let mut rng = seeded_rng(seed);
+        (0..proofs)
+            .map(|_| random_verification_data(&mut rng).into())
+            .collect()
+    }
+
+    fn leaves(commitments: &[VerificationDataCommitment]) -> Vec<[u8; 32]> {
+        commitments.iter().map(commitment_leaf).collect()
+    }
+
+    #[test]
+    fn test_responded_proofs_are_removed_from_pending_submissions() {
+        let dir = std::env::temp_dir().join(format!("aligned_pending_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = PendingStore::in_dir(&dir);
+        assert!(store.load().unwrap().is_empty());
+
+        let first = commitments(0, 3);
+        let second = commitments(1, 2);
+        store
+            .record(PendingSubmission::new("ws://localhost:8080", first.clone()))
+            .unwrap();
+        store
+            .record(PendingSubmission::new(
+                "ws://localhost:8081",
+                second.clone(),
+            ))
+            .unwrap();
+
+        store
+            .remove(&[first[1].clone(), second[0].clone()])
+            .unwrap();
+        let submissions = store.load().unwrap();
+        assert_eq!(submissions.len(), 2);
+        assert_eq!(submissions[0].batcher_addr, "ws://localhost:8080");
+        assert_eq!(
+            leaves(&submissions[0].commitments),
+            leaves(&[first[0].clone(), first[2].clone()])
+        );
+        assert_eq!(leaves(&submissions[1].commitments), leaves(&second[1..]));
+
+        // Submissions without proofs are removed, and so is the file once none is pending
+        store.remove(&second).unwrap();
+        assert_eq!(store.load().unwrap().len(), 1);
+        store.remove(&first).unwrap();
+        assert!(!store.path().exists());
+    }
+}
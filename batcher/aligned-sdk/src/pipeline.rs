@@ -0,0 +1,147 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use ethers::signers::Signer;
+use log::debug;
+use tokio::time::timeout;
+
+use crate::errors::SubmitError;
+use crate::sdk::submit_multiple_with_timeout;
+use crate::types::{AlignedVerificationData, VerificationData};
+
+/// Maximum time each stage of `prove_and_submit` is allowed to take.
+#[derive(Debug, Clone)]
+pub struct PipelineTimeouts {
+    pub proving: Duration,
+    pub submission: Duration,
+}
+
+/// Progress of `prove_and_submit`, reported as each stage starts and ends.
+#[derive(Debug, Clone)]
+pub enum PipelineEvent {
+    ProvingStarted,
+    ProvingFinished { elapsed: Duration, proofs: usize },
+    SubmissionStarted,
+    SubmissionFinished { elapsed: Duration },
+}
+
+/// Generates proofs with a user supplied proving closure and submits them to the batcher, each
+/// stage bounded by its own timeout.
+/// The proving closure runs in a blocking thread, since proving is usually CPU bound. When its
+/// deadline is exceeded the pipeline returns, but the closure can't be interrupted and keeps
+/// running in the background until it ends.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proofs will be submitted.
+/// * `prove` - Closure returning the verification data of the generated proofs.
+/// * `wallet` - The wallet used to sign the proofs.
+/// * `timeouts` - The timeouts of the proving and submission stages.
+/// * `on_event` - Callback receiving the progress of the pipeline.
+/// # Returns
+/// * An array of aligned verification data obtained when submitting the proofs.
+/// # Errors
+/// * If the proving closure fails or panics.
+/// * If any of the stages exceeds its timeout.
+/// * Any error returned when submitting the proofs.
+pub async fn prove_and_submit<P, E, S>(
+    batcher_addr: &str,
+    prove: P,
+    wallet: S,
+    timeouts: PipelineTimeouts,
+    on_event: impl Fn(PipelineEvent),
+) -> Result<Option<Vec<AlignedVerificationData>>, SubmitError>
+where
+    P: FnOnce() -> Result<Vec<VerificationData>, E> + Send + 'static,
+    E: Display + Send + 'static,
+    S: Signer,
+{
+    on_event(PipelineEvent::ProvingStarted);
+    let proving_start = Instant::now();
+
+    let verification_data = timeout(timeouts.proving, tokio::task::spawn_blocking(prove))
+        .await
+        .map_err(|_| {
+            SubmitError::Timeout(format!("Proving took more than {:?}", timeouts.proving))
+        })?
+        .map_err(|e| SubmitError::ProvingError(e.to_string()))?
+        .map_err(|e| SubmitError::ProvingError(e.to_string()))?;
+
+    let elapsed = proving_start.elapsed();
+    debug!("Proving finished in {:?}", elapsed);
+    on_event(PipelineEvent::ProvingFinished {
+        elapsed,
+        proofs: verification_data.len(),
+    });
+
+    on_event(PipelineEvent::SubmissionStarted);
+    let submission_start = Instant::now();
+
+    let aligned_verification_data = submit_multiple_with_timeout(
+        batcher_addr,
+        &verification_data,
+        wallet,
+        timeouts.submission,
+    )
+    .await?;
+
+    let elapsed = submission_start.elapsed();
+    debug!("Submission finished in {:?}", elapsed);
+    on_event(PipelineEvent::SubmissionFinished { elapsed });
+
+    Ok(aligned_verification_data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::signers::LocalWallet;
+    use std::sync::Mutex;
+
+    fn wallet() -> LocalWallet {
+        "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap()
+    }
+
+    fn timeouts(proving: Duration) -> PipelineTimeouts {
+        PipelineTimeouts {
+            proving,
+            submission: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prove_and_submit_proving_timeout() {
+        let events = Mutex::new(Vec::new());
+
+        let result = prove_and_submit(
+            "ws://localhost:8080",
+            || {
+                std::thread::sleep(Duration::from_millis(500));
+                Ok::<_, String>(vec![])
+            },
+            wallet(),
+            timeouts(Duration::from_millis(10)),
+            |event| events.lock().unwrap().push(event),
+        )
+        .await;
+
+        assert!(matches!(result, Err(SubmitError::Timeout(_))));
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], PipelineEvent::ProvingStarted));
+    }
+
+    #[tokio::test]
+    async fn test_prove_and_submit_proving_error() {
+        let result = prove_and_submit(
+            "ws://localhost:8080",
+            || Err::<Vec<VerificationData>, _>("out of memory"),
+            wallet(),
+            timeouts(Duration::from_secs(1)),
+            |_| {},
+        )
+        .await;
+
+        assert!(matches!(result, Err(SubmitError::ProvingError(e)) if e == "out of memory"));
+    }
+}
@@ -0,0 +1,479 @@
+use std::sync::Arc;
+
+use ethers::abi::AbiDecode;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, H256, U256};
+use sha3::{Digest, Keccak256};
+
+use crate::errors::VerificationError;
+use crate::eth;
+use crate::rpc::AlignedRpcClient;
+use crate::sdk::get_aligned_service_manager_address;
+use crate::types::Chain;
+
+/// Number of the EigenLayer quorum whose operators attest the batches of Aligned.
+pub const ALIGNED_QUORUM_NUMBER: u8 = 0;
+
+/// Percentage of the stake of the quorum that has to sign a batch for it to be verified, as
+/// checked by the Aligned service manager contract.
+pub const QUORUM_THRESHOLD_PERCENTAGE: u8 = 67;
+
+/// Current state of the quorum of operators that attests the batches of Aligned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuorumInfo {
+    pub quorum_number: u8,
+    pub total_stake: U256,
+    pub operator_count: u32,
+    pub threshold_percentage: u8,
+}
+
+/// Stake of the quorum that had to sign a batch for it to be verified. Operators attest batches
+/// with their stake at the block in which the batch was created, which is the reference block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchAttestationThreshold {
+    pub reference_block: u32,
+    pub total_stake: U256,
+    pub required_stake: U256,
+    pub threshold_percentage: u8,
+}
+
+/// Stake that signed a batch in one of the quorums that attested it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuorumAttestation {
+    pub quorum_number: u8,
+    pub signed_stake: U256,
+    pub total_stake: U256,
+}
+
+impl QuorumAttestation {
+    /// Percentage of the stake of the quorum that signed the batch.
+    pub fn signed_stake_percentage(&self) -> f64 {
+        if self.total_stake.is_zero() {
+            return 0.0;
+        }
+        // Basis points keep two decimals without converting the stakes to floats
+        let basis_points: U256 = self.signed_stake * 10_000 / self.total_stake;
+        basis_points.as_u64() as f64 / 100.0
+    }
+}
+
+/// Aggregated BLS attestation with which the operators verified a batch. Tasks are identified by
+/// the merkle root of their batch, and ordered by the block in which they were created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchAttestation {
+    pub task_created_block: u32,
+    pub attestation_block: u64,
+    pub attestation_tx_hash: H256,
+    pub quorums: Vec<QuorumAttestation>,
+    pub non_signer_operator_ids: Vec<[u8; 32]>,
+}
+
+/// Operator registered in the quorum that attests the batches of Aligned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorInfo {
+    pub operator_id: [u8; 32],
+    pub address: Address,
+    pub stake: U256,
+}
+
+/// Result of checking on-chain that a proof was verified, along with the attestation of its
+/// batch when it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnchainVerification {
+    pub verified: bool,
+    pub attestation: Option<BatchAttestation>,
+}
+
+/// Returns the current stake and number of operators of the quorum that attests the batches of
+/// Aligned, along with the percentage of the stake needed to verify a batch.
+/// # Arguments
+/// * `chain` - The chain on which Aligned is deployed.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The information of the quorum.
+/// # Errors
+/// * `EthError` if there is an error creating the rpc provider or calling the contracts.
+/// * `InvalidAddress` if the address of the contracts is not valid.
+pub async fn get_quorum_info(
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<QuorumInfo, VerificationError> {
+    let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let service_manager = eth::aligned_service_manager(
+        eth_rpc_provider.clone(),
+        get_aligned_service_manager_address(&chain),
+    )
+    .await?;
+
+    let stake_registry = stake_registry(&service_manager, eth_rpc_provider.clone()).await?;
+    let total_stake = stake_registry
+        .get_current_total_stake(ALIGNED_QUORUM_NUMBER)
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let registry_coordinator =
+        registry_coordinator(&service_manager, eth_rpc_provider.clone()).await?;
+    let operator_count = index_registry(&registry_coordinator, eth_rpc_provider)
+        .await?
+        .total_operators_for_quorum(ALIGNED_QUORUM_NUMBER)
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    Ok(QuorumInfo {
+        quorum_number: ALIGNED_QUORUM_NUMBER,
+        total_stake: total_stake.into(),
+        operator_count,
+        threshold_percentage: QUORUM_THRESHOLD_PERCENTAGE,
+    })
+}
+
+/// Returns the operators currently registered in the quorum that attests the batches of Aligned,
+/// along with their stake.
+/// # Arguments
+/// * `chain` - The chain on which Aligned is deployed.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The registered operators.
+/// # Errors
+/// * `EthError` if there is an error creating the rpc provider or calling the contracts.
+/// * `InvalidAddress` if the address of the contracts is not valid.
+pub async fn get_operators(
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<Vec<OperatorInfo>, VerificationError> {
+    let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let service_manager = eth::aligned_service_manager(
+        eth_rpc_provider.clone(),
+        get_aligned_service_manager_address(&chain),
+    )
+    .await?;
+
+    let current_block = eth_rpc_provider
+        .get_block_number()
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let registry_coordinator =
+        registry_coordinator(&service_manager, eth_rpc_provider.clone()).await?;
+    let operator_ids = index_registry(&registry_coordinator, eth_rpc_provider.clone())
+        .await?
+        .get_operator_list_at_block_number(ALIGNED_QUORUM_NUMBER, current_block.as_u32())
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let stake_registry = stake_registry(&service_manager, eth_rpc_provider).await?;
+
+    let mut operators = Vec::with_capacity(operator_ids.len());
+    for operator_id in operator_ids {
+        let address = registry_coordinator
+            .get_operator_from_id(operator_id)
+            .await
+            .map_err(|e| VerificationError::EthError(e.to_string()))?;
+        let stake = stake_registry
+            .get_current_stake(operator_id, ALIGNED_QUORUM_NUMBER)
+            .await
+            .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+        operators.push(OperatorInfo {
+            operator_id,
+            address,
+            stake: stake.into(),
+        });
+    }
+
+    Ok(operators)
+}
+
+/// Returns the stake of the quorum that had to sign the given batch for it to be verified.
+/// # Arguments
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// * `chain` - The chain on which Aligned is deployed.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The stake needed to attest the batch, or `None` if the batch was never submitted.
+/// # Errors
+/// * `EthError` if there is an error creating the rpc provider or calling the contracts.
+/// * `InvalidAddress` if the address of the contracts is not valid.
+pub async fn get_batch_attestation_threshold(
+    batch_merkle_root: [u8; 32],
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<Option<BatchAttestationThreshold>, VerificationError> {
+    let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let service_manager = eth::aligned_service_manager(
+        eth_rpc_provider.clone(),
+        get_aligned_service_manager_address(&chain),
+    )
+    .await?;
+
+    let (reference_block, _) = service_manager
+        .batches_state(batch_merkle_root)
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+    // Batches created at block 0 can't exist, so it means the batch is not in the contract
+    if reference_block == 0 {
+        return Ok(None);
+    }
+
+    let stake_registry = stake_registry(&service_manager, eth_rpc_provider).await?;
+    let stake_indices = stake_registry
+        .get_total_stake_indices_at_block_number(
+            reference_block,
+            vec![ALIGNED_QUORUM_NUMBER].into(),
+        )
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+    let stake_index = stake_indices.first().ok_or(VerificationError::EthError(
+        "No total stake found for the quorum at the reference block".to_string(),
+    ))?;
+    let total_stake: U256 = stake_registry
+        .get_total_stake_at_block_number_from_index(
+            ALIGNED_QUORUM_NUMBER,
+            reference_block,
+            (*stake_index).into(),
+        )
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?
+        .into();
+
+    Ok(Some(BatchAttestationThreshold {
+        reference_block,
+        total_stake,
+        required_stake: required_stake(total_stake),
+        threshold_percentage: QUORUM_THRESHOLD_PERCENTAGE,
+    }))
+}
+
+/// Returns the attestation with which the operators verified the given batch, read from the
+/// `respondToTask` transaction of the aggregator. The signatures are checked again by the service
+/// manager contract to get the stake that signed.
+/// # Arguments
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// * `chain` - The chain on which Aligned is deployed.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The attestation of the batch, or `None` if the batch hasn't been verified.
+/// # Errors
+/// * `EthError` if there is an error creating the rpc provider, calling the contracts or
+///   decoding the attestation transaction.
+/// * `InvalidAddress` if the address of the contracts is not valid.
+pub async fn get_batch_attestation(
+    batch_merkle_root: [u8; 32],
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<Option<BatchAttestation>, VerificationError> {
+    let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let service_manager = eth::aligned_service_manager(
+        eth_rpc_provider.clone(),
+        get_aligned_service_manager_address(&chain),
+    )
+    .await?;
+
+    let rpc_client = AlignedRpcClient::new(eth_rpc_provider.clone());
+    let (task_created_block, responded) = rpc_client.batch_state(batch_merkle_root, &chain).await?;
+    if !responded {
+        return Ok(None);
+    }
+
+    let verified_events = rpc_client
+        .batch_verified_events(batch_merkle_root, task_created_block, &chain)
+        .await?;
+    let Some((_, log_meta)) = verified_events.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let attestation_tx = eth_rpc_provider
+        .get_transaction(log_meta.transaction_hash)
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?
+        .ok_or(VerificationError::EthError(
+            "Attestation transaction not found".to_string(),
+        ))?;
+    // The aggregator calls the service manager directly, so the attestation is in the calldata
+    let respond_to_task_call = eth::RespondToTaskCall::decode(&attestation_tx.input)
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+    let non_signer_operator_ids = non_signer_operator_ids(&respond_to_task_call);
+
+    let (quorum_stake_totals, _) = service_manager
+        .check_signatures(
+            batch_merkle_root,
+            task_created_block,
+            respond_to_task_call.non_signer_stakes_and_signature,
+        )
+        .block(log_meta.block_number)
+        .call()
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    let quorums = quorum_stake_totals
+        .signed_stake_for_quorum
+        .into_iter()
+        .zip(quorum_stake_totals.total_stake_for_quorum)
+        .enumerate()
+        .map(
+            |(quorum_number, (signed_stake, total_stake))| QuorumAttestation {
+                quorum_number: quorum_number as u8,
+                signed_stake: signed_stake.into(),
+                total_stake: total_stake.into(),
+            },
+        )
+        .collect();
+
+    Ok(Some(BatchAttestation {
+        task_created_block,
+        attestation_block: log_meta.block_number.as_u64(),
+        attestation_tx_hash: log_meta.transaction_hash,
+        quorums,
+        non_signer_operator_ids,
+    }))
+}
+
+/// Returns the ids of the operators that didn't sign an attestation, given the calldata of the
+/// `respondToTask` transaction that posted it.
+/// # Arguments
+/// * `respond_to_task_calldata` - The input of the transaction, including the function selector.
+/// # Returns
+/// * The ids of the operators that didn't sign.
+/// # Errors
+/// * `ParsingError` if the calldata is not a `respondToTask` call.
+pub fn get_non_signer_operator_ids(
+    respond_to_task_calldata: &[u8],
+) -> Result<Vec<[u8; 32]>, VerificationError> {
+    let respond_to_task_call = eth::RespondToTaskCall::decode(respond_to_task_calldata)
+        .map_err(|e| VerificationError::ParsingError(e.to_string()))?;
+
+    Ok(non_signer_operator_ids(&respond_to_task_call))
+}
+
+// The id of an operator is the hash of its BLS public key, as computed by `BN254.hashG1Point`
+fn non_signer_operator_ids(respond_to_task_call: &eth::RespondToTaskCall) -> Vec<[u8; 32]> {
+    respond_to_task_call
+        .non_signer_stakes_and_signature
+        .non_signer_pubkeys
+        .iter()
+        .map(|pubkey| {
+            let mut coordinates = [0u8; 64];
+            pubkey.x.to_big_endian(&mut coordinates[..32]);
+            pubkey.y.to_big_endian(&mut coordinates[32..]);
+            Keccak256::digest(coordinates).into()
+        })
+        .collect()
+}
+
+async fn registry_coordinator(
+    service_manager: &eth::AlignedLayerServiceManager,
+    eth_rpc_provider: Provider<Http>,
+) -> Result<eth::RegistryCoordinator, VerificationError> {
+    let registry_coordinator_address = service_manager
+        .registry_coordinator()
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    Ok(eth::RegistryCoordinator::new(
+        registry_coordinator_address,
+        Arc::new(eth_rpc_provider),
+    ))
+}
+
+async fn index_registry(
+    registry_coordinator: &eth::RegistryCoordinator,
+    eth_rpc_provider: Provider<Http>,
+) -> Result<eth::IndexRegistry, VerificationError> {
+    let index_registry_address = registry_coordinator
+        .index_registry()
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    Ok(eth::IndexRegistry::new(
+        index_registry_address,
+        Arc::new(eth_rpc_provider),
+    ))
+}
+
+async fn stake_registry(
+    service_manager: &eth::AlignedLayerServiceManager,
+    eth_rpc_provider: Provider<Http>,
+) -> Result<eth::StakeRegistry, VerificationError> {
+    let stake_registry_address = service_manager
+        .stake_registry()
+        .await
+        .map_err(|e| VerificationError::EthError(e.to_string()))?;
+
+    Ok(eth::StakeRegistry::new(
+        stake_registry_address,
+        Arc::new(eth_rpc_provider),
+    ))
+}
+
+// The contract requires signed_stake * 100 >= total_stake * threshold, so the smallest signed
+// stake that passes is the division rounded up
+fn required_stake(total_stake: U256) -> U256 {
+    (total_stake * QUORUM_THRESHOLD_PERCENTAGE + 99) / 100
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_signed_stake_percentage() {
+        let quorum_attestation = |signed_stake: u64, total_stake: u64| QuorumAttestation {
+            quorum_number: ALIGNED_QUORUM_NUMBER,
+            signed_stake: signed_stake.into(),
+            total_stake: total_stake.into(),
+        };
+
+        assert_eq!(quorum_attestation(2, 3).signed_stake_percentage(), 66.66);
+        assert_eq!(quorum_attestation(3, 3).signed_stake_percentage(), 100.0);
+        assert_eq!(quorum_attestation(0, 0).signed_stake_percentage(), 0.0);
+    }
+
+    #[test]
+    fn test_get_non_signer_operator_ids() {
+        use ethers::abi::AbiEncode;
+
+        let calldata = eth::RespondToTaskCall {
+            batch_merkle_root: [1; 32],
+            non_signer_stakes_and_signature: eth::NonSignerStakesAndSignature {
+                non_signer_pubkeys: vec![eth::G1Point {
+                    x: 1.into(),
+                    y: 2.into(),
+                }],
+                ..Default::default()
+            },
+        }
+        .encode();
+
+        let mut coordinates = [0u8; 64];
+        coordinates[31] = 1;
+        coordinates[63] = 2;
+        let expected_operator_id: [u8; 32] = Keccak256::digest(coordinates).into();
+
+        assert_eq!(
+            get_non_signer_operator_ids(&calldata).unwrap(),
+            vec![expected_operator_id]
+        );
+        assert!(get_non_signer_operator_ids(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_required_stake_meets_contract_threshold() {
+        for total_stake in [0u64, 1, 99, 100, 101, 3, 1_000_000_007] {
+            let total_stake = U256::from(total_stake);
+            let required = required_stake(total_stake);
+
+            assert!(required * 100 >= total_stake * QUORUM_THRESHOLD_PERCENTAGE);
+            if !required.is_zero() {
+                assert!((required - 1) * 100 < total_stake * QUORUM_THRESHOLD_PERCENTAGE);
+            }
+        }
+    }
+}
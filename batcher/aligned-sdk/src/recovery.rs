@@ -0,0 +1,161 @@
+//! Recovery of the aligned verification data of a proof whose batch inclusion data file was lost.
+//! The data of every batch is uploaded by the batcher, so the merkle tree of the batch can be
+//! rebuilt from it and the inclusion proof of the proof taken from the tree.
+
+use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
+
+use crate::errors::VerificationError;
+use crate::merkle::{batch_commitments, BatchMerkleTree};
+use crate::rpc::AlignedRpcClient;
+use crate::types::{
+    AlignedVerificationData, BatchVersion, Chain, VerificationCommitmentBatch, VerificationData,
+    VerificationDataCommitment,
+};
+
+/// Rebuilds the aligned verification data of a proof from the data of the batch that holds it,
+/// downloaded from where the batcher uploaded it.
+/// # Arguments
+/// * `verification_data_commitment` - The commitment of the proof, which can be computed from
+///   its verification data.
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// * `chain` - The chain on which the batch was created.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The aligned verification data of the proof, as received when it was submitted.
+/// # Errors
+/// * `EthError` if there is an error querying the batch.
+/// * `InvalidAddress` if the address of the service manager is not valid.
+/// * `BatchNotFound` if no task was created for the batch.
+/// * `BatchDataError` if the data of the batch can't be found or downloaded.
+/// * `ParsingError` if the data of the batch is not valid.
+/// * `ProofNotInBatch` if the proof is not in the batch.
+pub async fn recover_aligned_verification_data(
+    verification_data_commitment: &VerificationDataCommitment,
+    batch_merkle_root: [u8; 32],
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<AlignedVerificationData, VerificationError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+    let (task_created_block, _) = rpc_client.batch_state(batch_merkle_root, &chain).await?;
+    if task_created_block == 0 {
+        return Err(VerificationError::BatchNotFound(batch_merkle_root));
+    }
+
+    let batch_data_pointer = rpc_client
+        .new_batch_events(task_created_block.into(), &chain)
+        .await?
+        .into_iter()
+        .find(|new_batch| new_batch.batch_merkle_root == batch_merkle_root)
+        .map(|new_batch| new_batch.batch_data_pointer)
+        .ok_or(VerificationError::BatchDataError(
+            "The NewBatch event of the batch was not found".to_string(),
+        ))?;
+    let batch_data = rpc_client.batch_data(&batch_data_pointer).await?;
+
+    rebuild_aligned_verification_data(&batch_data, verification_data_commitment, batch_merkle_root)
+}
+
+/// Rebuilds the aligned verification data of a proof from the data of its batch. The version of
+/// the batch is not part of its data, so it's the one whose tree has the merkle root of the
+/// batch.
+/// # Arguments
+/// * `batch_data` - The verification data of the batch, in the order it was uploaded.
+/// * `verification_data_commitment` - The commitment of the proof.
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// # Errors
+/// * `BatchDataError` if the tree of the data doesn't have the merkle root of the batch.
+/// * `ProofNotInBatch` if the proof is not in the batch.
+pub fn rebuild_aligned_verification_data(
+    batch_data: &[VerificationData],
+    verification_data_commitment: &VerificationDataCommitment,
+    batch_merkle_root: [u8; 32],
+) -> Result<AlignedVerificationData, VerificationError> {
+    let commitments = batch_commitments(batch_data);
+
+    let batch_merkle_tree = [BatchVersion::V1, BatchVersion::V2]
+        .into_iter()
+        .filter_map(|batch_version| BatchMerkleTree::build(batch_version, &commitments))
+        .find(|batch_merkle_tree| batch_merkle_tree.root == batch_merkle_root)
+        .ok_or(VerificationError::BatchDataError(
+            "The data of the batch doesn't match its merkle root".to_string(),
+        ))?;
+
+    let leaf = VerificationCommitmentBatch::hash_data(verification_data_commitment);
+    let index_in_batch = commitments
+        .iter()
+        .position(|commitment| VerificationCommitmentBatch::hash_data(commitment) == leaf)
+        .ok_or(VerificationError::ProofNotInBatch(batch_merkle_root))?;
+    let batch_inclusion_data = batch_merkle_tree
+        .batch_inclusion_data(index_in_batch)
+        .ok_or(VerificationError::ProofNotInBatch(batch_merkle_root))?;
+
+    Ok(AlignedVerificationData::new(
+        verification_data_commitment,
+        &batch_inclusion_data,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::types::Address;
+
+    use super::*;
+    use crate::sdk::verify_merkle_inclusion;
+    use crate::types::ProvingSystemId;
+
+    fn verification_data_batch(batch_size: u8) -> Vec<VerificationData> {
+        (0..batch_size)
+            .map(|i| VerificationData {
+                proving_system: ProvingSystemId::SP1,
+                proof: vec![i; 4],
+                pub_input: None,
+                verification_key: None,
+                vm_program_code: Some(vec![1, 2, 3]),
+                proof_generator_addr: Address::repeat_byte(i),
+                tag: None,
+                metadata: None,
+                schema_version: VerificationData::SCHEMA_VERSION,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rebuilt_inclusion_proofs_verify() {
+        for batch_version in [BatchVersion::V1, BatchVersion::V2] {
+            let batch_data = verification_data_batch(5);
+            let commitments: Vec<VerificationDataCommitment> =
+                batch_data.iter().cloned().map(Into::into).collect();
+            let root = BatchMerkleTree::build(batch_version, &commitments)
+                .unwrap()
+                .root;
+
+            for (index, commitment) in commitments.iter().enumerate() {
+                let aligned_verification_data =
+                    rebuild_aligned_verification_data(&batch_data, commitment, root).unwrap();
+                assert_eq!(aligned_verification_data.index_in_batch, index);
+                assert_eq!(aligned_verification_data.batch_version, batch_version);
+                assert!(verify_merkle_inclusion(&aligned_verification_data, root));
+            }
+        }
+    }
+
+    #[test]
+    fn test_proofs_of_other_batches_are_not_recovered() {
+        let batch_data = verification_data_batch(3);
+        let commitments: Vec<VerificationDataCommitment> =
+            batch_data.iter().cloned().map(Into::into).collect();
+        let root = BatchMerkleTree::build(BatchVersion::V1, &commitments)
+            .unwrap()
+            .root;
+
+        let other: VerificationDataCommitment = verification_data_batch(4)[3].clone().into();
+        assert!(matches!(
+            rebuild_aligned_verification_data(&batch_data, &other, root),
+            Err(VerificationError::ProofNotInBatch(batch_merkle_root)) if batch_merkle_root == root
+        ));
+        assert!(matches!(
+            rebuild_aligned_verification_data(&batch_data, &commitments[0], [0; 32]),
+            Err(VerificationError::BatchDataError(_))
+        ));
+    }
+}
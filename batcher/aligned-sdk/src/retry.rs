@@ -0,0 +1,504 @@
+//! The `RetryPolicy` shared by the networking of the SDK: connections and submissions to the
+//! batcher, requests to the Ethereum RPC node and downloads of the data of batches. Set it once
+//! on a client with `AlignedClientBuilder::network_retry_policy`.
+//!
+//! Submission of proofs that reconnects to the batcher when the connection is lost before all the
+//! proofs are responded, resubmitting only the proofs without a response.
+//!
+//! The batcher responds to the proofs of a connection in the order they were sent, so the proofs
+//! without a response are always the last ones sent. Batchers supporting sessions are asked to
+//! resume the session of the previous connection, as described in `session`, so only the proofs
+//! they didn't accept are sent again, and the responses lost with the connection are replayed.
+//! With other batchers, a proof whose response was lost after the batcher received it is
+//! submitted again, and can be included and paid for twice.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use ethers::signers::Signer;
+use log::{debug, warn};
+use rand::Rng;
+
+use crate::bug_report::Transcript;
+use crate::client::SubmitOptions;
+use crate::encryption::EncryptionConfig;
+use crate::errors::{SubmitError, VerificationError};
+use crate::sdk::{
+    connect_resuming, sign_with_signer, submit_with_progress, SubmissionProgress, TimedSubmission,
+};
+use crate::tls::TlsConfig;
+use crate::types::{AlignedVerificationData, VerificationData};
+
+/// A failed network operation, given to the classifier of a `RetryPolicy` to decide whether it
+/// is attempted again.
+#[derive(Debug, Clone, Copy)]
+pub enum NetworkFailure<'a> {
+    /// A failed connection or submission to the batcher.
+    Submit(&'a SubmitError),
+    /// A failed request to the Ethereum RPC node or download of the data of a batch.
+    Verification(&'a VerificationError),
+}
+
+impl<'a> From<&'a SubmitError> for NetworkFailure<'a> {
+    fn from(e: &'a SubmitError) -> Self {
+        NetworkFailure::Submit(e)
+    }
+}
+
+impl<'a> From<&'a VerificationError> for NetworkFailure<'a> {
+    fn from(e: &'a VerificationError) -> Self {
+        NetworkFailure::Verification(e)
+    }
+}
+
+/// The default classifier of a `RetryPolicy`. Connection errors to the batcher, errors of the
+/// RPC node, such as a node that can't be reached or is rate limiting, and failed downloads of
+/// batch data are transient. Rejections of the batcher, protocol violations, TLS errors and
+/// reverted calls are not.
+pub fn is_transient(failure: NetworkFailure) -> bool {
+    matches!(
+        failure,
+        NetworkFailure::Submit(SubmitError::ConnectionError(_))
+            | NetworkFailure::Verification(
+                VerificationError::EthError(_) | VerificationError::BatchDataError(_)
+            )
+    )
+}
+
+/// How many times a network operation is attempted, which failures are attempted again and how
+/// long to wait between attempts. The wait grows exponentially from `initial_backoff` up to
+/// `max_backoff`, and is shortened by a random amount of up to `jitter_percentage` of it, so
+/// that clients dropped at the same time don't reconnect at the same time.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub backoff_multiplier: u32,
+    /// Percentage of the wait that can be randomly removed from it, from 0 to 100.
+    pub jitter_percentage: u8,
+    /// Whether a failure is attempted again. Defaults to `is_transient`.
+    pub is_retryable: fn(NetworkFailure) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            backoff_multiplier: 2,
+            jitter_percentage: 50,
+            is_retryable: is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the time to wait after the given failed attempt, starting from 1, without jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let multiplier = self
+            .backoff_multiplier
+            .saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(multiplier)
+            .min(self.max_backoff)
+    }
+
+    /// Returns the time to wait after the given failed attempt, starting from 1, with jitter.
+    pub fn backoff_with_jitter(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        let max_jitter = backoff * self.jitter_percentage.min(100) as u32 / 100;
+        backoff - rand::thread_rng().gen_range(Duration::ZERO..=max_jitter)
+    }
+
+    /// Returns whether an operation that failed on the given attempt, starting from 1, is
+    /// attempted again.
+    pub fn should_retry(&self, attempt: u32, failure: NetworkFailure) -> bool {
+        attempt < self.max_attempts && (self.is_retryable)(failure)
+    }
+
+    /// Runs an operation until it succeeds, fails with an error that is not retried, or runs out
+    /// of attempts, waiting between attempts.
+    /// # Arguments
+    /// * `operation` - The name of the operation, for the logs.
+    /// * `run` - The operation, called once per attempt.
+    /// # Returns
+    /// * The result of the last attempt.
+    pub async fn retry<T, E, F, Fut>(&self, operation: &str, mut run: F) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Debug,
+        for<'e> &'e E: Into<NetworkFailure<'e>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match run().await {
+                Err(e) if self.should_retry(attempt, (&e).into()) => {
+                    let backoff = self.backoff_with_jitter(attempt);
+                    warn!(
+                        "{} failed on attempt {} of {}: {:?}. Retrying in {:?}",
+                        operation, attempt, self.max_attempts, e, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Submits multiple proofs to the batcher like `submit_multiple`, reconnecting and resubmitting
+/// the proofs without a response if the connection is lost.
+/// Connections closed without a reason and the errors retried by the classifier of the policy,
+/// connection errors by default, are retried. Rejections of the batcher, such as an invalid proof
+/// or an insufficient balance, are returned at once.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proofs will be submitted.
+/// * `verification_data` - An array of verification data of each proof.
+/// * `wallet` - The wallet used to sign the proofs.
+/// * `single_signature` - Whether to sign the proofs of each attempt at once, as in
+///   `submit_multiple_with_single_signature`.
+/// * `retry_policy` - The number of attempts and the wait between them.
+/// # Returns
+/// * An array of aligned verification data obtained when submitting the proofs, or `None` if
+///   the batcher didn't respond to every proof in the last attempt.
+/// # Errors
+/// * If there is an error connecting to the batcher in the last attempt.
+/// * If the batcher rejects the proofs.
+/// * If there is an error serializing the message.
+pub async fn submit_multiple_with_retry<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: S,
+    single_signature: bool,
+    retry_policy: &RetryPolicy,
+) -> Result<Option<Vec<AlignedVerificationData>>, SubmitError> {
+    submit_with_retry(
+        batcher_addr,
+        verification_data,
+        &wallet,
+        &SubmitOptions {
+            single_signature,
+            ..Default::default()
+        },
+        &TlsConfig::default(),
+        None,
+        &mut Transcript::new(),
+        retry_policy,
+    )
+    .await
+    .map(|submission| submission.aligned_verification_data)
+}
+
+/// Submits multiple proofs as `submit_multiple_with_retry`, with the given options, connecting
+/// with `tls_config`. Protocol violations of strict submissions and TLS errors are not retried.
+/// The timing of the proofs covers all the attempts, and so does `transcript`. The messages are
+/// encrypted if `encryption` is set, as in `sdk::submit_with_tls`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn submit_with_retry<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: &S,
+    options: &SubmitOptions,
+    tls_config: &TlsConfig,
+    encryption: Option<&EncryptionConfig>,
+    transcript: &mut Transcript,
+    retry_policy: &RetryPolicy,
+) -> Result<TimedSubmission, SubmitError> {
+    let mut progress = SubmissionProgress::default();
+
+    let mut attempt = 1;
+    loop {
+        let pending = &verification_data[progress.responded..];
+        // The session of the previous attempt is resumed, so that the proofs the batcher
+        // accepted before the connection was lost are not sent again
+        let resumption = progress
+            .session
+            .as_ref()
+            .map(|session| (session, progress.responded));
+        let result = match connect_resuming(batcher_addr, tls_config, resumption).await {
+            Ok(ws_stream) => {
+                debug!("WebSocket handshake has been successfully completed");
+
+                submit_with_progress(
+                    ws_stream,
+                    pending,
+                    |message| sign_with_signer(wallet, message),
+                    options.single_signature,
+                    options.strict,
+                    transcript,
+                    &mut progress,
+                    None,
+                    encryption,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        let failure = match result {
+            Ok(true) => {
+                return Ok(TimedSubmission {
+                    aligned_verification_data: Some(progress.aligned_verification_data),
+                    timings: progress.timings,
+                })
+            }
+            Ok(false) if attempt >= retry_policy.max_attempts => {
+                return Ok(TimedSubmission {
+                    aligned_verification_data: None,
+                    timings: progress.timings,
+                })
+            }
+            Ok(false) => "the connection was closed before all proofs were responded".to_string(),
+            Err(e) if retry_policy.should_retry(attempt, (&e).into()) => format!("{:?}", e),
+            Err(e) => return Err(e),
+        };
+
+        let backoff = retry_policy.backoff_with_jitter(attempt);
+        warn!(
+            "Attempt {} of {} failed: {}. {} of {} proofs responded, resubmitting the rest in {:?}",
+            attempt,
+            retry_policy.max_attempts,
+            failure,
+            progress.responded,
+            verification_data.len(),
+            backoff
+        );
+        tokio::time::sleep(backoff).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_exponentially_up_to_max() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            backoff_multiplier: 3,
+            jitter_percentage: 20,
+            ..Default::default()
+        };
+
+        let backoffs: Vec<Duration> = (1..=4)
+            .map(|attempt| retry_policy.backoff(attempt))
+            .collect();
+        assert_eq!(
+            backoffs,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(300),
+                Duration::from_millis(900),
+                Duration::from_secs(1),
+            ]
+        );
+        assert_eq!(retry_policy.backoff(u32::MAX), Duration::from_secs(1));
+
+        for attempt in 1..=4 {
+            let backoff = retry_policy.backoff_with_jitter(attempt);
+            assert!(backoff <= backoffs[attempt as usize - 1]);
+            assert!(backoff >= backoffs[attempt as usize - 1] * 80 / 100);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_failures_of_the_classifier_are_retried() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let attempts_until = |error: fn() -> VerificationError, retry_policy: RetryPolicy| async move {
+            let mut attempts = 0;
+            let result: Result<(), VerificationError> = retry_policy
+                .retry("test", || {
+                    attempts += 1;
+                    async move { Err(error()) }
+                })
+                .await;
+            assert!(result.is_err());
+            attempts
+        };
+
+        assert_eq!(
+            attempts_until(
+                || VerificationError::EthError("unreachable".to_string()),
+                retry_policy.clone()
+            )
+            .await,
+            3
+        );
+        assert_eq!(
+            attempts_until(
+                || VerificationError::BatchNotFound([0; 32]),
+                retry_policy.clone()
+            )
+            .await,
+            1
+        );
+        let never_retried = RetryPolicy {
+            is_retryable: |_| false,
+            ..retry_policy
+        };
+        assert_eq!(
+            attempts_until(
+                || VerificationError::EthError("unreachable".to_string()),
+                never_retried
+            )
+            .await,
+            1
+        );
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_unresponded_proofs_are_resubmitted() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::sdk::{protocol_handshake, SESSION_RESUMPTION_FEATURE};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+        use crate::types::VerificationDataCommitment;
+
+        // Every connection is closed after responding the first proof, by a batcher without
+        // sessions
+        let mut handshake = protocol_handshake();
+        handshake
+            .features
+            .retain(|feature| feature != SESSION_RESUMPTION_FEATURE);
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(1),
+            handshake: Some(handshake),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let mut rng = seeded_rng(0);
+        let verification_data: Vec<VerificationData> =
+            (0..3).map(|_| random_verification_data(&mut rng)).collect();
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let aligned_verification_data = submit_multiple_with_retry(
+            &mock_batcher.url(),
+            &verification_data,
+            deterministic_wallet(0),
+            false,
+            &retry_policy,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(aligned_verification_data.len(), 3);
+        for (aligned_verification_data, verification_data) in aligned_verification_data
+            .iter()
+            .zip(verification_data.clone())
+        {
+            let commitment = VerificationDataCommitment::from(verification_data);
+            assert_eq!(
+                aligned_verification_data
+                    .verification_data_commitment
+                    .proof_commitment,
+                commitment.proof_commitment
+            );
+        }
+
+        // Not enough attempts to respond every proof
+        let retry_policy = RetryPolicy {
+            max_attempts: 2,
+            ..retry_policy
+        };
+        assert!(submit_multiple_with_retry(
+            &mock_batcher.url(),
+            &verification_data,
+            deterministic_wallet(0),
+            true,
+            &retry_policy,
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+        // The timing covers every attempt, keeping the time each proof was first queued
+        let submission = submit_with_retry(
+            &mock_batcher.url(),
+            &verification_data,
+            &deterministic_wallet(0),
+            &SubmitOptions::default(),
+            &TlsConfig::default(),
+            None,
+            &mut Transcript::new(),
+            &RetryPolicy {
+                max_attempts: 3,
+                ..retry_policy
+            },
+        )
+        .await
+        .unwrap();
+        let timings = submission.timings;
+        assert_eq!(timings.len(), 3);
+        assert!(
+            timings
+                .iter()
+                .all(|timing| timing.queued_at == timings[0].queued_at
+                    && timing.included_at.is_some())
+        );
+        assert!(timings[2].sent_at > timings[0].included_at);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_resumed_sessions_only_send_the_proofs_not_accepted() {
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+
+        // Every connection is closed after sending one response, after the batcher received
+        // every proof
+        let mock_batcher = MockBatcher::start(FaultConfig {
+            close_after_responses: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let mut rng = seeded_rng(0);
+        let verification_data: Vec<VerificationData> =
+            (0..3).map(|_| random_verification_data(&mut rng)).collect();
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let submission = submit_with_retry(
+            &mock_batcher.url(),
+            &verification_data,
+            &deterministic_wallet(0),
+            &SubmitOptions::default(),
+            &TlsConfig::default(),
+            None,
+            &mut Transcript::new(),
+            &retry_policy,
+        )
+        .await
+        .unwrap();
+
+        // The responses lost with the connections are replayed instead
+        assert_eq!(submission.aligned_verification_data.unwrap().len(), 3);
+        assert_eq!(mock_batcher.received_proofs(), 3);
+        let timings = submission.timings;
+        assert!(timings
+            .iter()
+            .all(|timing| timing.sent_at < timings[0].included_at));
+    }
+}
@@ -0,0 +1,89 @@
+//! Verification data built from the receipts of `risc0-zkvm`, so Risc0 users can submit the
+//! proofs of their prover without serializing them. Receipts are serialized with the version of
+//! `risc0-zkvm` the operators verify them with, so a change of encoding between Risc0 versions
+//! fails to compile instead of being rejected by the batcher. Only built with the `risc0` feature,
+//! since it pulls in the Risc0 zkVM.
+
+use ethers::types::Address;
+use risc0_zkvm::sha::Digest;
+use risc0_zkvm::{InnerReceipt, Receipt};
+
+use crate::errors::SubmitError;
+use crate::types::{ProvingSystemId, VerificationData};
+
+impl VerificationData {
+    /// Builds the verification data of a Risc0 receipt of the program with the given image id,
+    /// as returned by the `prove` of a prover. Composite, succinct and Groth16 receipts are all
+    /// verified by the operators, so any of them can be sent, the smaller ones being cheaper to
+    /// send. The receipt is serialized with bincode as the operators expect, and its journal is
+    /// bound as the public input, so it can be used by contracts, see `sdk::get_risc0_journal`.
+    /// # Arguments
+    /// * `receipt` - The Risc0 receipt, with its journal.
+    /// * `image_id` - The image id of the program that was proven, such as its `METHOD_ID`.
+    /// * `proof_generator_addr` - The address of the generator of the proof.
+    /// # Returns
+    /// * The verification data of the receipt.
+    /// # Errors
+    /// * `InvalidProof` if the receipt is a fake one, made without proving, or can't be serialized.
+    pub fn from_risc0(
+        receipt: &Receipt,
+        image_id: impl Into<Digest>,
+        proof_generator_addr: Address,
+    ) -> Result<Self, SubmitError> {
+        if matches!(receipt.inner, InnerReceipt::Fake(_)) {
+            return Err(SubmitError::InvalidProof {
+                proving_system: ProvingSystemId::Risc0,
+                reason: "fake receipts, made in dev mode, can't be verified".to_string(),
+            });
+        }
+        let serialized_receipt =
+            bincode::serialize(receipt).map_err(|e| SubmitError::InvalidProof {
+                proving_system: ProvingSystemId::Risc0,
+                reason: format!("the receipt can't be serialized: {}", e),
+            })?;
+
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Risc0,
+            proof: serialized_receipt,
+            pub_input: Some(receipt.journal.bytes.clone()),
+            verification_key: None,
+            vm_program_code: Some(image_id.into().as_bytes().to_vec()),
+            proof_generator_addr,
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        verification_data.validate()?;
+        Ok(verification_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdk::get_risc0_journal;
+
+    #[test]
+    fn test_from_risc0_serializes_the_receipt_as_sent_to_aligned() {
+        let base_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let receipt_bytes =
+            std::fs::read(base_dir.join("test_files/risc_zero/risc_zero_fibonacci.proof")).unwrap();
+        let image_id =
+            std::fs::read(base_dir.join("test_files/risc_zero/fibonacci_id.bin")).unwrap();
+        let receipt: Receipt = bincode::deserialize(&receipt_bytes).unwrap();
+
+        let verification_data = VerificationData::from_risc0(
+            &receipt,
+            <[u8; 32]>::try_from(image_id.as_slice()).unwrap(),
+            Address::zero(),
+        )
+        .unwrap();
+
+        assert_eq!(verification_data.proof, receipt_bytes);
+        assert_eq!(
+            verification_data.pub_input,
+            Some(get_risc0_journal(&receipt_bytes).unwrap())
+        );
+        assert_eq!(verification_data.vm_program_code, Some(image_id));
+    }
+}
@@ -0,0 +1,589 @@
+//! A client of the Ethereum RPC node shared by the verification, event and balance queries of the
+//! SDK. It wraps the ethers provider to retry failed requests with a `RetryPolicy`, coalesce
+//! identical `verify_batch_inclusion` calls made concurrently into a single request, and track
+//! the latency of the requests of each method.
+//!
+//! Clones of a client share its in-flight requests and metrics, so applications checking many
+//! proofs of the same batch at once, such as one task per proof, hit the node once per proof.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ethers::prelude::LogMeta;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes, NameOrAddress, TransactionRequest, H160, H256, U256};
+use tokio::sync::OnceCell;
+
+use crate::errors::VerificationError;
+use crate::eth::{self, BatchVerifiedFilter, NewBatchFilter};
+use crate::retry::RetryPolicy;
+use crate::sdk::{get_aligned_service_manager_address, get_merkle_proof_bytes};
+use crate::tags::get_batch_data;
+use crate::types::{AlignedVerificationData, Chain, VerificationData};
+
+/// Requests sent to the node for a method and how long they took, including retries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RpcMetrics {
+    pub requests: u64,
+    pub failures: u64,
+    /// Calls answered by an identical request already in flight, without a request of their own.
+    pub coalesced: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl RpcMetrics {
+    pub fn mean_latency(&self) -> Option<Duration> {
+        (self.requests > 0).then(|| self.total_latency / self.requests as u32)
+    }
+}
+
+/// The address of the service manager and the calldata of a `verify_batch_inclusion` call.
+type InclusionQuery = (H160, Vec<u8>);
+
+/// Result of a `verify_batch_inclusion` call, shared by the identical calls waiting for it.
+type SharedInclusion = Arc<OnceCell<Result<bool, VerificationError>>>;
+
+struct RpcClientState {
+    provider: Provider<Http>,
+    retry_policy: RetryPolicy,
+    in_flight: Mutex<HashMap<InclusionQuery, SharedInclusion>>,
+    metrics: Mutex<BTreeMap<&'static str, RpcMetrics>>,
+}
+
+#[derive(Clone)]
+pub struct AlignedRpcClient {
+    state: Arc<RpcClientState>,
+}
+
+impl AlignedRpcClient {
+    /// Creates a client whose requests are not retried, unless a retry policy is set with
+    /// `with_retry_policy`.
+    pub fn new(provider: Provider<Http>) -> Self {
+        Self::with_retry_policy(
+            provider,
+            RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates a client that retries the requests failing with the errors retried by the
+    /// classifier of the policy. By default, `EthError`s, such as a node that can't be reached or
+    /// is rate limiting, and failed downloads of batch data are retried, and reverted calls are
+    /// not.
+    pub fn with_retry_policy(provider: Provider<Http>, retry_policy: RetryPolicy) -> Self {
+        AlignedRpcClient {
+            state: Arc::new(RpcClientState {
+                provider,
+                retry_policy,
+                in_flight: Mutex::new(HashMap::new()),
+                metrics: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Creates a client for the node at the given URL, as `new`.
+    /// # Errors
+    /// * `EthError` if the URL is not valid.
+    pub fn from_url(eth_rpc_url: &str) -> Result<Self, VerificationError> {
+        let provider = Provider::<Http>::try_from(eth_rpc_url)
+            .map_err(|e| VerificationError::EthError(e.to_string()))?;
+        Ok(Self::new(provider))
+    }
+
+    pub fn provider(&self) -> &Provider<Http> {
+        &self.state.provider
+    }
+
+    /// Returns the policy with which the requests of the client are retried.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.state.retry_policy
+    }
+
+    /// Returns the metrics of each method requested so far, by method name.
+    pub fn metrics(&self) -> BTreeMap<&'static str, RpcMetrics> {
+        self.state.metrics.lock().unwrap().clone()
+    }
+
+    /// Checks with the service manager of the chain if the proof is included in its batch and the
+    /// batch has been verified. Identical calls made while one is in flight wait for its result
+    /// instead of sending their own request.
+    /// # Errors
+    /// * The same as `sdk::verify_proof_onchain`, except for the batch version, which is not
+    ///   checked.
+    pub async fn verify_batch_inclusion(
+        &self,
+        aligned_verification_data: &AlignedVerificationData,
+        chain: &Chain,
+    ) -> Result<bool, VerificationError> {
+        let service_manager = self.service_manager(chain).await?;
+        let verification_data_comm = &aligned_verification_data.verification_data_commitment;
+        let call = service_manager.verify_batch_inclusion(
+            verification_data_comm.proof_commitment,
+            verification_data_comm.pub_input_commitment,
+            verification_data_comm.proving_system_aux_data_commitment,
+            verification_data_comm.proof_generator_addr,
+            aligned_verification_data.batch_merkle_root,
+            get_merkle_proof_bytes(aligned_verification_data).into(),
+            aligned_verification_data.index_in_batch.into(),
+        );
+
+        let query = (
+            service_manager.address(),
+            call.calldata().unwrap_or_default().to_vec(),
+        );
+        let in_flight = {
+            let mut in_flight = self.state.in_flight.lock().unwrap();
+            if in_flight.contains_key(&query) {
+                self.record_coalesced("verify_batch_inclusion");
+            }
+            in_flight.entry(query.clone()).or_default().clone()
+        };
+
+        let result = in_flight
+            .get_or_init(|| {
+                self.request("verify_batch_inclusion", || async {
+                    match call.call().await {
+                        Ok(result) => Ok(result),
+                        Err(e) => Err(eth::call_error(
+                            e,
+                            service_manager.client_ref(),
+                            service_manager.address(),
+                        )
+                        .await),
+                    }
+                })
+            })
+            .await
+            .clone();
+
+        // Later calls send a new request, since the batch may have been verified since then
+        let mut in_flight_queries = self.state.in_flight.lock().unwrap();
+        if in_flight_queries
+            .get(&query)
+            .is_some_and(|query_in_flight| Arc::ptr_eq(query_in_flight, &in_flight))
+        {
+            in_flight_queries.remove(&query);
+        }
+
+        result
+    }
+
+    /// Returns the block in which the batch was created and whether it has been responded.
+    /// # Errors
+    /// * `EthError` if there is an error calling the service manager.
+    /// * `InvalidAddress` if the address of the service manager is not valid.
+    pub async fn batch_state(
+        &self,
+        batch_merkle_root: [u8; 32],
+        chain: &Chain,
+    ) -> Result<(u32, bool), VerificationError> {
+        let service_manager = self.service_manager(chain).await?;
+        self.request("batches_state", || async {
+            service_manager
+                .batches_state(batch_merkle_root)
+                .await
+                .map_err(|e| VerificationError::EthError(e.to_string()))
+        })
+        .await
+    }
+
+    /// Returns the `NewBatch` events of the batch emitted in the block in which its task was
+    /// created.
+    /// # Errors
+    /// * `EthError` if there is an error querying the events.
+    /// * `InvalidAddress` if the address of the service manager is not valid.
+    pub async fn batch_created_events(
+        &self,
+        batch_merkle_root: [u8; 32],
+        task_created_block: u32,
+        chain: &Chain,
+    ) -> Result<Vec<(NewBatchFilter, LogMeta)>, VerificationError> {
+        let service_manager = self.service_manager(chain).await?;
+        self.request("batch_created_events", || async {
+            service_manager
+                .new_batch_filter()
+                .topic1(H256::from(batch_merkle_root))
+                .from_block(task_created_block)
+                .to_block(task_created_block)
+                .query_with_meta()
+                .await
+                .map_err(|e| VerificationError::EthError(e.to_string()))
+        })
+        .await
+    }
+
+    /// Returns the `BatchVerified` events of the batch emitted from the given block on.
+    /// # Errors
+    /// * `EthError` if there is an error querying the events.
+    /// * `InvalidAddress` if the address of the service manager is not valid.
+    pub async fn batch_verified_events(
+        &self,
+        batch_merkle_root: [u8; 32],
+        from_block: u32,
+        chain: &Chain,
+    ) -> Result<Vec<(BatchVerifiedFilter, LogMeta)>, VerificationError> {
+        let service_manager = self.service_manager(chain).await?;
+        self.request("batch_verified_events", || async {
+            service_manager
+                .batch_verified_filter()
+                .topic1(H256::from(batch_merkle_root))
+                .from_block(from_block)
+                .query_with_meta()
+                .await
+                .map_err(|e| VerificationError::EthError(e.to_string()))
+        })
+        .await
+    }
+
+    /// Returns the `NewBatch` events emitted from the given block on.
+    /// # Errors
+    /// * `EthError` if there is an error querying the events.
+    /// * `InvalidAddress` if the address of the service manager is not valid.
+    pub async fn new_batch_events(
+        &self,
+        from_block: u64,
+        chain: &Chain,
+    ) -> Result<Vec<NewBatchFilter>, VerificationError> {
+        let service_manager = self.service_manager(chain).await?;
+        self.request("new_batch_events", || async {
+            service_manager
+                .new_batch_filter()
+                .from_block(from_block)
+                .query()
+                .await
+                .map_err(|e| VerificationError::EthError(e.to_string()))
+        })
+        .await
+    }
+
+    /// Downloads the data of a batch from where the batcher uploaded it, retrying it with the
+    /// retry policy of the client.
+    /// # Errors
+    /// * `BatchDataError` if the data of the batch can't be downloaded.
+    /// * `ParsingError` if the data of the batch is not valid.
+    pub async fn batch_data(
+        &self,
+        batch_data_pointer: &str,
+    ) -> Result<Vec<VerificationData>, VerificationError> {
+        self.request("batch_data", || get_batch_data(batch_data_pointer))
+            .await
+    }
+
+    /// Returns the balance of a user in the batcher payment service contract.
+    /// # Errors
+    /// * `EthError` if there is an error calling the contract or its response is not a balance.
+    pub async fn user_balance(
+        &self,
+        batcher_payment_service: Address,
+        user_address: Address,
+    ) -> Result<U256, VerificationError> {
+        let selector = &ethers::utils::keccak256("UserBalances(address)".as_bytes())[..4];
+        let encoded_params = ethers::abi::encode(&[ethers::abi::Token::Address(user_address)]);
+
+        let mut call_data = selector.to_vec();
+        call_data.extend_from_slice(&encoded_params);
+
+        let tx = TypedTransaction::Legacy(TransactionRequest {
+            to: Some(NameOrAddress::Address(batcher_payment_service)),
+            data: Some(Bytes(call_data.into())),
+            ..Default::default()
+        });
+
+        let result = self
+            .request("user_balances", || async {
+                self.state
+                    .provider
+                    .call_raw(&tx)
+                    .await
+                    .map_err(|e| VerificationError::EthError(e.to_string()))
+            })
+            .await?;
+
+        if result.len() != 32 {
+            return Err(VerificationError::EthError(
+                "Invalid response from contract".to_string(),
+            ));
+        }
+        Ok(U256::from_big_endian(&result))
+    }
+
+    /// Returns the current gas price of the chain.
+    /// # Errors
+    /// * `EthError` if there is an error querying the node.
+    pub async fn gas_price(&self) -> Result<U256, VerificationError> {
+        self.request("gas_price", || async {
+            self.state
+                .provider
+                .get_gas_price()
+                .await
+                .map_err(|e| VerificationError::EthError(e.to_string()))
+        })
+        .await
+    }
+
+    async fn service_manager(
+        &self,
+        chain: &Chain,
+    ) -> Result<eth::AlignedLayerServiceManager, VerificationError> {
+        eth::aligned_service_manager(
+            self.state.provider.clone(),
+            get_aligned_service_manager_address(chain),
+        )
+        .await
+    }
+
+    /// Sends a request, retrying it with the retry policy of the client, and records the latency
+    /// of each attempt.
+    async fn request<T, F, Fut>(
+        &self,
+        method: &'static str,
+        request: F,
+    ) -> Result<T, VerificationError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, VerificationError>>,
+    {
+        self.state
+            .retry_policy
+            .retry(&format!("RPC request {}", method), || async {
+                let start = Instant::now();
+                let result = request().await;
+                self.record_request(method, start.elapsed(), result.is_ok());
+                result
+            })
+            .await
+    }
+
+    fn record_request(&self, method: &'static str, latency: Duration, succeeded: bool) {
+        let mut metrics = self.state.metrics.lock().unwrap();
+        let metrics = metrics.entry(method).or_default();
+        metrics.requests += 1;
+        if !succeeded {
+            metrics.failures += 1;
+        }
+        metrics.total_latency += latency;
+        metrics.max_latency = metrics.max_latency.max(latency);
+    }
+
+    fn record_coalesced(&self, method: &'static str) {
+        let mut metrics = self.state.metrics.lock().unwrap();
+        metrics.entry(method).or_default().coalesced += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use lambdaworks_crypto::merkle_tree::proof::Proof;
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use crate::types::{BatchVersion, NetworkEndpoints, VerificationDataCommitment};
+
+    /// Starts an RPC node answering every request with `true` after `delay`, failing the first
+    /// `failures` requests with an HTTP error. Returns its URL and the number of requests
+    /// received.
+    async fn start_rpc_node(delay: Duration, failures: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(answer_requests(stream, delay, failures, received.clone()));
+            }
+        });
+        (url, requests)
+    }
+
+    async fn answer_requests(
+        mut stream: TcpStream,
+        delay: Duration,
+        failures: usize,
+        requests: Arc<AtomicUsize>,
+    ) {
+        let mut buffer = Vec::new();
+        loop {
+            // Reads the headers and then the body of the next request
+            let headers_end = loop {
+                if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                    break end + 4;
+                }
+                let mut bytes = [0; 4096];
+                match stream.read(&mut bytes).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(read) => buffer.extend_from_slice(&bytes[..read]),
+                }
+            };
+            let headers = String::from_utf8_lossy(&buffer[..headers_end]).to_lowercase();
+            let content_length: usize = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|length| length.trim().parse().ok())
+                .unwrap_or(0);
+            while buffer.len() < headers_end + content_length {
+                let mut bytes = [0; 4096];
+                match stream.read(&mut bytes).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(read) => buffer.extend_from_slice(&bytes[..read]),
+                }
+            }
+            let body: Vec<u8> = buffer
+                .drain(..headers_end + content_length)
+                .skip(headers_end)
+                .collect();
+            let request: Value = serde_json::from_slice(&body).unwrap();
+
+            tokio::time::sleep(delay).await;
+            let response = if requests.fetch_add(1, Ordering::SeqCst) < failures {
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n".to_string()
+            } else {
+                let body = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": format!("0x{:064x}", 1),
+                })
+                .to_string();
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            };
+            if stream.write_all(response.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn aligned_verification_data() -> AlignedVerificationData {
+        AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment {
+                proof_commitment: [1; 32],
+                pub_input_commitment: [2; 32],
+                proving_system_aux_data_commitment: [3; 32],
+                proof_generator_addr: [4; 20],
+            },
+            batch_merkle_root: [5; 32],
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[6; 32]],
+            },
+            index_in_batch: 0,
+            batch_version: BatchVersion::V1,
+            batch_metadata: None,
+            metadata: None,
+            schema_version: AlignedVerificationData::SCHEMA_VERSION,
+        }
+    }
+
+    fn custom_chain() -> Chain {
+        Chain::Custom(NetworkEndpoints {
+            aligned_service_manager: format!("{:?}", Address::repeat_byte(7)),
+            ..Chain::Devnet.endpoints()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_identical_concurrent_calls_are_coalesced() {
+        let (url, requests) = start_rpc_node(Duration::from_millis(200), 0).await;
+        let rpc_client = AlignedRpcClient::from_url(&url).unwrap();
+        let aligned_verification_data = aligned_verification_data();
+        let chain = custom_chain();
+
+        let calls = (0..5).map(|_| {
+            let rpc_client = rpc_client.clone();
+            let aligned_verification_data = aligned_verification_data.clone();
+            let chain = chain.clone();
+            async move {
+                rpc_client
+                    .verify_batch_inclusion(&aligned_verification_data, &chain)
+                    .await
+            }
+        });
+        for result in futures_util::future::join_all(calls).await {
+            assert!(result.unwrap());
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let metrics = rpc_client.metrics()["verify_batch_inclusion"].clone();
+        assert_eq!(metrics.requests, 1);
+        assert_eq!(metrics.coalesced, 4);
+        assert!(metrics.max_latency >= Duration::from_millis(200));
+
+        // Once answered, the same call is requested again
+        assert!(rpc_client
+            .verify_batch_inclusion(&aligned_verification_data, &chain)
+            .await
+            .unwrap());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_requests_are_retried() {
+        let (url, requests) = start_rpc_node(Duration::ZERO, 2).await;
+        let provider = Provider::<Http>::try_from(url.as_str()).unwrap();
+        let chain = custom_chain();
+
+        // Without a retry policy, the first failure is returned
+        let rpc_client = AlignedRpcClient::new(provider.clone());
+        assert!(matches!(
+            rpc_client.batch_state([5; 32], &chain).await,
+            Err(VerificationError::EthError(_))
+        ));
+
+        let rpc_client = AlignedRpcClient::with_retry_policy(
+            provider,
+            RetryPolicy {
+                max_attempts: 2,
+                initial_backoff: Duration::from_millis(1),
+                ..Default::default()
+            },
+        );
+        let balance = rpc_client
+            .user_balance(Address::repeat_byte(7), Address::repeat_byte(8))
+            .await
+            .unwrap();
+        assert_eq!(balance, U256::one());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        let metrics = rpc_client.metrics()["user_balances"].clone();
+        assert_eq!(metrics.requests, 2);
+        assert_eq!(metrics.failures, 1);
+        assert!(metrics.mean_latency().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_balance_below_the_estimated_fee_is_insufficient() {
+        // The node answers 1 wei to both the balance and the gas price queries
+        let (url, _requests) = start_rpc_node(Duration::ZERO, 0).await;
+        let rpc_client = AlignedRpcClient::from_url(&url).unwrap();
+
+        let check = crate::fees::check_balance(
+            &rpc_client,
+            Address::repeat_byte(7),
+            Address::repeat_byte(8),
+            2,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(check.balance, U256::one());
+        assert_eq!(check.gas_price, U256::one());
+        assert_eq!(
+            check.estimated_fee,
+            U256::from(crate::fees::gas_per_proof(2) * 2)
+        );
+        assert!(!check.is_sufficient());
+        assert_eq!(rpc_client.metrics()["gas_price"].requests, 1);
+    }
+}
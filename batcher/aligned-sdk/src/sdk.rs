@@ -1,30 +1,147 @@
+use crate::auth;
+use crate::bug_report::{FrameDirection, Transcript};
+use crate::build_info;
+use crate::chunks::{ChunkSizer, ChunkSplitter, Payload};
+use crate::client::SubmitOptions;
+use crate::clock;
+use crate::encryption::{self, EncryptionConfig};
 use crate::errors;
 use crate::eth;
+use crate::lifecycle::SubmissionEvent;
+use crate::quorum::{get_batch_attestation, OnchainVerification};
+use crate::rpc::AlignedRpcClient;
+use crate::session::{self, ClientSession};
+use crate::tls::{self, TlsConfig};
 use crate::types::{
-    AlignedVerificationData, BatchInclusionData, Chain, ClientMessage, VerificationCommitmentBatch,
-    VerificationData, VerificationDataCommitment,
+    AlignedVerificationData, BatchInclusionData, BatchMerkleTreeBackend, BatchedClientMessage,
+    Chain, ClientMessage, ErrorResponse, HashFunction, ProtocolHandshake, ReplayProtection,
+    SponsoredClientMessage, VerificationCommitmentBatch, VerificationData,
+    VerificationDataCommitment,
 };
-use ethers::prelude::k256::ecdsa::SigningKey;
-use ethers::signers::Wallet;
+use crate::wire::{self, WireFormat, WireMessage};
+use ethers::abi::AbiEncode;
+use ethers::signers::Signer;
+use ethers::types::{Signature, H256};
+use lambdaworks_crypto::merkle_tree::traits::IsMerkleTreeBackend;
 use sha3::{Digest, Keccak256};
-use std::sync::Arc;
-use tokio::{net::TcpStream, sync::Mutex};
-use tokio_tungstenite::connect_async;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
 use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{client_async_tls_with_config, connect_async_tls_with_config};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
 use log::{debug, error};
+use serde::{Deserialize, Serialize};
 
 use ethers::providers::{Http, Provider};
 use ethers::utils::hex;
 use futures_util::{
-    future,
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt, TryStreamExt,
+    stream::{self, SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt,
 };
 
 pub const CURRENT_PROTOCOL_VERSION: u16 = 0;
 
+/// Header of the websocket handshake holding the protocol version of the client. Batchers answer
+/// clients that send it with a `ProtocolHandshake` instead of their bare protocol version.
+pub const PROTOCOL_VERSION_HEADER: &str = "x-aligned-protocol-version";
+
+/// Feature of batchers that reassemble messages sent in chunks, as described in `chunks`.
+pub const CHUNKED_MESSAGES_FEATURE: &str = "chunked_messages";
+
+/// Feature of batchers that accept client messages in the binary encoding described in `wire`.
+pub const BINARY_MESSAGES_FEATURE: &str = "binary_messages";
+
+/// Feature of batchers that accept client messages compressed with zstd, as described in `wire`.
+pub const ZSTD_MESSAGES_FEATURE: &str = "zstd_messages";
+
+/// Feature of batchers that echo the submission id of client messages in their responses, so
+/// that submissions can share a connection, as in `multiplex`.
+pub const SUBMISSION_IDS_FEATURE: &str = "submission_ids";
+
+/// Feature of batchers that send an `ErrorResponse` with the details of the rejection before
+/// closing the connection when they reject a message.
+pub const ERROR_RESPONSES_FEATURE: &str = "error_responses";
+
+/// Feature of batchers that keep the responses of the proofs of a connection for a while after
+/// it's lost, so that a client can resume its submission on a new connection, as described in
+/// `session`.
+pub const SESSION_RESUMPTION_FEATURE: &str = "session_resumption";
+
+/// Feature of batchers that answer an `InclusionQuery` with the status of the proofs they
+/// received, so that a client can get the responses of a submission it was interrupted in, as
+/// described in `pending`.
+pub const INCLUSION_QUERIES_FEATURE: &str = "inclusion_queries";
+
+/// Feature of batchers that answer a `GetSubmissionStatus` with how far a proof has gone in the
+/// batcher, so that a client can poll it after disconnecting, as `get_submission_status`.
+pub const SUBMISSION_STATUS_FEATURE: &str = "submission_status";
+
+/// Feature of batchers that take the metadata of verification data and echo it in the
+/// `BatchInclusionData` of their proofs, see `VerificationData::metadata`.
+pub const USER_METADATA_FEATURE: &str = "user_metadata";
+
+/// Feature of batchers that verify batched client messages signed over their entries merkle root
+/// and entry count, see `BatchedClientMessage::signed_hash`.
+pub const BATCHED_ENTRY_COUNT_FEATURE: &str = "batched_entry_count";
+
+/// Feature of batchers that check the nonce and expiry of the messages that have a
+/// `ReplayProtection`, so that a captured message is rejected if it's sent again. The SDK signs
+/// every message it sends to these batchers with one, valid for `REPLAY_PROTECTION_VALIDITY`.
+pub const REPLAY_PROTECTION_FEATURE: &str = "replay_protection";
+
+/// Time after which the messages signed by the SDK with a `ReplayProtection` are rejected.
+pub const REPLAY_PROTECTION_VALIDITY: Duration = Duration::from_secs(5 * 60);
+
+/// Feature of batchers that publish an encryption key in their handshake and accept client
+/// messages encrypted to it, as described in `encryption`. It's not one of the
+/// `PROTOCOL_FEATURES`, since batchers only announce it when configured with a key.
+pub const ENCRYPTED_PAYLOADS_FEATURE: &str = "encrypted_payloads";
+
+/// Features of the protocol implemented by this version of the SDK and the batcher.
+pub const PROTOCOL_FEATURES: &[&str] = &[
+    CHUNKED_MESSAGES_FEATURE,
+    BINARY_MESSAGES_FEATURE,
+    ZSTD_MESSAGES_FEATURE,
+    SUBMISSION_IDS_FEATURE,
+    ERROR_RESPONSES_FEATURE,
+    SESSION_RESUMPTION_FEATURE,
+    INCLUSION_QUERIES_FEATURE,
+    SUBMISSION_STATUS_FEATURE,
+    USER_METADATA_FEATURE,
+    BATCHED_ENTRY_COUNT_FEATURE,
+    REPLAY_PROTECTION_FEATURE,
+];
+
+/// Header of the websocket handshake holding the features of the client, separated by commas.
+/// Batchers only expect binary messages from clients that announce them in it.
+pub const PROTOCOL_FEATURES_HEADER: &str = "x-aligned-protocol-features";
+
+/// Returns the handshake sent by batchers of this version to the clients that announce their
+/// protocol version.
+pub fn protocol_handshake() -> ProtocolHandshake {
+    ProtocolHandshake {
+        protocol_version: CURRENT_PROTOCOL_VERSION,
+        features: PROTOCOL_FEATURES
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect(),
+        encryption_public_key: None,
+        session: None,
+    }
+}
+
+/// Header of the websocket handshake holding the client version, as returned by
+/// `build_info::client_version`.
+pub const CLIENT_VERSION_HEADER: &str = "x-aligned-client-version";
+
 /// Submits multiple proofs to the batcher to be verified in Aligned.
 /// # Arguments
 /// * `batcher_addr` - The address of the batcher to which the proof will be submitted.
@@ -36,289 +153,2015 @@ pub const CURRENT_PROTOCOL_VERSION: u16 = 0;
 /// * If there is an error connecting to the batcher.
 /// * If there is an error serializing the message.
 /// * If there is an error deserializing the message.
-pub async fn submit_multiple(
+pub async fn submit_multiple<S: Signer>(
     batcher_addr: &str,
     verification_data: &[VerificationData],
-    wallet: Wallet<SigningKey>,
+    wallet: S,
 ) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError> {
-    let (ws_stream, _) = connect_async(batcher_addr)
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+
+    _submit_multiple(
+        ws_stream,
+        verification_data,
+        |message| sign_with_signer(&wallet, message),
+        false,
+        false,
+        &mut Transcript::new(),
+    )
+    .await
+}
+
+/// Same as `submit_multiple`, closing the connection and failing with a `Timeout` error if the
+/// submission, including connecting to the batcher, takes more than `timeout`.
+pub(crate) async fn submit_multiple_with_timeout<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: S,
+    timeout: Duration,
+) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError> {
+    let timed_out =
+        || errors::SubmitError::Timeout(format!("Submission took more than {:?}", timeout));
+    let start = tokio::time::Instant::now();
+    let ws_stream = tokio::time::timeout(timeout, connect(batcher_addr, &TlsConfig::default()))
         .await
-        .map_err(errors::SubmitError::ConnectionError)?;
+        .map_err(|_| timed_out())??;
 
     debug!("WebSocket handshake has been successfully completed");
-    let (ws_write, ws_read) = ws_stream.split();
 
-    let ws_write = Arc::new(Mutex::new(ws_write));
+    let mut progress = SubmissionProgress::default();
+    let all_responded = submit_with_progress(
+        ws_stream,
+        verification_data,
+        |message| sign_with_signer(&wallet, message),
+        false,
+        false,
+        &mut Transcript::new(),
+        &mut progress,
+        Some(timeout.saturating_sub(start.elapsed())),
+        None,
+    )
+    .await
+    .map_err(|e| match e {
+        errors::SubmitError::Timeout(_) => timed_out(),
+        e => e,
+    })?;
 
-    _submit_multiple(ws_write, ws_read, verification_data, wallet).await
+    Ok(all_responded.then_some(progress.aligned_verification_data))
 }
 
-async fn _submit_multiple(
-    ws_write: Arc<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>,
-    mut ws_read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+/// Submits multiple proofs to the batcher to be verified in Aligned, signing all of them at once.
+/// A merkle tree is built with the commitments of the proofs and only its root is signed, so the
+/// wallet is asked for a single signature instead of one per proof.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proof will be submitted.
+/// * `verification_data` - An array of verification data of each proof.
+/// * `wallet` - The wallet used to sign the proofs.
+/// # Returns
+/// * An array of aligned verification data obtained when submitting the proof.
+/// # Errors
+/// * If there is an error connecting to the batcher.
+/// * If there is an error serializing the message.
+/// * If there is an error deserializing the message.
+pub async fn submit_multiple_with_single_signature<S: Signer>(
+    batcher_addr: &str,
     verification_data: &[VerificationData],
-    wallet: Wallet<SigningKey>,
+    wallet: S,
 ) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError> {
-    // First message from the batcher is the protocol version
-    if let Some(Ok(msg)) = ws_read.next().await {
-        match msg.into_data().try_into() {
-            Ok(data) => {
-                let expected_protocol_version = u16::from_be_bytes(data);
-                if expected_protocol_version > CURRENT_PROTOCOL_VERSION {
-                    return Err(errors::SubmitError::ProtocolVersionMismatch(
-                        CURRENT_PROTOCOL_VERSION,
-                        expected_protocol_version,
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+
+    _submit_multiple(
+        ws_stream,
+        verification_data,
+        |message| sign_with_signer(&wallet, message),
+        true,
+        false,
+        &mut Transcript::new(),
+    )
+    .await
+}
+
+/// Submits multiple proofs to the batcher like `submit_multiple`, with the given options and TLS
+/// configuration, and returns when each proof was queued, sent, acknowledged and included. The
+/// frames exchanged are recorded in `transcript`, for the bug report of the submission.
+/// The wallet is borrowed, so that signers that can't be cloned, such as hardware wallets, can be
+/// used for many submissions. The messages are encrypted to the key published by the batcher if
+/// `encryption` is set, as described in `encryption`. `AlignedClient` submits through it with
+/// the options of its builder.
+/// # Errors
+/// * `InvalidCertificate` if a root certificate of `tls_config` is not valid.
+/// * `TlsError` if the TLS handshake fails, such as when the certificate of the batcher is not
+///   trusted or is not valid for its name.
+/// * `Unauthorized` if the batcher refuses the access token of `tls_config`, or the lack of one.
+/// * `ProtocolMismatch` if messages are encrypted and the batcher doesn't support the
+///   `encrypted_payloads` feature, or needs to receive the messages in a single frame.
+/// * `ProtocolViolation` if the batcher presents a key other than the one of `encryption`, or
+///   an invalid key.
+/// * `ProtocolViolation` in strict mode if the batcher doesn't follow the protocol, see
+///   `SubmitOptions::strict`.
+/// * The same as `submit_multiple`.
+pub(crate) async fn submit_with_tls<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: &S,
+    options: &SubmitOptions,
+    tls_config: &TlsConfig,
+    encryption: Option<&EncryptionConfig>,
+    transcript: &mut Transcript,
+) -> Result<TimedSubmission, errors::SubmitError> {
+    let ws_stream = connect(batcher_addr, tls_config).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+
+    let mut progress = SubmissionProgress::default();
+    let all_responded = submit_with_progress(
+        ws_stream,
+        verification_data,
+        |message| sign_with_signer(wallet, message),
+        options.single_signature,
+        options.strict,
+        transcript,
+        &mut progress,
+        None,
+        encryption,
+    )
+    .await?;
+
+    if options.strict && !all_responded {
+        return Err(errors::SubmitError::ProtocolViolation(
+            "Not every proof was responded".to_string(),
+        ));
+    }
+    Ok(TimedSubmission {
+        aligned_verification_data: all_responded.then_some(progress.aligned_verification_data),
+        timings: progress.timings,
+    })
+}
+
+/// A proof of a submission without a matching response from the batcher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnconfirmedProof {
+    /// Position of the proof in the submitted verification data.
+    pub position: usize,
+    pub verification_data_commitment: VerificationDataCommitment,
+}
+
+/// Result of a best effort submission: the aligned verification data of the proofs responded,
+/// in the order they were sent, and the proofs without a matching response.
+pub struct PartialSubmission {
+    pub aligned_verification_data: Vec<AlignedVerificationData>,
+    pub unconfirmed: Vec<UnconfirmedProof>,
+    /// The error that stopped the submission after some proofs were responded, if any.
+    pub interrupted_by: Option<errors::SubmitError>,
+    /// Timing of every proof of the submission, in their order.
+    pub timings: Vec<EntryTiming>,
+}
+
+impl PartialSubmission {
+    /// Whether every proof was responded.
+    pub fn is_complete(&self) -> bool {
+        self.unconfirmed.is_empty()
+    }
+}
+
+/// Submits multiple proofs to the batcher like `submit_multiple`, returning the aligned
+/// verification data received before the connection is lost, instead of discarding it. The
+/// proofs without a matching response are returned with their commitments, so that they can be
+/// submitted again.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proofs will be submitted.
+/// * `verification_data` - An array of verification data of each proof.
+/// * `wallet` - The wallet used to sign the proofs.
+/// * `single_signature` - Whether to sign all the proofs at once, as in
+///   `submit_multiple_with_single_signature`.
+/// # Returns
+/// * The aligned verification data of the proofs responded and the proofs left unconfirmed.
+/// # Errors
+/// * The same as `submit_multiple`, if they happen before any proof is responded. Errors after
+///   that are returned in `interrupted_by`.
+pub async fn submit_multiple_best_effort<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: S,
+    single_signature: bool,
+) -> Result<PartialSubmission, errors::SubmitError> {
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+
+    let mut progress = SubmissionProgress::default();
+    let result = submit_with_progress(
+        ws_stream,
+        verification_data,
+        |message| sign_with_signer(&wallet, message),
+        single_signature,
+        false,
+        &mut Transcript::new(),
+        &mut progress,
+        None,
+        None,
+    )
+    .await;
+
+    let interrupted_by = match result {
+        Ok(_) => None,
+        Err(e) if progress.responded == 0 => return Err(e),
+        Err(e) => {
+            error!(
+                "Submission interrupted after {} of {} responses: {:?}",
+                progress.responded,
+                verification_data.len(),
+                e
+            );
+            Some(e)
+        }
+    };
+
+    let unconfirmed = progress
+        .unmatched
+        .into_iter()
+        .chain(progress.responded..verification_data.len())
+        .map(|position| UnconfirmedProof {
+            position,
+            verification_data_commitment: verification_data[position].clone().into(),
+        })
+        .collect();
+
+    Ok(PartialSubmission {
+        aligned_verification_data: progress.aligned_verification_data,
+        unconfirmed,
+        interrupted_by,
+        timings: progress.timings,
+    })
+}
+
+/// Submits multiple proofs to the batcher to be verified in Aligned, obtaining the signature of
+/// each proof from `sign_message` instead of an in-process wallet. This allows signing with an
+/// HSM, a remote signing service or an MPC provider.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proof will be submitted.
+/// * `verification_data` - An array of verification data of each proof.
+/// * `sign_message` - Async callback receiving the 32 bytes to sign for each proof. They have
+///   to be signed as an Ethereum message (EIP-191), in the same way `Wallet::sign_message` does.
+///   It's called for several proofs at once, and the proofs are still sent in their order.
+/// # Returns
+/// * An array of aligned verification data obtained when submitting the proof.
+/// # Errors
+/// * If there is an error connecting to the batcher.
+/// * If there is an error serializing the message.
+/// * If there is an error deserializing the message.
+/// * Any error returned by `sign_message`.
+pub async fn submit_multiple_with_external_signer<F, Fut>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    sign_message: F,
+) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError>
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = Result<Signature, errors::SubmitError>>,
+{
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+
+    _submit_multiple(
+        ws_stream,
+        verification_data,
+        sign_message,
+        false,
+        false,
+        &mut Transcript::new(),
+    )
+    .await
+}
+
+/// Opens a websocket connection to the batcher, sending the client version in the handshake.
+/// Addresses starting with `wss://` are connected with TLS, as set by `tls_config`, which also
+/// holds the access token sent, if any.
+pub(crate) async fn connect(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, errors::SubmitError> {
+    connect_resuming(batcher_addr, tls_config, None).await
+}
+
+/// Opens a websocket connection to the batcher as `connect`, resuming the session of a
+/// submission if `resumption` is set, with the number of proofs of the submission responded so
+/// far.
+pub(crate) async fn connect_resuming(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+    resumption: Option<(&ClientSession, usize)>,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, errors::SubmitError> {
+    let connector = tls_config.connector()?;
+    let auth_token = tls_config.auth_token.as_deref();
+
+    let result = match &tls_config.server_name {
+        None => {
+            connect_async_tls_with_config(
+                client_request(batcher_addr, auth_token, resumption)?,
+                None,
+                false,
+                connector,
+            )
+            .await
+        }
+        Some(server_name) => {
+            let (host_and_port, server_name_addr) =
+                tls::server_name_address(batcher_addr, server_name)?;
+            let stream = TcpStream::connect(host_and_port)
+                .await
+                .map_err(|e| errors::SubmitError::ConnectionError(e.into()))?;
+            client_async_tls_with_config(
+                client_request(&server_name_addr, auth_token, resumption)?,
+                stream,
+                None,
+                connector,
+            )
+            .await
+        }
+    };
+
+    let (ws_stream, _) = result.map_err(tls::connection_error)?;
+    Ok(ws_stream)
+}
+
+fn client_request(
+    batcher_addr: &str,
+    auth_token: Option<&str>,
+    resumption: Option<(&ClientSession, usize)>,
+) -> Result<Request, errors::SubmitError> {
+    let mut request = batcher_addr.into_client_request()?;
+    if let Some(auth_token) = auth_token {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, auth::bearer_header(auth_token)?);
+    }
+    if let Some((session, responded)) = resumption {
+        session.insert_headers(request.headers_mut(), responded)?;
+    }
+    request.headers_mut().insert(
+        CLIENT_VERSION_HEADER,
+        HeaderValue::from_static(build_info::client_version()),
+    );
+    request.headers_mut().insert(
+        PROTOCOL_VERSION_HEADER,
+        HeaderValue::from(CURRENT_PROTOCOL_VERSION),
+    );
+    request.headers_mut().insert(
+        PROTOCOL_FEATURES_HEADER,
+        HeaderValue::from_str(&PROTOCOL_FEATURES.join(","))
+            .expect("Protocol features are valid header values"),
+    );
+    Ok(request)
+}
+
+/// Signs the message with `Signer::sign_message`. Up to `signing_parallelism` messages of a bulk
+/// submission are signed at once, which only takes as many cores for signers that sign on other
+/// threads, such as a `PooledWallet`.
+/// # Errors
+/// * `SignerError` if the signer fails to sign the message.
+pub(crate) async fn sign_with_signer<S: Signer>(
+    signer: &S,
+    message: [u8; 32],
+) -> Result<Signature, errors::SubmitError> {
+    signer
+        .sign_message(message)
+        .await
+        .map_err(|e| errors::SubmitError::SignerError(e.to_string()))
+}
+
+/// Number of client messages signed at once when submitting multiple proofs.
+fn signing_parallelism() -> usize {
+    std::thread::available_parallelism().map_or(1, |parallelism| parallelism.get())
+}
+
+/// Returns the client messages of the proofs in their order, signing up to `parallelism` of them
+/// at once. Messages are yielded as soon as they and the ones before them are signed, so sending
+/// them doesn't wait for the whole submission to be signed. With a `replay_protection_validity`,
+/// each message gets a `ReplayProtection` valid for that long.
+pub(crate) fn sign_client_messages<'a, F, Fut>(
+    verification_data: &'a [VerificationData],
+    sign_message: &'a F,
+    parallelism: usize,
+    replay_protection_validity: Option<Duration>,
+) -> impl Stream<Item = Result<ClientMessage, errors::SubmitError>> + 'a
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = Result<Signature, errors::SubmitError>> + 'a,
+{
+    stream::iter(verification_data)
+        .map(move |verification_data| {
+            let replay_protection = replay_protection_validity.map(ReplayProtection::new);
+            signed_client_message(verification_data.clone(), replay_protection, sign_message)
+        })
+        .buffered(parallelism.max(1))
+}
+
+/// Signs the client message of the verification data, with the replay protection if there's one.
+pub(crate) async fn signed_client_message<F, Fut>(
+    verification_data: VerificationData,
+    replay_protection: Option<ReplayProtection>,
+    sign_message: F,
+) -> Result<ClientMessage, errors::SubmitError>
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = Result<Signature, errors::SubmitError>>,
+{
+    match replay_protection {
+        Some(replay_protection) => {
+            ClientMessage::new_with_replay_protection(
+                verification_data,
+                replay_protection,
+                sign_message,
+            )
+            .await
+        }
+        None => ClientMessage::new_with_signer(verification_data, sign_message).await,
+    }
+}
+
+/// Returns how long the messages sent to the batcher are valid for, if it checks their replay
+/// protection.
+pub(crate) fn replay_protection_validity(handshake: &ProtocolHandshake) -> Option<Duration> {
+    handshake
+        .supports(REPLAY_PROTECTION_FEATURE)
+        .then_some(REPLAY_PROTECTION_VALIDITY)
+}
+
+/// Proofs of a submission responded by the batcher so far, kept across connections when the
+/// submission is retried.
+#[derive(Default)]
+pub(crate) struct SubmissionProgress<'a> {
+    /// Number of proofs responded. Responses arrive in the order the proofs were sent, so these
+    /// are the first proofs of the submission.
+    pub(crate) responded: usize,
+    pub(crate) aligned_verification_data: Vec<AlignedVerificationData>,
+    /// Positions in the submission of the responded proofs whose response couldn't be read or
+    /// didn't match the proof.
+    pub(crate) unmatched: Vec<usize>,
+    /// Timing of each proof of the submission, by position.
+    pub(crate) timings: Vec<EntryTiming>,
+    /// Receives the `Sent` and `Accepted` events of the proofs as they happen.
+    pub(crate) on_event: Option<&'a (dyn Fn(SubmissionEvent) + Sync)>,
+    /// Session of the last connection, resumed by the next one.
+    pub(crate) session: Option<ClientSession>,
+}
+
+/// Times at which a proof of a submission went through each stage, read from the clock of the
+/// client. The batcher only responds once the batch of a proof is created, so a proof is
+/// acknowledged and included at the same time, unless its response doesn't match it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryTiming {
+    /// Position of the proof in the submitted verification data.
+    pub position: usize,
+    /// When the submission of the proof started.
+    pub queued_at: SystemTime,
+    /// When the last frame of the message of the proof was sent. If the proof was resubmitted,
+    /// this is the last time it was sent.
+    pub sent_at: Option<SystemTime>,
+    /// When the response of the batcher to the proof was received.
+    pub acknowledged_at: Option<SystemTime>,
+    /// When a response with valid batch inclusion data for the proof was received.
+    pub included_at: Option<SystemTime>,
+}
+
+impl EntryTiming {
+    fn queued(position: usize, queued_at: SystemTime) -> Self {
+        EntryTiming {
+            position,
+            queued_at,
+            sent_at: None,
+            acknowledged_at: None,
+            included_at: None,
+        }
+    }
+}
+
+/// Result of a submission with the timing of each proof: the aligned verification data, or
+/// `None` if not every proof was responded, and the timing of every proof of the submission, in
+/// their order.
+pub struct TimedSubmission {
+    pub aligned_verification_data: Option<Vec<AlignedVerificationData>>,
+    pub timings: Vec<EntryTiming>,
+}
+
+async fn _submit_multiple<F, Fut>(
+    ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    verification_data: &[VerificationData],
+    sign_message: F,
+    single_signature: bool,
+    strict: bool,
+    transcript: &mut Transcript,
+) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError>
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = Result<Signature, errors::SubmitError>>,
+{
+    let mut progress = SubmissionProgress::default();
+    let all_responded = submit_with_progress(
+        ws_stream,
+        verification_data,
+        sign_message,
+        single_signature,
+        strict,
+        transcript,
+        &mut progress,
+        None,
+        None,
+    )
+    .await?;
+
+    Ok(all_responded.then_some(progress.aligned_verification_data))
+}
+
+/// Submits the proofs through an open connection, recording the responses in `progress` as they
+/// arrive. Returns whether all the proofs were responded. If the connection resumed the session
+/// of `progress`, the proofs the batcher already accepted in it are not sent again, and only
+/// their responses are waited for. In strict mode, protocol anomalies
+/// are returned as `ProtocolViolation` errors instead of being logged and skipped. With a
+/// `timeout`, the submission fails with a `Timeout` error if it takes longer. The connection is
+/// closed once the submission ends.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn submit_with_progress<F, Fut>(
+    ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    verification_data: &[VerificationData],
+    sign_message: F,
+    single_signature: bool,
+    strict: bool,
+    transcript: &mut Transcript,
+    progress: &mut SubmissionProgress<'_>,
+    timeout: Option<Duration>,
+    encryption: Option<&EncryptionConfig>,
+) -> Result<bool, errors::SubmitError>
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = Result<Signature, errors::SubmitError>>,
+{
+    if verification_data.is_empty() {
+        return Err(errors::SubmitError::MissingParameter(
+            "verification_data".to_string(),
+        ));
+    }
+    for verification_data in verification_data {
+        verification_data.validate()?;
+    }
+
+    // Proofs resubmitted after a lost connection keep the time their submission started
+    let base = progress.responded;
+    let queued_at = clock::now();
+    while progress.timings.len() < base + verification_data.len() {
+        let position = progress.timings.len();
+        progress
+            .timings
+            .push(EntryTiming::queued(position, queued_at));
+    }
+
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+    let Some(handshake) = read_handshake(&mut ws_read, strict, transcript).await? else {
+        return Ok(false);
+    };
+    let encryption_key = encryption::negotiate(encryption, &handshake)?;
+    let accepted = session::negotiate(
+        &mut progress.session,
+        &handshake,
+        base,
+        verification_data.len(),
+    )?;
+    // Responses arrive in the order the proofs are sent, which is their order in the submission.
+    // This vector is reversed so that the commitment of each response can simply be popped.
+    let mut verification_data_commitments_rev: Vec<VerificationDataCommitment> = verification_data
+        .iter()
+        .rev()
+        .map(|vd| vd.clone().into())
+        .collect();
+
+    let mut send_transcript = transcript.fork();
+    // The sender and the receiver run at once, so the sent times are merged into `progress` after
+    let sent_at = SentTimes {
+        base: base + accepted,
+        sent_at: Mutex::new(vec![None; verification_data.len() - accepted]),
+        on_event: progress.on_event,
+    };
+    let send = send_messages(
+        &mut ws_write,
+        &verification_data[accepted..],
+        &sign_message,
+        single_signature,
+        &handshake,
+        encryption_key.as_ref(),
+        &mut send_transcript,
+        &sent_at,
+    );
+    let receive = receive(
+        ws_read,
+        verification_data.len(),
+        &mut verification_data_commitments_rev,
+        strict,
+        transcript,
+        progress,
+    );
+    let result = run_submission(send, receive, timeout).await;
+    transcript.join(send_transcript);
+
+    let sent_at = sent_at
+        .sent_at
+        .into_inner()
+        .expect("Sent times lock is not poisoned");
+    for (timing, sent_at) in progress.timings[base + accepted..].iter_mut().zip(sent_at) {
+        timing.sent_at = sent_at.or(timing.sent_at);
+    }
+
+    close_connection(&mut ws_write).await;
+    result
+}
+
+/// Closes the connection once the submission ends. The batcher may have closed it already, so
+/// errors are only logged.
+async fn close_connection(
+    ws_write: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+) {
+    debug!("Closing connection...");
+    if let Err(e) = ws_write.close().await {
+        debug!("Connection already closed: {}", e);
+    }
+}
+
+/// When the message of each proof sent over a connection was sent, by position in the
+/// connection. Each proof is also reported as `Sent` as soon as its message is sent.
+struct SentTimes<'a> {
+    /// Position in the submission of the first proof sent over the connection.
+    base: usize,
+    sent_at: Mutex<Vec<Option<SystemTime>>>,
+    on_event: Option<&'a (dyn Fn(SubmissionEvent) + Sync)>,
+}
+
+impl SentTimes<'_> {
+    fn record(&self, positions: std::ops::Range<usize>) {
+        let at = clock::now();
+        self.sent_at
+            .lock()
+            .expect("Sent times lock is not poisoned")[positions.clone()]
+        .fill(Some(at));
+        if let Some(on_event) = self.on_event {
+            for position in positions {
+                on_event(SubmissionEvent::Sent {
+                    position: self.base + position,
+                    at,
+                });
+            }
+        }
+    }
+}
+
+/// Signs and sends the messages of the proofs, in a single batched client message if
+/// `single_signature` is set, recording in `sent_at` when the message of each proof is sent.
+#[allow(clippy::too_many_arguments)]
+async fn send_messages<F, Fut>(
+    ws_write: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    verification_data: &[VerificationData],
+    sign_message: &F,
+    single_signature: bool,
+    handshake: &ProtocolHandshake,
+    encryption_key: Option<&[u8; 32]>,
+    transcript: &mut Transcript,
+    sent_at: &SentTimes<'_>,
+) -> Result<(), errors::SubmitError>
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = Result<Signature, errors::SubmitError>>,
+{
+    // The size of the chunks adapts to the throughput of the connection across its messages
+    let mut chunk_sizer = ChunkSizer::default();
+
+    // Every message is replay protected when the batcher checks it
+    let replay_protection_validity = replay_protection_validity(handshake);
+
+    // A single proof is always sent in a plain client message, since the entries merkle tree
+    // needs at least two leaves.
+    if single_signature && verification_data.len() > 1 {
+        let msg = match replay_protection_validity {
+            Some(validity) => {
+                BatchedClientMessage::new_with_replay_protection(
+                    verification_data.to_vec(),
+                    ReplayProtection::new(validity),
+                    sign_message,
+                )
+                .await?
+            }
+            None => {
+                BatchedClientMessage::new_with_signer(verification_data.to_vec(), sign_message)
+                    .await?
+            }
+        };
+        let msg = WireMessage::Batched(msg);
+        send_message(
+            ws_write,
+            &msg,
+            handshake,
+            encryption_key,
+            transcript,
+            &mut chunk_sizer,
+        )
+        .await?;
+        sent_at.record(0..verification_data.len());
+        debug!("Batched message sent...");
+        return Ok(());
+    }
+
+    let mut messages = sign_client_messages(
+        verification_data,
+        sign_message,
+        signing_parallelism(),
+        replay_protection_validity,
+    );
+    let mut position = 0;
+    while let Some(client_message) = messages.next().await {
+        let msg = WireMessage::Client(client_message?);
+        send_message(
+            ws_write,
+            &msg,
+            handshake,
+            encryption_key,
+            transcript,
+            &mut chunk_sizer,
+        )
+        .await?;
+        sent_at.record(position..position + 1);
+        position += 1;
+        debug!("Message sent...");
+    }
+    Ok(())
+}
+
+/// Runs the sending of the messages of a submission together with the reception of their
+/// responses, bounded by `timeout`. Responses are received while the messages are still being
+/// sent, so a rejection or a closed connection ends the submission right away. The submission
+/// ends as soon as the responses are received or either side fails, and whatever is still
+/// running is dropped with it.
+/// # Errors
+/// * The first error of the sender or the receiver.
+/// * `Timeout` if the submission takes more than `timeout`.
+async fn run_submission(
+    send: impl Future<Output = Result<(), errors::SubmitError>>,
+    receive: impl Future<Output = Result<bool, errors::SubmitError>>,
+    timeout: Option<Duration>,
+) -> Result<bool, errors::SubmitError> {
+    let deadline = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(send, receive, deadline);
+
+    let mut sent = false;
+    loop {
+        tokio::select! {
+            result = &mut send, if !sent => {
+                result?;
+                sent = true;
+            }
+            all_responded = &mut receive => return all_responded,
+            _ = &mut deadline => {
+                return Err(errors::SubmitError::Timeout(format!(
+                    "Submission took more than {:?}",
+                    timeout.unwrap_or_default()
+                )));
+            }
+        }
+    }
+}
+
+/// Sends a message in the format negotiated with the batcher, as described in `wire`. JSON
+/// messages are sent in a single text frame or, if they don't fit in a frame, in the binary frames
+/// of their chunks, as described in `chunks`, while binary messages are always sent in chunks.
+/// Large messages are compressed if the batcher supports it, and sent in chunks as well. With an
+/// `encryption_key`, messages are encrypted to it once compressed, and sent in chunks too. The
+/// size of the chunks is chosen by `chunk_sizer`, which measures the time each chunk takes to be
+/// sent.
+/// # Errors
+/// * `ProtocolMismatch` if the message doesn't fit in a frame and the batcher doesn't reassemble
+///   chunks, it has metadata and the batcher doesn't take it, or it's a batched client message
+///   and the batcher doesn't verify their entry count.
+/// * `SerdeError` or `InvalidBinaryData` if the message can't be encoded.
+/// * `ProtocolViolation` if the encryption key is not valid.
+/// * `ConnectionError` if there is an error sending the message.
+pub(crate) async fn send_message(
+    ws_write: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    message: &WireMessage,
+    handshake: &ProtocolHandshake,
+    encryption_key: Option<&[u8; 32]>,
+    transcript: &mut Transcript,
+    chunk_sizer: &mut ChunkSizer,
+) -> Result<(), errors::SubmitError> {
+    if message.has_metadata() && !handshake.supports(USER_METADATA_FEATURE) {
+        return Err(errors::SubmitError::ProtocolMismatch {
+            sdk_version: CURRENT_PROTOCOL_VERSION,
+            batcher_version: handshake.protocol_version,
+            missing_features: vec![USER_METADATA_FEATURE.to_string()],
+        });
+    }
+    if matches!(message, WireMessage::Batched(_))
+        && !handshake.supports(BATCHED_ENTRY_COUNT_FEATURE)
+    {
+        return Err(errors::SubmitError::ProtocolMismatch {
+            sdk_version: CURRENT_PROTOCOL_VERSION,
+            batcher_version: handshake.protocol_version,
+            missing_features: vec![BATCHED_ENTRY_COUNT_FEATURE.to_string()],
+        });
+    }
+    let compress = |encoded: &[u8]| {
+        if wire::compression_supported(&handshake.features) {
+            wire::compress(encoded)
+        } else {
+            Ok(None)
+        }
+    };
+    let payload = match WireFormat::negotiate(&handshake.features) {
+        WireFormat::Json => {
+            let json = message.to_json()?;
+            match compress(json.as_bytes())? {
+                Some(compressed) => Payload::Chunked(compressed),
+                None => Payload::json(json),
+            }
+        }
+        WireFormat::Binary => {
+            let encoded = message.encode(WireFormat::Binary)?;
+            Payload::Chunked(compress(&encoded)?.unwrap_or(encoded))
+        }
+    };
+
+    let bytes = match (payload, encryption_key) {
+        (Payload::Text(text), None) => {
+            return send_frame(ws_write, Message::Text(text), transcript).await
+        }
+        (Payload::Text(text), Some(key)) => encryption::encrypt(text.as_bytes(), key)?,
+        (Payload::Chunked(bytes), Some(key)) => encryption::encrypt(&bytes, key)?,
+        (Payload::Chunked(bytes), None) => bytes,
+    };
+    if !handshake.supports(CHUNKED_MESSAGES_FEATURE) {
+        return Err(errors::SubmitError::ProtocolMismatch {
+            sdk_version: CURRENT_PROTOCOL_VERSION,
+            batcher_version: handshake.protocol_version,
+            missing_features: vec![CHUNKED_MESSAGES_FEATURE.to_string()],
+        });
+    }
+
+    let mut chunk_splitter = ChunkSplitter::new(rand::random(), &bytes);
+    while let Some(chunk) = chunk_splitter.next_chunk(chunk_sizer.chunk_size()) {
+        let size = chunk.data.len();
+        let start = std::time::Instant::now();
+        send_frame(ws_write, Message::Binary(chunk.to_bytes()), transcript).await?;
+        chunk_sizer.record(size, start.elapsed());
+    }
+    debug!(
+        "Message of {} bytes sent in chunks, next chunk size: {} bytes",
+        bytes.len(),
+        chunk_sizer.chunk_size()
+    );
+    Ok(())
+}
+
+/// Sends a frame to the batcher, recording it in the transcript.
+async fn send_frame(
+    ws_write: &mut SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    msg: Message,
+    transcript: &mut Transcript,
+) -> Result<(), errors::SubmitError> {
+    transcript.record_frame(FrameDirection::Sent, &msg);
+    ws_write
+        .send(msg)
+        .await
+        .map_err(errors::SubmitError::ConnectionError)
+}
+
+/// Sends a query to the batcher in a new connection and returns its answer, read from the first
+/// text frame the batcher sends after its handshake. `description` names the query in errors.
+/// # Errors
+/// * `ConnectionError` if there is an error connecting to the batcher.
+/// * `ProtocolMismatch` if the batcher doesn't support `feature`, or its protocol version is
+///   newer than the one of the SDK.
+/// * `MessageRejected` if the batcher rejects the query with an `ErrorResponse`.
+/// * `BatcherRejection` if the batcher closes the connection without answering.
+/// * `ProtocolViolation` if the batcher doesn't send its handshake, or its answer is not valid.
+pub(crate) async fn query_batcher<R: serde::de::DeserializeOwned>(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+    feature: &str,
+    query: &WireMessage,
+    description: &str,
+) -> Result<R, errors::SubmitError> {
+    let ws_stream = connect(batcher_addr, tls_config).await?;
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+
+    let handshake = read_handshake(&mut ws_read, true, &mut Transcript::new())
+        .await?
+        .ok_or_else(|| {
+            errors::SubmitError::ProtocolViolation("Batcher did not send its handshake".to_string())
+        })?;
+    if !handshake.supports(feature) {
+        return Err(errors::SubmitError::ProtocolMismatch {
+            sdk_version: CURRENT_PROTOCOL_VERSION,
+            batcher_version: handshake.protocol_version,
+            missing_features: vec![feature.to_string()],
+        });
+    }
+
+    ws_write.send(Message::text(query.to_json()?)).await?;
+
+    let answer = loop {
+        match ws_read.next().await {
+            Some(Ok(Message::Text(text))) => {
+                if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(&text) {
+                    return Err(errors::SubmitError::MessageRejected(
+                        error_response.rejection,
                     ));
                 }
+                break serde_json::from_str::<R>(&text).map_err(|e| {
+                    errors::SubmitError::ProtocolViolation(format!(
+                        "Response to the {} is not valid: {}",
+                        description, e
+                    ))
+                })?;
             }
-            Err(_) => {
-                error!("Error while reading protocol version");
-                return Ok(None);
+            Some(Ok(Message::Close(Some(close_msg)))) => {
+                return Err(errors::SubmitError::BatcherRejection(
+                    close_msg.code.into(),
+                    close_msg.reason.as_bytes().to_vec(),
+                ));
+            }
+            Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+            Some(Ok(msg)) => debug!("Skipping unexpected frame of {} bytes", msg.len()),
+            Some(Err(e)) => return Err(errors::SubmitError::ConnectionError(e)),
+            None => {
+                return Err(errors::SubmitError::ProtocolViolation(format!(
+                    "Connection ended before the batcher answered the {}",
+                    description
+                )))
             }
         }
-    } else {
+    };
+    if let Err(e) = ws_write.close().await {
+        debug!("Error closing the connection to the batcher: {}", e);
+    }
+    Ok(answer)
+}
+
+/// Reads the handshake of the batcher, which is the first message it sends: a
+/// `ProtocolHandshake`, or the protocol version of batchers older than the handshake, which
+/// support none of the features. Returns `None` if it wasn't received. In strict mode, a missing
+/// or malformed handshake is an error.
+/// # Errors
+/// * `ProtocolMismatch` if the protocol version of the batcher is newer than the one of the SDK.
+pub(crate) async fn read_handshake(
+    ws_read: &mut SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    strict: bool,
+    transcript: &mut Transcript,
+) -> Result<Option<ProtocolHandshake>, errors::SubmitError> {
+    let Some(Ok(msg)) = ws_read.next().await else {
+        if strict {
+            return Err(errors::SubmitError::ProtocolViolation(
+                "Connection ended before the batcher sent its protocol version".to_string(),
+            ));
+        }
         error!("Batcher did not respond with the protocol version");
         return Ok(None);
+    };
+
+    transcript.record_frame(FrameDirection::Received, &msg);
+    let frame = describe_frame(&msg);
+    let handshake = match msg {
+        Message::Text(text) => serde_json::from_str::<ProtocolHandshake>(&text).ok(),
+        msg => msg
+            .into_data()
+            .try_into()
+            .ok()
+            .map(|data| ProtocolHandshake::without_features(u16::from_be_bytes(data))),
+    };
+
+    let Some(handshake) = handshake else {
+        if strict {
+            return Err(errors::SubmitError::ProtocolViolation(format!(
+                "Expected the protocol handshake or the protocol version as 2 bytes, received {}",
+                frame
+            )));
+        }
+        error!("Error while reading protocol version");
+        return Ok(None);
+    };
+
+    transcript.record_protocol_version(handshake.protocol_version);
+    if handshake.protocol_version > CURRENT_PROTOCOL_VERSION {
+        return Err(errors::SubmitError::ProtocolMismatch {
+            sdk_version: CURRENT_PROTOCOL_VERSION,
+            batcher_version: handshake.protocol_version,
+            missing_features: Vec::new(),
+        });
     }
+    debug!(
+        "Batcher protocol version: {}, features: {:?}",
+        handshake.protocol_version, handshake.features
+    );
+    Ok(Some(handshake))
+}
 
-    if verification_data.is_empty() {
+/// Connects to the batcher and returns the protocol version and features it negotiated, without
+/// submitting anything.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher.
+/// * `tls_config` - The root certificates and server name with which `wss://` addresses are
+///   connected.
+/// # Returns
+/// * The handshake of the batcher. Batchers older than the handshake support none of the features.
+/// # Errors
+/// * `ConnectionError` if there is an error connecting to the batcher.
+/// * `TlsError` if the TLS handshake with the batcher fails.
+/// * `Unauthorized` if the batcher refuses the access token of `tls_config`, or the lack of one.
+/// * `ProtocolMismatch` if the protocol version of the batcher is newer than the one of the SDK.
+/// * `ProtocolViolation` if the batcher doesn't send its handshake.
+pub async fn negotiate_protocol(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+) -> Result<ProtocolHandshake, errors::SubmitError> {
+    let ws_stream = connect(batcher_addr, tls_config).await?;
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+
+    let handshake = read_handshake(&mut ws_read, true, &mut Transcript::new()).await;
+    if let Err(e) = ws_write.close().await {
+        debug!("Error closing the connection to the batcher: {}", e);
+    }
+    handshake?.ok_or_else(|| {
+        errors::SubmitError::ProtocolViolation("Batcher did not send its handshake".to_string())
+    })
+}
+
+/// Submits proofs paid for by a sponsor to the batcher to be verified in Aligned. The messages
+/// are signed by the provers and sponsored with `SponsoredClientMessage::new`, and the batcher
+/// charges the balance of the sponsor of each message instead of its prover.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proofs will be submitted.
+/// * `sponsored_messages` - An array of the sponsored client message of each proof.
+/// # Returns
+/// * An array of aligned verification data obtained when submitting the proofs.
+/// # Errors
+/// * If there is an error connecting to the batcher.
+/// * If there is an error serializing the message.
+/// * If there is an error deserializing the message.
+pub async fn submit_sponsored(
+    batcher_addr: &str,
+    sponsored_messages: &[SponsoredClientMessage],
+) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError> {
+    if sponsored_messages.is_empty() {
         return Err(errors::SubmitError::MissingParameter(
-            "verification_data".to_string(),
+            "sponsored_messages".to_string(),
         ));
     }
-    let ws_write_clone = ws_write.clone();
-    // The sent verification data will be stored here so that we can calculate
-    // their commitments later.
-    let mut sent_verification_data: Vec<VerificationData> = Vec::new();
+    for sponsored_message in sponsored_messages {
+        sponsored_message
+            .client_message
+            .verification_data
+            .validate()?;
+    }
 
-    {
-        let mut ws_write = ws_write.lock().await;
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+    let (mut ws_write, mut ws_read) = ws_stream.split();
+    let mut transcript = Transcript::new();
+
+    let Some(handshake) = read_handshake(&mut ws_read, false, &mut transcript).await? else {
+        return Ok(None);
+    };
+
+    let mut verification_data_commitments_rev: Vec<VerificationDataCommitment> = sponsored_messages
+        .iter()
+        .map(|msg| msg.client_message.verification_data.clone().into())
+        .rev()
+        .collect();
+
+    let mut send_transcript = transcript.fork();
+    let send = async {
+        let mut chunk_sizer = ChunkSizer::default();
+        for sponsored_message in sponsored_messages {
+            let msg = WireMessage::Sponsored(sponsored_message.clone());
+            send_message(
+                &mut ws_write,
+                &msg,
+                &handshake,
+                None,
+                &mut send_transcript,
+                &mut chunk_sizer,
+            )
+            .await?;
+            debug!("Sponsored message sent...");
+        }
+        Ok(())
+    };
+
+    let mut progress = SubmissionProgress::default();
+    let receive = receive(
+        ws_read,
+        sponsored_messages.len(),
+        &mut verification_data_commitments_rev,
+        false,
+        &mut transcript,
+        &mut progress,
+    );
+    let result = run_submission(send, receive, None).await;
+    close_connection(&mut ws_write).await;
+    let all_responded = result?;
+
+    Ok(all_responded.then_some(progress.aligned_verification_data))
+}
+
+/// Submits a proof to the batcher to be verified in Aligned.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proof will be submitted.
+/// * `verification_data` - The verification data of the proof.
+/// * `wallet` - The wallet used to sign the proof.
+/// # Returns
+/// * The aligned verification data obtained when submitting the proof.
+/// # Errors
+/// * If there is an error connecting to the batcher.
+/// * If there is an error serializing the message.
+/// * If there is an error deserializing the message.
+pub async fn submit<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &VerificationData,
+    wallet: S,
+) -> Result<Option<AlignedVerificationData>, errors::SubmitError> {
+    let ws_stream = connect(batcher_addr, &TlsConfig::default()).await?;
+
+    debug!("WebSocket handshake has been successfully completed");
+
+    let verification_data = vec![verification_data.clone()];
+
+    let aligned_verification_data = _submit_multiple(
+        ws_stream,
+        &verification_data,
+        |message| sign_with_signer(&wallet, message),
+        false,
+        false,
+        &mut Transcript::new(),
+    )
+    .await?;
+
+    if let Some(mut aligned_verification_data) = aligned_verification_data {
+        Ok(aligned_verification_data.pop())
+    } else {
+        Ok(None)
+    }
+}
+
+/// Receives the responses of the batcher to the `total_messages` sent. Responses arrive in the
+/// order the messages were sent, so each one is matched with the next commitment of
+/// `verification_data_commitments_rev`. By default, frames that are not responses are skipped,
+/// and responses that can't be read or don't match their commitment are logged and dropped. In
+/// strict mode, any of them, or a connection ending before every message is responded, is
+/// returned as a `ProtocolViolation` error.
+async fn receive(
+    mut ws_read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    total_messages: usize,
+    verification_data_commitments_rev: &mut Vec<VerificationDataCommitment>,
+    strict: bool,
+    transcript: &mut Transcript,
+    progress: &mut SubmissionProgress<'_>,
+) -> Result<bool, errors::SubmitError> {
+    let mut num_responses = 0;
+
+    while let Some(msg) = ws_read.next().await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(e) if strict => return Err(errors::SubmitError::ConnectionError(e)),
+            Err(_) => break,
+        };
+        transcript.record_frame(FrameDirection::Received, &msg);
+
+        // The batcher closes the connection right after an error response, and its close frame
+        // only tells the code of the rejection
+        if let Message::Text(text) = &msg {
+            if let Ok(error_response) = serde_json::from_str::<ErrorResponse>(text) {
+                error!(
+                    "Message rejected by the batcher: {:?}",
+                    error_response.rejection
+                );
+                return Err(errors::SubmitError::MessageRejected(
+                    error_response.rejection,
+                ));
+            }
+        }
+
+        match msg {
+            Message::Close(Some(close_msg)) => {
+                error!("Connection was closed before receiving all messages. Reason: {}. Try submitting your proof again", close_msg.to_owned());
+                return Err(errors::SubmitError::BatcherRejection(
+                    close_msg.code.into(),
+                    close_msg.reason.as_bytes().to_vec(),
+                ));
+            }
+            Message::Close(None) => {
+                error!("Connection was closed before receiving all messages. Try submitting your proof again");
+                if strict {
+                    return Err(errors::SubmitError::ProtocolViolation(format!(
+                        "Connection closed without a reason after {} of {} responses",
+                        num_responses, total_messages
+                    )));
+                }
+                return Ok(false);
+            }
+            Message::Binary(data) => {
+                let position = progress.responded;
+                num_responses += 1;
+                progress.responded += 1;
+                let received_at = clock::now();
+                if let Some(timing) = progress.timings.get_mut(position) {
+                    timing.acknowledged_at = Some(received_at);
+                }
+
+                // Responses arrive in the order the messages were sent, so the commitment is
+                // taken even if the response can't be read to keep the following ones matched
+                let verification_data_commitment =
+                    verification_data_commitments_rev.pop().unwrap_or_default();
+
+                match serde_json::from_slice::<BatchInclusionData>(&data) {
+                    Ok(batch_inclusion_data) => {
+                        debug!("Received response from batcher");
+                        debug!(
+                            "Batch merkle root: {}",
+                            hex::encode(batch_inclusion_data.batch_merkle_root)
+                        );
+                        debug!("Index in batch: {}", batch_inclusion_data.index_in_batch);
+
+                        let matches_commitment =
+                            verify_response(&verification_data_commitment, &batch_inclusion_data);
+                        transcript.record_response(&batch_inclusion_data, matches_commitment);
+                        if matches_commitment {
+                            if let Some(timing) = progress.timings.get_mut(position) {
+                                timing.included_at = Some(received_at);
+                            }
+                            if let Some(on_event) = progress.on_event {
+                                on_event(SubmissionEvent::Accepted {
+                                    position,
+                                    batch_merkle_root: batch_inclusion_data.batch_merkle_root,
+                                    at: received_at,
+                                });
+                            }
+                            progress
+                                .aligned_verification_data
+                                .push(AlignedVerificationData::new(
+                                    &verification_data_commitment,
+                                    &batch_inclusion_data,
+                                ));
+                        } else if !strict {
+                            progress.unmatched.push(position);
+                        } else {
+                            return Err(errors::SubmitError::ProtocolViolation(
+                                mismatched_response_diagnostics(
+                                    num_responses,
+                                    total_messages,
+                                    verification_data_commitments_rev,
+                                    &batch_inclusion_data,
+                                ),
+                            ));
+                        }
+                    }
+                    Err(e) if strict => {
+                        return Err(errors::SubmitError::ProtocolViolation(format!(
+                            "Response {} of {} is not a batch inclusion data: {}",
+                            num_responses, total_messages, e
+                        )));
+                    }
+                    Err(e) => {
+                        error!("Error while deserializing batcher response: {}", e);
+                        progress.unmatched.push(position);
+                    }
+                }
+                if num_responses == total_messages {
+                    debug!("All messages responded");
+                    return Ok(true);
+                }
+            }
+            // Control frames are answered by the websocket library
+            Message::Ping(_) | Message::Pong(_) => {}
+            msg if strict => {
+                return Err(errors::SubmitError::ProtocolViolation(format!(
+                    "Unexpected {} after {} of {} responses",
+                    describe_frame(&msg),
+                    num_responses,
+                    total_messages
+                )));
+            }
+            msg => debug!("Skipping unexpected {}", describe_frame(&msg)),
+        }
+    }
+
+    if strict {
+        return Err(errors::SubmitError::ProtocolViolation(format!(
+            "Connection ended after {} of {} responses",
+            num_responses, total_messages
+        )));
+    }
+    Ok(false)
+}
+
+/// Describes the response that doesn't match the commitment of the message sent in its
+/// position, telling if it matches a message sent later, which means the responses are out of
+/// order.
+fn mismatched_response_diagnostics(
+    num_responses: usize,
+    total_messages: usize,
+    pending_commitments_rev: &[VerificationDataCommitment],
+    batch_inclusion_data: &BatchInclusionData,
+) -> String {
+    let later_position = pending_commitments_rev
+        .iter()
+        .position(|commitment| response_matches(commitment, batch_inclusion_data))
+        .map(|rev_index| total_messages - rev_index);
+
+    match later_position {
+        Some(position) => format!(
+            "Response {} of {} is for message {}, responses are out of order",
+            num_responses, total_messages, position
+        ),
+        None => format!(
+            "Response {} of {}, with merkle root 0x{} and index in batch {}, doesn't match any message sent",
+            num_responses,
+            total_messages,
+            hex::encode(batch_inclusion_data.batch_merkle_root),
+            batch_inclusion_data.index_in_batch
+        ),
+    }
+}
+
+fn describe_frame(msg: &Message) -> String {
+    let kind = match msg {
+        Message::Text(_) => "text",
+        Message::Binary(_) => "binary",
+        Message::Ping(_) => "ping",
+        Message::Pong(_) => "pong",
+        Message::Close(_) => "close",
+        Message::Frame(_) => "raw",
+    };
+    format!("{} frame of {} bytes", kind, msg.len())
+}
+
+pub(crate) fn verify_response(
+    verification_data_commitment: &VerificationDataCommitment,
+    batch_inclusion_data: &BatchInclusionData,
+) -> bool {
+    debug!("Verifying response data matches sent proof data ...");
+    if response_matches(verification_data_commitment, batch_inclusion_data) {
+        debug!("Done. Data sent matches batcher answer");
+        return true;
+    }
+
+    error!("Verification data commitments and batcher response with merkle root {} and index in batch {} don't match", hex::encode(batch_inclusion_data.batch_merkle_root), batch_inclusion_data.index_in_batch);
+    false
+}
+
+fn response_matches(
+    verification_data_commitment: &VerificationDataCommitment,
+    batch_inclusion_data: &BatchInclusionData,
+) -> bool {
+    let leaf = VerificationCommitmentBatch::hash_data(verification_data_commitment);
+    batch_inclusion_data.batch_version.verify_merkle_inclusion(
+        &batch_inclusion_data.batch_merkle_root,
+        &leaf,
+        &batch_inclusion_data.batch_inclusion_proof.merkle_path,
+        batch_inclusion_data.index_in_batch,
+    )
+}
+
+/// Checks locally, without an RPC node, that the proof is included in the batch with the given
+/// merkle root. The leaf is recomputed from the verification data commitment and its merkle path
+/// is walked up to the root with the same Keccak hashing as the batcher and the Aligned service
+/// manager contract. It doesn't check that the batch has been verified, which only the contract
+/// knows, so the root should come from a trusted source, such as a `NewBatch` event.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proofs.
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// # Returns
+/// * Whether the proof is included in the batch with that root.
+pub fn verify_merkle_inclusion(
+    aligned_verification_data: &AlignedVerificationData,
+    batch_merkle_root: [u8; 32],
+) -> bool {
+    verify_merkle_inclusion_with_backend::<VerificationCommitmentBatch>(
+        aligned_verification_data,
+        batch_merkle_root,
+    )
+}
+
+/// Checks locally that the proof is included in the batch with the given merkle root, as
+/// `verify_merkle_inclusion` does, for batches of deployments whose trees are built with another
+/// backend, such as `Sha256CommitmentBatch`.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proofs.
+/// * `batch_merkle_root` - The merkle root of the batch.
+/// # Returns
+/// * Whether the proof is included in the batch with that root.
+pub fn verify_merkle_inclusion_with_backend<B: BatchMerkleTreeBackend>(
+    aligned_verification_data: &AlignedVerificationData,
+    batch_merkle_root: [u8; 32],
+) -> bool {
+    let leaf = B::hash_data(&aligned_verification_data.verification_data_commitment);
+    aligned_verification_data
+        .batch_version
+        .verify_merkle_inclusion_with_backend::<B>(
+            &batch_merkle_root,
+            &leaf,
+            &aligned_verification_data.batch_inclusion_proof.merkle_path,
+            aligned_verification_data.index_in_batch,
+        )
+}
+
+/// Checks if the proof has been verified with Aligned and is included in the batch.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proofs.
+/// * `chain` - The chain on which the verification will be done.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * A boolean indicating whether the proof was verified on-chain and is included in the batch.
+/// # Errors
+/// * If there is an error creating the service manager.
+/// * If there is an error calling the service manager.
+/// * If there is an error verifying the proof on-chain.
+/// * If the call to the service manager reverts, with its decoded revert reason.
+/// * If the service manager is not deployed on the network of the RPC node.
+/// * If the proof is in a batch whose version can't be verified on-chain.
+pub async fn verify_proof_onchain(
+    aligned_verification_data: AlignedVerificationData,
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<bool, errors::VerificationError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+    _verify_proof_onchain(aligned_verification_data, chain, &rpc_client).await
+}
+
+/// Checks if the proof has been verified with Aligned and is included in the batch, and if it
+/// is, fetches the attestation with which the operators verified the batch.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proofs.
+/// * `chain` - The chain on which the verification will be done.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * Whether the proof was verified on-chain, and the attestation of its batch if it was.
+/// # Errors
+/// * The same as `verify_proof_onchain`.
+/// * If there is an error fetching the attestation of the batch.
+pub async fn verify_proof_onchain_with_attestation(
+    aligned_verification_data: AlignedVerificationData,
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<OnchainVerification, errors::VerificationError> {
+    let batch_merkle_root = aligned_verification_data.batch_merkle_root;
+
+    let verified =
+        verify_proof_onchain(aligned_verification_data, chain.clone(), eth_rpc_url).await?;
+    if !verified {
+        return Ok(OnchainVerification {
+            verified,
+            attestation: None,
+        });
+    }
+
+    let attestation = get_batch_attestation(batch_merkle_root, chain, eth_rpc_url).await?;
+
+    Ok(OnchainVerification {
+        verified,
+        attestation,
+    })
+}
+
+/// Interval at which `submit_and_wait_verification` checks if the batches of the proofs were
+/// verified, and maximum time it waits for all of them.
+#[derive(Debug, Clone)]
+pub struct VerificationWaitConfig {
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for VerificationWaitConfig {
+    fn default() -> Self {
+        VerificationWaitConfig {
+            poll_interval: Duration::from_secs(12),
+            timeout: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// A proof verified on-chain, with the transaction in which its batch was verified.
+#[derive(Clone)]
+pub struct VerifiedProof {
+    pub aligned_verification_data: AlignedVerificationData,
+    pub verification_tx_hash: H256,
+}
+
+/// Submits multiple proofs to the batcher like `submit_multiple`, and waits until the batches
+/// in which they were included are verified on-chain.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher to which the proofs will be submitted.
+/// * `verification_data` - An array of verification data of each proof.
+/// * `wallet` - The wallet used to sign the proofs.
+/// * `chain` - The chain on which the proofs are verified.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// * `wait_config` - The interval at which the batches are checked and the maximum time waited.
+/// # Returns
+/// * The aligned verification data of each proof, with the hash of the transaction in which its
+///   batch was verified. `None` if the batcher didn't respond to every proof.
+/// # Errors
+/// * The same as `submit_multiple`.
+/// * `Timeout` if the batches are not verified within the timeout.
+/// * The same as `verify_proof_onchain`, which is checked for each proof once its batch is
+///   verified.
+pub async fn submit_and_wait_verification<S: Signer>(
+    batcher_addr: &str,
+    verification_data: &[VerificationData],
+    wallet: S,
+    chain: Chain,
+    eth_rpc_url: &str,
+    wait_config: &VerificationWaitConfig,
+) -> Result<Option<Vec<VerifiedProof>>, errors::AlignedError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+
+    let Some(aligned_verification_data) =
+        submit_multiple(batcher_addr, verification_data, wallet).await?
+    else {
+        return Ok(None);
+    };
+
+    let verified_proofs = wait_verification(
+        aligned_verification_data,
+        chain,
+        &rpc_client,
+        wait_config,
+        None,
+    )
+    .await?;
+    Ok(Some(verified_proofs))
+}
+
+/// Waits until the batch of a proof already submitted is verified on-chain, checking the service
+/// manager at each poll interval, and checks that the proof is included in it. It returns
+/// as soon as the batch is verified, so it can gate a deployment on the settlement of a proof.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proof.
+/// * `chain` - The chain on which the proof is verified.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// * `wait_config` - The interval at which the batch is checked and the maximum time waited.
+/// # Returns
+/// * The proof, with the hash of the transaction in which its batch was verified.
+/// # Errors
+/// * `Timeout` if the batch is not verified within the timeout.
+/// * `UnsupportedBatchVersion` if the proof is in a batch whose version can't be verified on-chain.
+/// * The same as `verify_proof_onchain`, which is checked once the batch is verified. The proof
+///   not being included in the verified batch is an `EthError`.
+pub async fn wait_proof_verification(
+    aligned_verification_data: AlignedVerificationData,
+    chain: Chain,
+    eth_rpc_url: &str,
+    wait_config: &VerificationWaitConfig,
+) -> Result<VerifiedProof, errors::AlignedError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+
+    let verified_proofs = wait_verification(
+        vec![aligned_verification_data],
+        chain,
+        &rpc_client,
+        wait_config,
+        None,
+    )
+    .await?;
+    Ok(verified_proofs
+        .into_iter()
+        .next()
+        .expect("A verified proof is returned for each proof"))
+}
+
+/// Waits until the batches of the proofs are verified on-chain and checks that the proofs are
+/// included in them, reporting to `on_event` when each batch is seen posted and verified and
+/// each proof is checked.
+pub(crate) async fn wait_verification(
+    aligned_verification_data: Vec<AlignedVerificationData>,
+    chain: Chain,
+    rpc_client: &AlignedRpcClient,
+    wait_config: &VerificationWaitConfig,
+    on_event: Option<&(dyn Fn(SubmissionEvent) + Sync)>,
+) -> Result<Vec<VerifiedProof>, errors::AlignedError> {
+    // Fails before waiting if a proof could never be checked on-chain
+    for aligned_verification_data in &aligned_verification_data {
+        check_batch_version_is_verifiable_onchain(aligned_verification_data)?;
+    }
+
+    let wait = async {
+        let mut verification_tx_hashes: HashMap<[u8; 32], H256> = HashMap::new();
+        let mut verified_proofs = Vec::with_capacity(aligned_verification_data.len());
+
+        for (position, aligned_verification_data) in
+            aligned_verification_data.into_iter().enumerate()
+        {
+            let batch_merkle_root = aligned_verification_data.batch_merkle_root;
+            let verification_tx_hash = match verification_tx_hashes.get(&batch_merkle_root) {
+                Some(verification_tx_hash) => *verification_tx_hash,
+                None => {
+                    let verification_tx_hash = wait_batch_verification(
+                        rpc_client,
+                        &chain,
+                        batch_merkle_root,
+                        wait_config.poll_interval,
+                        on_event,
+                    )
+                    .await?;
+                    verification_tx_hashes.insert(batch_merkle_root, verification_tx_hash);
+                    verification_tx_hash
+                }
+            };
+
+            let verified =
+                _verify_proof_onchain(aligned_verification_data.clone(), chain.clone(), rpc_client)
+                    .await?;
+            if !verified {
+                return Err(errors::VerificationError::EthError(format!(
+                    "Batch 0x{} was verified but the proof is not included in it",
+                    hex::encode(batch_merkle_root)
+                )));
+            }
+
+            if let Some(on_event) = on_event {
+                on_event(SubmissionEvent::Verified {
+                    position,
+                    batch_merkle_root,
+                    verification_tx_hash,
+                    at: clock::now(),
+                });
+            }
+            verified_proofs.push(VerifiedProof {
+                aligned_verification_data,
+                verification_tx_hash,
+            });
+        }
+
+        Ok(verified_proofs)
+    };
+
+    let verified_proofs = tokio::time::timeout(wait_config.timeout, wait)
+        .await
+        .map_err(|_| {
+            errors::SubmitError::Timeout(format!(
+                "Batches were not verified within {:?}",
+                wait_config.timeout
+            ))
+        })??;
+    Ok(verified_proofs)
+}
 
-        for verification_data in verification_data.iter() {
-            let msg = ClientMessage::new(verification_data.clone(), wallet.clone()).await;
-            let msg_str = serde_json::to_string(&msg).map_err(errors::SubmitError::SerdeError)?;
-            ws_write
-                .send(Message::Text(msg_str.clone()))
-                .await
-                .map_err(errors::SubmitError::ConnectionError)?;
-            sent_verification_data.push(verification_data.clone());
-            debug!("Message sent...");
+/// Polls the service manager until the batch is responded, and returns the hash of the
+/// transaction that verified it. The batch is reported as `BatchPosted` the first time its task
+/// is seen created, and as `BatchVerified` once the transaction is found.
+async fn wait_batch_verification(
+    rpc_client: &AlignedRpcClient,
+    chain: &Chain,
+    batch_merkle_root: [u8; 32],
+    poll_interval: Duration,
+    on_event: Option<&(dyn Fn(SubmissionEvent) + Sync)>,
+) -> Result<H256, errors::VerificationError> {
+    let mut posted = false;
+    loop {
+        let (task_created_block, responded) =
+            rpc_client.batch_state(batch_merkle_root, chain).await?;
+
+        // The block is zero until the task of the batch is created
+        if task_created_block != 0 && !posted {
+            posted = true;
+            if let Some(on_event) = on_event {
+                on_event(SubmissionEvent::BatchPosted {
+                    batch_merkle_root,
+                    task_created_block,
+                    at: clock::now(),
+                });
+            }
         }
-    }
 
-    let num_responses = Arc::new(Mutex::new(0));
+        if responded {
+            let verified_events = rpc_client
+                .batch_verified_events(batch_merkle_root, task_created_block, chain)
+                .await?;
+            if let Some((_, log_meta)) = verified_events.into_iter().next() {
+                if let Some(on_event) = on_event {
+                    on_event(SubmissionEvent::BatchVerified {
+                        batch_merkle_root,
+                        verification_tx_hash: log_meta.transaction_hash,
+                        at: clock::now(),
+                    });
+                }
+                return Ok(log_meta.transaction_hash);
+            }
+        }
 
-    // This vector is reversed so that when responses are received, the commitments corresponding
-    // to that response can simply be popped of this vector.
-    let mut verification_data_commitments_rev: Vec<VerificationDataCommitment> =
-        sent_verification_data
-            .into_iter()
-            .map(|vd| vd.into())
-            .rev()
-            .collect();
+        debug!(
+            "Batch 0x{} not verified yet, checking again in {:?}",
+            hex::encode(batch_merkle_root),
+            poll_interval
+        );
+        tokio::time::sleep(poll_interval).await;
+    }
+}
 
-    let aligned_verification_data = receive(
-        ws_read,
-        ws_write_clone,
-        verification_data.len(),
-        num_responses,
-        &mut verification_data_commitments_rev,
-    )
-    .await?;
+pub(crate) async fn _verify_proof_onchain(
+    aligned_verification_data: AlignedVerificationData,
+    chain: Chain,
+    rpc_client: &AlignedRpcClient,
+) -> Result<bool, errors::VerificationError> {
+    check_batch_version_is_verifiable_onchain(&aligned_verification_data)?;
 
-    Ok(aligned_verification_data)
+    rpc_client
+        .verify_batch_inclusion(&aligned_verification_data, &chain)
+        .await
 }
 
-/// Submits a proof to the batcher to be verified in Aligned.
+/// Checks on-chain that a proof with the given public input has been verified with Aligned,
+/// through an `AlignedPubInputOpener` contract. The contract recomputes the public input
+/// commitment, so the public input can be trusted as the outputs of the proven program.
 /// # Arguments
-/// * `batcher_addr` - The address of the batcher to which the proof will be submitted.
-/// * `verification_data` - The verification data of the proof.
-/// * `wallet` - The wallet used to sign the proof.
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proof.
+/// * `pub_input` - The full public input of the proof.
+/// * `pub_input_opener_address` - The address of the `AlignedPubInputOpener` contract.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
 /// # Returns
-/// * The aligned verification data obtained when submitting the proof.
+/// * A boolean indicating whether the proof was verified on-chain with the given public input.
 /// # Errors
-/// * If there is an error connecting to the batcher.
-/// * If there is an error serializing the message.
-/// * If there is an error deserializing the message.
-pub async fn submit(
-    batcher_addr: &str,
-    verification_data: &VerificationData,
-    wallet: Wallet<SigningKey>,
-) -> Result<Option<AlignedVerificationData>, errors::SubmitError> {
-    let (ws_stream, _) = connect_async(batcher_addr)
-        .await
-        .map_err(errors::SubmitError::ConnectionError)?;
+/// * `EthError` if there is an error verifying the proof on-chain.
+/// * `CallReverted` if the call to the contract reverts, with its decoded revert reason.
+/// * `ContractNotDeployed` if there is no contract at the address on the network of the RPC node.
+/// * `InvalidAddress` if the address of the contract is not valid.
+/// * `UnsupportedBatchVersion` if the proof is in a batch whose version can't be verified on-chain.
+pub async fn verify_pub_input_opening_onchain(
+    aligned_verification_data: &AlignedVerificationData,
+    pub_input: &[u8],
+    pub_input_opener_address: &str,
+    eth_rpc_url: &str,
+) -> Result<bool, errors::VerificationError> {
+    check_batch_version_is_verifiable_onchain(aligned_verification_data)?;
 
-    debug!("WebSocket handshake has been successfully completed");
-    let (ws_write, ws_read) = ws_stream.split();
+    let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| errors::VerificationError::EthError(e.to_string()))?;
 
-    let ws_write = Arc::new(Mutex::new(ws_write));
+    let pub_input_opener =
+        eth::aligned_pub_input_opener(eth_rpc_provider, pub_input_opener_address).await?;
 
-    let verification_data = vec![verification_data.clone()];
+    let verification_data_comm = &aligned_verification_data.verification_data_commitment;
 
-    let aligned_verification_data =
-        _submit_multiple(ws_write, ws_read, &verification_data, wallet).await?;
+    let result = pub_input_opener
+        .verify_batch_inclusion_with_pub_input(
+            verification_data_comm.proof_commitment,
+            pub_input.to_vec().into(),
+            verification_data_comm.proving_system_aux_data_commitment,
+            verification_data_comm.proof_generator_addr,
+            aligned_verification_data.batch_merkle_root,
+            get_merkle_proof_bytes(aligned_verification_data).into(),
+            aligned_verification_data.index_in_batch.into(),
+        )
+        .call()
+        .await;
 
-    if let Some(mut aligned_verification_data) = aligned_verification_data {
-        Ok(aligned_verification_data.pop())
-    } else {
-        Ok(None)
+    match result {
+        Ok(result) => Ok(result),
+        Err(e) => {
+            Err(eth::call_error(e, pub_input_opener.client_ref(), pub_input_opener.address()).await)
+        }
     }
 }
 
-async fn receive(
-    ws_read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-    ws_write: Arc<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>,
-    total_messages: usize,
-    num_responses: Arc<Mutex<usize>>,
-    verification_data_commitments_rev: &mut Vec<VerificationDataCommitment>,
-) -> Result<Option<Vec<AlignedVerificationData>>, errors::SubmitError> {
-    // Responses are filtered to only admit binary or close messages.
-    let mut response_stream =
-        ws_read.try_filter(|msg| future::ready(msg.is_binary() || msg.is_close()));
-
-    let mut aligned_verification_data: Vec<AlignedVerificationData> = Vec::new();
+/// Returns the calldata of a `verifyBatchInclusionWithPubInput` call to the
+/// `AlignedPubInputOpener` contract, to check that a proof with the given public input has been
+/// verified with Aligned, for instance from a transaction built by another tool.
+/// The call returns false if the public input doesn't match the commitment of the proof.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proof.
+/// * `pub_input` - The full public input of the proof.
+/// # Returns
+/// * The ABI encoded calldata, including the function selector.
+/// # Errors
+/// * None.
+pub fn get_pub_input_opening_calldata(
+    aligned_verification_data: &AlignedVerificationData,
+    pub_input: &[u8],
+) -> Vec<u8> {
+    let verification_data_comm = &aligned_verification_data.verification_data_commitment;
+
+    eth::VerifyBatchInclusionWithPubInputCall {
+        proof_commitment: verification_data_comm.proof_commitment,
+        pub_input: pub_input.to_vec().into(),
+        proving_system_aux_data_commitment: verification_data_comm
+            .proving_system_aux_data_commitment,
+        proof_generator_addr: verification_data_comm.proof_generator_addr,
+        batch_merkle_root: aligned_verification_data.batch_merkle_root,
+        merkle_proof: get_merkle_proof_bytes(aligned_verification_data).into(),
+        verification_data_batch_index: aligned_verification_data.index_in_batch.into(),
+    }
+    .encode()
+}
 
-    while let Some(Ok(msg)) = response_stream.next().await {
-        if let Message::Close(close_frame) = msg {
-            if let Some(close_msg) = close_frame {
-                error!("Connection was closed before receiving all messages. Reason: {}. Try submitting your proof again", close_msg.to_owned());
-                ws_write.lock().await.close().await?;
-                return Ok(None);
-            }
-            error!("Connection was closed before receiving all messages. Try submitting your proof again");
-            ws_write.lock().await.close().await?;
-            return Ok(None);
-        } else {
-            let mut num_responses_lock = num_responses.lock().await;
-            *num_responses_lock += 1;
-
-            let data = msg.into_data();
-            match serde_json::from_slice::<BatchInclusionData>(&data) {
-                Ok(batch_inclusion_data) => {
-                    debug!("Received response from batcher");
-                    debug!(
-                        "Batch merkle root: {}",
-                        hex::encode(batch_inclusion_data.batch_merkle_root)
-                    );
-                    debug!("Index in batch: {}", batch_inclusion_data.index_in_batch);
-
-                    let verification_data_commitment =
-                        verification_data_commitments_rev.pop().unwrap_or_default();
-
-                    if verify_response(&verification_data_commitment, &batch_inclusion_data) {
-                        aligned_verification_data.push(AlignedVerificationData::new(
-                            &verification_data_commitment,
-                            &batch_inclusion_data,
-                        ));
-                    }
-                }
-                Err(e) => {
-                    error!("Error while deserializing batcher response: {}", e);
-                }
-            }
-            if *num_responses_lock == total_messages {
-                debug!("All messages responded. Closing connection...");
-                ws_write.lock().await.close().await?;
-                return Ok(Some(aligned_verification_data));
-            }
-        }
+/// Returns the calldata of a `verifyBatchInclusion` call to the Aligned service manager
+/// contract, to check that a proof has been verified with Aligned from a contract, a script or
+/// a transaction built by another tool.
+/// # Arguments
+/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proof.
+/// # Returns
+/// * The ABI encoded calldata, including the function selector.
+/// # Errors
+/// * `UnsupportedBatchVersion` if the batch of the proof can't be verified on-chain.
+pub fn get_verify_batch_inclusion_calldata(
+    aligned_verification_data: &AlignedVerificationData,
+) -> Result<Vec<u8>, errors::VerificationError> {
+    check_batch_version_is_verifiable_onchain(aligned_verification_data)?;
+    let verification_data_comm = &aligned_verification_data.verification_data_commitment;
+
+    Ok(eth::VerifyBatchInclusionCall {
+        proof_commitment: verification_data_comm.proof_commitment,
+        pub_input_commitment: verification_data_comm.pub_input_commitment,
+        proving_system_aux_data_commitment: verification_data_comm
+            .proving_system_aux_data_commitment,
+        proof_generator_addr: verification_data_comm.proof_generator_addr,
+        batch_merkle_root: aligned_verification_data.batch_merkle_root,
+        merkle_proof: get_merkle_proof_bytes(aligned_verification_data).into(),
+        verification_data_batch_index: aligned_verification_data.index_in_batch.into(),
     }
+    .encode())
+}
 
-    Ok(None)
+// The service manager contract only verifies paths of binary merkle trees
+pub(crate) fn check_batch_version_is_verifiable_onchain(
+    aligned_verification_data: &AlignedVerificationData,
+) -> Result<(), errors::VerificationError> {
+    if !aligned_verification_data.batch_version.is_v1() {
+        return Err(errors::VerificationError::UnsupportedBatchVersion(
+            aligned_verification_data.batch_version,
+        ));
+    }
+    Ok(())
 }
 
-fn verify_response(
-    verification_data_commitment: &VerificationDataCommitment,
-    batch_inclusion_data: &BatchInclusionData,
-) -> bool {
-    debug!("Verifying response data matches sent proof data ...");
-    let batch_inclusion_proof = batch_inclusion_data.batch_inclusion_proof.clone();
+// All the elements from the merkle proof have to be concatenated
+pub(crate) fn get_merkle_proof_bytes(
+    aligned_verification_data: &AlignedVerificationData,
+) -> Vec<u8> {
+    aligned_verification_data
+        .batch_inclusion_proof
+        .merkle_path
+        .iter()
+        .flatten()
+        .copied()
+        .collect()
+}
 
-    if batch_inclusion_proof.verify::<VerificationCommitmentBatch>(
-        &batch_inclusion_data.batch_merkle_root,
-        batch_inclusion_data.index_in_batch,
-        verification_data_commitment,
-    ) {
-        debug!("Done. Data sent matches batcher answer");
-        return true;
-    }
+/// Returns the address of the Aligned service manager contract deployed on the given chain.
+/// # Arguments
+/// * `chain` - The chain on which the contract is deployed.
+/// # Returns
+/// * The address of the contract as an hex string. For `Chain::Custom`, the address among its
+///   endpoints.
+pub fn get_aligned_service_manager_address(chain: &Chain) -> &str {
+    chain.aligned_service_manager()
+}
 
-    error!("Verification data commitments and batcher response with merkle root {} and index in batch {} don't match", hex::encode(batch_inclusion_data.batch_merkle_root), batch_inclusion_data.index_in_batch);
-    false
+/// Returns the commitment for a given input. Input can be verification key, public input, etc.
+/// # Arguments
+/// * `content` - The content for which the commitment will be calculated.
+/// # Returns
+/// * The commitment.
+/// # Errors
+/// * None.
+pub fn get_commitment(content: &[u8]) -> [u8; 32] {
+    get_commitment_with_hash(content, HashFunction::Keccak256)
 }
 
-/// Checks if the proof has been verified with Aligned and is included in the batch.
+/// Returns the commitment for a given input as `get_commitment`, computed with the given hash
+/// function. Only Keccak-256 commitments are the ones Aligned checks on-chain.
 /// # Arguments
-/// * `aligned_verification_data` - The aligned verification data obtained when submitting the proofs.
-/// * `chain` - The chain on which the verification will be done.
-/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// * `content` - The content for which the commitment will be calculated.
+/// * `hash_function` - The hash function of the commitment.
 /// # Returns
-/// * A boolean indicating whether the proof was verified on-chain and is included in the batch.
+/// * The commitment.
 /// # Errors
-/// * If there is an error creating the service manager.
-/// * If there is an error calling the service manager.
-/// * If there is an error verifying the proof on-chain.
-pub async fn verify_proof_onchain(
-    aligned_verification_data: AlignedVerificationData,
-    chain: Chain,
-    eth_rpc_url: &str,
-) -> Result<bool, errors::VerificationError> {
-    let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url)
-        .map_err(|e: url::ParseError| errors::VerificationError::EthError(e.to_string()))?;
-    _verify_proof_onchain(aligned_verification_data, chain, eth_rpc_provider).await
+/// * None.
+pub fn get_commitment_with_hash(content: &[u8], hash_function: HashFunction) -> [u8; 32] {
+    match hash_function {
+        HashFunction::Keccak256 => Keccak256::digest(content).into(),
+        HashFunction::Sha256 => sha2::Sha256::digest(content).into(),
+        HashFunction::Blake3 => blake3::hash(content).into(),
+    }
 }
 
-async fn _verify_proof_onchain(
-    aligned_verification_data: AlignedVerificationData,
-    chain: Chain,
-    eth_rpc_provider: Provider<Http>,
-) -> Result<bool, errors::VerificationError> {
-    let contract_address = match chain {
-        Chain::Devnet => "0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8",
-        Chain::Holesky => "0x58F280BeBE9B34c9939C3C39e0890C81f163B623",
-    };
+/// Extracts the public values committed by an SP1 program from its proof, so they can be used as
+/// the `pub_input` of the verification data and be bound to the proof on-chain.
+/// The proof is the bincode serialized `SP1CompressedProof` sent to Aligned. With the `sp1`
+/// feature, it's deserialized with the SP1 SDK. Otherwise it's parsed from its end, which is the
+/// public values followed by the SP1 version, both serialized as length prefixed byte vectors, and
+/// the version has to be `SP1_VERSION`. The extracted bytes are the same as
+/// `proof.public_values.to_vec()`, the batcher and operators reject proofs whose public values
+/// don't match the public input.
+/// # Arguments
+/// * `proof` - The bincode serialized SP1 proof.
+/// # Returns
+/// * The public values of the proof.
+/// # Errors
+/// * `GenericError` if the proof is not an SP1 proof of `SP1_VERSION`.
+pub fn get_sp1_public_values(proof: &[u8]) -> Result<Vec<u8>, errors::SubmitError> {
+    sp1_public_values(proof).ok_or(errors::SubmitError::GenericError(format!(
+        "Public values not found in SP1 proof, proofs of SP1 {} are expected",
+        SP1_VERSION
+    )))
+}
 
-    // All the elements from the merkle proof have to be concatenated
-    let merkle_proof: Vec<u8> = aligned_verification_data
-        .batch_inclusion_proof
-        .merkle_path
-        .into_iter()
-        .flatten()
-        .collect();
+/// Version of SP1 of the proofs verified by the operators, which is the one of the SP1 SDK of the
+/// `sp1` feature. Serialized SP1 proofs end with it.
+pub const SP1_VERSION: &str = "v1.0.8-testnet";
 
-    let verification_data_comm = aligned_verification_data.verification_data_commitment;
+#[cfg(feature = "sp1")]
+fn sp1_public_values(proof: &[u8]) -> Option<Vec<u8>> {
+    let proof: sp1_sdk::SP1CompressedProof = bincode::deserialize(proof).ok()?;
+    (proof.sp1_version == SP1_VERSION).then(|| proof.public_values.to_vec())
+}
 
-    let service_manager = eth::aligned_service_manager(eth_rpc_provider, contract_address).await?;
+#[cfg(not(feature = "sp1"))]
+fn sp1_public_values(proof: &[u8]) -> Option<Vec<u8>> {
+    let public_values_end = proof.len().checked_sub(8 + SP1_VERSION.len())?;
+    let (rest, sp1_version) = proof.split_at(public_values_end);
+    let (len_prefix, sp1_version) = sp1_version.split_at(8);
+    if len_prefix != (SP1_VERSION.len() as u64).to_le_bytes()
+        || sp1_version != SP1_VERSION.as_bytes()
+    {
+        return None;
+    }
+    get_trailing_byte_vector(rest).map(<[u8]>::to_vec)
+}
 
-    let call = service_manager.verify_batch_inclusion(
-        verification_data_comm.proof_commitment,
-        verification_data_comm.pub_input_commitment,
-        verification_data_comm.proving_system_aux_data_commitment,
-        verification_data_comm.proof_generator_addr,
-        aligned_verification_data.batch_merkle_root,
-        merkle_proof.into(),
-        aligned_verification_data.index_in_batch.into(),
-    );
+/// Extracts the journal of a Risc0 receipt, which is the public input of Risc0 proofs in Aligned.
+/// The journal holds the outputs committed by the guest program with `env::commit`, encoded with
+/// the Risc0 serde format: every value is written as little endian 32-bit words. They are the same
+/// bytes as `receipt.journal.bytes`, and can be decoded with `receipt.journal.decode()`.
+/// With the `risc0` feature, the receipt is deserialized with the Risc0 zkVM. Otherwise it's
+/// parsed from its end, which is the journal, serialized as a length prefixed byte vector,
+/// followed by the metadata of the receipt, as the receipts of the version of Risc0 verified by
+/// the operators are serialized.
+/// # Arguments
+/// * `receipt` - The bincode serialized Risc0 receipt.
+/// # Returns
+/// * The journal of the receipt.
+/// # Errors
+/// * `GenericError` if the receipt is not a Risc0 receipt.
+pub fn get_risc0_journal(receipt: &[u8]) -> Result<Vec<u8>, errors::SubmitError> {
+    risc0_journal(receipt).ok_or(errors::SubmitError::GenericError(
+        "Journal not found in Risc0 receipt".to_string(),
+    ))
+}
 
-    let result = call
-        .await
-        .map_err(|e| errors::VerificationError::EthError(e.to_string()))?;
+#[cfg(feature = "risc0")]
+fn risc0_journal(receipt: &[u8]) -> Option<Vec<u8>> {
+    let receipt: risc0_zkvm::Receipt = bincode::deserialize(receipt).ok()?;
+    Some(receipt.journal.bytes)
+}
 
-    Ok(result)
+#[cfg(not(feature = "risc0"))]
+fn risc0_journal(receipt: &[u8]) -> Option<Vec<u8>> {
+    let journal_end = receipt.len().checked_sub(RISC0_RECEIPT_METADATA_SIZE)?;
+    get_trailing_byte_vector(&receipt[..journal_end]).map(<[u8]>::to_vec)
 }
 
-/// Returns the commitment for a given input. Input can be verification key, public input, etc.
+/// Returns the `pub_input_commitment` that a Risc0 proof with the given journal has on-chain, so
+/// contracts can check the outputs of the guest program and not only the validity of the proof.
 /// # Arguments
-/// * `content` - The content for which the commitment will be calculated.
+/// * `journal` - The journal of the receipt.
 /// # Returns
-/// * The commitment.
+/// * The public input commitment.
 /// # Errors
 /// * None.
-pub fn get_commitment(content: &[u8]) -> [u8; 32] {
-    let mut hasher = Keccak256::new();
-    hasher.update(content);
-    hasher.finalize().into()
+pub fn get_risc0_pub_input_commitment(journal: &[u8]) -> [u8; 32] {
+    get_commitment(journal)
+}
+
+/// Size of the metadata that serialized receipts end with: the digest of the verifier parameters,
+/// as 8 words.
+#[cfg(not(feature = "risc0"))]
+const RISC0_RECEIPT_METADATA_SIZE: usize = 32;
+
+/// Returns the byte vector that a bincode serialized value ends with. The length prefix of the
+/// vector precedes it, so its position depends on the length being read, and every length that
+/// fits is a candidate. The longest one is taken, otherwise a vector ending with another
+/// serialized vector would be mistaken for the vector it ends with.
+#[cfg(not(all(feature = "sp1", feature = "risc0")))]
+fn get_trailing_byte_vector(bytes: &[u8]) -> Option<&[u8]> {
+    (8..=bytes.len())
+        .find(|&start| {
+            let len_prefix: [u8; 8] = bytes[start - 8..start].try_into().unwrap_or_default();
+            u64::from_le_bytes(len_prefix) == (bytes.len() - start) as u64
+        })
+        .map(|start| &bytes[start..])
 }
 
 #[cfg(test)]
@@ -333,7 +2176,208 @@ mod test {
     use std::str::FromStr;
     use tokio::time::sleep;
 
-    use ethers::signers::LocalWallet;
+    use ethers::signers::{LocalWallet, Signer};
+
+    #[tokio::test]
+    async fn test_client_messages_signed_in_parallel_keep_their_order() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let wallet = LocalWallet::from_str(
+            "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80",
+        )
+        .unwrap();
+        let verification_data: Vec<VerificationData> = (0..8u8)
+            .map(|i| VerificationData {
+                proving_system: ProvingSystemId::SP1,
+                proof: vec![i],
+                pub_input: None,
+                verification_key: None,
+                vm_program_code: Some(vec![i, i]),
+                proof_generator_addr: wallet.address(),
+                tag: None,
+                metadata: None,
+                schema_version: VerificationData::SCHEMA_VERSION,
+            })
+            .collect();
+
+        // The first messages take the longest to sign, so signatures finish in reverse order
+        let calls = AtomicU64::new(0);
+        let sign_message = |message: [u8; 32]| {
+            let delay = 8 - calls.fetch_add(1, Ordering::SeqCst);
+            let wallet = &wallet;
+            async move {
+                sleep(Duration::from_millis(delay * 10)).await;
+                sign_with_signer(wallet, message).await
+            }
+        };
+
+        let client_messages: Vec<ClientMessage> =
+            sign_client_messages(&verification_data, &sign_message, 4, None)
+                .map(|client_message| client_message.unwrap())
+                .collect()
+                .await;
+
+        assert_eq!(client_messages.len(), verification_data.len());
+        for (client_message, verification_data) in client_messages.iter().zip(&verification_data) {
+            assert_eq!(
+                client_message.verification_data.proof,
+                verification_data.proof
+            );
+            let expected = ClientMessage::new(verification_data.clone(), &wallet)
+                .await
+                .unwrap();
+            assert_eq!(client_message.signature, expected.signature);
+        }
+    }
+
+    #[test]
+    fn test_get_sp1_public_values() {
+        let base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let proof = read_file(base_dir.join("test_files/sp1/sp1_fibonacci.proof")).unwrap();
+
+        // n = 500, followed by the last two fibonacci numbers modulo 7919
+        let mut expected = Vec::new();
+        for value in [500u32, 1268, 1926] {
+            expected.extend_from_slice(&value.to_le_bytes());
+        }
+
+        assert_eq!(get_sp1_public_values(&proof).unwrap(), expected);
+    }
+
+    // Proofs are deserialized with the SP1 SDK with the `sp1` feature, so they have to be real ones
+    #[cfg(not(feature = "sp1"))]
+    #[test]
+    fn test_get_sp1_public_values_ending_in_serialized_vector() {
+        // The public values are a vector of 4 bytes serialized by the program
+        let public_values = [4, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4];
+        let mut proof = vec![7; 100];
+        proof.extend_from_slice(&(public_values.len() as u64).to_le_bytes());
+        proof.extend_from_slice(&public_values);
+        proof.extend_from_slice(&(b"v1.0.8-testnet".len() as u64).to_le_bytes());
+        proof.extend_from_slice(b"v1.0.8-testnet");
+
+        assert_eq!(get_sp1_public_values(&proof).unwrap(), public_values);
+        assert!(get_sp1_public_values(&[1, 2, 3]).is_err());
+
+        // Proofs of other versions of SP1 may be serialized differently
+        let other_version = [&proof[..proof.len() - 1], b"x"].concat();
+        assert!(get_sp1_public_values(&other_version).is_err());
+    }
+
+    #[test]
+    fn test_get_pub_input_opening_calldata() {
+        use ethers::abi::AbiDecode;
+        use lambdaworks_crypto::merkle_tree::proof::Proof;
+
+        let aligned_verification_data = AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment {
+                proof_commitment: [1; 32],
+                pub_input_commitment: get_commitment(&[4, 5, 6]),
+                proving_system_aux_data_commitment: [2; 32],
+                proof_generator_addr: [3; 20],
+            },
+            batch_merkle_root: [7; 32],
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[8; 32], [9; 32]],
+            },
+            index_in_batch: 2,
+            batch_version: crate::types::BatchVersion::V1,
+            batch_metadata: None,
+            metadata: None,
+            schema_version: AlignedVerificationData::SCHEMA_VERSION,
+        };
+
+        let calldata = get_pub_input_opening_calldata(&aligned_verification_data, &[4, 5, 6]);
+
+        let selector = &get_commitment(
+            b"verifyBatchInclusionWithPubInput(bytes32,bytes,bytes32,bytes20,bytes32,bytes,uint256)",
+        )[..4];
+        assert_eq!(&calldata[..4], selector);
+
+        let call = eth::VerifyBatchInclusionWithPubInputCall::decode(&calldata).unwrap();
+        assert_eq!(call.proof_commitment, [1; 32]);
+        assert_eq!(call.pub_input.to_vec(), vec![4, 5, 6]);
+        assert_eq!(call.proving_system_aux_data_commitment, [2; 32]);
+        assert_eq!(call.proof_generator_addr, [3; 20]);
+        assert_eq!(call.batch_merkle_root, [7; 32]);
+        assert_eq!(call.merkle_proof.to_vec(), [[8u8; 32], [9u8; 32]].concat());
+        assert_eq!(call.verification_data_batch_index, 2.into());
+    }
+
+    #[test]
+    fn test_get_verify_batch_inclusion_calldata() {
+        use ethers::abi::AbiDecode;
+        use lambdaworks_crypto::merkle_tree::proof::Proof;
+
+        let mut aligned_verification_data = AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment {
+                proof_commitment: [1; 32],
+                pub_input_commitment: [4; 32],
+                proving_system_aux_data_commitment: [2; 32],
+                proof_generator_addr: [3; 20],
+            },
+            batch_merkle_root: [7; 32],
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[8; 32], [9; 32]],
+            },
+            index_in_batch: 2,
+            batch_version: crate::types::BatchVersion::V1,
+            batch_metadata: None,
+            metadata: None,
+            schema_version: AlignedVerificationData::SCHEMA_VERSION,
+        };
+
+        let calldata = get_verify_batch_inclusion_calldata(&aligned_verification_data).unwrap();
+
+        let selector = &get_commitment(
+            b"verifyBatchInclusion(bytes32,bytes32,bytes32,bytes20,bytes32,bytes,uint256)",
+        )[..4];
+        assert_eq!(&calldata[..4], selector);
+
+        let call = eth::VerifyBatchInclusionCall::decode(&calldata).unwrap();
+        assert_eq!(call.proof_commitment, [1; 32]);
+        assert_eq!(call.pub_input_commitment, [4; 32]);
+        assert_eq!(call.proving_system_aux_data_commitment, [2; 32]);
+        assert_eq!(call.proof_generator_addr, [3; 20]);
+        assert_eq!(call.batch_merkle_root, [7; 32]);
+        assert_eq!(call.merkle_proof.to_vec(), [[8u8; 32], [9u8; 32]].concat());
+        assert_eq!(call.verification_data_batch_index, 2.into());
+
+        aligned_verification_data.batch_version = crate::types::BatchVersion::V2;
+        assert!(matches!(
+            get_verify_batch_inclusion_calldata(&aligned_verification_data),
+            Err(errors::VerificationError::UnsupportedBatchVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_risc0_journal() {
+        let base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let receipt =
+            read_file(base_dir.join("test_files/risc_zero/risc_zero_fibonacci.proof")).unwrap();
+        let expected =
+            read_file(base_dir.join("test_files/risc_zero/risc_zero_fibonacci.pub")).unwrap();
+
+        let journal = get_risc0_journal(&receipt).unwrap();
+        assert_eq!(journal, expected);
+
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Risc0,
+            proof: receipt,
+            pub_input: Some(journal.clone()),
+            verification_key: None,
+            vm_program_code: Some(vec![0; 32]),
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        let commitment: VerificationDataCommitment = verification_data.into();
+        assert_eq!(
+            commitment.pub_input_commitment,
+            get_risc0_pub_input_commitment(&journal)
+        );
+    }
 
     #[tokio::test]
     async fn test_submit_success() {
@@ -352,6 +2396,9 @@ mod test {
             verification_key: None,
             vm_program_code: elf,
             proof_generator_addr,
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
         };
 
         let verification_data = vec![verification_data];
@@ -382,6 +2429,9 @@ mod test {
             verification_key: None,
             vm_program_code: None,
             proof_generator_addr: contract_addr,
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
         }];
 
         let wallet = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
@@ -391,7 +2441,11 @@ mod test {
 
         let result = submit_multiple("ws://localhost:8080", &verification_data, wallet).await;
 
-        assert!(result.is_ok());
+        // SP1 proofs can't be verified without their program
+        assert!(matches!(
+            result,
+            Err(SubmitError::InvalidVerificationData(_))
+        ));
     }
 
     #[tokio::test]
@@ -409,10 +2463,13 @@ mod test {
         let verification_data = VerificationData {
             proving_system: ProvingSystemId::Groth16Bn254,
             proof,
-            pub_input: pub_input,
+            pub_input,
             verification_key: vk,
             vm_program_code: None,
             proof_generator_addr,
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
         };
 
         let verification_data = vec![verification_data];
@@ -458,6 +2515,9 @@ mod test {
             verification_key: None,
             vm_program_code: elf,
             proof_generator_addr,
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
         };
 
         let verification_data = vec![verification_data];
@@ -491,6 +2551,250 @@ mod test {
         assert!(!result, "Proof verified on chain");
     }
 
+    // Differential test of `BatchInclusionData::verify` against the contract, with inclusion
+    // data tampered so that nodes are hashed in the wrong order or the wrong leaf is checked.
+    #[tokio::test]
+    async fn test_batch_inclusion_data_verification_matches_contract() {
+        let base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+        let proof = read_file(base_dir.join("test_files/groth16_bn254/plonk.proof")).unwrap();
+        let pub_input =
+            read_file(base_dir.join("test_files/groth16_bn254/plonk_pub_input.pub")).ok();
+        let vk = read_file(base_dir.join("test_files/groth16_bn254/plonk.vk")).ok();
+
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            proof,
+            pub_input,
+            verification_key: vk,
+            vm_program_code: None,
+            proof_generator_addr: Address::from_str("0x66f9664f97F2b50F62D13eA064982f936dE76657")
+                .unwrap(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+
+        let wallet = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap();
+
+        let aligned_verification_data =
+            submit_multiple("ws://localhost:8080", &vec![verification_data; 3], wallet)
+                .await
+                .unwrap()
+                .unwrap();
+
+        sleep(std::time::Duration::from_secs(20)).await;
+
+        for aligned_verification_data in aligned_verification_data {
+            let commitment = aligned_verification_data
+                .verification_data_commitment
+                .clone();
+            let batch_inclusion_data = BatchInclusionData {
+                batch_merkle_root: aligned_verification_data.batch_merkle_root,
+                batch_inclusion_proof: aligned_verification_data.batch_inclusion_proof.clone(),
+                index_in_batch: aligned_verification_data.index_in_batch,
+                batch_version: aligned_verification_data.batch_version,
+                submission_id: None,
+                metadata: None,
+                schema_version: BatchInclusionData::SCHEMA_VERSION,
+            };
+
+            let mut swapped_index = batch_inclusion_data.clone();
+            swapped_index.index_in_batch ^= 1;
+            let mut reversed_path = batch_inclusion_data.clone();
+            reversed_path.batch_inclusion_proof.merkle_path.reverse();
+            let mut other_commitment = commitment.clone();
+            other_commitment.proof_commitment[0] ^= 1;
+
+            let cases = [
+                (batch_inclusion_data.clone(), commitment.clone()),
+                (swapped_index, commitment.clone()),
+                (reversed_path, commitment.clone()),
+                (batch_inclusion_data, other_commitment),
+            ];
+            for (batch_inclusion_data, commitment) in cases {
+                let verified_onchain = verify_proof_onchain(
+                    AlignedVerificationData::new(&commitment, &batch_inclusion_data),
+                    Chain::Devnet,
+                    "http://localhost:8545",
+                )
+                .await
+                .unwrap();
+
+                assert_eq!(batch_inclusion_data.verify(&commitment), verified_onchain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_merkle_inclusion_without_rpc() {
+        use lambdaworks_crypto::merkle_tree::merkle::MerkleTree;
+
+        let commitments: Vec<VerificationDataCommitment> = (0..5u8)
+            .map(|i| VerificationDataCommitment {
+                proof_commitment: [i; 32],
+                ..Default::default()
+            })
+            .collect();
+        let batch_merkle_tree = MerkleTree::<VerificationCommitmentBatch>::build(&commitments);
+
+        for (i, commitment) in commitments.iter().enumerate() {
+            let aligned_verification_data = AlignedVerificationData::new(
+                commitment,
+                &BatchInclusionData::new(i, &batch_merkle_tree),
+            );
+            assert!(verify_merkle_inclusion(
+                &aligned_verification_data,
+                batch_merkle_tree.root
+            ));
+            assert!(!verify_merkle_inclusion(
+                &aligned_verification_data,
+                [0; 32]
+            ));
+        }
+
+        // The proof of another leaf doesn't prove the inclusion of this one
+        let aligned_verification_data = AlignedVerificationData::new(
+            &commitments[0],
+            &BatchInclusionData::new(1, &batch_merkle_tree),
+        );
+        assert!(!verify_merkle_inclusion(
+            &aligned_verification_data,
+            batch_merkle_tree.root
+        ));
+    }
+
+    #[test]
+    fn test_verify_merkle_inclusion_with_sha256_backend() {
+        use crate::merkle::BatchMerkleTree;
+        use crate::types::{BatchVersion, Sha256CommitmentBatch};
+
+        let commitments: Vec<VerificationDataCommitment> = (0..5u8)
+            .map(|i| VerificationDataCommitment {
+                proof_commitment: [i; 32],
+                ..Default::default()
+            })
+            .collect();
+        for batch_version in [BatchVersion::V1, BatchVersion::V2] {
+            let tree = BatchMerkleTree::<Sha256CommitmentBatch>::build_with_backend(
+                batch_version,
+                &commitments,
+            )
+            .unwrap();
+
+            for (i, commitment) in commitments.iter().enumerate() {
+                let aligned_verification_data = AlignedVerificationData::new(
+                    commitment,
+                    &tree.batch_inclusion_data(i).unwrap(),
+                );
+                assert!(
+                    verify_merkle_inclusion_with_backend::<Sha256CommitmentBatch>(
+                        &aligned_verification_data,
+                        tree.root
+                    )
+                );
+                assert!(!verify_merkle_inclusion(
+                    &aligned_verification_data,
+                    tree.root
+                ));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_proof_onchain_rejects_v2_batches() {
+        use lambdaworks_crypto::merkle_tree::proof::Proof;
+
+        let aligned_verification_data = AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment::default(),
+            batch_merkle_root: [7; 32],
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[8; 32], [9; 32], [10; 32]],
+            },
+            index_in_batch: 2,
+            batch_version: crate::types::BatchVersion::V2,
+            batch_metadata: None,
+            metadata: None,
+            schema_version: AlignedVerificationData::SCHEMA_VERSION,
+        };
+
+        // The batch version is checked before calling the contract
+        let result = verify_proof_onchain(
+            aligned_verification_data,
+            Chain::Devnet,
+            "http://localhost:1",
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(errors::VerificationError::UnsupportedBatchVersion(
+                crate::types::BatchVersion::V2
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wait_proof_verification_rejects_v2_batches_before_waiting() {
+        use lambdaworks_crypto::merkle_tree::proof::Proof;
+
+        let aligned_verification_data = AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment::default(),
+            batch_merkle_root: [7; 32],
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[8; 32], [9; 32], [10; 32]],
+            },
+            index_in_batch: 2,
+            batch_version: crate::types::BatchVersion::V2,
+            batch_metadata: None,
+            metadata: None,
+            schema_version: AlignedVerificationData::SCHEMA_VERSION,
+        };
+
+        // V2 batches are never verified on-chain, so they aren't waited for at all
+        let result = wait_proof_verification(
+            aligned_verification_data,
+            Chain::Devnet,
+            "http://localhost:1",
+            &VerificationWaitConfig {
+                poll_interval: Duration::from_secs(3600),
+                timeout: Duration::MAX,
+            },
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(errors::AlignedError::VerificationError(
+                errors::VerificationError::UnsupportedBatchVersion(crate::types::BatchVersion::V2)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_commitments_with_each_hash_function() {
+        let commitment =
+            |hash_function| hex::encode(get_commitment_with_hash(b"abc", hash_function));
+        assert_eq!(
+            commitment(HashFunction::Keccak256),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        assert_eq!(
+            commitment(HashFunction::Sha256),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            commitment(HashFunction::Blake3),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert_eq!(
+            get_commitment(b"abc"),
+            get_commitment_with_hash(b"abc", HashFunction::default())
+        );
+    }
+
     fn read_file(file_name: PathBuf) -> Result<Vec<u8>, SubmitError> {
         std::fs::read(&file_name).map_err(|e| SubmitError::IoError(file_name, e))
     }
@@ -0,0 +1,101 @@
+//! Lenient deserializers for the files saved by previous releases of the SDK and the CLI.
+//! Byte arrays are serialized as arrays of numbers, but they can also be read from hex strings,
+//! with or without the `0x` prefix, so that files keep loading if their encoding changes.
+//! As the encoding is detected from the input, they only work with self-describing formats such as
+//! JSON.
+
+use std::fmt;
+
+use lambdaworks_crypto::merkle_tree::proof::Proof;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+struct BytesOrHexVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for BytesOrHexVisitor<N> {
+    type Value = [u8; N];
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} bytes as an array of numbers or a hex string",
+            N
+        )
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0u8; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(de::Error::invalid_length(N + 1, &self));
+        }
+        Ok(bytes)
+    }
+
+    fn visit_str<E: de::Error>(self, hex_str: &str) -> Result<Self::Value, E> {
+        let hex_str = hex_str.strip_prefix("0x").unwrap_or(hex_str);
+        let mut bytes = [0u8; N];
+        hex::decode_to_slice(hex_str, &mut bytes).map_err(de::Error::custom)?;
+        Ok(bytes)
+    }
+}
+
+/// Deserializes a byte array from an array of numbers or a hex string.
+pub(crate) fn bytes_or_hex<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<[u8; N], D::Error> {
+    deserializer.deserialize_any(BytesOrHexVisitor::<N>)
+}
+
+#[derive(Deserialize)]
+struct Node(#[serde(deserialize_with = "bytes_or_hex")] [u8; 32]);
+
+/// Deserializes a merkle proof whose path nodes are arrays of numbers or hex strings.
+pub(crate) fn merkle_proof<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Proof<[u8; 32]>, D::Error> {
+    #[derive(Deserialize)]
+    struct MerkleProof {
+        merkle_path: Vec<Node>,
+    }
+
+    let proof = MerkleProof::deserialize(deserializer)?;
+    Ok(Proof {
+        merkle_path: proof.merkle_path.into_iter().map(|node| node.0).collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Address(#[serde(deserialize_with = "bytes_or_hex")] [u8; 4]);
+
+    #[test]
+    fn test_bytes_or_hex() {
+        let expected = Address([0xde, 0xad, 0xbe, 0xef]);
+
+        assert_eq!(
+            serde_json::from_str::<Address>("[222, 173, 190, 239]").unwrap(),
+            expected
+        );
+        assert_eq!(
+            serde_json::from_str::<Address>("\"0xdeadbeef\"").unwrap(),
+            expected
+        );
+        assert_eq!(
+            serde_json::from_str::<Address>("\"DEADBEEF\"").unwrap(),
+            expected
+        );
+
+        assert!(serde_json::from_str::<Address>("[222, 173, 190]").is_err());
+        assert!(serde_json::from_str::<Address>("[222, 173, 190, 239, 0]").is_err());
+        assert!(serde_json::from_str::<Address>("\"0xdeadbe\"").is_err());
+        assert!(serde_json::from_str::<Address>("\"0xdeadbeefzz\"").is_err());
+    }
+}
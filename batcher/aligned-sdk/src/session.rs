@@ -0,0 +1,202 @@
+//! Sessions with which a submission continues on a new connection after the previous one was
+//! lost, without sending again the proofs the batcher already accepted.
+//!
+//! Batchers supporting the `session_resumption` feature open a session for each connection of a
+//! client that announces it, and send its token in the `session` of the handshake. The batcher
+//! keeps the responses to the proofs of the session, including the ones it couldn't deliver
+//! because the connection was lost, until some time after the connection ends. A client
+//! reconnecting sends the token in the `x-aligned-session-token` header, with the number of
+//! responses of the session it received in `x-aligned-session-responses`. The batcher answers
+//! with the same token and the number of proofs of the session it accepted, replays the responses
+//! the client didn't receive, and sends the responses of the proofs still waiting for their batch
+//! to the new connection. The client then only sends the proofs the batcher didn't accept.
+//! Sessions the batcher doesn't know, because they expired or the batcher restarted, are answered
+//! with a new token, and every proof without a response is sent again.
+
+use ethers::utils::hex;
+use log::debug;
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue};
+
+use crate::errors::SubmitError;
+use crate::sdk::SESSION_RESUMPTION_FEATURE;
+use crate::types::ProtocolHandshake;
+
+/// Header of the websocket handshake holding the token of the session the client resumes.
+pub const SESSION_TOKEN_HEADER: &str = "x-aligned-session-token";
+
+/// Header of the websocket handshake holding the number of responses of the resumed session
+/// received by the client.
+pub const SESSION_RESPONSES_HEADER: &str = "x-aligned-session-responses";
+
+/// Session of a submission with the batcher, kept across the connections of its attempts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ClientSession {
+    token: String,
+    /// Position in the submission of the first proof sent in the session.
+    base: usize,
+}
+
+impl ClientSession {
+    /// Inserts the headers resuming the session in the handshake request of a connection, given
+    /// the number of proofs of the submission responded so far.
+    /// # Errors
+    /// * `ProtocolViolation` if the token sent by the batcher can't be sent in a header.
+    pub(crate) fn insert_headers(
+        &self,
+        headers: &mut HeaderMap,
+        responded: usize,
+    ) -> Result<(), SubmitError> {
+        let token = HeaderValue::from_str(&self.token).map_err(|_| {
+            SubmitError::ProtocolViolation(
+                "Batcher sent a session token that can't be sent in a header".to_string(),
+            )
+        })?;
+        headers.insert(SESSION_TOKEN_HEADER, token);
+        headers.insert(
+            SESSION_RESPONSES_HEADER,
+            HeaderValue::from(responded.saturating_sub(self.base)),
+        );
+        Ok(())
+    }
+}
+
+/// Returns how many of the proofs sent over a connection the batcher already accepted in the
+/// session it resumed, and sets `session` to the session of the handshake. The proofs of the
+/// connection start at position `base` of the submission, and there are `proofs` of them.
+/// # Errors
+/// * `ProtocolViolation` if the batcher resumed the session with fewer accepted proofs than it
+///   responded, or more than were sent.
+pub(crate) fn negotiate(
+    session: &mut Option<ClientSession>,
+    handshake: &ProtocolHandshake,
+    base: usize,
+    proofs: usize,
+) -> Result<usize, SubmitError> {
+    let Some(handshake_session) = handshake
+        .session
+        .as_ref()
+        .filter(|_| handshake.supports(SESSION_RESUMPTION_FEATURE))
+    else {
+        *session = None;
+        return Ok(0);
+    };
+
+    match session {
+        Some(session) if session.token == handshake_session.token => {
+            let accepted = session.base + handshake_session.accepted_proofs;
+            if accepted < base || accepted > base + proofs {
+                return Err(SubmitError::ProtocolViolation(format!(
+                    "Batcher resumed the session with {} accepted proofs, but {} were responded of {}",
+                    handshake_session.accepted_proofs,
+                    base - session.base,
+                    base + proofs - session.base
+                )));
+            }
+            debug!(
+                "Resumed the session, {} of {} proofs were already accepted",
+                accepted - base,
+                proofs
+            );
+            Ok(accepted - base)
+        }
+        _ => {
+            *session = Some(ClientSession {
+                token: handshake_session.token.clone(),
+                base,
+            });
+            Ok(0)
+        }
+    }
+}
+
+/// Returns a new random session token, as 32 bytes in hex.
+pub fn new_session_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Returns the token of the session a client resumes in its handshake request and the number
+/// of responses of the session it received, or `None` if it doesn't resume one.
+pub fn resumption_request(headers: &HeaderMap) -> Option<(String, usize)> {
+    let token = headers
+        .get(SESSION_TOKEN_HEADER)?
+        .to_str()
+        .ok()?
+        .to_string();
+    let responses = headers
+        .get(SESSION_RESPONSES_HEADER)
+        .and_then(|responses| responses.to_str().ok())
+        .and_then(|responses| responses.parse().ok())
+        .unwrap_or(0);
+    Some((token, responses))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdk::protocol_handshake;
+    use crate::types::SessionHandshake;
+
+    fn handshake(token: &str, accepted_proofs: usize) -> ProtocolHandshake {
+        ProtocolHandshake {
+            session: Some(SessionHandshake {
+                token: token.to_string(),
+                accepted_proofs,
+            }),
+            ..protocol_handshake()
+        }
+    }
+
+    #[test]
+    fn test_resumed_sessions_skip_the_accepted_proofs() {
+        // The session is opened after 2 proofs of the submission were responded
+        let mut session = None;
+        assert_eq!(
+            negotiate(&mut session, &handshake("first", 0), 2, 5).unwrap(),
+            0
+        );
+
+        // 1 more proof was responded, and 3 of the session were accepted
+        let mut headers = HeaderMap::new();
+        session
+            .as_ref()
+            .unwrap()
+            .insert_headers(&mut headers, 3)
+            .unwrap();
+        assert_eq!(resumption_request(&headers), Some(("first".to_string(), 1)));
+        assert_eq!(
+            negotiate(&mut session, &handshake("first", 3), 3, 4).unwrap(),
+            2
+        );
+
+        // A batcher that accepted fewer proofs than it responded
+        assert!(matches!(
+            negotiate(&mut session, &handshake("first", 0), 3, 4),
+            Err(SubmitError::ProtocolViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_sessions_are_replaced() {
+        let mut session = Some(ClientSession {
+            token: "expired".to_string(),
+            base: 0,
+        });
+        assert_eq!(
+            negotiate(&mut session, &handshake("second", 0), 2, 3).unwrap(),
+            0
+        );
+        assert_eq!(
+            session,
+            Some(ClientSession {
+                token: "second".to_string(),
+                base: 2,
+            })
+        );
+
+        // Batchers without sessions
+        let without_sessions = ProtocolHandshake::without_features(0);
+        assert_eq!(negotiate(&mut session, &without_sessions, 2, 3).unwrap(), 0);
+        assert_eq!(session, None);
+        assert_eq!(resumption_request(&HeaderMap::new()), None);
+    }
+}
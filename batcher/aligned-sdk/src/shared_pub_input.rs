@@ -0,0 +1,140 @@
+//! Proofs verified against the same public input, such as proofs of different facts about the
+//! same block header. The public input is kept once in memory and hashed once, instead of once
+//! per proof. The batcher can't reference data sent in previous messages, so the public input is
+//! still sent with every proof.
+
+use ethers::types::Address;
+use sha3::{Digest, Keccak256};
+
+use crate::types::{
+    proving_system_aux_data_commitment, ProvingSystemId, VerificationData,
+    VerificationDataCommitment,
+};
+
+/// A proof whose public input is the one shared by its `SharedPubInputProofs`.
+#[derive(Debug, Clone)]
+pub struct SharedPubInputProof {
+    pub proving_system: ProvingSystemId,
+    pub proof: Vec<u8>,
+    pub verification_key: Option<Vec<u8>>,
+    pub vm_program_code: Option<Vec<u8>>,
+    pub proof_generator_addr: Address,
+}
+
+#[derive(Debug, Clone)]
+pub struct SharedPubInputProofs {
+    pub_input: Vec<u8>,
+    pub_input_commitment: [u8; 32],
+    proofs: Vec<SharedPubInputProof>,
+}
+
+impl SharedPubInputProofs {
+    pub fn new(pub_input: Vec<u8>) -> Self {
+        let pub_input_commitment = Keccak256::digest(&pub_input).into();
+        SharedPubInputProofs {
+            pub_input,
+            pub_input_commitment,
+            proofs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, proof: SharedPubInputProof) {
+        self.proofs.push(proof);
+    }
+
+    pub fn pub_input(&self) -> &[u8] {
+        &self.pub_input
+    }
+
+    pub fn proofs(&self) -> &[SharedPubInputProof] {
+        &self.proofs
+    }
+
+    /// Returns the commitments of the proofs, in the order they were pushed, which are the same
+    /// as the commitments of their verification data.
+    pub fn commitments(&self) -> Vec<VerificationDataCommitment> {
+        self.proofs
+            .iter()
+            .map(|proof| VerificationDataCommitment {
+                proof_commitment: Keccak256::digest(&proof.proof).into(),
+                pub_input_commitment: self.pub_input_commitment,
+                proving_system_aux_data_commitment: proving_system_aux_data_commitment(
+                    proof.vm_program_code.as_deref(),
+                    proof.verification_key.as_deref(),
+                ),
+                proof_generator_addr: proof.proof_generator_addr.into(),
+            })
+            .collect()
+    }
+
+    /// Returns the verification data of the proofs, in the order they were pushed, to submit
+    /// them with the `submit_multiple` functions.
+    pub fn to_verification_data(&self) -> Vec<VerificationData> {
+        self.proofs
+            .iter()
+            .map(|proof| VerificationData {
+                proving_system: proof.proving_system.clone(),
+                proof: proof.proof.clone(),
+                pub_input: Some(self.pub_input.clone()),
+                verification_key: proof.verification_key.clone(),
+                vm_program_code: proof.vm_program_code.clone(),
+                proof_generator_addr: proof.proof_generator_addr,
+                tag: None,
+                metadata: None,
+                schema_version: VerificationData::SCHEMA_VERSION,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_commitments_match_verification_data_commitments() {
+        let mut proofs = SharedPubInputProofs::new(b"block header".to_vec());
+        proofs.push(SharedPubInputProof {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            proof: vec![1, 2, 3],
+            verification_key: Some(vec![4, 5, 6]),
+            vm_program_code: None,
+            proof_generator_addr: Address::repeat_byte(1),
+        });
+        proofs.push(SharedPubInputProof {
+            proving_system: ProvingSystemId::SP1,
+            proof: vec![7, 8, 9],
+            verification_key: None,
+            vm_program_code: Some(vec![10, 11]),
+            proof_generator_addr: Address::repeat_byte(2),
+        });
+
+        let expected: Vec<VerificationDataCommitment> = proofs
+            .to_verification_data()
+            .into_iter()
+            .map(VerificationDataCommitment::from)
+            .collect();
+        let commitments = proofs.commitments();
+
+        assert_eq!(commitments.len(), 2);
+        for (commitment, expected) in commitments.iter().zip(&expected) {
+            assert_eq!(commitment.proof_commitment, expected.proof_commitment);
+            assert_eq!(
+                commitment.pub_input_commitment,
+                expected.pub_input_commitment
+            );
+            assert_eq!(
+                commitment.proving_system_aux_data_commitment,
+                expected.proving_system_aux_data_commitment
+            );
+            assert_eq!(
+                commitment.proof_generator_addr,
+                expected.proof_generator_addr
+            );
+        }
+        assert_ne!(
+            commitments[0].proof_commitment,
+            commitments[1].proof_commitment
+        );
+    }
+}
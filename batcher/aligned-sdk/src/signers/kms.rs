@@ -0,0 +1,42 @@
+//! Signing with keys held in AWS KMS, so that services submitting proofs, such as CI or cron
+//! jobs, don't need a private key on disk. Each message is signed by a request to KMS, and the
+//! key never leaves it.
+//!
+//! Keys have to be asymmetric `ECC_SECG_P256K1` keys with the `SIGN_VERIFY` usage. The AWS
+//! credentials are read as the AWS CLI reads them: from the environment, the AWS profile or the
+//! role of the instance.
+
+use std::str::FromStr;
+
+pub use ethers::signers::AwsSigner;
+use rusoto_core::Region;
+use rusoto_kms::KmsClient;
+
+use crate::errors::SubmitError;
+
+/// Chain ID of the signer, as for a `LocalWallet`. Messages signed for the batcher don't depend
+/// on it, it's only used to sign transactions.
+const DEFAULT_CHAIN_ID: u64 = 1;
+
+/// Returns a signer for a KMS key, which can be passed to `submit_multiple` and the other
+/// functions taking a wallet. The public key of the key is retrieved from KMS to get its address.
+/// Its chain ID can be set with `Signer::with_chain_id` to also sign transactions.
+/// # Arguments
+/// * `key_id` - The ID, ARN or alias of the key.
+/// * `region` - The AWS region of the key, such as `us-east-1`.
+/// # Errors
+/// * `SignerError` if the region is not valid or the public key can't be retrieved, such as when
+///   the key doesn't exist or the credentials can't use it.
+pub async fn kms_signer(key_id: &str, region: &str) -> Result<AwsSigner, SubmitError> {
+    let kms_region = Region::from_str(region)
+        .map_err(|e| SubmitError::SignerError(format!("Invalid AWS region {}: {}", region, e)))?;
+
+    AwsSigner::new(KmsClient::new(kms_region), key_id, DEFAULT_CHAIN_ID)
+        .await
+        .map_err(|e| {
+            SubmitError::SignerError(format!(
+                "Error while getting the public key of KMS key {}: {}",
+                key_id, e
+            ))
+        })
+}
@@ -0,0 +1,8 @@
+//! Signers set up by the SDK and the CLI. Any `ethers` signer can sign the proofs submitted with
+//! the SDK. The ones other than an in-process wallet are behind features so that their
+//! dependencies are only built when used.
+
+pub mod pooled;
+
+#[cfg(feature = "kms")]
+pub mod kms;
@@ -0,0 +1,95 @@
+//! A local wallet that signs on the blocking thread pool of tokio. A `LocalWallet` signs in place,
+//! on the task submitting the proofs, so the messages of a bulk submission are signed one after
+//! the other. Wrapped in a `PooledWallet`, up to `signing_parallelism` of them are signed at once,
+//! each on a thread of the pool.
+
+use std::io;
+
+use async_trait::async_trait;
+use ethers::signers::{LocalWallet, Signer, WalletError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::Eip712;
+use ethers::types::{Address, Signature};
+use ethers::utils::hash_message;
+
+/// A `LocalWallet` whose messages are signed on the blocking thread pool of tokio. It can be
+/// passed to `submit_multiple` and the other functions taking a wallet, and has to be used from
+/// a tokio runtime.
+#[derive(Clone, Debug)]
+pub struct PooledWallet(LocalWallet);
+
+impl PooledWallet {
+    /// Wraps the wallet, keeping its address and chain ID.
+    pub fn new(wallet: LocalWallet) -> Self {
+        Self(wallet)
+    }
+
+    /// Returns the wrapped wallet.
+    pub fn into_inner(self) -> LocalWallet {
+        self.0
+    }
+}
+
+impl From<LocalWallet> for PooledWallet {
+    fn from(wallet: LocalWallet) -> Self {
+        Self::new(wallet)
+    }
+}
+
+#[async_trait]
+impl Signer for PooledWallet {
+    type Error = WalletError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        let hash = hash_message(message);
+        let wallet = self.0.clone();
+
+        tokio::task::spawn_blocking(move || wallet.sign_hash(hash))
+            .await
+            .map_err(|e| WalletError::IoError(io::Error::new(io::ErrorKind::Other, e)))?
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        self.0.sign_transaction(tx).await
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        self.0.sign_typed_data(payload).await
+    }
+
+    fn address(&self) -> Address {
+        self.0.address()
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.0.chain_id()
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        Self(self.0.with_chain_id(chain_id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn pooled_wallet_signs_as_the_wrapped_wallet() {
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let pooled = PooledWallet::new(wallet.clone());
+        let message = [7u8; 32];
+
+        assert_eq!(pooled.address(), wallet.address());
+        assert_eq!(
+            pooled.sign_message(message).await.unwrap(),
+            wallet.sign_message(message).await.unwrap()
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Verification data built from the proof artifacts of `sp1-sdk`, so SP1 users can submit the
+//! proofs of their prover without serializing them. Only built with the `sp1` feature, since it
+//! pulls in the SP1 SDK.
+
+use ethers::types::Address;
+use sp1_sdk::SP1CompressedProof;
+
+use crate::errors::SubmitError;
+use crate::types::{ProvingSystemId, VerificationData};
+
+impl VerificationData {
+    /// Builds the verification data of a compressed SP1 proof of the program `elf`, as returned
+    /// by `ProverClient::prove_compressed`. The proof is serialized with bincode as the operators
+    /// expect, and its public values are bound as the public input, so they can be used by
+    /// contracts, see `sdk::get_sp1_public_values`.
+    /// # Arguments
+    /// * `proof` - The SP1 proof, with its public values.
+    /// * `elf` - The compiled program that was proven.
+    /// * `proof_generator_addr` - The address of the generator of the proof.
+    /// # Returns
+    /// * The verification data of the proof.
+    /// # Errors
+    /// * `InvalidProof` if the proof can't be serialized.
+    /// * `InvalidVerificationData` if the program is larger than the batcher accepts.
+    pub fn from_sp1(
+        proof: &SP1CompressedProof,
+        elf: &[u8],
+        proof_generator_addr: Address,
+    ) -> Result<Self, SubmitError> {
+        let serialized_proof =
+            bincode::serialize(proof).map_err(|e| SubmitError::InvalidProof {
+                proving_system: ProvingSystemId::SP1,
+                reason: format!("the proof can't be serialized: {}", e),
+            })?;
+
+        let verification_data = VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: serialized_proof,
+            pub_input: Some(proof.public_values.to_vec()),
+            verification_key: None,
+            vm_program_code: Some(elf.to_vec()),
+            proof_generator_addr,
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        verification_data.validate()?;
+        Ok(verification_data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdk::get_sp1_public_values;
+
+    #[test]
+    fn test_from_sp1_serializes_the_proof_as_sent_to_aligned() {
+        let base_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let proof_bytes =
+            std::fs::read(base_dir.join("test_files/sp1/sp1_fibonacci.proof")).unwrap();
+        let elf = std::fs::read(base_dir.join("test_files/sp1/sp1_fibonacci-elf")).unwrap();
+        let proof: SP1CompressedProof = bincode::deserialize(&proof_bytes).unwrap();
+
+        let verification_data = VerificationData::from_sp1(&proof, &elf, Address::zero()).unwrap();
+
+        assert_eq!(verification_data.proof, proof_bytes);
+        assert_eq!(
+            verification_data.pub_input,
+            Some(get_sp1_public_values(&proof_bytes).unwrap())
+        );
+        assert_eq!(verification_data.vm_program_code, Some(elf));
+    }
+}
@@ -0,0 +1,47 @@
+//! Status of a submitted proof in the batcher, for clients polling it after disconnecting from
+//! the batcher. The batcher knows a proof while it's in the batch queue, and then while its batch
+//! is one of the latest ones, as for the inclusion queries of `pending`. Once the verification
+//! task of the batch is created, its status in Aligned is read on-chain, as in `batch_status`.
+
+use crate::errors::SubmitError;
+use crate::sdk::{query_batcher, SUBMISSION_STATUS_FEATURE};
+use crate::tls::TlsConfig;
+use crate::types::{
+    GetSubmissionStatus, SubmissionStatus, SubmissionStatusResponse, VerificationDataCommitment,
+};
+use crate::wire::WireMessage;
+
+/// Asks the batcher how far the proof with this commitment has gone.
+/// # Arguments
+/// * `batcher_addr` - The address of the batcher the proof was sent to.
+/// * `tls_config` - The root certificates and server name with which `wss://` addresses are
+///   connected.
+/// * `verification_data_commitment` - The verification data commitment of the proof.
+/// # Returns
+/// * The status of the proof in the batcher.
+/// # Errors
+/// * `ConnectionError` if there is an error connecting to the batcher.
+/// * `TlsError` if the TLS handshake with the batcher fails.
+/// * `Unauthorized` if the batcher refuses the access token of `tls_config`, or the lack of one.
+/// * `ProtocolMismatch` if the batcher doesn't support the `submission_status` feature, or its
+///   protocol version is newer than the one of the SDK.
+/// * `MessageRejected` if the batcher rejects the query.
+/// * `ProtocolViolation` if the batcher doesn't send its handshake, or its answer is not valid.
+pub async fn get_submission_status(
+    batcher_addr: &str,
+    tls_config: &TlsConfig,
+    verification_data_commitment: &VerificationDataCommitment,
+) -> Result<SubmissionStatus, SubmitError> {
+    let query = WireMessage::GetSubmissionStatus(GetSubmissionStatus {
+        verification_data_commitment: verification_data_commitment.clone(),
+    });
+    let response: SubmissionStatusResponse = query_batcher(
+        batcher_addr,
+        tls_config,
+        SUBMISSION_STATUS_FEATURE,
+        &query,
+        "submission status query",
+    )
+    .await?;
+    Ok(response.status)
+}
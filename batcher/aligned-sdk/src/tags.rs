@@ -0,0 +1,177 @@
+//! Retrieval of proofs by the application tag set in their `VerificationData`, so that teams
+//! submitting proofs for several products can tell their submissions apart.
+//!
+//! Tags are not part of the commitment of the proofs, so they are only stored in the batch data
+//! uploaded by the batcher. Finding the proofs with a tag requires downloading the data of every
+//! batch searched, which is why the search is limited to the latest batches.
+
+use ethers::types::Address;
+
+use crate::errors::VerificationError;
+use crate::rpc::AlignedRpcClient;
+use crate::types::{Chain, VerificationData, VerificationDataCommitment};
+
+/// A proof with the searched tag, found in the data of a batch.
+#[derive(Debug, Clone)]
+pub struct TaggedProof {
+    pub batch_merkle_root: [u8; 32],
+    pub task_created_block: u32,
+    pub index_in_batch: usize,
+    pub verification_data_commitment: VerificationDataCommitment,
+}
+
+/// Returns the proofs with the given tag in the latest batches created on-chain.
+/// # Arguments
+/// * `tag` - The tag of the proofs.
+/// * `proof_generator_addr` - If set, only the proofs submitted with this address are returned.
+/// * `last_batches` - The number of batches to search, starting from the latest one.
+/// * `from_block` - The block from which batches are searched.
+/// * `chain` - The chain on which the batches were created.
+/// * `eth_rpc_url` - The URL of the Ethereum RPC node.
+/// # Returns
+/// * The proofs with the tag, ordered by batch creation and then by index in the batch.
+/// # Errors
+/// * `EthError` if there is an error querying the batches created on-chain.
+/// * `BatchDataError` if the data of a batch can't be downloaded.
+/// * `ParsingError` if the data of a batch is not valid.
+pub async fn get_tagged_proofs(
+    tag: [u8; 32],
+    proof_generator_addr: Option<Address>,
+    last_batches: usize,
+    from_block: u64,
+    chain: Chain,
+    eth_rpc_url: &str,
+) -> Result<Vec<TaggedProof>, VerificationError> {
+    let rpc_client = AlignedRpcClient::from_url(eth_rpc_url)?;
+    let new_batch_events = rpc_client.new_batch_events(from_block, &chain).await?;
+    let first_searched = new_batch_events.len().saturating_sub(last_batches);
+
+    let mut tagged_proofs = Vec::new();
+    for new_batch in &new_batch_events[first_searched..] {
+        let batch_data = rpc_client.batch_data(&new_batch.batch_data_pointer).await?;
+        tagged_proofs.extend(
+            find_tagged_proofs(&batch_data, tag, proof_generator_addr)
+                .into_iter()
+                .map(
+                    |(index_in_batch, verification_data_commitment)| TaggedProof {
+                        batch_merkle_root: new_batch.batch_merkle_root,
+                        task_created_block: new_batch.task_created_block,
+                        index_in_batch,
+                        verification_data_commitment,
+                    },
+                ),
+        );
+    }
+
+    Ok(tagged_proofs)
+}
+
+/// Returns the index in the batch and the commitment of the proofs of a batch with the given tag.
+/// # Arguments
+/// * `batch_data` - The verification data of the batch, in the order it was uploaded.
+/// * `tag` - The tag of the proofs.
+/// * `proof_generator_addr` - If set, only the proofs submitted with this address are returned.
+pub fn find_tagged_proofs(
+    batch_data: &[VerificationData],
+    tag: [u8; 32],
+    proof_generator_addr: Option<Address>,
+) -> Vec<(usize, VerificationDataCommitment)> {
+    batch_data
+        .iter()
+        .enumerate()
+        .filter(|(_, verification_data)| {
+            verification_data.tag == Some(tag)
+                && proof_generator_addr
+                    .map_or(true, |addr| verification_data.proof_generator_addr == addr)
+        })
+        .map(|(index_in_batch, verification_data)| {
+            (index_in_batch, verification_data.clone().into())
+        })
+        .collect()
+}
+
+pub(crate) async fn get_batch_data(
+    batch_data_pointer: &str,
+) -> Result<Vec<VerificationData>, VerificationError> {
+    let response = reqwest::get(batch_data_pointer)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| VerificationError::BatchDataError(e.to_string()))?;
+    let batch_data = response
+        .bytes()
+        .await
+        .map_err(|e| VerificationError::BatchDataError(e.to_string()))?;
+
+    serde_json::from_slice(&batch_data).map_err(|e| VerificationError::ParsingError(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::ProvingSystemId;
+
+    fn verification_data(tag: Option<[u8; 32]>, proof_generator_addr: Address) -> VerificationData {
+        VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: vec![1, 2, 3],
+            pub_input: None,
+            verification_key: None,
+            vm_program_code: Some(vec![4, 5, 6]),
+            proof_generator_addr,
+            tag,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        }
+    }
+
+    #[test]
+    fn test_find_tagged_proofs() {
+        let tag = [1; 32];
+        let mine = Address::repeat_byte(1);
+        let theirs = Address::repeat_byte(2);
+        let batch_data = vec![
+            verification_data(Some(tag), mine),
+            verification_data(None, mine),
+            verification_data(Some([2; 32]), mine),
+            verification_data(Some(tag), theirs),
+            verification_data(Some(tag), mine),
+        ];
+
+        let indices = |proofs: Vec<(usize, VerificationDataCommitment)>| -> Vec<usize> {
+            proofs.into_iter().map(|(index, _)| index).collect()
+        };
+        assert_eq!(
+            indices(find_tagged_proofs(&batch_data, tag, None)),
+            vec![0, 3, 4]
+        );
+        assert_eq!(
+            indices(find_tagged_proofs(&batch_data, tag, Some(mine))),
+            vec![0, 4]
+        );
+        assert!(find_tagged_proofs(&batch_data, [3; 32], None).is_empty());
+    }
+
+    #[test]
+    fn test_tag_is_not_committed_and_is_kept_in_batch_data() {
+        let tagged = verification_data(Some([1; 32]), Address::repeat_byte(1));
+        let untagged = verification_data(None, Address::repeat_byte(1));
+
+        let tagged_commitment = VerificationDataCommitment::from(tagged.clone());
+        let untagged_commitment = VerificationDataCommitment::from(untagged.clone());
+        assert_eq!(
+            tagged_commitment.proof_commitment,
+            untagged_commitment.proof_commitment
+        );
+        assert_eq!(
+            tagged_commitment.proving_system_aux_data_commitment,
+            untagged_commitment.proving_system_aux_data_commitment
+        );
+
+        // Untagged data is serialized as before tags existed
+        assert!(!serde_json::to_string(&untagged).unwrap().contains("tag"));
+        let batch_data: Vec<VerificationData> =
+            serde_json::from_slice(&serde_json::to_vec(&vec![tagged, untagged]).unwrap()).unwrap();
+        assert_eq!(batch_data[0].tag, Some([1; 32]));
+        assert_eq!(batch_data[1].tag, None);
+    }
+}
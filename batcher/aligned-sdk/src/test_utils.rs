@@ -0,0 +1,98 @@
+//! Utilities to write deterministic tests of applications using the SDK, available with the
+//! `test-utils` feature. With a deterministic wallet, a frozen clock and seeded randomness, the
+//! messages sent to the batcher are byte-identical between runs, so they can be snapshot tested.
+
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::signers::Wallet;
+use ethers::types::Address;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use sha3::{Digest, Keccak256};
+
+pub use crate::clock::{freeze_clock, unfreeze_clock};
+use crate::types::{ProvingSystemId, VerificationData};
+
+/// Returns a wallet whose private key is derived from the given index, so each index always
+/// gives the same wallet. It must only be used in tests, as its key is public.
+pub fn deterministic_wallet(index: u64) -> Wallet<SigningKey> {
+    let mut hasher = Keccak256::new();
+    hasher.update(b"aligned-sdk test wallet");
+    hasher.update(index.to_le_bytes());
+    let private_key: [u8; 32] = hasher.finalize().into();
+
+    // A keccak256 hash is a valid secp256k1 private key unless it's zero or greater than the
+    // order of the curve, which doesn't happen in practice
+    let signing_key = SigningKey::from_bytes(&private_key.into())
+        .expect("Test wallet private key is not a valid secp256k1 key");
+    Wallet::from(signing_key)
+}
+
+/// Returns a random number generator that always produces the same values for the same seed.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::seed_from_u64(seed)
+}
+
+/// Returns verification data with random contents generated by the given random number
+/// generator. The data is not a valid proof, it's meant to test the submission logic.
+pub fn random_verification_data(rng: &mut impl Rng) -> VerificationData {
+    let mut random_bytes = |len: usize| -> Vec<u8> { (0..len).map(|_| rng.gen()).collect() };
+
+    VerificationData {
+        proving_system: ProvingSystemId::Groth16Bn254,
+        proof: random_bytes(256),
+        pub_input: Some(random_bytes(32)),
+        verification_key: Some(random_bytes(128)),
+        vm_program_code: None,
+        proof_generator_addr: Address::from_slice(&random_bytes(20)),
+        tag: None,
+        metadata: None,
+        schema_version: VerificationData::SCHEMA_VERSION,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock;
+    use crate::types::ClientMessage;
+    use ethers::signers::Signer;
+    use std::time::{Duration, SystemTime};
+
+    async fn client_message(seed: u64) -> Vec<u8> {
+        let verification_data = random_verification_data(&mut seeded_rng(seed));
+        let msg = ClientMessage::new(verification_data, &deterministic_wallet(0))
+            .await
+            .unwrap();
+        serde_json::to_vec(&msg).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_messages_are_byte_identical() {
+        assert_eq!(client_message(1).await, client_message(1).await);
+        assert_ne!(client_message(1).await, client_message(2).await);
+    }
+
+    #[test]
+    fn test_deterministic_wallet() {
+        assert_eq!(
+            deterministic_wallet(0).address(),
+            deterministic_wallet(0).address()
+        );
+        assert_ne!(
+            deterministic_wallet(0).address(),
+            deterministic_wallet(1).address()
+        );
+    }
+
+    #[test]
+    fn test_frozen_clock() {
+        let frozen_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        freeze_clock(frozen_at);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(clock::now(), frozen_at);
+
+        unfreeze_clock();
+        assert!(clock::now() > frozen_at);
+    }
+}
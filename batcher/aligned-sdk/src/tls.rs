@@ -0,0 +1,173 @@
+//! TLS configuration of the connection to batchers behind TLS, with `wss://` addresses.
+//!
+//! By default the certificate of the batcher is validated against the root certificates of the
+//! system, for the host of its address. Batchers with a certificate issued by a private CA, or
+//! reached through an address other than the name in their certificate, such as an IP address
+//! or a tunnel, need the options of `TlsConfig`. It also holds the access token of batchers
+//! restricting who can connect, as described in `auth`.
+
+use native_tls::{Certificate, TlsConnector};
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::Connector;
+use url::Url;
+
+use crate::errors::SubmitError;
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    /// PEM encoded root certificates trusted in addition to the ones of the system, such as the
+    /// one of the private CA that issued the certificate of the batcher.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// Name sent in the SNI extension and validated against the certificate of the batcher,
+    /// instead of the host of its address. It's also sent as the `Host` of the handshake, while
+    /// the connection is still opened to the host of the address.
+    pub server_name: Option<String>,
+    /// Access token sent as a bearer token in the handshake, to batchers of private deployments.
+    pub auth_token: Option<String>,
+}
+
+impl TlsConfig {
+    /// Adds a PEM encoded root certificate to trust, as read from a `.pem` or `.crt` file.
+    pub fn with_root_certificate(mut self, pem: Vec<u8>) -> Self {
+        self.root_certificates.push(pem);
+        self
+    }
+
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    pub fn with_auth_token(mut self, auth_token: impl Into<String>) -> Self {
+        self.auth_token = Some(auth_token.into());
+        self
+    }
+
+    /// Returns the connector with the root certificates of the configuration, or `None` to use
+    /// the default one if there are none.
+    /// # Errors
+    /// * `InvalidCertificate` if a root certificate is not valid PEM.
+    /// * `TlsError` if the TLS backend can't be initialized.
+    pub(crate) fn connector(&self) -> Result<Option<Connector>, SubmitError> {
+        if self.root_certificates.is_empty() {
+            return Ok(None);
+        }
+
+        let mut builder = TlsConnector::builder();
+        for pem in &self.root_certificates {
+            let certificate = Certificate::from_pem(pem)
+                .map_err(|e| SubmitError::InvalidCertificate(e.to_string()))?;
+            builder.add_root_certificate(certificate);
+        }
+        let connector = builder
+            .build()
+            .map_err(|e| SubmitError::TlsError(e.to_string()))?;
+        Ok(Some(Connector::NativeTls(connector)))
+    }
+}
+
+/// Returns the host and port to which the connection is opened, and the address whose host is
+/// `server_name`, from which the TLS and websocket handshakes take the server name.
+pub(crate) fn server_name_address(
+    batcher_addr: &str,
+    server_name: &str,
+) -> Result<((String, u16), String), SubmitError> {
+    let invalid_address = |e: String| SubmitError::InvalidAddress(batcher_addr.to_string(), e);
+
+    let mut url = Url::parse(batcher_addr).map_err(|e| invalid_address(e.to_string()))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| invalid_address("missing host".to_string()))?
+        .to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| invalid_address("missing port".to_string()))?;
+
+    // The port is kept, since the URL has no port when it's the default one of the scheme
+    url.set_host(Some(server_name))
+        .map_err(|e| SubmitError::InvalidAddress(server_name.to_string(), e.to_string()))?;
+
+    Ok(((host, port), url.to_string()))
+}
+
+/// Turns an error opening the connection into a `SubmitError`, telling TLS errors, such as an
+/// untrusted certificate, and refused access tokens apart from connection errors, since they
+/// don't go away by retrying.
+pub(crate) fn connection_error(e: tungstenite::Error) -> SubmitError {
+    match e {
+        tungstenite::Error::Tls(e) => SubmitError::TlsError(e.to_string()),
+        tungstenite::Error::Http(response) if response.status() == StatusCode::UNAUTHORIZED => {
+            SubmitError::Unauthorized("Batcher refused the access token".to_string())
+        }
+        e => SubmitError::ConnectionError(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_server_name_replaces_host_of_address() {
+        assert_eq!(
+            server_name_address("wss://10.0.0.1:8443/ws", "batcher.example.com").unwrap(),
+            (
+                ("10.0.0.1".to_string(), 8443),
+                "wss://batcher.example.com:8443/ws".to_string()
+            )
+        );
+        // The default port of the scheme
+        assert_eq!(
+            server_name_address("wss://10.0.0.1", "batcher.example.com").unwrap(),
+            (
+                ("10.0.0.1".to_string(), 443),
+                "wss://batcher.example.com/".to_string()
+            )
+        );
+
+        assert!(matches!(
+            server_name_address("not an address", "batcher.example.com"),
+            Err(SubmitError::InvalidAddress(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_root_certificate() {
+        assert!(TlsConfig::default().connector().unwrap().is_none());
+
+        let tls_config = TlsConfig::default().with_root_certificate(b"not a certificate".to_vec());
+        assert!(matches!(
+            tls_config.connector(),
+            Err(SubmitError::InvalidCertificate(_))
+        ));
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_tls_handshake_failure_is_a_tls_error() {
+        use crate::client::AlignedClient;
+        use crate::mock_batcher::{FaultConfig, MockBatcher};
+        use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+
+        // The mock batcher doesn't speak TLS
+        let mock_batcher = MockBatcher::start(FaultConfig::default()).await.unwrap();
+        let batcher_addr = mock_batcher.url().replacen("ws://", "wss://", 1);
+
+        for tls_config in [
+            TlsConfig::default(),
+            TlsConfig::default().with_server_name("batcher.example.com"),
+        ] {
+            let client = AlignedClient::builder()
+                .batcher_addr(&batcher_addr)
+                .wallet(deterministic_wallet(0))
+                .tls_config(tls_config)
+                .build()
+                .unwrap();
+            let result = client
+                .submit_multiple(&[random_verification_data(&mut seeded_rng(0))])
+                .await;
+            assert!(matches!(result, Err(SubmitError::TlsError(_))));
+        }
+    }
+}
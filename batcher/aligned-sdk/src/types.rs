@@ -1,15 +1,28 @@
-use ethers::core::k256::ecdsa::SigningKey;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
 use ethers::signers::Signer;
-use ethers::signers::Wallet;
 use ethers::types::Address;
 use ethers::types::Signature;
 use ethers::types::SignatureError;
+use ethers::types::H256;
+use lambdaworks_crypto::hash::poseidon::{starknet::PoseidonCairoStark252, Poseidon};
 use lambdaworks_crypto::merkle_tree::{
     merkle::MerkleTree, proof::Proof, traits::IsMerkleTreeBackend,
 };
+use lambdaworks_math::field::element::FieldElement;
+use lambdaworks_math::field::fields::fft_friendly::stark_252_prime_field::Stark252PrimeField;
+use lambdaworks_math::traits::ByteConversion;
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use sha3::{Digest, Keccak256};
 
+use crate::clock;
+use crate::errors::SubmitError;
+use crate::serde_compat;
+
 #[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
 pub enum ProvingSystemId {
     GnarkPlonkBls12_381,
@@ -22,6 +35,83 @@ pub enum ProvingSystemId {
     Risc0,
 }
 
+/// Size of the image id of Risc0 programs, which is sent as their vm program code.
+pub const RISC0_IMAGE_ID_SIZE: usize = 32;
+
+/// Size of the verification keys of Halo2 proofs, which hold the lengths of their constraint
+/// system, verifier key and params followed by the three of them.
+const HALO2_MAX_VERIFICATION_KEY_SIZE: usize = 12 + 2 * 1024 + 1024 + 4 * 1024;
+
+/// Artifacts that the verification data of a proving system holds, and the limits the batcher
+/// puts on their sizes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvingSystemCapabilities {
+    pub requires_verification_key: bool,
+    /// Whether the code of the program, or its image id, has to be sent along with the proof.
+    pub requires_vm_program: bool,
+    pub requires_pub_input: bool,
+    /// Whether public inputs can be sent. SP1 proofs are verified with or without them.
+    pub accepts_pub_input: bool,
+    /// Exact size of the vm program, for systems that identify programs by a fixed size id.
+    pub vm_program_size: Option<usize>,
+    pub max_verification_key_size: Option<usize>,
+}
+
+impl ProvingSystemId {
+    /// Returns every proving system supported by Aligned.
+    pub fn all() -> [ProvingSystemId; 7] {
+        [
+            ProvingSystemId::GnarkPlonkBls12_381,
+            ProvingSystemId::GnarkPlonkBn254,
+            ProvingSystemId::Groth16Bn254,
+            ProvingSystemId::SP1,
+            ProvingSystemId::Halo2KZG,
+            ProvingSystemId::Halo2IPA,
+            ProvingSystemId::Risc0,
+        ]
+    }
+
+    /// Returns which artifacts the verification data of the proving system holds.
+    pub fn capabilities(&self) -> ProvingSystemCapabilities {
+        match self {
+            ProvingSystemId::SP1 => ProvingSystemCapabilities {
+                requires_verification_key: false,
+                requires_vm_program: true,
+                requires_pub_input: false,
+                accepts_pub_input: true,
+                vm_program_size: None,
+                max_verification_key_size: None,
+            },
+            ProvingSystemId::Risc0 => ProvingSystemCapabilities {
+                requires_verification_key: false,
+                requires_vm_program: true,
+                requires_pub_input: true,
+                accepts_pub_input: true,
+                vm_program_size: Some(RISC0_IMAGE_ID_SIZE),
+                max_verification_key_size: None,
+            },
+            ProvingSystemId::Halo2KZG | ProvingSystemId::Halo2IPA => ProvingSystemCapabilities {
+                requires_verification_key: true,
+                requires_vm_program: false,
+                requires_pub_input: true,
+                accepts_pub_input: true,
+                vm_program_size: None,
+                max_verification_key_size: Some(HALO2_MAX_VERIFICATION_KEY_SIZE),
+            },
+            ProvingSystemId::GnarkPlonkBls12_381
+            | ProvingSystemId::GnarkPlonkBn254
+            | ProvingSystemId::Groth16Bn254 => ProvingSystemCapabilities {
+                requires_verification_key: true,
+                requires_vm_program: false,
+                requires_pub_input: true,
+                accepts_pub_input: true,
+                vm_program_size: None,
+                max_verification_key_size: None,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VerificationData {
     pub proving_system: ProvingSystemId,
@@ -30,20 +120,138 @@ pub struct VerificationData {
     pub verification_key: Option<Vec<u8>>,
     pub vm_program_code: Option<Vec<u8>>,
     pub proof_generator_addr: Address,
+    /// Application defined tag, stored in the batch data to find the proof later with
+    /// `tags::get_tagged_proofs`. It is not part of the commitment, so it is not signed and
+    /// doesn't change the batch merkle root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<[u8; 32]>,
+    /// Application defined metadata, such as the id of the job that generated the proof, echoed
+    /// back by the batcher in the `BatchInclusionData` of the proof. Like the tag it is not part
+    /// of the commitment, but it is not stored in the batch data. Only batchers supporting the
+    /// `user_metadata` feature take it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Vec<u8>>,
+    // Skipped for V1, so messages and batch data are the same as before schemas were versioned
+    #[serde(default, skip_serializing_if = "SchemaVersion::is_v1")]
+    pub schema_version: SchemaVersion,
+}
+
+impl VerificationData {
+    /// Version of the schema of the verification data built by this SDK.
+    pub const SCHEMA_VERSION: SchemaVersion = SchemaVersion::V1;
+
+    /// Maximum size of the metadata, which is kept by the batcher until the batch is responded.
+    pub const MAX_METADATA_SIZE: usize = 1 << 10;
+
+    /// Checks that the verification data holds the artifacts its proving system needs, as given
+    /// by `ProvingSystemId::capabilities`, and no others. Artifacts a system doesn't take are
+    /// rejected because they change the commitment of the proof: the auxiliary data commitment
+    /// is the one of the vm program if there is one, and of the verification key otherwise.
+    /// # Errors
+    /// * `InvalidVerificationData` if the proof is empty, an artifact is missing or not taken by
+    ///   the proving system, or has an invalid size.
+    pub fn validate(&self) -> Result<(), SubmitError> {
+        let proving_system = &self.proving_system;
+        let capabilities = proving_system.capabilities();
+        let invalid = |e: String| Err(SubmitError::InvalidVerificationData(e));
+
+        if self.proof.is_empty() {
+            return invalid(format!("the {:?} proof is empty", proving_system));
+        }
+
+        let artifacts = [
+            (
+                "verification key",
+                self.verification_key.as_ref(),
+                capabilities.requires_verification_key,
+                capabilities.requires_verification_key,
+            ),
+            (
+                "vm program",
+                self.vm_program_code.as_ref(),
+                capabilities.requires_vm_program,
+                capabilities.requires_vm_program,
+            ),
+            (
+                "public input",
+                self.pub_input.as_ref(),
+                capabilities.requires_pub_input,
+                capabilities.accepts_pub_input,
+            ),
+        ];
+        for (name, artifact, required, accepted) in artifacts {
+            match artifact {
+                None if required => {
+                    return invalid(format!("{:?} proofs require a {}", proving_system, name))
+                }
+                Some(_) if !accepted => {
+                    return invalid(format!("{:?} proofs don't take a {}", proving_system, name))
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(vm_program_size), Some(vm_program_code)) =
+            (capabilities.vm_program_size, &self.vm_program_code)
+        {
+            if vm_program_code.len() != vm_program_size {
+                return invalid(format!(
+                    "the {:?} vm program must be {} bytes, found {}",
+                    proving_system,
+                    vm_program_size,
+                    vm_program_code.len()
+                ));
+            }
+        }
+        if let (Some(max_verification_key_size), Some(verification_key)) = (
+            capabilities.max_verification_key_size,
+            &self.verification_key,
+        ) {
+            if verification_key.len() > max_verification_key_size {
+                return invalid(format!(
+                    "the {:?} verification key must be at most {} bytes, found {}",
+                    proving_system,
+                    max_verification_key_size,
+                    verification_key.len()
+                ));
+            }
+        }
+        if let Some(metadata) = &self.metadata {
+            if metadata.len() > Self::MAX_METADATA_SIZE {
+                return invalid(format!(
+                    "the metadata must be at most {} bytes, found {}",
+                    Self::MAX_METADATA_SIZE,
+                    metadata.len()
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct VerificationDataCommitment {
+    #[serde(deserialize_with = "serde_compat::bytes_or_hex")]
     pub proof_commitment: [u8; 32],
+    #[serde(deserialize_with = "serde_compat::bytes_or_hex")]
     pub pub_input_commitment: [u8; 32],
     // This could be either the VM code (ELF, bytecode) or the verification key
     // depending on the proving system.
+    #[serde(deserialize_with = "serde_compat::bytes_or_hex")]
     pub proving_system_aux_data_commitment: [u8; 32],
+    #[serde(deserialize_with = "serde_compat::bytes_or_hex")]
     pub proof_generator_addr: [u8; 20],
 }
 
 impl From<VerificationData> for VerificationDataCommitment {
     fn from(verification_data: VerificationData) -> Self {
+        VerificationDataCommitment::from(&verification_data)
+    }
+}
+
+impl From<&VerificationData> for VerificationDataCommitment {
+    fn from(verification_data: &VerificationData) -> Self {
         let mut hasher = Keccak256::new();
 
         // compute proof commitment
@@ -58,18 +266,10 @@ impl From<VerificationData> for VerificationDataCommitment {
         }
 
         // compute proving system auxiliary data commitment
-        let mut proving_system_aux_data_commitment = [0u8; 32];
-        // FIXME(marian): This should probably be reworked, for the moment when the proving
-        // system is SP1, `proving_system_aux_data` stands for the compiled ELF, while in the case
-        // of Groth16 and PLONK, stands for the verification key.
-
-        if let Some(vm_program_code) = &verification_data.vm_program_code {
-            hasher.update(vm_program_code);
-            proving_system_aux_data_commitment = hasher.finalize_reset().into();
-        } else if let Some(verification_key) = &verification_data.verification_key {
-            hasher.update(verification_key);
-            proving_system_aux_data_commitment = hasher.finalize_reset().into();
-        }
+        let proving_system_aux_data_commitment = proving_system_aux_data_commitment(
+            verification_data.vm_program_code.as_deref(),
+            verification_data.verification_key.as_deref(),
+        );
 
         // serialize proof generator address to bytes
         let proof_generator_addr = verification_data.proof_generator_addr.into();
@@ -83,28 +283,274 @@ impl From<VerificationData> for VerificationDataCommitment {
     }
 }
 
-#[derive(Clone, Default)]
-pub struct VerificationCommitmentBatch;
+pub(crate) fn proving_system_aux_data_commitment(
+    vm_program_code: Option<&[u8]>,
+    verification_key: Option<&[u8]>,
+) -> [u8; 32] {
+    // FIXME(marian): This should probably be reworked, for the moment when the proving
+    // system is SP1, `proving_system_aux_data` stands for the compiled ELF, while in the case
+    // of Groth16 and PLONK, stands for the verification key.
+    match vm_program_code.or(verification_key) {
+        Some(aux_data) => Keccak256::digest(aux_data).into(),
+        None => [0u8; 32],
+    }
+}
+
+/// Backend of the merkle trees of batches, which hashes the verification data commitments into
+/// leaves and the children of the nodes of trees of any arity.
+pub trait BatchMerkleTreeBackend:
+    IsMerkleTreeBackend<Node = [u8; 32], Data = VerificationDataCommitment>
+{
+    /// Hashes the children of a node, from left to right. For two children it's the same as
+    /// `hash_new_parent`.
+    fn hash_children(children: &[[u8; 32]]) -> [u8; 32];
+}
+
+/// Hash function of the leaves and nodes of the merkle trees of `CommitmentBatch`.
+pub trait Hasher: Clone + Default + Send + Sync {
+    /// Hashes the concatenation of the inputs.
+    fn hash(inputs: &[&[u8]]) -> [u8; 32];
+}
+
+impl Hasher for Keccak256 {
+    fn hash(inputs: &[&[u8]]) -> [u8; 32] {
+        digest_inputs::<Keccak256>(inputs)
+    }
+}
 
-impl IsMerkleTreeBackend for VerificationCommitmentBatch {
+impl Hasher for Sha256 {
+    fn hash(inputs: &[&[u8]]) -> [u8; 32] {
+        digest_inputs::<Sha256>(inputs)
+    }
+}
+
+fn digest_inputs<D: Digest>(inputs: &[&[u8]]) -> [u8; 32]
+where
+    [u8; 32]: From<sha3::digest::Output<D>>,
+{
+    let mut hasher = D::new();
+    for input in inputs {
+        hasher.update(input);
+    }
+    hasher.finalize().into()
+}
+
+/// Backend of the merkle trees of batches that hashes the concatenation of the fields of each
+/// verification data commitment into its leaf, and of the children of each node into the node,
+/// with the hash function `H`.
+#[derive(Clone, Debug, Default)]
+pub struct CommitmentBatch<H>(PhantomData<H>);
+
+/// Keccak backend of the merkle trees of batches, the one of the trees built by the batcher and
+/// verified by the Aligned service manager contract.
+pub type VerificationCommitmentBatch = CommitmentBatch<Keccak256>;
+
+/// SHA-256 backend of the merkle trees of batches, for deployments whose proofs are verified in
+/// zkVMs with SHA-256 precompiles, where it's cheaper than keccak. Roots of these trees can't be
+/// verified by the Aligned service manager contract.
+pub type Sha256CommitmentBatch = CommitmentBatch<Sha256>;
+
+impl<H: Hasher> IsMerkleTreeBackend for CommitmentBatch<H> {
     type Node = [u8; 32];
     type Data = VerificationDataCommitment;
 
     fn hash_data(leaf: &Self::Data) -> Self::Node {
-        let mut hasher = Keccak256::new();
-        hasher.update(leaf.proof_commitment);
-        hasher.update(leaf.pub_input_commitment);
-        hasher.update(leaf.proving_system_aux_data_commitment);
-        hasher.update(leaf.proof_generator_addr);
+        H::hash(&[
+            &leaf.proof_commitment,
+            &leaf.pub_input_commitment,
+            &leaf.proving_system_aux_data_commitment,
+            &leaf.proof_generator_addr,
+        ])
+    }
 
-        hasher.finalize().into()
+    fn hash_new_parent(child_1: &Self::Node, child_2: &Self::Node) -> Self::Node {
+        H::hash(&[child_1, child_2])
+    }
+}
+
+impl<H: Hasher> CommitmentBatch<H> {
+    /// Hashes the children of a node of a tree of any arity, from left to right. For two
+    /// children it's the same as `hash_new_parent`.
+    pub fn hash_children(children: &[[u8; 32]]) -> [u8; 32] {
+        let inputs: Vec<&[u8]> = children.iter().map(|child| child.as_slice()).collect();
+        H::hash(&inputs)
+    }
+}
+
+impl<H: Hasher> BatchMerkleTreeBackend for CommitmentBatch<H> {
+    fn hash_children(children: &[[u8; 32]]) -> [u8; 32] {
+        CommitmentBatch::<H>::hash_children(children)
+    }
+}
+
+/// Poseidon backend of the merkle trees of batches, with the Poseidon hash of Starknet over the
+/// Stark252 field, for rollups that verify inclusion proofs inside SNARK circuits, where keccak
+/// takes orders of magnitude more constraints. Roots of these trees can't be verified by the
+/// Aligned service manager contract.
+///
+/// Nodes are field elements in big-endian bytes. Leaves hash the 128-bit halves of each
+/// commitment, most significant first, followed by the proof generator address, with
+/// `hash_many`, since 32-byte commitments don't fit in a field element. Two children are hashed
+/// with `hash`, as `poseidon_hash` does in Cairo, and four with `hash_many`.
+#[derive(Clone, Debug, Default)]
+pub struct PoseidonCommitmentBatch;
+
+type Stark252FieldElement = FieldElement<Stark252PrimeField>;
+
+impl PoseidonCommitmentBatch {
+    fn field_element(bytes: &[u8]) -> Stark252FieldElement {
+        // Shorter inputs are left padded with zeros, and nodes out of the field, which no tree
+        // has, are reduced
+        let mut padded = [0u8; 32];
+        padded[32 - bytes.len()..].copy_from_slice(bytes);
+        Stark252FieldElement::from_bytes_be(&padded).expect("32 bytes are a field element")
+    }
+
+    fn node(element: &Stark252FieldElement) -> [u8; 32] {
+        element.to_bytes_be()
+    }
+}
+
+impl IsMerkleTreeBackend for PoseidonCommitmentBatch {
+    type Node = [u8; 32];
+    type Data = VerificationDataCommitment;
+
+    fn hash_data(leaf: &Self::Data) -> Self::Node {
+        let inputs: Vec<Stark252FieldElement> = [
+            leaf.proof_commitment,
+            leaf.pub_input_commitment,
+            leaf.proving_system_aux_data_commitment,
+        ]
+        .iter()
+        .flat_map(|commitment| commitment.chunks_exact(16))
+        .chain([leaf.proof_generator_addr.as_slice()])
+        .map(Self::field_element)
+        .collect();
+
+        Self::node(&PoseidonCairoStark252::hash_many(&inputs))
     }
 
     fn hash_new_parent(child_1: &Self::Node, child_2: &Self::Node) -> Self::Node {
-        let mut hasher = Keccak256::new();
-        hasher.update(child_1);
-        hasher.update(child_2);
-        hasher.finalize().into()
+        Self::node(&PoseidonCairoStark252::hash(
+            &Self::field_element(child_1),
+            &Self::field_element(child_2),
+        ))
+    }
+}
+
+impl BatchMerkleTreeBackend for PoseidonCommitmentBatch {
+    fn hash_children(children: &[[u8; 32]]) -> [u8; 32] {
+        if let [child_1, child_2] = children {
+            return Self::hash_new_parent(child_1, child_2);
+        }
+
+        let inputs: Vec<Stark252FieldElement> = children
+            .iter()
+            .map(|child| Self::field_element(child))
+            .collect();
+        Self::node(&PoseidonCairoStark252::hash_many(&inputs))
+    }
+}
+
+/// Version of the schema with which a `VerificationData`, `BatchInclusionData` or
+/// `AlignedVerificationData` was serialized, so files and messages of previous releases keep
+/// loading as fields are added. Values serialized before the version was tagged have no version
+/// and are V1. Fields added later must have a default, which is the value of the field for older
+/// versions, and the version is bumped when a missing field isn't the same as its default, so
+/// readers can tell the two apart. Values of versions newer than the ones of the SDK fail to
+/// load instead of losing their new fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SchemaVersion {
+    /// Values without a version tag
+    #[default]
+    V1,
+    /// Aligned verification data with batch metadata, which is unknown in V1 files when missing
+    V2,
+}
+
+impl SchemaVersion {
+    pub fn is_v1(&self) -> bool {
+        *self == SchemaVersion::V1
+    }
+}
+
+/// Version of a batch, which defines the arity of the merkle tree of its verification data
+/// commitments. Only V1 batches can be verified by the Aligned service manager contract, so they
+/// are the only ones built by the batcher. V2 trees have half the levels of V1 trees, so checking
+/// a path takes half the hashes, but each level of the path has three siblings instead of one, so
+/// paths are 1.5 times longer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchVersion {
+    /// Binary merkle tree
+    #[default]
+    V1,
+    /// Quaternary merkle tree
+    V2,
+}
+
+impl BatchVersion {
+    pub fn arity(&self) -> usize {
+        match self {
+            BatchVersion::V1 => 2,
+            BatchVersion::V2 => 4,
+        }
+    }
+
+    pub fn is_v1(&self) -> bool {
+        *self == BatchVersion::V1
+    }
+
+    /// Checks that a leaf is included in a batch merkle tree of this version with the given root.
+    /// The path holds the siblings of the node at each level, from the leaves to the root and
+    /// from left to right, skipping the node itself. The position of the node among its siblings
+    /// is given by the digit of the index of the leaf in base arity for that level, starting from
+    /// the least significant one. As in the Aligned service manager contract, digits of the index
+    /// beyond the length of the path are not checked.
+    /// # Arguments
+    /// * `root` - The root of the batch merkle tree.
+    /// * `leaf` - The leaf to check, which is the hash of a `VerificationDataCommitment`.
+    /// * `path` - The siblings of the node at each level of the tree.
+    /// * `index` - The position of the leaf in the batch.
+    /// # Returns
+    /// * Whether the leaf is included in the tree.
+    pub fn verify_merkle_inclusion(
+        &self,
+        root: &[u8; 32],
+        leaf: &[u8; 32],
+        path: &[[u8; 32]],
+        index: usize,
+    ) -> bool {
+        self.verify_merkle_inclusion_with_backend::<VerificationCommitmentBatch>(
+            root, leaf, path, index,
+        )
+    }
+
+    /// Checks that a leaf is included in a batch merkle tree of this version built with another
+    /// backend, as `verify_merkle_inclusion` does for keccak trees.
+    pub fn verify_merkle_inclusion_with_backend<B: BatchMerkleTreeBackend>(
+        &self,
+        root: &[u8; 32],
+        leaf: &[u8; 32],
+        path: &[[u8; 32]],
+        index: usize,
+    ) -> bool {
+        let arity = self.arity();
+        if path.len() % (arity - 1) != 0 {
+            return false;
+        }
+
+        let mut node = *leaf;
+        let mut index = index;
+        for siblings in path.chunks_exact(arity - 1) {
+            let position = index % arity;
+            let mut children = siblings.to_vec();
+            children.insert(position, node);
+
+            node = B::hash_children(&children);
+            index /= arity;
+        }
+
+        node == *root
     }
 }
 
@@ -115,12 +561,28 @@ pub struct BatchInclusionData {
     pub batch_merkle_root: [u8; 32],
     pub batch_inclusion_proof: Proof<[u8; 32]>,
     pub index_in_batch: usize,
+    // Skipped for V1 batches, so messages are the same as before batches were versioned
+    #[serde(default, skip_serializing_if = "BatchVersion::is_v1")]
+    pub batch_version: BatchVersion,
+    /// The submission id of the client message this responds to, echoed by the batcher.
+    // Skipped when not set, so messages are the same as before submission ids existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission_id: Option<u64>,
+    /// The metadata of the verification data of the proof, see `VerificationData::metadata`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Vec<u8>>,
+    // Skipped for V1, so messages are the same as before schemas were versioned
+    #[serde(default, skip_serializing_if = "SchemaVersion::is_v1")]
+    pub schema_version: SchemaVersion,
 }
 
 impl BatchInclusionData {
-    pub fn new(
+    /// Version of the schema of the inclusion data built by this SDK.
+    pub const SCHEMA_VERSION: SchemaVersion = SchemaVersion::V1;
+
+    pub fn new<B: BatchMerkleTreeBackend>(
         verification_data_batch_index: usize,
-        batch_merkle_tree: &MerkleTree<VerificationCommitmentBatch>,
+        batch_merkle_tree: &MerkleTree<B>,
     ) -> Self {
         let batch_inclusion_proof = batch_merkle_tree
             .get_proof_by_pos(verification_data_batch_index)
@@ -130,53 +592,527 @@ impl BatchInclusionData {
             batch_merkle_root: batch_merkle_tree.root,
             batch_inclusion_proof,
             index_in_batch: verification_data_batch_index,
+            batch_version: BatchVersion::V1,
+            submission_id: None,
+            metadata: None,
+            schema_version: Self::SCHEMA_VERSION,
         }
     }
+
+    /// Checks that the verification data commitment is included in the batch, with the same
+    /// math as `verifyBatchInclusion` of the Aligned service manager contract, so that a proof
+    /// for which this returns true is verified on-chain once its batch is responded. The contract
+    /// hashes the leaf and each pair of nodes with keccak, placing the node on the left when the
+    /// bit of the index for its level is 0, so only V1 batches can be verified. Empty paths are
+    /// rejected, since the merkle library of the contract reverts on them.
+    /// # Arguments
+    /// * `verification_data_commitment` - The commitment of the verification data of the proof.
+    /// # Returns
+    /// * Whether the contract would find the commitment included in the batch.
+    pub fn verify(&self, verification_data_commitment: &VerificationDataCommitment) -> bool {
+        let merkle_path = &self.batch_inclusion_proof.merkle_path;
+        if !self.batch_version.is_v1() || merkle_path.is_empty() {
+            return false;
+        }
+
+        let leaf = VerificationCommitmentBatch::hash_data(verification_data_commitment);
+        BatchVersion::V1.verify_merkle_inclusion(
+            &self.batch_merkle_root,
+            &leaf,
+            merkle_path,
+            self.index_in_batch,
+        )
+    }
+
+    /// Checks that the verification data commitment is included in a batch whose tree was built
+    /// with another backend, such as `Sha256CommitmentBatch`, of any version. There is no
+    /// contract to match for these trees, so the path is checked as by
+    /// `BatchVersion::verify_merkle_inclusion_with_backend`.
+    /// # Arguments
+    /// * `verification_data_commitment` - The commitment of the verification data of the proof.
+    /// # Returns
+    /// * Whether the commitment is included in the batch.
+    pub fn verify_with_backend<B: BatchMerkleTreeBackend>(
+        &self,
+        verification_data_commitment: &VerificationDataCommitment,
+    ) -> bool {
+        let leaf = B::hash_data(verification_data_commitment);
+        self.batch_version
+            .verify_merkle_inclusion_with_backend::<B>(
+                &self.batch_merkle_root,
+                &leaf,
+                &self.batch_inclusion_proof.merkle_path,
+                self.index_in_batch,
+            )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientMessage {
     pub verification_data: VerificationData,
     pub signature: Signature,
+    // Skipped when not set, so messages are the same as before replay protection existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_protection: Option<ReplayProtection>,
+    /// Identifies the message among the ones of its connection, so that the batcher echoes it in
+    /// its response and submissions can share a connection. It's not signed, since it only
+    /// routes the response. Batchers only echo it if they support the `submission_ids` feature.
+    // Skipped when not set, so messages are the same as before submission ids existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission_id: Option<u64>,
 }
 
 impl ClientMessage {
     /// Client message is a wrap around verification data and its signature.
     /// The signature is obtained by calculating the commitments and then hashing them.
-    pub async fn new(verification_data: VerificationData, wallet: Wallet<SigningKey>) -> Self {
-        let hashed_leaf = VerificationCommitmentBatch::hash_data(&verification_data.clone().into());
-        let signature = wallet.sign_message(hashed_leaf).await.unwrap();
+    /// Any `ethers` signer can sign it, such as a `LocalWallet` or a hardware wallet.
+    /// # Errors
+    /// * `SignerError` if the wallet fails to sign the message.
+    pub async fn new<S: Signer>(
+        verification_data: VerificationData,
+        wallet: &S,
+    ) -> Result<Self, SubmitError> {
+        Self::new_with_signer(verification_data, |hashed_leaf| {
+            sign_with_signer(wallet, hashed_leaf)
+        })
+        .await
+    }
 
-        ClientMessage {
+    /// Builds the client message asking `sign_message` for the signature, instead of signing it
+    /// with an in-process wallet. `sign_message` receives the 32 bytes to sign, which have to be
+    /// signed as an Ethereum message (EIP-191), in the same way `Wallet::sign_message` does.
+    pub async fn new_with_signer<F, Fut, E>(
+        verification_data: VerificationData,
+        sign_message: F,
+    ) -> Result<Self, E>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, E>>,
+    {
+        Self::build(verification_data, None, sign_message).await
+    }
+
+    /// Builds the client message with a nonce and an expiry, which are signed along with the
+    /// commitment of the verification data so that the batcher can reject the message if it's
+    /// sent again. See `ClientMessage::new_with_signer`.
+    pub async fn new_with_replay_protection<F, Fut, E>(
+        verification_data: VerificationData,
+        replay_protection: ReplayProtection,
+        sign_message: F,
+    ) -> Result<Self, E>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, E>>,
+    {
+        Self::build(verification_data, Some(replay_protection), sign_message).await
+    }
+
+    async fn build<F, Fut, E>(
+        verification_data: VerificationData,
+        replay_protection: Option<ReplayProtection>,
+        sign_message: F,
+    ) -> Result<Self, E>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, E>>,
+    {
+        let signed_hash = signed_hash(&verification_data, replay_protection.as_ref());
+        let signature = sign_message(signed_hash).await?;
+
+        Ok(ClientMessage {
             verification_data,
             signature,
-        }
+            replay_protection,
+            submission_id: None,
+        })
+    }
+
+    /// Returns the 32 bytes signed by the prover: the hashed commitment of the verification
+    /// data, or the replay protected hash of it if the message has a nonce and an expiry.
+    pub fn signed_hash(&self) -> [u8; 32] {
+        signed_hash(&self.verification_data, self.replay_protection.as_ref())
     }
 
     /// The signature of the message is verified, and when it correct, the
     /// recovered address from the signature is returned.
     pub fn verify_signature(&self) -> Result<Address, SignatureError> {
-        let hashed_leaf =
-            VerificationCommitmentBatch::hash_data(&self.verification_data.clone().into());
+        let signed_hash = self.signed_hash();
 
-        // IMPORTANT: If the `.to_vec()` conversion is not made for `hashed_leaf`, the recovered
+        // IMPORTANT: If the `.to_vec()` conversion is not made for `signed_hash`, the recovered
         // address from the signature will not be the same as the one who signed. This is a bug in
         // the ethers-rs library
-        let recovered = self.signature.recover(hashed_leaf.to_vec())?;
-        self.signature.verify(hashed_leaf.to_vec(), recovered)?;
+        let recovered = self.signature.recover(signed_hash.to_vec())?;
+        self.signature.verify(signed_hash.to_vec(), recovered)?;
         Ok(recovered)
     }
 }
 
+fn signed_hash(
+    verification_data: &VerificationData,
+    replay_protection: Option<&ReplayProtection>,
+) -> [u8; 32] {
+    let hashed_leaf = VerificationCommitmentBatch::hash_data(&verification_data.clone().into());
+    match replay_protection {
+        Some(replay_protection) => replay_protection.protected_hash(&hashed_leaf),
+        None => hashed_leaf,
+    }
+}
+
+/// Last nonce returned by `ReplayProtection::new` in this process.
+static LAST_NONCE: AtomicU64 = AtomicU64::new(0);
+
+/// Nonce and expiry of a client message. The batcher rejects the nonces it already accepted from
+/// the same address before their expiry, and rejects messages after their expiry, so a captured
+/// message can't be sent again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayProtection {
+    pub nonce: u64,
+    /// Unix timestamp, in seconds, after which the message is rejected.
+    pub expires_at: u64,
+}
+
+impl ReplayProtection {
+    /// Returns a replay protection expiring after `validity`. The nonce is the current time in
+    /// microseconds, so it doesn't repeat across runs without keeping state, and it's increased if
+    /// needed so that it's greater than the previous one returned in this process.
+    pub fn new(validity: Duration) -> Self {
+        let now = clock::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let now_micros = u64::try_from(now.as_micros()).unwrap_or(u64::MAX);
+
+        let previous = LAST_NONCE
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                Some(now_micros.max(last.saturating_add(1)))
+            })
+            .expect("Closure always returns a nonce");
+
+        ReplayProtection {
+            nonce: now_micros.max(previous.saturating_add(1)),
+            expires_at: now.saturating_add(validity).as_secs(),
+        }
+    }
+
+    /// Returns the hash signed for a message with this replay protection: the keccak256 of the
+    /// hash it's signed over without it, which is the hashed commitment of the verification data
+    /// of a client message, followed by the nonce and the expiry as big endian integers.
+    pub fn protected_hash(&self, unprotected_hash: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(unprotected_hash);
+        hasher.update(self.nonce.to_be_bytes());
+        hasher.update(self.expires_at.to_be_bytes());
+        hasher.finalize().into()
+    }
+}
+
+/// Entry of a `BatchedClientMessage`: the verification data along with its inclusion proof in
+/// the merkle tree whose root was signed by the client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchedClientMessageEntry {
+    pub verification_data: VerificationData,
+    pub entries_inclusion_proof: Proof<[u8; 32]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchedClientMessage {
+    pub entries: Vec<BatchedClientMessageEntry>,
+    pub entries_merkle_root: [u8; 32],
+    /// Number of entries, signed along with their merkle root so that entries can't be dropped
+    /// from a captured message, nor the padding entry of the tree added to it.
+    pub entry_count: u64,
+    pub signature: Signature,
+    // Skipped when not set, so messages are the same as before replay protection existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay_protection: Option<ReplayProtection>,
+}
+
+impl BatchedClientMessage {
+    /// Batched client message is a wrap around several verification data entries and a single
+    /// signature. The commitments of the entries are used to build a merkle tree, whose root is
+    /// signed. Each entry carries its inclusion proof in that tree, so that the batcher only has
+    /// to check the signature once.
+    /// At least two entries are required to build the tree.
+    /// # Errors
+    /// * `GenericError` if there are less than two entries.
+    /// * `SignerError` if the wallet fails to sign the entries merkle root.
+    pub async fn new<S: Signer>(
+        verification_data: Vec<VerificationData>,
+        wallet: &S,
+    ) -> Result<Self, SubmitError> {
+        Self::new_with_signer(verification_data, |entries_merkle_root| {
+            sign_with_signer(wallet, entries_merkle_root)
+        })
+        .await
+    }
+
+    /// Builds the batched client message asking `sign_message` for the signature of the entries
+    /// merkle root and count. See `ClientMessage::new_with_signer`.
+    /// # Errors
+    /// * `GenericError` if there are less than two entries.
+    /// * Any error returned by `sign_message`.
+    pub async fn new_with_signer<F, Fut, E>(
+        verification_data: Vec<VerificationData>,
+        sign_message: F,
+    ) -> Result<Self, E>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, E>>,
+        E: From<SubmitError>,
+    {
+        Self::build(verification_data, None, sign_message).await
+    }
+
+    /// Builds the batched client message with a nonce and an expiry, which are signed along with
+    /// the entries merkle root and count, as in `ClientMessage::new_with_replay_protection`.
+    /// # Errors
+    /// * `GenericError` if there are less than two entries.
+    /// * Any error returned by `sign_message`.
+    pub async fn new_with_replay_protection<F, Fut, E>(
+        verification_data: Vec<VerificationData>,
+        replay_protection: ReplayProtection,
+        sign_message: F,
+    ) -> Result<Self, E>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, E>>,
+        E: From<SubmitError>,
+    {
+        Self::build(verification_data, Some(replay_protection), sign_message).await
+    }
+
+    async fn build<F, Fut, E>(
+        verification_data: Vec<VerificationData>,
+        replay_protection: Option<ReplayProtection>,
+        sign_message: F,
+    ) -> Result<Self, E>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, E>>,
+        E: From<SubmitError>,
+    {
+        // The merkle tree of lambdaworks can't be built with less than two leaves
+        if verification_data.len() < 2 {
+            return Err(SubmitError::GenericError(format!(
+                "A batched client message needs at least two entries, found {}",
+                verification_data.len()
+            ))
+            .into());
+        }
+        let entry_count = verification_data.len() as u64;
+
+        let commitments: Vec<VerificationDataCommitment> = verification_data
+            .iter()
+            .map(|verification_data| verification_data.clone().into())
+            .collect();
+
+        let entries_merkle_tree = MerkleTree::<VerificationCommitmentBatch>::build(&commitments);
+        let signed_hash = entries_signed_hash(
+            &entries_merkle_tree.root,
+            entry_count,
+            replay_protection.as_ref(),
+        );
+        let signature = sign_message(signed_hash).await?;
+
+        let entries = verification_data
+            .into_iter()
+            .enumerate()
+            .map(|(idx, verification_data)| BatchedClientMessageEntry {
+                verification_data,
+                entries_inclusion_proof: entries_merkle_tree.get_proof_by_pos(idx).unwrap(),
+            })
+            .collect();
+
+        Ok(BatchedClientMessage {
+            entries,
+            entries_merkle_root: entries_merkle_tree.root,
+            entry_count,
+            signature,
+            replay_protection,
+        })
+    }
+
+    /// Returns the 32 bytes signed by the client: the keccak256 of the entries merkle root
+    /// followed by the number of entries as a big endian integer, or the replay protected hash of
+    /// it if the message has a nonce and an expiry.
+    pub fn signed_hash(&self) -> [u8; 32] {
+        entries_signed_hash(
+            &self.entries_merkle_root,
+            self.entry_count,
+            self.replay_protection.as_ref(),
+        )
+    }
+
+    /// The signature of the entries merkle root and count is verified, and when it correct, the
+    /// recovered address from the signature is returned.
+    pub fn verify_signature(&self) -> Result<Address, SignatureError> {
+        let signed_hash = self.signed_hash();
+
+        // See `ClientMessage::verify_signature` for why the hash is converted to a vector
+        let recovered = self.signature.recover(signed_hash.to_vec())?;
+        self.signature.verify(signed_hash.to_vec(), recovered)?;
+        Ok(recovered)
+    }
+
+    /// Checks that the message has the signed number of entries, and that every entry is
+    /// included in the signed merkle root at its position.
+    pub fn verify_entries(&self) -> bool {
+        self.entries.len() as u64 == self.entry_count
+            && self.entries.iter().enumerate().all(|(idx, entry)| {
+                entry
+                    .entries_inclusion_proof
+                    .verify::<VerificationCommitmentBatch>(
+                        &self.entries_merkle_root,
+                        idx,
+                        &entry.verification_data.clone().into(),
+                    )
+            })
+    }
+}
+
+fn entries_signed_hash(
+    entries_merkle_root: &[u8; 32],
+    entry_count: u64,
+    replay_protection: Option<&ReplayProtection>,
+) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(entries_merkle_root);
+    hasher.update(entry_count.to_be_bytes());
+    let entries_hash = hasher.finalize().into();
+    match replay_protection {
+        Some(replay_protection) => replay_protection.protected_hash(&entries_hash),
+        None => entries_hash,
+    }
+}
+
+/// A client message whose proof is paid for by a sponsor instead of the address that signed it.
+/// The prover signs the client message as usual, and the sponsor signs the sponsorship hash of
+/// that message, authorizing the batcher to charge its balance for that proof and prover only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SponsoredClientMessage {
+    pub client_message: ClientMessage,
+    pub sponsor_signature: Signature,
+}
+
+impl SponsoredClientMessage {
+    /// Sponsors the client message signed by a prover with the sponsor wallet.
+    /// # Errors
+    /// * `InvalidSignature` if the signature of the prover is not valid.
+    /// * `SignerError` if the sponsor wallet fails to sign the sponsorship hash.
+    pub async fn new<S: Signer>(
+        client_message: ClientMessage,
+        sponsor_wallet: &S,
+    ) -> Result<Self, SubmitError> {
+        Self::new_with_signer(client_message, |sponsorship_hash| {
+            sign_with_signer(sponsor_wallet, sponsorship_hash)
+        })
+        .await
+    }
+
+    /// Sponsors the client message signed by a prover, asking `sign_message` for the signature
+    /// of the sponsorship hash. See `ClientMessage::new_with_signer`.
+    /// # Errors
+    /// * `InvalidSignature` if the signature of the prover is not valid.
+    /// * Any error returned by `sign_message`.
+    pub async fn new_with_signer<F, Fut>(
+        client_message: ClientMessage,
+        sign_message: F,
+    ) -> Result<Self, SubmitError>
+    where
+        F: Fn([u8; 32]) -> Fut,
+        Fut: Future<Output = Result<Signature, SubmitError>>,
+    {
+        let prover_addr = client_message.verify_signature()?;
+        let sponsor_signature =
+            sign_message(sponsorship_hash(&client_message.signed_hash(), prover_addr)).await?;
+
+        Ok(SponsoredClientMessage {
+            client_message,
+            sponsor_signature,
+        })
+    }
+
+    /// Both signatures of the message are verified, and when they are correct, the recovered
+    /// addresses of the prover and the sponsor are returned, in that order.
+    pub fn verify_signatures(&self) -> Result<(Address, Address), SignatureError> {
+        let prover_addr = self.client_message.verify_signature()?;
+        let sponsorship_hash = sponsorship_hash(&self.client_message.signed_hash(), prover_addr);
+
+        // See `ClientMessage::verify_signature` for why the hash is converted to a vector
+        let sponsor_addr = self.sponsor_signature.recover(sponsorship_hash.to_vec())?;
+        self.sponsor_signature
+            .verify(sponsorship_hash.to_vec(), sponsor_addr)?;
+        Ok((prover_addr, sponsor_addr))
+    }
+}
+
+/// Returns the hash signed by the sponsor of a proof: the keccak256 of the hash signed by the
+/// prover, as returned by `ClientMessage::signed_hash`, followed by the address of the prover.
+/// The nonce of a replay protected message is part of that hash, so the sponsorship can't be
+/// reused with another nonce.
+pub fn sponsorship_hash(signed_hash: &[u8; 32], prover_addr: Address) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(signed_hash);
+    hasher.update(prover_addr.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Signs the 32 bytes as an Ethereum message with any `ethers` signer.
+async fn sign_with_signer<S: Signer>(
+    wallet: &S,
+    message: [u8; 32],
+) -> Result<Signature, SubmitError> {
+    wallet
+        .sign_message(message)
+        .await
+        .map_err(|e| SubmitError::SignerError(e.to_string()))
+}
+
+/// Proof that a verification data was included in a batch.
+/// It's saved to files by the CLI, so files saved by previous releases have to keep loading.
+/// Byte arrays can be read from hex strings, fields added later must have a default, and unknown
+/// fields are ignored. See `test_files/aligned_verification_data` for files of previous releases.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct AlignedVerificationData {
     pub verification_data_commitment: VerificationDataCommitment,
+    #[serde(deserialize_with = "serde_compat::bytes_or_hex")]
     pub batch_merkle_root: [u8; 32],
+    #[serde(deserialize_with = "serde_compat::merkle_proof")]
     pub batch_inclusion_proof: Proof<[u8; 32]>,
     pub index_in_batch: usize,
+    #[serde(default, skip_serializing_if = "BatchVersion::is_v1")]
+    pub batch_version: BatchVersion,
+    /// Where the batch was created and verified on-chain. Files saved by previous releases and
+    /// responses of the batcher don't have it, see `batch_metadata::enrich`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_metadata: Option<BatchMetadata>,
+    /// The metadata of the verification data of the proof, see `VerificationData::metadata`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Vec<u8>>,
+    /// Version of the schema of the data, V1 for files saved before it was tagged, see `migrate`.
+    #[serde(default, skip_serializing_if = "SchemaVersion::is_v1")]
+    pub schema_version: SchemaVersion,
+}
+
+/// Block and transaction of an event of the Aligned service manager contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventLocation {
+    pub block_number: u64,
+    pub tx_hash: H256,
+}
+
+/// Where a batch was created and verified on-chain, from its `NewBatch` and `BatchVerified`
+/// events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchMetadata {
+    pub created: EventLocation,
+    /// Not set while the batch is not verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified: Option<EventLocation>,
 }
 
 impl AlignedVerificationData {
+    /// Version of the schema of the aligned verification data built by this SDK, and to which
+    /// `migrate` brings older ones.
+    pub const SCHEMA_VERSION: SchemaVersion = SchemaVersion::V2;
+
     pub fn new(
         verification_data_commitment: &VerificationDataCommitment,
         inclusion_data: &BatchInclusionData,
@@ -190,12 +1126,819 @@ impl AlignedVerificationData {
             batch_merkle_root,
             batch_inclusion_proof: batch_inclusion_proof.clone(),
             index_in_batch,
+            batch_version: inclusion_data.batch_version,
+            batch_metadata: None,
+            metadata: inclusion_data.metadata.clone(),
+            schema_version: Self::SCHEMA_VERSION,
         }
     }
+
+    /// Brings an aligned verification data of an older schema, such as the ones of files saved
+    /// by previous releases, to `SCHEMA_VERSION`. Missing fields were already set to their
+    /// defaults when it was loaded, so only the version changes: a V1 aligned verification data
+    /// without batch metadata is left without it, as metadata that is not known yet.
+    /// # Returns
+    /// * Whether the aligned verification data was of an older schema.
+    pub fn migrate(&mut self) -> bool {
+        let migrated = self.schema_version < Self::SCHEMA_VERSION;
+        self.schema_version = Self::SCHEMA_VERSION;
+        migrated
+    }
+}
+
+/// First message of the batcher to clients that announce their protocol version in the
+/// `PROTOCOL_VERSION_HEADER` of the websocket handshake, holding its protocol version and the
+/// features of the protocol it supports. Clients that don't announce it receive only the protocol
+/// version, as 2 big-endian bytes.
+/// Features are names, so that clients can read the handshake of batchers with features they
+/// don't know.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolHandshake {
+    pub protocol_version: u16,
+    pub features: Vec<String>,
+    /// The X25519 public key to which clients can encrypt their messages, published by batchers
+    /// supporting the `encrypted_payloads` feature.
+    // Skipped when not set, so handshakes are the same as before encryption existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_public_key: Option<[u8; 32]>,
+    /// The session of the connection, opened by batchers supporting the `session_resumption`
+    /// feature for the clients that announce it, as described in `session`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionHandshake>,
+}
+
+/// Session of a connection, sent in the handshake. Its token is the one the client sent to
+/// resume it, or a new one if the session was opened by this connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionHandshake {
+    pub token: String,
+    /// Number of proofs of the session the batcher accepted so far, which the client doesn't
+    /// need to send again.
+    pub accepted_proofs: usize,
+}
+
+impl ProtocolHandshake {
+    /// The handshake of a batcher that only sends its protocol version, which supports none of
+    /// the features.
+    pub fn without_features(protocol_version: u16) -> Self {
+        ProtocolHandshake {
+            protocol_version,
+            features: Vec::new(),
+            encryption_public_key: None,
+            session: None,
+        }
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|supported| supported == feature)
+    }
+}
+
+/// Codes sent by the batcher in the close frame of the connection when it rejects a
+/// message. They belong to the range the WebSocket protocol reserves for applications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum RejectionCode {
+    InvalidSignature = 4000,
+    InsufficientBalance = 4001,
+    ProofTooLarge = 4002,
+    InvalidProof = 4003,
+    InvalidMessage = 4004,
+    ReplayedMessage = 4005,
+}
+
+impl TryFrom<u16> for RejectionCode {
+    type Error = u16;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        match code {
+            4000 => Ok(RejectionCode::InvalidSignature),
+            4001 => Ok(RejectionCode::InsufficientBalance),
+            4002 => Ok(RejectionCode::ProofTooLarge),
+            4003 => Ok(RejectionCode::InvalidProof),
+            4004 => Ok(RejectionCode::InvalidMessage),
+            4005 => Ok(RejectionCode::ReplayedMessage),
+            _ => Err(code),
+        }
+    }
+}
+
+impl RejectionCode {
+    /// The code of a rejection, also sent in the close frame after its error response.
+    pub fn of(rejection: &Rejection) -> Self {
+        match rejection {
+            Rejection::InvalidSignature => RejectionCode::InvalidSignature,
+            Rejection::InsufficientBalance { .. } => RejectionCode::InsufficientBalance,
+            Rejection::ProofTooLarge { .. } => RejectionCode::ProofTooLarge,
+            Rejection::InvalidProof { .. } => RejectionCode::InvalidProof,
+            Rejection::InvalidMessage { .. } => RejectionCode::InvalidMessage,
+            Rejection::ReplayedMessage { .. } => RejectionCode::ReplayedMessage,
+        }
+    }
+}
+
+/// Why the batcher rejected a message, with the details clients need to fix it. Serialized
+/// with the name of its `RejectionCode` in the `code` field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum Rejection {
+    InvalidSignature,
+    /// The payer has no balance in the batcher payment service.
+    InsufficientBalance {
+        address: Address,
+    },
+    ProofTooLarge {
+        proof_size: usize,
+        max_proof_size: usize,
+    },
+    /// The proof didn't verify when pre-verified by the batcher.
+    InvalidProof {
+        proving_system: ProvingSystemId,
+    },
+    /// The message couldn't be read, or its entries are not the ones signed.
+    InvalidMessage {
+        reason: String,
+    },
+    /// The nonce of the message was already used, or the message expired.
+    ReplayedMessage {
+        reason: String,
+    },
+}
+
+/// Response sent by batchers supporting the `error_responses` feature, in a text frame, when
+/// they reject a message. The connection is then closed with the `RejectionCode` of the
+/// rejection, as for clients that don't support the feature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    #[serde(flatten)]
+    pub rejection: Rejection,
+    /// The submission id of the rejected message, if it had one and could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission_id: Option<u64>,
 }
 
-#[derive(Debug, Clone)]
+/// Query of the status of proofs by the commitments of their verification data, sent in a text
+/// frame to batchers supporting the `inclusion_queries` feature, which answer it with an
+/// `InclusionQueryResponse` in a text frame. It isn't signed, as the inclusion data of a proof
+/// is public once its batch is submitted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionQuery {
+    pub commitments: Vec<VerificationDataCommitment>,
+}
+
+/// Response of the batcher to an `InclusionQuery`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionQueryResponse {
+    /// The status of each proof, in the order of the commitments of the query.
+    pub statuses: Vec<InclusionStatus>,
+}
+
+/// What the batcher knows about a proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InclusionStatus {
+    /// The proof is in the batch queue, waiting for its batch.
+    Pending,
+    /// The proof was included in a batch, with this inclusion data.
+    Included(BatchInclusionData),
+    /// The batcher didn't receive the proof, or it no longer keeps the batch that included it.
+    Unknown,
+}
+
+/// Query of the status of a proof by the commitment of its verification data, sent in a text
+/// frame to batchers supporting the `submission_status` feature, which answer it with a
+/// `SubmissionStatusResponse` in a text frame. As an `InclusionQuery`, it isn't signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetSubmissionStatus {
+    pub verification_data_commitment: VerificationDataCommitment,
+}
+
+/// Response of the batcher to a `GetSubmissionStatus`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionStatusResponse {
+    pub status: SubmissionStatus,
+}
+
+/// How far a proof has gone in the batcher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionStatus {
+    /// The proof is in the batch queue, waiting for its batch.
+    Pending,
+    /// The proof is in a batch whose verification task is being created.
+    Batched {
+        batch_merkle_root: [u8; 32],
+        index_in_batch: usize,
+    },
+    /// The verification task of the batch of the proof was created in this transaction.
+    Submitted {
+        batch_merkle_root: [u8; 32],
+        index_in_batch: usize,
+        tx_hash: H256,
+    },
+    /// The batcher didn't receive the proof, or it no longer keeps the batch that included it.
+    Unknown,
+}
+
+/// Hash function with which a commitment is computed. Aligned commits to the verification data
+/// of proofs with Keccak-256, so the others are only meant for registries keying artifacts by
+/// their hash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashFunction {
+    #[default]
+    Keccak256,
+    Sha256,
+    Blake3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Chain {
     Devnet,
     Holesky,
+    /// A deployment of Aligned with the given endpoints, such as a fork, a local upgrade or a
+    /// private deployment.
+    Custom(NetworkEndpoints),
+}
+
+impl Chain {
+    /// Returns the endpoints of the deployment of Aligned on the chain, to be changed into the
+    /// ones of a `Chain::Custom`.
+    pub fn endpoints(&self) -> NetworkEndpoints {
+        NetworkEndpoints {
+            batcher_url: self.batcher_url().to_string(),
+            eth_rpc_url: self.eth_rpc_url().to_string(),
+            aligned_service_manager: self.aligned_service_manager().to_string(),
+            batcher_payment_service: self.batcher_payment_service().to_string(),
+        }
+    }
+
+    pub fn batcher_url(&self) -> &str {
+        match self {
+            Chain::Devnet => "ws://localhost:8080",
+            Chain::Holesky => "wss://batcher.alignedlayer.com",
+            Chain::Custom(endpoints) => &endpoints.batcher_url,
+        }
+    }
+
+    pub fn eth_rpc_url(&self) -> &str {
+        match self {
+            Chain::Devnet => "http://localhost:8545",
+            Chain::Holesky => "https://ethereum-holesky-rpc.publicnode.com",
+            Chain::Custom(endpoints) => &endpoints.eth_rpc_url,
+        }
+    }
+
+    pub fn aligned_service_manager(&self) -> &str {
+        match self {
+            Chain::Devnet => "0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8",
+            Chain::Holesky => "0x58F280BeBE9B34c9939C3C39e0890C81f163B623",
+            Chain::Custom(endpoints) => &endpoints.aligned_service_manager,
+        }
+    }
+
+    pub fn batcher_payment_service(&self) -> &str {
+        match self {
+            Chain::Devnet => "0x7969c5eD335650692Bc04293B07F5BF2e7A673C0",
+            Chain::Holesky => "0x815aeCA64a974297942D2Bbf034ABEe22a38A003",
+            Chain::Custom(endpoints) => &endpoints.batcher_payment_service,
+        }
+    }
+}
+
+/// Endpoints of a deployment of Aligned: the batcher to which proofs are submitted, the Ethereum
+/// RPC node with which their verification is checked, and the addresses of its contracts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkEndpoints {
+    pub batcher_url: String,
+    pub eth_rpc_url: String,
+    /// Address of the `AlignedServiceManager` contract, with which the verification of the
+    /// proofs is checked.
+    pub aligned_service_manager: String,
+    /// Address of the `BatcherPaymentService` contract, in which the proofs are paid for.
+    pub batcher_payment_service: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::signers::LocalWallet;
+
+    fn verification_data(proof: Vec<u8>) -> VerificationData {
+        VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            proof,
+            pub_input: Some(vec![1, 2, 3]),
+            verification_key: Some(vec![4, 5, 6]),
+            vm_program_code: None,
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_message_with_external_signer() {
+        let wallet = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap();
+
+        // The signer only sees the bytes to sign, as a remote signing service would
+        let msg = ClientMessage::new_with_signer(verification_data(vec![0]), |hashed_leaf| {
+            let wallet = wallet.clone();
+            async move { wallet.sign_message(hashed_leaf.to_vec()).await }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(msg.verify_signature().unwrap(), wallet.address());
+    }
+
+    #[tokio::test]
+    async fn test_batched_client_message_signature_and_entries() {
+        let wallet = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap();
+
+        let verification_data: Vec<_> = (0..3).map(|i| verification_data(vec![i])).collect();
+        let mut msg = BatchedClientMessage::new(verification_data.clone(), &wallet)
+            .await
+            .unwrap();
+
+        assert!(msg.verify_entries());
+        assert_eq!(msg.verify_signature().unwrap(), wallet.address());
+
+        // Swapping two entries invalidates their inclusion proofs
+        let mut swapped = msg.clone();
+        swapped.entries.swap(0, 1);
+        assert!(!swapped.verify_entries());
+
+        // The remaining entries are still included in the root, but not as many as were signed
+        let dropped_entry = msg.entries.pop().unwrap();
+        assert!(!msg.verify_entries());
+
+        // The padding leaf of the tree repeats the last entry, which is included at its index
+        let commitments: Vec<VerificationDataCommitment> =
+            verification_data.into_iter().map(Into::into).collect();
+        let tree = MerkleTree::<VerificationCommitmentBatch>::build(&commitments);
+        msg.entries.push(dropped_entry.clone());
+        msg.entries.push(BatchedClientMessageEntry {
+            entries_inclusion_proof: tree.get_proof_by_pos(3).unwrap(),
+            ..dropped_entry
+        });
+        assert!(msg.entries[3]
+            .entries_inclusion_proof
+            .verify::<VerificationCommitmentBatch>(
+                &msg.entries_merkle_root,
+                3,
+                &msg.entries[3].verification_data.clone().into()
+            ));
+        assert!(!msg.verify_entries());
+
+        // Changing the entry count invalidates the signature
+        msg.entry_count = 4;
+        assert_ne!(msg.verify_signature().ok(), Some(wallet.address()));
+    }
+
+    #[tokio::test]
+    async fn test_batched_client_messages_need_two_entries() {
+        let wallet = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap();
+
+        for len in 0..2 {
+            let verification_data = (0..len).map(|i| verification_data(vec![i])).collect();
+            let result = BatchedClientMessage::new(verification_data, &wallet).await;
+            assert!(matches!(result, Err(SubmitError::GenericError(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sponsored_client_message_signatures() {
+        let prover = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap();
+        let sponsor = "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d"
+            .parse::<LocalWallet>()
+            .unwrap();
+
+        let client_message = ClientMessage::new(verification_data(vec![0]), &prover)
+            .await
+            .unwrap();
+        let msg = SponsoredClientMessage::new(client_message.clone(), &sponsor)
+            .await
+            .unwrap();
+        assert_eq!(
+            msg.verify_signatures().unwrap(),
+            (prover.address(), sponsor.address())
+        );
+
+        // The sponsor signature doesn't authorize paying for any other proof
+        let other_client_message = ClientMessage::new(verification_data(vec![1]), &prover)
+            .await
+            .unwrap();
+        let forged = SponsoredClientMessage {
+            client_message: other_client_message,
+            sponsor_signature: msg.sponsor_signature,
+        };
+        assert_ne!(forged.verify_signatures().unwrap().1, sponsor.address());
+
+        // Messages with an invalid prover signature can't be sponsored
+        let mut invalid_client_message = client_message;
+        invalid_client_message.signature.r = 0.into();
+        assert!(
+            SponsoredClientMessage::new(invalid_client_message, &sponsor)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_protection_is_signed() {
+        let wallet = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap();
+
+        let replay_protection = ReplayProtection::new(Duration::from_secs(60));
+        assert!(ReplayProtection::new(Duration::from_secs(60)).nonce > replay_protection.nonce);
+
+        let msg = ClientMessage::new_with_replay_protection(
+            verification_data(vec![0]),
+            replay_protection,
+            |signed_hash| {
+                let wallet = wallet.clone();
+                async move { wallet.sign_message(signed_hash).await }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(msg.verify_signature().unwrap(), wallet.address());
+
+        // Changing the nonce or dropping it invalidates the signature
+        let mut other_nonce = msg.clone();
+        other_nonce.replay_protection = Some(ReplayProtection {
+            nonce: replay_protection.nonce + 1,
+            ..replay_protection
+        });
+        assert_ne!(other_nonce.verify_signature().unwrap(), wallet.address());
+        let mut unprotected = msg.clone();
+        unprotected.replay_protection = None;
+        assert_ne!(unprotected.verify_signature().unwrap(), wallet.address());
+
+        // The replay protection of batched messages is signed along with their entries
+        let verification_data_batch = (0..2).map(|i| verification_data(vec![i])).collect();
+        let batched_msg = BatchedClientMessage::new_with_replay_protection(
+            verification_data_batch,
+            replay_protection,
+            |signed_hash| sign_with_signer(&wallet, signed_hash),
+        )
+        .await
+        .unwrap();
+        assert!(batched_msg.verify_entries());
+        assert_eq!(batched_msg.verify_signature().unwrap(), wallet.address());
+        let mut unprotected = batched_msg.clone();
+        unprotected.replay_protection = None;
+        assert_ne!(unprotected.verify_signature().unwrap(), wallet.address());
+
+        // Messages without replay protection are serialized as before it existed
+        let msg = ClientMessage::new(verification_data(vec![0]), &wallet)
+            .await
+            .unwrap();
+        assert!(!serde_json::to_string(&msg)
+            .unwrap()
+            .contains("replay_protection"));
+    }
+
+    #[test]
+    fn test_v1_merkle_inclusion_matches_lambdaworks_proofs() {
+        let commitments: Vec<VerificationDataCommitment> =
+            (0..4).map(|i| verification_data(vec![i]).into()).collect();
+        let tree = MerkleTree::<VerificationCommitmentBatch>::build(&commitments);
+
+        for (index, commitment) in commitments.iter().enumerate() {
+            let proof = tree.get_proof_by_pos(index).unwrap();
+            let leaf = VerificationCommitmentBatch::hash_data(commitment);
+
+            assert!(BatchVersion::V1.verify_merkle_inclusion(
+                &tree.root,
+                &leaf,
+                &proof.merkle_path,
+                index
+            ));
+            assert!(!BatchVersion::V1.verify_merkle_inclusion(
+                &tree.root,
+                &leaf,
+                &proof.merkle_path,
+                index + 1
+            ));
+        }
+    }
+
+    #[test]
+    fn test_sha256_batch_inclusion_data_verifies_with_its_backend() {
+        let commitments: Vec<VerificationDataCommitment> =
+            (0..3).map(|i| verification_data(vec![i]).into()).collect();
+        let tree = MerkleTree::<Sha256CommitmentBatch>::build(&commitments);
+
+        let mut leaf = Sha256::new();
+        leaf.update(commitments[0].proof_commitment);
+        leaf.update(commitments[0].pub_input_commitment);
+        leaf.update(commitments[0].proving_system_aux_data_commitment);
+        leaf.update(commitments[0].proof_generator_addr);
+        assert_eq!(
+            Sha256CommitmentBatch::hash_data(&commitments[0]),
+            <[u8; 32]>::from(leaf.finalize())
+        );
+
+        for (index, commitment) in commitments.iter().enumerate() {
+            let batch_inclusion_data = BatchInclusionData::new(index, &tree);
+
+            assert!(batch_inclusion_data.verify_with_backend::<Sha256CommitmentBatch>(commitment));
+            // Neither the contract nor keccak trees include it
+            assert!(!batch_inclusion_data.verify(commitment));
+            assert!(!batch_inclusion_data
+                .verify_with_backend::<VerificationCommitmentBatch>(commitment));
+        }
+    }
+
+    #[test]
+    fn test_v2_merkle_inclusion_rejects_incomplete_levels() {
+        let leaves = [[0; 32], [1; 32], [2; 32], [3; 32]];
+        let root = VerificationCommitmentBatch::hash_children(&leaves);
+
+        assert!(BatchVersion::V2.verify_merkle_inclusion(
+            &root,
+            &leaves[2],
+            &[leaves[0], leaves[1], leaves[3]],
+            2
+        ));
+        assert!(!BatchVersion::V2.verify_merkle_inclusion(
+            &root,
+            &leaves[2],
+            &[leaves[0], leaves[1]],
+            2
+        ));
+    }
+
+    #[test]
+    fn test_batch_inclusion_data_without_version_is_v1() {
+        let json = r#"{"batch_merkle_root":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"batch_inclusion_proof":{"merkle_path":[]},"index_in_batch":0}"#;
+        let batch_inclusion_data: BatchInclusionData = serde_json::from_str(json).unwrap();
+        assert_eq!(batch_inclusion_data.batch_version, BatchVersion::V1);
+        assert_eq!(batch_inclusion_data.schema_version, SchemaVersion::V1);
+
+        // V1 batches are serialized as before the version was added
+        assert_eq!(serde_json::to_string(&batch_inclusion_data).unwrap(), json);
+    }
+
+    fn aligned_verification_data_files() -> Vec<(String, Vec<u8>)> {
+        let dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test_files/aligned_verification_data");
+        let mut files: Vec<(String, Vec<u8>)> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, std::fs::read(path).unwrap())
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn is_included(aligned_verification_data: &AlignedVerificationData) -> bool {
+        let leaf = VerificationCommitmentBatch::hash_data(
+            &aligned_verification_data.verification_data_commitment,
+        );
+        aligned_verification_data
+            .batch_version
+            .verify_merkle_inclusion(
+                &aligned_verification_data.batch_merkle_root,
+                &leaf,
+                &aligned_verification_data.batch_inclusion_proof.merkle_path,
+                aligned_verification_data.index_in_batch,
+            )
+    }
+
+    #[test]
+    fn test_files_of_previous_releases_load() {
+        let files = aligned_verification_data_files();
+        assert!(!files.is_empty());
+
+        for (name, file) in files {
+            let aligned_verification_data: AlignedVerificationData = serde_json::from_slice(&file)
+                .unwrap_or_else(|e| panic!("Could not load {}: {}", name, e));
+            assert!(is_included(&aligned_verification_data), "{}", name);
+
+            // Files are saved in the same format they were saved by previous releases
+            let saved: serde_json::Value =
+                serde_json::to_value(&aligned_verification_data).unwrap();
+            assert_eq!(
+                saved,
+                serde_json::from_slice::<serde_json::Value>(&file).unwrap(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_files_of_previous_releases_are_v1_until_migrated() {
+        for (name, file) in aligned_verification_data_files() {
+            let mut aligned_verification_data: AlignedVerificationData =
+                serde_json::from_slice(&file).unwrap();
+            assert_eq!(
+                aligned_verification_data.schema_version,
+                SchemaVersion::V1,
+                "{}",
+                name
+            );
+
+            assert!(aligned_verification_data.migrate());
+            assert!(!aligned_verification_data.migrate());
+            let saved = serde_json::to_value(&aligned_verification_data).unwrap();
+            assert_eq!(saved["schema_version"], "V2", "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_verification_data_of_the_current_schema_is_serialized_without_version() {
+        let json = serde_json::to_value(verification_data(vec![1])).unwrap();
+        assert!(json.get("schema_version").is_none());
+
+        let loaded: VerificationData = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.schema_version, VerificationData::SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_aligned_verification_data_with_hex_and_new_fields_loads() {
+        for (name, file) in aligned_verification_data_files() {
+            let expected: AlignedVerificationData = serde_json::from_slice(&file).unwrap();
+
+            let mut json: serde_json::Value = serde_json::from_slice(&file).unwrap();
+            let hex_encode = |value: &mut serde_json::Value| {
+                let bytes: Vec<u8> = serde_json::from_value(value.clone()).unwrap();
+                *value = format!("0x{}", hex::encode(bytes)).into();
+            };
+            hex_encode(&mut json["batch_merkle_root"]);
+            for node in json["batch_inclusion_proof"]["merkle_path"]
+                .as_array_mut()
+                .unwrap()
+            {
+                hex_encode(node);
+            }
+            for field in [
+                "proof_commitment",
+                "pub_input_commitment",
+                "proving_system_aux_data_commitment",
+                "proof_generator_addr",
+            ] {
+                hex_encode(&mut json["verification_data_commitment"][field]);
+            }
+            // Fields of later releases are ignored
+            json["submission_metadata"] = serde_json::json!({ "block_number": 1 });
+
+            let aligned_verification_data: AlignedVerificationData =
+                serde_json::from_value(json).unwrap();
+            assert_eq!(
+                serde_json::to_value(&aligned_verification_data).unwrap(),
+                serde_json::to_value(&expected).unwrap(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_capabilities_of_every_proving_system() {
+        for proving_system in ProvingSystemId::all() {
+            let capabilities = proving_system.capabilities();
+            assert!(
+                capabilities.requires_verification_key != capabilities.requires_vm_program,
+                "{:?} must be verified with either a verification key or a vm program",
+                proving_system
+            );
+            assert!(!capabilities.requires_pub_input || capabilities.accepts_pub_input);
+        }
+        assert_eq!(
+            ProvingSystemId::Risc0.capabilities().vm_program_size,
+            Some(RISC0_IMAGE_ID_SIZE)
+        );
+        assert!(!ProvingSystemId::SP1.capabilities().requires_pub_input);
+    }
+
+    #[test]
+    fn test_validate_checks_the_artifacts_of_the_proving_system() {
+        let sp1 = VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: vec![1],
+            pub_input: None,
+            verification_key: None,
+            vm_program_code: Some(vec![2]),
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        assert!(sp1.validate().is_ok());
+
+        let risc0 = VerificationData {
+            proving_system: ProvingSystemId::Risc0,
+            pub_input: Some(vec![3]),
+            vm_program_code: Some(vec![0; RISC0_IMAGE_ID_SIZE]),
+            ..sp1.clone()
+        };
+        assert!(risc0.validate().is_ok());
+
+        let groth16 = VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            pub_input: Some(vec![3]),
+            verification_key: Some(vec![4]),
+            vm_program_code: None,
+            ..sp1.clone()
+        };
+        assert!(groth16.validate().is_ok());
+        let with_metadata = VerificationData {
+            metadata: Some(vec![5; VerificationData::MAX_METADATA_SIZE]),
+            ..groth16.clone()
+        };
+        assert!(with_metadata.validate().is_ok());
+
+        let invalid = [
+            VerificationData {
+                proof: vec![],
+                ..sp1.clone()
+            },
+            VerificationData {
+                vm_program_code: None,
+                ..sp1.clone()
+            },
+            VerificationData {
+                pub_input: None,
+                ..risc0.clone()
+            },
+            VerificationData {
+                vm_program_code: Some(vec![0; RISC0_IMAGE_ID_SIZE - 1]),
+                ..risc0
+            },
+            VerificationData {
+                verification_key: None,
+                ..groth16.clone()
+            },
+            // A vm program would replace the verification key in the commitment
+            VerificationData {
+                vm_program_code: Some(vec![2]),
+                ..groth16.clone()
+            },
+            VerificationData {
+                proving_system: ProvingSystemId::Halo2KZG,
+                verification_key: Some(vec![0; HALO2_MAX_VERIFICATION_KEY_SIZE + 1]),
+                ..groth16.clone()
+            },
+            VerificationData {
+                metadata: Some(vec![5; VerificationData::MAX_METADATA_SIZE + 1]),
+                ..groth16
+            },
+        ];
+        for verification_data in invalid {
+            assert!(
+                matches!(
+                    verification_data.validate(),
+                    Err(SubmitError::InvalidVerificationData(_))
+                ),
+                "{:?} should be invalid",
+                verification_data
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_response_names_its_code() {
+        let error_response = ErrorResponse {
+            rejection: Rejection::ProofTooLarge {
+                proof_size: 2048,
+                max_proof_size: 1024,
+            },
+            submission_id: Some(3),
+        };
+        let serialized = serde_json::to_string(&error_response).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"code":"proof_too_large","proof_size":2048,"max_proof_size":1024,"submission_id":3}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ErrorResponse>(&serialized).unwrap(),
+            error_response
+        );
+        assert_eq!(
+            RejectionCode::of(&error_response.rejection),
+            RejectionCode::ProofTooLarge
+        );
+
+        // Responses with codes unknown to the SDK can't be read, leaving the client with the
+        // close frame
+        let unknown = r#"{"code":"rate_limited","retry_after":10}"#;
+        assert!(serde_json::from_str::<ErrorResponse>(unknown).is_err());
+    }
 }
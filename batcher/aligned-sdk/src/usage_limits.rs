@@ -0,0 +1,223 @@
+//! Daily limits on the proofs submitted and the Ether spent by a client, enforced before the
+//! proofs are sent, so that a runaway automated submitter can't use up the balance of its payer
+//! in the batcher.
+//!
+//! The usage of the current day is kept in a JSON file, the usage store, shared by every client
+//! and CLI invocation using it. Days are UTC days. The spend of a submission is its estimated
+//! fee, `fees::max_submission_fee`, which is the most it can be charged, so the actual spend is
+//! usually lower than the one recorded.
+//!
+//! The store is not locked, so clients submitting at the same time from different processes can
+//! exceed the limits by the proofs of their concurrent submissions.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::types::U256;
+use ethers::utils::format_ether;
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::errors::SubmitError;
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Limits on the usage of a day. Usage without a limit is still recorded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageLimits {
+    pub max_proofs_per_day: Option<u64>,
+    /// Most Ether spent in fees per day, in wei.
+    pub max_spend_per_day: Option<U256>,
+}
+
+impl UsageLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_proofs_per_day.is_none() && self.max_spend_per_day.is_none()
+    }
+}
+
+/// Usage of a day, since the Unix epoch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub day: u64,
+    pub proofs: u64,
+    /// Estimated fees of the proofs, in wei.
+    pub spend: U256,
+}
+
+/// Usage of the current day, kept in a JSON file.
+#[derive(Debug)]
+pub struct UsageStore {
+    path: PathBuf,
+}
+
+impl UsageStore {
+    /// Returns the store kept in the file at `path`, which is created on the first recorded
+    /// submission.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UsageStore { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the usage of the current day.
+    /// # Errors
+    /// * `IoError` if the file of the store exists but can't be read.
+    /// * `SerdeError` if the file of the store is not valid.
+    pub fn today(&self) -> Result<DailyUsage, SubmitError> {
+        let today = current_day();
+        let usage = match std::fs::read(&self.path) {
+            Ok(usage) => serde_json::from_slice::<DailyUsage>(&usage)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => DailyUsage::default(),
+            Err(e) => return Err(SubmitError::IoError(self.path.clone(), e)),
+        };
+        if usage.day != today {
+            return Ok(DailyUsage {
+                day: today,
+                ..DailyUsage::default()
+            });
+        }
+        Ok(usage)
+    }
+
+    /// Checks that submitting `proofs` at an estimated fee of `spend` doesn't exceed the limits
+    /// of the day, and records them. Nothing is recorded if they would exceed the limits.
+    /// # Returns
+    /// * The usage of the day, including the submission.
+    /// # Errors
+    /// * `UsageLimitExceeded` if the submission would exceed a limit.
+    /// * The errors of `today` and `record`.
+    pub fn reserve(
+        &self,
+        limits: &UsageLimits,
+        proofs: usize,
+        spend: U256,
+    ) -> Result<DailyUsage, SubmitError> {
+        let usage = self.today()?;
+        let proofs_after = usage.proofs.saturating_add(proofs as u64);
+        let spend_after = usage.spend.saturating_add(spend);
+
+        if let Some(max_proofs_per_day) = limits.max_proofs_per_day {
+            if proofs_after > max_proofs_per_day {
+                return Err(SubmitError::UsageLimitExceeded(format!(
+                    "submitting {} proofs would make {} today, above the limit of {} proofs per day",
+                    proofs, proofs_after, max_proofs_per_day
+                )));
+            }
+        }
+        if let Some(max_spend_per_day) = limits.max_spend_per_day {
+            if spend_after > max_spend_per_day {
+                return Err(SubmitError::UsageLimitExceeded(format!(
+                    "the proofs are estimated to cost up to {} ether, which would make {} ether today, above the limit of {} ether per day",
+                    format_ether(spend),
+                    format_ether(spend_after),
+                    format_ether(max_spend_per_day)
+                )));
+            }
+        }
+        self.record(proofs, spend)
+    }
+
+    /// Records the submission of `proofs` at an estimated fee of `spend`, without checking any
+    /// limit.
+    /// # Returns
+    /// * The usage of the day, including the submission.
+    /// # Errors
+    /// * `IoError` if the file of the store can't be read or written.
+    /// * `SerdeError` if the file of the store is not valid.
+    pub fn record(&self, proofs: usize, spend: U256) -> Result<DailyUsage, SubmitError> {
+        let mut usage = self.today()?;
+        usage.proofs = usage.proofs.saturating_add(proofs as u64);
+        usage.spend = usage.spend.saturating_add(spend);
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| SubmitError::IoError(dir.into(), e))?;
+        }
+        // Written next to the store and renamed, so that an interrupted write doesn't lose it
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec(&usage)?)
+            .map_err(|e| SubmitError::IoError(tmp_path.clone(), e))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .map_err(|e| SubmitError::IoError(self.path.clone(), e))?;
+        Ok(usage)
+    }
+}
+
+fn current_day() -> u64 {
+    clock::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / SECONDS_PER_DAY
+}
+
+/// Returns the time at which the current day ends, when the usage of the store starts over.
+pub fn next_reset() -> SystemTime {
+    UNIX_EPOCH + std::time::Duration::from_secs((current_day() + 1) * SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_store(name: &str) -> UsageStore {
+        let path = std::env::temp_dir().join(format!(
+            "aligned_usage_{}_{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        UsageStore::new(path)
+    }
+
+    #[test]
+    fn test_submissions_above_the_limits_are_refused() {
+        let store = test_store("limits");
+        let limits = UsageLimits {
+            max_proofs_per_day: Some(10),
+            max_spend_per_day: Some(U256::from(1000)),
+        };
+
+        let usage = store.reserve(&limits, 6, U256::from(600)).unwrap();
+        assert_eq!((usage.proofs, usage.spend), (6, U256::from(600)));
+        assert!(matches!(
+            store.reserve(&limits, 5, U256::zero()),
+            Err(SubmitError::UsageLimitExceeded(_))
+        ));
+        assert!(matches!(
+            store.reserve(&limits, 1, U256::from(401)),
+            Err(SubmitError::UsageLimitExceeded(_))
+        ));
+
+        // Refused submissions are not recorded, and overridden ones are
+        assert_eq!(store.today().unwrap().proofs, 6);
+        store.reserve(&limits, 4, U256::from(400)).unwrap();
+        let usage = store.record(3, U256::from(1)).unwrap();
+        assert_eq!((usage.proofs, usage.spend), (13, U256::from(1001)));
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn test_usage_starts_over_every_day() {
+        use std::time::Duration;
+
+        let store = test_store("days");
+        let limits = UsageLimits {
+            max_proofs_per_day: Some(1),
+            max_spend_per_day: None,
+        };
+        let day = UNIX_EPOCH + Duration::from_secs(20000 * SECONDS_PER_DAY);
+
+        clock::freeze_clock(day + Duration::from_secs(SECONDS_PER_DAY - 1));
+        store.reserve(&limits, 1, U256::zero()).unwrap();
+        assert!(store.reserve(&limits, 1, U256::zero()).is_err());
+        assert_eq!(next_reset(), day + Duration::from_secs(SECONDS_PER_DAY));
+
+        clock::freeze_clock(day + Duration::from_secs(SECONDS_PER_DAY));
+        assert_eq!(store.today().unwrap().proofs, 0);
+        store.reserve(&limits, 1, U256::zero()).unwrap();
+        clock::unfreeze_clock();
+    }
+}
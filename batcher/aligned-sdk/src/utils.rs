@@ -1,5 +1,17 @@
+use std::path::Path;
+
+use ethers::types::Address;
+
 use crate::errors::SubmitError;
-use crate::types::ProvingSystemId;
+use crate::sdk::{get_risc0_journal, get_sp1_public_values};
+use crate::types::{ProvingSystemId, VerificationData};
+
+/// First bytes of proof bundles, the `.aligned` files holding everything needed to submit a proof.
+pub const BUNDLE_MAGIC: &[u8; 4] = b"ALPB";
+/// Latest version of the format of proof bundles.
+pub const BUNDLE_FORMAT_VERSION: u8 = 1;
+/// Extension of proof bundle files.
+pub const BUNDLE_EXTENSION: &str = "aligned";
 
 pub fn parse_proving_system(proving_system: &str) -> Result<Option<ProvingSystemId>, SubmitError> {
     match proving_system {
@@ -15,3 +27,569 @@ pub fn parse_proving_system(proving_system: &str) -> Result<Option<ProvingSystem
         )),
     }
 }
+
+/// Returns the proving systems whose proofs have the structure of the given files, so that a
+/// proof submitted with the wrong proving system is caught before sending it. Proofs are told
+/// apart by their serialization: SP1 proofs end with the SP1 version, Risc0 receipts end with
+/// their journal and metadata, and gnark proofs are sequences of compressed points whose sizes
+/// and flags depend on the curve. Halo2 transcripts have no structure of their own, so Halo2
+/// proofs are told apart by their verification key, made of three parts preceded by their
+/// lengths. The KZG and IPA variants of Halo2 can't be told apart.
+/// # Arguments
+/// * `proof` - The proof file.
+/// * `verification_key` - The verification key file, if any.
+/// # Returns
+/// * The proving systems the files look like, which is empty if they don't look like any.
+pub fn detect_proving_systems(
+    proof: &[u8],
+    verification_key: Option<&[u8]>,
+) -> Vec<ProvingSystemId> {
+    if get_sp1_public_values(proof).is_ok() {
+        vec![ProvingSystemId::SP1]
+    } else if is_risc0_receipt(proof) {
+        vec![ProvingSystemId::Risc0]
+    } else if is_gnark_groth16_bn254_proof(proof) {
+        vec![ProvingSystemId::Groth16Bn254]
+    } else if is_gnark_plonk_proof(proof, &BN254) {
+        vec![ProvingSystemId::GnarkPlonkBn254]
+    } else if is_gnark_plonk_proof(proof, &BLS12_381) {
+        vec![ProvingSystemId::GnarkPlonkBls12_381]
+    } else if verification_key.is_some_and(is_halo2_verification_key) {
+        vec![ProvingSystemId::Halo2KZG, ProvingSystemId::Halo2IPA]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Returns the proving system of the proof, as detected by `detect_proving_systems`.
+/// # Errors
+/// * `InvalidProvingSystem` if the files don't look like the proof of any proving system, or
+///   look like the proofs of more than one.
+pub fn infer_proving_system(
+    proof: &[u8],
+    verification_key: Option<&[u8]>,
+) -> Result<ProvingSystemId, SubmitError> {
+    match detect_proving_systems(proof, verification_key).as_slice() {
+        [proving_system] => Ok(proving_system.clone()),
+        [] => Err(SubmitError::InvalidProvingSystem(
+            "the proving system of the proof can't be detected, it must be given".to_string(),
+        )),
+        proving_systems => Err(SubmitError::InvalidProvingSystem(format!(
+            "the proof looks like a {} proof, the proving system must be given",
+            names(proving_systems)
+        ))),
+    }
+}
+
+/// Checks that the files look like a proof of the given proving system. Files that don't look
+/// like the proof of any proving system are accepted, and left to the batcher to verify.
+/// # Errors
+/// * `InvalidProvingSystem` if the files look like the proof of another proving system.
+pub fn check_proving_system(
+    proving_system: &ProvingSystemId,
+    proof: &[u8],
+    verification_key: Option<&[u8]>,
+) -> Result<(), SubmitError> {
+    let detected = detect_proving_systems(proof, verification_key);
+    if detected.is_empty() || detected.contains(proving_system) {
+        return Ok(());
+    }
+    Err(SubmitError::InvalidProvingSystem(format!(
+        "this file looks like a {} proof, not a {:?} one",
+        names(&detected),
+        proving_system
+    )))
+}
+
+fn names(proving_systems: &[ProvingSystemId]) -> String {
+    proving_systems
+        .iter()
+        .map(|proving_system| format!("{:?}", proving_system))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+/// Receipts start with the bincode tag of the variant of their inner receipt, of which there
+/// are a few.
+const RISC0_MAX_INNER_RECEIPT_TAG: u32 = 8;
+
+fn is_risc0_receipt(proof: &[u8]) -> bool {
+    proof.len() >= 4
+        && u32::from_le_bytes(proof[..4].try_into().unwrap()) < RISC0_MAX_INNER_RECEIPT_TAG
+        && get_risc0_journal(proof).is_ok()
+}
+
+fn is_halo2_verification_key(verification_key: &[u8]) -> bool {
+    if verification_key.len() < 12 {
+        return false;
+    }
+    let lengths = verification_key[..12]
+        .chunks_exact(4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as u64)
+        .sum::<u64>();
+    lengths + 12 == verification_key.len() as u64
+}
+
+/// Sizes of the compressed points of a curve in gnark, and the flags of their first byte.
+struct GnarkCurve {
+    g1_size: usize,
+    flag_bits: u32,
+    compressed_flags: [u8; 3],
+}
+
+// Flags of compressed points being the smallest, largest or infinity
+const BN254: GnarkCurve = GnarkCurve {
+    g1_size: 32,
+    flag_bits: 2,
+    compressed_flags: [0b10, 0b11, 0b01],
+};
+const BLS12_381: GnarkCurve = GnarkCurve {
+    g1_size: 48,
+    flag_bits: 3,
+    compressed_flags: [0b100, 0b101, 0b110],
+};
+
+const GNARK_FR_SIZE: usize = 32;
+
+/// Reader of the binary encoding of gnark, with which a file is checked to have the layout of a
+/// proof.
+struct GnarkReader<'a> {
+    bytes: &'a [u8],
+    curve: &'a GnarkCurve,
+}
+
+impl<'a> GnarkReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn point(&mut self, size: usize) -> Option<()> {
+        let point = self.take(size)?;
+        let flags = point[0] >> (8 - self.curve.flag_bits);
+        self.curve.compressed_flags.contains(&flags).then_some(())
+    }
+
+    fn g1(&mut self) -> Option<()> {
+        self.point(self.curve.g1_size)
+    }
+
+    fn g2(&mut self) -> Option<()> {
+        self.point(self.curve.g1_size * 2)
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        let len = self.take(4)?;
+        Some(u32::from_be_bytes(len.try_into().unwrap()) as usize)
+    }
+
+    fn g1_vec(&mut self) -> Option<()> {
+        (0..self.len()?).try_for_each(|_| self.g1())
+    }
+
+    fn fr_vec(&mut self) -> Option<()> {
+        let len = self.len()?;
+        self.take(len.checked_mul(GNARK_FR_SIZE)?).map(|_| ())
+    }
+}
+
+/// Gnark Groth16 proofs are A in G1, B in G2 and C in G1, followed by the commitments of the
+/// circuit and their proof of knowledge in newer gnark versions.
+fn is_gnark_groth16_bn254_proof(proof: &[u8]) -> bool {
+    let mut reader = GnarkReader {
+        bytes: proof,
+        curve: &BN254,
+    };
+    let mut read = || -> Option<()> {
+        reader.g1()?;
+        reader.g2()?;
+        reader.g1()?;
+        if !reader.bytes.is_empty() {
+            reader.g1_vec()?;
+            reader.g1()?;
+        }
+        Some(())
+    };
+    read().is_some() && reader.bytes.is_empty()
+}
+
+/// Gnark Plonk proofs are the commitments to the wires, to the permutation and to the quotient,
+/// followed by the batched opening proof, the opening proof of the shifted permutation and the
+/// commitments of the circuit.
+fn is_gnark_plonk_proof(proof: &[u8], curve: &GnarkCurve) -> bool {
+    let mut reader = GnarkReader {
+        bytes: proof,
+        curve,
+    };
+    let mut read = || -> Option<()> {
+        for _ in 0..8 {
+            reader.g1()?;
+        }
+        reader.fr_vec()?;
+        reader.g1()?;
+        reader.take(GNARK_FR_SIZE)?;
+        reader.g1_vec()
+    };
+    read().is_some() && reader.bytes.is_empty()
+}
+
+/// Encodes the proof of a verification data as a proof bundle, so it can be moved around as a
+/// single file and submitted later. Bundles start with `BUNDLE_MAGIC` followed by the format
+/// version, and hold, in order:
+/// * The name of the proving system, as a u8 length and its bytes.
+/// * The proof generator address, 20 bytes.
+/// * The proof, as a big endian u32 length and its bytes.
+/// * The public input, verification key and vm program code, each as 1 followed by a big endian
+///   u32 length and its bytes if there is one, or 0 otherwise.
+///
+/// The tag and metadata of the verification data are given when submitting, so they are not
+/// bundled.
+pub fn bundle_to_bytes(verification_data: &VerificationData) -> Vec<u8> {
+    let proving_system = format!("{:?}", verification_data.proving_system);
+    let artifacts = [
+        &verification_data.pub_input,
+        &verification_data.verification_key,
+        &verification_data.vm_program_code,
+    ];
+
+    let mut bytes = Vec::with_capacity(
+        4 + 1
+            + 1
+            + proving_system.len()
+            + 20
+            + 4
+            + verification_data.proof.len()
+            + artifacts
+                .iter()
+                .map(|artifact| {
+                    artifact
+                        .as_ref()
+                        .map_or(1, |artifact| 1 + 4 + artifact.len())
+                })
+                .sum::<usize>(),
+    );
+    bytes.extend_from_slice(BUNDLE_MAGIC);
+    bytes.push(BUNDLE_FORMAT_VERSION);
+    bytes.push(proving_system.len() as u8);
+    bytes.extend_from_slice(proving_system.as_bytes());
+    bytes.extend_from_slice(verification_data.proof_generator_addr.as_bytes());
+    push_bundle_field(&mut bytes, &verification_data.proof);
+    for artifact in artifacts {
+        match artifact {
+            Some(artifact) => {
+                bytes.push(1);
+                push_bundle_field(&mut bytes, artifact);
+            }
+            None => bytes.push(0),
+        }
+    }
+    bytes
+}
+
+fn push_bundle_field(bytes: &mut Vec<u8>, field: &[u8]) {
+    bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(field);
+}
+
+/// Decodes a proof bundle, as encoded by `bundle_to_bytes`.
+/// # Returns
+/// * The verification data of the bundled proof, without tag nor metadata.
+/// # Errors
+/// * `InvalidBinaryData` if the bytes are not a proof bundle, or one of a newer format version.
+/// * `InvalidProvingSystem` if the proving system of the bundle is unknown.
+/// * `InvalidVerificationData` if the bundle doesn't hold the artifacts its proving system needs.
+pub fn bundle_from_bytes(bytes: &[u8]) -> Result<VerificationData, SubmitError> {
+    let mut reader = BundleReader { bytes };
+
+    if reader.take(4)? != BUNDLE_MAGIC {
+        return Err(SubmitError::InvalidBinaryData(
+            "Not a proof bundle".to_string(),
+        ));
+    }
+    let format_version = reader.take(1)?[0];
+    if format_version > BUNDLE_FORMAT_VERSION || format_version == 0 {
+        return Err(SubmitError::InvalidBinaryData(format!(
+            "Unsupported bundle format version {}, the SDK should be updated",
+            format_version
+        )));
+    }
+
+    let proving_system_len = reader.take(1)?[0] as usize;
+    let proving_system = std::str::from_utf8(reader.take(proving_system_len)?).map_err(|_| {
+        SubmitError::InvalidBinaryData("The proving system is not valid UTF-8".to_string())
+    })?;
+    let proving_system =
+        parse_proving_system(proving_system)?.expect("Known proving systems are always parsed");
+    let proof_generator_addr = Address::from_slice(reader.take(20)?);
+    let proof = reader.field()?;
+    let pub_input = reader.optional_field()?;
+    let verification_key = reader.optional_field()?;
+    let vm_program_code = reader.optional_field()?;
+    if !reader.bytes.is_empty() {
+        return Err(SubmitError::InvalidBinaryData(format!(
+            "Unexpected {} bytes after the proof bundle",
+            reader.bytes.len()
+        )));
+    }
+
+    let verification_data = VerificationData {
+        proving_system,
+        proof,
+        pub_input,
+        verification_key,
+        vm_program_code,
+        proof_generator_addr,
+        tag: None,
+        metadata: None,
+        schema_version: VerificationData::SCHEMA_VERSION,
+    };
+    verification_data.validate()?;
+    Ok(verification_data)
+}
+
+/// Reads the proof bundle in a file.
+/// # Errors
+/// * `IoError` if the file can't be read.
+/// * The errors of `bundle_from_bytes`.
+pub fn read_bundle(path: &Path) -> Result<VerificationData, SubmitError> {
+    let bytes = std::fs::read(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))?;
+    bundle_from_bytes(&bytes)
+}
+
+/// Writes the proof of a verification data into a file as a proof bundle.
+/// # Errors
+/// * `IoError` if the file can't be written.
+pub fn write_bundle(path: &Path, verification_data: &VerificationData) -> Result<(), SubmitError> {
+    std::fs::write(path, bundle_to_bytes(verification_data))
+        .map_err(|e| SubmitError::IoError(path.to_path_buf(), e))
+}
+
+struct BundleReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BundleReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SubmitError> {
+        if self.bytes.len() < len {
+            return Err(SubmitError::InvalidBinaryData(
+                "Unexpected end of the proof bundle".to_string(),
+            ));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn field(&mut self) -> Result<Vec<u8>, SubmitError> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn optional_field(&mut self) -> Result<Option<Vec<u8>>, SubmitError> {
+        match self.take(1)?[0] {
+            0 => Ok(None),
+            1 => self.field().map(Some),
+            flag => Err(SubmitError::InvalidBinaryData(format!(
+                "Invalid field flag {}",
+                flag
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn read_test_file(path: &str) -> Vec<u8> {
+        std::fs::read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_proving_systems_are_detected() {
+        let sp1_proof = read_test_file("test_files/sp1/sp1_fibonacci.proof");
+        let risc0_receipt = read_test_file("test_files/risc_zero/risc_zero_fibonacci.proof");
+        let groth16_proof = read_test_file("test_files/groth16_bn254/plonk.proof");
+        let groth16_vk = read_test_file("test_files/groth16_bn254/plonk.vk");
+
+        assert_eq!(
+            infer_proving_system(&sp1_proof, None).unwrap(),
+            ProvingSystemId::SP1
+        );
+        assert_eq!(
+            infer_proving_system(&risc0_receipt, None).unwrap(),
+            ProvingSystemId::Risc0
+        );
+        assert_eq!(
+            infer_proving_system(&groth16_proof, Some(&groth16_vk)).unwrap(),
+            ProvingSystemId::Groth16Bn254
+        );
+        // Without its commitments, as written by older gnark versions
+        assert_eq!(
+            detect_proving_systems(&groth16_proof[..128], None),
+            vec![ProvingSystemId::Groth16Bn254]
+        );
+        assert!(detect_proving_systems(&groth16_proof[..150], None).is_empty());
+    }
+
+    #[test]
+    fn test_gnark_plonk_proofs_are_detected_by_curve() {
+        let plonk_proof = |curve: &GnarkCurve, flags: u8| {
+            let g1 = |proof: &mut Vec<u8>| {
+                let mut point = vec![0; curve.g1_size];
+                point[0] = flags << (8 - curve.flag_bits);
+                proof.extend(point);
+            };
+            let mut proof = Vec::new();
+            (0..8).for_each(|_| g1(&mut proof));
+            proof.extend(2u32.to_be_bytes());
+            proof.extend([0; 2 * GNARK_FR_SIZE]);
+            g1(&mut proof);
+            proof.extend([0; GNARK_FR_SIZE]);
+            proof.extend(1u32.to_be_bytes());
+            g1(&mut proof);
+            proof
+        };
+
+        assert_eq!(
+            detect_proving_systems(&plonk_proof(&BN254, 0b10), None),
+            vec![ProvingSystemId::GnarkPlonkBn254]
+        );
+        assert_eq!(
+            detect_proving_systems(&plonk_proof(&BLS12_381, 0b101), None),
+            vec![ProvingSystemId::GnarkPlonkBls12_381]
+        );
+        // Uncompressed points
+        assert!(detect_proving_systems(&plonk_proof(&BN254, 0b00), None).is_empty());
+    }
+
+    #[test]
+    fn test_halo2_proofs_are_detected_by_their_verification_key() {
+        let mut verification_key = Vec::new();
+        for len in [3u32, 2, 1] {
+            verification_key.extend(len.to_le_bytes());
+        }
+        verification_key.extend([7; 6]);
+
+        let transcript = [5; 100];
+        assert_eq!(
+            detect_proving_systems(&transcript, Some(&verification_key)),
+            vec![ProvingSystemId::Halo2KZG, ProvingSystemId::Halo2IPA]
+        );
+        assert!(matches!(
+            infer_proving_system(&transcript, Some(&verification_key)),
+            Err(SubmitError::InvalidProvingSystem(_))
+        ));
+        assert!(check_proving_system(
+            &ProvingSystemId::Halo2IPA,
+            &transcript,
+            Some(&verification_key)
+        )
+        .is_ok());
+        assert!(detect_proving_systems(&transcript, Some(&verification_key[1..])).is_empty());
+    }
+
+    #[test]
+    fn test_proofs_of_other_proving_systems_are_rejected() {
+        let sp1_proof = read_test_file("test_files/sp1/sp1_fibonacci.proof");
+
+        let result = check_proving_system(&ProvingSystemId::Groth16Bn254, &sp1_proof, None);
+        assert!(matches!(
+            result,
+            Err(SubmitError::InvalidProvingSystem(e))
+                if e == "this file looks like a SP1 proof, not a Groth16Bn254 one"
+        ));
+        assert!(check_proving_system(&ProvingSystemId::SP1, &sp1_proof, None).is_ok());
+        // Unknown files are left to the batcher
+        assert!(check_proving_system(&ProvingSystemId::Risc0, &[1, 2, 3], None).is_ok());
+    }
+
+    fn json(verification_data: &VerificationData) -> serde_json::Value {
+        serde_json::to_value(verification_data).unwrap()
+    }
+
+    fn groth16_verification_data() -> VerificationData {
+        VerificationData {
+            proving_system: ProvingSystemId::Groth16Bn254,
+            proof: read_test_file("test_files/groth16_bn254/plonk.proof"),
+            pub_input: Some(read_test_file(
+                "test_files/groth16_bn254/plonk_pub_input.pub",
+            )),
+            verification_key: Some(read_test_file("test_files/groth16_bn254/plonk.vk")),
+            vm_program_code: None,
+            proof_generator_addr: Address::repeat_byte(7),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        }
+    }
+
+    #[test]
+    fn test_bundles_hold_the_proof_and_its_artifacts() {
+        let sp1 = VerificationData {
+            proving_system: ProvingSystemId::SP1,
+            proof: read_test_file("test_files/sp1/sp1_fibonacci.proof"),
+            pub_input: None,
+            verification_key: None,
+            vm_program_code: Some(read_test_file("test_files/sp1/sp1_fibonacci-elf")),
+            proof_generator_addr: Address::repeat_byte(1),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+        let groth16 = groth16_verification_data();
+
+        for verification_data in [sp1, groth16] {
+            let bytes = bundle_to_bytes(&verification_data);
+            assert!(bytes.starts_with(BUNDLE_MAGIC));
+            assert_eq!(
+                json(&bundle_from_bytes(&bytes).unwrap()),
+                json(&verification_data)
+            );
+        }
+    }
+
+    #[test]
+    fn test_tags_and_metadata_are_not_bundled() {
+        let mut verification_data = groth16_verification_data();
+        verification_data.tag = Some([3; 32]);
+        verification_data.metadata = Some(b"job-1".to_vec());
+
+        let bundled = bundle_from_bytes(&bundle_to_bytes(&verification_data)).unwrap();
+
+        assert_eq!(json(&bundled), json(&groth16_verification_data()));
+    }
+
+    #[test]
+    fn test_invalid_bundles_are_rejected() {
+        let bytes = bundle_to_bytes(&groth16_verification_data());
+
+        let mut newer_version = bytes.clone();
+        newer_version[4] = BUNDLE_FORMAT_VERSION + 1;
+        let mut unknown_proving_system = bytes.clone();
+        unknown_proving_system[6] = b'X';
+        let mut trailing_bytes = bytes.clone();
+        trailing_bytes.push(0);
+
+        for invalid in [
+            &bytes[..bytes.len() - 1],
+            newer_version.as_slice(),
+            trailing_bytes.as_slice(),
+            b"ALVD\x01".as_slice(),
+        ] {
+            assert!(matches!(
+                bundle_from_bytes(invalid),
+                Err(SubmitError::InvalidBinaryData(_))
+            ));
+        }
+        assert!(matches!(
+            bundle_from_bytes(&unknown_proving_system),
+            Err(SubmitError::InvalidProvingSystem(_))
+        ));
+    }
+}
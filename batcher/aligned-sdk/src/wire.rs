@@ -0,0 +1,755 @@
+//! Encodings of the client messages sent to the batcher, shared by the SDK and the batcher.
+//!
+//! Messages are encoded as JSON, understood by every batcher, unless both sides support the
+//! `binary_messages` feature, in which case they are encoded with bincode. JSON writes the bytes
+//! of proofs as arrays of numbers, taking up to 4 bytes for each of them and being slow to parse,
+//! while bincode writes them as they are.
+//!
+//! The binary encoding is the bincode of `BinaryMessage`, whose structs mirror the ones of the
+//! messages with fixed layouts: they are not affected by the attributes that keep the JSON of
+//! the messages compatible with previous releases, which need a self-describing format. Binary
+//! messages are always sent in the frames of their chunks, as described in `chunks`, so they
+//! can't be mistaken for the text frames of JSON messages.
+//!
+//! The submission id of client messages, and of the client message of sponsored messages, is
+//! encoded by wrapping the message in `BinaryMessage::Identified`, which is appended to the
+//! variants so that messages without it keep their encoding. It's only sent to batchers
+//! supporting the `submission_ids` feature, which older batchers would reject.
+//!
+//! In the same way, the metadata of the verification data of a message is encoded by wrapping it
+//! in `BinaryMessage::WithMetadata`, which is only sent to batchers supporting the
+//! `user_metadata` feature. JSON messages carry it in the `metadata` of their verification data.
+//!
+//! Peers supporting the `zstd_messages` feature also accept the encoded messages compressed
+//! with zstd, which shrinks programs and proofs such as SP1 ELFs and Halo2 proofs several times
+//! over. Messages of at least `COMPRESSION_THRESHOLD` bytes are compressed when it makes them
+//! smaller, and sent in chunks as binary messages are. Compressed messages are told apart by the
+//! magic number of zstd frames, which neither JSON nor binary messages start with.
+//!
+//! Messages can also be encrypted to the key of the batcher after being compressed, as described
+//! in `encryption`.
+//!
+//! Inclusion queries and submission status queries are sent as JSON in text frames, as they are
+//! small and only sent to batchers supporting the `inclusion_queries` and `submission_status`
+//! features. Their binary encoding is appended to the
+//! variants of `BinaryMessage` as well, so that every message can be encoded in both formats.
+
+use std::fmt;
+use std::io::{self, Read};
+
+use ethers::types::{Address, Signature, U256};
+use lambdaworks_crypto::merkle_tree::proof::Proof;
+use serde::{Deserialize, Serialize};
+
+use crate::sdk::{BINARY_MESSAGES_FEATURE, CHUNKED_MESSAGES_FEATURE, ZSTD_MESSAGES_FEATURE};
+use crate::types::{
+    BatchedClientMessage, BatchedClientMessageEntry, ClientMessage, GetSubmissionStatus,
+    InclusionQuery, ProvingSystemId, ReplayProtection, SponsoredClientMessage, VerificationData,
+    VerificationDataCommitment,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl WireFormat {
+    /// Returns the format of the messages sent to or received from a peer with these features.
+    /// Binary messages are sent in chunks, so both features are needed to use them.
+    pub fn negotiate(peer_features: &[String]) -> Self {
+        let supports = |feature: &str| peer_features.iter().any(|f| f == feature);
+        if supports(BINARY_MESSAGES_FEATURE) && supports(CHUNKED_MESSAGES_FEATURE) {
+            WireFormat::Binary
+        } else {
+            WireFormat::Json
+        }
+    }
+}
+
+/// Messages smaller than this are sent uncompressed, as compressing them saves too little.
+pub const COMPRESSION_THRESHOLD: usize = 16 << 10;
+
+/// The default level of zstd, which compresses proofs about as well as higher levels while being
+/// much faster.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// First bytes of a zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Returns whether the messages sent to a peer with these features can be compressed.
+/// Compressed messages are sent in chunks, so both features are needed to use them.
+pub fn compression_supported(peer_features: &[String]) -> bool {
+    let supports = |feature: &str| peer_features.iter().any(|f| f == feature);
+    supports(ZSTD_MESSAGES_FEATURE) && supports(CHUNKED_MESSAGES_FEATURE)
+}
+
+/// Compresses an encoded message with zstd. Returns `None` if the message is smaller than
+/// `COMPRESSION_THRESHOLD` or compressing it doesn't make it smaller, so it's sent as it is.
+pub fn compress(message: &[u8]) -> Result<Option<Vec<u8>>, WireError> {
+    if message.len() < COMPRESSION_THRESHOLD {
+        return Ok(None);
+    }
+    let compressed =
+        zstd::bulk::compress(message, COMPRESSION_LEVEL).map_err(WireError::Compression)?;
+    Ok((compressed.len() < message.len()).then_some(compressed))
+}
+
+/// Decompresses a message compressed with `compress`, returning any other message as it is.
+/// # Errors
+/// * `Compression` if the message is not a valid zstd frame.
+/// * `DecompressedTooLarge` if the message decompresses to more than `max_size` bytes.
+pub fn decompress(message: Vec<u8>, max_size: usize) -> Result<Vec<u8>, WireError> {
+    if !message.starts_with(&ZSTD_MAGIC) {
+        return Ok(message);
+    }
+
+    // The output is bounded, so that a small message can't exhaust the memory of the batcher
+    let mut decompressed = Vec::new();
+    zstd::stream::read::Decoder::new(message.as_slice())
+        .map_err(WireError::Compression)?
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(WireError::Compression)?;
+    if decompressed.len() > max_size {
+        return Err(WireError::DecompressedTooLarge(max_size));
+    }
+    Ok(decompressed)
+}
+
+/// Parses the features announced by a client in the `PROTOCOL_FEATURES_HEADER`.
+pub fn parse_features(header: &str) -> Vec<String> {
+    header
+        .split(',')
+        .map(str::trim)
+        .filter(|feature| !feature.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Any of the messages a client sends to the batcher.
+#[derive(Debug, Clone)]
+pub enum WireMessage {
+    Client(ClientMessage),
+    Batched(BatchedClientMessage),
+    Sponsored(SponsoredClientMessage),
+    InclusionQuery(InclusionQuery),
+    GetSubmissionStatus(GetSubmissionStatus),
+}
+
+pub enum WireError {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    /// The JSON is not any of the client messages.
+    UnknownMessage,
+    Compression(io::Error),
+    /// The compressed message is larger than the given maximum size once decompressed.
+    DecompressedTooLarge(usize),
+    /// The public key to encrypt the message to is a point of low order.
+    InvalidEncryptionKey,
+    /// The encrypted message can't be decrypted with the key of the batcher.
+    Decryption,
+}
+
+impl fmt::Debug for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireError::Json(e) => write!(f, "Invalid JSON message: {}", e),
+            WireError::Binary(e) => write!(f, "Invalid binary message: {}", e),
+            WireError::UnknownMessage => write!(f, "Failed to deserialize client message"),
+            WireError::Compression(e) => write!(f, "Invalid compressed message: {}", e),
+            WireError::DecompressedTooLarge(max_size) => write!(
+                f,
+                "Compressed message is larger than the maximum of {} bytes",
+                max_size
+            ),
+            WireError::InvalidEncryptionKey => {
+                write!(f, "Encryption key of the batcher is a point of low order")
+            }
+            WireError::Decryption => write!(f, "Failed to decrypt the encrypted message"),
+        }
+    }
+}
+
+impl WireMessage {
+    /// Returns the submission id of the message, if it has one. Batched messages are responded
+    /// once per entry, and queries are not submissions, so they have none.
+    pub fn submission_id(&self) -> Option<u64> {
+        match self {
+            WireMessage::Client(msg) => msg.submission_id,
+            WireMessage::Batched(_)
+            | WireMessage::InclusionQuery(_)
+            | WireMessage::GetSubmissionStatus(_) => None,
+            WireMessage::Sponsored(msg) => msg.client_message.submission_id,
+        }
+    }
+
+    /// Returns the verification data of the message: the one of client and sponsored messages,
+    /// and the ones of the entries of batched messages, in order. Queries have none.
+    pub fn verification_data(&self) -> Vec<&VerificationData> {
+        match self {
+            WireMessage::Client(msg) => vec![&msg.verification_data],
+            WireMessage::Batched(msg) => msg
+                .entries
+                .iter()
+                .map(|entry| &entry.verification_data)
+                .collect(),
+            WireMessage::Sponsored(msg) => vec![&msg.client_message.verification_data],
+            WireMessage::InclusionQuery(_) | WireMessage::GetSubmissionStatus(_) => vec![],
+        }
+    }
+
+    fn verification_data_mut(&mut self) -> Vec<&mut VerificationData> {
+        match self {
+            WireMessage::Client(msg) => vec![&mut msg.verification_data],
+            WireMessage::Batched(msg) => msg
+                .entries
+                .iter_mut()
+                .map(|entry| &mut entry.verification_data)
+                .collect(),
+            WireMessage::Sponsored(msg) => vec![&mut msg.client_message.verification_data],
+            WireMessage::InclusionQuery(_) | WireMessage::GetSubmissionStatus(_) => vec![],
+        }
+    }
+
+    /// Returns the nonce and expiry of the message, signed by its prover. Queries have none.
+    pub fn replay_protection(&self) -> Option<&ReplayProtection> {
+        match self {
+            WireMessage::Client(msg) => msg.replay_protection.as_ref(),
+            WireMessage::Batched(msg) => msg.replay_protection.as_ref(),
+            WireMessage::Sponsored(msg) => msg.client_message.replay_protection.as_ref(),
+            WireMessage::InclusionQuery(_) | WireMessage::GetSubmissionStatus(_) => None,
+        }
+    }
+
+    /// Returns whether the verification data of the message has metadata, which is only sent to
+    /// batchers supporting the `user_metadata` feature.
+    pub fn has_metadata(&self) -> bool {
+        self.verification_data()
+            .iter()
+            .any(|verification_data| verification_data.metadata.is_some())
+    }
+
+    /// Encodes the message in the given format.
+    pub fn encode(&self, format: WireFormat) -> Result<Vec<u8>, WireError> {
+        match format {
+            WireFormat::Json => self.to_json().map(String::into_bytes),
+            WireFormat::Binary => {
+                bincode::serialize(&BinaryMessage::from(self)).map_err(WireError::Binary)
+            }
+        }
+    }
+
+    /// Encodes the message as JSON, the text sent in the frames of JSON messages.
+    pub fn to_json(&self) -> Result<String, WireError> {
+        let encoded = match self {
+            WireMessage::Client(msg) => serde_json::to_string(msg),
+            WireMessage::Batched(msg) => serde_json::to_string(msg),
+            WireMessage::Sponsored(msg) => serde_json::to_string(msg),
+            WireMessage::InclusionQuery(msg) => serde_json::to_string(msg),
+            WireMessage::GetSubmissionStatus(msg) => serde_json::to_string(msg),
+        };
+        encoded.map_err(WireError::Json)
+    }
+
+    /// Decodes a message encoded in the given format. As JSON messages don't say which message
+    /// they are, each of them is tried in turn.
+    pub fn decode(format: WireFormat, bytes: &[u8]) -> Result<Self, WireError> {
+        match format {
+            WireFormat::Json => {
+                if let Ok(msg) = serde_json::from_slice::<ClientMessage>(bytes) {
+                    Ok(WireMessage::Client(msg))
+                } else if let Ok(msg) = serde_json::from_slice::<BatchedClientMessage>(bytes) {
+                    Ok(WireMessage::Batched(msg))
+                } else if let Ok(msg) = serde_json::from_slice::<SponsoredClientMessage>(bytes) {
+                    Ok(WireMessage::Sponsored(msg))
+                } else if let Ok(msg) = serde_json::from_slice::<InclusionQuery>(bytes) {
+                    Ok(WireMessage::InclusionQuery(msg))
+                } else if let Ok(msg) = serde_json::from_slice::<GetSubmissionStatus>(bytes) {
+                    Ok(WireMessage::GetSubmissionStatus(msg))
+                } else {
+                    Err(WireError::UnknownMessage)
+                }
+            }
+            WireFormat::Binary => bincode::deserialize::<BinaryMessage>(bytes)
+                .map(WireMessage::from)
+                .map_err(WireError::Binary),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum BinaryMessage {
+    Client(BinaryClientMessage),
+    Batched {
+        entries: Vec<(BinaryVerificationData, Vec<[u8; 32]>)>,
+        entries_merkle_root: [u8; 32],
+        entry_count: u64,
+        signature: BinarySignature,
+        replay_protection: Option<(u64, u64)>,
+    },
+    Sponsored {
+        client_message: BinaryClientMessage,
+        sponsor_signature: BinarySignature,
+    },
+    /// A client or sponsored message with the submission id of its client message.
+    Identified {
+        submission_id: u64,
+        message: Box<BinaryMessage>,
+    },
+    InclusionQuery {
+        commitments: Vec<BinaryCommitment>,
+    },
+    GetSubmissionStatus {
+        commitment: BinaryCommitment,
+    },
+    /// A message with the metadata of its verification data, in the order of
+    /// `WireMessage::verification_data`.
+    WithMetadata {
+        metadata: Vec<Option<Vec<u8>>>,
+        message: Box<BinaryMessage>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryClientMessage {
+    verification_data: BinaryVerificationData,
+    signature: BinarySignature,
+    replay_protection: Option<(u64, u64)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryVerificationData {
+    proving_system: ProvingSystemId,
+    proof: Vec<u8>,
+    pub_input: Option<Vec<u8>>,
+    verification_key: Option<Vec<u8>>,
+    vm_program_code: Option<Vec<u8>>,
+    proof_generator_addr: [u8; 20],
+    tag: Option<[u8; 32]>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryCommitment {
+    proof_commitment: [u8; 32],
+    pub_input_commitment: [u8; 32],
+    proving_system_aux_data_commitment: [u8; 32],
+    proof_generator_addr: [u8; 20],
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinarySignature {
+    r: [u8; 32],
+    s: [u8; 32],
+    v: u64,
+}
+
+impl From<&WireMessage> for BinaryMessage {
+    fn from(message: &WireMessage) -> Self {
+        let binary_message = match message {
+            WireMessage::Client(msg) => BinaryMessage::Client(msg.into()),
+            WireMessage::Batched(msg) => BinaryMessage::Batched {
+                entries: msg
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        (
+                            (&entry.verification_data).into(),
+                            entry.entries_inclusion_proof.merkle_path.clone(),
+                        )
+                    })
+                    .collect(),
+                entries_merkle_root: msg.entries_merkle_root,
+                entry_count: msg.entry_count,
+                signature: (&msg.signature).into(),
+                replay_protection: msg.replay_protection.map(|replay_protection| {
+                    (replay_protection.nonce, replay_protection.expires_at)
+                }),
+            },
+            WireMessage::Sponsored(msg) => BinaryMessage::Sponsored {
+                client_message: (&msg.client_message).into(),
+                sponsor_signature: (&msg.sponsor_signature).into(),
+            },
+            WireMessage::InclusionQuery(msg) => BinaryMessage::InclusionQuery {
+                commitments: msg.commitments.iter().map(BinaryCommitment::from).collect(),
+            },
+            WireMessage::GetSubmissionStatus(msg) => BinaryMessage::GetSubmissionStatus {
+                commitment: (&msg.verification_data_commitment).into(),
+            },
+        };
+        let binary_message = match message.submission_id() {
+            Some(submission_id) => BinaryMessage::Identified {
+                submission_id,
+                message: Box::new(binary_message),
+            },
+            None => binary_message,
+        };
+        if message.has_metadata() {
+            BinaryMessage::WithMetadata {
+                metadata: message
+                    .verification_data()
+                    .into_iter()
+                    .map(|verification_data| verification_data.metadata.clone())
+                    .collect(),
+                message: Box::new(binary_message),
+            }
+        } else {
+            binary_message
+        }
+    }
+}
+
+impl From<BinaryMessage> for WireMessage {
+    fn from(message: BinaryMessage) -> Self {
+        match message {
+            BinaryMessage::Client(msg) => WireMessage::Client(msg.into()),
+            BinaryMessage::Batched {
+                entries,
+                entries_merkle_root,
+                entry_count,
+                signature,
+                replay_protection,
+            } => WireMessage::Batched(BatchedClientMessage {
+                entries: entries
+                    .into_iter()
+                    .map(
+                        |(verification_data, merkle_path)| BatchedClientMessageEntry {
+                            verification_data: verification_data.into(),
+                            entries_inclusion_proof: Proof { merkle_path },
+                        },
+                    )
+                    .collect(),
+                entries_merkle_root,
+                entry_count,
+                signature: signature.into(),
+                replay_protection: replay_protection
+                    .map(|(nonce, expires_at)| ReplayProtection { nonce, expires_at }),
+            }),
+            BinaryMessage::Sponsored {
+                client_message,
+                sponsor_signature,
+            } => WireMessage::Sponsored(SponsoredClientMessage {
+                client_message: client_message.into(),
+                sponsor_signature: sponsor_signature.into(),
+            }),
+            BinaryMessage::Identified {
+                submission_id,
+                message,
+            } => {
+                let mut message = WireMessage::from(*message);
+                match &mut message {
+                    WireMessage::Client(msg) => msg.submission_id = Some(submission_id),
+                    WireMessage::Sponsored(msg) => {
+                        msg.client_message.submission_id = Some(submission_id)
+                    }
+                    // Batched messages and queries are never identified
+                    WireMessage::Batched(_)
+                    | WireMessage::InclusionQuery(_)
+                    | WireMessage::GetSubmissionStatus(_) => {}
+                }
+                message
+            }
+            BinaryMessage::InclusionQuery { commitments } => {
+                WireMessage::InclusionQuery(InclusionQuery {
+                    commitments: commitments
+                        .into_iter()
+                        .map(VerificationDataCommitment::from)
+                        .collect(),
+                })
+            }
+            BinaryMessage::GetSubmissionStatus { commitment } => {
+                WireMessage::GetSubmissionStatus(GetSubmissionStatus {
+                    verification_data_commitment: commitment.into(),
+                })
+            }
+            BinaryMessage::WithMetadata { metadata, message } => {
+                let mut message = WireMessage::from(*message);
+                for (verification_data, metadata) in
+                    message.verification_data_mut().into_iter().zip(metadata)
+                {
+                    verification_data.metadata = metadata;
+                }
+                message
+            }
+        }
+    }
+}
+
+impl From<&ClientMessage> for BinaryClientMessage {
+    fn from(msg: &ClientMessage) -> Self {
+        BinaryClientMessage {
+            verification_data: (&msg.verification_data).into(),
+            signature: (&msg.signature).into(),
+            replay_protection: msg
+                .replay_protection
+                .map(|replay_protection| (replay_protection.nonce, replay_protection.expires_at)),
+        }
+    }
+}
+
+impl From<BinaryClientMessage> for ClientMessage {
+    fn from(msg: BinaryClientMessage) -> Self {
+        ClientMessage {
+            verification_data: msg.verification_data.into(),
+            signature: msg.signature.into(),
+            replay_protection: msg
+                .replay_protection
+                .map(|(nonce, expires_at)| ReplayProtection { nonce, expires_at }),
+            submission_id: None,
+        }
+    }
+}
+
+impl From<&VerificationData> for BinaryVerificationData {
+    fn from(verification_data: &VerificationData) -> Self {
+        BinaryVerificationData {
+            proving_system: verification_data.proving_system.clone(),
+            proof: verification_data.proof.clone(),
+            pub_input: verification_data.pub_input.clone(),
+            verification_key: verification_data.verification_key.clone(),
+            vm_program_code: verification_data.vm_program_code.clone(),
+            proof_generator_addr: verification_data.proof_generator_addr.0,
+            tag: verification_data.tag,
+        }
+    }
+}
+
+impl From<BinaryVerificationData> for VerificationData {
+    fn from(verification_data: BinaryVerificationData) -> Self {
+        VerificationData {
+            proving_system: verification_data.proving_system,
+            proof: verification_data.proof,
+            pub_input: verification_data.pub_input,
+            verification_key: verification_data.verification_key,
+            vm_program_code: verification_data.vm_program_code,
+            proof_generator_addr: Address::from(verification_data.proof_generator_addr),
+            tag: verification_data.tag,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        }
+    }
+}
+
+impl From<&VerificationDataCommitment> for BinaryCommitment {
+    fn from(commitment: &VerificationDataCommitment) -> Self {
+        BinaryCommitment {
+            proof_commitment: commitment.proof_commitment,
+            pub_input_commitment: commitment.pub_input_commitment,
+            proving_system_aux_data_commitment: commitment.proving_system_aux_data_commitment,
+            proof_generator_addr: commitment.proof_generator_addr,
+        }
+    }
+}
+
+impl From<BinaryCommitment> for VerificationDataCommitment {
+    fn from(commitment: BinaryCommitment) -> Self {
+        VerificationDataCommitment {
+            proof_commitment: commitment.proof_commitment,
+            pub_input_commitment: commitment.pub_input_commitment,
+            proving_system_aux_data_commitment: commitment.proving_system_aux_data_commitment,
+            proof_generator_addr: commitment.proof_generator_addr,
+        }
+    }
+}
+
+impl From<&Signature> for BinarySignature {
+    fn from(signature: &Signature) -> Self {
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        signature.r.to_big_endian(&mut r);
+        signature.s.to_big_endian(&mut s);
+        BinarySignature {
+            r,
+            s,
+            v: signature.v,
+        }
+    }
+}
+
+impl From<BinarySignature> for Signature {
+    fn from(signature: BinarySignature) -> Self {
+        Signature {
+            r: U256::from_big_endian(&signature.r),
+            s: U256::from_big_endian(&signature.s),
+            v: signature.v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{deterministic_wallet, random_verification_data, seeded_rng};
+    use std::time::Duration;
+
+    async fn client_message(seed: u64) -> ClientMessage {
+        let mut verification_data = random_verification_data(&mut seeded_rng(seed));
+        verification_data.tag = Some([7; 32]);
+        let mut msg = ClientMessage::new(verification_data, &deterministic_wallet(0))
+            .await
+            .unwrap();
+        msg.replay_protection = Some(ReplayProtection::new(Duration::from_secs(60)));
+        msg
+    }
+
+    #[tokio::test]
+    async fn test_messages_are_decoded_in_both_formats() {
+        let client_msg = client_message(0).await;
+        let verification_data = vec![
+            random_verification_data(&mut seeded_rng(1)),
+            random_verification_data(&mut seeded_rng(2)),
+        ];
+        let mut batched_msg =
+            BatchedClientMessage::new(verification_data, &deterministic_wallet(0))
+                .await
+                .unwrap();
+        batched_msg.replay_protection = Some(ReplayProtection::new(Duration::from_secs(60)));
+        let sponsored_msg =
+            SponsoredClientMessage::new(client_msg.clone(), &deterministic_wallet(1))
+                .await
+                .unwrap();
+        let mut identified_msg = client_msg.clone();
+        identified_msg.submission_id = Some(42);
+        let identified_sponsored_msg = SponsoredClientMessage {
+            client_message: identified_msg.clone(),
+            ..sponsored_msg.clone()
+        };
+        let inclusion_query = InclusionQuery {
+            commitments: vec![
+                random_verification_data(&mut seeded_rng(3)).into(),
+                random_verification_data(&mut seeded_rng(4)).into(),
+            ],
+        };
+        let get_submission_status = GetSubmissionStatus {
+            verification_data_commitment: random_verification_data(&mut seeded_rng(5)).into(),
+        };
+        // The metadata is not signed, so it can be set after signing
+        let mut identified_msg_with_metadata = identified_msg.clone();
+        identified_msg_with_metadata.verification_data.metadata = Some(b"job-1".to_vec());
+        let mut batched_msg_with_metadata = batched_msg.clone();
+        batched_msg_with_metadata.entries[1]
+            .verification_data
+            .metadata = Some(b"job-2".to_vec());
+        let messages = [
+            WireMessage::Client(client_msg),
+            WireMessage::Batched(batched_msg),
+            WireMessage::Client(identified_msg_with_metadata),
+            WireMessage::Batched(batched_msg_with_metadata),
+            WireMessage::Sponsored(sponsored_msg),
+            WireMessage::Client(identified_msg),
+            WireMessage::Sponsored(identified_sponsored_msg),
+            WireMessage::InclusionQuery(inclusion_query),
+            WireMessage::GetSubmissionStatus(get_submission_status),
+        ];
+
+        for format in [WireFormat::Json, WireFormat::Binary] {
+            for message in &messages {
+                let encoded = message.encode(format).unwrap();
+                let decoded = WireMessage::decode(format, &encoded).unwrap();
+                assert_eq!(decoded.submission_id(), message.submission_id());
+                assert_eq!(decoded.has_metadata(), message.has_metadata());
+                // Both encodings are compared in JSON, as the messages don't implement PartialEq
+                assert_eq!(
+                    decoded.encode(WireFormat::Json).unwrap(),
+                    message.encode(WireFormat::Json).unwrap()
+                );
+            }
+        }
+
+        assert!(matches!(
+            WireMessage::decode(WireFormat::Json, b"{}"),
+            Err(WireError::UnknownMessage)
+        ));
+        assert!(matches!(
+            WireMessage::decode(WireFormat::Binary, &[0xff; 8]),
+            Err(WireError::Binary(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_binary_messages_are_smaller() {
+        let mut msg = client_message(0).await;
+        msg.verification_data.proof = vec![0xff; 10_000];
+        let msg = WireMessage::Client(msg);
+
+        let json = msg.encode(WireFormat::Json).unwrap();
+        let binary = msg.encode(WireFormat::Binary).unwrap();
+        assert!(binary.len() < 10_500);
+        assert!(json.len() > 3 * binary.len());
+    }
+
+    #[tokio::test]
+    async fn test_large_messages_are_compressed() {
+        let mut msg = client_message(0).await;
+        msg.verification_data.proof = vec![0xab; 1 << 20];
+        let msg = WireMessage::Client(msg);
+
+        for format in [WireFormat::Json, WireFormat::Binary] {
+            let encoded = msg.encode(format).unwrap();
+            let compressed = compress(&encoded).unwrap().unwrap();
+            assert!(compressed.len() * 10 < encoded.len());
+
+            let decompressed = decompress(compressed, encoded.len()).unwrap();
+            assert_eq!(decompressed, encoded);
+            // Uncompressed messages are returned as they are
+            assert_eq!(decompress(encoded.clone(), encoded.len()).unwrap(), encoded);
+        }
+    }
+
+    #[test]
+    fn test_small_or_incompressible_messages_are_not_compressed() {
+        assert!(compress(&[0; COMPRESSION_THRESHOLD - 1]).unwrap().is_none());
+
+        let mut rng = seeded_rng(0);
+        let random: Vec<u8> = (0..COMPRESSION_THRESHOLD)
+            .map(|_| rand::Rng::gen(&mut rng))
+            .collect();
+        assert!(compress(&random).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decompressed_size_is_bounded() {
+        let compressed = compress(&[0; 1 << 20]).unwrap().unwrap();
+        assert!(matches!(
+            decompress(compressed.clone(), (1 << 20) - 1),
+            Err(WireError::DecompressedTooLarge(_))
+        ));
+        assert!(decompress(compressed, 1 << 20).is_ok());
+
+        let mut corrupted = ZSTD_MAGIC.to_vec();
+        corrupted.extend_from_slice(&[0xff; 16]);
+        assert!(matches!(
+            decompress(corrupted, 1 << 20),
+            Err(WireError::Compression(_))
+        ));
+    }
+
+    #[test]
+    fn test_binary_format_needs_both_features() {
+        assert_eq!(
+            parse_features(" chunked_messages,binary_messages,"),
+            vec![CHUNKED_MESSAGES_FEATURE, BINARY_MESSAGES_FEATURE]
+        );
+        let features =
+            |features: &[&str]| -> Vec<String> { features.iter().map(|f| f.to_string()).collect() };
+        assert_eq!(WireFormat::negotiate(&[]), WireFormat::Json);
+        assert_eq!(
+            WireFormat::negotiate(&features(&[BINARY_MESSAGES_FEATURE])),
+            WireFormat::Json
+        );
+        assert_eq!(
+            WireFormat::negotiate(&features(&[
+                CHUNKED_MESSAGES_FEATURE,
+                BINARY_MESSAGES_FEATURE
+            ])),
+            WireFormat::Binary
+        );
+
+        assert!(!compression_supported(&features(&[ZSTD_MESSAGES_FEATURE])));
+        assert!(compression_supported(&features(&[
+            CHUNKED_MESSAGES_FEATURE,
+            ZSTD_MESSAGES_FEATURE
+        ])));
+    }
+}
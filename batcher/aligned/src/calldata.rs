@@ -0,0 +1,49 @@
+//! Calldata of a `verifyBatchInclusion` call to the Aligned service manager, for `export-calldata`,
+//! so contract developers can check the verification of a proof on-chain without writing an
+//! encoding script. The arguments are also given as `cast` takes them, in a command that makes
+//! the call.
+
+use aligned_sdk::types::AlignedVerificationData;
+use ethers::utils::hex;
+
+/// Signature of the function, with its return type as `cast call` takes it.
+pub const VERIFY_BATCH_INCLUSION_SIGNATURE: &str =
+    "verifyBatchInclusion(bytes32,bytes32,bytes32,bytes20,bytes32,bytes,uint256)(bool)";
+
+/// Returns the arguments of the call, in order, as `cast` takes them: the bytes in hex and the
+/// index in decimal.
+pub fn call_arguments(aligned_verification_data: &AlignedVerificationData) -> Vec<String> {
+    let commitment = &aligned_verification_data.verification_data_commitment;
+    let merkle_proof: Vec<u8> = aligned_verification_data
+        .batch_inclusion_proof
+        .merkle_path
+        .iter()
+        .flatten()
+        .copied()
+        .collect();
+    vec![
+        hex_arg(&commitment.proof_commitment),
+        hex_arg(&commitment.pub_input_commitment),
+        hex_arg(&commitment.proving_system_aux_data_commitment),
+        hex_arg(&commitment.proof_generator_addr),
+        hex_arg(&aligned_verification_data.batch_merkle_root),
+        hex_arg(&merkle_proof),
+        aligned_verification_data.index_in_batch.to_string(),
+    ]
+}
+
+/// Returns the `cast call` command that calls the function of the service manager at this
+/// address with these arguments.
+pub fn cast_command(service_manager: &str, arguments: &[String], eth_rpc_url: &str) -> String {
+    format!(
+        "cast call {} \"{}\" {} --rpc-url {}",
+        service_manager,
+        VERIFY_BATCH_INCLUSION_SIGNATURE,
+        arguments.join(" "),
+        eth_rpc_url
+    )
+}
+
+fn hex_arg(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
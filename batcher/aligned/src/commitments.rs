@@ -0,0 +1,111 @@
+//! Commitments of many files at once, for `get-commitment` with several `--input` or a
+//! directory, such as the verification keys and programs of the circuits registered by an
+//! application. Each commitment is the one the batcher computes for the file as the proving
+//! system auxiliary data of a proof: the Keccak-256 of the program of SP1 and Risc0 proofs, and of
+//! the verification key of the other proving systems, which are both `get_commitment` of the file.
+//! Registries keying the files by another hash can compute them with `--hash_function` instead.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::sdk::get_commitment_with_hash;
+use aligned_sdk::types::HashFunction;
+use clap::ValueEnum;
+use ethers::utils::hex;
+
+/// Format of the mapping from each file to its commitment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MappingFormat {
+    /// A `file,commitment` header, followed by a line per file.
+    Csv,
+    /// An object with the commitment of each file, by file.
+    Json,
+}
+
+/// Returns the files of the inputs, where each directory is replaced by the regular files
+/// directly in it, sorted by name. Subdirectories are not read.
+/// # Errors
+/// * `IoError` if an input can't be read.
+pub fn input_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, SubmitError> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        let entries =
+            std::fs::read_dir(input).map_err(|e| SubmitError::IoError(input.clone(), e))?;
+        let mut dir_files = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| SubmitError::IoError(input.clone(), e))?
+                .path();
+            if path.is_file() {
+                dir_files.push(path);
+            }
+        }
+        dir_files.sort();
+        files.extend(dir_files);
+    }
+    Ok(files)
+}
+
+/// Returns the commitment of each file with the given hash function, in order.
+/// # Errors
+/// * `IoError` if a file can't be read.
+pub fn commitments(
+    files: &[PathBuf],
+    hash_function: HashFunction,
+) -> Result<Vec<(PathBuf, [u8; 32])>, SubmitError> {
+    files
+        .iter()
+        .map(|file| {
+            let content = std::fs::read(file).map_err(|e| SubmitError::IoError(file.clone(), e))?;
+            Ok((
+                file.clone(),
+                get_commitment_with_hash(&content, hash_function),
+            ))
+        })
+        .collect()
+}
+
+/// Returns the commitment of each file by file, as JSON, with the commitments in hex.
+pub fn commitments_json(commitments: &[(PathBuf, [u8; 32])]) -> serde_json::Value {
+    let commitments: BTreeMap<String, String> = commitments
+        .iter()
+        .map(|(file, commitment)| (file_name(file), format!("0x{}", hex::encode(commitment))))
+        .collect();
+    serde_json::json!(commitments)
+}
+
+/// Returns the mapping from each file to its commitment in the given format.
+pub fn mapping(commitments: &[(PathBuf, [u8; 32])], format: MappingFormat) -> String {
+    match format {
+        MappingFormat::Csv => {
+            let mut csv = String::from("file,commitment\n");
+            for (file, commitment) in commitments {
+                csv.push_str(&format!(
+                    "{},0x{}\n",
+                    csv_field(&file_name(file)),
+                    hex::encode(commitment)
+                ));
+            }
+            csv
+        }
+        MappingFormat::Json => format!("{}\n", commitments_json(commitments)),
+    }
+}
+
+fn file_name(file: &Path) -> String {
+    file.display().to_string()
+}
+
+/// Quotes a field with a comma, a quote or a line break, as in RFC 4180.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
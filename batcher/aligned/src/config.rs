@@ -0,0 +1,184 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, Command};
+use serde::Deserialize;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::types::NetworkEndpoints;
+
+const CONFIG_ARG: &str = "config";
+
+/// Values of the config file, which are the defaults of the flags of every command taking
+/// them. Flags given on the command line, or through their environment variables, override
+/// them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CliConfig {
+    /// Address of the batcher, `--conn`.
+    batcher_url: Option<String>,
+    /// Ethereum RPC provider address, `--rpc`.
+    rpc_url: Option<String>,
+    /// Ethereum network, `--chain`.
+    chain: Option<String>,
+    /// Address of the AlignedServiceManager contract, `--contract_address`.
+    contract_address: Option<String>,
+    /// Address of the BatcherPaymentService contract, `--batcher_addr`.
+    batcher_payment_service: Option<String>,
+    /// Keystore with which proofs are signed and deposits are made, `--keystore_path`.
+    keystore_path: Option<String>,
+    /// AWS KMS key with which proofs are signed, `--aws_kms_key_id`.
+    aws_kms_key_id: Option<String>,
+    /// AWS region of the KMS key, `--aws_region`.
+    aws_region: Option<String>,
+    /// Directory where the aligned verification data is written, `--aligned_verification_data_path`.
+    output_dir: Option<String>,
+    /// Custom network, a `[network]` table with all its endpoints, which sets `--conn`, `--rpc`,
+    /// `--contract_address` and `--batcher_addr` unless they are set by the keys above.
+    network: Option<NetworkEndpoints>,
+    /// Most proofs submitted per day, `--max_proofs_per_day`.
+    max_proofs_per_day: Option<u64>,
+    /// Most ether spent in fees per day, as a string such as `"0.1"`, `--max_spend_per_day`.
+    max_spend_per_day: Option<String>,
+    /// File recording the usage of the day, `--usage_store`.
+    usage_store: Option<String>,
+}
+
+impl CliConfig {
+    /// Returns the flags set by the config file, with their values.
+    fn flags(&self) -> Vec<(&'static str, String)> {
+        let network = self.network.as_ref();
+        [
+            (
+                "conn",
+                or_network(&self.batcher_url, network.map(|n| &n.batcher_url)),
+            ),
+            (
+                "rpc",
+                or_network(&self.rpc_url, network.map(|n| &n.eth_rpc_url)),
+            ),
+            ("chain", self.chain.as_deref()),
+            (
+                "contract_address",
+                or_network(
+                    &self.contract_address,
+                    network.map(|n| &n.aligned_service_manager),
+                ),
+            ),
+            (
+                "batcher_addr",
+                or_network(
+                    &self.batcher_payment_service,
+                    network.map(|n| &n.batcher_payment_service),
+                ),
+            ),
+            ("keystore_path", self.keystore_path.as_deref()),
+            ("aws_kms_key_id", self.aws_kms_key_id.as_deref()),
+            ("aws_region", self.aws_region.as_deref()),
+            ("aligned_verification_data_path", self.output_dir.as_deref()),
+            ("max_spend_per_day", self.max_spend_per_day.as_deref()),
+            ("usage_store", self.usage_store.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(flag, value)| value.map(|value| (flag, value.to_string())))
+        .chain(
+            self.max_proofs_per_day
+                .map(|max_proofs_per_day| ("max_proofs_per_day", max_proofs_per_day.to_string())),
+        )
+        .collect()
+    }
+}
+
+fn or_network<'a>(value: &'a Option<String>, network: Option<&'a String>) -> Option<&'a str> {
+    value.as_deref().or(network.map(String::as_str))
+}
+
+/// Global `--config` flag, which is read before the command line is parsed.
+pub fn config_arg() -> Arg {
+    Arg::new(CONFIG_ARG)
+        .long(CONFIG_ARG)
+        .global(true)
+        .value_name("FILE")
+        .help("Config file setting the defaults of the flags, instead of $XDG_CONFIG_HOME/aligned/config.toml")
+}
+
+/// Loads the config file given with `--config` in the arguments, or the one of the user if
+/// there is one, which is `$XDG_CONFIG_HOME/aligned/config.toml`, or `~/.config/aligned/config.toml`
+/// when `XDG_CONFIG_HOME` is not set.
+/// # Errors
+/// * `IoError` if the config file given can't be read.
+/// * `GenericError` if the config file is not valid.
+pub fn load(args: impl IntoIterator<Item = OsString>) -> Result<CliConfig, SubmitError> {
+    let path = match config_path_arg(args) {
+        Some(path) => path,
+        None => match user_config_path().filter(|path| path.is_file()) {
+            Some(path) => path,
+            None => return Ok(CliConfig::default()),
+        },
+    };
+    read(&path)
+}
+
+fn read(path: &Path) -> Result<CliConfig, SubmitError> {
+    let config =
+        std::fs::read_to_string(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))?;
+    toml::from_str(&config).map_err(|e| {
+        SubmitError::GenericError(format!("Invalid config file {}: {}", path.display(), e))
+    })
+}
+
+/// Returns the value of `--config`, which is looked for before the command line is parsed
+/// since the config file sets the defaults of the other flags.
+fn config_path_arg(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(config_home) => PathBuf::from(config_home),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("aligned").join("config.toml"))
+}
+
+/// Sets the values of the config file as the defaults of the flags of the command and of its
+/// subcommands. Flags set by the config file are no longer required.
+pub fn with_defaults(command: Command, config: &CliConfig) -> Command {
+    set_defaults(command, &config.flags())
+}
+
+fn set_defaults(mut command: Command, flags: &[(&'static str, String)]) -> Command {
+    let args: Vec<_> = command
+        .get_arguments()
+        .filter_map(|arg| {
+            let (_, value) = flags
+                .iter()
+                .find(|(flag, _)| arg.get_long() == Some(flag))?;
+            Some((arg.get_id().clone(), value.clone()))
+        })
+        .collect();
+    for (id, value) in args {
+        command = command.mut_arg(id, |arg| arg.default_value(value).required(false));
+    }
+
+    let subcommands: Vec<_> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    for subcommand in subcommands {
+        command = command.mut_subcommand(subcommand, |subcommand| set_defaults(subcommand, flags));
+    }
+    command
+}
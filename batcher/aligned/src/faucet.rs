@@ -0,0 +1,78 @@
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use aligned_sdk::errors::SubmitError;
+
+/// Body of the requests sent to a faucet endpoint.
+#[derive(Serialize)]
+struct FaucetRequest {
+    address: Address,
+}
+
+/// Response of a faucet endpoint. Faucets may leave out the transaction, such as when the
+/// request is queued.
+#[derive(Deserialize)]
+struct FaucetResponse {
+    tx_hash: Option<String>,
+}
+
+/// Asks the faucet at `faucet_url` to send Ether to the address, by posting it as JSON in an
+/// `address` field. Returns the hash of the funding transaction, if the faucet responds with it
+/// in a `tx_hash` field.
+pub async fn request_from_faucet(
+    faucet_url: &str,
+    address: Address,
+) -> Result<Option<String>, SubmitError> {
+    let body = serde_json::to_string(&FaucetRequest { address })?;
+    let response = reqwest::Client::new()
+        .post(faucet_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| SubmitError::GenericError(format!("Error while calling the faucet: {}", e)))?;
+
+    let status = response.status();
+    let body = response.text().await.map_err(|e| {
+        SubmitError::GenericError(format!("Error while reading the faucet response: {}", e))
+    })?;
+    if !status.is_success() {
+        return Err(SubmitError::GenericError(format!(
+            "Faucet responded with {}: {}",
+            status, body
+        )));
+    }
+
+    // Faucets answering with something other than JSON still funded the address
+    Ok(serde_json::from_str::<FaucetResponse>(&body)
+        .ok()
+        .and_then(|response| response.tx_hash))
+}
+
+/// Sends `amount` to the address from an account funded by anvil, which is how addresses are
+/// funded on devnet. Returns the hash of the transaction once it's included.
+pub async fn fund_from_anvil(
+    eth_rpc_url: &str,
+    wallet: LocalWallet,
+    address: Address,
+    amount: U256,
+) -> Result<H256, SubmitError> {
+    let provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e)))?;
+    let chain_id = provider
+        .get_chainid()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while getting chain id: {}", e)))?;
+    let client = SignerMiddleware::new(provider, wallet.with_chain_id(chain_id.as_u64()));
+
+    let tx = TransactionRequest::new().to(address).value(amount);
+    let receipt = client
+        .send_transaction(tx, None)
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while sending transaction: {}", e)))?
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while sending transaction: {}", e)))?
+        .ok_or_else(|| SubmitError::EthError("Transaction was dropped".to_string()))?;
+
+    Ok(receipt.transaction_hash)
+}
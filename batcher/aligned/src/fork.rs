@@ -0,0 +1,38 @@
+use std::process::Command;
+
+use ethers::prelude::*;
+use ethers::utils::{Anvil, AnvilInstance};
+use log::debug;
+
+use aligned_sdk::errors::SubmitError;
+
+/// Spawns a local anvil node forking the network behind `fork_url` at the given block, and
+/// returns it along with a provider connected to it. The fork lives as long as the returned
+/// `AnvilInstance` is not dropped.
+/// # Errors
+/// * `GenericError` if `anvil` is not installed or not in the `PATH`.
+/// * `EthError` if the provider can't connect to the fork.
+pub fn spawn_fork(
+    fork_url: &str,
+    fork_block_number: Option<u64>,
+) -> Result<(AnvilInstance, Provider<Http>), SubmitError> {
+    // Anvil::spawn panics when it can't run anvil, so its binary is looked up first
+    if Command::new("anvil").arg("--version").output().is_err() {
+        return Err(SubmitError::GenericError(
+            "anvil was not found in the PATH, install Foundry with `curl -L https://foundry.paradigm.xyz | bash && foundryup`"
+                .to_string(),
+        ));
+    }
+
+    let mut anvil = Anvil::new().fork(fork_url);
+    if let Some(fork_block_number) = fork_block_number {
+        anvil = anvil.fork_block_number(fork_block_number);
+    }
+    let anvil = anvil.spawn();
+    debug!("Anvil fork listening on {}", anvil.endpoint());
+
+    let provider = Provider::<Http>::try_from(anvil.endpoint())
+        .map_err(|e| SubmitError::EthError(format!("Error while connecting to anvil: {}", e)))?;
+
+    Ok((anvil, provider))
+}
@@ -0,0 +1,88 @@
+//! Foundry test of the inclusion of a proof, for `export-foundry-test`, so contract teams
+//! integrating Aligned can check that `verifyBatchInclusion` accepts a saved proof before writing
+//! their own contracts. The test forks the network of the RPC node and calls the service manager
+//! with the arguments of `export-calldata`.
+
+use aligned_sdk::types::AlignedVerificationData;
+use ethers::types::Address;
+use ethers::utils::{hex, to_checksum};
+
+/// Name of the contract of the test, to run it with `forge test --match-contract`.
+pub const CONTRACT_NAME: &str = "AlignedInclusionTest";
+
+/// Returns the source of a Foundry test asserting that the service manager at this address finds
+/// the proof included in a verified batch, on a fork of the network of the RPC node.
+/// # Arguments
+/// * `service_manager` - The address of the Aligned service manager contract.
+/// * `aligned_verification_data` - The aligned verification data of the proof.
+/// * `eth_rpc_url` - The URL of the RPC node whose network is forked.
+pub fn foundry_test(
+    service_manager: Address,
+    aligned_verification_data: &AlignedVerificationData,
+    eth_rpc_url: &str,
+) -> String {
+    let commitment = &aligned_verification_data.verification_data_commitment;
+    let merkle_proof: Vec<u8> = aligned_verification_data
+        .batch_inclusion_proof
+        .merkle_path
+        .iter()
+        .flatten()
+        .copied()
+        .collect();
+
+    // Solidity hex literals take the bytes without the 0x prefix
+    format!(
+        r#"// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.12;
+
+import {{Test}} from "forge-std/Test.sol";
+
+interface IAlignedServiceManager {{
+    function verifyBatchInclusion(
+        bytes32 proofCommitment,
+        bytes32 pubInputCommitment,
+        bytes32 provingSystemAuxDataCommitment,
+        bytes20 proofGeneratorAddr,
+        bytes32 batchMerkleRoot,
+        bytes memory merkleProof,
+        uint256 verificationDataBatchIndex
+    ) external view returns (bool);
+}}
+
+// Generated by `aligned export-foundry-test`. Run it with:
+// forge test --match-contract {contract_name}
+contract {contract_name} is Test {{
+    IAlignedServiceManager constant SERVICE_MANAGER =
+        IAlignedServiceManager({service_manager});
+
+    function setUp() public {{
+        vm.createSelectFork("{eth_rpc_url}");
+    }}
+
+    function testProofIsIncludedInVerifiedBatch() public view {{
+        bool included = SERVICE_MANAGER.verifyBatchInclusion(
+            hex"{proof_commitment}",
+            hex"{pub_input_commitment}",
+            hex"{proving_system_aux_data_commitment}",
+            hex"{proof_generator_addr}",
+            hex"{batch_merkle_root}",
+            hex"{merkle_proof}",
+            {index_in_batch}
+        );
+        assertTrue(included, "proof is not included in a verified batch");
+    }}
+}}
+"#,
+        contract_name = CONTRACT_NAME,
+        service_manager = to_checksum(&service_manager, None),
+        eth_rpc_url = eth_rpc_url,
+        proof_commitment = hex::encode(commitment.proof_commitment),
+        pub_input_commitment = hex::encode(commitment.pub_input_commitment),
+        proving_system_aux_data_commitment =
+            hex::encode(commitment.proving_system_aux_data_commitment),
+        proof_generator_addr = hex::encode(commitment.proof_generator_addr),
+        batch_merkle_root = hex::encode(aligned_verification_data.batch_merkle_root),
+        merkle_proof = hex::encode(merkle_proof),
+        index_in_batch = aligned_verification_data.index_in_batch,
+    )
+}
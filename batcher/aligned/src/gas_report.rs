@@ -0,0 +1,112 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use log::info;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::eth::{AlignedLayerServiceManagerContract, BatchVerifiedFilter};
+
+use crate::fork::spawn_fork;
+
+/// Gas estimated for a `verifyBatchInclusion` call with a merkle proof of the given depth.
+pub struct GasReportEntry {
+    pub merkle_depth: usize,
+    pub max_batch_size: usize,
+    pub gas: U256,
+}
+
+pub struct GasReport {
+    /// Root of the responded batch used to exercise the full verification path.
+    pub batch_merkle_root: [u8; 32],
+    /// Gas of the early return taken when the batch does not exist.
+    pub unknown_batch_gas: U256,
+    pub entries: Vec<GasReportEntry>,
+}
+
+/// Estimates the gas consumed by `verifyBatchInclusion` for merkle depths from 1 up to
+/// `max_merkle_depth`, using `eth_estimateGas` on a local anvil fork of the network.
+/// The merkle proof is always processed entirely by the contract, whether it is valid or not,
+/// so the estimation is made with the last responded batch and a zeroed proof of each
+/// depth. Estimations include the intrinsic cost of the transaction.
+pub async fn gas_report(
+    fork_url: &str,
+    contract_address: &str,
+    from_block: u64,
+    max_merkle_depth: usize,
+) -> Result<GasReport, SubmitError> {
+    let provider = Provider::<Http>::try_from(fork_url)
+        .map_err(|e| SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e)))?;
+
+    let contract_address = Address::from_str(contract_address)
+        .map_err(|e| SubmitError::InvalidAddress(contract_address.to_string(), e.to_string()))?;
+
+    let service_manager =
+        AlignedLayerServiceManagerContract::new(contract_address, Arc::new(provider));
+
+    let batch_verified_events = service_manager
+        .event::<BatchVerifiedFilter>()
+        .from_block(from_block)
+        .query()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while querying BatchVerified: {}", e)))?;
+
+    let batch_merkle_root = batch_verified_events
+        .last()
+        .ok_or(SubmitError::GenericError(
+            "No responded batch was found to estimate verification gas".to_string(),
+        ))?
+        .batch_merkle_root;
+
+    info!("Forking network to estimate verification gas...");
+    let (_anvil, fork_provider) = spawn_fork(fork_url, None)?;
+    let fork_service_manager =
+        AlignedLayerServiceManagerContract::new(contract_address, Arc::new(fork_provider));
+
+    let unknown_batch_gas =
+        estimate_verify_batch_inclusion_gas(&fork_service_manager, [0u8; 32], 1).await?;
+
+    let mut entries = Vec::with_capacity(max_merkle_depth);
+    for merkle_depth in 1..=max_merkle_depth {
+        let gas = estimate_verify_batch_inclusion_gas(
+            &fork_service_manager,
+            batch_merkle_root,
+            merkle_depth,
+        )
+        .await?;
+
+        entries.push(GasReportEntry {
+            merkle_depth,
+            max_batch_size: 1 << merkle_depth,
+            gas,
+        });
+    }
+
+    Ok(GasReport {
+        batch_merkle_root,
+        unknown_batch_gas,
+        entries,
+    })
+}
+
+async fn estimate_verify_batch_inclusion_gas(
+    service_manager: &AlignedLayerServiceManagerContract<Provider<Http>>,
+    batch_merkle_root: [u8; 32],
+    merkle_depth: usize,
+) -> Result<U256, SubmitError> {
+    let merkle_proof = vec![0u8; 32 * merkle_depth];
+
+    service_manager
+        .verify_batch_inclusion(
+            [0u8; 32],
+            [0u8; 32],
+            [0u8; 32],
+            [0u8; 20],
+            batch_merkle_root,
+            merkle_proof.into(),
+            U256::zero(),
+        )
+        .estimate_gas()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while estimating gas: {}", e)))
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::eth::{AlignedLayerServiceManagerContract, BatchVerifiedFilter, NewBatchFilter};
+
+pub struct VerifiedBatch {
+    pub batch_merkle_root: [u8; 32],
+    /// Block in which the verification task of the batch was created.
+    pub task_created_block: Option<u32>,
+    /// Where the data of the batch is stored. Left out if the batch was created before the
+    /// scanned range.
+    pub batch_data_pointer: Option<String>,
+    pub verified_block: u64,
+    /// Unix timestamp, in seconds, of the block in which the batch was verified.
+    pub verified_timestamp: u64,
+    pub verified_tx_hash: H256,
+}
+
+/// Lists the batches verified between `from_block` and `to_block`, both included, in the order
+/// in which they were verified, by scanning the `NewBatch` and `BatchVerified` events of the
+/// service manager. `to_block` defaults to the latest block.
+pub async fn list_batches(
+    eth_rpc_url: &str,
+    contract_address: &str,
+    from_block: u64,
+    to_block: Option<u64>,
+) -> Result<Vec<VerifiedBatch>, SubmitError> {
+    let provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e)))?;
+
+    let contract_address = Address::from_str(contract_address)
+        .map_err(|e| SubmitError::InvalidAddress(contract_address.to_string(), e.to_string()))?;
+    let service_manager =
+        AlignedLayerServiceManagerContract::new(contract_address, Arc::new(provider.clone()));
+
+    let to_block: BlockNumber = to_block.map_or(BlockNumber::Latest, |block| block.into());
+
+    let new_batch_events = service_manager
+        .event::<NewBatchFilter>()
+        .from_block(from_block)
+        .to_block(to_block)
+        .query()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while querying NewBatch: {}", e)))?;
+    let mut new_batches: HashMap<[u8; 32], NewBatchFilter> = new_batch_events
+        .into_iter()
+        .map(|new_batch| (new_batch.batch_merkle_root, new_batch))
+        .collect();
+
+    let batch_verified_events = service_manager
+        .event::<BatchVerifiedFilter>()
+        .from_block(from_block)
+        .to_block(to_block)
+        .query_with_meta()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while querying BatchVerified: {}", e)))?;
+
+    // Batches are usually verified in different blocks, but the timestamps are cached in case
+    // they aren't
+    let mut block_timestamps: HashMap<u64, u64> = HashMap::new();
+    let mut verified_batches = Vec::with_capacity(batch_verified_events.len());
+
+    for (event, log_meta) in batch_verified_events {
+        let verified_block = log_meta.block_number.as_u64();
+        let verified_timestamp = match block_timestamps.get(&verified_block) {
+            Some(timestamp) => *timestamp,
+            None => {
+                let timestamp = provider
+                    .get_block(verified_block)
+                    .await
+                    .map_err(|e| {
+                        SubmitError::EthError(format!("Error while getting block: {}", e))
+                    })?
+                    .ok_or(SubmitError::EthError(format!(
+                        "Block {} not found",
+                        verified_block
+                    )))?
+                    .timestamp
+                    .as_u64();
+                block_timestamps.insert(verified_block, timestamp);
+                timestamp
+            }
+        };
+
+        let new_batch = new_batches.remove(&event.batch_merkle_root);
+        verified_batches.push(VerifiedBatch {
+            batch_merkle_root: event.batch_merkle_root,
+            task_created_block: new_batch
+                .as_ref()
+                .map(|new_batch| new_batch.task_created_block),
+            batch_data_pointer: new_batch.map(|new_batch| new_batch.batch_data_pointer),
+            verified_block,
+            verified_timestamp,
+            verified_tx_hash: log_meta.transaction_hash,
+        });
+    }
+
+    Ok(verified_batches)
+}
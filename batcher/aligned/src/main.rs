@@ -1,10 +1,13 @@
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::BufReader;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
+use clap::Args;
+use clap::CommandFactory;
+use clap::FromArgMatches;
 use clap::Parser;
 use clap::Subcommand;
 use clap::ValueEnum;
@@ -15,34 +18,146 @@ use ethers::utils::hex;
 use ethers::utils::parse_ether;
 use log::warn;
 use log::{error, info};
-use transaction::eip2718::TypedTransaction;
-
+use zeroize::Zeroizing;
+
+use aligned_sdk::batch_metadata::read_enriched;
+use aligned_sdk::batch_status::{get_batch_status, BatchStatus};
+use aligned_sdk::build_info::long_version;
+use aligned_sdk::client::{AlignedClient, SubmitOptions};
+use aligned_sdk::discovery::{
+    resolve_batcher_endpoints, DEFAULT_DISCOVERY_URL, DEVNET_ENVIRONMENT,
+};
+use aligned_sdk::encryption::EncryptionConfig;
 use aligned_sdk::errors::{AlignedError, SubmitError};
-use aligned_sdk::sdk::{get_commitment, submit_multiple, verify_proof_onchain};
+use aligned_sdk::fees;
+use aligned_sdk::get_submission_status;
+use aligned_sdk::inclusion_file::{self, FileFormat};
+use aligned_sdk::merkle::batch_commitments;
+use aligned_sdk::pending::{resume_submission, PendingStore, PendingSubmission};
+use aligned_sdk::recovery::recover_aligned_verification_data;
+use aligned_sdk::rpc::AlignedRpcClient;
+use aligned_sdk::sdk::{
+    get_aligned_service_manager_address, get_commitment_with_hash, get_sp1_public_values,
+    get_verify_batch_inclusion_calldata, submit_multiple_best_effort, verify_merkle_inclusion,
+    verify_proof_onchain, wait_proof_verification, EntryTiming, UnconfirmedProof,
+    VerificationWaitConfig,
+};
+use aligned_sdk::signers::pooled::PooledWallet;
+use aligned_sdk::tags::{get_tagged_proofs, TaggedProof};
+use aligned_sdk::tls::TlsConfig;
 use aligned_sdk::types::AlignedVerificationData;
 use aligned_sdk::types::Chain;
+use aligned_sdk::types::HashFunction;
+use aligned_sdk::types::NetworkEndpoints;
 use aligned_sdk::types::ProvingSystemId;
+use aligned_sdk::types::Rejection;
+use aligned_sdk::types::SubmissionStatus;
 use aligned_sdk::types::VerificationData;
-
+use aligned_sdk::types::VerificationDataCommitment;
+use aligned_sdk::usage_limits::{UsageLimits, UsageStore};
+use aligned_sdk::utils::{
+    check_proving_system, infer_proving_system, read_bundle, write_bundle, BUNDLE_EXTENSION,
+};
+
+use crate::commitments::MappingFormat;
+use crate::AlignedCommands::Convert;
 use crate::AlignedCommands::DepositToBatcher;
+use crate::AlignedCommands::EnrichVerificationData;
+use crate::AlignedCommands::ExportCalldata;
+use crate::AlignedCommands::ExportFoundryTest;
+use crate::AlignedCommands::Faucet;
+use crate::AlignedCommands::GasReport;
+use crate::AlignedCommands::GetBatchStatus;
 use crate::AlignedCommands::GetCommitment;
+use crate::AlignedCommands::GetTaggedProofs;
 use crate::AlignedCommands::GetUserBalance;
+use crate::AlignedCommands::ListBatches;
+use crate::AlignedCommands::OperatorStatus;
+use crate::AlignedCommands::Pack;
+use crate::AlignedCommands::Recover;
+use crate::AlignedCommands::ReplayBatch;
+use crate::AlignedCommands::Resume;
+use crate::AlignedCommands::Status;
 use crate::AlignedCommands::Submit;
+use crate::AlignedCommands::Unpack;
+use crate::AlignedCommands::VerifyBinary;
+use crate::AlignedCommands::VerifyInclusionLocal;
 use crate::AlignedCommands::VerifyProofOnchain;
 
+mod calldata;
+mod commitments;
+mod config;
+mod faucet;
+mod fork;
+mod foundry_test;
+mod gas_report;
+mod list_batches;
+mod manifest;
+mod operator_status;
+mod repetitions;
+mod replay;
+mod verify_binary;
+mod wallet;
+
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version = long_version(env!("CARGO_PKG_VERSION")), about, long_about = None)]
 pub struct AlignedArgs {
+    #[arg(
+        name = "Format of the results printed to the standard output, given before the command",
+        long = "output",
+        value_enum,
+        default_value = "text"
+    )]
+    pub output: OutputFormat,
     #[clap(subcommand)]
     pub command: AlignedCommands,
 }
 
+/// Format of the results of a command. The logs are written to the standard error in both
+/// formats, so that the standard output of `json` only holds the results, for scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    /// Prints the results of a command as a single line of JSON, if the output is `json`.
+    fn print_json(self, results: impl FnOnce() -> serde_json::Value) {
+        if self == OutputFormat::Json {
+            println!("{}", results());
+        }
+    }
+}
+
+// The arguments are parsed once, so the size of the largest command doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum AlignedCommands {
     #[clap(about = "Submit proof to the batcher")]
     Submit(SubmitArgs),
     #[clap(about = "Verify the proof was included in a verified batch on Ethereum")]
     VerifyProofOnchain(VerifyProofOnchainArgs),
+    #[clap(
+        about = "Verify the merkle path of the proof against a batch merkle root, without an RPC node",
+        name = "verify-inclusion-local"
+    )]
+    VerifyInclusionLocal(VerifyInclusionLocalArgs),
+    #[clap(
+        about = "Export the calldata of a verifyBatchInclusion call for a proof, with a cast command making it",
+        name = "export-calldata"
+    )]
+    ExportCalldata(ExportCalldataArgs),
+    #[clap(
+        about = "Export a Foundry test asserting that verifyBatchInclusion accepts a proof on a fork",
+        name = "export-foundry-test"
+    )]
+    ExportFoundryTest(ExportFoundryTestArgs),
+    #[clap(
+        about = "Add the blocks and transactions in which the batch of a proof was created and verified to its aligned verification data",
+        name = "enrich-verification-data"
+    )]
+    EnrichVerificationData(EnrichVerificationDataArgs),
 
     // Get commitment for file, command name is get-commitment
     #[clap(about = "Get commitment for file", name = "get-commitment")]
@@ -54,6 +169,76 @@ pub enum AlignedCommands {
     DepositToBatcher(DepositToBatcherArgs),
     #[clap(about = "Get user balance from the batcher", name = "get-user-balance")]
     GetUserBalance(GetUserBalanceArgs),
+    #[clap(
+        about = "Replay the aggregator response of a batch on a local anvil fork and report gas used",
+        name = "replay-batch"
+    )]
+    ReplayBatch(ReplayBatchArgs),
+    #[clap(
+        about = "Report the gas used to verify batch inclusion for different batch sizes",
+        name = "gas-report"
+    )]
+    GasReport(GasReportArgs),
+    #[clap(
+        about = "List the registered operators with their stake and the batches they signed",
+        name = "operator-status"
+    )]
+    OperatorStatus(OperatorStatusArgs),
+    #[clap(
+        about = "Convert a batch inclusion data file to another format",
+        name = "convert"
+    )]
+    Convert(ConvertArgs),
+    #[clap(
+        about = "Write a proof and the files needed to verify it into a single .aligned bundle",
+        name = "pack"
+    )]
+    Pack(PackArgs),
+    #[clap(
+        about = "Write the proof and the files of an .aligned bundle into a directory",
+        name = "unpack"
+    )]
+    Unpack(UnpackArgs),
+    #[clap(
+        about = "List the proofs with an application tag in the latest batches",
+        name = "get-tagged-proofs"
+    )]
+    GetTaggedProofs(GetTaggedProofsArgs),
+    #[clap(
+        about = "Show whether a batch was created and verified on-chain, with its proofs and attestation",
+        name = "get-batch-status"
+    )]
+    GetBatchStatus(GetBatchStatusArgs),
+    #[clap(
+        about = "List the batches verified in a range of blocks with their timestamps and data",
+        name = "list-batches"
+    )]
+    ListBatches(ListBatchesArgs),
+    #[clap(
+        about = "Recover the lost aligned verification data of a proof from the data of its batch",
+        name = "recover"
+    )]
+    Recover(RecoverArgs),
+    #[clap(
+        about = "Get the responses of the proofs of interrupted submissions from the batcher",
+        name = "resume"
+    )]
+    Resume(ResumeArgs),
+    #[clap(
+        about = "Ask the batcher how far a submitted proof has gone, to poll it after disconnecting",
+        name = "status"
+    )]
+    Status(StatusArgs),
+    #[clap(
+        about = "Request Ether to pay for proofs from a faucet",
+        name = "faucet"
+    )]
+    Faucet(FaucetArgs),
+    #[clap(
+        about = "Verify that this binary is the one of a signed release of Aligned",
+        name = "verify-binary"
+    )]
+    VerifyBinary(VerifyBinaryArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -65,38 +250,237 @@ pub struct SubmitArgs {
         default_value = "ws://localhost:8080"
     )]
     connect_addr: String,
-    #[arg(name = "Proving system", long = "proving_system")]
-    proving_system_flag: ProvingSystemArg,
-    #[arg(name = "Proof file path", long = "proof")]
-    proof_file_name: PathBuf,
-    #[arg(name = "Public input file name", long = "public_input")]
-    pub_input_file_name: Option<PathBuf>,
-    #[arg(name = "Verification key file name", long = "vk")]
-    verification_key_file_name: Option<PathBuf>,
-    #[arg(name = "VM prgram code file name", long = "vm_program")]
-    vm_program_code_file_name: Option<PathBuf>,
+    #[arg(
+        name = "Environment whose batcher to submit to, such as holesky, instead of --conn",
+        long = "env",
+        conflicts_with = "Batcher address"
+    )]
+    env: Option<String>,
+    #[arg(
+        name = "URL of the document listing the batchers of each environment",
+        long = "discovery_url",
+        default_value = DEFAULT_DISCOVERY_URL
+    )]
+    discovery_url: String,
+    #[arg(
+        name = "Address of the key that signed the document listing the batchers, required by --env but for devnet",
+        long = "discovery_signer"
+    )]
+    discovery_signer: Option<String>,
+    #[command(flatten)]
+    proof_args: ProofArgs,
     #[arg(
         name = "Number of repetitions",
         long = "repetitions",
         default_value = "1"
     )]
     repetitions: usize,
-    #[arg(
-        name = "Proof generator address",
-        long = "proof_generator_addr",
-        default_value = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
-    )] // defaults to anvil address 1
-    proof_generator_addr: String,
     #[arg(
         name = "Aligned verification data directory Path",
         long = "aligned_verification_data_path",
         default_value = "./aligned_verification_data/"
     )]
-    batch_inclusion_data_directory_path: String,
+    batch_inclusion_data_directory_path: PathBuf,
     #[arg(name = "Path to local keystore", long = "keystore_path")]
     keystore_path: Option<PathBuf>,
-    #[arg(name = "Private key", long = "private_key")]
+    #[arg(
+        name = "Password of the keystore, asked for if not set",
+        long = "keystore_password",
+        env = "ALIGNED_KEYSTORE_PASSWORD",
+        hide_env_values = true
+    )]
+    keystore_password: Option<String>,
+    #[arg(
+        name = "Private key",
+        long = "private_key",
+        env = "ALIGNED_PRIVATE_KEY",
+        hide_env_values = true
+    )]
     private_key: Option<String>,
+    #[arg(
+        name = "ID, ARN or alias of an AWS KMS key to sign the proofs with",
+        long = "aws_kms_key_id",
+        alias = "aws-kms-key-id",
+        env = "ALIGNED_AWS_KMS_KEY_ID"
+    )]
+    aws_kms_key_id: Option<String>,
+    #[arg(
+        name = "AWS region of the KMS key",
+        long = "aws_region",
+        alias = "aws-region",
+        env = "AWS_REGION"
+    )]
+    aws_region: Option<String>,
+    #[arg(
+        name = "Sign all proofs with a single signature",
+        long = "single_signature"
+    )]
+    single_signature: bool,
+    #[arg(
+        name = "File to which a bug report is written if the submission fails",
+        long = "bug_report"
+    )]
+    bug_report_path: Option<PathBuf>,
+    #[arg(
+        name = "Application tag of the proofs, as 32 bytes in hex",
+        long = "tag"
+    )]
+    tag: Option<String>,
+    #[arg(
+        name = "Metadata of the proofs, such as the id of their job, echoed back in their aligned verification data",
+        long = "metadata"
+    )]
+    metadata: Option<String>,
+    #[arg(
+        name = "JSON manifest listing the proofs to submit in one connection, instead of --proof",
+        long = "manifest",
+        conflicts_with = "Proof file path"
+    )]
+    manifest: Option<PathBuf>,
+    #[arg(
+        name = "Bundle of the proof to submit, written by aligned pack, instead of --proof",
+        long = "bundle",
+        conflicts_with_all = [
+            "Proof file path",
+            "JSON manifest listing the proofs to submit in one connection, instead of --proof",
+        ]
+    )]
+    bundle: Option<PathBuf>,
+    #[arg(
+        name = "Fail on any unexpected frame or response of the batcher",
+        long = "strict"
+    )]
+    strict: bool,
+    #[arg(
+        name = "Keep the responses received if the connection is lost, listing the proofs without one",
+        long = "best_effort"
+    )]
+    best_effort: bool,
+    #[arg(
+        name = "Submit each repetition in its own connection, with at most this many at once",
+        long = "concurrency",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = [
+            "File to which a bug report is written if the submission fails",
+            "Keep the responses received if the connection is lost, listing the proofs without one",
+        ]
+    )]
+    concurrency: Option<u64>,
+    #[arg(
+        name = "Print the response and the timing of each proof as JSON",
+        long = "json",
+        conflicts_with = "File to which a bug report is written if the submission fails"
+    )]
+    json: bool,
+    #[arg(
+        name = "PEM file of a root certificate to trust for wss:// batchers, in addition to the system ones",
+        long = "tls_root_ca"
+    )]
+    tls_root_ca_paths: Vec<PathBuf>,
+    #[arg(
+        name = "Name to validate the certificate of a wss:// batcher against, instead of the host of --conn",
+        long = "tls_server_name"
+    )]
+    tls_server_name: Option<String>,
+    #[arg(
+        name = "Access token sent to batchers of private deployments",
+        long = "auth_token",
+        alias = "auth-token",
+        env = "ALIGNED_AUTH_TOKEN",
+        hide_env_values = true
+    )]
+    auth_token: Option<String>,
+    #[arg(
+        name = "Encrypt the messages to the key published by the batcher",
+        long = "encrypt"
+    )]
+    encrypt: bool,
+    #[arg(
+        name = "Public key the batcher must present to encrypt the messages to, as 32 bytes in hex",
+        long = "batcher_encryption_key",
+        requires = "Encrypt the messages to the key published by the batcher"
+    )]
+    batcher_encryption_key: Option<String>,
+    #[arg(
+        name = "Ethereum RPC provider address, used to check the balance of the payer before submitting",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "Batcher Eth Address",
+        long = "batcher_addr",
+        default_value = "0x7969c5eD335650692Bc04293B07F5BF2e7A673C0"
+    )]
+    batcher_eth_address: String,
+    #[arg(
+        name = "Submit even if the balance of the payer is below the estimated fee",
+        long = "allow_insufficient"
+    )]
+    allow_insufficient: bool,
+    #[arg(
+        name = "Verify the proof locally before submitting it, in CLIs built with local verification",
+        long = "verify_before_submit",
+        alias = "verify-before-submit"
+    )]
+    verify_before_submit: bool,
+    #[command(flatten)]
+    usage_limit_args: UsageLimitArgs,
+}
+
+/// Daily limits on the proofs submitted and the Ether spent, checked against the usage recorded
+/// by previous submissions.
+#[derive(Args, Debug)]
+pub struct UsageLimitArgs {
+    #[arg(
+        name = "Most proofs submitted per day, counting the ones of previous submissions",
+        long = "max_proofs_per_day"
+    )]
+    max_proofs_per_day: Option<u64>,
+    #[arg(
+        name = "Most ether spent in fees per day, estimated as the fee of the proofs, counting the ones of previous submissions",
+        long = "max_spend_per_day"
+    )]
+    max_spend_per_day: Option<String>,
+    #[arg(
+        name = "Submit even above the daily limits, still recording the usage",
+        long = "ignore_usage_limits"
+    )]
+    ignore_usage_limits: bool,
+    #[arg(
+        name = "File recording the usage of the day, instead of $XDG_STATE_HOME/aligned/usage.json",
+        long = "usage_store"
+    )]
+    usage_store: Option<PathBuf>,
+}
+
+/// Files and address from which the verification data of a proof is built.
+#[derive(Args, Debug)]
+pub struct ProofArgs {
+    #[arg(
+        name = "Proving system, detected from the proof and verification key files if not set",
+        long = "proving_system"
+    )]
+    proving_system_flag: Option<ProvingSystemArg>,
+    #[arg(name = "Proof file path", long = "proof")]
+    proof_file_name: Option<PathBuf>,
+    #[arg(name = "Public input file name", long = "public_input")]
+    pub_input_file_name: Option<PathBuf>,
+    #[arg(name = "Verification key file name", long = "vk")]
+    verification_key_file_name: Option<PathBuf>,
+    #[arg(name = "VM prgram code file name", long = "vm_program")]
+    vm_program_code_file_name: Option<PathBuf>,
+    #[arg(
+        name = "Proof generator address",
+        long = "proof_generator_addr",
+        default_value = DEFAULT_PROOF_GENERATOR_ADDR
+    )]
+    proof_generator_addr: String,
+    #[arg(
+        name = "Use the public values of the SP1 proof as public input",
+        long = "sp1_public_values"
+    )]
+    sp1_public_values: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -133,7 +517,137 @@ pub struct DepositToBatcherArgs {
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct VerifyProofOnchainArgs {
-    #[arg(name = "Aligned verification data", long = "aligned-verification-data")]
+    #[arg(
+        name = "Aligned verification data",
+        long = "aligned_verification_data",
+        alias = "aligned-verification-data"
+    )]
+    batch_inclusion_data: PathBuf,
+    #[arg(
+        name = "Ethereum RPC provider address",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "Keep checking until the batch of the proof is verified, and fail if the proof is not in it",
+        long = "follow"
+    )]
+    follow: bool,
+    #[arg(
+        name = "Seconds between checks of the batch with --follow",
+        long = "poll_interval",
+        alias = "poll-interval",
+        default_value_t = 12
+    )]
+    poll_interval_secs: u64,
+    #[arg(
+        name = "Seconds to wait for the batch with --follow, forever if not given",
+        long = "timeout"
+    )]
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct VerifyInclusionLocalArgs {
+    #[arg(
+        name = "Aligned verification data",
+        long = "aligned_verification_data",
+        alias = "aligned-verification-data"
+    )]
+    batch_inclusion_data: PathBuf,
+    #[arg(
+        name = "Batch merkle root to verify against, instead of the one in the aligned verification data",
+        long = "merkle_root",
+        alias = "merkle-root"
+    )]
+    batch_merkle_root: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ExportCalldataArgs {
+    #[arg(
+        name = "Aligned verification data",
+        long = "aligned_verification_data",
+        alias = "aligned-verification-data"
+    )]
+    batch_inclusion_data: PathBuf,
+    #[arg(
+        name = "Ethereum RPC provider address, used in the cast command",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ExportFoundryTestArgs {
+    #[arg(
+        name = "Aligned verification data",
+        long = "aligned_verification_data",
+        alias = "aligned-verification-data"
+    )]
+    batch_inclusion_data: PathBuf,
+    #[arg(
+        name = "Ethereum RPC provider address, whose network the test forks",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "File to write the test into, such as test/AlignedInclusion.t.sol",
+        long = "output"
+    )]
+    output_file: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct EnrichVerificationDataArgs {
+    #[arg(
+        name = "Aligned verification data",
+        long = "aligned_verification_data",
+        alias = "aligned-verification-data"
+    )]
     batch_inclusion_data: PathBuf,
     #[arg(
         name = "Ethereum RPC provider address",
@@ -147,15 +661,44 @@ pub struct VerifyProofOnchainArgs {
         default_value = "devnet"
     )]
     chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "Save the batch metadata into the file, keeping its format",
+        long = "rewrite"
+    )]
+    rewrite: bool,
 }
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct GetCommitmentArgs {
-    #[arg(name = "File name", long = "input")]
-    input_file: PathBuf,
+    #[arg(
+        name = "File name, or directory whose files to commit to, one or more",
+        long = "input",
+        required = true,
+        num_args = 1..
+    )]
+    input_files: Vec<PathBuf>,
     #[arg(name = "Output file", long = "output")]
     output_file: Option<PathBuf>,
+    #[arg(
+        name = "Format of the mapping from each file to its commitment, for several files",
+        long = "format",
+        default_value = "csv"
+    )]
+    format: MappingFormat,
+    #[arg(
+        name = "Hash function of the commitments. Only keccak commitments are the ones checked on-chain",
+        long = "hash_function",
+        alias = "hash-function",
+        default_value = "keccak"
+    )]
+    hash_function: HashFunctionArg,
 }
 
 #[derive(Parser, Debug)]
@@ -181,23 +724,482 @@ pub struct GetUserBalanceArgs {
     user_address: String,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
-enum ChainArg {
-    Devnet,
-    Holesky,
-}
-
-impl From<ChainArg> for aligned_sdk::types::Chain {
-    fn from(chain_arg: ChainArg) -> Self {
-        match chain_arg {
-            ChainArg::Devnet => aligned_sdk::types::Chain::Devnet,
-            ChainArg::Holesky => aligned_sdk::types::Chain::Holesky,
-        }
-    }
-}
-
-#[derive(Debug, Clone, ValueEnum)]
-pub enum ProvingSystemArg {
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ReplayBatchArgs {
+    #[arg(
+        name = "Batch merkle root",
+        long = "merkle_root",
+        alias = "merkle-root"
+    )]
+    batch_merkle_root: String,
+    #[arg(
+        name = "RPC url of the network to fork",
+        long = "fork_url",
+        alias = "fork-url"
+    )]
+    fork_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "Block from which to search for the batch",
+        long = "from_block",
+        default_value = "0"
+    )]
+    from_block: u64,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct GasReportArgs {
+    #[arg(
+        name = "RPC url of the network to fork",
+        long = "fork_url",
+        alias = "fork-url"
+    )]
+    fork_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "Block from which to search for a responded batch",
+        long = "from_block",
+        default_value = "0"
+    )]
+    from_block: u64,
+    #[arg(
+        name = "Maximum merkle tree depth to estimate",
+        long = "max_depth",
+        default_value = "16"
+    )]
+    max_merkle_depth: usize,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct OperatorStatusArgs {
+    #[arg(
+        name = "Ethereum RPC provider address",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "Block from which to count verified batches",
+        long = "from_block",
+        default_value = "0"
+    )]
+    from_block: u64,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ConvertArgs {
+    #[arg(name = "Batch inclusion data file, in any format", long = "input")]
+    input_file: PathBuf,
+    #[arg(name = "Output file", long = "output")]
+    output_file: PathBuf,
+    #[arg(
+        name = "Format of the output file, detected from its extension if not set",
+        long = "format"
+    )]
+    format: Option<FileFormatArg>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct PackArgs {
+    #[command(flatten)]
+    proof_args: ProofArgs,
+    #[arg(name = "Bundle file", long = "output")]
+    output_file: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct UnpackArgs {
+    #[arg(name = "Bundle file", long = "bundle")]
+    bundle_file: PathBuf,
+    #[arg(name = "Directory the files are written into", long = "output")]
+    output_directory: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct GetTaggedProofsArgs {
+    #[arg(
+        name = "Application tag of the proofs, as 32 bytes in hex",
+        long = "tag"
+    )]
+    tag: String,
+    #[arg(
+        name = "Only list the proofs of this proof generator address",
+        long = "proof_generator_addr"
+    )]
+    proof_generator_addr: Option<String>,
+    #[arg(
+        name = "Number of batches to search, starting from the latest one",
+        long = "last_batches",
+        default_value = "10"
+    )]
+    last_batches: usize,
+    #[arg(
+        name = "Block from which to search for batches",
+        long = "from_block",
+        default_value = "0"
+    )]
+    from_block: u64,
+    #[arg(
+        name = "Ethereum RPC provider address",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct GetBatchStatusArgs {
+    #[arg(
+        name = "Batch merkle root",
+        long = "batch_merkle_root",
+        alias = "batch-merkle-root"
+    )]
+    batch_merkle_root: String,
+    #[arg(
+        name = "Ethereum RPC provider address",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(name = "Print the status as JSON", long = "json")]
+    json: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ListBatchesArgs {
+    #[arg(
+        name = "Ethereum RPC provider address",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+    #[arg(
+        name = "Block from which to list verified batches",
+        long = "from_block",
+        default_value = "0"
+    )]
+    from_block: u64,
+    #[arg(
+        name = "Last block in which to list verified batches, the latest one if not set",
+        long = "to_block"
+    )]
+    to_block: Option<u64>,
+    #[arg(name = "Print the batches as JSON", long = "json")]
+    json: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct RecoverArgs {
+    #[arg(
+        name = "Batch merkle root",
+        long = "batch_merkle_root",
+        alias = "batch-merkle-root"
+    )]
+    batch_merkle_root: String,
+    #[command(flatten)]
+    proof_args: ProofArgs,
+    #[arg(
+        name = "Aligned verification data directory Path",
+        long = "aligned_verification_data_path",
+        default_value = "./aligned_verification_data/"
+    )]
+    batch_inclusion_data_directory_path: PathBuf,
+    #[arg(
+        name = "Ethereum RPC provider address",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Address of the AlignedServiceManager contract, overriding the one of the chain",
+        long = "contract_address",
+        alias = "contract-address"
+    )]
+    contract_address: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct ResumeArgs {
+    #[arg(
+        name = "Aligned verification data directory Path",
+        long = "aligned_verification_data_path",
+        default_value = "./aligned_verification_data/"
+    )]
+    batch_inclusion_data_directory_path: PathBuf,
+    #[arg(
+        name = "PEM file of a root certificate to trust for wss:// batchers, in addition to the system ones",
+        long = "tls_root_ca"
+    )]
+    tls_root_ca_paths: Vec<PathBuf>,
+    #[arg(
+        name = "Name to validate the certificate of a wss:// batcher against, instead of its host",
+        long = "tls_server_name"
+    )]
+    tls_server_name: Option<String>,
+    #[arg(
+        name = "Access token sent to batchers of private deployments",
+        long = "auth_token",
+        alias = "auth-token",
+        env = "ALIGNED_AUTH_TOKEN",
+        hide_env_values = true
+    )]
+    auth_token: Option<String>,
+    #[arg(
+        name = "Forget the proofs the batcher doesn't know instead of keeping them pending",
+        long = "discard_unknown"
+    )]
+    discard_unknown: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct StatusArgs {
+    #[arg(
+        name = "Batcher address",
+        long = "conn",
+        default_value = "ws://localhost:8080"
+    )]
+    connect_addr: String,
+    #[command(flatten)]
+    proof_args: ProofArgs,
+    #[arg(
+        name = "PEM file of a root certificate to trust for wss:// batchers, in addition to the system ones",
+        long = "tls_root_ca"
+    )]
+    tls_root_ca_paths: Vec<PathBuf>,
+    #[arg(
+        name = "Name to validate the certificate of a wss:// batcher against, instead of the host of --conn",
+        long = "tls_server_name"
+    )]
+    tls_server_name: Option<String>,
+    #[arg(
+        name = "Access token sent to batchers of private deployments",
+        long = "auth_token",
+        alias = "auth-token",
+        env = "ALIGNED_AUTH_TOKEN",
+        hide_env_values = true
+    )]
+    auth_token: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct FaucetArgs {
+    #[clap(subcommand)]
+    command: FaucetCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FaucetCommands {
+    #[clap(about = "Request Ether for an address", name = "request")]
+    Request(FaucetRequestArgs),
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct FaucetRequestArgs {
+    #[arg(name = "The address to fund", long = "address", required = true)]
+    address: String,
+    #[arg(
+        name = "URL of the faucet endpoint, required outside of devnet",
+        long = "faucet_url"
+    )]
+    faucet_url: Option<String>,
+    #[arg(
+        name = "Ethereum RPC provider address, used to fund the address on devnet",
+        long = "rpc",
+        default_value = "http://localhost:8545"
+    )]
+    eth_rpc_url: String,
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "chain",
+        default_value = "devnet"
+    )]
+    chain: ChainArg,
+    #[arg(
+        name = "Amount sent on devnet",
+        long = "amount",
+        default_value = "1ether"
+    )]
+    amount: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct VerifyBinaryArgs {
+    #[arg(
+        name = "URL of the manifest of the release, instead of the one of this version on GitHub",
+        long = "manifest_url"
+    )]
+    manifest_url: Option<String>,
+    #[arg(
+        name = "Address of the key that signed the manifest of the release",
+        long = "release_signer"
+    )]
+    release_signer: String,
+    #[arg(
+        name = "Name of the binary in the release, instead of the one of this architecture",
+        long = "asset"
+    )]
+    asset: Option<String>,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum FileFormatArg {
+    Json,
+    Binary,
+}
+
+impl From<FileFormatArg> for FileFormat {
+    fn from(format: FileFormatArg) -> Self {
+        match format {
+            FileFormatArg::Json => FileFormat::Json,
+            FileFormatArg::Binary => FileFormat::Binary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum HashFunctionArg {
+    Keccak,
+    Sha256,
+    Blake3,
+}
+
+impl From<HashFunctionArg> for HashFunction {
+    fn from(hash_function: HashFunctionArg) -> Self {
+        match hash_function {
+            HashFunctionArg::Keccak => HashFunction::Keccak256,
+            HashFunctionArg::Sha256 => HashFunction::Sha256,
+            HashFunctionArg::Blake3 => HashFunction::Blake3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum ChainArg {
+    Devnet,
+    Holesky,
+}
+
+impl From<ChainArg> for aligned_sdk::types::Chain {
+    fn from(chain_arg: ChainArg) -> Self {
+        match chain_arg {
+            ChainArg::Devnet => aligned_sdk::types::Chain::Devnet,
+            ChainArg::Holesky => aligned_sdk::types::Chain::Holesky,
+        }
+    }
+}
+
+/// Returns the chain of the `--chain` argument, or a custom one with its endpoints and the
+/// AlignedServiceManager of `--contract_address` if it's set, so that forks and new deployments
+/// can be used without recompiling.
+fn chain_with_contract_address(
+    chain_arg: ChainArg,
+    contract_address: Option<String>,
+) -> Result<aligned_sdk::types::Chain, SubmitError> {
+    let Some(contract_address) = contract_address else {
+        return Ok(chain_arg.into());
+    };
+    Address::from_str(&contract_address)
+        .map_err(|e| SubmitError::InvalidAddress(contract_address.clone(), e.to_string()))?;
+    let chain: aligned_sdk::types::Chain = chain_arg.into();
+    Ok(aligned_sdk::types::Chain::Custom(NetworkEndpoints {
+        aligned_service_manager: contract_address,
+        ..chain.endpoints()
+    }))
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum ProvingSystemArg {
     #[clap(name = "GnarkPlonkBls12_381")]
     GnarkPlonkBls12_381,
     #[clap(name = "GnarkPlonkBn254")]
@@ -216,6 +1218,10 @@ pub enum ProvingSystemArg {
 
 const ANVIL_PRIVATE_KEY: &str = "2a871d0798f97d79848a013d4936a73bf4cc922c825d33c1cf7073dff6d409c6"; // Anvil address 9
 
+const UNCONFIRMED_PROOFS_FILE: &str = "unconfirmed_proofs.json";
+/// Anvil's first account, with which proofs are submitted by default.
+const DEFAULT_PROOF_GENERATOR_ADDR: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
 impl From<ProvingSystemArg> for ProvingSystemId {
     fn from(proving_system: ProvingSystemArg) -> Self {
         match proving_system {
@@ -227,283 +1233,1893 @@ impl From<ProvingSystemArg> for ProvingSystemId {
             ProvingSystemArg::Halo2IPA => ProvingSystemId::Halo2IPA,
             ProvingSystemArg::Risc0 => ProvingSystemId::Risc0,
         }
-    }
-}
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), AlignedError> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let config = config::load(std::env::args_os())?;
+    let command = config::with_defaults(AlignedArgs::command(), &config).arg(config::config_arg());
+    let args = AlignedArgs::from_arg_matches(&command.get_matches()).unwrap_or_else(|e| e.exit());
+    let output = args.output;
+    if output == OutputFormat::Json && !prints_json(&args.command) {
+        warn!("This command has no JSON output yet, its results are only logged");
+    }
+
+    match args.command {
+        Submit(mut submit_args) => {
+            submit_args.json |= output == OutputFormat::Json;
+            let batch_inclusion_data_directory_path =
+                submit_args.batch_inclusion_data_directory_path.clone();
+
+            std::fs::create_dir_all(&batch_inclusion_data_directory_path).map_err(|e| {
+                SubmitError::IoError(batch_inclusion_data_directory_path.clone(), e)
+            })?;
+
+            let connect_addr = match &submit_args.env {
+                Some(env) => {
+                    let discovery_signer = match submit_args.discovery_signer.as_deref() {
+                        Some(discovery_signer) => {
+                            Address::from_str(discovery_signer).map_err(|e| {
+                                SubmitError::InvalidAddress(
+                                    discovery_signer.to_string(),
+                                    e.to_string(),
+                                )
+                            })?
+                        }
+                        // The batcher of devnet is not looked up, so no document is checked
+                        None if env == DEVNET_ENVIRONMENT => Address::zero(),
+                        None => {
+                            return Err(SubmitError::MissingParameter(
+                                "--discovery_signer".to_string(),
+                            )
+                            .into())
+                        }
+                    };
+                    let endpoints = resolve_batcher_endpoints(
+                        env,
+                        &submit_args.discovery_url,
+                        discovery_signer,
+                    )
+                    .await?;
+                    info!("Batchers of environment {}: {}", env, endpoints.join(", "));
+                    endpoints[0].clone()
+                }
+                None => submit_args.connect_addr.clone(),
+            };
+            let bug_report_path = submit_args.bug_report_path.clone();
+            let strict = submit_args.strict;
+            let best_effort = submit_args.best_effort;
+
+            if strict && best_effort {
+                warn!("Can't submit in strict and best effort modes at once. Please use only one");
+                return Ok(());
+            }
+            if bug_report_path.is_some() && best_effort {
+                warn!("Can't write a bug report in best effort mode yet");
+                return Ok(());
+            }
+
+            let mut tls_config = TlsConfig {
+                server_name: submit_args.tls_server_name.clone(),
+                auth_token: submit_args.auth_token.take(),
+                ..Default::default()
+            };
+            for tls_root_ca_path in &submit_args.tls_root_ca_paths {
+                tls_config = tls_config.with_root_certificate(read_file(tls_root_ca_path.clone())?);
+            }
+            let has_tls_options =
+                tls_config.server_name.is_some() || !tls_config.root_certificates.is_empty();
+            if has_tls_options && best_effort {
+                warn!("Can't use TLS options in best effort mode yet");
+                return Ok(());
+            }
+            if tls_config.auth_token.is_some() && best_effort {
+                warn!("Can't send an access token in best effort mode yet");
+                return Ok(());
+            }
+            if submit_args.encrypt && best_effort {
+                warn!("Can't encrypt the messages in best effort mode yet");
+                return Ok(());
+            }
+
+            // Secrets are moved out of the arguments so they are zeroized once the wallet is built
+            let keystore_path = submit_args.keystore_path.clone();
+            let keystore_password = submit_args.keystore_password.take().map(Zeroizing::new);
+            let private_key = submit_args.private_key.take().map(Zeroizing::new);
+
+            if keystore_path.is_some() && private_key.is_some() {
+                warn!("Can't have a keystore path and a private key as input. Please use only one");
+                return Ok(());
+            }
+            if submit_args.aws_kms_key_id.is_some()
+                && (keystore_path.is_some() || private_key.is_some())
+            {
+                warn!("Can't have an AWS KMS key and a keystore path or private key as input. Please use only one");
+                return Ok(());
+            }
+
+            if let Some(aws_kms_key_id) = submit_args.aws_kms_key_id.clone() {
+                let aws_region = submit_args
+                    .aws_region
+                    .clone()
+                    .ok_or(SubmitError::MissingParameter("aws_region".to_string()))?;
+                let wallet = wallet::kms_signer(&aws_kms_key_id, &aws_region).await?;
+                submit_proofs(submit_args, &connect_addr, &tls_config, wallet)
+                    .await
+                    .inspect_err(explain_rejection)?;
+                return Ok(());
+            }
+
+            let wallet = if let Some(keystore_path) = keystore_path {
+                wallet::decrypt_keystore(&keystore_path, keystore_password)?
+            } else if let Some(private_key) = private_key {
+                wallet::wallet_from_private_key(private_key)?
+            } else {
+                warn!("Missing keystore used for payment. This proof will not be included if sent to Eth Mainnet");
+                LocalWallet::from_str(ANVIL_PRIVATE_KEY).expect("Failed to create wallet")
+            };
+
+            submit_proofs(
+                submit_args,
+                &connect_addr,
+                &tls_config,
+                PooledWallet::new(wallet),
+            )
+            .await
+            .inspect_err(explain_rejection)?;
+        }
+
+        VerifyProofOnchain(verify_inclusion_args) => {
+            let chain = chain_with_contract_address(
+                verify_inclusion_args.chain,
+                verify_inclusion_args.contract_address,
+            )?;
+            // Files can be JSON or binary, depending on the client that saved them
+            let aligned_verification_data =
+                inclusion_file::read(&verify_inclusion_args.batch_inclusion_data)?;
+
+            if verify_inclusion_args.follow {
+                let wait_config = VerificationWaitConfig {
+                    poll_interval: Duration::from_secs(verify_inclusion_args.poll_interval_secs),
+                    timeout: verify_inclusion_args
+                        .timeout_secs
+                        .map_or(Duration::MAX, Duration::from_secs),
+                };
+                info!(
+                    "Waiting for batch 0x{} to be verified...",
+                    hex::encode(aligned_verification_data.batch_merkle_root)
+                );
+                let verified_proof = wait_proof_verification(
+                    aligned_verification_data,
+                    chain.clone(),
+                    &verify_inclusion_args.eth_rpc_url,
+                    &wait_config,
+                )
+                .await?;
+
+                info!(
+                    "Your proof was verified in Aligned and included in the batch! Its batch was verified in transaction {:#x}",
+                    verified_proof.verification_tx_hash
+                );
+                output.print_json(|| {
+                    serde_json::json!({
+                        "verified": true,
+                        "aligned_service_manager": get_aligned_service_manager_address(&chain),
+                        "verification_tx_hash": format!("{:#x}", verified_proof.verification_tx_hash),
+                        "proof": aligned_verification_data_json(&verified_proof.aligned_verification_data),
+                    })
+                });
+                return Ok(());
+            }
+
+            info!("Verifying response data matches sent proof data...");
+            let response = verify_proof_onchain(
+                aligned_verification_data.clone(),
+                chain.clone(),
+                &verify_inclusion_args.eth_rpc_url,
+            )
+            .await?;
+
+            if response {
+                info!("Your proof was verified in Aligned and included in the batch!");
+            } else {
+                info!("Your proof was not included in the batch.");
+            }
+            output.print_json(|| {
+                serde_json::json!({
+                    "verified": response,
+                    "aligned_service_manager": get_aligned_service_manager_address(&chain),
+                    "proof": aligned_verification_data_json(&aligned_verification_data),
+                })
+            });
+        }
+        VerifyInclusionLocal(verify_inclusion_args) => {
+            let aligned_verification_data =
+                inclusion_file::read(&verify_inclusion_args.batch_inclusion_data)?;
+            let batch_merkle_root = match &verify_inclusion_args.batch_merkle_root {
+                Some(batch_merkle_root) => parse_batch_merkle_root(batch_merkle_root)?,
+                None => {
+                    warn!("No --merkle_root given, verifying against the merkle root in the aligned verification data. This only checks the file is consistent, not that the batch exists");
+                    aligned_verification_data.batch_merkle_root
+                }
+            };
+
+            let included = verify_merkle_inclusion(&aligned_verification_data, batch_merkle_root);
+            if included {
+                info!(
+                    "Your proof is included in the batch with merkle root 0x{}",
+                    hex::encode(batch_merkle_root)
+                );
+            } else {
+                info!(
+                    "Your proof is not included in the batch with merkle root 0x{}",
+                    hex::encode(batch_merkle_root)
+                );
+            }
+            output.print_json(|| {
+                serde_json::json!({
+                    "included": included,
+                    "batch_merkle_root": format!("0x{}", hex::encode(batch_merkle_root)),
+                    "proof": aligned_verification_data_json(&aligned_verification_data),
+                })
+            });
+        }
+        ExportCalldata(export_calldata_args) => {
+            let chain = chain_with_contract_address(
+                export_calldata_args.chain,
+                export_calldata_args.contract_address,
+            )?;
+            // Files can be JSON or binary, depending on the client that saved them
+            let aligned_verification_data =
+                inclusion_file::read(&export_calldata_args.batch_inclusion_data)?;
+
+            let calldata = get_verify_batch_inclusion_calldata(&aligned_verification_data)?;
+            let service_manager = get_aligned_service_manager_address(&chain);
+            let arguments = calldata::call_arguments(&aligned_verification_data);
+            let cast_command = calldata::cast_command(
+                service_manager,
+                &arguments,
+                &export_calldata_args.eth_rpc_url,
+            );
+
+            output.print_json(|| {
+                serde_json::json!({
+                    "service_manager": service_manager,
+                    "calldata": format!("0x{}", hex::encode(&calldata)),
+                    // The calldata without the function selector
+                    "arguments": format!("0x{}", hex::encode(&calldata[4..])),
+                    "cast_command": cast_command,
+                })
+            });
+            if output != OutputFormat::Json {
+                info!("Calldata of verifyBatchInclusion on {}:", service_manager);
+                println!("0x{}", hex::encode(&calldata));
+                info!("Call it with:");
+                println!("{}", cast_command);
+            }
+        }
+        ExportFoundryTest(export_foundry_test_args) => {
+            let chain = chain_with_contract_address(
+                export_foundry_test_args.chain,
+                export_foundry_test_args.contract_address,
+            )?;
+            let aligned_verification_data =
+                inclusion_file::read(&export_foundry_test_args.batch_inclusion_data)?;
+            // Only V1 batches are verified by the contract
+            get_verify_batch_inclusion_calldata(&aligned_verification_data)?;
+
+            let service_manager = get_aligned_service_manager_address(&chain);
+            let service_manager_address = Address::from_str(service_manager).map_err(|e| {
+                SubmitError::InvalidAddress(service_manager.to_string(), e.to_string())
+            })?;
+            let source = foundry_test::foundry_test(
+                service_manager_address,
+                &aligned_verification_data,
+                &export_foundry_test_args.eth_rpc_url,
+            );
+
+            match &export_foundry_test_args.output_file {
+                Some(output_file) => {
+                    std::fs::write(output_file, &source)
+                        .map_err(|e| SubmitError::IoError(output_file.clone(), e))?;
+                    info!(
+                        "Foundry test written into {}, run it with: forge test --match-contract {}",
+                        output_file.display(),
+                        foundry_test::CONTRACT_NAME
+                    );
+                }
+                None if output != OutputFormat::Json => print!("{}", source),
+                None => {}
+            }
+            output.print_json(|| {
+                serde_json::json!({
+                    "service_manager": service_manager,
+                    "contract_name": foundry_test::CONTRACT_NAME,
+                    "output_file": export_foundry_test_args.output_file,
+                    "source": source,
+                })
+            });
+        }
+        EnrichVerificationData(enrich_args) => {
+            let chain =
+                chain_with_contract_address(enrich_args.chain, enrich_args.contract_address)?;
+            let aligned_verification_data = read_enriched(
+                &enrich_args.batch_inclusion_data,
+                chain,
+                &enrich_args.eth_rpc_url,
+                enrich_args.rewrite,
+            )
+            .await?;
+
+            if let Some(batch_metadata) = &aligned_verification_data.batch_metadata {
+                info!(
+                    "Batch created in block {} by transaction {:#x}",
+                    batch_metadata.created.block_number, batch_metadata.created.tx_hash
+                );
+                match &batch_metadata.verified {
+                    Some(verified) => info!(
+                        "Batch verified in block {} by transaction {:#x}",
+                        verified.block_number, verified.tx_hash
+                    ),
+                    None => info!("Batch not verified yet"),
+                }
+            }
+            if enrich_args.rewrite {
+                info!(
+                    "Batch metadata is saved in {}",
+                    enrich_args.batch_inclusion_data.display()
+                );
+            }
+            output.print_json(|| aligned_verification_data_json(&aligned_verification_data));
+        }
+        GetCommitment(args) => {
+            // A single file keeps writing the bare commitment to the output file
+            let input_file = match args.input_files.as_slice() {
+                [input_file] if !input_file.is_dir() => input_file.clone(),
+                _ => {
+                    get_commitments(args, output)?;
+                    return Ok(());
+                }
+            };
+            let content = read_file(input_file)?;
+
+            let hash = get_commitment_with_hash(&content, args.hash_function.into());
+
+            info!("Commitment: {}", hex::encode(hash));
+            output.print_json(
+                || serde_json::json!({ "commitment": format!("0x{}", hex::encode(hash)) }),
+            );
+            if let Some(output_file) = args.output_file {
+                let mut file = File::create(output_file.clone())
+                    .map_err(|e| SubmitError::IoError(output_file.clone(), e))?;
+
+                file.write_all(hex::encode(hash).as_bytes())
+                    .map_err(|e| SubmitError::IoError(output_file.clone(), e))?;
+            }
+        }
+        DepositToBatcher(deposit_to_batcher_args) => {
+            if !deposit_to_batcher_args.amount.ends_with("ether") {
+                error!("Amount should be in the format XX.XXether");
+                return Ok(());
+            }
 
-#[tokio::main]
-async fn main() -> Result<(), AlignedError> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let args: AlignedArgs = AlignedArgs::parse();
+            let chain: aligned_sdk::types::Chain = deposit_to_batcher_args.chain.into();
 
-    match args.command {
-        Submit(submit_args) => {
-            let batch_inclusion_data_directory_path =
-                PathBuf::from(&submit_args.batch_inclusion_data_directory_path);
+            let amount = deposit_to_batcher_args.amount.replace("ether", "");
 
-            std::fs::create_dir_all(&batch_inclusion_data_directory_path).map_err(|e| {
-                SubmitError::IoError(batch_inclusion_data_directory_path.clone(), e)
+            let eth_rpc_url = deposit_to_batcher_args.eth_rpc_url;
+
+            let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url).map_err(|e| {
+                SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e))
             })?;
 
-            let repetitions = submit_args.repetitions;
-            let connect_addr = submit_args.connect_addr.clone();
+            let keystore_path = &deposit_to_batcher_args.keystore_path;
+
+            let mut wallet = if let Some(keystore_path) = keystore_path {
+                wallet::decrypt_keystore(keystore_path, None)?
+            } else {
+                warn!("Missing keystore used for payment.");
+                return Ok(());
+            };
+
+            match chain {
+                Chain::Devnet => wallet = wallet.with_chain_id(31337u64),
+                Chain::Holesky => wallet = wallet.with_chain_id(17000u64),
+                Chain::Custom(_) => {
+                    let chain_id = eth_rpc_provider.get_chainid().await.map_err(|e| {
+                        SubmitError::EthError(format!("Error while getting chain id: {}", e))
+                    })?;
+                    wallet = wallet.with_chain_id(chain_id.as_u64())
+                }
+            }
 
-            let keystore_path = &submit_args.keystore_path;
-            let private_key = &submit_args.private_key;
+            let client = SignerMiddleware::new(eth_rpc_provider.clone(), wallet.clone());
 
-            if keystore_path.is_some() && private_key.is_some() {
-                warn!("Can't have a keystore path and a private key as input. Please use only one");
+            let balance = client
+                .get_balance(wallet.address(), None)
+                .await
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while getting balance: {}", e))
+                })?;
+
+            let amount_ether = parse_ether(&amount)
+                .map_err(|e| SubmitError::EthError(format!("Error while parsing amount: {}", e)))?;
+
+            if amount_ether <= U256::from(0) {
+                error!("Amount should be greater than 0");
                 return Ok(());
             }
 
-            let wallet = if let Some(keystore_path) = keystore_path {
-                let password = rpassword::prompt_password("Please enter your keystore password:")
-                    .map_err(|e| SubmitError::GenericError(e.to_string()))?;
-                Wallet::decrypt_keystore(keystore_path, password)
-                    .map_err(|e| SubmitError::GenericError(e.to_string()))?
-            } else if let Some(private_key) = private_key {
-                private_key
-                    .parse::<LocalWallet>()
-                    .map_err(|e| SubmitError::GenericError(e.to_string()))?
+            if balance < amount_ether {
+                error!("Insufficient funds to pay to the batcher. Please deposit some Ether in your wallet.");
+                return Ok(());
+            }
+
+            let batcher_addr = Address::from_str(&deposit_to_batcher_args.batcher_eth_address)
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while parsing batcher address: {}", e))
+                })?;
+
+            let tx = TransactionRequest::new()
+                .to(batcher_addr)
+                .value(amount_ether)
+                .from(wallet.address());
+
+            info!("Sending {} ether to the batcher", amount);
+
+            let tx = client
+                .send_transaction(tx, None)
+                .await
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while sending transaction: {}", e))
+                })?
+                .await
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while sending transaction: {}", e))
+                })?;
+
+            if let Some(tx) = tx {
+                info!(
+                    "Payment sent to the batcher successfully. Tx: 0x{:x}",
+                    tx.transaction_hash
+                );
+                output.print_json(|| {
+                    serde_json::json!({
+                        "tx_hash": format!("0x{:x}", tx.transaction_hash),
+                        "from": wallet.address(),
+                        "to": batcher_addr,
+                        "amount_wei": amount_ether.to_string(),
+                    })
+                });
             } else {
-                warn!("Missing keystore used for payment. This proof will not be included if sent to Eth Mainnet");
-                LocalWallet::from_str(ANVIL_PRIVATE_KEY).expect("Failed to create wallet")
-            };
+                error!("Transaction failed");
+            }
+        }
+        GetUserBalance(get_user_balance_args) => {
+            let eth_rpc_url = get_user_balance_args.eth_rpc_url;
 
-            let verification_data = verification_data_from_args(submit_args)?;
+            let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url).map_err(|e| {
+                SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e))
+            })?;
 
-            let verification_data_arr = vec![verification_data; repetitions];
+            let user_address =
+                Address::from_str(&get_user_balance_args.user_address).map_err(|e| {
+                    SubmitError::EthError(format!("Error while parsing user address: {}", e))
+                })?;
 
-            info!("Submitting proofs to the Aligned batcher...");
+            let batcher_addr = Address::from_str(&get_user_balance_args.batcher_eth_address)
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while parsing batcher address: {}", e))
+                })?;
 
-            let aligned_verification_data_vec =
-                submit_multiple(&connect_addr, &verification_data_arr, wallet).await?;
+            let balance = AlignedRpcClient::new(eth_rpc_provider)
+                .user_balance(batcher_addr, user_address)
+                .await
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while getting user balance: {:?}", e))
+                })?;
 
-            if let Some(aligned_verification_data_vec) = aligned_verification_data_vec {
-                let mut unique_batch_merkle_roots = HashSet::new();
+            info!(
+                "User {} has {} ether in the batcher",
+                user_address,
+                format_ether(balance)
+            );
+            output.print_json(|| {
+                serde_json::json!({
+                    "user": user_address,
+                    "batcher_payment_service": batcher_addr,
+                    "balance_wei": balance.to_string(),
+                })
+            });
+        }
+        ReplayBatch(replay_batch_args) => {
+            let chain = chain_with_contract_address(
+                replay_batch_args.chain,
+                replay_batch_args.contract_address,
+            )?;
+            let batch_merkle_root = parse_batch_merkle_root(&replay_batch_args.batch_merkle_root)?;
+
+            let report = replay::replay_batch(
+                &replay_batch_args.fork_url,
+                get_aligned_service_manager_address(&chain),
+                batch_merkle_root,
+                replay_batch_args.from_block,
+            )
+            .await?;
 
-                for aligned_verification_data in aligned_verification_data_vec {
-                    save_response(
-                        batch_inclusion_data_directory_path.clone(),
-                        &aligned_verification_data,
-                    )?;
-                    unique_batch_merkle_roots.insert(aligned_verification_data.batch_merkle_root);
-                }
+            info!("Batch created at block: {}", report.task_created_block);
+            info!("Batch responded at block: {}", report.response_block);
+            info!(
+                "Original respondToTask gas used: {}",
+                format_gas(report.original_gas_used)
+            );
+            info!(
+                "Replayed respondToTask gas used: {}",
+                format_gas(report.replayed_gas_used)
+            );
+        }
+        GasReport(gas_report_args) => {
+            let chain = chain_with_contract_address(
+                gas_report_args.chain,
+                gas_report_args.contract_address,
+            )?;
+
+            let report = gas_report::gas_report(
+                &gas_report_args.fork_url,
+                get_aligned_service_manager_address(&chain),
+                gas_report_args.from_block,
+                gas_report_args.max_merkle_depth,
+            )
+            .await?;
+
+            info!(
+                "Gas estimated with batch 0x{}, including 21000 of intrinsic gas",
+                hex::encode(report.batch_merkle_root)
+            );
+            info!("Batch not found: {}", report.unknown_batch_gas);
+            info!(
+                "{:>12} | {:>14} | {:>10}",
+                "Merkle depth", "Max batch size", "Gas"
+            );
+            for entry in report.entries {
+                info!(
+                    "{:>12} | {:>14} | {:>10}",
+                    entry.merkle_depth, entry.max_batch_size, entry.gas
+                );
+            }
+        }
+        OperatorStatus(operator_status_args) => {
+            let chain = chain_with_contract_address(
+                operator_status_args.chain,
+                operator_status_args.contract_address,
+            )?;
+
+            let report = operator_status::operator_status(
+                &operator_status_args.eth_rpc_url,
+                chain.clone(),
+                get_aligned_service_manager_address(&chain),
+                operator_status_args.from_block,
+            )
+            .await?;
+
+            info!(
+                "{} operators registered with a total stake of {} ether",
+                report.operators.len(),
+                format_ether(report.total_stake)
+            );
+            info!(
+                "{} batches verified since block {}",
+                report.verified_batches, operator_status_args.from_block
+            );
+            info!(
+                "{:>42} | {:>24} | {:>7} | {:>14}",
+                "Operator", "Stake (ether)", "Share", "Signed batches"
+            );
+            for operator in report.operators {
+                info!(
+                    "{:>42} | {:>24} | {:>6.2}% | {:>14}",
+                    format!("{:?}", operator.address),
+                    format_ether(operator.stake),
+                    stake_share_percentage(operator.stake, report.total_stake),
+                    format!("{}/{}", operator.signed_batches, operator.eligible_batches)
+                );
+            }
+        }
+        Convert(convert_args) => {
+            let format = match convert_args.format {
+                Some(format) => format.into(),
+                None => format_from_extension(&convert_args.output_file)?,
+            };
+
+            // Files of previous releases are written in the current version of the format
+            let aligned_verification_data = inclusion_file::read(&convert_args.input_file)?;
+            inclusion_file::write(
+                &convert_args.output_file,
+                &aligned_verification_data,
+                format,
+            )?;
+            info!(
+                "Batch inclusion data written into {} as {:?}",
+                convert_args.output_file.display(),
+                format
+            );
+        }
+        Pack(pack_args) => {
+            let verification_data = verification_data_from_args(pack_args.proof_args, None, None)?;
+            if pack_args.output_file.extension() != Some(BUNDLE_EXTENSION.as_ref()) {
+                warn!(
+                    "The bundle is written without the .{} extension",
+                    BUNDLE_EXTENSION
+                );
+            }
+            write_bundle(&pack_args.output_file, &verification_data)?;
+            info!(
+                "{:?} proof bundled into {}",
+                verification_data.proving_system,
+                pack_args.output_file.display()
+            );
+            output.print_json(|| {
+                serde_json::json!({
+                    "bundle": pack_args.output_file,
+                    "proving_system": format!("{:?}", verification_data.proving_system),
+                    "proof_generator_addr": format!("{:#x}", verification_data.proof_generator_addr),
+                })
+            });
+        }
+        Unpack(unpack_args) => {
+            let verification_data = read_bundle(&unpack_args.bundle_file)?;
+            let files = write_bundle_files(&unpack_args.output_directory, &verification_data)?;
+
+            let proof_args = files
+                .iter()
+                .map(|(flag, path)| format!("--{} {}", flag, path.display()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            info!(
+                "{:?} proof unpacked into {}, submit it with: aligned submit --proving_system {:?} {} --proof_generator_addr {:#x}",
+                verification_data.proving_system,
+                unpack_args.output_directory.display(),
+                verification_data.proving_system,
+                proof_args,
+                verification_data.proof_generator_addr
+            );
+            output.print_json(|| {
+                serde_json::json!({
+                    "proving_system": format!("{:?}", verification_data.proving_system),
+                    "proof_generator_addr": format!("{:#x}", verification_data.proof_generator_addr),
+                    "files": files
+                        .iter()
+                        .map(|(flag, path)| (flag.to_string(), serde_json::json!(path)))
+                        .collect::<serde_json::Map<_, _>>(),
+                })
+            });
+        }
+        GetTaggedProofs(get_tagged_proofs_args) => {
+            let tag = parse_tag(&get_tagged_proofs_args.tag)?;
+            let proof_generator_addr = get_tagged_proofs_args
+                .proof_generator_addr
+                .map(|addr| {
+                    Address::from_str(&addr)
+                        .map_err(|e| SubmitError::InvalidAddress(addr.clone(), e.to_string()))
+                })
+                .transpose()?;
+
+            let tagged_proofs = get_tagged_proofs(
+                tag,
+                proof_generator_addr,
+                get_tagged_proofs_args.last_batches,
+                get_tagged_proofs_args.from_block,
+                chain_with_contract_address(
+                    get_tagged_proofs_args.chain,
+                    get_tagged_proofs_args.contract_address,
+                )?,
+                &get_tagged_proofs_args.eth_rpc_url,
+            )
+            .await?;
 
-                if unique_batch_merkle_roots.len() > 1 {
-                    info!("Proofs submitted to aligned. See the batches in the explorer:");
-                } else {
-                    info!("Proofs submitted to aligned. See the batch in the explorer:");
+            info!(
+                "{} proofs with tag 0x{} found in the last {} batches",
+                tagged_proofs.len(),
+                hex::encode(tag),
+                get_tagged_proofs_args.last_batches
+            );
+            output.print_json(|| tagged_proofs_json(tag, &tagged_proofs));
+            for tagged_proof in tagged_proofs {
+                info!(
+                    "Proof {} of batch 0x{}, created at block {}, submitted by 0x{}",
+                    tagged_proof.index_in_batch,
+                    hex::encode(tagged_proof.batch_merkle_root),
+                    tagged_proof.task_created_block,
+                    hex::encode(
+                        tagged_proof
+                            .verification_data_commitment
+                            .proof_generator_addr
+                    )
+                );
+            }
+        }
+        GetBatchStatus(get_batch_status_args) => {
+            let chain = chain_with_contract_address(
+                get_batch_status_args.chain,
+                get_batch_status_args.contract_address,
+            )?;
+            let batch_merkle_root =
+                parse_batch_merkle_root(&get_batch_status_args.batch_merkle_root)?;
+
+            let batch_status =
+                get_batch_status(batch_merkle_root, chain, &get_batch_status_args.eth_rpc_url)
+                    .await?;
+
+            if get_batch_status_args.json || output == OutputFormat::Json {
+                println!("{}", batch_status_json(&batch_status));
+            } else {
+                log_batch_status(&batch_status);
+            }
+        }
+        ListBatches(list_batches_args) => {
+            if let Some(to_block) = list_batches_args.to_block {
+                if to_block < list_batches_args.from_block {
+                    error!("--to_block must not be lower than --from_block");
+                    return Ok(());
                 }
+            }
+            let chain = chain_with_contract_address(
+                list_batches_args.chain,
+                list_batches_args.contract_address,
+            )?;
+
+            let verified_batches = list_batches::list_batches(
+                &list_batches_args.eth_rpc_url,
+                get_aligned_service_manager_address(&chain),
+                list_batches_args.from_block,
+                list_batches_args.to_block,
+            )
+            .await?;
 
-                for batch_merkle_root in unique_batch_merkle_roots {
-                    info!(
-                        "https://explorer.alignedlayer.com/batches/0x{}",
-                        hex::encode(batch_merkle_root)
-                    );
+            if list_batches_args.json || output == OutputFormat::Json {
+                println!("{}", verified_batches_json(&verified_batches));
+                return Ok(());
+            }
+
+            info!(
+                "{} batches verified since block {}",
+                verified_batches.len(),
+                list_batches_args.from_block
+            );
+            if verified_batches.is_empty() {
+                return Ok(());
+            }
+            info!(
+                "{:>66} | {:>14} | {:>10} | {}",
+                "Batch merkle root", "Verified block", "Timestamp", "Data pointer"
+            );
+            for verified_batch in verified_batches {
+                info!(
+                    "{:>66} | {:>14} | {:>10} | {}",
+                    format!("0x{}", hex::encode(verified_batch.batch_merkle_root)),
+                    verified_batch.verified_block,
+                    verified_batch.verified_timestamp,
+                    verified_batch
+                        .batch_data_pointer
+                        .as_deref()
+                        .unwrap_or("unknown, created before --from_block")
+                );
+            }
+        }
+        Recover(recover_args) => {
+            let chain =
+                chain_with_contract_address(recover_args.chain, recover_args.contract_address)?;
+            let batch_merkle_root = parse_batch_merkle_root(&recover_args.batch_merkle_root)?;
+            // Tags are not part of the commitment, so they are not needed to find the proof
+            let verification_data =
+                verification_data_from_args(recover_args.proof_args, None, None)?;
+
+            info!(
+                "Searching for the proof in the data of batch 0x{}...",
+                hex::encode(batch_merkle_root)
+            );
+            let aligned_verification_data = recover_aligned_verification_data(
+                &verification_data.into(),
+                batch_merkle_root,
+                chain,
+                &recover_args.eth_rpc_url,
+            )
+            .await?;
+            info!(
+                "Found the proof at index {} of the batch",
+                aligned_verification_data.index_in_batch
+            );
+            output.print_json(|| aligned_verification_data_json(&aligned_verification_data));
+
+            let batch_inclusion_data_directory_path =
+                recover_args.batch_inclusion_data_directory_path;
+            std::fs::create_dir_all(&batch_inclusion_data_directory_path).map_err(|e| {
+                SubmitError::IoError(batch_inclusion_data_directory_path.clone(), e)
+            })?;
+            save_response(
+                batch_inclusion_data_directory_path,
+                &aligned_verification_data,
+            )?;
+        }
+        Resume(resume_args) => resume_pending_submissions(resume_args, output).await?,
+        Status(status_args) => {
+            // Tags are not part of the commitment, so they are not needed to find the proof
+            let verification_data =
+                verification_data_from_args(status_args.proof_args, None, None)?;
+            let mut tls_config = TlsConfig {
+                server_name: status_args.tls_server_name,
+                auth_token: status_args.auth_token,
+                ..Default::default()
+            };
+            for tls_root_ca_path in &status_args.tls_root_ca_paths {
+                tls_config = tls_config.with_root_certificate(read_file(tls_root_ca_path.clone())?);
+            }
+
+            let submission_status = get_submission_status(
+                &status_args.connect_addr,
+                &tls_config,
+                &verification_data.into(),
+            )
+            .await?;
+            log_submission_status(&submission_status);
+            output.print_json(|| submission_status_json(&submission_status));
+        }
+        Faucet(FaucetArgs {
+            command: FaucetCommands::Request(faucet_request_args),
+        }) => {
+            let address = Address::from_str(&faucet_request_args.address).map_err(|e| {
+                SubmitError::InvalidAddress(faucet_request_args.address.clone(), e.to_string())
+            })?;
+
+            if let Some(faucet_url) = &faucet_request_args.faucet_url {
+                info!("Requesting Ether for {:?} from {}", address, faucet_url);
+                match faucet::request_from_faucet(faucet_url, address).await? {
+                    Some(tx_hash) => info!("Faucet funded the address. Tx: {}", tx_hash),
+                    None => info!("Faucet accepted the request"),
+                }
+            } else if let ChainArg::Devnet = faucet_request_args.chain {
+                if !faucet_request_args.amount.ends_with("ether") {
+                    error!("Amount should be in the format XX.XXether");
+                    return Ok(());
                 }
+                let amount =
+                    parse_ether(faucet_request_args.amount.replace("ether", "")).map_err(|e| {
+                        SubmitError::EthError(format!("Error while parsing amount: {}", e))
+                    })?;
+
+                info!(
+                    "Sending {} ether to {:?} from the devnet account",
+                    format_ether(amount),
+                    address
+                );
+                let wallet =
+                    LocalWallet::from_str(ANVIL_PRIVATE_KEY).expect("Failed to create wallet");
+                let tx_hash = faucet::fund_from_anvil(
+                    &faucet_request_args.eth_rpc_url,
+                    wallet,
+                    address,
+                    amount,
+                )
+                .await?;
+                info!("Address funded successfully. Tx: 0x{:x}", tx_hash);
+            } else {
+                error!("There is no built-in faucet for this chain. Please set --faucet_url");
+                return Ok(());
+            }
+
+            info!("Deposit the Ether in the batcher with `aligned deposit-to-batcher` to pay for proofs");
+        }
+        VerifyBinary(verify_binary_args) => {
+            let signer = Address::from_str(&verify_binary_args.release_signer).map_err(|e| {
+                SubmitError::InvalidAddress(
+                    verify_binary_args.release_signer.clone(),
+                    e.to_string(),
+                )
+            })?;
+            let Some(asset) = verify_binary_args
+                .asset
+                .or_else(|| verify_binary::default_asset().map(str::to_string))
+            else {
+                return Err(SubmitError::GenericError(
+                    "There are no release binaries for this architecture. Please set --asset"
+                        .to_string(),
+                )
+                .into());
+            };
+            let manifest_url = verify_binary_args
+                .manifest_url
+                .unwrap_or_else(verify_binary::default_manifest_url);
+            let binary_path = std::env::current_exe()
+                .map_err(|e| SubmitError::IoError(PathBuf::from("aligned"), e))?;
+
+            info!(
+                "Verifying {} against {}",
+                binary_path.display(),
+                manifest_url
+            );
+            let release =
+                verify_binary::verify_binary(&manifest_url, signer, &binary_path, &asset).await?;
+            info!(
+                "This binary is {} of release {} (git {}), signed by {:?}",
+                asset, release.version, release.git_hash, signer
+            );
+            output.print_json(|| {
+                serde_json::json!({
+                    "asset": asset,
+                    "version": release.version,
+                    "git_hash": release.git_hash,
+                    "signer": signer,
+                })
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the commitments of several files, or of the files of a directory, and writes the
+/// mapping from each file to its commitment to the output file, or to the standard output.
+/// # Errors
+/// * `IoError` if a file can't be read or the output file can't be written.
+/// * `GenericError` if there are no files.
+fn get_commitments(args: GetCommitmentArgs, output: OutputFormat) -> Result<(), SubmitError> {
+    let files = commitments::input_files(&args.input_files)?;
+    if files.is_empty() {
+        return Err(SubmitError::GenericError(
+            "There are no files to commit to in the inputs".to_string(),
+        ));
+    }
+    let commitments = commitments::commitments(&files, args.hash_function.into())?;
+    info!("Computed the commitments of {} files", commitments.len());
+
+    let mapping = commitments::mapping(&commitments, args.format);
+    match args.output_file {
+        Some(output_file) => {
+            std::fs::write(&output_file, mapping)
+                .map_err(|e| SubmitError::IoError(output_file.clone(), e))?;
+            output.print_json(|| {
+                serde_json::json!({ "commitments": commitments::commitments_json(&commitments) })
+            });
+        }
+        // The standard output only holds the results with `--output json`
+        None if output == OutputFormat::Json => output.print_json(
+            || serde_json::json!({ "commitments": commitments::commitments_json(&commitments) }),
+        ),
+        None => print!("{}", mapping),
+    }
+    Ok(())
+}
+
+/// Asks the batcher of each pending submission for the responses of its proofs, saving the ones
+/// included in a batch and removing them from the pending file. The proofs still waiting for
+/// their batch are kept, as are the ones the batcher doesn't know unless `--discard_unknown` is
+/// set.
+/// # Errors
+/// * `IoError` if the pending file can't be read or written, or a response can't be saved.
+/// * `GenericError` if a pending submission couldn't be resumed, once the others are.
+async fn resume_pending_submissions(
+    args: ResumeArgs,
+    output: OutputFormat,
+) -> Result<(), SubmitError> {
+    let pending_store = PendingStore::in_dir(&args.batch_inclusion_data_directory_path);
+    let submissions = pending_store.load()?;
+    if submissions.is_empty() {
+        info!(
+            "There are no pending submissions in {}",
+            pending_store.path().display()
+        );
+        output.print_json(|| serde_json::json!({ "submissions": [] }));
+        return Ok(());
+    }
+
+    let mut tls_config = TlsConfig {
+        server_name: args.tls_server_name,
+        auth_token: args.auth_token,
+        ..Default::default()
+    };
+    for tls_root_ca_path in &args.tls_root_ca_paths {
+        tls_config = tls_config.with_root_certificate(read_file(tls_root_ca_path.clone())?);
+    }
+
+    let mut failed = 0;
+    let mut results = Vec::new();
+    for submission in &submissions {
+        info!(
+            "Resuming {} proofs sent to {} at {}",
+            submission.commitments.len(),
+            submission.batcher_addr,
+            submission.submitted_at
+        );
+        let mut result = serde_json::json!({
+            "batcher_addr": submission.batcher_addr,
+            "submitted_at": submission.submitted_at,
+        });
+        let resumed = match resume_submission(
+            &submission.batcher_addr,
+            &tls_config,
+            &submission.commitments,
+        )
+        .await
+        {
+            Ok(resumed) => resumed,
+            Err(e) => {
+                error!(
+                    "Submission to {} could not be resumed: {:?}",
+                    submission.batcher_addr, e
+                );
+                failed += 1;
+                result["error"] = format!("{:?}", e).into();
+                results.push(result);
+                continue;
+            }
+        };
+        info!(
+            "{} proofs were included in a batch, {} are waiting for their batch and {} are unknown to the batcher",
+            resumed.aligned_verification_data.len(),
+            resumed.pending.len(),
+            resumed.unknown.len()
+        );
+
+        let included: Vec<VerificationDataCommitment> = resumed
+            .aligned_verification_data
+            .iter()
+            .map(|aligned_verification_data| {
+                aligned_verification_data
+                    .verification_data_commitment
+                    .clone()
+            })
+            .collect();
+        result["included"] = resumed
+            .aligned_verification_data
+            .iter()
+            .map(aligned_verification_data_json)
+            .collect::<Vec<_>>()
+            .into();
+        result["pending"] = resumed.pending.len().into();
+        result["unknown"] = resumed.unknown.len().into();
+        if !resumed.aligned_verification_data.is_empty() {
+            save_responses(
+                &args.batch_inclusion_data_directory_path,
+                resumed.aligned_verification_data,
+            )?;
+        }
+        pending_store.remove(&included)?;
+        if !resumed.unknown.is_empty() {
+            if args.discard_unknown {
+                pending_store.remove(&resumed.unknown)?;
             } else {
-                error!("No batch inclusion data was received from the batcher");
+                warn!("The proofs unknown to the batcher were not received by it, or their batch is no longer kept by it. Submit them again, or recover them with `aligned recover`, and then forget them with --discard_unknown");
             }
         }
+        results.push(result);
+    }
+    output.print_json(|| serde_json::json!({ "submissions": results }));
+
+    if failed > 0 {
+        return Err(SubmitError::GenericError(format!(
+            "{} of {} pending submissions could not be resumed",
+            failed,
+            submissions.len()
+        )));
+    }
+    Ok(())
+}
 
-        VerifyProofOnchain(verify_inclusion_args) => {
-            let chain = verify_inclusion_args.chain.into();
-            let batch_inclusion_file =
-                File::open(verify_inclusion_args.batch_inclusion_data.clone()).map_err(|e| {
-                    SubmitError::IoError(verify_inclusion_args.batch_inclusion_data.clone(), e)
-                })?;
+/// Returns whether the command prints its results as JSON with `--output json`.
+fn prints_json(command: &AlignedCommands) -> bool {
+    !matches!(
+        command,
+        ReplayBatch(_) | GasReport(_) | OperatorStatus(_) | Convert(_) | Faucet(_)
+    )
+}
 
-            let reader = BufReader::new(batch_inclusion_file);
+/// Returns the commitments of a proof as JSON, each in hex.
+fn commitments_json(commitment: &VerificationDataCommitment) -> serde_json::Value {
+    serde_json::json!({
+        "proof_commitment": format!("0x{}", hex::encode(commitment.proof_commitment)),
+        "pub_input_commitment": format!("0x{}", hex::encode(commitment.pub_input_commitment)),
+        "proving_system_aux_data_commitment": format!(
+            "0x{}",
+            hex::encode(commitment.proving_system_aux_data_commitment)
+        ),
+        "proof_generator_addr": format!("0x{}", hex::encode(commitment.proof_generator_addr)),
+    })
+}
 
-            let aligned_verification_data: AlignedVerificationData =
-                serde_json::from_reader(reader).map_err(SubmitError::SerdeError)?;
+/// Returns where a proof is in its batch as JSON, with its commitments.
+fn aligned_verification_data_json(
+    aligned_verification_data: &AlignedVerificationData,
+) -> serde_json::Value {
+    serde_json::json!({
+        "batch_merkle_root": format!(
+            "0x{}",
+            hex::encode(aligned_verification_data.batch_merkle_root)
+        ),
+        "index_in_batch": aligned_verification_data.index_in_batch,
+        "commitments": commitments_json(&aligned_verification_data.verification_data_commitment),
+        "batch_metadata": aligned_verification_data.batch_metadata,
+        "metadata": aligned_verification_data
+            .metadata
+            .as_deref()
+            .map(String::from_utf8_lossy),
+    })
+}
 
-            info!("Verifying response data matches sent proof data...");
-            let response = verify_proof_onchain(
-                aligned_verification_data,
-                chain,
-                &verify_inclusion_args.eth_rpc_url,
-            )
-            .await?;
+fn tagged_proofs_json(tag: [u8; 32], tagged_proofs: &[TaggedProof]) -> serde_json::Value {
+    let proofs: Vec<serde_json::Value> = tagged_proofs
+        .iter()
+        .map(|tagged_proof| {
+            serde_json::json!({
+                "batch_merkle_root": format!("0x{}", hex::encode(tagged_proof.batch_merkle_root)),
+                "index_in_batch": tagged_proof.index_in_batch,
+                "task_created_block": tagged_proof.task_created_block,
+                "commitments": commitments_json(&tagged_proof.verification_data_commitment),
+            })
+        })
+        .collect();
+    serde_json::json!({ "tag": format!("0x{}", hex::encode(tag)), "proofs": proofs })
+}
 
-            if response {
-                info!("Your proof was verified in Aligned and included in the batch!");
-            } else {
-                info!("Your proof was not included in the batch.");
-            }
-        }
-        GetCommitment(args) => {
-            let content = read_file(args.input_file)?;
+fn batch_status_json(batch_status: &BatchStatus) -> serde_json::Value {
+    let attestation = batch_status.attestation.as_ref().map(|attestation| {
+        let quorums: Vec<serde_json::Value> = attestation
+            .quorums
+            .iter()
+            .map(|quorum| {
+                serde_json::json!({
+                    "quorum_number": quorum.quorum_number,
+                    "signed_stake": quorum.signed_stake.to_string(),
+                    "total_stake": quorum.total_stake.to_string(),
+                    "signed_stake_percentage": quorum.signed_stake_percentage(),
+                })
+            })
+            .collect();
+        let non_signer_operator_ids: Vec<String> = attestation
+            .non_signer_operator_ids
+            .iter()
+            .map(|operator_id| format!("0x{}", hex::encode(operator_id)))
+            .collect();
+
+        serde_json::json!({
+            "attestation_block": attestation.attestation_block,
+            "attestation_tx_hash": format!("{:?}", attestation.attestation_tx_hash),
+            "quorums": quorums,
+            "non_signer_operator_ids": non_signer_operator_ids,
+        })
+    });
 
-            let hash = get_commitment(&content);
+    serde_json::json!({
+        "batch_merkle_root": format!("0x{}", hex::encode(batch_status.batch_merkle_root)),
+        "task_created": batch_status.task_created_block.is_some(),
+        "task_created_block": batch_status.task_created_block,
+        "responded": batch_status.responded,
+        "num_proofs": batch_status.num_proofs,
+        "batch_data_pointer": batch_status.batch_data_pointer,
+        "attestation": attestation,
+    })
+}
 
-            info!("Commitment: {}", hex::encode(hash));
-            if let Some(output_file) = args.output_file {
-                let mut file = File::create(output_file.clone())
-                    .map_err(|e| SubmitError::IoError(output_file.clone(), e))?;
+fn submission_status_json(submission_status: &SubmissionStatus) -> serde_json::Value {
+    match submission_status {
+        SubmissionStatus::Pending => serde_json::json!({ "status": "pending" }),
+        SubmissionStatus::Batched {
+            batch_merkle_root,
+            index_in_batch,
+        } => serde_json::json!({
+            "status": "batched",
+            "batch_merkle_root": format!("0x{}", hex::encode(batch_merkle_root)),
+            "index_in_batch": index_in_batch,
+        }),
+        SubmissionStatus::Submitted {
+            batch_merkle_root,
+            index_in_batch,
+            tx_hash,
+        } => serde_json::json!({
+            "status": "submitted",
+            "batch_merkle_root": format!("0x{}", hex::encode(batch_merkle_root)),
+            "index_in_batch": index_in_batch,
+            "tx_hash": format!("{:?}", tx_hash),
+        }),
+        SubmissionStatus::Unknown => serde_json::json!({ "status": "unknown" }),
+    }
+}
 
-                file.write_all(hex::encode(hash).as_bytes())
-                    .map_err(|e| SubmitError::IoError(output_file.clone(), e))?;
-            }
+fn log_submission_status(submission_status: &SubmissionStatus) {
+    match submission_status {
+        SubmissionStatus::Pending => {
+            info!("The proof is in the batch queue of the batcher, waiting for its batch")
         }
-        DepositToBatcher(deposit_to_batcher_args) => {
-            if !deposit_to_batcher_args.amount.ends_with("ether") {
-                error!("Amount should be in the format XX.XXether");
-                return Ok(());
-            }
+        SubmissionStatus::Batched {
+            batch_merkle_root,
+            index_in_batch,
+        } => info!(
+            "The proof is at index {} of batch 0x{}, whose verification task is being created",
+            index_in_batch,
+            hex::encode(batch_merkle_root)
+        ),
+        SubmissionStatus::Submitted {
+            batch_merkle_root,
+            index_in_batch,
+            tx_hash,
+        } => {
+            info!(
+                "The proof is at index {} of batch 0x{}, whose verification task was created in transaction {:?}",
+                index_in_batch,
+                hex::encode(batch_merkle_root),
+                tx_hash
+            );
+            info!("Check whether the batch was verified with `get-batch-status`");
+        }
+        SubmissionStatus::Unknown => warn!(
+            "The batcher doesn't know the proof: it didn't receive it, or no longer keeps its batch. Find its batch with `get-tagged-proofs` or `list-batches`, and recover it with `recover`"
+        ),
+    }
+}
 
-            let chain: aligned_sdk::types::Chain = deposit_to_batcher_args.chain.into();
+fn verified_batches_json(verified_batches: &[list_batches::VerifiedBatch]) -> serde_json::Value {
+    verified_batches
+        .iter()
+        .map(|verified_batch| {
+            serde_json::json!({
+                "batch_merkle_root": format!("0x{}", hex::encode(verified_batch.batch_merkle_root)),
+                "task_created_block": verified_batch.task_created_block,
+                "batch_data_pointer": verified_batch.batch_data_pointer,
+                "verified_block": verified_batch.verified_block,
+                "verified_timestamp": verified_batch.verified_timestamp,
+                "verified_tx_hash": format!("{:?}", verified_batch.verified_tx_hash),
+            })
+        })
+        .collect()
+}
 
-            let amount = deposit_to_batcher_args.amount.replace("ether", "");
+fn log_batch_status(batch_status: &BatchStatus) {
+    let batch_merkle_root = hex::encode(batch_status.batch_merkle_root);
+    let Some(task_created_block) = batch_status.task_created_block else {
+        info!(
+            "The task of batch 0x{} was not created. The batch is still in the batcher or doesn't exist",
+            batch_merkle_root
+        );
+        return;
+    };
 
-            let eth_rpc_url = deposit_to_batcher_args.eth_rpc_url;
+    info!(
+        "The task of batch 0x{} was created at block {}",
+        batch_merkle_root, task_created_block
+    );
+    match batch_status.num_proofs {
+        Some(num_proofs) => info!("The batch holds {} proofs", num_proofs),
+        None => info!("The number of proofs of the batch is unknown"),
+    }
 
-            let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url).map_err(|e| {
-                SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e))
-            })?;
+    let Some(attestation) = &batch_status.attestation else {
+        if batch_status.responded {
+            info!("The batch was responded, but its attestation was not found");
+        } else {
+            info!("The batch was not responded yet");
+        }
+        return;
+    };
 
-            let keystore_path = &deposit_to_batcher_args.keystore_path;
+    info!(
+        "The batch was verified at block {}. Tx: {:?}",
+        attestation.attestation_block, attestation.attestation_tx_hash
+    );
+    for quorum in &attestation.quorums {
+        info!(
+            "Quorum {} signed {:.2}% of its stake: {} of {} ether",
+            quorum.quorum_number,
+            quorum.signed_stake_percentage(),
+            format_ether(quorum.signed_stake),
+            format_ether(quorum.total_stake)
+        );
+    }
+    info!(
+        "{} operators didn't sign",
+        attestation.non_signer_operator_ids.len()
+    );
+}
 
-            let mut wallet = if let Some(keystore_path) = keystore_path {
-                let password = rpassword::prompt_password("Please enter your keystore password:")
-                    .map_err(|e| SubmitError::GenericError(e.to_string()))?;
-                Wallet::decrypt_keystore(keystore_path, password)
-                    .map_err(|e| SubmitError::GenericError(e.to_string()))?
-            } else {
-                warn!("Missing keystore used for payment.");
-                return Ok(());
-            };
+/// Logs what can be done about a message the batcher rejected, when the batcher sent the details
+/// of the rejection.
+fn explain_rejection(e: &SubmitError) {
+    let SubmitError::MessageRejected(rejection) = e else {
+        return;
+    };
+    match rejection {
+        Rejection::InvalidSignature => {
+            error!("Check that the keystore or private key is the one of the payer of the proofs")
+        }
+        Rejection::InsufficientBalance { address } => error!(
+            "Deposit Ether for {:?} in the batcher with `aligned deposit-to-batcher`",
+            address
+        ),
+        Rejection::ProofTooLarge { max_proof_size, .. } => error!(
+            "The batcher accepts proofs of up to {} bytes. Check that --proof is the proof file",
+            max_proof_size
+        ),
+        Rejection::InvalidProof { proving_system } => error!(
+            "The proof doesn't verify as a {:?} proof. Check --proving_system, --vk and --public_input",
+            proving_system
+        ),
+        Rejection::InvalidMessage { .. } => {
+            error!("The batcher could not read the message. Check that the CLI is up to date")
+        }
+        Rejection::ReplayedMessage { .. } => {
+            error!("The message was already sent. Submit the proofs again to send a new one")
+        }
+    }
+}
 
-            match chain {
-                Chain::Devnet => wallet = wallet.with_chain_id(31337u64),
-                Chain::Holesky => wallet = wallet.with_chain_id(17000u64),
-            }
+/// Submits the proofs of the submit command signed with `wallet`, which can be any signer, and
+/// saves the aligned verification data of each one.
+async fn submit_proofs<S: Signer>(
+    submit_args: SubmitArgs,
+    connect_addr: &str,
+    tls_config: &TlsConfig,
+    wallet: S,
+) -> Result<(), SubmitError> {
+    let batch_inclusion_data_directory_path = submit_args.batch_inclusion_data_directory_path;
+    let repetitions = submit_args.repetitions;
+    let single_signature = submit_args.single_signature;
+    let bug_report_path = submit_args.bug_report_path;
+    let strict = submit_args.strict;
+    let best_effort = submit_args.best_effort;
+    let concurrency = submit_args.concurrency;
+    let json = submit_args.json;
+    let encryption = if submit_args.encrypt {
+        let mut encryption = EncryptionConfig::default();
+        if let Some(batcher_encryption_key) = &submit_args.batcher_encryption_key {
+            encryption =
+                encryption.with_batcher_public_key(parse_encryption_key(batcher_encryption_key)?);
+        }
+        Some(encryption)
+    } else {
+        None
+    };
+
+    // The proofs of a manifest are read first, to check the balance covers all of them
+    let manifest_verification_data = match &submit_args.manifest {
+        Some(manifest) => {
+            let verification_data = manifest::read(
+                manifest,
+                submit_args.tag.as_deref(),
+                submit_args.metadata.as_deref(),
+            )?;
+            info!(
+                "Read {} proofs from {}",
+                verification_data.len(),
+                manifest.display()
+            );
+            Some(verification_data)
+        }
+        None => None,
+    };
+    let num_proofs = manifest_verification_data.as_ref().map_or(1, Vec::len) * repetitions;
+
+    let balance_check = check_payer_balance(
+        &submit_args.eth_rpc_url,
+        &submit_args.batcher_eth_address,
+        wallet.address(),
+        num_proofs,
+        submit_args.allow_insufficient,
+    )
+    .await?;
+
+    let verification_data = match (manifest_verification_data, &submit_args.bundle) {
+        (Some(verification_data), _) => verification_data,
+        (None, Some(bundle)) => vec![verification_data_from_bundle(
+            bundle,
+            submit_args.tag,
+            submit_args.metadata,
+        )?],
+        (None, None) => vec![verification_data_from_args(
+            submit_args.proof_args,
+            submit_args.tag,
+            submit_args.metadata,
+        )?],
+    };
+    if submit_args.verify_before_submit {
+        for verification_data in &verification_data {
+            verify_before_submit(verification_data)?;
+        }
+        info!("The proofs are valid");
+    }
+    reserve_usage(
+        &submit_args.usage_limit_args,
+        num_proofs,
+        balance_check.map(|balance_check| balance_check.estimated_fee),
+    )?;
+
+    if let Some(concurrency) = concurrency {
+        return submit_repetitions(
+            connect_addr,
+            &verification_data,
+            repetitions,
+            concurrency as usize,
+            wallet,
+            single_signature,
+            strict,
+            tls_config,
+            encryption,
+            json,
+            &batch_inclusion_data_directory_path,
+        )
+        .await;
+    }
 
-            let client = SignerMiddleware::new(eth_rpc_provider.clone(), wallet.clone());
+    // The proofs of a manifest are repeated in order
+    let verification_data_arr: Vec<VerificationData> = verification_data
+        .iter()
+        .cycle()
+        .take(num_proofs)
+        .cloned()
+        .collect();
+
+    // The proofs are pending until their responses are received, so that they can be resumed
+    // with `aligned resume` if the submission is interrupted
+    let pending_store = PendingStore::in_dir(&batch_inclusion_data_directory_path);
+    let pending_commitments = batch_commitments(&verification_data_arr);
+    pending_store.record(PendingSubmission::new(
+        connect_addr,
+        pending_commitments.clone(),
+    ))?;
+
+    info!("Submitting proofs to the Aligned batcher...");
+
+    let submission: Result<(Option<Vec<AlignedVerificationData>>, Vec<EntryTiming>), SubmitError> =
+        async {
+            Ok(if best_effort {
+                let partial_submission = submit_multiple_best_effort(
+                    connect_addr,
+                    &verification_data_arr,
+                    wallet,
+                    single_signature,
+                )
+                .await?;
+                if let Some(e) = &partial_submission.interrupted_by {
+                    error!("Submission interrupted: {:?}", e);
+                }
+                if !partial_submission.is_complete() {
+                    save_unconfirmed_proofs(
+                        &batch_inclusion_data_directory_path,
+                        &partial_submission.unconfirmed,
+                    )?;
+                }
+                (
+                    Some(partial_submission.aligned_verification_data),
+                    partial_submission.timings,
+                )
+            } else {
+                // Repetitions submit the same proofs, so they are not duplicates
+                let mut builder = AlignedClient::builder()
+                    .batcher_addr(connect_addr)
+                    .wallet(wallet)
+                    .submit_options(SubmitOptions {
+                        single_signature,
+                        strict,
+                        bug_report_path,
+                    })
+                    .tls_config(tls_config.clone())
+                    .duplicate_window(std::time::Duration::ZERO);
+                if let Some(encryption) = encryption {
+                    builder = builder.encryption(encryption);
+                }
+                let submission = builder
+                    .build()?
+                    .submit_multiple_with_timing(&verification_data_arr)
+                    .await?;
+                (submission.aligned_verification_data, submission.timings)
+            })
+        }
+        .await;
+    settle_pending_submission(&pending_store, &pending_commitments, &submission)?;
+    let (aligned_verification_data_vec, timings) = submission?;
+
+    if json {
+        println!(
+            "{}",
+            submission_json(aligned_verification_data_vec.as_deref(), &timings)
+        );
+    }
 
-            let balance = client
-                .get_balance(wallet.address(), None)
-                .await
-                .map_err(|e| {
-                    SubmitError::EthError(format!("Error while getting balance: {}", e))
-                })?;
+    match aligned_verification_data_vec {
+        Some(aligned_verification_data_vec) => save_responses(
+            &batch_inclusion_data_directory_path,
+            aligned_verification_data_vec,
+        ),
+        None => {
+            error!("No batch inclusion data was received from the batcher");
+            Ok(())
+        }
+    }
+}
 
-            let amount_ether = parse_ether(&amount)
-                .map_err(|e| SubmitError::EthError(format!("Error while parsing amount: {}", e)))?;
+/// Removes from the pending file the proofs of a submission that were responded, keeping the
+/// others to be resumed. The proofs of a submission that failed before sending them are removed
+/// too.
+fn settle_pending_submission(
+    pending_store: &PendingStore,
+    commitments: &[VerificationDataCommitment],
+    submission: &Result<(Option<Vec<AlignedVerificationData>>, Vec<EntryTiming>), SubmitError>,
+) -> Result<(), SubmitError> {
+    let responded: Vec<VerificationDataCommitment> = match submission {
+        Ok((aligned_verification_data_vec, _)) => aligned_verification_data_vec
+            .iter()
+            .flatten()
+            .map(|aligned_verification_data| {
+                aligned_verification_data
+                    .verification_data_commitment
+                    .clone()
+            })
+            .collect(),
+        Err(e) if !proofs_may_have_been_sent(e) => commitments.to_vec(),
+        Err(_) => Vec::new(),
+    };
+    pending_store.remove(&responded)?;
+
+    if responded.len() < commitments.len() {
+        warn!(
+            "{} proofs were not responded, get their responses later with `aligned resume`",
+            commitments.len() - responded.len()
+        );
+    }
+    Ok(())
+}
 
-            if amount_ether <= U256::from(0) {
-                error!("Amount should be greater than 0");
-                return Ok(());
-            }
+/// Returns whether the batcher may have received proofs of a submission that failed with this
+/// error, which is only ruled out for errors raised before the first message is sent.
+fn proofs_may_have_been_sent(e: &SubmitError) -> bool {
+    match e {
+        SubmitError::ConnectionError(tokio_tungstenite::tungstenite::Error::Io(e)) => {
+            e.kind() != std::io::ErrorKind::ConnectionRefused
+        }
+        SubmitError::TlsError(_)
+        | SubmitError::InvalidCertificate(_)
+        | SubmitError::Unauthorized(_)
+        | SubmitError::ProtocolMismatch { .. }
+        | SubmitError::DuplicateSubmission(_) => false,
+        _ => true,
+    }
+}
 
-            if balance < amount_ether {
-                error!("Insufficient funds to pay to the batcher. Please deposit some Ether in your wallet.");
-                return Ok(());
-            }
+/// Submits each repetition of the proofs in its own connection, with at most `concurrency` of
+/// them at once, and reports the throughput and the failures of all of them. The responses of
+/// the repetitions that succeed are saved even if others fail.
+/// # Errors
+/// * `GenericError` if any repetition failed, once the others are done.
+/// * `IoError` if a response can't be saved.
+#[allow(clippy::too_many_arguments)]
+async fn submit_repetitions<S: Signer>(
+    connect_addr: &str,
+    verification_data: &[VerificationData],
+    repetitions: usize,
+    concurrency: usize,
+    wallet: S,
+    single_signature: bool,
+    strict: bool,
+    tls_config: &TlsConfig,
+    encryption: Option<EncryptionConfig>,
+    json: bool,
+    batch_inclusion_data_directory_path: &Path,
+) -> Result<(), SubmitError> {
+    // Every repetition submits the same proofs, so they are not duplicates
+    let mut builder = AlignedClient::builder()
+        .batcher_addr(connect_addr)
+        .wallet(wallet)
+        .submit_options(SubmitOptions {
+            single_signature,
+            strict,
+            ..Default::default()
+        })
+        .tls_config(tls_config.clone())
+        .duplicate_window(std::time::Duration::ZERO);
+    if let Some(encryption) = encryption {
+        builder = builder.encryption(encryption);
+    }
+    let client = builder.build()?;
 
-            let batcher_addr = Address::from_str(&deposit_to_batcher_args.batcher_eth_address)
-                .map_err(|e| {
-                    SubmitError::EthError(format!("Error while parsing batcher address: {}", e))
-                })?;
+    let (results, report) =
+        repetitions::submit_repetitions(&client, verification_data, repetitions, concurrency).await;
+    info!(
+        "{} of {} repetitions succeeded and {} failed, {} proofs included in {:.2?}, {:.2} proofs per second",
+        report.repetitions - report.failed,
+        report.repetitions,
+        report.failed,
+        report.included_proofs,
+        report.elapsed,
+        report.proofs_per_second()
+    );
+    if json {
+        println!("{}", repetitions_json(&results, &report));
+    }
 
-            let tx = TransactionRequest::new()
-                .to(batcher_addr)
-                .value(amount_ether)
-                .from(wallet.address());
+    let aligned_verification_data_vec: Vec<AlignedVerificationData> = results
+        .into_iter()
+        .filter_map(|repetition| repetition.result.ok())
+        .flat_map(|submission| submission.aligned_verification_data.unwrap_or_default())
+        .collect();
+    if !aligned_verification_data_vec.is_empty() {
+        save_responses(
+            batch_inclusion_data_directory_path,
+            aligned_verification_data_vec,
+        )?;
+    }
 
-            info!("Sending {} ether to the batcher", amount);
+    if report.failed > 0 {
+        return Err(SubmitError::GenericError(format!(
+            "{} of {} repetitions failed",
+            report.failed, report.repetitions
+        )));
+    }
+    Ok(())
+}
 
-            let tx = client
-                .send_transaction(tx, None)
-                .await
-                .map_err(|e| {
-                    SubmitError::EthError(format!("Error while sending transaction: {}", e))
-                })?
-                .await
-                .map_err(|e| {
-                    SubmitError::EthError(format!("Error while sending transaction: {}", e))
-                })?;
+fn repetitions_json(
+    results: &[repetitions::Repetition],
+    report: &repetitions::RepetitionsReport,
+) -> serde_json::Value {
+    let repetitions: Vec<serde_json::Value> = results
+        .iter()
+        .map(|repetition| {
+            let mut json = match &repetition.result {
+                Ok(submission) => submission_json(
+                    submission.aligned_verification_data.as_deref(),
+                    &submission.timings,
+                ),
+                Err(e) => serde_json::json!({ "error": format!("{:?}", e) }),
+            };
+            json["repetition"] = repetition.position.into();
+            json["elapsed_ms"] = (repetition.elapsed.as_millis() as u64).into();
+            json
+        })
+        .collect();
+    serde_json::json!({
+        "repetitions": repetitions,
+        "failed": report.failed,
+        "included_proofs": report.included_proofs,
+        "elapsed_ms": report.elapsed.as_millis() as u64,
+        "proofs_per_second": report.proofs_per_second(),
+    })
+}
 
-            if let Some(tx) = tx {
-                info!(
-                    "Payment sent to the batcher successfully. Tx: 0x{:x}",
-                    tx.transaction_hash
-                );
-            } else {
-                error!("Transaction failed");
-            }
-        }
-        GetUserBalance(get_user_balance_args) => {
-            let eth_rpc_url = get_user_balance_args.eth_rpc_url;
+/// Saves the batch inclusion data of each proof and logs the batches they were included in.
+fn save_responses(
+    batch_inclusion_data_directory_path: &Path,
+    aligned_verification_data_vec: Vec<AlignedVerificationData>,
+) -> Result<(), SubmitError> {
+    let mut unique_batch_merkle_roots = HashSet::new();
+
+    for aligned_verification_data in aligned_verification_data_vec {
+        save_response(
+            batch_inclusion_data_directory_path.to_path_buf(),
+            &aligned_verification_data,
+        )?;
+        unique_batch_merkle_roots.insert(aligned_verification_data.batch_merkle_root);
+    }
 
-            let eth_rpc_provider = Provider::<Http>::try_from(eth_rpc_url).map_err(|e| {
-                SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e))
-            })?;
+    if unique_batch_merkle_roots.len() > 1 {
+        info!("Proofs submitted to aligned. See the batches in the explorer:");
+    } else {
+        info!("Proofs submitted to aligned. See the batch in the explorer:");
+    }
 
-            let user_address =
-                Address::from_str(&get_user_balance_args.user_address).map_err(|e| {
-                    SubmitError::EthError(format!("Error while parsing user address: {}", e))
-                })?;
+    for batch_merkle_root in unique_batch_merkle_roots {
+        info!(
+            "https://explorer.alignedlayer.com/batches/0x{}",
+            hex::encode(batch_merkle_root)
+        );
+    }
+    Ok(())
+}
 
-            let batcher_addr = Address::from_str(&get_user_balance_args.batcher_eth_address)
-                .map_err(|e| {
-                    SubmitError::EthError(format!("Error while parsing batcher address: {}", e))
-                })?;
+/// Returns the response and the timing of each proof of a submission as JSON, with the times as
+/// milliseconds since the Unix epoch. The responses are the ones of the included proofs, in
+/// their order.
+fn submission_json(
+    aligned_verification_data: Option<&[AlignedVerificationData]>,
+    timings: &[EntryTiming],
+) -> serde_json::Value {
+    let unix_millis = |time: Option<std::time::SystemTime>| {
+        time.and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_millis() as u64)
+    };
+    let aligned_verification_data = aligned_verification_data.unwrap_or_default();
+    // Submissions with a bug report are not timed, so only their responses are known
+    if timings.is_empty() {
+        let proofs: Vec<serde_json::Value> = aligned_verification_data
+            .iter()
+            .enumerate()
+            .map(|(position, aligned_verification_data)| {
+                serde_json::json!({
+                    "position": position,
+                    "response": aligned_verification_data_json(aligned_verification_data),
+                })
+            })
+            .collect();
+        return serde_json::json!({ "proofs": proofs });
+    }
+    let mut responses = aligned_verification_data.iter();
+
+    let proofs: Vec<serde_json::Value> = timings
+        .iter()
+        .map(|timing| {
+            let response = timing
+                .included_at
+                .and_then(|_| responses.next())
+                .map(aligned_verification_data_json);
+            serde_json::json!({
+                "position": timing.position,
+                "response": response,
+                "queued_at_ms": unix_millis(Some(timing.queued_at)),
+                "sent_at_ms": unix_millis(timing.sent_at),
+                "acknowledged_at_ms": unix_millis(timing.acknowledged_at),
+                "included_at_ms": unix_millis(timing.included_at),
+            })
+        })
+        .collect();
+
+    serde_json::json!({ "proofs": proofs })
+}
 
-            let balance = get_user_balance(eth_rpc_provider, batcher_addr, user_address)
-                .await
-                .map_err(|e| {
-                    SubmitError::EthError(format!("Error while getting user balance: {}", e))
-                })?;
+/// Checks the balance of the payer in the batcher payment service covers the estimated fee of
+/// the proofs, so that they are not sent only to be dropped by the batcher. The submission goes
+/// on with a warning if the balance can't be queried, or if `allow_insufficient` is set.
+/// Returns the balance and the estimated fee, if they could be queried.
+async fn check_payer_balance(
+    eth_rpc_url: &str,
+    batcher_eth_address: &str,
+    payer: Address,
+    num_proofs: usize,
+    allow_insufficient: bool,
+) -> Result<Option<fees::BalanceCheck>, SubmitError> {
+    let batcher_addr = Address::from_str(batcher_eth_address).map_err(|e| {
+        SubmitError::EthError(format!("Error while parsing batcher address: {}", e))
+    })?;
 
-            info!(
-                "User {} has {} ether in the batcher",
-                user_address,
-                format_ether(balance)
+    let balance_check = match AlignedRpcClient::from_url(eth_rpc_url) {
+        Ok(rpc_client) => fees::check_balance(&rpc_client, batcher_addr, payer, num_proofs).await,
+        Err(e) => Err(e),
+    };
+    let balance_check = match balance_check {
+        Ok(balance_check) => balance_check,
+        Err(e) => {
+            warn!(
+                "Could not check the balance of {} in the batcher, submitting anyway: {:?}",
+                payer, e
             );
+            return Ok(None);
+        }
+    };
+
+    info!(
+        "Payer {} has {} ether in the batcher, the proofs are estimated to cost up to {} ether",
+        payer,
+        format_ether(balance_check.balance),
+        format_ether(balance_check.estimated_fee)
+    );
+
+    match balance_check.ensure_sufficient() {
+        Err(e) if allow_insufficient => {
+            warn!("{:?}. The batcher may drop the proofs", e);
         }
+        result => result?,
     }
+    Ok(Some(balance_check))
+}
 
+/// Checks the proofs don't exceed the daily usage limits, and records them in the usage store.
+/// Nothing is recorded without limits. With `ignore_usage_limits`, proofs exceeding the limits
+/// are submitted with a warning, and recorded.
+fn reserve_usage(
+    usage_limit_args: &UsageLimitArgs,
+    num_proofs: usize,
+    estimated_fee: Option<U256>,
+) -> Result<(), SubmitError> {
+    let max_spend_per_day = match &usage_limit_args.max_spend_per_day {
+        Some(max_spend_per_day) => Some(parse_ether(max_spend_per_day).map_err(|e| {
+            SubmitError::GenericError(format!("Invalid --max_spend_per_day: {}", e))
+        })?),
+        None => None,
+    };
+    let limits = UsageLimits {
+        max_proofs_per_day: usage_limit_args.max_proofs_per_day,
+        max_spend_per_day,
+    };
+    if limits.is_unlimited() {
+        return Ok(());
+    }
+    let store = match &usage_limit_args.usage_store {
+        Some(usage_store) => UsageStore::new(usage_store),
+        None => UsageStore::new(
+            default_usage_store_path()
+                .ok_or_else(|| SubmitError::MissingParameter("usage_store".to_string()))?,
+        ),
+    };
+    let ignore_usage_limits = usage_limit_args.ignore_usage_limits;
+
+    let spend = match estimated_fee {
+        Some(estimated_fee) => estimated_fee,
+        None if limits.max_spend_per_day.is_some() && !ignore_usage_limits => {
+            return Err(SubmitError::GenericError(
+                "The fee of the proofs could not be estimated to check --max_spend_per_day, use --ignore_usage_limits to submit anyway".to_string(),
+            ));
+        }
+        None => U256::zero(),
+    };
+    let usage = match store.reserve(&limits, num_proofs, spend) {
+        Err(e @ SubmitError::UsageLimitExceeded(_)) if ignore_usage_limits => {
+            warn!("{:?}. Submitting anyway", e);
+            store.record(num_proofs, spend)?
+        }
+        result => result?,
+    };
+    info!(
+        "{} proofs and up to {} ether submitted today, recorded in {}. The limits reset at midnight UTC",
+        usage.proofs,
+        format_ether(usage.spend),
+        store.path().display()
+    );
     Ok(())
 }
 
-fn verification_data_from_args(args: SubmitArgs) -> Result<VerificationData, SubmitError> {
-    let proving_system = args.proving_system_flag.into();
+/// Returns `$XDG_STATE_HOME/aligned/usage.json`, or `~/.local/state/aligned/usage.json` when
+/// `XDG_STATE_HOME` is not set.
+fn default_usage_store_path() -> Option<PathBuf> {
+    let state_home = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(state_home) => PathBuf::from(state_home),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(state_home.join("aligned").join("usage.json"))
+}
 
+fn verification_data_from_args(
+    args: ProofArgs,
+    tag: Option<String>,
+    metadata: Option<String>,
+) -> Result<VerificationData, SubmitError> {
     // Read proof file
-    let proof = read_file(args.proof_file_name)?;
+    let proof = read_file_option("--proof", args.proof_file_name)?;
+    let verification_key_file = args.verification_key_file_name.map(read_file).transpose()?;
+
+    let proving_system = match args.proving_system_flag {
+        Some(proving_system_flag) => {
+            let proving_system = proving_system_flag.into();
+            check_proving_system(&proving_system, &proof, verification_key_file.as_deref())?;
+            proving_system
+        }
+        None => {
+            let proving_system = infer_proving_system(&proof, verification_key_file.as_deref())?;
+            info!("Detected a {:?} proof", proving_system);
+            proving_system
+        }
+    };
+
+    let capabilities = proving_system.capabilities();
 
     let mut pub_input: Option<Vec<u8>> = None;
     let mut verification_key: Option<Vec<u8>> = None;
     let mut vm_program_code: Option<Vec<u8>> = None;
 
-    match proving_system {
-        ProvingSystemId::SP1 => {
-            vm_program_code = Some(read_file_option(
-                "--vm_program",
-                args.vm_program_code_file_name,
-            )?);
-        }
-        ProvingSystemId::Risc0 => {
-            vm_program_code = Some(read_file_option(
-                "--vm_program",
-                args.vm_program_code_file_name,
-            )?);
-            pub_input = Some(read_file_option(
-                "--public_input",
-                args.pub_input_file_name,
-            )?);
-        }
-        ProvingSystemId::Halo2KZG
-        | ProvingSystemId::Halo2IPA
-        | ProvingSystemId::GnarkPlonkBls12_381
-        | ProvingSystemId::GnarkPlonkBn254
-        | ProvingSystemId::Groth16Bn254 => {
-            verification_key = Some(read_file_option("--vk", args.verification_key_file_name)?);
-            pub_input = Some(read_file_option(
-                "--public_input",
-                args.pub_input_file_name,
-            )?);
+    if capabilities.requires_vm_program {
+        vm_program_code = Some(read_file_option(
+            "--vm_program",
+            args.vm_program_code_file_name,
+        )?);
+    }
+    if capabilities.requires_verification_key {
+        verification_key =
+            Some(verification_key_file.ok_or(SubmitError::MissingParameter("--vk".to_string()))?);
+    }
+    if capabilities.requires_pub_input {
+        pub_input = Some(read_file_option(
+            "--public_input",
+            args.pub_input_file_name,
+        )?);
+    } else if let ProvingSystemId::SP1 = proving_system {
+        if args.sp1_public_values {
+            pub_input = Some(get_sp1_public_values(&proof)?);
         }
     }
 
@@ -511,16 +3127,138 @@ fn verification_data_from_args(args: SubmitArgs) -> Result<VerificationData, Sub
         SubmitError::InvalidAddress(args.proof_generator_addr.clone(), e.to_string())
     })?;
 
-    Ok(VerificationData {
+    let tag = tag.as_deref().map(parse_tag).transpose()?;
+
+    let verification_data = VerificationData {
         proving_system,
         proof,
         pub_input,
         verification_key,
         vm_program_code,
         proof_generator_addr,
+        tag,
+        metadata: metadata.map(String::into_bytes),
+        schema_version: VerificationData::SCHEMA_VERSION,
+    };
+    verification_data.validate()?;
+
+    Ok(verification_data)
+}
+
+/// Reads the verification data of a proof bundle, with the tag and metadata of the submission.
+fn verification_data_from_bundle(
+    bundle: &Path,
+    tag: Option<String>,
+    metadata: Option<String>,
+) -> Result<VerificationData, SubmitError> {
+    let mut verification_data = read_bundle(bundle)?;
+    info!(
+        "Read a {:?} proof from {}",
+        verification_data.proving_system,
+        bundle.display()
+    );
+    verification_data.tag = tag.as_deref().map(parse_tag).transpose()?;
+    verification_data.metadata = metadata.map(String::into_bytes);
+    verification_data.validate()?;
+    Ok(verification_data)
+}
+
+/// Verifies the proof as the operators would, so that an invalid proof is not paid for.
+#[cfg(feature = "local-verification")]
+fn verify_before_submit(verification_data: &VerificationData) -> Result<(), SubmitError> {
+    info!("Verifying the proof locally...");
+    aligned_sdk::verify_local(verification_data)
+}
+
+/// Fails, since proofs can only be verified by CLIs built with the `local-verification` feature.
+#[cfg(not(feature = "local-verification"))]
+fn verify_before_submit(_verification_data: &VerificationData) -> Result<(), SubmitError> {
+    Err(SubmitError::GenericError(
+        "This CLI was built without local verification, rebuild it with the local-verification feature"
+            .to_string(),
+    ))
+}
+
+fn format_from_extension(path: &Path) -> Result<FileFormat, SubmitError> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => Ok(FileFormat::Json),
+        Some("bin") => Ok(FileFormat::Binary),
+        _ => Err(SubmitError::GenericError(format!(
+            "Can't detect the format of {:?} from its extension, please set --format",
+            path
+        ))),
+    }
+}
+
+fn parse_batch_merkle_root(batch_merkle_root: &str) -> Result<[u8; 32], SubmitError> {
+    let bytes = hex::decode(batch_merkle_root.trim_start_matches("0x"))?;
+    bytes.try_into().map_err(|_| {
+        SubmitError::GenericError(format!(
+            "Invalid batch merkle root: {}, expected 32 bytes",
+            batch_merkle_root
+        ))
     })
 }
 
+fn parse_tag(tag: &str) -> Result<[u8; 32], SubmitError> {
+    let bytes = hex::decode(tag.trim_start_matches("0x"))?;
+    bytes
+        .try_into()
+        .map_err(|_| SubmitError::GenericError(format!("Invalid tag: {}, expected 32 bytes", tag)))
+}
+
+fn parse_encryption_key(key: &str) -> Result<[u8; 32], SubmitError> {
+    let bytes = hex::decode(key.trim_start_matches("0x"))?;
+    bytes.try_into().map_err(|_| {
+        SubmitError::GenericError(format!(
+            "Invalid encryption key: {}, expected 32 bytes",
+            key
+        ))
+    })
+}
+
+fn format_gas(gas: Option<U256>) -> String {
+    gas.map(|gas| gas.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn stake_share_percentage(stake: U256, total_stake: U256) -> f64 {
+    if total_stake.is_zero() {
+        return 0.0;
+    }
+    let basis_points: U256 = stake * 10_000 / total_stake;
+    basis_points.as_u64() as f64 / 100.0
+}
+
+/// Writes the proof of a bundle and the files needed to verify it into a directory, each named
+/// after the flag of the submit command that takes it.
+/// # Returns
+/// * The flags and paths of the written files.
+fn write_bundle_files(
+    directory: &Path,
+    verification_data: &VerificationData,
+) -> Result<Vec<(&'static str, PathBuf)>, SubmitError> {
+    std::fs::create_dir_all(directory)
+        .map_err(|e| SubmitError::IoError(directory.to_path_buf(), e))?;
+
+    let files = [
+        ("proof", Some(&verification_data.proof)),
+        ("public_input", verification_data.pub_input.as_ref()),
+        ("vk", verification_data.verification_key.as_ref()),
+        ("vm_program", verification_data.vm_program_code.as_ref()),
+    ];
+    let mut written = Vec::new();
+    for (flag, contents) in files {
+        let Some(contents) = contents else {
+            continue;
+        };
+        let path = directory.join(flag);
+        std::fs::write(&path, contents).map_err(|e| SubmitError::IoError(path.clone(), e))?;
+        written.push((flag, path));
+    }
+    Ok(written)
+}
+
 fn read_file(file_name: PathBuf) -> Result<Vec<u8>, SubmitError> {
     std::fs::read(&file_name).map_err(|e| SubmitError::IoError(file_name, e))
 }
@@ -530,6 +3268,25 @@ fn read_file_option(param_name: &str, file_name: Option<PathBuf>) -> Result<Vec<
     read_file(file_name)
 }
 
+/// Writes the proofs of a best effort submission without a response to
+/// `unconfirmed_proofs.json` in the given directory, so that they can be submitted again.
+fn save_unconfirmed_proofs(
+    batch_inclusion_data_directory_path: &Path,
+    unconfirmed: &[UnconfirmedProof],
+) -> Result<(), SubmitError> {
+    let unconfirmed_proofs_path = batch_inclusion_data_directory_path.join(UNCONFIRMED_PROOFS_FILE);
+    let data = serde_json::to_vec_pretty(unconfirmed)?;
+    std::fs::write(&unconfirmed_proofs_path, data)
+        .map_err(|e| SubmitError::IoError(unconfirmed_proofs_path.clone(), e))?;
+    warn!(
+        "{} proofs were not confirmed by the batcher, they are listed in {}",
+        unconfirmed.len(),
+        unconfirmed_proofs_path.display()
+    );
+
+    Ok(())
+}
+
 fn save_response(
     batch_inclusion_data_directory_path: PathBuf,
     aligned_verification_data: &AlignedVerificationData,
@@ -556,33 +3313,3 @@ fn save_response(
 
     Ok(())
 }
-
-pub async fn get_user_balance(
-    provider: Provider<Http>,
-    contract_address: Address,
-    user_address: Address,
-) -> Result<U256, ProviderError> {
-    let selector = &ethers::utils::keccak256("UserBalances(address)".as_bytes())[..4];
-
-    let encoded_params = ethers::abi::encode(&[ethers::abi::Token::Address(user_address)]);
-
-    let mut call_data = selector.to_vec();
-    call_data.extend_from_slice(&encoded_params);
-
-    let tx = TypedTransaction::Legacy(TransactionRequest {
-        to: Some(NameOrAddress::Address(contract_address)),
-        data: Some(Bytes(call_data.into())),
-        ..Default::default()
-    });
-
-    let result = provider.call_raw(&tx).await?;
-
-    if result.len() == 32 {
-        let balance = U256::from_big_endian(&result);
-        Ok(balance)
-    } else {
-        Err(ProviderError::CustomError(
-            "Invalid response from contract".to_string(),
-        ))
-    }
-}
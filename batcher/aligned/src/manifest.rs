@@ -0,0 +1,121 @@
+//! Manifests listing the proofs of a bulk submission, given with `submit --manifest`, so that
+//! they are all sent in a single connection to the batcher.
+//!
+//! The entries take the same values as the flags of `submit`, and their paths are relative to
+//! the directory of the manifest:
+//!
+//! ```json
+//! {
+//!   "proofs": [
+//!     { "proving_system": "SP1", "proof": "sp1/fibonacci.proof", "vm_program": "sp1/fibonacci.elf" },
+//!     { "proof": "groth16/proof", "public_input": "groth16/pub", "vk": "groth16/vk", "tag": "0x01…", "metadata": "job-42" }
+//!   ]
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::types::VerificationData;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::{
+    verification_data_from_args, ProofArgs, ProvingSystemArg, DEFAULT_PROOF_GENERATOR_ADDR,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    proofs: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestEntry {
+    /// Detected from the proof and verification key files if not set, as with `--proving_system`.
+    proving_system: Option<String>,
+    proof: PathBuf,
+    public_input: Option<PathBuf>,
+    vk: Option<PathBuf>,
+    vm_program: Option<PathBuf>,
+    proof_generator_addr: Option<String>,
+    #[serde(default)]
+    sp1_public_values: bool,
+    /// The tag of the proof, instead of the `--tag` of the submission.
+    tag: Option<String>,
+    /// The metadata of the proof, instead of the `--metadata` of the submission.
+    metadata: Option<String>,
+}
+
+/// Reads the manifest at `path` and returns the verification data of its entries, in order.
+/// Entries without a tag get `tag`, the one of `--tag`, and entries without metadata get
+/// `metadata`, the one of `--metadata`.
+/// # Errors
+/// * `IoError` if the manifest can't be read.
+/// * `GenericError` if the manifest is not valid, has no entries, or the verification data of an
+///   entry can't be built from its files, with the position of the entry.
+pub fn read(
+    path: &Path,
+    tag: Option<&str>,
+    metadata: Option<&str>,
+) -> Result<Vec<VerificationData>, SubmitError> {
+    let manifest =
+        std::fs::read_to_string(path).map_err(|e| SubmitError::IoError(path.to_path_buf(), e))?;
+    let manifest: Manifest = serde_json::from_str(&manifest).map_err(|e| {
+        SubmitError::GenericError(format!("Invalid manifest {}: {}", path.display(), e))
+    })?;
+    if manifest.proofs.is_empty() {
+        return Err(SubmitError::GenericError(format!(
+            "The manifest {} has no proofs",
+            path.display()
+        )));
+    }
+
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    manifest
+        .proofs
+        .into_iter()
+        .enumerate()
+        .map(|(position, entry)| {
+            let entry_tag = entry.tag.clone().or(tag.map(str::to_string));
+            let entry_metadata = entry.metadata.clone().or(metadata.map(str::to_string));
+            proof_args(entry, base_dir)
+                .and_then(|proof_args| {
+                    verification_data_from_args(proof_args, entry_tag, entry_metadata)
+                })
+                .map_err(|e| {
+                    SubmitError::GenericError(format!(
+                        "Entry {} of the manifest {}: {:?}",
+                        position,
+                        path.display(),
+                        e
+                    ))
+                })
+        })
+        .collect()
+}
+
+fn proof_args(entry: ManifestEntry, base_dir: &Path) -> Result<ProofArgs, SubmitError> {
+    let proving_system_flag = entry
+        .proving_system
+        .map(|proving_system| {
+            ProvingSystemArg::from_str(&proving_system, false).map_err(|_| {
+                SubmitError::GenericError(format!("Unknown proving system {}", proving_system))
+            })
+        })
+        .transpose()?;
+    let resolve = |path: PathBuf| base_dir.join(path);
+
+    Ok(ProofArgs {
+        proving_system_flag,
+        proof_file_name: Some(resolve(entry.proof)),
+        pub_input_file_name: entry.public_input.map(resolve),
+        verification_key_file_name: entry.vk.map(resolve),
+        vm_program_code_file_name: entry.vm_program.map(resolve),
+        proof_generator_addr: entry
+            .proof_generator_addr
+            .unwrap_or_else(|| DEFAULT_PROOF_GENERATOR_ADDR.to_string()),
+        sp1_public_values: entry.sp1_public_values,
+    })
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::eth::{
+    AlignedLayerServiceManagerContract, BatchVerifiedFilter, IndexRegistryContract,
+    RegistryCoordinatorContract,
+};
+use aligned_sdk::quorum::{get_non_signer_operator_ids, get_operators, ALIGNED_QUORUM_NUMBER};
+use aligned_sdk::types::Chain;
+
+pub struct OperatorStatus {
+    pub address: Address,
+    pub stake: U256,
+    /// Batches verified while the operator was registered.
+    pub eligible_batches: usize,
+    /// Batches among the eligible ones that the operator signed.
+    pub signed_batches: usize,
+}
+
+pub struct OperatorStatusReport {
+    pub total_stake: U256,
+    pub verified_batches: usize,
+    pub operators: Vec<OperatorStatus>,
+}
+
+/// Lists the operators currently registered in Aligned with their stake, and counts how many of
+/// the batches verified since `from_block` each of them signed. An operator is eligible to sign
+/// a batch if it was registered at the block in which the batch was created, which is the
+/// reference block of the attestation.
+pub async fn operator_status(
+    eth_rpc_url: &str,
+    chain: Chain,
+    contract_address: &str,
+    from_block: u64,
+) -> Result<OperatorStatusReport, SubmitError> {
+    let operators = get_operators(chain, eth_rpc_url).await.map_err(|e| {
+        SubmitError::EthError(format!("Error while getting the operators: {:?}", e))
+    })?;
+
+    let provider = Provider::<Http>::try_from(eth_rpc_url)
+        .map_err(|e| SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e)))?;
+    let client = Arc::new(provider.clone());
+
+    let contract_address = Address::from_str(contract_address)
+        .map_err(|e| SubmitError::InvalidAddress(contract_address.to_string(), e.to_string()))?;
+    let service_manager = AlignedLayerServiceManagerContract::new(contract_address, client.clone());
+
+    let registry_coordinator_address = service_manager
+        .registry_coordinator()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while getting the registry: {}", e)))?;
+    let index_registry_address =
+        RegistryCoordinatorContract::new(registry_coordinator_address, client.clone())
+            .index_registry()
+            .await
+            .map_err(|e| {
+                SubmitError::EthError(format!("Error while getting the registry: {}", e))
+            })?;
+    let index_registry = IndexRegistryContract::new(index_registry_address, client);
+
+    let batch_verified_events = service_manager
+        .event::<BatchVerifiedFilter>()
+        .from_block(from_block)
+        .query_with_meta()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while querying BatchVerified: {}", e)))?;
+
+    let mut eligible_batches = vec![0; operators.len()];
+    let mut signed_batches = vec![0; operators.len()];
+
+    for (event, log_meta) in &batch_verified_events {
+        let (task_created_block, _) = service_manager
+            .batches_state(event.batch_merkle_root)
+            .await
+            .map_err(|e| SubmitError::EthError(format!("Error while getting the batch: {}", e)))?;
+
+        let registered_operator_ids: HashSet<[u8; 32]> = index_registry
+            .get_operator_list_at_block_number(ALIGNED_QUORUM_NUMBER, task_created_block)
+            .await
+            .map_err(|e| SubmitError::EthError(format!("Error while getting operators: {}", e)))?
+            .into_iter()
+            .collect();
+
+        let attestation_tx = provider
+            .get_transaction(log_meta.transaction_hash)
+            .await
+            .map_err(|e| SubmitError::EthError(format!("Error while getting transaction: {}", e)))?
+            .ok_or(SubmitError::EthError(format!(
+                "Transaction {:?} not found",
+                log_meta.transaction_hash
+            )))?;
+        let non_signer_operator_ids: HashSet<[u8; 32]> =
+            get_non_signer_operator_ids(&attestation_tx.input)
+                .map_err(|e| {
+                    SubmitError::EthError(format!("Error while decoding attestation: {:?}", e))
+                })?
+                .into_iter()
+                .collect();
+
+        for (i, operator) in operators.iter().enumerate() {
+            if !registered_operator_ids.contains(&operator.operator_id) {
+                continue;
+            }
+            eligible_batches[i] += 1;
+            if !non_signer_operator_ids.contains(&operator.operator_id) {
+                signed_batches[i] += 1;
+            }
+        }
+    }
+
+    let total_stake = operators
+        .iter()
+        .fold(U256::zero(), |total, operator| total + operator.stake);
+
+    let operators = operators
+        .into_iter()
+        .zip(eligible_batches.into_iter().zip(signed_batches))
+        .map(
+            |(operator, (eligible_batches, signed_batches))| OperatorStatus {
+                address: operator.address,
+                stake: operator.stake,
+                eligible_batches,
+                signed_batches,
+            },
+        )
+        .collect();
+
+    Ok(OperatorStatusReport {
+        total_stake,
+        verified_batches: batch_verified_events.len(),
+        operators,
+    })
+}
@@ -0,0 +1,111 @@
+//! Repetitions of `submit --concurrency`, each submitted in its own connection to the batcher,
+//! with at most `concurrency` of them in flight at once, to load test the batcher. Each
+//! repetition is identified by its position in the logs and the results, and the throughput and
+//! the failures of all of them are reported at the end.
+
+use std::time::{Duration, Instant};
+
+use aligned_sdk::client::AlignedClient;
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::sdk::TimedSubmission;
+use aligned_sdk::types::VerificationData;
+use ethers::signers::Signer;
+use futures_util::stream::{self, StreamExt};
+use log::{error, info};
+
+/// Result of a repetition.
+pub struct Repetition {
+    /// Position of the repetition, from 0.
+    pub position: usize,
+    /// Time from the start of the repetition to its last response or its failure.
+    pub elapsed: Duration,
+    pub result: Result<TimedSubmission, SubmitError>,
+}
+
+impl Repetition {
+    /// Returns the number of proofs of the repetition included in a batch.
+    pub fn included_proofs(&self) -> usize {
+        match &self.result {
+            Ok(submission) => submission
+                .aligned_verification_data
+                .as_ref()
+                .map_or(0, Vec::len),
+            Err(_) => 0,
+        }
+    }
+}
+
+/// Throughput and failures of all the repetitions.
+pub struct RepetitionsReport {
+    pub repetitions: usize,
+    pub failed: usize,
+    pub included_proofs: usize,
+    /// Time from the start of the first repetition to the end of the last one.
+    pub elapsed: Duration,
+}
+
+impl RepetitionsReport {
+    /// Returns the proofs included per second over all the repetitions.
+    pub fn proofs_per_second(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed == 0.0 {
+            return 0.0;
+        }
+        self.included_proofs as f64 / elapsed
+    }
+}
+
+/// Submits `verification_data` `repetitions` times with `client`, each time in a new connection,
+/// with at most `concurrency` submissions at once.
+/// # Returns
+/// * The result of each repetition, by position, and the report of all of them.
+pub async fn submit_repetitions<S: Signer>(
+    client: &AlignedClient<S>,
+    verification_data: &[VerificationData],
+    repetitions: usize,
+    concurrency: usize,
+) -> (Vec<Repetition>, RepetitionsReport) {
+    let started_at = Instant::now();
+    let mut results: Vec<Repetition> = stream::iter(0..repetitions)
+        .map(|position| async move {
+            let repetition_started_at = Instant::now();
+            let result = client.submit_multiple_with_timing(verification_data).await;
+            let repetition = Repetition {
+                position,
+                elapsed: repetition_started_at.elapsed(),
+                result,
+            };
+            log_repetition(&repetition);
+            repetition
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|repetition| repetition.position);
+
+    let report = RepetitionsReport {
+        repetitions,
+        failed: results
+            .iter()
+            .filter(|repetition| repetition.result.is_err())
+            .count(),
+        included_proofs: results.iter().map(Repetition::included_proofs).sum(),
+        elapsed: started_at.elapsed(),
+    };
+    (results, report)
+}
+
+fn log_repetition(repetition: &Repetition) {
+    match &repetition.result {
+        Ok(_) => info!(
+            "Repetition {}: {} proofs included in {:.2?}",
+            repetition.position,
+            repetition.included_proofs(),
+            repetition.elapsed
+        ),
+        Err(e) => error!(
+            "Repetition {} failed after {:.2?}: {:?}",
+            repetition.position, repetition.elapsed, e
+        ),
+    }
+}
@@ -0,0 +1,131 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethers::abi::AbiDecode;
+use ethers::prelude::*;
+use ethers::utils::hex;
+use log::info;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::eth::{
+    AlignedLayerServiceManagerContract, BatchVerifiedFilter, NewBatchFilter, RespondToTaskCall,
+};
+
+use crate::fork::spawn_fork;
+
+/// Gas figures obtained when replaying the aggregator response of a batch.
+pub struct ReplayReport {
+    pub task_created_block: u64,
+    pub response_block: u64,
+    pub original_gas_used: Option<U256>,
+    pub replayed_gas_used: Option<U256>,
+}
+
+/// Replays the `respondToTask` transaction sent by the aggregator for the given batch on a local
+/// anvil fork of the network, taken at the block preceding the original response.
+/// The original calldata is decoded and re-encoded, so that a failure to reconstruct the
+/// aggregator's data is reported instead of blindly replaying raw bytes.
+/// Requires `anvil` to be installed and available in the `PATH`.
+pub async fn replay_batch(
+    fork_url: &str,
+    contract_address: &str,
+    batch_merkle_root: [u8; 32],
+    from_block: u64,
+) -> Result<ReplayReport, SubmitError> {
+    let provider = Provider::<Http>::try_from(fork_url)
+        .map_err(|e| SubmitError::EthError(format!("Error while connecting to Ethereum: {}", e)))?;
+
+    let contract_address = Address::from_str(contract_address)
+        .map_err(|e| SubmitError::InvalidAddress(contract_address.to_string(), e.to_string()))?;
+
+    let service_manager =
+        AlignedLayerServiceManagerContract::new(contract_address, Arc::new(provider.clone()));
+
+    let merkle_root_topic = H256::from(batch_merkle_root);
+
+    let new_batch_events = service_manager
+        .event::<NewBatchFilter>()
+        .topic1(merkle_root_topic)
+        .from_block(from_block)
+        .query()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while querying NewBatch: {}", e)))?;
+
+    let new_batch = new_batch_events
+        .first()
+        .ok_or(SubmitError::GenericError(format!(
+            "No batch with merkle root 0x{} was found",
+            hex::encode(batch_merkle_root)
+        )))?;
+
+    let batch_verified_events = service_manager
+        .event::<BatchVerifiedFilter>()
+        .topic1(merkle_root_topic)
+        .from_block(new_batch.task_created_block as u64)
+        .query_with_meta()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while querying BatchVerified: {}", e)))?;
+
+    let (_, response_meta) = batch_verified_events
+        .first()
+        .ok_or(SubmitError::GenericError(
+            "Batch was not responded by the aggregator, there is nothing to replay".to_string(),
+        ))?;
+
+    let response_tx = provider
+        .get_transaction(response_meta.transaction_hash)
+        .await?
+        .ok_or(SubmitError::EthError(
+            "Aggregator response transaction not found".to_string(),
+        ))?;
+
+    let original_receipt = provider
+        .get_transaction_receipt(response_meta.transaction_hash)
+        .await?;
+
+    let respond_to_task = RespondToTaskCall::decode(&response_tx.input).map_err(|e| {
+        SubmitError::GenericError(format!("Could not decode respondToTask call: {}", e))
+    })?;
+
+    let response_block = response_meta.block_number.as_u64();
+    let aggregator_address = response_tx.from;
+    let fork_block = response_block
+        .checked_sub(1)
+        .ok_or(SubmitError::GenericError(
+            "The aggregator response is in the genesis block, there is no block to fork before it"
+                .to_string(),
+        ))?;
+
+    info!(
+        "Forking network at block {} to replay the aggregator response...",
+        fork_block
+    );
+    let (_anvil, fork_provider) = spawn_fork(fork_url, Some(fork_block))?;
+
+    fork_provider
+        .request::<_, ()>("anvil_impersonateAccount", [aggregator_address])
+        .await?;
+
+    let fork_service_manager =
+        AlignedLayerServiceManagerContract::new(contract_address, Arc::new(fork_provider));
+
+    let call = fork_service_manager
+        .respond_to_task(
+            respond_to_task.batch_merkle_root,
+            respond_to_task.non_signer_stakes_and_signature,
+        )
+        .from(aggregator_address);
+
+    let replayed_receipt = call
+        .send()
+        .await
+        .map_err(|e| SubmitError::EthError(format!("Error while replaying respondToTask: {}", e)))?
+        .await?;
+
+    Ok(ReplayReport {
+        task_created_block: new_batch.task_created_block as u64,
+        response_block,
+        original_gas_used: original_receipt.and_then(|receipt| receipt.gas_used),
+        replayed_gas_used: replayed_receipt.and_then(|receipt| receipt.gas_used),
+    })
+}
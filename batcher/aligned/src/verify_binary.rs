@@ -0,0 +1,150 @@
+//! Verification that the running binary is the one of a release of Aligned, for users who pipe
+//! their private keys into it.
+//!
+//! Each release publishes a manifest next to its binaries, listing the version, the commit it was
+//! built from and the SHA-256 of each binary, by the name of its file in the release:
+//!
+//! ```json
+//! { "version": "0.1.0", "git_hash": "3f2a1bc", "binaries": { "aligned-x86": "9f86d0…" } }
+//! ```
+//!
+//! As the documents of `aligned_sdk::discovery`, the manifest is signed as an Ethereum message
+//! (EIP-191), by the release workflow with the `RELEASE_SIGNING_KEY` secret, and its signature is
+//! served at the URL of the manifest followed by `.sig`. The address of the key has to be given
+//! with `--release_signer`, as it's only trusted if it's published by Aligned apart from the
+//! release. Releases are built reproducibly with `make build_aligned_release`, so anyone can
+//! rebuild a release and compare the hashes of its manifest.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use aligned_sdk::build_info::GIT_HASH;
+use aligned_sdk::errors::SubmitError;
+use ethers::types::{Address, Signature};
+use ethers::utils::hex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+pub const CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const SIGNATURE_URL_SUFFIX: &str = ".sig";
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub git_hash: String,
+    /// SHA-256 of each binary of the release in hex, by the name of its file.
+    pub binaries: HashMap<String, String>,
+}
+
+/// Returns the URL of the manifest of the release of this version of the CLI.
+pub fn default_manifest_url() -> String {
+    format!(
+        "https://github.com/yetanotherco/aligned_layer/releases/download/v{}/aligned-manifest.json",
+        CLI_VERSION
+    )
+}
+
+/// Returns the name of the file of the release binary for this architecture, as downloaded by
+/// `install_aligned.sh`.
+pub fn default_asset() -> Option<&'static str> {
+    match std::env::consts::ARCH {
+        "x86_64" => Some("aligned-x86"),
+        "aarch64" => Some("aligned-arm64"),
+        _ => None,
+    }
+}
+
+/// Downloads the manifest at `manifest_url` and its signature, and checks that the binary at
+/// `binary_path` is the `asset` of the release of this version of the CLI.
+/// # Errors
+/// * `GenericError` if the manifest or its signature can't be downloaded, or the manifest is not
+///   served over HTTPS.
+/// * `IoError` if the binary can't be read.
+/// * The errors of `verify_release`.
+pub async fn verify_binary(
+    manifest_url: &str,
+    signer: Address,
+    binary_path: &Path,
+    asset: &str,
+) -> Result<ReleaseManifest, SubmitError> {
+    check_manifest_url(manifest_url)?;
+    let manifest = download(manifest_url).await?;
+    let signature = download(&format!("{}{}", manifest_url, SIGNATURE_URL_SUFFIX)).await?;
+    let binary =
+        std::fs::read(binary_path).map_err(|e| SubmitError::IoError(binary_path.into(), e))?;
+
+    verify_release(&manifest, &signature, signer, &binary, asset)
+}
+
+/// Checks that the manifest was signed by `signer`, is the one of the release of this version of
+/// the CLI, built from the same commit, and that `binary` hashes to its `asset`.
+/// # Errors
+/// * `GenericError` if the signature is not valid or was made by another key, the manifest is not
+///   valid or is the one of another release, or lists another hash for the binary.
+pub fn verify_release(
+    manifest: &str,
+    signature: &str,
+    signer: Address,
+    binary: &[u8],
+    asset: &str,
+) -> Result<ReleaseManifest, SubmitError> {
+    let signature = Signature::from_str(signature.trim()).map_err(|e| {
+        SubmitError::GenericError(format!("Invalid signature of the release manifest: {}", e))
+    })?;
+    signature.verify(manifest, signer).map_err(|_| {
+        SubmitError::GenericError(format!(
+            "The release manifest was not signed by {:?}, it may have been tampered with",
+            signer
+        ))
+    })?;
+
+    let release: ReleaseManifest = serde_json::from_str(manifest)
+        .map_err(|e| SubmitError::GenericError(format!("Invalid release manifest: {}", e)))?;
+    if release.version != CLI_VERSION || release.git_hash != GIT_HASH {
+        return Err(SubmitError::GenericError(format!(
+            "The manifest is the one of release {} (git {}), but this binary is {} (git {})",
+            release.version, release.git_hash, CLI_VERSION, GIT_HASH
+        )));
+    }
+
+    let expected_hash = release
+        .binaries
+        .get(asset)
+        .ok_or_else(|| SubmitError::GenericError(format!("The release has no binary {}", asset)))?;
+    let hash = hex::encode(Sha256::digest(binary));
+    if !expected_hash.eq_ignore_ascii_case(&hash) {
+        return Err(SubmitError::GenericError(format!(
+            "The SHA-256 of this binary is {}, but the one of {} in release {} is {}",
+            hash, asset, release.version, expected_hash
+        )));
+    }
+    Ok(release)
+}
+
+/// Only accepts manifests served over HTTPS, or from the loopback interface, such as a local
+/// mirror of the releases.
+fn check_manifest_url(manifest_url: &str) -> Result<(), SubmitError> {
+    let url = url::Url::parse(manifest_url).map_err(|e| {
+        SubmitError::GenericError(format!("Invalid manifest URL {}: {}", manifest_url, e))
+    })?;
+    let loopback = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    if url.scheme() != "https" && !(url.scheme() == "http" && loopback) {
+        return Err(SubmitError::GenericError(format!(
+            "The release manifest must be downloaded over HTTPS, not from {}",
+            manifest_url
+        )));
+    }
+    Ok(())
+}
+
+async fn download(url: &str) -> Result<String, SubmitError> {
+    reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| SubmitError::GenericError(format!("Error while downloading {}: {}", url, e)))?
+        .text()
+        .await
+        .map_err(|e| SubmitError::GenericError(format!("Error while downloading {}: {}", url, e)))
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use ethers::prelude::*;
+use zeroize::Zeroizing;
+
+use aligned_sdk::errors::SubmitError;
+#[cfg(feature = "kms")]
+use aligned_sdk::signers::kms::AwsSigner;
+
+/// Decrypts the keystore at `keystore_path` with the password, prompting the user for it if it's
+/// not given. The password is zeroized once the keystore is decrypted.
+pub fn decrypt_keystore(
+    keystore_path: &Path,
+    password: Option<Zeroizing<String>>,
+) -> Result<LocalWallet, SubmitError> {
+    let password = match password {
+        Some(password) => password,
+        None => Zeroizing::new(
+            rpassword::prompt_password("Please enter your keystore password:")
+                .map_err(|e| SubmitError::GenericError(e.to_string()))?,
+        ),
+    };
+    Wallet::decrypt_keystore(keystore_path, password.as_str()).map_err(|e| {
+        SubmitError::GenericError(format!(
+            "Error while decrypting keystore {}: {}",
+            keystore_path.display(),
+            e
+        ))
+    })
+}
+
+/// Builds a wallet from a private key in hex, with or without a `0x` prefix. The key is
+/// zeroized once parsed, and the wallet zeroizes its own copy when dropped.
+pub fn wallet_from_private_key(private_key: Zeroizing<String>) -> Result<LocalWallet, SubmitError> {
+    private_key
+        .trim()
+        .parse::<LocalWallet>()
+        .map_err(|e| SubmitError::GenericError(format!("Invalid private key: {}", e)))
+}
+
+/// Builds a signer for the AWS KMS key, which signs each proof with a request to KMS, so no key
+/// is stored on disk. See `aligned_sdk::signers::kms`.
+#[cfg(feature = "kms")]
+pub async fn kms_signer(key_id: &str, region: &str) -> Result<AwsSigner, SubmitError> {
+    aligned_sdk::signers::kms::kms_signer(key_id, region).await
+}
+
+/// Fails, since KMS keys can only be used by CLIs built with the `kms` feature.
+#[cfg(not(feature = "kms"))]
+pub async fn kms_signer(_key_id: &str, _region: &str) -> Result<LocalWallet, SubmitError> {
+    Err(SubmitError::SignerError(
+        "This CLI was built without AWS KMS support, rebuild it with the kms feature".to_string(),
+    ))
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{aligned_cmd, assert_json_success, test_dir, test_file};
+
+fn pack_groth16(bundle: &Path) -> Output {
+    aligned_cmd(&["pack", "--proving_system", "Groth16Bn254"])
+        .arg("--proof")
+        .arg(test_file("groth16_bn254/plonk.proof"))
+        .arg("--public_input")
+        .arg(test_file("groth16_bn254/plonk_pub_input.pub"))
+        .arg("--vk")
+        .arg(test_file("groth16_bn254/plonk.vk"))
+        .args(["--proof_generator_addr", &format!("0x{}", "07".repeat(20))])
+        .arg("--output")
+        .arg(bundle)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_unpacked_bundles_hold_the_packed_files() {
+    let dir = test_dir("bundle", "unpack");
+    let bundle = dir.join("plonk.aligned");
+
+    let packed = assert_json_success(&pack_groth16(&bundle));
+    assert_eq!(packed["proving_system"], "Groth16Bn254");
+
+    let output = aligned_cmd(&["unpack"])
+        .arg("--bundle")
+        .arg(&bundle)
+        .arg("--output")
+        .arg(dir.join("unpacked"))
+        .output()
+        .unwrap();
+    let unpacked = assert_json_success(&output);
+
+    assert_eq!(unpacked["proving_system"], "Groth16Bn254");
+    assert_eq!(
+        unpacked["proof_generator_addr"],
+        format!("0x{}", "07".repeat(20))
+    );
+    for (flag, file) in [
+        ("proof", "plonk.proof"),
+        ("public_input", "plonk_pub_input.pub"),
+        ("vk", "plonk.vk"),
+    ] {
+        let path = unpacked["files"][flag].as_str().unwrap();
+        assert_eq!(
+            fs::read(path).unwrap(),
+            fs::read(test_file(&format!("groth16_bn254/{}", file))).unwrap()
+        );
+    }
+    assert_eq!(unpacked["files"].as_object().unwrap().len(), 3);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_bundles_are_submitted_instead_of_the_proof_files() {
+    let dir = test_dir("bundle", "submit");
+    let bundle = dir.join("plonk.aligned");
+    assert_json_success(&pack_groth16(&bundle));
+
+    // The batcher and RPC node can't be reached, so the submission fails once the bundle is read
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("submit")
+        .arg("--bundle")
+        .arg(&bundle)
+        .args(["--conn", "ws://127.0.0.1:1", "--rpc", "http://127.0.0.1:1"])
+        .arg("--aligned_verification_data_path")
+        .arg(dir.join("aligned_verification_data"))
+        .output()
+        .unwrap();
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("Read a Groth16Bn254 proof from"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(logs.contains("Submitting proofs to the Aligned batcher"));
+
+    // The proof is either in the bundle or in the flags
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("submit")
+        .arg("--bundle")
+        .arg(&bundle)
+        .args(["--proof", "a.proof"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_files_that_are_not_bundles_are_rejected() {
+    let dir = test_dir("bundle", "invalid");
+    let output = aligned_cmd(&["unpack"])
+        .arg("--bundle")
+        .arg(test_file("groth16_bn254/plonk.proof"))
+        .arg("--output")
+        .arg(dir.join("unpacked"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Not a proof bundle"));
+    assert!(!dir.join("unpacked").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use ethers::utils::hex;
+use serde_json::Value;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use common::test_dir;
+
+fn keccak(content: &[u8]) -> String {
+    format!("0x{}", hex::encode(Keccak256::digest(content)))
+}
+
+/// Returns a test directory with files to compute the commitments of.
+fn files_dir(name: &str) -> PathBuf {
+    let dir = test_dir("commitments", name);
+    fs::create_dir_all(dir.join("circuits/nested")).unwrap();
+    fs::write(dir.join("circuits/b.vk"), b"b").unwrap();
+    fs::write(dir.join("circuits/a.vk"), b"aligned").unwrap();
+    fs::write(dir.join("circuits/nested/c.vk"), b"c").unwrap();
+    fs::write(dir.join("program, v2.elf"), b"elf").unwrap();
+    dir
+}
+
+fn get_commitment_cmd(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("get-commitment")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn single_commitment(path: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("aligned_commitment_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let output_file = dir.join("commitment");
+    get_commitment_cmd(&["--input", path, "--output", output_file.to_str().unwrap()]);
+    format!("0x{}", fs::read_to_string(output_file).unwrap())
+}
+
+#[test]
+fn test_commitments_of_many_files_are_written_as_csv() {
+    let dir = files_dir("csv");
+    let circuits = dir.join("circuits");
+    let program = dir.join("program, v2.elf");
+
+    let output = get_commitment_cmd(&[
+        "--input",
+        circuits.to_str().unwrap(),
+        program.to_str().unwrap(),
+    ]);
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+
+    // The files of the directory are sorted, and its subdirectories are not read
+    let a = circuits.join("a.vk");
+    let b = circuits.join("b.vk");
+    assert_eq!(
+        lines,
+        [
+            "file,commitment".to_string(),
+            format!("{},{}", a.display(), single_commitment(a.to_str().unwrap())),
+            format!("{},{}", b.display(), single_commitment(b.to_str().unwrap())),
+            format!(
+                "\"{}\",{}",
+                program.display(),
+                single_commitment(program.to_str().unwrap())
+            ),
+        ]
+    );
+    assert_eq!(single_commitment(a.to_str().unwrap()), keccak(b"aligned"));
+}
+
+#[test]
+fn test_commitments_of_many_files_are_written_as_json() {
+    let dir = files_dir("json");
+    let circuits = dir.join("circuits");
+    let mapping = dir.join("commitments.json");
+
+    get_commitment_cmd(&[
+        "--input",
+        circuits.to_str().unwrap(),
+        "--format",
+        "json",
+        "--output",
+        mapping.to_str().unwrap(),
+    ]);
+    let json: Value = serde_json::from_slice(&fs::read(&mapping).unwrap()).unwrap();
+    let a = circuits.join("a.vk");
+    assert_eq!(json.as_object().unwrap().len(), 2);
+    assert_eq!(json[a.to_str().unwrap()], keccak(b"aligned"));
+
+    // With `--output json`, the standard output only holds the results
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(["--output", "json", "get-commitment", "--input"])
+        .arg(&circuits)
+        .output()
+        .unwrap();
+    let results: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(results["commitments"], json);
+}
+
+#[test]
+fn test_commitments_with_another_hash_function() {
+    let dir = files_dir("hash_function");
+    let a = dir.join("circuits/a.vk");
+
+    let output = get_commitment_cmd(&[
+        "--input",
+        a.to_str().unwrap(),
+        "--hash_function",
+        "sha256",
+        "--output",
+        dir.join("commitment").to_str().unwrap(),
+    ]);
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        fs::read_to_string(dir.join("commitment")).unwrap(),
+        hex::encode(Sha256::digest(b"aligned"))
+    );
+
+    let output = get_commitment_cmd(&[
+        "--input",
+        dir.join("circuits").to_str().unwrap(),
+        "--hash_function",
+        "blake3",
+    ]);
+    let csv = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        csv.lines().nth(1).unwrap(),
+        format!(
+            "{},0x21835d288d8923d6e80d36de3c42f2d044df417effaec32dea196b621843a6ec",
+            a.display()
+        )
+    );
+}
@@ -0,0 +1,51 @@
+//! Helpers shared by the tests of the CLI. Each test binary only uses some of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+/// Returns an empty temporary directory for the test `name` of the file `prefix`, removing what a
+/// previous run left in it.
+pub fn test_dir(prefix: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "aligned_{}_{}_{}",
+        prefix,
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns the path of a file of the test files of the SDK.
+pub fn test_file(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../aligned-sdk/test_files")
+        .join(path)
+}
+
+/// Returns the command running the CLI with `--output json` and the given arguments.
+pub fn aligned_cmd(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_aligned"));
+    command.args(["--output", "json"]).args(args);
+    command
+}
+
+/// Checks that the command succeeded, printing its logs otherwise.
+pub fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Checks that the command succeeded, and returns its standard output as JSON.
+pub fn assert_json_success(output: &Output) -> Value {
+    assert_success(output);
+    serde_json::from_slice(&output.stdout).unwrap()
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::test_dir;
+
+const ADDRESS: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+/// Runs a faucet request, which on devnet funds the address from the RPC node, and otherwise
+/// needs a faucet URL.
+fn faucet_request_cmd(args: &[&str], config_home: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("faucet")
+        .arg("request")
+        .arg("--address")
+        .arg(ADDRESS)
+        .args(args)
+        .env("XDG_CONFIG_HOME", config_home)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_config_file_sets_the_defaults_of_the_flags() {
+    let dir = test_dir("config", "flags");
+    let config = dir.join("aligned.toml");
+    fs::write(
+        &config,
+        "chain = \"holesky\"\nrpc_url = \"http://127.0.0.1:1\"\n",
+    )
+    .unwrap();
+    let config = config.to_str().unwrap();
+
+    let output = faucet_request_cmd(&["--config", config], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("no built-in faucet for this chain"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    // Flags override the config file, which still sets the RPC node
+    let output = faucet_request_cmd(&["--config", config, "--chain", "devnet"], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("from the devnet account") && logs.contains("Error while"),
+        "unexpected logs: {}",
+        logs
+    );
+}
+
+#[test]
+fn test_config_file_of_the_user_is_discovered() {
+    let dir = test_dir("config", "discovery");
+    fs::create_dir_all(dir.join("aligned")).unwrap();
+    fs::write(dir.join("aligned/config.toml"), "chain = \"holesky\"\n").unwrap();
+
+    let output = faucet_request_cmd(&[], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("no built-in faucet for this chain"),
+        "unexpected logs: {}",
+        logs
+    );
+}
+
+#[test]
+fn test_invalid_config_files_are_rejected() {
+    let dir = test_dir("config", "invalid");
+    let config = dir.join("aligned.toml");
+    fs::write(&config, "batcher = \"ws://localhost:8080\"\n").unwrap();
+
+    let output = faucet_request_cmd(&["--config", config.to_str().unwrap()], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Invalid config file") && logs.contains("batcher"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    let output = faucet_request_cmd(&["--config", "missing.toml"], &dir);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.toml"));
+}
+
+#[test]
+fn test_config_file_sets_the_endpoints_of_a_custom_network() {
+    let dir = test_dir("config", "network");
+    let config = dir.join("aligned.toml");
+    let network = "[network]\n\
+        batcher_url = \"ws://127.0.0.1:1\"\n\
+        eth_rpc_url = \"http://127.0.0.1:1\"\n\
+        aligned_service_manager = \"0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8\"\n\
+        batcher_payment_service = \"0x7969c5eD335650692Bc04293B07F5BF2e7A673C0\"\n";
+    fs::write(&config, network).unwrap();
+
+    let output = faucet_request_cmd(&["--config", config.to_str().unwrap()], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(logs.contains("127.0.0.1:1"), "unexpected logs: {}", logs);
+
+    // The keys of the endpoints override the network
+    fs::write(
+        &config,
+        format!("rpc_url = \"http://127.0.0.1:2\"\n{}", network),
+    )
+    .unwrap();
+    let output = faucet_request_cmd(&["--config", config.to_str().unwrap()], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(logs.contains("127.0.0.1:2"), "unexpected logs: {}", logs);
+
+    // Every endpoint of the network is needed
+    fs::write(&config, "[network]\nbatcher_url = \"ws://127.0.0.1:1\"\n").unwrap();
+    let output = faucet_request_cmd(&["--config", config.to_str().unwrap()], &dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Invalid config file") && logs.contains("eth_rpc_url"),
+        "unexpected logs: {}",
+        logs
+    );
+}
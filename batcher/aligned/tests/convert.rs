@@ -0,0 +1,52 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use common::{assert_success, test_dir, test_file};
+
+fn file_of_previous_release() -> PathBuf {
+    test_file("aligned_verification_data/unversioned_batch.json")
+}
+
+fn convert_cmd(input: &Path, output: &Path, format: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_aligned"));
+    command
+        .arg("convert")
+        .arg("--input")
+        .arg(input)
+        .arg("--output")
+        .arg(output);
+    if let Some(format) = format {
+        command.arg("--format").arg(format);
+    }
+    command.output().unwrap()
+}
+
+#[test]
+fn test_convert_to_binary_and_back() {
+    let dir = test_dir("convert", "round_trip");
+    let input = file_of_previous_release();
+    let binary = dir.join("inclusion_data.bin");
+    let json = dir.join("inclusion_data");
+
+    assert_success(&convert_cmd(&input, &binary, None));
+    assert!(fs::read(&binary).unwrap().starts_with(b"ALVD"));
+
+    assert_success(&convert_cmd(&binary, &json, Some("json")));
+    let converted: serde_json::Value = serde_json::from_slice(&fs::read(&json).unwrap()).unwrap();
+    let original: serde_json::Value = serde_json::from_slice(&fs::read(&input).unwrap()).unwrap();
+    assert_eq!(converted, original);
+}
+
+#[test]
+fn test_convert_unknown_extension_requires_format() {
+    let dir = test_dir("convert", "unknown_extension");
+    let output = dir.join("inclusion_data.cbor");
+
+    let result = convert_cmd(&file_of_previous_release(), &output, None);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("--format"));
+    assert!(!output.exists());
+}
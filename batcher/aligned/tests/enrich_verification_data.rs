@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+fn aligned_verification_data_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../aligned-sdk/test_files/aligned_verification_data/unversioned_batch.json")
+}
+
+fn enrich_cmd(file: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(["--output", "json", "enrich-verification-data", "--rewrite"])
+        .arg("--aligned_verification_data")
+        .arg(file)
+        .args(["--rpc", "http://127.0.0.1:1"])
+        .output()
+        .unwrap()
+}
+
+fn copy_into_temp_dir(name: &str, contents: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aligned-enrich-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("aligned_verification_data.json");
+    std::fs::write(&file, contents).unwrap();
+    file
+}
+
+#[test]
+fn test_file_is_unchanged_when_the_node_cant_be_reached() {
+    let contents = std::fs::read(aligned_verification_data_file()).unwrap();
+    let file = copy_into_temp_dir("unreachable", &contents);
+
+    let output = enrich_cmd(&file);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read(&file).unwrap(), contents);
+
+    std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_verified_batch_metadata_is_printed_without_the_node() {
+    let mut json: Value =
+        serde_json::from_slice(&std::fs::read(aligned_verification_data_file()).unwrap()).unwrap();
+    let batch_metadata = serde_json::json!({
+        "created": {
+            "block_number": 1572531,
+            "tx_hash": format!("0x{}", "01".repeat(32)),
+        },
+        "verified": {
+            "block_number": 1572533,
+            "tx_hash": format!("0x{}", "02".repeat(32)),
+        },
+    });
+    json["batch_metadata"] = batch_metadata.clone();
+    let file = copy_into_temp_dir("verified", &serde_json::to_vec(&json).unwrap());
+
+    let output = enrich_cmd(&file);
+    assert!(
+        output.status.success(),
+        "command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let printed: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(printed["batch_metadata"], batch_metadata);
+    assert_eq!(printed["index_in_batch"], json["index_in_batch"]);
+
+    std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
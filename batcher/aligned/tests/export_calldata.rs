@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use ethers::utils::hex;
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+
+fn aligned_verification_data_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../aligned-sdk/test_files/aligned_verification_data/unversioned_batch.json")
+}
+
+fn export_calldata_cmd(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(args)
+        .arg("export-calldata")
+        .arg("--aligned_verification_data")
+        .arg(aligned_verification_data_file())
+        .args([
+            "--contract_address",
+            "0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[test]
+fn test_calldata_calls_verify_batch_inclusion() {
+    let output = export_calldata_cmd(&["--output", "json"]);
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    let selector = &Keccak256::digest(
+        b"verifyBatchInclusion(bytes32,bytes32,bytes32,bytes20,bytes32,bytes,uint256)",
+    )[..4];
+    let calldata = json["calldata"].as_str().unwrap();
+    let arguments = json["arguments"].as_str().unwrap();
+    assert_eq!(
+        calldata,
+        format!("0x{}{}", hex::encode(selector), &arguments[2..])
+    );
+
+    // The batch merkle root of the file is the fifth argument, and the index the seventh
+    let file: Value =
+        serde_json::from_slice(&std::fs::read(aligned_verification_data_file()).unwrap()).unwrap();
+    let batch_merkle_root: Vec<u8> = file["batch_merkle_root"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|byte| byte.as_u64().unwrap() as u8)
+        .collect();
+    let words: Vec<&str> = (0..7)
+        .map(|i| &arguments[2 + 64 * i..2 + 64 * (i + 1)])
+        .collect();
+    assert_eq!(words[4], hex::encode(batch_merkle_root));
+    assert_eq!(
+        u64::from_str_radix(words[6], 16).unwrap(),
+        file["index_in_batch"].as_u64().unwrap()
+    );
+
+    let cast_command = json["cast_command"].as_str().unwrap();
+    assert!(cast_command.starts_with(
+        "cast call 0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8 \"verifyBatchInclusion(bytes32,bytes32,bytes32,bytes20,bytes32,bytes,uint256)(bool)\" 0x"
+    ));
+    assert!(cast_command.ends_with(&format!(
+        " {} --rpc-url http://localhost:8545",
+        file["index_in_batch"]
+    )));
+}
+
+#[test]
+fn test_calldata_and_cast_command_are_printed() {
+    let output = export_calldata_cmd(&[]);
+    let json_output = export_calldata_cmd(&["--output", "json"]);
+    let json: Value = serde_json::from_slice(&json_output.stdout).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            json["calldata"].as_str().unwrap(),
+            json["cast_command"].as_str().unwrap()
+        ]
+    );
+}
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use ethers::utils::hex;
+use serde_json::Value;
+
+fn aligned_verification_data_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../aligned-sdk/test_files/aligned_verification_data/unversioned_batch.json")
+}
+
+fn export_foundry_test_cmd(global_args: &[&str], args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(global_args)
+        .arg("export-foundry-test")
+        .arg("--aligned_verification_data")
+        .arg(aligned_verification_data_file())
+        .args([
+            "--contract_address",
+            "0x1613beb3b2c4f22ee086b2b38c1476a3ce7f78e8",
+            "--rpc",
+            "https://ethereum-holesky-rpc.publicnode.com",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "command failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[test]
+fn test_foundry_test_calls_verify_batch_inclusion_on_a_fork() {
+    let output = export_foundry_test_cmd(&[], &[]);
+    let source = String::from_utf8(output.stdout).unwrap();
+
+    // Solidity only takes checksummed address literals
+    assert!(source.contains("IAlignedServiceManager(0x1613beB3B2C4f22Ee086B2b38C1476A3cE7f78E8)"));
+    assert!(source.contains("vm.createSelectFork(\"https://ethereum-holesky-rpc.publicnode.com\")"));
+    assert!(source.contains("contract AlignedInclusionTest is Test"));
+
+    let file: Value =
+        serde_json::from_slice(&std::fs::read(aligned_verification_data_file()).unwrap()).unwrap();
+    let batch_merkle_root: Vec<u8> = file["batch_merkle_root"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|byte| byte.as_u64().unwrap() as u8)
+        .collect();
+    assert!(source.contains(&format!("hex\"{}\",", hex::encode(batch_merkle_root))));
+    assert!(source.contains(&format!(
+        "\n            {}\n        );",
+        file["index_in_batch"]
+    )));
+}
+
+#[test]
+fn test_foundry_test_is_written_into_the_output_file() {
+    let dir = std::env::temp_dir().join(format!("aligned-foundry-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output_file = dir.join("AlignedInclusion.t.sol");
+
+    let output = export_foundry_test_cmd(
+        &["--output", "json"],
+        &["--output", output_file.to_str().unwrap()],
+    );
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(json["contract_name"], "AlignedInclusionTest");
+    assert_eq!(
+        json["source"].as_str().unwrap(),
+        std::fs::read_to_string(&output_file).unwrap()
+    );
+    // Text output is the same source
+    assert_eq!(
+        String::from_utf8(export_foundry_test_cmd(&[], &[]).stdout).unwrap(),
+        json["source"].as_str().unwrap()
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,72 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Output};
+use std::thread;
+
+const ADDRESS: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+/// Starts a faucet answering a single request with the given status and body. Returns its URL
+/// and a handle resolving to the body of the request received.
+fn start_faucet(status: &'static str, body: &'static str) -> (String, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/request", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        // Reads until the end of the JSON body, which is a single object
+        while !request.ends_with(b"}") {
+            let read = stream.read(&mut buffer).unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+
+        let request = String::from_utf8(request).unwrap();
+        request[request.find("\r\n\r\n").unwrap() + 4..].to_string()
+    });
+    (url, handle)
+}
+
+fn faucet_request_cmd(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("faucet")
+        .arg("request")
+        .arg("--address")
+        .arg(ADDRESS)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_faucet_request_posts_the_address() {
+    let (url, faucet) = start_faucet("200 OK", r#"{"tx_hash":"0xabcd"}"#);
+
+    let output = faucet_request_cmd(&["--faucet_url", &url, "--chain", "holesky"]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "faucet request failed: {}", logs);
+    assert!(logs.contains("0xabcd"));
+
+    let request: serde_json::Value = serde_json::from_str(&faucet.join().unwrap()).unwrap();
+    assert_eq!(request["address"], ADDRESS);
+}
+
+#[test]
+fn test_faucet_request_fails_when_the_faucet_refuses() {
+    let (url, faucet) = start_faucet("429 Too Many Requests", r#"{"error":"rate limited"}"#);
+
+    let output = faucet_request_cmd(&["--faucet_url", &url]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rate limited"));
+    faucet.join().unwrap();
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use common::{test_dir, test_file};
+
+/// Submits to a batcher and an RPC node that can't be reached, so that the submission fails
+/// once the manifest was read.
+fn submit_manifest_cmd(manifest: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("submit")
+        .arg("--manifest")
+        .arg(manifest)
+        .arg("--conn")
+        .arg("ws://127.0.0.1:1")
+        .arg("--rpc")
+        .arg("http://127.0.0.1:1")
+        .arg("--aligned_verification_data_path")
+        .arg(manifest.parent().unwrap().join("aligned_verification_data"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Writes a manifest with an SP1 proof, by absolute paths, and a Groth16 one, by paths relative
+/// to the manifest.
+fn write_manifest(dir: &Path) -> PathBuf {
+    for file in ["plonk.proof", "plonk.vk", "plonk_pub_input.pub"] {
+        fs::copy(
+            test_file(&format!("groth16_bn254/{}", file)),
+            dir.join(file),
+        )
+        .unwrap();
+    }
+    let manifest = serde_json::json!({
+        "proofs": [
+            {
+                "proof": test_file("sp1/sp1_fibonacci.proof"),
+                "vm_program": test_file("sp1/sp1_fibonacci-elf"),
+            },
+            {
+                "proving_system": "Groth16Bn254",
+                "proof": "plonk.proof",
+                "public_input": "plonk_pub_input.pub",
+                "vk": "plonk.vk",
+                "tag": format!("0x{}", "01".repeat(32)),
+                "metadata": "job-42",
+            },
+        ]
+    });
+    let path = dir.join("proofs.json");
+    fs::write(&path, manifest.to_string()).unwrap();
+    path
+}
+
+#[test]
+fn test_proofs_of_the_manifest_are_read() {
+    let dir = test_dir("manifest", "read");
+    let manifest = write_manifest(&dir);
+
+    let output = submit_manifest_cmd(&manifest, &[]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("Detected a SP1 proof"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(
+        logs.contains("Read 2 proofs from"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(logs.contains("Submitting proofs to the Aligned batcher"));
+}
+
+#[test]
+fn test_invalid_manifests_are_rejected() {
+    let dir = test_dir("manifest", "invalid");
+    let manifest = dir.join("proofs.json");
+
+    fs::write(
+        &manifest,
+        r#"{"proofs":[{"proving_system":"SP1","proof":"missing.proof"}]}"#,
+    )
+    .unwrap();
+    let output = submit_manifest_cmd(&manifest, &[]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Entry 0 of the manifest") && logs.contains("missing.proof"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    for invalid_manifest in [r#"{"proofs":[]}"#, r#"{"proofs":[{"prof":"a.proof"}]}"#] {
+        fs::write(&manifest, invalid_manifest).unwrap();
+        let output = submit_manifest_cmd(&manifest, &[]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("manifest"));
+    }
+
+    // Metadata larger than the batcher takes is rejected before submitting
+    let metadata = "a".repeat(2 << 10);
+    let output = submit_manifest_cmd(&write_manifest(&dir), &["--metadata", &metadata]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Entry 0 of the manifest") && logs.contains("the metadata must be at most"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    // The proofs are either in the manifest or in the flags
+    let output = submit_manifest_cmd(&write_manifest(&dir), &["--proof", "a.proof"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
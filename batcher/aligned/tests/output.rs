@@ -0,0 +1,63 @@
+mod common;
+
+use serde_json::Value;
+
+use common::{aligned_cmd, assert_json_success, test_dir, test_file};
+
+/// Runs the command with `--output json`, and returns its standard output as JSON.
+fn json_output_of(args: &[&str]) -> Value {
+    assert_json_success(&aligned_cmd(args).output().unwrap())
+}
+
+#[test]
+fn test_commitments_are_printed_as_json() {
+    let dir = test_dir("output", "commitments");
+    let input = dir.join("input");
+    std::fs::write(&input, b"aligned").unwrap();
+    let output_file = dir.join("commitment");
+
+    // The `--output` of the command is the file the commitment is written into
+    let json = json_output_of(&[
+        "get-commitment",
+        "--input",
+        input.to_str().unwrap(),
+        "--output",
+        output_file.to_str().unwrap(),
+    ]);
+    let commitment = json["commitment"].as_str().unwrap();
+    assert_eq!(commitment.len(), 66);
+    assert_eq!(
+        std::fs::read_to_string(&output_file).unwrap(),
+        commitment.trim_start_matches("0x")
+    );
+}
+
+#[test]
+fn test_verification_results_are_printed_as_json() {
+    let json = json_output_of(&[
+        "verify-inclusion-local",
+        "--aligned_verification_data",
+        test_file("aligned_verification_data/unversioned_batch.json")
+            .to_str()
+            .unwrap(),
+    ]);
+    assert_eq!(json["included"], Value::Bool(true));
+    assert_eq!(
+        json["batch_merkle_root"],
+        json["proof"]["batch_merkle_root"]
+    );
+    assert!(json["proof"]["index_in_batch"].is_u64());
+    for commitment in [
+        "proof_commitment",
+        "pub_input_commitment",
+        "proving_system_aux_data_commitment",
+    ] {
+        assert_eq!(
+            json["proof"]["commitments"][commitment]
+                .as_str()
+                .unwrap()
+                .len(),
+            66
+        );
+    }
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use aligned_sdk::sdk::get_commitment;
+use ethers::utils::hex;
+
+use common::test_dir;
+
+fn get_commitment_cmd(input: &Path, output: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("get-commitment")
+        .arg("--input")
+        .arg(input)
+        .arg("--output")
+        .arg(output)
+        .output()
+        .unwrap()
+}
+
+fn assert_commitment_written(input: &Path, output: &Path) {
+    let result = get_commitment_cmd(input, output);
+    assert!(
+        result.status.success(),
+        "get-commitment failed: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let expected = hex::encode(get_commitment(&fs::read(input).unwrap()));
+    assert_eq!(fs::read_to_string(output).unwrap(), expected);
+}
+
+#[cfg(unix)]
+fn non_unicode_file_name() -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(b"proof_\xff\xfe.bin".to_vec())
+}
+
+#[cfg(windows)]
+fn non_unicode_file_name() -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+    // An unpaired surrogate is a valid file name on Windows but can't be represented as UTF-8
+    let mut name: Vec<u16> = "proof_".encode_utf16().collect();
+    name.push(0xD800);
+    name.extend(".bin".encode_utf16());
+    OsString::from_wide(&name)
+}
+
+#[test]
+fn test_get_commitment_unicode_path() {
+    let dir = test_dir("paths", "unicode_paths").join("pruebas_ñandú_証明");
+    fs::create_dir_all(&dir).unwrap();
+
+    let input = dir.join("clave_de_verificación.vk");
+    let output = dir.join("compromiso_✓.txt");
+    fs::write(&input, b"verification key").unwrap();
+
+    assert_commitment_written(&input, &output);
+}
+
+// macOS file systems reject file names that are not valid unicode
+#[cfg(any(target_os = "linux", windows))]
+#[test]
+fn test_get_commitment_non_unicode_path() {
+    let dir = test_dir("paths", "non_unicode_paths");
+
+    let input = dir.join(non_unicode_file_name());
+    let output = dir.join("commitment.txt");
+    fs::write(&input, b"verification key").unwrap();
+
+    assert_commitment_written(&input, &output);
+}
+
+#[test]
+fn test_get_commitment_long_path() {
+    // Longer than the 260 characters of MAX_PATH on Windows
+    let mut dir = test_dir("paths", "long_paths");
+    for i in 0..10 {
+        dir = dir.join(format!("long_directory_name_number_{:02}", i));
+    }
+    fs::create_dir_all(&dir).unwrap();
+
+    let input = dir.join("verification_key.vk");
+    let output = dir.join("commitment.txt");
+    fs::write(&input, b"verification key").unwrap();
+
+    assert_commitment_written(&input, &output);
+}
+
+#[test]
+fn test_missing_file_error_contains_path() {
+    let dir = test_dir("paths", "missing_file");
+    let input = dir.join("archivo_inexistente_ñ.vk");
+    let output = dir.join("commitment.txt");
+
+    let result = get_commitment_cmd(&input, &output);
+    assert!(!result.status.success());
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains(input.to_str().unwrap()),
+        "Error message does not contain the path: {}",
+        stderr
+    );
+}
+
+#[cfg(any(target_os = "linux", windows))]
+#[test]
+fn test_missing_non_unicode_file_error_contains_path() {
+    let dir = test_dir("paths", "missing_non_unicode_file");
+    let input = dir.join(non_unicode_file_name());
+    let output = dir.join("commitment.txt");
+
+    let result = get_commitment_cmd(&input, &output);
+    assert!(!result.status.success());
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains(&format!("{:?}", input)),
+        "Error message does not contain the path: {}",
+        stderr
+    );
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use std::process::Command;
+
+use serde_json::Value;
+
+use common::test_file;
+
+#[test]
+fn test_failures_of_concurrent_repetitions_are_reported() {
+    let dir = std::env::temp_dir().join(format!("aligned_repetitions_{}", std::process::id()));
+
+    // The batcher can't be reached, so every repetition fails on its own
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("--output")
+        .arg("json")
+        .arg("submit")
+        .arg("--proving_system")
+        .arg("SP1")
+        .arg("--proof")
+        .arg(test_file("sp1/sp1_fibonacci.proof"))
+        .arg("--vm_program")
+        .arg(test_file("sp1/sp1_fibonacci-elf"))
+        .arg("--repetitions")
+        .arg("3")
+        .arg("--concurrency")
+        .arg("2")
+        .arg("--conn")
+        .arg("ws://127.0.0.1:1")
+        .arg("--rpc")
+        .arg("http://127.0.0.1:1")
+        .arg("--aligned_verification_data_path")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    for repetition in 0..3 {
+        assert!(
+            logs.contains(&format!("Repetition {} failed", repetition)),
+            "unexpected logs: {}",
+            logs
+        );
+    }
+    assert!(logs.contains("3 of 3 repetitions failed"));
+
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["failed"], 3);
+    assert_eq!(json["included_proofs"], 0);
+    let repetitions = json["repetitions"].as_array().unwrap();
+    assert_eq!(repetitions.len(), 3);
+    for (position, repetition) in repetitions.iter().enumerate() {
+        assert_eq!(repetition["repetition"], position);
+        assert!(repetition["error"].is_string());
+    }
+}
+
+#[test]
+fn test_concurrency_must_be_positive() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(["submit", "--proof", "a.proof", "--concurrency", "0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--concurrency"));
+}
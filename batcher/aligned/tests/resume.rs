@@ -0,0 +1,96 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+use common::{test_dir, test_file};
+
+fn resume_cmd(dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args([
+            "--output",
+            "json",
+            "resume",
+            "--aligned_verification_data_path",
+        ])
+        .arg(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_nothing_to_resume_without_pending_submissions() {
+    let dir = test_dir("resume", "empty");
+
+    let output = resume_cmd(&dir);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There are no pending submissions"));
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["submissions"], serde_json::json!([]));
+}
+
+#[test]
+fn test_unreachable_batcher_keeps_the_submission_pending() {
+    let dir = test_dir("resume", "unreachable");
+    let pending_file = dir.join(".aligned_pending.json");
+    let commitment = serde_json::json!({
+        "proof_commitment": vec![1u8; 32],
+        "pub_input_commitment": vec![2u8; 32],
+        "proving_system_aux_data_commitment": vec![3u8; 32],
+        "proof_generator_addr": format!("0x{}", "04".repeat(20)),
+    });
+    let pending = serde_json::json!([{
+        "batcher_addr": "ws://127.0.0.1:1",
+        "submitted_at": 1700000000,
+        "commitments": [commitment],
+    }]);
+    fs::write(&pending_file, pending.to_string()).unwrap();
+
+    let output = resume_cmd(&dir);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("1 of 1 pending submissions could not be resumed"),
+        "unexpected logs: {}",
+        logs
+    );
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["submissions"][0]["batcher_addr"], "ws://127.0.0.1:1");
+    assert!(json["submissions"][0]["error"].is_string());
+
+    let kept: Value = serde_json::from_slice(&fs::read(&pending_file).unwrap()).unwrap();
+    assert_eq!(kept, pending);
+}
+
+#[test]
+fn test_submissions_refused_before_sending_are_not_pending() {
+    let dir = test_dir("resume", "refused");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("submit")
+        .arg("--proving_system")
+        .arg("SP1")
+        .arg("--proof")
+        .arg(test_file("sp1/sp1_fibonacci.proof"))
+        .arg("--vm_program")
+        .arg(test_file("sp1/sp1_fibonacci-elf"))
+        .arg("--conn")
+        .arg("ws://127.0.0.1:1")
+        .arg("--rpc")
+        .arg("http://127.0.0.1:1")
+        .arg("--aligned_verification_data_path")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Submitting proofs to the Aligned batcher"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(!dir.join(".aligned_pending.json").exists());
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use std::process::Command;
+
+use common::test_file;
+
+#[test]
+fn test_status_of_a_proof_needs_its_batcher() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(["--output", "json", "status", "--proving_system", "SP1"])
+        .arg("--proof")
+        .arg(test_file("sp1/sp1_fibonacci.proof"))
+        .arg("--vm_program")
+        .arg(test_file("sp1/sp1_fibonacci-elf"))
+        .args(["--conn", "ws://127.0.0.1:1"])
+        .output()
+        .unwrap();
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Connection refused"),
+        "unexpected logs: {}",
+        logs
+    );
+    // Nothing is printed without a status
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use common::{test_dir, test_file};
+
+fn test_store(name: &str) -> PathBuf {
+    test_dir("usage", name).join("usage.json")
+}
+
+/// Submits two proofs to a batcher and an RPC node that can't be reached, so that the
+/// submission fails once the usage limits were checked.
+fn submit_cmd(usage_store: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("submit")
+        .arg("--proving_system")
+        .arg("SP1")
+        .arg("--proof")
+        .arg(test_file("sp1/sp1_fibonacci.proof"))
+        .arg("--vm_program")
+        .arg(test_file("sp1/sp1_fibonacci-elf"))
+        .arg("--repetitions")
+        .arg("2")
+        .arg("--conn")
+        .arg("ws://127.0.0.1:1")
+        .arg("--rpc")
+        .arg("http://127.0.0.1:1")
+        .arg("--usage_store")
+        .arg(usage_store)
+        .arg("--aligned_verification_data_path")
+        .arg(usage_store.parent().unwrap())
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn recorded_proofs(usage_store: &Path) -> u64 {
+    let usage: serde_json::Value = serde_json::from_slice(&fs::read(usage_store).unwrap()).unwrap();
+    usage["proofs"].as_u64().unwrap()
+}
+
+#[test]
+fn test_submissions_above_the_daily_limits_are_refused() {
+    let usage_store = test_store("limits");
+
+    let output = submit_cmd(&usage_store, &["--max_proofs_per_day", "3"]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("Submitting proofs to the Aligned batcher"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert_eq!(recorded_proofs(&usage_store), 2);
+
+    let output = submit_cmd(&usage_store, &["--max_proofs_per_day", "3"]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Usage limit exceeded") && !logs.contains("Submitting proofs"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert_eq!(recorded_proofs(&usage_store), 2);
+
+    // The override submits and records the proofs anyway
+    let output = submit_cmd(
+        &usage_store,
+        &["--max_proofs_per_day", "3", "--ignore_usage_limits"],
+    );
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("Submitting proofs to the Aligned batcher"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert_eq!(recorded_proofs(&usage_store), 4);
+}
+
+#[test]
+fn test_spend_limit_requires_the_estimated_fee() {
+    let usage_store = test_store("spend");
+
+    // The RPC node can't be reached, so the fee of the proofs is unknown
+    let output = submit_cmd(&usage_store, &["--max_spend_per_day", "0.1"]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("could not be estimated"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(!usage_store.exists());
+}
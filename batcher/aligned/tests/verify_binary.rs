@@ -0,0 +1,138 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::{Command, Output};
+use std::str::FromStr;
+use std::thread;
+
+use ethers::signers::{LocalWallet, Signer};
+use ethers::utils::{hash_message, hex};
+use sha2::{Digest, Sha256};
+
+const RELEASE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+const ASSET: &str = "aligned-x86";
+
+/// Serves the manifest and its signature, at `/aligned-manifest.json` and
+/// `/aligned-manifest.json.sig`, for the given number of requests. Returns the URL of the manifest.
+fn start_release_server(manifest: String, signature: String, requests: usize) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/aligned-manifest.json",
+        listener.local_addr().unwrap()
+    );
+
+    thread::spawn(move || {
+        for _ in 0..requests {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            let body = if request_line.contains(".sig ") {
+                &signature
+            } else {
+                &manifest
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    url
+}
+
+/// Returns the manifest of a release of the binary under test, with the given SHA-256 for it.
+fn manifest(binary_hash: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("--version")
+        .output()
+        .unwrap();
+    // Such as `aligned 0.1.0 (git 3f2a1bc, built ...)`
+    let version = String::from_utf8(output.stdout).unwrap();
+    let mut words = version.split_whitespace().skip(1);
+    let cli_version = words.next().unwrap();
+    let git_hash = words.nth(1).unwrap().trim_end_matches(',');
+
+    format!(
+        r#"{{"version":"{}","git_hash":"{}","binaries":{{"{}":"{}"}}}}"#,
+        cli_version, git_hash, ASSET, binary_hash
+    )
+}
+
+fn sign(wallet: &LocalWallet, manifest: &str) -> String {
+    wallet
+        .sign_hash(hash_message(manifest))
+        .unwrap()
+        .to_string()
+}
+
+fn verify_binary_cmd(manifest_url: &str, signer: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("verify-binary")
+        .arg("--manifest_url")
+        .arg(manifest_url)
+        .arg("--release_signer")
+        .arg(signer)
+        .arg("--asset")
+        .arg(ASSET)
+        .output()
+        .unwrap()
+}
+
+fn binary_hash() -> String {
+    let binary = std::fs::read(env!("CARGO_BIN_EXE_aligned")).unwrap();
+    hex::encode(Sha256::digest(binary))
+}
+
+#[test]
+fn test_binary_of_the_release_is_verified() {
+    let wallet = LocalWallet::from_str(RELEASE_KEY).unwrap();
+    let manifest = manifest(&binary_hash());
+    let url = start_release_server(manifest.clone(), sign(&wallet, &manifest), 2);
+
+    let output = verify_binary_cmd(&url, &format!("{:?}", wallet.address()));
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "unexpected logs: {}", logs);
+    assert!(logs.contains("This binary is aligned-x86 of release"));
+}
+
+#[test]
+fn test_tampered_binaries_and_manifests_are_rejected() {
+    let wallet = LocalWallet::from_str(RELEASE_KEY).unwrap();
+
+    // A binary whose hash is not the one in the manifest
+    let manifest = manifest(&hex::encode([0u8; 32]));
+    let url = start_release_server(manifest.clone(), sign(&wallet, &manifest), 2);
+    let output = verify_binary_cmd(&url, &format!("{:?}", wallet.address()));
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("The SHA-256 of this binary is"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    // A manifest signed by another key
+    let other_wallet = LocalWallet::from_str(&"11".repeat(32)).unwrap();
+    let manifest = self::manifest(&binary_hash());
+    let url = start_release_server(manifest.clone(), sign(&other_wallet, &manifest), 2);
+    let output = verify_binary_cmd(&url, &format!("{:?}", wallet.address()));
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("was not signed by"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    // Manifests are only downloaded over HTTPS
+    let output = verify_binary_cmd(
+        "http://releases.example.com/aligned-manifest.json",
+        &format!("{:?}", wallet.address()),
+    );
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(logs.contains("over HTTPS"), "unexpected logs: {}", logs);
+}
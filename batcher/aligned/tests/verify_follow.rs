@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn aligned_verification_data_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../aligned-sdk/test_files/aligned_verification_data")
+        .join(name)
+}
+
+fn verify_follow_cmd(file: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .args(["--output", "json", "verify-proof-onchain", "--follow"])
+        .arg("--aligned_verification_data")
+        .arg(aligned_verification_data_file(file))
+        .args(["--rpc", "http://127.0.0.1:1", "--poll_interval", "1"])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_follow_fails_without_rpc_node() {
+    let output = verify_follow_cmd("unversioned_batch.json");
+    let logs = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Waiting for batch 0x"),
+        "unexpected logs: {}",
+        logs
+    );
+    // Nothing is printed until the proof is verified
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_follow_fails_without_waiting_for_v2_batches() {
+    let output = verify_follow_cmd("batch_version_v2.json");
+    let logs = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Batches of version V2 can't be verified on-chain"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use ethers::utils::hex;
+
+fn aligned_verification_data_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../aligned-sdk/test_files/aligned_verification_data/unversioned_batch.json")
+}
+
+fn verify_inclusion_local_cmd(batch_merkle_root: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_aligned"));
+    command
+        .arg("verify-inclusion-local")
+        .arg("--aligned_verification_data")
+        .arg(aligned_verification_data_file());
+    if let Some(batch_merkle_root) = batch_merkle_root {
+        command.arg("--merkle_root").arg(batch_merkle_root);
+    }
+    command.output().unwrap()
+}
+
+fn batch_merkle_root_of_file() -> String {
+    let file: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(aligned_verification_data_file()).unwrap()).unwrap();
+    let batch_merkle_root: Vec<u8> = file["batch_merkle_root"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|byte| byte.as_u64().unwrap() as u8)
+        .collect();
+    hex::encode(batch_merkle_root)
+}
+
+#[test]
+fn test_inclusion_is_verified_without_rpc() {
+    let output = verify_inclusion_local_cmd(Some(&batch_merkle_root_of_file()));
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "verification failed: {}", logs);
+    assert!(logs.contains("Your proof is included"));
+
+    // Without a root, the one in the file is used
+    let output = verify_inclusion_local_cmd(None);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Your proof is included"));
+}
+
+#[test]
+fn test_inclusion_in_another_batch_is_rejected() {
+    let output = verify_inclusion_local_cmd(Some(&"ab".repeat(32)));
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "verification failed: {}", logs);
+    assert!(logs.contains("Your proof is not included"));
+}
+
+#[test]
+fn test_kebab_case_flags_are_accepted() {
+    let output = Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("verify-inclusion-local")
+        .arg("--aligned-verification-data")
+        .arg(aligned_verification_data_file())
+        .arg("--merkle-root")
+        .arg(batch_merkle_root_of_file())
+        .output()
+        .unwrap();
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "verification failed: {}", logs);
+    assert!(logs.contains("Your proof is included"));
+}
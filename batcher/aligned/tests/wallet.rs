@@ -0,0 +1,69 @@
+use std::process::{Command, Output};
+
+// Anvil's first account, 0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266
+const PRIVATE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+/// Runs a submit that stops before connecting to the batcher, since the proof doesn't exist.
+/// The balance check is made first, with the address of the wallet, against an RPC node that
+/// isn't running.
+fn submit_cmd(args: &[&str], envs: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_aligned"))
+        .arg("submit")
+        .args(["--proving_system", "SP1", "--proof", "missing.proof"])
+        .args(["--vm_program", "missing.elf", "--rpc", "http://127.0.0.1:1"])
+        .arg("--aligned_verification_data_path")
+        .arg(std::env::temp_dir().join("aligned_wallet_test"))
+        .args(args)
+        .env_remove("ALIGNED_PRIVATE_KEY")
+        .env_remove("ALIGNED_AWS_KMS_KEY_ID")
+        .env_remove("AWS_REGION")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_private_key_is_read_from_the_environment() {
+    let output = submit_cmd(&[], &[("ALIGNED_PRIVATE_KEY", PRIVATE_KEY)]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(logs.contains("0xf39f…2266"), "unexpected logs: {}", logs);
+    assert!(!logs.contains("Missing keystore"));
+    assert!(!logs.contains(PRIVATE_KEY));
+}
+
+#[test]
+fn test_invalid_private_keys_are_rejected_without_being_logged() {
+    let output = submit_cmd(&["--private_key", "0x1234"], &[]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        logs.contains("Invalid private key"),
+        "unexpected logs: {}",
+        logs
+    );
+    assert!(!logs.contains("0x1234"));
+}
+
+#[test]
+fn test_kms_keys_need_a_region_and_no_other_key() {
+    let output = submit_cmd(
+        &[
+            "--aws_kms_key_id",
+            "alias/prover",
+            "--private_key",
+            PRIVATE_KEY,
+        ],
+        &[],
+    );
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        logs.contains("Please use only one"),
+        "unexpected logs: {}",
+        logs
+    );
+
+    let output = submit_cmd(&["--aws_kms_key_id", "alias/prover"], &[]);
+    let logs = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(logs.contains("aws_region"), "unexpected logs: {}", logs);
+}
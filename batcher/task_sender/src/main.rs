@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use clap::ValueEnum;
+use env_logger::Env;
+use ethers::prelude::*;
+use log::{info, warn};
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::types::{Chain, ProvingSystemId};
+
+use crate::report::{RunParameters, SoakReport};
+use crate::sender::{send_tasks, OnchainVerificationConfig, TaskSenderConfig};
+use crate::test_files::load_verification_data;
+
+mod report;
+mod sender;
+mod test_files;
+
+const VERIFICATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const ANVIL_PRIVATE_KEY: &str = "2a871d0798f97d79848a013d4936a73bf4cc922c825d33c1cf7073dff6d409c6"; // Anvil address 9
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Periodically sends test proofs to the batcher",
+    long_about = None
+)]
+pub struct TaskSenderArgs {
+    #[arg(
+        name = "The Ethereum network's name",
+        long = "network",
+        default_value = "devnet"
+    )]
+    network: NetworkArg,
+    #[arg(
+        name = "Batcher address, defaults to the batcher of the network",
+        long = "conn"
+    )]
+    connect_addr: Option<String>,
+    #[arg(
+        name = "Proving systems of the proofs to send",
+        long = "proving_systems",
+        value_delimiter = ',',
+        default_value = "Groth16Bn254"
+    )]
+    proving_systems: Vec<ProvingSystemArg>,
+    #[arg(
+        name = "Submissions sent per second",
+        long = "rate",
+        default_value = "0.5"
+    )]
+    rate: f64,
+    #[arg(
+        name = "Seconds to send submissions for, runs until stopped if not set",
+        long = "duration"
+    )]
+    duration: Option<u64>,
+    #[arg(
+        name = "Minimum number of proofs per submission",
+        long = "min_proofs",
+        default_value = "1"
+    )]
+    min_proofs_per_submission: usize,
+    #[arg(
+        name = "Maximum number of proofs per submission",
+        long = "max_proofs",
+        default_value = "1"
+    )]
+    max_proofs_per_submission: usize,
+    #[arg(
+        name = "Seed of the random sampling of the proofs",
+        long = "seed",
+        default_value = "0"
+    )]
+    seed: u64,
+    #[arg(
+        name = "Test files directory path",
+        long = "test_files_dir",
+        default_value = "../../scripts/test_files"
+    )]
+    test_files_dir: PathBuf,
+    #[arg(
+        name = "Ethereum RPC provider address, checks the batches are verified if set",
+        long = "rpc"
+    )]
+    eth_rpc_url: Option<String>,
+    #[arg(
+        name = "Seconds to wait for a batch to be verified",
+        long = "verification_timeout",
+        default_value = "300"
+    )]
+    verification_timeout: u64,
+    #[arg(name = "JSON report file path", long = "report_json")]
+    report_json_path: Option<PathBuf>,
+    #[arg(name = "HTML report file path", long = "report_html")]
+    report_html_path: Option<PathBuf>,
+    #[arg(name = "Path to local keystore", long = "keystore_path")]
+    keystore_path: Option<PathBuf>,
+    #[arg(name = "Private key", long = "private_key")]
+    private_key: Option<String>,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum NetworkArg {
+    Devnet,
+    Holesky,
+}
+
+impl NetworkArg {
+    fn batcher_url(&self) -> &'static str {
+        match self {
+            NetworkArg::Devnet => "ws://localhost:8080",
+            NetworkArg::Holesky => "wss://batcher.alignedlayer.com",
+        }
+    }
+}
+
+impl From<NetworkArg> for Chain {
+    fn from(network: NetworkArg) -> Self {
+        match network {
+            NetworkArg::Devnet => Chain::Devnet,
+            NetworkArg::Holesky => Chain::Holesky,
+        }
+    }
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum ProvingSystemArg {
+    #[clap(name = "GnarkPlonkBls12_381")]
+    GnarkPlonkBls12_381,
+    #[clap(name = "GnarkPlonkBn254")]
+    GnarkPlonkBn254,
+    #[clap(name = "Groth16Bn254")]
+    Groth16Bn254,
+    #[clap(name = "SP1")]
+    SP1,
+    #[clap(name = "Halo2KZG")]
+    Halo2KZG,
+    #[clap(name = "Halo2IPA")]
+    Halo2IPA,
+    #[clap(name = "Risc0")]
+    Risc0,
+}
+
+impl From<ProvingSystemArg> for ProvingSystemId {
+    fn from(proving_system: ProvingSystemArg) -> Self {
+        match proving_system {
+            ProvingSystemArg::GnarkPlonkBls12_381 => ProvingSystemId::GnarkPlonkBls12_381,
+            ProvingSystemArg::GnarkPlonkBn254 => ProvingSystemId::GnarkPlonkBn254,
+            ProvingSystemArg::Groth16Bn254 => ProvingSystemId::Groth16Bn254,
+            ProvingSystemArg::SP1 => ProvingSystemId::SP1,
+            ProvingSystemArg::Halo2KZG => ProvingSystemId::Halo2KZG,
+            ProvingSystemArg::Halo2IPA => ProvingSystemId::Halo2IPA,
+            ProvingSystemArg::Risc0 => ProvingSystemId::Risc0,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), SubmitError> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let args = TaskSenderArgs::parse();
+
+    if args.proving_systems.is_empty() {
+        return Err(SubmitError::MissingParameter(
+            "--proving_systems".to_string(),
+        ));
+    }
+    if !(args.rate.is_finite() && args.rate > 0.0) {
+        return Err(SubmitError::GenericError(
+            "--rate must be a positive number".to_string(),
+        ));
+    }
+    if args.min_proofs_per_submission == 0
+        || args.min_proofs_per_submission > args.max_proofs_per_submission
+    {
+        return Err(SubmitError::GenericError(
+            "--min_proofs must be positive and not greater than --max_proofs".to_string(),
+        ));
+    }
+
+    let wallet = if let Some(keystore_path) = &args.keystore_path {
+        if args.private_key.is_some() {
+            return Err(SubmitError::GenericError(
+                "Can't have a keystore path and a private key as input. Please use only one"
+                    .to_string(),
+            ));
+        }
+        let password = rpassword::prompt_password("Please enter your keystore password:")
+            .map_err(|e| SubmitError::GenericError(e.to_string()))?;
+        Wallet::decrypt_keystore(keystore_path, password)
+            .map_err(|e| SubmitError::GenericError(e.to_string()))?
+    } else if let Some(private_key) = &args.private_key {
+        private_key
+            .parse::<LocalWallet>()
+            .map_err(|e| SubmitError::GenericError(e.to_string()))?
+    } else {
+        warn!("Missing keystore used for payment, using the devnet anvil wallet");
+        LocalWallet::from_str(ANVIL_PRIVATE_KEY).expect("Failed to create wallet")
+    };
+
+    let proofs = args
+        .proving_systems
+        .iter()
+        .map(|proving_system| {
+            load_verification_data(proving_system.clone().into(), &args.test_files_dir)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let batcher_url = args
+        .connect_addr
+        .unwrap_or_else(|| args.network.batcher_url().to_string());
+    info!(
+        "Sending {:?} proofs to {} at {} submissions per second",
+        args.proving_systems, batcher_url, args.rate
+    );
+
+    let parameters = RunParameters {
+        batcher_url: batcher_url.clone(),
+        proving_systems: args
+            .proving_systems
+            .iter()
+            .map(|proving_system| format!("{:?}", proving_system))
+            .collect(),
+        rate: args.rate,
+        duration_secs: args.duration,
+        min_proofs_per_submission: args.min_proofs_per_submission,
+        max_proofs_per_submission: args.max_proofs_per_submission,
+        seed: args.seed,
+        verified_onchain: args.eth_rpc_url.is_some(),
+    };
+
+    let verification = args
+        .eth_rpc_url
+        .map(|eth_rpc_url| OnchainVerificationConfig {
+            chain: args.network.into(),
+            eth_rpc_url,
+            timeout: Duration::from_secs(args.verification_timeout),
+            poll_interval: VERIFICATION_POLL_INTERVAL,
+        });
+
+    let config = TaskSenderConfig {
+        batcher_url,
+        proofs,
+        rate: args.rate,
+        duration: args.duration.map(Duration::from_secs),
+        min_proofs_per_submission: args.min_proofs_per_submission,
+        max_proofs_per_submission: args.max_proofs_per_submission,
+        seed: args.seed,
+        verification,
+    };
+
+    let start = Instant::now();
+    let records = send_tasks(config, wallet).await;
+    let report = SoakReport::new(parameters, &records, start.elapsed());
+
+    info!(
+        "Sent {} submissions with {} proofs, {} failed",
+        report.submissions_sent, report.proofs_sent, report.submissions_failed
+    );
+    info!(
+        "{} proofs were included in {} batches",
+        report.proofs_responded,
+        report.batches.len()
+    );
+    for latency in &report.latencies {
+        info!(
+            "{} latency: p50 {} ms, p90 {} ms, p99 {} ms",
+            latency.phase, latency.p50_ms, latency.p90_ms, latency.p99_ms
+        );
+    }
+
+    if let Some(report_json_path) = args.report_json_path {
+        let json = report.to_json().map_err(SubmitError::SerdeError)?;
+        std::fs::write(&report_json_path, json)
+            .map_err(|e| SubmitError::IoError(report_json_path.clone(), e))?;
+        info!("JSON report written to {}", report_json_path.display());
+    }
+    if let Some(report_html_path) = args.report_html_path {
+        std::fs::write(&report_html_path, report.to_html())
+            .map_err(|e| SubmitError::IoError(report_html_path.clone(), e))?;
+        info!("HTML report written to {}", report_html_path.display());
+    }
+
+    Ok(())
+}
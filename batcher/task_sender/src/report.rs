@@ -0,0 +1,528 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::Duration;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::types::{AlignedVerificationData, RejectionCode};
+use ethers::utils::hex;
+use serde::Serialize;
+
+/// Outcome of a single submission of the task sender.
+pub struct SubmissionRecord {
+    pub proofs: usize,
+    pub result: Result<SubmissionResponse, String>,
+}
+
+pub struct SubmissionResponse {
+    pub aligned_verification_data: Vec<AlignedVerificationData>,
+    /// Time from the connection to the batcher until the last inclusion proof was received.
+    pub response_latency: Duration,
+    /// Time from the response until the batch was verified on-chain, if it was checked and
+    /// verified before the timeout.
+    pub verification_latency: Option<Duration>,
+}
+
+/// Parameters of the run, included in the report so runs can be compared and reproduced.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunParameters {
+    pub batcher_url: String,
+    pub proving_systems: Vec<String>,
+    pub rate: f64,
+    pub duration_secs: Option<u64>,
+    pub min_proofs_per_submission: usize,
+    pub max_proofs_per_submission: usize,
+    pub seed: u64,
+    pub verified_onchain: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SoakReport {
+    pub parameters: RunParameters,
+    pub elapsed_secs: f64,
+    pub submissions_sent: usize,
+    pub submissions_failed: usize,
+    pub success_rate: f64,
+    pub proofs_sent: usize,
+    pub proofs_responded: usize,
+    /// Submissions responded by the batcher whose batch was not verified before the timeout.
+    /// Always zero if the batches were not checked on-chain.
+    pub verification_timeouts: usize,
+    pub latencies: Vec<PhaseLatency>,
+    /// Failed submissions grouped by the reason of the failure.
+    pub rejections: BTreeMap<String, usize>,
+    pub batches: Vec<BatchUtilization>,
+}
+
+/// Latency percentiles of a phase of the submissions, in milliseconds.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PhaseLatency {
+    pub phase: String,
+    pub samples: usize,
+    pub p50_ms: u128,
+    pub p90_ms: u128,
+    pub p99_ms: u128,
+    pub max_ms: u128,
+}
+
+/// Occupation of a batch that included proofs of the run. Batches only report the position of
+/// each of its proofs, so the number of proofs in the batch is at least the highest position
+/// seen plus one, and at most the number of leaves of a tree of its depth.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct BatchUtilization {
+    pub batch_merkle_root: String,
+    pub proofs_from_run: usize,
+    pub min_batch_size: usize,
+    pub leaf_capacity: usize,
+    pub utilization: f64,
+}
+
+impl SoakReport {
+    pub fn new(
+        parameters: RunParameters,
+        records: &[SubmissionRecord],
+        elapsed: Duration,
+    ) -> SoakReport {
+        let mut rejections = BTreeMap::new();
+        let mut response_latencies = Vec::new();
+        let mut verification_latencies = Vec::new();
+        let mut end_to_end_latencies = Vec::new();
+        let mut verification_timeouts = 0;
+        let mut proofs_responded = 0;
+        let mut batches: HashMap<[u8; 32], BatchUtilization> = HashMap::new();
+
+        for record in records {
+            let response = match &record.result {
+                Ok(response) => response,
+                Err(reason) => {
+                    *rejections.entry(reason.clone()).or_insert(0) += 1;
+                    continue;
+                }
+            };
+
+            proofs_responded += response.aligned_verification_data.len();
+            response_latencies.push(response.response_latency);
+            match response.verification_latency {
+                Some(verification_latency) => {
+                    verification_latencies.push(verification_latency);
+                    end_to_end_latencies.push(response.response_latency + verification_latency);
+                }
+                None if parameters.verified_onchain => verification_timeouts += 1,
+                None => {}
+            }
+
+            for aligned_verification_data in &response.aligned_verification_data {
+                let batch = batches
+                    .entry(aligned_verification_data.batch_merkle_root)
+                    .or_insert_with(|| new_batch_utilization(aligned_verification_data));
+                batch.proofs_from_run += 1;
+                batch.min_batch_size = batch
+                    .min_batch_size
+                    .max(aligned_verification_data.index_in_batch + 1);
+            }
+        }
+
+        let mut batches: Vec<BatchUtilization> = batches
+            .into_values()
+            .map(|mut batch| {
+                batch.utilization = batch.min_batch_size as f64 / batch.leaf_capacity as f64;
+                batch
+            })
+            .collect();
+        batches.sort_by(|a, b| a.batch_merkle_root.cmp(&b.batch_merkle_root));
+
+        let mut latencies = vec![PhaseLatency::new("response", &mut response_latencies)];
+        if parameters.verified_onchain {
+            latencies.push(PhaseLatency::new(
+                "verification",
+                &mut verification_latencies,
+            ));
+            latencies.push(PhaseLatency::new("end_to_end", &mut end_to_end_latencies));
+        }
+
+        let submissions_failed = rejections.values().sum();
+        let success_rate = if records.is_empty() {
+            0.0
+        } else {
+            (records.len() - submissions_failed) as f64 / records.len() as f64
+        };
+
+        SoakReport {
+            parameters,
+            elapsed_secs: elapsed.as_secs_f64(),
+            submissions_sent: records.len(),
+            submissions_failed,
+            success_rate,
+            proofs_sent: records.iter().map(|record| record.proofs).sum(),
+            proofs_responded,
+            verification_timeouts,
+            latencies,
+            rejections,
+            batches,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let parameters = &self.parameters;
+
+        // Writing to a String never fails
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Aligned soak test report</title>\n\
+             <style>table {{ border-collapse: collapse; }} \
+             th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: right; }}</style>\n\
+             </head>\n<body>\n<h1>Aligned soak test report</h1>\n"
+        );
+
+        html.push_str("<h2>Run</h2>\n");
+        push_table(
+            &mut html,
+            &["Parameter", "Value"],
+            vec![
+                vec!["Batcher".to_string(), escape(&parameters.batcher_url)],
+                vec![
+                    "Proving systems".to_string(),
+                    escape(&parameters.proving_systems.join(", ")),
+                ],
+                vec![
+                    "Rate (submissions/s)".to_string(),
+                    parameters.rate.to_string(),
+                ],
+                vec![
+                    "Duration (s)".to_string(),
+                    parameters
+                        .duration_secs
+                        .map(|duration| duration.to_string())
+                        .unwrap_or_else(|| "until stopped".to_string()),
+                ],
+                vec![
+                    "Proofs per submission".to_string(),
+                    format!(
+                        "{} to {}",
+                        parameters.min_proofs_per_submission, parameters.max_proofs_per_submission
+                    ),
+                ],
+                vec!["Seed".to_string(), parameters.seed.to_string()],
+                vec![
+                    "Elapsed (s)".to_string(),
+                    format!("{:.1}", self.elapsed_secs),
+                ],
+            ],
+        );
+
+        html.push_str("<h2>Submissions</h2>\n");
+        push_table(
+            &mut html,
+            &[
+                "Sent",
+                "Failed",
+                "Success rate",
+                "Proofs sent",
+                "Proofs responded",
+            ],
+            vec![vec![
+                self.submissions_sent.to_string(),
+                self.submissions_failed.to_string(),
+                format!("{:.2}%", self.success_rate * 100.0),
+                self.proofs_sent.to_string(),
+                self.proofs_responded.to_string(),
+            ]],
+        );
+        if parameters.verified_onchain {
+            let _ = writeln!(
+                html,
+                "<p>{} responded submissions were not verified on-chain before the timeout.</p>",
+                self.verification_timeouts
+            );
+        }
+
+        html.push_str("<h2>Latency</h2>\n");
+        push_table(
+            &mut html,
+            &[
+                "Phase", "Samples", "p50 (ms)", "p90 (ms)", "p99 (ms)", "Max (ms)",
+            ],
+            self.latencies
+                .iter()
+                .map(|latency| {
+                    vec![
+                        latency.phase.clone(),
+                        latency.samples.to_string(),
+                        latency.p50_ms.to_string(),
+                        latency.p90_ms.to_string(),
+                        latency.p99_ms.to_string(),
+                        latency.max_ms.to_string(),
+                    ]
+                })
+                .collect(),
+        );
+
+        html.push_str("<h2>Rejections</h2>\n");
+        push_table(
+            &mut html,
+            &["Reason", "Submissions"],
+            self.rejections
+                .iter()
+                .map(|(reason, count)| vec![escape(reason), count.to_string()])
+                .collect(),
+        );
+
+        html.push_str("<h2>Batches</h2>\n");
+        push_table(
+            &mut html,
+            &[
+                "Merkle root",
+                "Proofs from run",
+                "Min batch size",
+                "Leaf capacity",
+                "Utilization",
+            ],
+            self.batches
+                .iter()
+                .map(|batch| {
+                    vec![
+                        batch.batch_merkle_root.clone(),
+                        batch.proofs_from_run.to_string(),
+                        batch.min_batch_size.to_string(),
+                        batch.leaf_capacity.to_string(),
+                        format!("{:.2}%", batch.utilization * 100.0),
+                    ]
+                })
+                .collect(),
+        );
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+impl PhaseLatency {
+    fn new(phase: &str, latencies: &mut [Duration]) -> PhaseLatency {
+        latencies.sort();
+        PhaseLatency {
+            phase: phase.to_string(),
+            samples: latencies.len(),
+            p50_ms: percentile(latencies, 50),
+            p90_ms: percentile(latencies, 90),
+            p99_ms: percentile(latencies, 99),
+            max_ms: latencies.last().map_or(0, Duration::as_millis),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies, in milliseconds. Zero if there are none.
+fn percentile(sorted_latencies: &[Duration], percentile: usize) -> u128 {
+    if sorted_latencies.is_empty() {
+        return 0;
+    }
+    let rank = (percentile * sorted_latencies.len()).div_ceil(100).max(1);
+    sorted_latencies[rank - 1].as_millis()
+}
+
+fn new_batch_utilization(aligned_verification_data: &AlignedVerificationData) -> BatchUtilization {
+    let arity = aligned_verification_data.batch_version.arity();
+    // Each level of the tree holds the siblings of the node, all but one of the arity children
+    let depth = aligned_verification_data
+        .batch_inclusion_proof
+        .merkle_path
+        .len()
+        / (arity - 1);
+
+    BatchUtilization {
+        batch_merkle_root: format!(
+            "0x{}",
+            hex::encode(aligned_verification_data.batch_merkle_root)
+        ),
+        proofs_from_run: 0,
+        min_batch_size: 0,
+        leaf_capacity: arity.saturating_pow(depth as u32),
+        utilization: 0.0,
+    }
+}
+
+/// Name of the reason of a failed submission, used to group them in the report. Rejections by
+/// the batcher are grouped by their rejection code, other errors by their kind.
+pub fn rejection_reason(error: &SubmitError) -> String {
+    match error {
+        SubmitError::BatcherRejection(code, _) => match RejectionCode::try_from(*code) {
+            Ok(code) => format!("{:?}", code),
+            Err(code) => format!("BatcherRejection({})", code),
+        },
+        SubmitError::MessageRejected(rejection) => format!("{:?}", RejectionCode::of(rejection)),
+        SubmitError::ConnectionError(_) => "ConnectionError".to_string(),
+        SubmitError::TlsError(_) => "TlsError".to_string(),
+        SubmitError::InvalidCertificate(_) => "InvalidCertificate".to_string(),
+        SubmitError::Unauthorized(_) => "Unauthorized".to_string(),
+        SubmitError::IoError(_, _) => "IoError".to_string(),
+        SubmitError::SerdeError(_) => "SerdeError".to_string(),
+        SubmitError::InvalidBinaryData(_) => "InvalidBinaryData".to_string(),
+        SubmitError::EthError(_) => "EthError".to_string(),
+        SubmitError::SignerError(_) => "SignerError".to_string(),
+        SubmitError::InvalidSignature(_) => "InvalidSignature".to_string(),
+        SubmitError::MissingParameter(_) => "MissingParameter".to_string(),
+        SubmitError::InvalidProvingSystem(_) => "InvalidProvingSystem".to_string(),
+        SubmitError::InvalidVerificationData(_) => "InvalidVerificationData".to_string(),
+        SubmitError::InvalidProof { .. } => "InvalidProof".to_string(),
+        SubmitError::InvalidAddress(_, _) => "InvalidAddress".to_string(),
+        SubmitError::ProtocolMismatch { .. } => "ProtocolMismatch".to_string(),
+        SubmitError::ProtocolViolation(_) => "ProtocolViolation".to_string(),
+        SubmitError::InsufficientBalance { .. } => "InsufficientBalance".to_string(),
+        SubmitError::ProvingError(_) => "ProvingError".to_string(),
+        SubmitError::DiscoveryError(_) => "DiscoveryError".to_string(),
+        SubmitError::DuplicateSubmission(_) => "DuplicateSubmission".to_string(),
+        SubmitError::UsageLimitExceeded(_) => "UsageLimitExceeded".to_string(),
+        SubmitError::Timeout(_) => "Timeout".to_string(),
+        SubmitError::GenericError(_) => "GenericError".to_string(),
+    }
+}
+
+fn push_table(html: &mut String, headers: &[&str], rows: Vec<Vec<String>>) {
+    html.push_str("<table>\n<tr>");
+    for header in headers {
+        let _ = write!(html, "<th>{}</th>", header);
+    }
+    html.push_str("</tr>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for cell in row {
+            let _ = write!(html, "<td>{}</td>", cell);
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aligned_sdk::types::{BatchVersion, Rejection, VerificationDataCommitment};
+    use lambdaworks_crypto::merkle_tree::proof::Proof;
+
+    fn parameters(verified_onchain: bool) -> RunParameters {
+        RunParameters {
+            batcher_url: "ws://localhost:8080".to_string(),
+            proving_systems: vec!["SP1".to_string()],
+            rate: 1.0,
+            duration_secs: Some(10),
+            min_proofs_per_submission: 1,
+            max_proofs_per_submission: 2,
+            seed: 0,
+            verified_onchain,
+        }
+    }
+
+    fn aligned_verification_data(
+        batch_merkle_root: [u8; 32],
+        index_in_batch: usize,
+    ) -> AlignedVerificationData {
+        AlignedVerificationData {
+            verification_data_commitment: VerificationDataCommitment::default(),
+            batch_merkle_root,
+            batch_inclusion_proof: Proof {
+                merkle_path: vec![[0; 32]; 3],
+            },
+            index_in_batch,
+            batch_version: BatchVersion::V1,
+            batch_metadata: None,
+            metadata: None,
+            schema_version: AlignedVerificationData::SCHEMA_VERSION,
+        }
+    }
+
+    fn responded(
+        aligned_verification_data: Vec<AlignedVerificationData>,
+        response_ms: u64,
+        verification_ms: Option<u64>,
+    ) -> SubmissionRecord {
+        SubmissionRecord {
+            proofs: aligned_verification_data.len(),
+            result: Ok(SubmissionResponse {
+                aligned_verification_data,
+                response_latency: Duration::from_millis(response_ms),
+                verification_latency: verification_ms.map(Duration::from_millis),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+
+        assert_eq!(percentile(&latencies, 50), 50);
+        assert_eq!(percentile(&latencies, 90), 90);
+        assert_eq!(percentile(&latencies, 99), 99);
+        assert_eq!(percentile(&latencies[..1], 99), 1);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn test_report_aggregates_submissions() {
+        let records = vec![
+            responded(
+                vec![
+                    aligned_verification_data([1; 32], 0),
+                    aligned_verification_data([1; 32], 4),
+                ],
+                100,
+                Some(1000),
+            ),
+            responded(vec![aligned_verification_data([2; 32], 1)], 300, None),
+            SubmissionRecord {
+                proofs: 1,
+                result: Err("InvalidProof".to_string()),
+            },
+        ];
+
+        let report = SoakReport::new(parameters(true), &records, Duration::from_secs(10));
+
+        assert_eq!(report.submissions_sent, 3);
+        assert_eq!(report.submissions_failed, 1);
+        assert_eq!(report.proofs_sent, 4);
+        assert_eq!(report.proofs_responded, 3);
+        assert_eq!(report.verification_timeouts, 1);
+        assert_eq!(report.rejections.get("InvalidProof"), Some(&1));
+        assert_eq!(report.latencies[0].samples, 2);
+        assert_eq!(report.latencies[0].max_ms, 300);
+        assert_eq!(report.latencies[2].p50_ms, 1100);
+        assert_eq!(
+            report.batches[0],
+            BatchUtilization {
+                batch_merkle_root: format!("0x{}", hex::encode([1; 32])),
+                proofs_from_run: 2,
+                min_batch_size: 5,
+                leaf_capacity: 8,
+                utilization: 0.625,
+            }
+        );
+        assert!(report.to_html().contains("<td>InvalidProof</td><td>1</td>"));
+    }
+
+    #[test]
+    fn test_rejection_reason_uses_rejection_code() {
+        assert_eq!(
+            rejection_reason(&SubmitError::BatcherRejection(4003, vec![])),
+            "InvalidProof"
+        );
+        assert_eq!(
+            rejection_reason(&SubmitError::BatcherRejection(1011, vec![])),
+            "BatcherRejection(1011)"
+        );
+        // Error responses are grouped with the close codes of the same rejection
+        assert_eq!(
+            rejection_reason(&SubmitError::MessageRejected(Rejection::InvalidSignature)),
+            "InvalidSignature"
+        );
+    }
+}
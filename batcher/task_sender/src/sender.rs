@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use aligned_sdk::sdk::{submit_multiple, verify_proof_onchain};
+use aligned_sdk::types::{AlignedVerificationData, Chain, VerificationData};
+use ethers::core::k256::ecdsa::SigningKey;
+use ethers::signers::Wallet;
+use ethers::types::Address;
+use log::{error, info, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep, Instant, MissedTickBehavior};
+
+use crate::report::{rejection_reason, SubmissionRecord, SubmissionResponse};
+
+pub struct TaskSenderConfig {
+    pub batcher_url: String,
+    /// Verification data of the proofs to send, one per proving system.
+    pub proofs: Vec<VerificationData>,
+    /// Submissions sent per second.
+    pub rate: f64,
+    /// How long to send submissions for. Runs until stopped if `None`.
+    pub duration: Option<Duration>,
+    /// Bounds of the number of proofs of each submission, sampled uniformly.
+    pub min_proofs_per_submission: usize,
+    pub max_proofs_per_submission: usize,
+    /// Seed of the sampling of the proofs, so runs with the same seed send the same submissions.
+    pub seed: u64,
+    /// Checks that the batches of the responses are verified on-chain if set.
+    pub verification: Option<OnchainVerificationConfig>,
+}
+
+#[derive(Clone)]
+pub struct OnchainVerificationConfig {
+    pub chain: Chain,
+    pub eth_rpc_url: String,
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+/// Sends submissions of randomly chosen proofs to the batcher at a fixed rate until the duration
+/// ends, then waits for the responses of the submissions in flight.
+/// Every proof is given a random proof generator address, so that the batcher treats repeated
+/// proofs as different tasks.
+/// # Returns
+/// * The outcome of every submission, in the order in which they finished.
+pub async fn send_tasks(
+    config: TaskSenderConfig,
+    wallet: Wallet<SigningKey>,
+) -> Vec<SubmissionRecord> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / config.rate));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let deadline = config.duration.map(|duration| Instant::now() + duration);
+
+    let mut records = Vec::new();
+    let mut submissions_sent = 0;
+    let mut submissions = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            instant = ticker.tick() => {
+                if deadline.is_some_and(|deadline| instant >= deadline) {
+                    break;
+                }
+
+                let verification_data = sample_submission(&config, &mut rng);
+                submissions_sent += 1;
+                info!(
+                    "Sending submission {} with {} proofs",
+                    submissions_sent,
+                    verification_data.len()
+                );
+
+                let batcher_url = config.batcher_url.clone();
+                let wallet = wallet.clone();
+                let verification = config.verification.clone();
+                submissions.spawn(async move {
+                    send_submission(&batcher_url, verification_data, wallet, verification).await
+                });
+            }
+            Some(result) = submissions.join_next() => {
+                push_record(&mut records, result);
+            }
+        }
+    }
+
+    info!(
+        "Waiting for the responses of {} submissions",
+        submissions.len()
+    );
+    while let Some(result) = submissions.join_next().await {
+        push_record(&mut records, result);
+    }
+
+    records
+}
+
+fn push_record(
+    records: &mut Vec<SubmissionRecord>,
+    result: Result<SubmissionRecord, tokio::task::JoinError>,
+) {
+    match result {
+        Ok(record) => records.push(record),
+        Err(e) => error!("Submission task failed: {}", e),
+    }
+}
+
+async fn send_submission(
+    batcher_url: &str,
+    verification_data: Vec<VerificationData>,
+    wallet: Wallet<SigningKey>,
+    verification: Option<OnchainVerificationConfig>,
+) -> SubmissionRecord {
+    let proofs = verification_data.len();
+    let start = Instant::now();
+
+    let aligned_verification_data =
+        match submit_multiple(batcher_url, &verification_data, wallet).await {
+            Ok(Some(aligned_verification_data)) => aligned_verification_data,
+            Ok(None) => {
+                error!("No batch inclusion data was received from the batcher");
+                return SubmissionRecord {
+                    proofs,
+                    result: Err("NoResponse".to_string()),
+                };
+            }
+            Err(e) => {
+                error!("Submission failed: {:?}", e);
+                return SubmissionRecord {
+                    proofs,
+                    result: Err(rejection_reason(&e)),
+                };
+            }
+        };
+    let response_latency = start.elapsed();
+
+    let verification_latency = match verification {
+        Some(verification) => wait_for_verification(&aligned_verification_data, verification).await,
+        None => None,
+    };
+
+    SubmissionRecord {
+        proofs,
+        result: Ok(SubmissionResponse {
+            aligned_verification_data,
+            response_latency,
+            verification_latency,
+        }),
+    }
+}
+
+/// Polls until every batch including the submitted proofs is verified on-chain.
+/// # Returns
+/// * The time it took to verify all the batches, or `None` if they were not verified before the
+///   timeout.
+async fn wait_for_verification(
+    aligned_verification_data: &[AlignedVerificationData],
+    verification: OnchainVerificationConfig,
+) -> Option<Duration> {
+    let start = Instant::now();
+    let mut batch_merkle_roots = HashSet::new();
+
+    for aligned_verification_data in aligned_verification_data {
+        if !batch_merkle_roots.insert(aligned_verification_data.batch_merkle_root) {
+            continue;
+        }
+
+        loop {
+            match verify_proof_onchain(
+                aligned_verification_data.clone(),
+                verification.chain.clone(),
+                &verification.eth_rpc_url,
+            )
+            .await
+            {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => warn!("Error while checking the batch verification: {:?}", e),
+            }
+
+            if start.elapsed() >= verification.timeout {
+                warn!("Batch was not verified in {:?}", verification.timeout);
+                return None;
+            }
+            sleep(verification.poll_interval).await;
+        }
+    }
+
+    Some(start.elapsed())
+}
+
+fn sample_submission(config: &TaskSenderConfig, rng: &mut StdRng) -> Vec<VerificationData> {
+    let proofs_per_submission =
+        rng.gen_range(config.min_proofs_per_submission..=config.max_proofs_per_submission);
+
+    (0..proofs_per_submission)
+        .map(|_| {
+            let mut verification_data =
+                config.proofs[rng.gen_range(0..config.proofs.len())].clone();
+            verification_data.proof_generator_addr = Address::from(rng.gen::<[u8; 20]>());
+            verification_data
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use aligned_sdk::types::ProvingSystemId;
+
+    fn config(seed: u64) -> TaskSenderConfig {
+        let proof = |proving_system| VerificationData {
+            proving_system,
+            proof: vec![1, 2, 3],
+            pub_input: None,
+            verification_key: None,
+            vm_program_code: None,
+            proof_generator_addr: Address::zero(),
+            tag: None,
+            metadata: None,
+            schema_version: VerificationData::SCHEMA_VERSION,
+        };
+
+        TaskSenderConfig {
+            batcher_url: "ws://localhost:8080".to_string(),
+            proofs: vec![proof(ProvingSystemId::SP1), proof(ProvingSystemId::Risc0)],
+            rate: 1.0,
+            duration: None,
+            min_proofs_per_submission: 2,
+            max_proofs_per_submission: 5,
+            seed,
+            verification: None,
+        }
+    }
+
+    fn sample_submissions(seed: u64) -> Vec<Vec<(ProvingSystemId, Address)>> {
+        let config = config(seed);
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        (0..10)
+            .map(|_| {
+                sample_submission(&config, &mut rng)
+                    .into_iter()
+                    .map(|data| (data.proving_system, data.proof_generator_addr))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_submissions_are_reproducible_from_the_seed() {
+        let submissions = sample_submissions(7);
+
+        assert_eq!(submissions, sample_submissions(7));
+        assert_ne!(submissions, sample_submissions(8));
+        for submission in submissions {
+            assert!((2..=5).contains(&submission.len()));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use aligned_sdk::errors::SubmitError;
+use aligned_sdk::types::{ProvingSystemId, VerificationData};
+use ethers::types::Address;
+
+/// Loads the verification data of the test proof of the given proving system, found in the
+/// `scripts/test_files` directory of the repository. The proof generator address is left zeroed,
+/// as the sender sets a random one for each proof.
+pub fn load_verification_data(
+    proving_system: ProvingSystemId,
+    test_files_dir: &Path,
+) -> Result<VerificationData, SubmitError> {
+    let read = |file_name: &str| -> Result<Vec<u8>, SubmitError> {
+        let path = test_files_dir.join(file_name);
+        std::fs::read(&path).map_err(|e| SubmitError::IoError(path, e))
+    };
+
+    let mut pub_input = None;
+    let mut verification_key = None;
+    let mut vm_program_code = None;
+
+    let proof = match proving_system {
+        ProvingSystemId::GnarkPlonkBls12_381 => {
+            pub_input = Some(read("gnark_plonk_bls12_381_script/plonk_pub_input.pub")?);
+            verification_key = Some(read("gnark_plonk_bls12_381_script/plonk.vk")?);
+            read("gnark_plonk_bls12_381_script/plonk.proof")?
+        }
+        ProvingSystemId::GnarkPlonkBn254 => {
+            pub_input = Some(read("gnark_plonk_bn254_script/plonk_pub_input.pub")?);
+            verification_key = Some(read("gnark_plonk_bn254_script/plonk.vk")?);
+            read("gnark_plonk_bn254_script/plonk.proof")?
+        }
+        ProvingSystemId::Groth16Bn254 => {
+            pub_input = Some(read("gnark_groth16_bn254_script/groth16.pub")?);
+            verification_key = Some(read("gnark_groth16_bn254_script/groth16.vk")?);
+            read("gnark_groth16_bn254_script/groth16.proof")?
+        }
+        ProvingSystemId::SP1 => {
+            vm_program_code = Some(read("sp1/sp1_fibonacci.elf")?);
+            read("sp1/sp1_fibonacci.proof")?
+        }
+        ProvingSystemId::Halo2KZG => {
+            pub_input = Some(read("halo2_kzg/pub_input.bin")?);
+            verification_key = Some(read("halo2_kzg/params.bin")?);
+            read("halo2_kzg/proof.bin")?
+        }
+        ProvingSystemId::Halo2IPA => {
+            pub_input = Some(read("halo2_ipa/pub_input.bin")?);
+            verification_key = Some(read("halo2_ipa/params.bin")?);
+            read("halo2_ipa/proof.bin")?
+        }
+        ProvingSystemId::Risc0 => {
+            pub_input = Some(read(
+                "risc_zero/fibonacci_proof_generator/risc_zero_fibonacci.pub",
+            )?);
+            vm_program_code = Some(read(
+                "risc_zero/fibonacci_proof_generator/fibonacci_id.bin",
+            )?);
+            read("risc_zero/fibonacci_proof_generator/risc_zero_fibonacci.proof")?
+        }
+    };
+
+    Ok(VerificationData {
+        proving_system,
+        proof,
+        pub_input,
+        verification_key,
+        vm_program_code,
+        proof_generator_addr: Address::zero(),
+        tag: None,
+        metadata: None,
+        schema_version: VerificationData::SCHEMA_VERSION,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_verification_data_of_every_proving_system() {
+        let test_files_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../scripts/test_files");
+
+        for proving_system in [
+            ProvingSystemId::GnarkPlonkBls12_381,
+            ProvingSystemId::GnarkPlonkBn254,
+            ProvingSystemId::Groth16Bn254,
+            ProvingSystemId::SP1,
+            ProvingSystemId::Halo2KZG,
+            ProvingSystemId::Halo2IPA,
+            ProvingSystemId::Risc0,
+        ] {
+            let verification_data =
+                load_verification_data(proving_system.clone(), &test_files_dir).unwrap();
+            assert!(!verification_data.proof.is_empty());
+        }
+    }
+}
@@ -0,0 +1,16 @@
+pragma solidity =0.8.12;
+
+import {Script} from "forge-std/Script.sol";
+import {AlignedPubInputOpener} from "../../src/core/AlignedPubInputOpener.sol";
+
+contract AlignedPubInputOpenerDeployer is Script {
+    function run(address _alignedServiceManager) external returns (address) {
+        vm.startBroadcast();
+
+        AlignedPubInputOpener alignedPubInputOpener = new AlignedPubInputOpener(_alignedServiceManager);
+
+        vm.stopBroadcast();
+
+        return address(alignedPubInputOpener);
+    }
+}
@@ -0,0 +1,34 @@
+#!/bin/bash
+
+# cd to the directory of this script so that this can be run from anywhere
+parent_path=$( cd "$(dirname "${BASH_SOURCE[0]}")" ; pwd -P )
+# At this point we are in contracts/scripts
+cd "$parent_path"
+
+# At this point we are in contracts
+cd ../
+
+source scripts/.env
+
+ALIGNED_LAYER_SERVICE_MANAGER_ADDRESS=$(jq -r '.addresses.alignedLayerServiceManager' "$OUTPUT_PATH")
+
+# Deploy Pub Input Opener Contract
+forge_output=$(forge script script/deploy/AlignedPubInputOpenerDeployer.s.sol \
+    "$ALIGNED_LAYER_SERVICE_MANAGER_ADDRESS" \
+    --rpc-url $RPC_URL \
+    --private-key $PRIVATE_KEY \
+    --broadcast \
+    --legacy \
+    --verify \
+    --etherscan-api-key $ETHERSCAN_API_KEY \
+    --sig "run(address _alignedServiceManager)")
+
+echo "$forge_output"
+
+# Extract the pub input opener address from the output
+aligned_pub_input_opener=$(echo "$forge_output" | awk '/0: address/ {print $3}')
+
+# Save the address in the deployment output
+jq --arg aligned_pub_input_opener "$aligned_pub_input_opener" '.addresses.alignedPubInputOpener = $aligned_pub_input_opener' "$OUTPUT_PATH" > "$OUTPUT_PATH.temp"
+
+mv "$OUTPUT_PATH.temp" "$OUTPUT_PATH"
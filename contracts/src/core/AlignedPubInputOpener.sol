@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity =0.8.12;
+
+/// @notice Helper to use the public input of a proof verified in Aligned. Given the full public
+/// input bytes, it recomputes their commitment and checks it against the verified batch, so the
+/// caller can trust the public input as the outputs of the proven program.
+contract AlignedPubInputOpener {
+    address public immutable alignedServiceManager;
+
+    constructor(address _alignedServiceManager) {
+        alignedServiceManager = _alignedServiceManager;
+    }
+
+    function getPubInputCommitment(
+        bytes calldata pubInput
+    ) public pure returns (bytes32) {
+        return keccak256(pubInput);
+    }
+
+    function verifyBatchInclusionWithPubInput(
+        bytes32 proofCommitment,
+        bytes calldata pubInput,
+        bytes32 provingSystemAuxDataCommitment,
+        bytes20 proofGeneratorAddr,
+        bytes32 batchMerkleRoot,
+        bytes calldata merkleProof,
+        uint256 verificationDataBatchIndex
+    ) external view returns (bool) {
+        (bool callWasSuccessfull, bytes memory proofIsIncluded) = alignedServiceManager.staticcall(
+            abi.encodeWithSignature(
+                "verifyBatchInclusion(bytes32,bytes32,bytes32,bytes20,bytes32,bytes,uint256)",
+                proofCommitment,
+                getPubInputCommitment(pubInput),
+                provingSystemAuxDataCommitment,
+                proofGeneratorAddr,
+                batchMerkleRoot,
+                merkleProof,
+                verificationDataBatchIndex
+            )
+        );
+
+        require(callWasSuccessfull, "static_call failed");
+
+        return abi.decode(proofIsIncluded, (bool));
+    }
+}
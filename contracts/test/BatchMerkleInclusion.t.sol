@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.12;
+
+import "forge-std/Test.sol";
+import {Merkle} from "eigenlayer-core/contracts/libraries/Merkle.sol";
+
+/// @notice Checks that batches built by the batcher, which repeats the last leaf until the batch
+/// has a power of two leaves and at least two of them, are verified by the same library used in
+/// `verifyBatchInclusion`. The vectors are generated by the batcher merkle module tests.
+contract BatchMerkleInclusionTest is Test {
+    bytes32 constant LEAF_0 =
+        0xe0ecda36404070acf895ce17f44dbb737fff3f15e0bf0467570177a9372c94bc;
+    bytes32 constant LEAF_1 =
+        0x26c45f259540438c88fb13e49a3e7a3fa8967b2a20b8c5ee6bf808aa9324f555;
+    bytes32 constant LEAF_2 =
+        0xf5ddad909ae863a89e95b1bf317bee5ec162b9e1b3ddd4ba3a1e131480cf6e8a;
+
+    bytes32 constant SINGLE_ENTRY_BATCH_ROOT =
+        0x2fa4dd7b3ae6ef007f6bf7ef5bb1ac9f943eb967824859af4e32f97c4ceb8cab;
+    bytes32 constant THREE_ENTRIES_BATCH_ROOT =
+        0xd911d7c0654f5d2804084ba6484642f515093830b9e80212723082de62da4a54;
+
+    function hashPair(bytes32 a, bytes32 b) internal pure returns (bytes32) {
+        return keccak256(abi.encodePacked(a, b));
+    }
+
+    function testSingleEntryBatch() public {
+        assertEq(SINGLE_ENTRY_BATCH_ROOT, hashPair(LEAF_0, LEAF_0));
+
+        bytes memory proof = abi.encodePacked(LEAF_0);
+        assertTrue(
+            Merkle.verifyInclusionKeccak(proof, SINGLE_ENTRY_BATCH_ROOT, LEAF_0, 0)
+        );
+    }
+
+    function testOddBatch() public {
+        bytes32 left = hashPair(LEAF_0, LEAF_1);
+        bytes32 right = hashPair(LEAF_2, LEAF_2);
+        assertEq(THREE_ENTRIES_BATCH_ROOT, hashPair(left, right));
+
+        assertTrue(
+            Merkle.verifyInclusionKeccak(
+                abi.encodePacked(LEAF_1, right),
+                THREE_ENTRIES_BATCH_ROOT,
+                LEAF_0,
+                0
+            )
+        );
+        assertTrue(
+            Merkle.verifyInclusionKeccak(
+                abi.encodePacked(LEAF_0, right),
+                THREE_ENTRIES_BATCH_ROOT,
+                LEAF_1,
+                1
+            )
+        );
+        assertTrue(
+            Merkle.verifyInclusionKeccak(
+                abi.encodePacked(LEAF_2, left),
+                THREE_ENTRIES_BATCH_ROOT,
+                LEAF_2,
+                2
+            )
+        );
+    }
+
+    function testOddBatchRejectsWrongIndex() public {
+        bytes32 right = hashPair(LEAF_2, LEAF_2);
+
+        assertFalse(
+            Merkle.verifyInclusionKeccak(
+                abi.encodePacked(LEAF_1, right),
+                THREE_ENTRIES_BATCH_ROOT,
+                LEAF_0,
+                1
+            )
+        );
+    }
+}
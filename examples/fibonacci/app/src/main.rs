@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use aligned_sdk::pipeline::{prove_and_submit, PipelineEvent, PipelineTimeouts};
+use aligned_sdk::sdk::{get_sp1_public_values, verify_proof_onchain};
+use aligned_sdk::types::{AlignedVerificationData, Chain, ProvingSystemId, VerificationData};
+use clap::Parser;
+use ethers::prelude::*;
+
+abigen!(
+    FibonacciValidatorContract,
+    r#"[
+        function verifyFibonacciNumbers(bytes32 proofCommitment, bytes pubInput, bytes32 provingSystemAuxDataCommitment, bytes20 proofGeneratorAddr, bytes32 batchMerkleRoot, bytes merkleProof, uint256 verificationDataBatchIndex) external
+        function verifiedFibonacciNumbers(uint32 n, uint256 index) external view returns (uint32)
+    ]"#
+);
+
+// Proof of the fibonacci program for n = 500, generated by
+// scripts/test_files/sp1/fibonacci_proof_generator
+const PROOF: &[u8] = include_bytes!("../../../../scripts/test_files/sp1/sp1_fibonacci.proof");
+const ELF: &[u8] = include_bytes!("../../../../scripts/test_files/sp1/sp1_fibonacci.elf");
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[arg(long, default_value = "ws://localhost:8080")]
+    batcher_url: String,
+    #[arg(long, default_value = "http://localhost:8545")]
+    rpc_url: String,
+    // Defaults to anvil account 1, which is funded in the batcher payment service on devnet
+    #[arg(
+        long,
+        default_value = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+    )]
+    private_key: String,
+    #[arg(long)]
+    validator_contract_address: Address,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let provider = Provider::<Http>::try_from(args.rpc_url.as_str())?;
+    let chain_id = provider.get_chainid().await?;
+    let wallet = args
+        .private_key
+        .parse::<LocalWallet>()?
+        .with_chain_id(chain_id.as_u64());
+
+    // 1. Generate the proof and submit it to Aligned
+    let proof_generator_addr = wallet.address();
+    let aligned_verification_data = prove_and_submit(
+        &args.batcher_url,
+        move || prove_fibonacci(proof_generator_addr),
+        wallet.clone(),
+        PipelineTimeouts {
+            proving: Duration::from_secs(600),
+            submission: Duration::from_secs(120),
+        },
+        print_progress,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to prove and submit: {:?}", e))?
+    .and_then(|aligned_verification_data| aligned_verification_data.into_iter().next())
+    .ok_or(anyhow::anyhow!("No verification data was received"))?;
+
+    // 2. Wait for the batch with the proof to be verified by Aligned
+    wait_for_verification(&aligned_verification_data, &args.rpc_url).await?;
+
+    // 3. Use the outputs of the program in a contract, which checks them against the proof
+    let pub_input = get_sp1_public_values(PROOF)
+        .map_err(|e| anyhow::anyhow!("Failed to get public values: {:?}", e))?;
+
+    let signer = Arc::new(SignerMiddleware::new(provider, wallet));
+    let validator = FibonacciValidatorContract::new(args.validator_contract_address, signer);
+
+    let verification_data_comm = &aligned_verification_data.verification_data_commitment;
+    let merkle_proof: Vec<u8> = aligned_verification_data
+        .batch_inclusion_proof
+        .merkle_path
+        .iter()
+        .flatten()
+        .copied()
+        .collect();
+
+    let receipt = validator
+        .verify_fibonacci_numbers(
+            verification_data_comm.proof_commitment,
+            pub_input.clone().into(),
+            verification_data_comm.proving_system_aux_data_commitment,
+            verification_data_comm.proof_generator_addr,
+            aligned_verification_data.batch_merkle_root,
+            merkle_proof.into(),
+            aligned_verification_data.index_in_batch.into(),
+        )
+        .send()
+        .await?
+        .await?
+        .ok_or(anyhow::anyhow!("Transaction was dropped"))?;
+
+    println!(
+        "Fibonacci numbers verified on-chain. Transaction hash: {:x}",
+        receipt.transaction_hash
+    );
+
+    let n = u32::from_le_bytes(pub_input[0..4].try_into()?);
+    let fib_n = validator
+        .verified_fibonacci_numbers(n, U256::zero())
+        .call()
+        .await?;
+    let fib_n_plus_one = validator
+        .verified_fibonacci_numbers(n, U256::one())
+        .call()
+        .await?;
+
+    println!(
+        "The contract now knows that fib({}) = {} and fib({}) = {}, modulo 7919",
+        n,
+        fib_n,
+        n + 1,
+        fib_n_plus_one
+    );
+
+    Ok(())
+}
+
+/// Stands for the proving step of the application, replace it with a call to
+/// `ProverClient::prove_compressed` and serialize the proof with bincode.
+fn prove_fibonacci(proof_generator_addr: Address) -> Result<Vec<VerificationData>, String> {
+    // Binding the public values makes them usable by contracts
+    let pub_input = get_sp1_public_values(PROOF).map_err(|e| format!("{:?}", e))?;
+
+    Ok(vec![VerificationData {
+        proving_system: ProvingSystemId::SP1,
+        proof: PROOF.to_vec(),
+        pub_input: Some(pub_input),
+        verification_key: None,
+        vm_program_code: Some(ELF.to_vec()),
+        proof_generator_addr,
+        tag: None,
+        metadata: None,
+        schema_version: VerificationData::SCHEMA_VERSION,
+    }])
+}
+
+fn print_progress(event: PipelineEvent) {
+    match event {
+        PipelineEvent::ProvingStarted => println!("Generating proof..."),
+        PipelineEvent::ProvingFinished { elapsed, .. } => {
+            println!("Proof generated in {:?}", elapsed)
+        }
+        PipelineEvent::SubmissionStarted => println!("Submitting proof to Aligned..."),
+        PipelineEvent::SubmissionFinished { elapsed } => {
+            println!("Proof submitted in {:?}", elapsed)
+        }
+    }
+}
+
+async fn wait_for_verification(
+    aligned_verification_data: &AlignedVerificationData,
+    rpc_url: &str,
+) -> anyhow::Result<()> {
+    println!(
+        "Proof included in batch {}, waiting for verification...",
+        hex::encode(aligned_verification_data.batch_merkle_root)
+    );
+
+    for _ in 0..20 {
+        if verify_proof_onchain(aligned_verification_data.clone(), Chain::Devnet, rpc_url)
+            .await
+            .is_ok_and(|verified| verified)
+        {
+            println!("Proof verified in Aligned");
+            return Ok(());
+        }
+
+        println!("Proof not verified yet. Waiting 10 seconds before checking again...");
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+
+    anyhow::bail!("Proof was not verified in time")
+}
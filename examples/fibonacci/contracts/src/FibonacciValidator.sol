@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity =0.8.12;
+
+contract FibonacciValidator {
+    address public alignedPubInputOpener;
+
+    // Commitment of the fibonacci program ELF, obtained with `aligned get-commitment`
+    bytes32 public fibonacciProgramCommitment = 0xbe887fb918984e42ef371f3e7591b639959a5b115ab7af0dc686e6b7e2dea658;
+
+    // Map from n to the fibonacci numbers fib(n) and fib(n + 1), modulo 7919, proven for it
+    mapping(uint32 => uint32[2]) public verifiedFibonacciNumbers;
+
+    event FibonacciNumbersVerified(uint32 n, uint32 fibN, uint32 fibNPlusOne);
+
+    constructor(address _alignedPubInputOpener) {
+        alignedPubInputOpener = _alignedPubInputOpener;
+    }
+
+    function verifyFibonacciNumbers(
+        bytes32 proofCommitment,
+        bytes calldata pubInput,
+        bytes32 provingSystemAuxDataCommitment,
+        bytes20 proofGeneratorAddr,
+        bytes32 batchMerkleRoot,
+        bytes memory merkleProof,
+        uint256 verificationDataBatchIndex
+    ) external {
+        require(fibonacciProgramCommitment == provingSystemAuxDataCommitment, "ELF does not match");
+
+        // The opener recomputes the commitment of the public input, so it can be trusted as the
+        // outputs of the fibonacci program if the proof was verified in Aligned
+        (bool callWasSuccessfull, bytes memory proofIsIncluded) = alignedPubInputOpener.staticcall(
+            abi.encodeWithSignature(
+                "verifyBatchInclusionWithPubInput(bytes32,bytes,bytes32,bytes20,bytes32,bytes,uint256)",
+                proofCommitment,
+                pubInput,
+                provingSystemAuxDataCommitment,
+                proofGeneratorAddr,
+                batchMerkleRoot,
+                merkleProof,
+                verificationDataBatchIndex
+            )
+        );
+
+        require(callWasSuccessfull, "static_call failed");
+        require(abi.decode(proofIsIncluded, (bool)), "proof not included in batch");
+
+        // The program commits n, fib(n) and fib(n + 1) as little endian u32
+        require(pubInput.length == 12, "unexpected public input length");
+        uint32 n = readUint32LittleEndian(pubInput, 0);
+        uint32 fibN = readUint32LittleEndian(pubInput, 4);
+        uint32 fibNPlusOne = readUint32LittleEndian(pubInput, 8);
+
+        verifiedFibonacciNumbers[n] = [fibN, fibNPlusOne];
+
+        emit FibonacciNumbersVerified(n, fibN, fibNPlusOne);
+    }
+
+    function readUint32LittleEndian(bytes calldata data, uint256 offset) internal pure returns (uint32) {
+        return uint32(uint8(data[offset]))
+            | (uint32(uint8(data[offset + 1])) << 8)
+            | (uint32(uint8(data[offset + 2])) << 16)
+            | (uint32(uint8(data[offset + 3])) << 24);
+    }
+}
@@ -0,0 +1,53 @@
+#!/bin/bash
+
+# cd to the directory of this script so that this can be run from anywhere
+parent_path=$( cd "$(dirname "${BASH_SOURCE[0]}")" || exit 1 ; pwd -P )
+
+# At this point we are in examples/fibonacci/scripts
+cd "$parent_path" || exit 1
+
+# At this point we are in examples/fibonacci
+cd ../ || exit 1
+
+RPC_URL=${RPC_URL:-http://localhost:8545}
+BATCHER_URL=${BATCHER_URL:-ws://localhost:8080}
+# Anvil account 1, funded in the batcher payment service when the batcher is started on devnet
+PRIVATE_KEY=${PRIVATE_KEY:-0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80}
+
+ALIGNED_SERVICE_MANAGER_ADDRESS=$(jq -r '.addresses.alignedLayerServiceManager' ../../contracts/script/output/devnet/alignedlayer_deployment_output.json)
+
+echo "Deploying AlignedPubInputOpener..."
+ALIGNED_PUB_INPUT_OPENER_ADDRESS=$(forge create \
+    --root ../../contracts \
+    --rpc-url "$RPC_URL" \
+    --private-key "$PRIVATE_KEY" \
+    src/core/AlignedPubInputOpener.sol:AlignedPubInputOpener \
+    --constructor-args "$ALIGNED_SERVICE_MANAGER_ADDRESS" \
+    | awk '/Deployed to:/ {print $3}')
+
+if [ -z "$ALIGNED_PUB_INPUT_OPENER_ADDRESS" ]; then
+    echo "Failed to deploy AlignedPubInputOpener"
+    exit 1
+fi
+
+echo "Deploying FibonacciValidator..."
+FIBONACCI_VALIDATOR_ADDRESS=$(forge create \
+    --root ./contracts \
+    --rpc-url "$RPC_URL" \
+    --private-key "$PRIVATE_KEY" \
+    src/FibonacciValidator.sol:FibonacciValidator \
+    --constructor-args "$ALIGNED_PUB_INPUT_OPENER_ADDRESS" \
+    | awk '/Deployed to:/ {print $3}')
+
+if [ -z "$FIBONACCI_VALIDATOR_ADDRESS" ]; then
+    echo "Failed to deploy FibonacciValidator"
+    exit 1
+fi
+
+echo "FibonacciValidator deployed to $FIBONACCI_VALIDATOR_ADDRESS"
+
+cd app && cargo run -r -- \
+    --batcher-url "$BATCHER_URL" \
+    --rpc-url "$RPC_URL" \
+    --private-key "$PRIVATE_KEY" \
+    --validator-contract-address "$FIBONACCI_VALIDATOR_ADDRESS"
@@ -15,10 +15,7 @@ use ethers::signers::{LocalWallet, Signer};
 use ethers::types::{Address, Bytes, H160, U256};
 use sp1_sdk::{ProverClient, SP1Stdin};
 
-abigen!(
-    VerifierContract,
-    "VerifierContract.json",
-);
+abigen!(VerifierContract, "VerifierContract.json",);
 
 const BATCHER_URL: &str = "wss://batcher.alignedlayer.com";
 const BATCHER_PAYMENTS_ADDRESS: &str = "0x815aeCA64a974297942D2Bbf034ABEe22a38A003";
@@ -110,13 +107,18 @@ async fn main() {
                 vm_program_code: Some(ELF.to_vec()),
                 verification_key: None,
                 pub_input: None,
+                tag: None,
+                metadata: None,
+                schema_version: VerificationData::SCHEMA_VERSION,
             };
 
             match submit_proof_and_wait_for_verification(
                 verification_data,
                 wallet.clone(),
                 rpc_url.clone(),
-            ).await {
+            )
+            .await
+            {
                 Ok(aligned_verification_data) => {
                     println!("Proof verified in Aligned, claiming prize...");
 
@@ -125,7 +127,7 @@ async fn main() {
                         signer.clone(),
                         args.verifier_contract_address,
                     )
-                        .await
+                    .await
                     {
                         println!("Failed to claim prize: {:?}", e);
                     }
@@ -200,8 +202,8 @@ async fn submit_proof_and_wait_for_verification(
                     Chain::Holesky,
                     rpc_url.as_str(),
                 )
-                    .await
-                    .is_ok_and(|r| r)
+                .await
+                .is_ok_and(|r| r)
                 {
                     return Ok(aligned_verification_data);
                 }
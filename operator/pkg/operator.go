@@ -227,8 +227,10 @@ func (o *Operator) verify(verificationData VerificationData, results chan bool)
 	case common.SP1:
 		proofLen := (uint32)(len(verificationData.Proof))
 		elfLen := (uint32)(len(verificationData.VmProgramCode))
+		pubInputLen := (uint32)(len(verificationData.PubInput))
 
-		verificationResult := sp1.VerifySp1Proof(verificationData.Proof, proofLen, verificationData.VmProgramCode, elfLen)
+		verificationResult := sp1.VerifySp1Proof(verificationData.Proof, proofLen, verificationData.VmProgramCode, elfLen,
+			verificationData.PubInput, pubInputLen)
 		o.Logger.Infof("SP1 proof verification result: %t", verificationResult)
 		results <- verificationResult
 	case common.Halo2IPA:
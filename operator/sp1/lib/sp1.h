@@ -2,4 +2,6 @@
 #include <stdint.h>
 
 bool verify_sp1_proof_ffi(unsigned char *proof_buffer, uint32_t proof_len,
-                          unsigned char *elf_buffer, uint32_t elf_len);
+                          unsigned char *elf_buffer, uint32_t elf_len,
+                          unsigned char *public_values_buffer,
+                          uint32_t public_values_len);
@@ -1,5 +1,5 @@
 use lazy_static::lazy_static;
-use sp1_sdk::ProverClient;
+use sp1_sdk::{ProverClient, SP1CompressedProof};
 use std::slice;
 
 lazy_static! {
@@ -12,6 +12,8 @@ pub extern "C" fn verify_sp1_proof_ffi(
     proof_len: u32,
     elf_bytes: *const u8,
     elf_len: u32,
+    public_values: *const u8,
+    public_values_len: u32,
 ) -> bool {
     if proof_bytes.is_null() || elf_bytes.is_null() {
         return false;
@@ -21,7 +23,17 @@ pub extern "C" fn verify_sp1_proof_ffi(
 
     let elf_bytes = unsafe { slice::from_raw_parts(elf_bytes, elf_len as usize) };
 
-    if let Ok(proof) = bincode::deserialize(proof_bytes) {
+    // A null pointer means that no public input was sent along with the proof
+    let public_values = (!public_values.is_null())
+        .then(|| unsafe { slice::from_raw_parts(public_values, public_values_len as usize) });
+
+    if let Ok(proof) = bincode::deserialize::<SP1CompressedProof>(proof_bytes) {
+        if public_values
+            .is_some_and(|public_values| public_values != proof.public_values.as_slice())
+        {
+            return false;
+        }
+
         let (_pk, vk) = PROVER_CLIENT.setup(elf_bytes);
         return PROVER_CLIENT.verify_compressed(&proof, &vk).is_ok();
     }
@@ -35,14 +47,24 @@ mod tests {
 
     const PROOF: &[u8] = include_bytes!("../../../../scripts/test_files/sp1/sp1_fibonacci.proof");
     const ELF: &[u8] = include_bytes!("../../../../scripts/test_files/sp1/sp1_fibonacci.elf");
+    // n = 500 and the last two fibonacci numbers, as committed by the program
+    const PUBLIC_VALUES: &[u8] = &[
+        0xf4, 0x01, 0x00, 0x00, 0xf4, 0x04, 0x00, 0x00, 0x86, 0x07, 0x00, 0x00,
+    ];
 
     #[test]
     fn verify_sp1_proof_with_elf_works() {
         let proof_bytes = PROOF.as_ptr();
         let elf_bytes = ELF.as_ptr();
 
-        let result =
-            verify_sp1_proof_ffi(proof_bytes, PROOF.len() as u32, elf_bytes, ELF.len() as u32);
+        let result = verify_sp1_proof_ffi(
+            proof_bytes,
+            PROOF.len() as u32,
+            elf_bytes,
+            ELF.len() as u32,
+            std::ptr::null(),
+            0,
+        );
         assert!(result)
     }
 
@@ -56,6 +78,37 @@ mod tests {
             (PROOF.len() - 1) as u32,
             elf_bytes,
             ELF.len() as u32,
+            std::ptr::null(),
+            0,
+        );
+        assert!(!result)
+    }
+
+    #[test]
+    fn verify_sp1_proof_with_public_values_works() {
+        let result = verify_sp1_proof_ffi(
+            PROOF.as_ptr(),
+            PROOF.len() as u32,
+            ELF.as_ptr(),
+            ELF.len() as u32,
+            PUBLIC_VALUES.as_ptr(),
+            PUBLIC_VALUES.len() as u32,
+        );
+        assert!(result)
+    }
+
+    #[test]
+    fn verify_sp1_aborts_with_wrong_public_values() {
+        let mut public_values = PUBLIC_VALUES.to_vec();
+        public_values[0] ^= 1;
+
+        let result = verify_sp1_proof_ffi(
+            PROOF.as_ptr(),
+            PROOF.len() as u32,
+            ELF.as_ptr(),
+            ELF.len() as u32,
+            public_values.as_ptr(),
+            public_values.len() as u32,
         );
         assert!(!result)
     }
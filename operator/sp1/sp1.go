@@ -9,9 +9,18 @@ package sp1
 import "C"
 import "unsafe"
 
-func VerifySp1Proof(proofBuffer []byte, proofLen uint32, elfBuffer []byte, elfLen uint32) bool {
+// VerifySp1Proof verifies an SP1 proof. When publicValues is not nil, it must match the public
+// values committed in the proof.
+func VerifySp1Proof(proofBuffer []byte, proofLen uint32, elfBuffer []byte, elfLen uint32, publicValues []byte, publicValuesLen uint32) bool {
 	proofPtr := (*C.uchar)(unsafe.Pointer(&proofBuffer[0]))
 	elfPtr := (*C.uchar)(unsafe.Pointer(&elfBuffer[0]))
 
-	return (bool)(C.verify_sp1_proof_ffi(proofPtr, (C.uint32_t)(proofLen), elfPtr, (C.uint32_t)(elfLen)))
+	var publicValuesPtr *C.uchar
+	if publicValues != nil {
+		// The extra byte keeps the pointer valid when the public values are empty
+		publicValuesBuffer := append(publicValues[:publicValuesLen:publicValuesLen], 0)
+		publicValuesPtr = (*C.uchar)(unsafe.Pointer(&publicValuesBuffer[0]))
+	}
+
+	return (bool)(C.verify_sp1_proof_ffi(proofPtr, (C.uint32_t)(proofLen), elfPtr, (C.uint32_t)(elfLen), publicValuesPtr, (C.uint32_t)(publicValuesLen)))
 }
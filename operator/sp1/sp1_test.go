@@ -32,7 +32,31 @@ func TestFibonacciSp1ProofVerifies(t *testing.T) {
 		t.Errorf("could not read bytes from file")
 	}
 
-	if !sp1.VerifySp1Proof(proofBytes, uint32(nReadProofBytes), elfBytes, uint32(nReadElfBytes)) {
+	if !sp1.VerifySp1Proof(proofBytes, uint32(nReadProofBytes), elfBytes, uint32(nReadElfBytes), nil, 0) {
 		t.Errorf("proof did not verify")
 	}
 }
+
+func TestFibonacciSp1ProofVerifiesWithPublicValues(t *testing.T) {
+	proofBytes, err := os.ReadFile("../../scripts/test_files/sp1/sp1_fibonacci.proof")
+	if err != nil {
+		t.Errorf("could not open proof file: %s", err)
+	}
+
+	elfBytes, err := os.ReadFile("../../scripts/test_files/sp1/sp1_fibonacci.elf")
+	if err != nil {
+		t.Errorf("could not open elf file: %s", err)
+	}
+
+	// n = 500 and the last two fibonacci numbers, as committed by the program
+	publicValues := []byte{0xf4, 0x01, 0x00, 0x00, 0xf4, 0x04, 0x00, 0x00, 0x86, 0x07, 0x00, 0x00}
+
+	if !sp1.VerifySp1Proof(proofBytes, uint32(len(proofBytes)), elfBytes, uint32(len(elfBytes)), publicValues, uint32(len(publicValues))) {
+		t.Errorf("proof did not verify")
+	}
+
+	publicValues[0] ^= 1
+	if sp1.VerifySp1Proof(proofBytes, uint32(len(proofBytes)), elfBytes, uint32(len(elfBytes)), publicValues, uint32(len(publicValues))) {
+		t.Errorf("proof with wrong public values verified")
+	}
+}
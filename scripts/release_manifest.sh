@@ -0,0 +1,23 @@
+#!/bin/bash
+
+# Writes the manifest of a release, aligned-manifest.json, listing its version, the commit it was
+# built from and the SHA-256 of each of its binaries, as checked by `aligned verify-binary`.
+
+if [[ "$#" -lt 2 ]]; then
+  echo "Usage: $0 <version> <binary>..."
+  exit 1
+fi;
+
+version="$1"
+shift
+
+binaries=""
+for binary in "$@"; do
+  hash=$(shasum -a 256 "$binary" | cut -d ' ' -f 1)
+  binaries="$binaries${binaries:+,}\"$(basename "$binary")\":\"$hash\""
+done
+
+printf '{"version":"%s","git_hash":"%s","binaries":{%s}}' \
+  "$version" "$(git rev-parse --short HEAD)" "$binaries" > aligned-manifest.json
+
+echo "Wrote aligned-manifest.json for release $version"
@@ -0,0 +1,22 @@
+#!/bin/bash
+
+# Signs a document as an Ethereum message (EIP-191) with the key in SIGNING_KEY, and writes the
+# signature in hex next to it, at its path followed by .sig, where the CLI downloads it from.
+# Used for the release manifests and for the documents listing the batchers of each environment.
+
+if [[ "$#" -ne 1 ]]; then
+  echo "Usage: SIGNING_KEY=<private_key> $0 <document>"
+  exit 1
+fi;
+
+if [[ -z "$SIGNING_KEY" ]]; then
+  echo "SIGNING_KEY is empty, please set the private key that signs the document"
+  exit 1
+fi;
+
+# The document is signed as bytes, so that a trailing newline is signed too
+document_hex="0x$(xxd -p "$1" | tr -d '\n')"
+
+cast wallet sign --private-key "$SIGNING_KEY" "$document_hex" > "$1.sig"
+
+echo "Signed $1 with $(cast wallet address --private-key "$SIGNING_KEY")"